@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use music_organizer_core::Metadata;
+
+// cargo-fuzz builds with panic=abort, so panics caught by `try_read_from` still crash the target
+fuzz_target!(|data: &[u8]| {
+    let name = format!("music-organizer-fuzz-{}.flac", std::process::id());
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, data).unwrap();
+    let _ = Metadata::try_read_from(&path);
+});
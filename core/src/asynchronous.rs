@@ -0,0 +1,378 @@
+//! Async variants of indexing and execution for embedding the core in a tokio runtime. Blocking
+//! work like reading tags and copying files runs on tokio's blocking thread pool and progress is
+//! reported through unbounded channels.
+
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinSet;
+use tracing::info;
+
+use crate::fs::{
+    is_companion_extension, is_hidden, is_image_extension, is_music_video_extension,
+    is_playlist_extension, is_song_extension, ExtraKind,
+};
+use crate::index::{
+    has_skip_marker, read_metadata, song_from_metadata, video_from_metadata, Interner,
+};
+use crate::{
+    CacheEntry, Changes, Cleanup, ExecOptions, IndexCache, IndexEvent, Metadata, MetadataError,
+    MusicIndex, RealFs,
+};
+
+/// An [`IndexEvent`] that can be sent through a channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OwnedIndexEvent {
+    FileIndexed(PathBuf),
+    DirScanned(PathBuf),
+    Warning { path: PathBuf, reason: String },
+}
+
+impl From<IndexEvent<'_>> for OwnedIndexEvent {
+    fn from(event: IndexEvent<'_>) -> Self {
+        match event {
+            IndexEvent::FileIndexed(p) => Self::FileIndexed(p.to_owned()),
+            IndexEvent::DirScanned(p) => Self::DirScanned(p.to_owned()),
+            IndexEvent::Warning { path, reason } => {
+                Self::Warning { path: path.to_owned(), reason: reason.to_owned() }
+            }
+        }
+    }
+}
+
+/// Progress of executing operations asynchronously.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExecEvent {
+    /// Bytes copied of a file, only sent when copying.
+    Copied { path: PathBuf, copied: u64, total: u64 },
+    /// An operation on the path finished.
+    Done { path: PathBuf, result: Result<(), String> },
+}
+
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(unix)]
+async fn dir_id(path: &Path) -> Option<DirId> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = tokio::fs::metadata(path).await.ok()?;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+async fn dir_id(path: &Path) -> Option<DirId> {
+    tokio::fs::canonicalize(path).await.ok()
+}
+
+/// Async version of [`crate::index::foreign_marker`].
+async fn foreign_marker<'a>(markers: &'a [String], dir: &Path) -> Option<&'a str> {
+    for m in markers.iter() {
+        if tokio::fs::symlink_metadata(dir.join(m)).await.is_ok() {
+            return Some(m);
+        }
+    }
+    None
+}
+
+type MetadataResult = (PathBuf, Result<(Metadata, Option<CacheEntry>), MetadataError>);
+
+impl MusicIndex {
+    /// Async version of [`MusicIndex::read`]. Directories are read using async IO and at most
+    /// [`MusicIndex::threads`] tags are read concurrently on the blocking thread pool.
+    pub async fn read_async(&mut self, events: &UnboundedSender<OwnedIndexEvent>) {
+        let send = |e: IndexEvent| {
+            let _ = events.send(e.into());
+        };
+
+        let cache = std::sync::Arc::new(self.cache.take());
+        let mut new_cache = cache.as_ref().as_ref().map(|_| IndexCache::default());
+        let max_tasks = self
+            .threads
+            .or_else(|| std::thread::available_parallelism().ok())
+            .map_or(8, NonZeroUsize::get);
+
+        let separators = self.artist_separators.clone();
+        let custom_fields = self.custom_fields.clone();
+        let mut interner = Interner::default();
+        let mut tasks: JoinSet<MetadataResult> = JoinSet::new();
+        let mut handle = |index: &mut MusicIndex, result: Result<MetadataResult, _>| {
+            let Ok((p, read)) = result else { return };
+            let m = match read {
+                Ok((m, entry)) => {
+                    if let (Some(c), Some(e)) = (&mut new_cache, entry) {
+                        c.insert(e);
+                    }
+                    m
+                }
+                Err(e) => {
+                    send(IndexEvent::Warning { path: &p, reason: &e.to_string() });
+                    Metadata::default()
+                }
+            };
+            if p.extension().is_some_and(is_music_video_extension) {
+                match video_from_metadata(p, m, &separators) {
+                    Ok(v) => {
+                        send(IndexEvent::FileIndexed(&v.path));
+                        index.videos.push(v);
+                    }
+                    Err(p) => {
+                        send(IndexEvent::FileIndexed(&p));
+                        index.extras.push(p);
+                    }
+                }
+                return;
+            }
+            if has_skip_marker(&m) {
+                send(IndexEvent::FileIndexed(&p));
+                index.skipped.push(p);
+                return;
+            }
+            match song_from_metadata(p, m, &separators, &custom_fields) {
+                Ok(mut s) => {
+                    interner.intern_song(&mut s);
+                    send(IndexEvent::FileIndexed(&s.path));
+                    index.songs.push(s);
+                }
+                Err(p) => {
+                    send(IndexEvent::FileIndexed(&p));
+                    index.unknown.push(p);
+                }
+            }
+        };
+
+        let mut visited: HashSet<DirId> = HashSet::new();
+        let mut dirs = vec![(self.music_dir.clone(), 0)];
+        while let Some((dir, depth)) = dirs.pop() {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            if self.max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+            if self.follow_symlinks {
+                if let Some(id) = dir_id(&dir).await {
+                    if !visited.insert(id) {
+                        continue;
+                    }
+                }
+            }
+
+            let mut r = match tokio::fs::read_dir(&dir).await {
+                Ok(r) => r,
+                Err(e) => {
+                    send(IndexEvent::Warning { path: &dir, reason: &e.to_string() });
+                    continue;
+                }
+            };
+            loop {
+                let e = match r.next_entry().await {
+                    Ok(Some(e)) => e,
+                    Ok(None) => break,
+                    Err(e) => {
+                        send(IndexEvent::Warning { path: &dir, reason: &e.to_string() });
+                        break;
+                    }
+                };
+                let p = e.path();
+                if !self.include_hidden && is_hidden(&p) {
+                    continue;
+                }
+
+                let Ok(mut file_type) = e.file_type().await else { continue };
+                if file_type.is_symlink() {
+                    if !self.follow_symlinks {
+                        continue;
+                    }
+                    let Ok(meta) = tokio::fs::metadata(&p).await else { continue };
+                    file_type = meta.file_type();
+                }
+
+                if file_type.is_dir() {
+                    if self.excluded_dirs.contains(&p) {
+                        continue;
+                    }
+                    if let Some(marker) = foreign_marker(&self.foreign_markers, &p).await {
+                        info!(path = %p.display(), marker, "skipped dir managed by another tool");
+                        self.foreign_dirs.push(p);
+                        continue;
+                    }
+                    dirs.push((p, depth + 1));
+                } else if file_type.is_file() {
+                    if let Ok(meta) = tokio::fs::metadata(&p).await {
+                        self.sizes.insert(p.clone(), meta.len());
+                        if let Ok(modified) = meta.modified() {
+                            self.modified.insert(p.clone(), modified);
+                        }
+                    }
+                    let extension = p.extension().unwrap_or_default();
+                    if is_song_extension(extension) || is_music_video_extension(extension) {
+                        let cache = std::sync::Arc::clone(&cache);
+                        tasks.spawn_blocking(move || {
+                            let read = read_metadata(cache.as_ref().as_ref(), &p);
+                            (p, read)
+                        });
+                        if tasks.len() >= max_tasks {
+                            if let Some(r) = tasks.join_next().await {
+                                handle(self, r);
+                            }
+                        }
+                    } else if is_image_extension(extension) {
+                        send(IndexEvent::FileIndexed(&p));
+                        self.images.push(p);
+                    } else if is_companion_extension(extension) {
+                        send(IndexEvent::FileIndexed(&p));
+                        self.companions.push(p);
+                    } else if ExtraKind::from_extension(extension).is_some() {
+                        send(IndexEvent::FileIndexed(&p));
+                        self.extras.push(p);
+                    } else if is_playlist_extension(extension) {
+                        send(IndexEvent::FileIndexed(&p));
+                        self.playlists.push(p);
+                    } else {
+                        self.add_unsupported(&p);
+                    }
+                }
+            }
+            send(IndexEvent::DirScanned(&dir));
+        }
+
+        while let Some(r) = tasks.join_next().await {
+            handle(self, r);
+        }
+        self.cache = new_cache;
+        self.classify_audiobooks();
+    }
+}
+
+/// Runs a blocking operation on the blocking thread pool and reports the result.
+async fn run_blocking<E: ToString + Send + 'static>(
+    path: &Path,
+    events: &UnboundedSender<ExecEvent>,
+    f: impl FnOnce() -> Result<(), E> + Send + 'static,
+) -> bool {
+    let result = match tokio::task::spawn_blocking(f).await {
+        Ok(r) => r.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let failed = result.is_err();
+    let _ = events.send(ExecEvent::Done { path: path.to_owned(), result });
+    failed
+}
+
+type Sender = UnboundedSender<ExecEvent>;
+
+/// Runs a sync execution of `changes` on the blocking thread pool, which reports the results
+/// itself. Returns whether an operation failed.
+async fn run_moves(
+    changes: Changes,
+    options: &ExecOptions,
+    events: &Sender,
+    f: impl FnOnce(&Changes, &ExecOptions, &Sender) -> bool + Send + 'static,
+) -> bool {
+    let options = options.clone();
+    let events = events.clone();
+    tokio::task::spawn_blocking(move || f(&changes, &options, &events)).await.unwrap_or(true)
+}
+
+impl Changes {
+    /// Async version of executing the dir creations, song operations, file operations, playlist
+    /// updates and playlist creations in that order.
+    pub async fn execute_async(&self, options: &ExecOptions, events: &UnboundedSender<ExecEvent>) {
+        let mtimes = self.dir_mtimes(&RealFs, options);
+        self.execute_phases_async(options, events).await;
+        Self::restore_dir_mtimes(&RealFs, mtimes);
+    }
+
+    async fn execute_phases_async(
+        &self,
+        options: &ExecOptions,
+        events: &UnboundedSender<ExecEvent>,
+    ) {
+        let stop = |failed: bool| options.cancel.is_cancelled() || (failed && options.fail_fast);
+
+        for d in self.dir_creations.iter() {
+            let op = d.clone();
+            if stop(run_blocking(&d.path, events, move || op.execute(&RealFs)).await) {
+                return;
+            }
+        }
+
+        // moves are run by the sync execution, which orders chained moves
+        let song_operations = self.song_operations.clone();
+        let f = move |changes: &Changes, options: &ExecOptions, events: &Sender| {
+            let mut failed = false;
+            let progress = &mut |p: &Path, copied, total| {
+                let _ = events.send(ExecEvent::Copied { path: p.to_owned(), copied, total });
+            };
+            changes.execute_song_operations(&RealFs, options, progress, &mut |o, r| {
+                failed |= r.is_err();
+                let result = r.map_err(|e| e.to_string());
+                let _ = events.send(ExecEvent::Done { path: o.song.path.clone(), result });
+            });
+            failed
+        };
+        let changes = Changes { song_operations, ..Default::default() };
+        if stop(run_moves(changes, options, events, f).await) {
+            return;
+        }
+
+        let file_operations = self.file_operations.clone();
+        let f = move |changes: &Changes, options: &ExecOptions, events: &Sender| {
+            let mut failed = false;
+            let progress = &mut |p: &Path, copied, total| {
+                let _ = events.send(ExecEvent::Copied { path: p.to_owned(), copied, total });
+            };
+            changes.execute_file_operations(&RealFs, options, progress, &mut |o, r| {
+                failed |= r.is_err();
+                let result = r.map_err(|e| e.to_string());
+                let _ = events.send(ExecEvent::Done { path: o.old_path.clone(), result });
+            });
+            failed
+        };
+        let changes = Changes { file_operations, ..Default::default() };
+        if stop(run_moves(changes, options, events, f).await) {
+            return;
+        }
+
+        for u in self.playlist_updates.iter() {
+            let op = u.clone();
+            if stop(run_blocking(&u.path, events, move || op.execute(&RealFs)).await) {
+                return;
+            }
+        }
+
+        for c in self.playlist_creations.iter() {
+            let op = c.clone();
+            if stop(run_blocking(&c.path, events, move || op.execute(&RealFs)).await) {
+                return;
+            }
+        }
+    }
+}
+
+impl Cleanup {
+    /// Async version of executing the file deletions followed by the dir deletions.
+    pub async fn execute_async(&self, options: &ExecOptions, events: &UnboundedSender<ExecEvent>) {
+        let stop = |failed: bool| options.cancel.is_cancelled() || (failed && options.fail_fast);
+        let delete_type = options.delete_type;
+
+        for d in self.file_deletions.iter() {
+            let op = d.clone();
+            if stop(run_blocking(&d.path, events, move || op.execute(&RealFs, delete_type)).await) {
+                return;
+            }
+        }
+
+        for d in self.dir_deletions.iter() {
+            let op = d.clone();
+            if stop(run_blocking(&d.path, events, move || op.execute(&RealFs, delete_type)).await) {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,612 @@
+use std::io::{Read, Write};
+#[cfg(any(feature = "mp3", feature = "mp4"))]
+use std::io::{Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "mp4")]
+use crate::Chapter;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Codec {
+    Mp3,
+    Aac,
+    Alac,
+    Flac,
+}
+
+impl std::fmt::Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mp3 => f.write_str("MP3"),
+            Self::Aac => f.write_str("AAC"),
+            Self::Alac => f.write_str("ALAC"),
+            Self::Flac => f.write_str("FLAC"),
+        }
+    }
+}
+
+impl std::str::FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mp3" => Ok(Self::Mp3),
+            "aac" => Ok(Self::Aac),
+            "alac" => Ok(Self::Alac),
+            "flac" => Ok(Self::Flac),
+            _ => Err(format!("Unknown codec: {s}")),
+        }
+    }
+}
+
+impl Codec {
+    pub fn is_lossless(&self) -> bool {
+        matches!(self, Self::Alac | Self::Flac)
+    }
+}
+
+/// Properties of the audio stream, as opposed to the tags.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudioInfo {
+    pub codec: Option<Codec>,
+    pub duration: Option<Duration>,
+    /// Average bitrate in kbit/s.
+    pub bitrate: Option<u32>,
+    /// Sample rate in Hz.
+    pub sample_rate: Option<u32>,
+}
+
+/// Average bitrate in kbit/s of `bytes` of audio data played over `duration`.
+#[cfg(any(feature = "mp3", feature = "flac"))]
+pub(crate) fn avg_bitrate(bytes: u64, duration: Duration) -> Option<u32> {
+    let millis = duration.as_millis() as u64;
+    (bytes * 8).checked_div(millis).map(|b| b as u32)
+}
+
+#[cfg(feature = "mp3")]
+const MP3_SEARCH_LIMIT: u64 = 64 * 1024;
+
+/// Reads the first MPEG audio frame after the ID3v2 tag, and a Xing/Info or VBRI header if
+/// present. Without one of these headers the file is assumed to have a constant bitrate.
+#[cfg(feature = "mp3")]
+pub(crate) fn read_mp3(file: &mut (impl Read + Seek)) -> Option<AudioInfo> {
+    let file_len = file.seek(SeekFrom::End(0)).ok()?;
+    file.seek(SeekFrom::Start(0)).ok()?;
+
+    let mut header = [0; 10];
+    file.read_exact(&mut header).ok()?;
+    let mut start = 0;
+    if &header[0..3] == b"ID3" {
+        let size = header[6..10].iter().fold(0, |s, b| (s << 7) | (*b as u64 & 0x7f));
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        start = 10 + size + footer;
+    }
+
+    let mut buf = Vec::new();
+    file.seek(SeekFrom::Start(start)).ok()?;
+    file.by_ref().take(MP3_SEARCH_LIMIT).read_to_end(&mut buf).ok()?;
+
+    let (offset, frame) = (0..buf.len().saturating_sub(4)).find_map(|i| {
+        let frame = FrameHeader::parse(&buf[i..])?;
+        // require a second frame right after this one, to avoid false syncs
+        match buf.get(i + frame.len..) {
+            Some(next) if next.len() >= 4 => FrameHeader::parse(next).map(|_| (i, frame)),
+            _ => Some((i, frame)),
+        }
+    })?;
+    let audio_bytes = file_len - start - offset as u64;
+
+    let frame_buf = &buf[offset..];
+    let (frames, bytes) = frame.read_vbr_header(frame_buf).unwrap_or((None, None));
+    let duration = match frames {
+        Some(frames) => {
+            Duration::from_secs_f64(frames as f64 * frame.samples as f64 / frame.sample_rate as f64)
+        }
+        None => Duration::from_secs_f64(audio_bytes as f64 * 8.0 / (frame.bitrate * 1000) as f64),
+    };
+    let bitrate = match frames {
+        Some(_) => avg_bitrate(bytes.map_or(audio_bytes, |b| b as u64), duration),
+        None => Some(frame.bitrate),
+    };
+
+    Some(AudioInfo {
+        codec: Some(Codec::Mp3),
+        duration: Some(duration),
+        bitrate,
+        sample_rate: Some(frame.sample_rate),
+    })
+}
+
+#[cfg(feature = "mp3")]
+struct FrameHeader {
+    mpeg1: bool,
+    mono: bool,
+    /// kbit/s
+    bitrate: u32,
+    sample_rate: u32,
+    samples: u32,
+    len: usize,
+}
+
+#[cfg(feature = "mp3")]
+const BITRATES_V1_L1: [u32; 15] =
+    [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448];
+#[cfg(feature = "mp3")]
+const BITRATES_V1_L2: [u32; 15] =
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384];
+#[cfg(feature = "mp3")]
+const BITRATES_V1_L3: [u32; 15] =
+    [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+#[cfg(feature = "mp3")]
+const BITRATES_V2_L1: [u32; 15] =
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256];
+#[cfg(feature = "mp3")]
+const BITRATES_V2_L23: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+#[cfg(feature = "mp3")]
+const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+#[cfg(feature = "mp3")]
+impl FrameHeader {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() < 4 || buf[0] != 0xff || buf[1] & 0xe0 != 0xe0 {
+            return None;
+        }
+
+        let version = (buf[1] >> 3) & 0b11;
+        let layer = (buf[1] >> 1) & 0b11;
+        let bitrate_index = (buf[2] >> 4) as usize;
+        let sample_rate_index = ((buf[2] >> 2) & 0b11) as usize;
+        let padding = ((buf[2] >> 1) & 1) as usize;
+        let mono = (buf[3] >> 6) == 0b11;
+
+        // 0b01 is reserved, 0b00 is MPEG 2.5
+        let mpeg1 = match version {
+            0b11 => true,
+            0b10 | 0b00 => false,
+            _ => return None,
+        };
+        if layer == 0 || bitrate_index == 0 || bitrate_index == 15 || sample_rate_index == 3 {
+            return None;
+        }
+
+        let bitrate = match (mpeg1, layer) {
+            (true, 0b11) => BITRATES_V1_L1,
+            (true, 0b10) => BITRATES_V1_L2,
+            (true, _) => BITRATES_V1_L3,
+            (false, 0b11) => BITRATES_V2_L1,
+            (false, _) => BITRATES_V2_L23,
+        }[bitrate_index];
+        let sample_rate = match version {
+            0b11 => SAMPLE_RATES[sample_rate_index],
+            0b10 => SAMPLE_RATES[sample_rate_index] / 2,
+            _ => SAMPLE_RATES[sample_rate_index] / 4,
+        };
+        let samples = match (mpeg1, layer) {
+            (_, 0b11) => 384,
+            (false, 0b01) => 576,
+            _ => 1152,
+        };
+        let len = match layer {
+            0b11 => (12 * bitrate as usize * 1000 / sample_rate as usize + padding) * 4,
+            _ => samples as usize / 8 * bitrate as usize * 1000 / sample_rate as usize + padding,
+        };
+
+        Some(Self { mpeg1, mono, bitrate, sample_rate, samples, len })
+    }
+
+    /// Returns the number of frames and bytes stored in a Xing/Info or VBRI header.
+    fn read_vbr_header(&self, frame: &[u8]) -> Option<(Option<u32>, Option<u32>)> {
+        let read_u32 = |i: usize| -> Option<u32> {
+            frame.get(i..i + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        };
+
+        let side_info = match (self.mpeg1, self.mono) {
+            (true, false) => 32,
+            (true, true) | (false, false) => 17,
+            (false, true) => 9,
+        };
+        let xing = 4 + side_info;
+        if let Some(b"Xing" | b"Info") = frame.get(xing..xing + 4) {
+            let flags = read_u32(xing + 4)?;
+            let mut i = xing + 8;
+            let frames = if flags & 0x1 != 0 {
+                i += 4;
+                read_u32(i - 4)
+            } else {
+                None
+            };
+            let bytes = if flags & 0x2 != 0 { read_u32(i) } else { None };
+            return Some((frames, bytes));
+        }
+
+        if frame.get(36..40) == Some(b"VBRI") {
+            return Some((read_u32(50), read_u32(46)));
+        }
+
+        None
+    }
+}
+
+/// Finds the codec of the first sample description in `moov/trak/mdia/minf/stbl/stsd`.
+#[cfg(feature = "mp4")]
+pub(crate) fn read_mp4_codec(file: &mut (impl Read + Seek)) -> Option<Codec> {
+    const PATH: [&[u8; 4]; 6] = [b"moov", b"trak", b"mdia", b"minf", b"stbl", b"stsd"];
+
+    let mut start = 0;
+    let mut end = file.seek(SeekFrom::End(0)).ok()?;
+    for name in PATH {
+        (start, end) = find_atom(file, start, end, name)?;
+    }
+
+    // version and flags, entry count, size of the first entry
+    file.seek(SeekFrom::Start(start + 12)).ok()?;
+    let mut fourcc = [0; 4];
+    file.read_exact(&mut fourcc).ok()?;
+    match &fourcc {
+        b"mp4a" => Some(Codec::Aac),
+        b"alac" => Some(Codec::Alac),
+        b"fLaC" => Some(Codec::Flac),
+        _ => None,
+    }
+}
+
+/// Reads the Nero chapter list in `moov/udta/chpl`, which most audiobook encoders write. Returns
+/// `None` if the file has no chapter list or it's malformed.
+#[cfg(feature = "mp4")]
+pub(crate) fn read_mp4_chapters(file: &mut (impl Read + Seek)) -> Option<Vec<Chapter>> {
+    const PATH: [&[u8; 4]; 3] = [b"moov", b"udta", b"chpl"];
+    /// Chapter lists are small, larger atoms are most likely corrupt.
+    const MAX_LEN: u64 = 1 << 20;
+
+    let mut start = 0;
+    let mut end = file.seek(SeekFrom::End(0)).ok()?;
+    for name in PATH {
+        (start, end) = find_atom(file, start, end, name)?;
+    }
+    if end - start > MAX_LEN {
+        return None;
+    }
+
+    let mut data = vec![0; (end - start) as usize];
+    file.seek(SeekFrom::Start(start)).ok()?;
+    file.read_exact(&mut data).ok()?;
+
+    // version and flags, version 1 has 4 more reserved bytes
+    let mut pos = if *data.first()? == 0 { 4 } else { 8 };
+    let count = *data.get(pos)?;
+    pos += 1;
+    let mut chapters = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        // the start is stored in units of 100 nanoseconds
+        let start = u64::from_be_bytes(data.get(pos..pos + 8)?.try_into().ok()?);
+        let len = *data.get(pos + 8)? as usize;
+        let title = data.get(pos + 9..pos + 9 + len)?;
+        pos += 9 + len;
+        chapters.push(Chapter {
+            title: String::from_utf8_lossy(title).into_owned(),
+            start: Duration::from_nanos(start.saturating_mul(100)),
+        });
+    }
+    Some(chapters)
+}
+
+/// Returns the range of the content of the first atom named `name` between `start` and `end`.
+#[cfg(feature = "mp4")]
+fn find_atom(
+    file: &mut (impl Read + Seek),
+    mut start: u64,
+    end: u64,
+    name: &[u8; 4],
+) -> Option<(u64, u64)> {
+    while start + 8 <= end {
+        file.seek(SeekFrom::Start(start)).ok()?;
+        let mut head = [0; 8];
+        file.read_exact(&mut head).ok()?;
+        let mut len = u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as u64;
+        let mut head_len = 8;
+        if len == 1 {
+            let mut ext = [0; 8];
+            file.read_exact(&mut ext).ok()?;
+            len = u64::from_be_bytes(ext);
+            head_len = 16;
+        } else if len == 0 {
+            len = end - start;
+        }
+        if len < head_len {
+            return None;
+        }
+
+        if &head[4..8] == name {
+            return Some((start + head_len, (start + len).min(end)));
+        }
+        start += len;
+    }
+
+    None
+}
+
+/// Copies the audio data of a file without its tags to `writer`, so copies of a song that only
+/// differ in their tags have the same audio data. Returns `None` for files of other formats, or
+/// that can't be parsed, `writer` might have received part of the file by then.
+pub(crate) fn copy_audio(
+    path: &Path,
+    size: u64,
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+) -> Option<()> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    match ext.to_ascii_lowercase().as_str() {
+        "mp3" => copy_without_id3(size, reader, writer),
+        "flac" => copy_flac_frames(reader, writer),
+        "m4a" | "m4b" => copy_mp4_media_data(size, reader, writer),
+        _ => None,
+    }
+}
+
+fn skip(reader: &mut impl Read, len: u64) -> Option<()> {
+    copy_exact(reader, &mut std::io::sink(), len)
+}
+
+fn copy_exact(reader: &mut impl Read, writer: &mut impl Write, len: u64) -> Option<()> {
+    let copied = std::io::copy(&mut reader.take(len), writer).ok()?;
+    (copied == len).then_some(())
+}
+
+/// Strips an ID3v2 tag at the start and an ID3v1 tag at the end.
+fn copy_without_id3(size: u64, reader: &mut impl Read, writer: &mut impl Write) -> Option<()> {
+    let mut header = [0; 10];
+    reader.read_exact(&mut header).ok()?;
+    let mut remaining = size.checked_sub(10)?;
+    if header.starts_with(b"ID3") {
+        // the size is stored as a syncsafe integer and excludes the header and footer
+        let tag_size = header[6..10].iter().fold(0, |s, b| s << 7 | (b & 0x7f) as u64);
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        remaining = remaining.checked_sub(tag_size + footer)?;
+        skip(reader, tag_size + footer)?;
+    } else {
+        writer.write_all(&header).ok()?;
+    }
+
+    match remaining.checked_sub(128) {
+        Some(audio) => {
+            copy_exact(reader, writer, audio)?;
+            let mut trailer = [0; 128];
+            reader.read_exact(&mut trailer).ok()?;
+            if !trailer.starts_with(b"TAG") {
+                writer.write_all(&trailer).ok()?;
+            }
+            Some(())
+        }
+        None => copy_exact(reader, writer, remaining),
+    }
+}
+
+/// Skips the metadata blocks, which include the vorbis comments and pictures.
+fn copy_flac_frames(reader: &mut impl Read, writer: &mut impl Write) -> Option<()> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic).ok()?;
+    if &magic != b"fLaC" {
+        return None;
+    }
+    loop {
+        let mut head = [0; 4];
+        reader.read_exact(&mut head).ok()?;
+        skip(reader, u32::from_be_bytes([0, head[1], head[2], head[3]]) as u64)?;
+        // the last block is marked by the highest bit
+        if head[0] & 0x80 != 0 {
+            return std::io::copy(reader, writer).ok().map(|_| ());
+        }
+    }
+}
+
+/// The content of the top level `mdat` atom, the tags are stored in `moov`.
+fn copy_mp4_media_data(size: u64, reader: &mut impl Read, writer: &mut impl Write) -> Option<()> {
+    let mut pos = 0u64;
+    while pos + 8 <= size {
+        let mut head = [0; 8];
+        reader.read_exact(&mut head).ok()?;
+        let mut len = u32::from_be_bytes(head[..4].try_into().ok()?) as u64;
+        let mut head_len = 8;
+        if len == 1 {
+            let mut large = [0; 8];
+            reader.read_exact(&mut large).ok()?;
+            len = u64::from_be_bytes(large);
+            head_len = 16;
+        } else if len == 0 {
+            len = size - pos;
+        }
+        if len < head_len {
+            return None;
+        }
+
+        if &head[4..] == b"mdat" {
+            return copy_exact(reader, writer, len - head_len);
+        }
+        skip(reader, len - head_len)?;
+        pos = pos.saturating_add(len);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "mp3", feature = "mp4"))]
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// A MPEG 1 layer III frame header of 128 kbit/s at 44.1 kHz, the frame is 417 bytes long.
+    #[cfg(feature = "mp3")]
+    const FRAME_HEADER: [u8; 4] = [0xff, 0xfb, 0x90, 0x00];
+
+    #[cfg(feature = "mp3")]
+    fn frames(num: usize) -> Vec<u8> {
+        let mut frame = FRAME_HEADER.to_vec();
+        frame.resize(417, 0);
+        frame.repeat(num)
+    }
+
+    fn id3(tag: &[u8]) -> Vec<u8> {
+        [b"ID3\x04\0\0\0\0\0".as_slice(), &[tag.len() as u8], tag].concat()
+    }
+
+    fn atom(name: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        [&(content.len() as u32 + 8).to_be_bytes(), name.as_slice(), content].concat()
+    }
+
+    #[test]
+    #[cfg(feature = "mp3")]
+    fn parses_frame_headers() {
+        let frame = FrameHeader::parse(&FRAME_HEADER).unwrap();
+        assert!(frame.mpeg1 && !frame.mono);
+        assert_eq!((frame.bitrate, frame.sample_rate, frame.samples), (128, 44100, 1152));
+        assert_eq!(frame.len, 417);
+
+        // MPEG 2 layer III, 64 kbit/s at 22.05 kHz, mono
+        let frame = FrameHeader::parse(&[0xff, 0xf3, 0x80, 0xc0]).unwrap();
+        assert!(!frame.mpeg1 && frame.mono);
+        assert_eq!((frame.bitrate, frame.sample_rate, frame.samples), (64, 22050, 576));
+        assert_eq!(frame.len, 208);
+
+        // reserved version, free and bad bitrates, reserved sample rate and no sync
+        let invalid: [[u8; 4]; 5] = [
+            [0xff, 0xeb, 0x90, 0],
+            [0xff, 0xfb, 0x00, 0],
+            [0xff, 0xfb, 0xf0, 0],
+            [0xff, 0xfb, 0x9c, 0],
+            [0xfe, 0xfb, 0x90, 0],
+        ];
+        for header in invalid {
+            assert!(FrameHeader::parse(&header).is_none(), "{header:x?}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "mp3")]
+    fn reads_constant_bitrate_mp3() {
+        let data = [id3(b"tags"), frames(10)].concat();
+        let info = read_mp3(&mut Cursor::new(data)).unwrap();
+        assert_eq!(info.codec, Some(Codec::Mp3));
+        assert_eq!(info.duration, Some(Duration::from_secs_f64(4170.0 * 8.0 / 128000.0)));
+        assert_eq!(info.bitrate, Some(128));
+        assert_eq!(info.sample_rate, Some(44100));
+    }
+
+    #[test]
+    #[cfg(feature = "mp3")]
+    fn reads_xing_and_vbri_headers() {
+        let duration = |frames: u32| Duration::from_secs_f64(frames as f64 * 1152.0 / 44100.0);
+
+        // the side info of a stereo MPEG 1 frame is 32 bytes long
+        let mut xing = frames(3);
+        xing[36..40].copy_from_slice(b"Xing");
+        xing[40..44].copy_from_slice(&3u32.to_be_bytes());
+        xing[44..48].copy_from_slice(&100u32.to_be_bytes());
+        xing[48..52].copy_from_slice(&41700u32.to_be_bytes());
+        let info = read_mp3(&mut Cursor::new(xing)).unwrap();
+        assert_eq!(info.duration, Some(duration(100)));
+        assert_eq!(info.bitrate, Some(41700 * 8 / 2612));
+
+        let mut vbri = frames(3);
+        vbri[36..40].copy_from_slice(b"VBRI");
+        vbri[46..50].copy_from_slice(&83400u32.to_be_bytes());
+        vbri[50..54].copy_from_slice(&200u32.to_be_bytes());
+        let info = read_mp3(&mut Cursor::new(vbri)).unwrap();
+        assert_eq!(info.duration, Some(duration(200)));
+        assert_eq!(info.bitrate, Some(83400 * 8 / 5224));
+    }
+
+    #[test]
+    #[cfg(feature = "mp3")]
+    fn rejects_mp3_without_frames() {
+        let data = [id3(b"tags"), vec![0; 1000]].concat();
+        assert_eq!(read_mp3(&mut Cursor::new(data)), None);
+    }
+
+    #[cfg(feature = "mp4")]
+    fn mp4(stsd_entry: &[u8; 4], udta: &[u8]) -> Vec<u8> {
+        let stsd = [&[0; 4], &1u32.to_be_bytes(), &36u32.to_be_bytes(), stsd_entry.as_slice()];
+        let stbl = atom(b"stbl", &atom(b"stsd", &stsd.concat()));
+        let trak = atom(b"trak", &atom(b"mdia", &atom(b"minf", &stbl)));
+        let moov = atom(b"moov", &[atom(b"udta", udta), trak].concat());
+        [atom(b"ftyp", b"M4A "), moov, atom(b"mdat", b"audio")].concat()
+    }
+
+    #[test]
+    #[cfg(feature = "mp4")]
+    fn reads_mp4_codecs() {
+        for (fourcc, codec) in
+            [(b"mp4a", Codec::Aac), (b"alac", Codec::Alac), (b"fLaC", Codec::Flac)]
+        {
+            assert_eq!(read_mp4_codec(&mut Cursor::new(mp4(fourcc, &[]))), Some(codec));
+        }
+        assert_eq!(read_mp4_codec(&mut Cursor::new(mp4(b"avc1", &[]))), None);
+        assert_eq!(read_mp4_codec(&mut Cursor::new(atom(b"moov", &[]))), None);
+    }
+
+    #[test]
+    #[cfg(feature = "mp4")]
+    fn reads_nero_chapters() {
+        let chapter = |start: u64, title: &str| {
+            [&start.to_be_bytes(), [title.len() as u8].as_slice(), title.as_bytes()].concat()
+        };
+        let chapters = [chapter(0, "One"), chapter(600_000_000, "Two")].concat();
+        let chpl = atom(b"chpl", &[[1, 0, 0, 0, 0, 0, 0, 0, 2].as_slice(), &chapters].concat());
+
+        let read = read_mp4_chapters(&mut Cursor::new(mp4(b"mp4a", &chpl))).unwrap();
+        assert_eq!(
+            read,
+            [
+                Chapter { title: "One".to_owned(), start: Duration::ZERO },
+                Chapter { title: "Two".to_owned(), start: Duration::from_secs(60) },
+            ]
+        );
+
+        // the list claims more chapters than it contains
+        let chpl = atom(b"chpl", &[[0, 0, 0, 0, 3].as_slice(), &chapters].concat());
+        assert_eq!(read_mp4_chapters(&mut Cursor::new(mp4(b"mp4a", &chpl))), None);
+        assert_eq!(read_mp4_chapters(&mut Cursor::new(mp4(b"mp4a", &[]))), None);
+    }
+
+    fn audio(path: &str, data: &[u8]) -> Option<Vec<u8>> {
+        let mut audio = Vec::new();
+        copy_audio(Path::new(path), data.len() as u64, &mut &data[..], &mut audio)?;
+        Some(audio)
+    }
+
+    #[test]
+    fn strips_id3_tags() {
+        let v1 = [b"TAG".as_slice(), &[b'x'; 125]].concat();
+        let frames = vec![0xaa; 200];
+        assert_eq!(
+            audio("a.mp3", &[id3(b"tags"), frames.clone(), v1].concat()),
+            Some(frames.clone())
+        );
+        assert_eq!(audio("a.MP3", &frames), Some(frames));
+        assert_eq!(audio("a.mp3", b"short"), None);
+    }
+
+    #[test]
+    fn skips_flac_metadata() {
+        let block = |last: bool, content: &[u8]| {
+            let head = if last { 0x84 } else { 0x04 };
+            [&[head, 0, 0, content.len() as u8], content].concat()
+        };
+        let data = [b"fLaC".as_slice(), &block(false, b"vorbis"), &block(true, b"pic"), b"frames"];
+        assert_eq!(audio("a.flac", &data.concat()), Some(b"frames".to_vec()));
+        assert_eq!(audio("a.flac", &data[..2].concat()), None);
+        assert_eq!(audio("a.flac", b"RIFF"), None);
+    }
+
+    #[test]
+    fn copies_mp4_media_data() {
+        let data = [atom(b"ftyp", b"M4A "), atom(b"moov", b"tags"), atom(b"mdat", b"audio")];
+        assert_eq!(audio("a.m4a", &data.concat()), Some(b"audio".to_vec()));
+        assert_eq!(audio("a.m4b", &data[..2].concat()), None);
+        assert_eq!(audio("a.ogg", &data.concat()), None);
+    }
+}
@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{AudioInfo, MusicIndex, Song};
+
+/// Audiobooks are kept in a separate tree, only the release artists (the authors), artists,
+/// release (the book), title, year and genre fields have values.
+pub const DEFAULT_AUDIOBOOK_LAYOUT: &str = "Audiobooks/{release_artists}/{release}/{title}";
+
+/// Files with these extensions are classified as audiobooks after indexing.
+const AUDIOBOOK_EXTENSIONS: [&str; 1] = ["m4b"];
+
+/// A chapter of an audiobook, see [`Audiobook::chapters`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub title: String,
+    /// The offset from the start of the file.
+    pub start: Duration,
+}
+
+/// An m4b audiobook, which is organized using the
+/// [`ChangeOptions::audiobook_layout`](crate::ChangeOptions::audiobook_layout) and never checked
+/// like songs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Audiobook {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub path: PathBuf,
+    pub authors: Vec<String>,
+    pub artists: Vec<String>,
+    pub book: String,
+    pub title: String,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    pub audio: AudioInfo,
+    /// Read from the Nero chapter list, empty if the file has none.
+    pub chapters: Vec<Chapter>,
+}
+
+impl Audiobook {
+    /// Reads the chapters of the file, the tags are taken from the song it was indexed as.
+    fn from_song(song: Song) -> Self {
+        let chapters = read_chapters(&song);
+        Self {
+            authors: song.release_artists.to_vec(),
+            artists: song.artists.to_vec(),
+            book: song.release.to_string(),
+            title: song.title,
+            year: song.year,
+            genre: song.genre,
+            audio: song.audio,
+            chapters,
+            path: song.path,
+        }
+    }
+}
+
+#[cfg(feature = "mp4")]
+fn read_chapters(song: &Song) -> Vec<Chapter> {
+    let Ok(mut file) = std::fs::File::open(&song.path) else { return Vec::new() };
+    crate::audio::read_mp4_chapters(&mut file).unwrap_or_default()
+}
+
+#[cfg(not(feature = "mp4"))]
+fn read_chapters(_: &Song) -> Vec<Chapter> {
+    Vec::new()
+}
+
+fn is_audiobook(song: &Song) -> bool {
+    let ext = song.path.extension().unwrap_or_default();
+    AUDIOBOOK_EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e))
+}
+
+impl MusicIndex {
+    /// Moves songs that are audiobooks into [`MusicIndex::audiobooks`] and reads their chapters,
+    /// so no music specific checks are applied to them. Run after reading the index.
+    pub(crate) fn classify_audiobooks(&mut self) {
+        let (audiobooks, songs): (Vec<Song>, Vec<Song>) =
+            std::mem::take(&mut self.songs).into_iter().partition(is_audiobook);
+        self.songs = songs;
+        for song in audiobooks {
+            let audiobook = Audiobook::from_song(song);
+            let chapters = audiobook.chapters.len();
+            debug!(path = %audiobook.path.display(), chapters, "audiobook");
+            self.audiobooks.push(audiobook);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{ChangeOptions, Changes, Checks, LibraryBuilder, MemFs};
+
+    #[test]
+    fn organizes_audiobooks_separately() {
+        // the same book once as an audiobook and once as a song
+        let library = LibraryBuilder::new("/music")
+            .release("Tolkien", "The Hobbit", 1)
+            .map(|s| {
+                s.path.set_extension("m4b");
+            })
+            .release("Tolkien", "The Hobbit", 1)
+            .map(|s| s.title = "The Hobbit".to_owned());
+        let mut index = library.index();
+        index.classify_audiobooks();
+        assert_eq!(index.songs.len(), 1);
+        assert_eq!(index.audiobooks.len(), 1);
+
+        let options = ChangeOptions::from(PathBuf::from("/music"));
+        let changes = Changes::generate(&MemFs::new(), Checks::from(&index), &options);
+
+        let op =
+            changes.file_operations.iter().find(|o| o.old_path.extension() == Some("m4b".as_ref()));
+        let expected = "/music/Audiobooks/Tolkien/The Hobbit/The Hobbit.m4b";
+        assert_eq!(op.map(|o| o.new_path.as_path()), Some(Path::new(expected)));
+        assert_eq!(changes.song_operations.len(), 1);
+    }
+}
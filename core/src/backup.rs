@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::debug;
+
+use crate::fs::copy_dir;
+use crate::Fs;
+
+/// A dir of the backup dir that files are copied into before they are moved or their tags are
+/// rewritten in place, see [`ExecOptions::backup`](crate::ExecOptions::backup). Each run gets
+/// its own dir named after the time it started, so runs sort chronologically.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backup {
+    pub dir: PathBuf,
+    /// Paths inside of it are backed up relative to it, e.g. the music dir, others with their
+    /// full path.
+    pub root: PathBuf,
+}
+
+impl Backup {
+    pub fn new(backup_dir: &Path, root: impl Into<PathBuf>, time: SystemTime) -> Self {
+        Self { dir: backup_dir.join(format_time(time, '-')), root: root.into() }
+    }
+
+    /// The path the file is backed up at.
+    pub fn path(&self, path: &Path) -> PathBuf {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let relative = relative.strip_prefix("/").unwrap_or(relative);
+        self.dir.join(relative)
+    }
+
+    /// Copies a file or dir into the backup, unless it was already backed up during this run.
+    pub fn save(&self, fs: &impl Fs, path: &Path) -> std::io::Result<()> {
+        let backup = self.path(path);
+        if fs.exists(&backup) {
+            return Ok(());
+        }
+        debug!(path = %path.display(), backup = %backup.display(), "backup");
+        if let Some(parent) = backup.parent() {
+            fs.create_dir_all(parent)?;
+        }
+        match fs.is_dir(path) {
+            true => copy_dir(fs, path, &backup, false, &mut |_, _| ())?,
+            false => fs.copy(path, &backup, &mut |_, _| ())?,
+        };
+        Ok(())
+    }
+}
+
+/// Removes all but the newest `keep` backups of the backup dir, returns the removed dirs.
+pub fn prune_backups(
+    fs: &impl Fs,
+    backup_dir: &Path,
+    keep: usize,
+) -> std::io::Result<Vec<PathBuf>> {
+    if !fs.is_dir(backup_dir) {
+        return Ok(Vec::new());
+    }
+    let mut runs: Vec<PathBuf> =
+        fs.read_dir(backup_dir)?.into_iter().filter(|p| fs.is_dir(p)).collect();
+    runs.sort();
+    let num_pruned = runs.len().saturating_sub(keep);
+    runs.truncate(num_pruned);
+    for r in runs.iter() {
+        debug!(dir = %r.display(), "prune backup");
+        remove_dir_all(fs, r)?;
+    }
+    Ok(runs)
+}
+
+fn remove_dir_all(fs: &impl Fs, dir: &Path) -> std::io::Result<()> {
+    for p in fs.read_dir(dir)? {
+        match fs.is_dir(&p) {
+            true => remove_dir_all(fs, &p)?,
+            false => fs.remove_file(&p)?,
+        }
+    }
+    fs.remove_dir(dir)
+}
+
+/// `2024-05-17T08-30-00` with `-` as the time separator, in UTC.
+pub(crate) fn format_time(time: SystemTime, time_separator: char) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+
+    // the days since the epoch as a date of the proleptic gregorian calendar
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let s = time_separator;
+    format!("{year:04}-{month:02}-{day:02}T{hours:02}{s}{minutes:02}{s}{seconds:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{ExecOptions, MemFs, Song, SongOperation};
+
+    #[test]
+    fn backs_up_moved_songs_and_prunes_old_backups() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a.mp3", "one");
+        fs.add_file("/backup/2020-01-01T00-00-00/b.mp3", "old");
+        fs.add_file("/backup/2021-01-01T00-00-00/b.mp3", "old");
+        let time = UNIX_EPOCH + Duration::from_secs(1715934600);
+        let backup = Backup::new(Path::new("/backup"), "/music", time);
+        assert_eq!(backup.dir, Path::new("/backup/2024-05-17T08-30-00"));
+
+        let song = Song { path: PathBuf::from("/music/a.mp3"), ..Default::default() };
+        let op = SongOperation {
+            new_path: Some(PathBuf::from("/music/b.mp3")),
+            ..SongOperation::new(song)
+        };
+        let options = ExecOptions { backup: Some(backup), ..Default::default() };
+        op.execute(&fs, &options, &mut |_, _| ()).unwrap();
+
+        let backed_up = fs.read(Path::new("/backup/2024-05-17T08-30-00/a.mp3")).unwrap();
+        assert_eq!(backed_up, b"one");
+        assert!(!fs.exists(Path::new("/music/a.mp3")));
+
+        let pruned = prune_backups(&fs, Path::new("/backup"), 2).unwrap();
+        assert_eq!(pruned, [PathBuf::from("/backup/2020-01-01T00-00-00")]);
+        assert!(!fs.exists(Path::new("/backup/2020-01-01T00-00-00")));
+        assert!(fs.exists(Path::new("/backup/2021-01-01T00-00-00/b.mp3")));
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::{util, Changes, Checks, Fs, Value};
+
+/// The `library.db` of beets, whose names are used as the canonical ones and whose paths are
+/// kept in sync when songs are moved, so both tools can manage the same library.
+pub struct BeetsDb {
+    conn: Connection,
+}
+
+/// An entry of the `items` table of beets.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BeetsItem {
+    pub path: PathBuf,
+    pub artist: String,
+    pub album_artist: String,
+    pub album: String,
+    pub title: String,
+}
+
+impl BeetsDb {
+    /// Opens an existing database, it is never created.
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+        Ok(Self { conn })
+    }
+
+    pub fn load_items(&self) -> rusqlite::Result<Vec<BeetsItem>> {
+        let mut stmt =
+            self.conn.prepare("SELECT path, artist, albumartist, album, title FROM items")?;
+        let items = stmt.query_map([], |row| {
+            let text = |i: usize| -> rusqlite::Result<String> {
+                Ok(row.get::<_, Option<String>>(i)?.unwrap_or_default())
+            };
+            Ok(BeetsItem {
+                path: path_from_bytes(row.get_ref(0)?.as_bytes()?),
+                artist: text(1)?,
+                album_artist: text(2)?,
+                album: text(3)?,
+                title: text(4)?,
+            })
+        })?;
+        items.collect()
+    }
+
+    /// Points the items of moved songs to their new paths. Only songs that are at their new
+    /// path and no longer at the old one are updated, so failed or skipped operations are left
+    /// alone. Returns the number of updated items.
+    pub fn update_paths(&mut self, fs: &impl Fs, changes: &Changes) -> rusqlite::Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut updated = 0;
+        {
+            let mut stmt = tx.prepare("UPDATE items SET path = ?1 WHERE path = ?2")?;
+            for o in changes.song_operations.iter() {
+                let Some(new_path) = &o.new_path else { continue };
+                if !fs.exists(new_path) || fs.exists(&o.song.path) {
+                    continue;
+                }
+                updated += stmt.execute([path_bytes(new_path), path_bytes(&o.song.path)])?;
+            }
+        }
+        tx.commit()?;
+        Ok(updated)
+    }
+}
+
+impl Checks<'_> {
+    /// Updates the release artists, artists, release and title of songs tracked by beets to
+    /// the names beets uses. Empty values in beets are ignored.
+    pub fn import_beets(&mut self, items: &[BeetsItem]) {
+        let items: HashMap<&Path, &BeetsItem> = items.iter().map(|i| (&*i.path, i)).collect();
+        for song in self.index.songs.iter() {
+            let Some(item) = items.get(song.path.as_path()) else { continue };
+
+            let changed = |old: &str, new: &str| !new.is_empty() && old != new;
+            let release_artists = changed(&song.release_artists.join(", "), &item.album_artist);
+            let artists = changed(&song.artists.join(", "), &item.artist);
+            let release = changed(&song.release, &item.album);
+            let title = changed(&song.title, &item.title);
+            if !(release_artists || artists || release || title) {
+                continue;
+            }
+
+            util::update_tag(
+                &mut self.song_operations,
+                &self.retag_excluded,
+                song,
+                "beets import",
+                |u| {
+                    if release_artists {
+                        u.release_artists = Value::Update(vec![item.album_artist.clone()]);
+                    }
+                    if artists {
+                        u.artists = Value::Update(vec![item.artist.clone()]);
+                    }
+                    if release {
+                        u.release = Value::Update(item.album.clone());
+                    }
+                    if title {
+                        u.title = Value::Update(item.title.clone());
+                    }
+                },
+            );
+        }
+    }
+}
+
+/// Beets stores paths as blobs of the raw bytes.
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> &[u8] {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> &[u8] {
+    path.to_str().unwrap_or_default().as_bytes()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{song, SongTags};
+    use crate::MusicIndex;
+
+    #[test]
+    fn imports_canonical_names() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/a.mp3", "Bar", "Foo").with_title("One"),
+            song("/music/b.mp3", "bar", "foo").with_title("Two"),
+            song("/music/c.mp3", "Qux", "baz").with_title("Three"),
+        ];
+        let item = |path: &str, title: &str| BeetsItem {
+            path: PathBuf::from(path),
+            artist: "Foo".into(),
+            album_artist: "Foo".into(),
+            album: "Bar".into(),
+            title: title.into(),
+        };
+        let items = [item("/music/a.mp3", "One"), item("/music/b.mp3", "")];
+
+        let mut checks = Checks::from(&index);
+        checks.import_beets(&items);
+        assert_eq!(checks.song_operations.len(), 1);
+        let op = &checks.song_operations[0];
+        let update = op.tag_update.as_ref().unwrap();
+        assert_eq!(op.song.path, Path::new("/music/b.mp3"));
+        assert_eq!(update.release_artists, Value::Update(vec!["Foo".to_owned()]));
+        assert_eq!(update.release, Value::Update("Bar".to_owned()));
+        assert_eq!(update.title, Value::Unchanged);
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use tracing::{debug, info_span};
+
+use crate::changes::release_order;
+use crate::{Changes, Checks, MusicIndex, Song, SongOperation, TagUpdate};
+
+/// The dir inside of a release dir that bonus tracks are placed in, see
+/// [`ChangeOptions::bonus_dir`](crate::ChangeOptions::bonus_dir).
+pub const DEFAULT_BONUS_DIR: &str = "Extras";
+
+/// Mark bonus tracks when found in a bracketed suffix of the title, e.g. `Song (Bonus Track)`.
+const BONUS_MARKERS: [&str; 3] = ["bonus", "instrumental", "hidden track"];
+
+/// Whether the title ends with a bracketed suffix containing one of the [`BONUS_MARKERS`].
+fn has_bonus_marker(title: &str) -> bool {
+    let title = title.trim().to_lowercase();
+    let suffix = match title.chars().last() {
+        Some(')') => title.rfind('(').map(|i| &title[i..]),
+        Some(']') => title.rfind('[').map(|i| &title[i..]),
+        _ => None,
+    };
+    suffix.is_some_and(|s| BONUS_MARKERS.iter().any(|m| s.contains(m)))
+}
+
+/// Whether the song is a bonus track, considering updates queued by checks. Bonus tracks have a
+/// track number beyond the total tracks or a [marker](BONUS_MARKERS) in the title.
+fn is_bonus(song: &Song, tag_update: Option<&TagUpdate>) -> bool {
+    let track = tag_update.and_then(|t| t.track_number.num_value()).or(song.track_number);
+    let total = tag_update.and_then(|t| t.total_tracks.num_value()).or(song.total_tracks);
+    let title = tag_update.and_then(|t| t.title.str_value()).unwrap_or(&song.title);
+    match (track, total.filter(|&t| t != 0)) {
+        (Some(track), Some(total)) if track > total => true,
+        _ => has_bonus_marker(title),
+    }
+}
+
+/// The bonus tracks of a release. None are returned if all songs look like bonus tracks, e.g.
+/// for an album of instrumentals.
+fn bonus_songs<'a>(songs: impl IntoIterator<Item = (&'a Song, bool)>) -> Vec<&'a Song> {
+    let (bonus, main): (Vec<_>, Vec<_>) = songs.into_iter().partition(|(_, b)| *b);
+    match main.is_empty() {
+        true => Vec::new(),
+        false => bonus.into_iter().map(|(s, _)| s).collect(),
+    }
+}
+
+impl Checks<'_> {
+    /// Checks for bonus tracks, instrumentals and hidden tracks, which have a track number beyond
+    /// the total tracks or a suffix like `(Bonus Track)` in their title. Only flags them, they
+    /// are placed in their own dir if a [bonus dir](crate::ChangeOptions::bonus_dir) is set.
+    pub fn check_bonus_tracks(&mut self, f: &mut impl FnMut(&Song)) {
+        let _span = info_span!("check", check = "bonus_tracks").entered();
+        for ar in self.artists.iter() {
+            for rl in ar.releases.iter() {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                let songs = rl.songs.iter().map(|&s| {
+                    let op = self.song_operations.iter().find(|o| o.song.path == s.path);
+                    (s, is_bonus(s, op.and_then(|o| o.tag_update.as_ref())))
+                });
+                for s in bonus_songs(songs) {
+                    debug!(release = %rl.name, title = s.title, "bonus track");
+                    self.stats.bonus_tracks.issues += 1;
+                    f(s);
+                }
+            }
+        }
+    }
+}
+
+impl Changes {
+    /// The bonus tracks of all releases, grouped like the releases of the layout.
+    pub(crate) fn bonus_tracks<'a>(&self, index: &'a MusicIndex) -> HashSet<&'a Path> {
+        let ops: HashMap<&Path, &SongOperation> =
+            self.song_operations.iter().map(|o| (o.song.path.as_path(), o)).collect();
+
+        let mut releases: HashMap<(String, String), Vec<(&Song, bool)>> = HashMap::new();
+        for s in index.songs.iter() {
+            let tag_update = ops.get(s.path.as_path()).and_then(|o| o.tag_update.as_ref());
+            let (artists, release, ..) = release_order(s, tag_update);
+            releases.entry((artists, release)).or_default().push((s, is_bonus(s, tag_update)));
+        }
+        (releases.into_values()).flat_map(bonus_songs).map(|s| s.path.as_path()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{ChangeOptions, LibraryBuilder, MemFs};
+
+    #[test]
+    fn places_bonus_tracks_in_extras_dir() {
+        let titles = ["One", "Two (Bonus Track)", "Three", "Four (Remix) [Instrumental]"];
+        let library = LibraryBuilder::new("/music").release("Foo", "Bar", 4).map(|s| {
+            s.title = titles[s.track_number.unwrap() as usize - 1].to_owned();
+            s.total_tracks = Some(2);
+        });
+        let index = library.index();
+        let mut checks = Checks::from(&index);
+        let mut flagged = Vec::new();
+        checks.check_bonus_tracks(&mut |s| flagged.push(s.title.clone()));
+        flagged.sort();
+        assert_eq!(flagged, ["Four (Remix) [Instrumental]", "Three", "Two (Bonus Track)"]);
+
+        let options = ChangeOptions {
+            bonus_dir: Some(DEFAULT_BONUS_DIR.to_owned()),
+            ..ChangeOptions::from(PathBuf::from("/music"))
+        };
+        let changes = Changes::generate(&MemFs::new(), checks, &options);
+
+        let path = |p: &str| {
+            let op = changes.song_operations.iter().find(|o| o.song.path == Path::new(p));
+            op.and_then(|o| o.new_path.clone()).unwrap()
+        };
+        let one = path("/music/Foo - Bar - 01.mp3");
+        assert_eq!(one, Path::new("/music/Foo/Bar/01 - Foo - One.mp3"));
+        let expected = "/music/Foo/Bar/Extras/03 - Foo - Three.mp3";
+        assert_eq!(path("/music/Foo - Bar - 03.mp3"), Path::new(expected));
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Changes, Fs, Metadata};
+
+/// Parsed metadata of previously indexed files, so unchanged files don't have to be read again.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+    pub metadata: Metadata,
+}
+
+impl IndexCache {
+    /// Loads a cache file, a missing file results in an empty cache.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let content = match std::fs::read(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let entries: Vec<CacheEntry> = serde_json::from_slice(&content)?;
+        let entries = entries.into_iter().map(|e| (e.path.clone(), e)).collect();
+        Ok(Self { entries })
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // non UTF-8 paths can't be represented in JSON
+        let entries: Vec<&CacheEntry> =
+            self.entries.values().filter(|e| e.path.to_str().is_some()).collect();
+        let content = serde_json::to_vec(&entries)?;
+        std::fs::write(path, content)
+    }
+
+    /// Returns the cached metadata if the file wasn't modified since it was cached.
+    pub fn get(&self, path: &Path, size: u64, modified: SystemTime) -> Option<&Metadata> {
+        let entry = self.entries.get(path)?;
+        (entry.size == size && entry.modified == modified).then_some(&entry.metadata)
+    }
+
+    pub fn entry(&self, path: &Path) -> Option<&CacheEntry> {
+        self.entries.get(path)
+    }
+
+    pub fn insert(&mut self, entry: CacheEntry) {
+        self.entries.insert(entry.path.clone(), entry);
+    }
+
+    /// Moves the entries of moved songs to their new paths, so re-running over an organized
+    /// library doesn't read their metadata again. Only songs that are at their new path and no
+    /// longer at the old one are updated, entries of retagged songs are invalidated by their
+    /// modification time. Returns the number of updated entries.
+    pub fn update_paths(&mut self, fs: &impl Fs, changes: &Changes) -> usize {
+        let mut updated = 0;
+        for o in changes.song_operations.iter() {
+            let Some(new_path) = &o.new_path else { continue };
+            if !fs.exists(new_path) || fs.exists(&o.song.path) {
+                continue;
+            }
+            let Some(mut entry) = self.entries.remove(&o.song.path) else { continue };
+            entry.path = new_path.clone();
+            self.entries.insert(new_path.clone(), entry);
+            updated += 1;
+        }
+        updated
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,2166 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsString;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::fs::check_unchanged;
+use crate::{
+    cover, dedup, romanize, util, ArtistSeparators, ChangedOnDisk, Checks, CoverName, DirCreation,
+    ExecOptions, ExtraKind, FileOpType, FileOperation, Fs, Layout, Mode, MusicIndex,
+    NonLatinArtists, Owner, PlaylistCreation, PlaylistUpdate, Quarantine, Song, SongOperation,
+    TagUpdate, Value, DEFAULT_AUDIOBOOK_LAYOUT, DEFAULT_VIDEO_LAYOUT, EXTRA_FIELD_PREFIX,
+};
+
+/// Subdirectories of a release that are moved with its songs, compared case insensitively.
+const ASSET_DIR_NAMES: [&str; 3] = ["scans", "covers", "artwork"];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChangeOptions {
+    pub output_dir: PathBuf,
+    /// Mode applied to newly created directories, if none is set the process umask is used.
+    pub dir_mode: Option<Mode>,
+    /// Owner applied to newly created directories, e.g. the user of a media server.
+    pub dir_owner: Option<Owner>,
+    pub layout: Layout,
+    /// Path template of music videos, defaults to the [`DEFAULT_VIDEO_LAYOUT`].
+    pub video_layout: Option<Layout>,
+    /// Path template of audiobooks, defaults to the
+    /// [`DEFAULT_AUDIOBOOK_LAYOUT`](crate::DEFAULT_AUDIOBOOK_LAYOUT).
+    pub audiobook_layout: Option<Layout>,
+    /// Only apply the tag and mode updates of the checks, leaving all files where they are.
+    pub tag_only: bool,
+    /// Rewrite the entries of playlists that reference moved files. Only useful when moving,
+    /// since copied files stay where the playlists expect them.
+    pub update_playlists: bool,
+    /// Create an m3u playlist of each release inside of its directory.
+    pub release_playlists: bool,
+    /// Create an m3u playlist of all releases of each set of release artists.
+    pub artist_playlists: bool,
+    /// Joins multiple artists in directory and file names.
+    pub artist_separators: ArtistSeparators,
+    pub image_policy: ImagePolicy,
+    /// Covers of moved or copied songs are renamed to the name a media server prefers.
+    pub cover_name: CoverName,
+    pub extra_policies: ExtraPolicies,
+    pub non_latin_artists: NonLatinArtists,
+    pub collaborations: Collaborations,
+    /// Set corrupt unknown songs and files that would overwrite another file aside in the
+    /// [`QUARANTINE_DIR_NAME`](crate::QUARANTINE_DIR_NAME) dir, instead of organizing them.
+    pub quarantine: bool,
+    /// Place bonus tracks in this dir inside of their release dir, e.g. the
+    /// [`DEFAULT_BONUS_DIR`](crate::DEFAULT_BONUS_DIR), keeping the release dir clean.
+    pub bonus_dir: Option<String>,
+    /// Lowercase the extension of renamed songs, videos and audiobooks, e.g. `.MP3` to `.mp3`.
+    pub lowercase_extensions: bool,
+    /// Adjusts the dirs and file names of songs after the layout was rendered.
+    #[cfg(feature = "scripting")]
+    pub script: Option<std::sync::Arc<crate::Script>>,
+}
+
+/// What happens to extra files, like documents, videos or archives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExtraPolicy {
+    /// Extra files are moved or copied along with the songs of their directory.
+    #[default]
+    Follow,
+    /// Extra files are left where they are.
+    Leave,
+    /// Extra files are collected in the `extras` dir of the output dir, keeping their path
+    /// relative to the music dir.
+    Collect,
+}
+
+/// The [`ExtraPolicy`] of each [`ExtraKind`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExtraPolicies {
+    pub documents: ExtraPolicy,
+    pub videos: ExtraPolicy,
+    pub archives: ExtraPolicy,
+}
+
+impl ExtraPolicies {
+    pub fn get(&self, kind: ExtraKind) -> ExtraPolicy {
+        match kind {
+            ExtraKind::Document => self.documents,
+            ExtraKind::Video => self.videos,
+            ExtraKind::Archive => self.archives,
+        }
+    }
+
+    pub fn set(&mut self, kind: ExtraKind, policy: ExtraPolicy) {
+        match kind {
+            ExtraKind::Document => self.documents = policy,
+            ExtraKind::Video => self.videos = policy,
+            ExtraKind::Archive => self.archives = policy,
+        }
+    }
+}
+
+/// Where releases of multiple release artists, like `A & B`, are filed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Collaborations {
+    /// In a joint dir of all release artists.
+    #[default]
+    Joint,
+    /// In the dir of the first release artist and hard linked into the dirs of the others, so
+    /// the release shows up under each of them. Links that split from the song, e.g. because
+    /// another program retagged them, are replaced if they have the same audio data or tags,
+    /// other files at the path of a link are reported as collisions.
+    Linked,
+    /// In the dir of the first release artist, with the others noted in the release name, e.g.
+    /// `Release (with B)`.
+    FirstArtist,
+}
+
+/// What happens to images next to songs and to directories of scans or artwork.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImagePolicy {
+    /// Images are moved or copied along with the songs of their directory.
+    #[default]
+    Follow,
+    /// Images are copied to the new directory of their songs, even if the songs are moved.
+    Copy,
+    /// Images are left where they are.
+    Leave,
+    /// Images are left where they are and deleted with their otherwise empty directory, see
+    /// [`Cleanup::remove_images`](crate::Cleanup::remove_images).
+    Delete,
+}
+
+impl From<PathBuf> for ChangeOptions {
+    fn from(output_dir: PathBuf) -> Self {
+        Self { output_dir, ..Default::default() }
+    }
+}
+
+/// The planned operations, which own all their data so the index can be dropped afterwards.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Changes {
+    pub dir_creations: Vec<DirCreation>,
+    pub song_operations: Vec<SongOperation>,
+    pub file_operations: Vec<FileOperation>,
+    #[serde(default)]
+    pub playlist_updates: Vec<PlaylistUpdate>,
+    #[serde(default)]
+    pub playlist_creations: Vec<PlaylistCreation>,
+    #[serde(default)]
+    pub quarantines: Vec<Quarantine>,
+    /// The modification times of the songs and files when they were indexed. Song and file
+    /// operations are skipped if their file was modified since, disappeared or their
+    /// destination was taken, see [`ChangedOnDisk`].
+    #[serde(default)]
+    pub modified: BTreeMap<PathBuf, SystemTime>,
+}
+
+impl Changes {
+    pub fn generate(fs: &impl Fs, checks: Checks, options: &ChangeOptions) -> Self {
+        let mut new = Changes {
+            dir_creations: Vec::new(),
+            song_operations: checks.song_operations,
+            file_operations: Vec::new(),
+            playlist_updates: Vec::new(),
+            playlist_creations: Vec::new(),
+            quarantines: Vec::new(),
+            modified: BTreeMap::new(),
+        };
+        if !options.tag_only {
+            new.generate_diff(fs, checks.index, options);
+            if options.quarantine {
+                new.quarantine_corrupt(fs, checks.index, options);
+                new.quarantine_collisions(fs, checks.index, options);
+            }
+        }
+        if options.update_playlists {
+            new.generate_playlist_updates(fs, checks.index);
+        }
+        if options.release_playlists || options.artist_playlists {
+            new.generate_playlist_creations(fs, checks.index, options);
+        }
+        let paths = (new.song_operations.iter().map(|o| &o.song.path))
+            .chain(new.file_operations.iter().map(|o| &o.old_path));
+        new.modified =
+            paths.filter_map(|p| Some((p.clone(), *checks.index.modified.get(p)?))).collect();
+        new.sort();
+        new
+    }
+
+    /// Sorts the operations, so they don't depend on the order songs were indexed in. Song
+    /// operations are sorted like [`Changes::group_song_operations`], then by path, all other
+    /// operations by path, which creates parent dirs before their children.
+    pub fn sort(&mut self) {
+        self.dir_creations.sort_by(|a, b| a.path.cmp(&b.path));
+        self.song_operations.sort_by_cached_key(|o| {
+            (release_order(&o.song, o.tag_update.as_ref()), o.song.path.clone())
+        });
+        self.file_operations.sort_by(|a, b| a.old_path.cmp(&b.old_path));
+        self.playlist_updates.sort_by(|a, b| a.path.cmp(&b.path));
+        self.playlist_creations.sort_by(|a, b| a.path.cmp(&b.path));
+        self.quarantines.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+}
+
+/// Identifies an operation of [`Changes`] by the path it operates on, for song and file
+/// operations this is the old path. Ids stay the same when the changes are generated again or
+/// serialized, so a subset of them can be [selected](Changes::select) and executed later.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "path", rename_all = "snake_case")]
+pub enum OperationId {
+    DirCreation(PathBuf),
+    SongOperation(PathBuf),
+    FileOperation(PathBuf),
+    PlaylistUpdate(PathBuf),
+    PlaylistCreation(PathBuf),
+    Quarantine(PathBuf),
+}
+
+impl std::fmt::Display for OperationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (kind, path) = match self {
+            Self::DirCreation(p) => ("dir", p),
+            Self::SongOperation(p) => ("song", p),
+            Self::FileOperation(p) => ("file", p),
+            Self::PlaylistUpdate(p) => ("playlist-update", p),
+            Self::PlaylistCreation(p) => ("playlist-creation", p),
+            Self::Quarantine(p) => ("quarantine", p),
+        };
+        write!(f, "{kind}:{}", path.display())
+    }
+}
+
+impl DirCreation {
+    pub fn id(&self) -> OperationId {
+        OperationId::DirCreation(self.path.clone())
+    }
+}
+
+impl SongOperation {
+    pub fn id(&self) -> OperationId {
+        OperationId::SongOperation(self.song.path.clone())
+    }
+}
+
+impl FileOperation {
+    /// Hard links are identified by their new path, since the links of a song share its path.
+    pub fn id(&self) -> OperationId {
+        match self.op_type {
+            Some(FileOpType::HardLink) => OperationId::FileOperation(self.new_path.clone()),
+            _ => OperationId::FileOperation(self.old_path.clone()),
+        }
+    }
+}
+
+impl PlaylistUpdate {
+    pub fn id(&self) -> OperationId {
+        OperationId::PlaylistUpdate(self.path.clone())
+    }
+}
+
+impl PlaylistCreation {
+    pub fn id(&self) -> OperationId {
+        OperationId::PlaylistCreation(self.path.clone())
+    }
+}
+
+impl Quarantine {
+    pub fn id(&self) -> OperationId {
+        OperationId::Quarantine(self.path.clone())
+    }
+}
+
+/// The song operations of one release, see [`Changes::group_song_operations`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReleaseChanges<'a> {
+    pub name: String,
+    pub song_operations: Vec<&'a SongOperation>,
+}
+
+/// The song operations of one set of release artists, see [`Changes::group_song_operations`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArtistChanges<'a> {
+    pub names: Vec<String>,
+    pub releases: Vec<ReleaseChanges<'a>>,
+}
+
+impl ArtistChanges<'_> {
+    pub fn num_song_operations(&self) -> usize {
+        self.releases.iter().map(|r| r.song_operations.len()).sum()
+    }
+}
+
+impl Changes {
+    /// Groups the song operations by release artists and release, using the values after the tag
+    /// updates. Artists and releases are sorted case insensitively, songs by disc and track number.
+    pub fn group_song_operations(&self) -> Vec<ArtistChanges<'_>> {
+        let mut ops: Vec<&SongOperation> = self.song_operations.iter().collect();
+        ops.sort_by_cached_key(|o| release_order(&o.song, o.tag_update.as_ref()));
+
+        let mut artists: Vec<ArtistChanges> = Vec::new();
+        for o in ops {
+            let t = o.tag_update.as_ref();
+            let names = t
+                .and_then(|t| t.release_artists.slice_value())
+                .unwrap_or(&o.song.release_artists)
+                .to_vec();
+            let release =
+                t.and_then(|t| t.release.str_value()).unwrap_or(&o.song.release).to_owned();
+
+            let artist = match artists.last_mut() {
+                Some(a) if a.names == names => a,
+                _ => {
+                    artists.push(ArtistChanges { names, releases: Vec::new() });
+                    artists.last_mut().unwrap()
+                }
+            };
+            match artist.releases.last_mut() {
+                Some(r) if r.name == release => r.song_operations.push(o),
+                _ => {
+                    artist.releases.push(ReleaseChanges { name: release, song_operations: vec![o] })
+                }
+            }
+        }
+
+        artists
+    }
+}
+
+/// Whether the file at `link` is still a link of the song, or a copy that split from it, e.g.
+/// because another program retagged it. Copies have the same audio data or the same tags.
+fn is_stale_link(fs: &impl Fs, index: &MusicIndex, song: &Song, link: &Path) -> bool {
+    let tags = |s: &Song| Song {
+        path: PathBuf::new(),
+        mode: None,
+        audio: Default::default(),
+        ..s.clone()
+    };
+    let same_audio = || match (dedup::hash_audio(fs, &song.path), dedup::hash_audio(fs, link)) {
+        (Ok((a, _)), Ok((b, _))) => a == b,
+        _ => false,
+    };
+    fs.same_file(&song.path, link)
+        || index.songs.iter().any(|s| s.path == link && tags(s) == tags(song))
+        || same_audio()
+}
+
+/// The extension of a renamed file, lowercased if [`ChangeOptions::lowercase_extensions`] is set.
+fn file_extension(path: &Path, options: &ChangeOptions) -> OsString {
+    let ext = path.extension().unwrap_or_default();
+    match options.lowercase_extensions {
+        true => ext.to_ascii_lowercase(),
+        false => ext.to_owned(),
+    }
+}
+
+/// Orders songs by release artists and release case insensitively, then by disc and track number,
+/// considering pending tag updates.
+pub(crate) fn release_order(
+    song: &Song,
+    tag_update: Option<&TagUpdate>,
+) -> (String, String, u16, u16) {
+    let join = ", ";
+    let field = |name| layout_field(song, tag_update, name, join).unwrap_or_default();
+    let number = |name| layout_field(song, tag_update, name, join).and_then(|n| n.parse().ok());
+    (
+        field("release_artists").to_lowercase(),
+        field("release").to_lowercase(),
+        number("disc").unwrap_or(0),
+        number("track").unwrap_or(0),
+    )
+}
+
+/// The value of a layout field, considering pending tag updates. Multiple artists are joined
+/// with `artist_join`.
+pub(crate) fn layout_field(
+    song: &Song,
+    tag_update: Option<&TagUpdate>,
+    name: &str,
+    artist_join: &str,
+) -> Option<String> {
+    let non_empty = |s: String| (!s.is_empty()).then_some(s);
+
+    match name {
+        "release_artists" => non_empty(
+            tag_update
+                .and_then(|t| t.release_artists.slice_value())
+                .unwrap_or(&song.release_artists)
+                .join(artist_join),
+        ),
+        "artists" => non_empty(
+            tag_update
+                .and_then(|t| t.artists.slice_value())
+                .unwrap_or(&song.artists)
+                .join(artist_join),
+        ),
+        // the group of a release is only known in the layout of songs, see
+        // `Changes::release_groups`
+        "release" | "release_title" => non_empty(
+            tag_update.and_then(|t| t.release.str_value()).unwrap_or(&song.release).to_owned(),
+        ),
+        "title" => non_empty(
+            tag_update.and_then(|t| t.title.str_value()).unwrap_or(&song.title).to_owned(),
+        ),
+        "track" => {
+            let track = tag_update.and_then(|t| t.track_number.num_value()).or(song.track_number);
+            Some(track.unwrap_or(0).to_string())
+        }
+        "total_tracks" => tag_update
+            .and_then(|t| t.total_tracks.num_value())
+            .or(song.total_tracks)
+            .map(|n| n.to_string()),
+        "disc" => {
+            let disc = tag_update.and_then(|t| t.disc_number.num_value()).or(song.disc_number);
+            let total_discs =
+                tag_update.and_then(|t| t.total_discs.num_value()).or(song.total_discs);
+            // only releases with multiple discs need the disc number to keep tracks apart
+            (total_discs.unwrap_or(0) > 1).then(|| disc.unwrap_or(0).to_string())
+        }
+        "total_discs" => tag_update
+            .and_then(|t| t.total_discs.num_value())
+            .or(song.total_discs)
+            .map(|n| n.to_string()),
+        "codec" => song.audio.codec.map(|c| c.to_string()),
+        "bitrate" => song.audio.bitrate.map(|b| b.to_string()),
+        "sample_rate" => song.audio.sample_rate.map(|s| s.to_string()),
+        "duration" => song.audio.duration.map(|d| {
+            let secs = d.as_secs();
+            format!("{}m{:02}s", secs / 60, secs % 60)
+        }),
+        "quality" => song.audio.codec.map(|c| match c.is_lossless() {
+            true => "lossless".to_owned(),
+            false => "lossy".to_owned(),
+        }),
+        _ => {
+            let key = name.strip_prefix(EXTRA_FIELD_PREFIX)?.to_uppercase();
+            let value = tag_update
+                .and_then(|t| t.extra.get(&key))
+                .and_then(Value::str_value)
+                .or_else(|| song.extra.get(&key).map(String::as_str));
+            value.map(str::to_owned).and_then(non_empty)
+        }
+    }
+}
+
+impl Changes {
+    pub(crate) fn new_song_path<'a>(&'a self, song: &'a Song) -> &'a Path {
+        if let Some(o) = self.song_operations.iter().find(|o| o.song.path == song.path) {
+            if let Some(p) = &o.new_path {
+                return p;
+            }
+        }
+
+        &song.path
+    }
+
+    /// The new directory of all songs inside of `dir`, if they are moved to the same one. Bonus
+    /// tracks placed in their own dir are ignored.
+    fn new_song_dir(
+        &self,
+        index: &MusicIndex,
+        dir: &Path,
+        bonus_tracks: &HashSet<&Path>,
+    ) -> Option<PathBuf> {
+        let mut new_song_dirs = index
+            .songs
+            .iter()
+            .filter(|s| s.path.parent().unwrap() == dir && !bonus_tracks.contains(s.path.as_path()))
+            .map(|s| self.new_song_path(s).parent().unwrap());
+
+        let new_song_dir = new_song_dirs.next()?;
+        if new_song_dir == dir || new_song_dirs.any(|n| n != new_song_dir) {
+            return None;
+        }
+        Some(new_song_dir.to_owned())
+    }
+
+    /// Whether `dir` contains songs and none of them are moved.
+    fn songs_stay(&self, index: &MusicIndex, dir: &Path) -> bool {
+        let mut songs = index.songs.iter().filter(|s| s.path.parent() == Some(dir)).peekable();
+        songs.peek().is_some() && songs.all(|s| self.new_song_path(s).parent() == Some(dir))
+    }
+
+    /// The new artist directory of `dir`, which contains no songs itself but release directories,
+    /// if all of their songs are moved into release directories inside of the same directory.
+    /// Bonus tracks placed in their own dir are ignored.
+    fn new_artist_dir(
+        &self,
+        index: &MusicIndex,
+        dir: &Path,
+        output_dir: &Path,
+        bonus_tracks: &HashSet<&Path>,
+    ) -> Option<PathBuf> {
+        if index.songs.iter().any(|s| s.path.parent() == Some(dir)) {
+            return None;
+        }
+        let mut new_artist_dirs = index
+            .songs
+            .iter()
+            .filter(|s| s.path.parent().and_then(Path::parent) == Some(dir))
+            .filter(|s| !bonus_tracks.contains(s.path.as_path()))
+            .map(|s| self.new_song_path(s).parent().and_then(Path::parent));
+
+        let new_artist_dir = new_artist_dirs.next()??;
+        if new_artist_dir == dir
+            || !new_artist_dir.starts_with(output_dir)
+            || new_artist_dir == output_dir
+            || new_artist_dirs.any(|n| n != Some(new_artist_dir))
+        {
+            return None;
+        }
+        Some(new_artist_dir.to_owned())
+    }
+
+    pub(crate) fn dir_creation(
+        &mut self,
+        fs: &impl Fs,
+        options: &ChangeOptions,
+        path: &Path,
+    ) -> bool {
+        if !self.dir_creations.iter().any(|d| d.path == path) && !fs.exists(path) {
+            self.dir_creations.push(DirCreation {
+                path: path.to_owned(),
+                mode: options.dir_mode,
+                owner: options.dir_owner,
+            });
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Plans the creation of the missing dirs of `relative` inside of `parent` and returns the
+    /// full path. Existing or planned dirs whose name only differs by case are reused, so an
+    /// artist isn't split into `Radiohead` and `radiohead`, which case insensitive file systems
+    /// can't even represent. `listings` caches the subdirs of already visited dirs.
+    fn create_dirs(
+        &mut self,
+        fs: &impl Fs,
+        options: &ChangeOptions,
+        listings: &mut HashMap<PathBuf, Vec<PathBuf>>,
+        parent: &Path,
+        relative: &Path,
+    ) -> PathBuf {
+        let mut path = parent.to_path_buf();
+        for d in relative.components() {
+            let name = d.as_os_str().to_string_lossy().to_lowercase();
+            let exact = path.join(d);
+            let listing = listings.entry(path.clone()).or_insert_with(|| {
+                let entries = fs.read_dir(&path).unwrap_or_default();
+                entries.into_iter().filter(|p| fs.is_dir(p)).collect()
+            });
+            let planned = (self.dir_creations.iter().map(|c| &c.path))
+                .filter(|p| p.parent() == Some(path.as_path()));
+            let mut dirs = listing.iter().chain(planned);
+            let variant = match dirs.clone().any(|p| *p == exact) {
+                true => None,
+                false => dirs.find(|p| {
+                    p.file_name().is_some_and(|n| n.to_string_lossy().to_lowercase() == name)
+                }),
+            };
+            match variant {
+                Some(variant) => {
+                    debug!(dir = %exact.display(), existing = %variant.display(), "case variant");
+                    path = variant.clone();
+                }
+                None => {
+                    path = exact;
+                    self.dir_creation(fs, options, &path);
+                }
+            }
+        }
+        path
+    }
+
+    /// Suffixes of the release names of songs, which keep same-named releases of the same
+    /// artists apart, e.g. an original and its remaster. Songs are only split up if their disc
+    /// and track numbers collide, by year if the years differ and otherwise by their total
+    /// tracks.
+    fn release_suffixes<'a>(&self, index: &'a MusicIndex) -> HashMap<&'a Path, String> {
+        let ops: HashMap<&Path, &SongOperation> =
+            self.song_operations.iter().map(|o| (o.song.path.as_path(), o)).collect();
+        let mut songs: Vec<_> = (index.songs.iter())
+            .map(|s| {
+                let tag_update = ops.get(s.path.as_path()).and_then(|o| o.tag_update.as_ref());
+                (release_order(s, tag_update), s)
+            })
+            .collect();
+        songs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let year: fn(&Song) -> Option<String> = |s| s.year.map(|y| format!(" ({y})"));
+        let total_tracks: fn(&Song) -> Option<String> =
+            |s| s.total_tracks.map(|t| format!(" [{t} tracks]"));
+
+        let mut suffixes = HashMap::new();
+        for release in songs.chunk_by(|(a, _), (b, _)| (&a.0, &a.1) == (&b.0, &b.1)) {
+            // sorted by disc and track number, so colliding numbers are next to each other
+            let collide = release.windows(2).any(|w| {
+                let ((a, _), (b, _)) = (&w[0], &w[1]);
+                a == b && a.3 != 0
+            });
+            if !collide {
+                continue;
+            }
+            let distinct = |f: &fn(&Song) -> Option<String>| {
+                release.iter().filter_map(|(_, s)| f(s)).collect::<HashSet<_>>().len() > 1
+            };
+            let Some(suffix) = [year, total_tracks].into_iter().find(distinct) else { continue };
+            for (_, s) in release {
+                if let Some(suffix) = suffix(s) {
+                    suffixes.insert(s.path.as_path(), suffix);
+                }
+            }
+        }
+        suffixes
+    }
+
+    fn generate_diff(&mut self, fs: &impl Fs, index: &MusicIndex, options: &ChangeOptions) {
+        let output_dir = options.output_dir.as_path();
+        self.dir_creation(fs, options, output_dir);
+        let release_suffixes = self.release_suffixes(index);
+        let release_groups = self.release_groups(index);
+        let bonus_tracks = match options.bonus_dir {
+            Some(_) => self.bonus_tracks(index),
+            None => HashSet::new(),
+        };
+        let mut listings = HashMap::new();
+
+        for song in index.songs.iter() {
+            let op = self.song_operations.iter().find(|o| o.song.path == song.path);
+            let tag_update = op.and_then(|op| op.tag_update.as_ref());
+            let retagged = tag_update.is_some();
+            let release_group = release_groups.get(song.path.as_path());
+            let release_artists: &[String] = tag_update
+                .and_then(|t| t.release_artists.slice_value())
+                .unwrap_or(&song.release_artists);
+            let (first_artist, other_artists) = match (options.collaborations, release_artists) {
+                (Collaborations::Joint, _) | (_, [] | [_]) => (None, &[][..]),
+                (_, [first, others @ ..]) => (Some(first.as_str()), others),
+            };
+            let collaboration = match options.collaborations {
+                Collaborations::FirstArtist if first_artist.is_some() => Some(format!(
+                    " (with {})",
+                    other_artists.join(&options.artist_separators.name_join)
+                )),
+                _ => None,
+            };
+            let release_suffix =
+                [release_suffixes.get(song.path.as_path()).cloned(), collaboration]
+                    .into_iter()
+                    .flatten()
+                    .reduce(|a, b| a + b.as_str());
+            let release_suffix = release_suffix.as_ref();
+
+            let render = |romanized: bool, artist: Option<&str>| {
+                options.layout.render(|name| {
+                    let value = match (name, release_group) {
+                        ("release_artists", _) if artist.is_some() => artist.map(str::to_owned),
+                        ("release_group", group) => group.map(|g| g.name.clone()),
+                        ("release_title", Some(group)) => Some(group.title.clone()),
+                        _ => layout_field(
+                            song,
+                            tag_update,
+                            name,
+                            &options.artist_separators.name_join,
+                        ),
+                    };
+                    match (name, release_suffix) {
+                        ("release" | "release_title", Some(suffix)) => {
+                            value.map(|v| v + suffix.as_str())
+                        }
+                        ("release_artists" | "artists", _) if romanized => {
+                            value.map(|v| romanize::romanize(&v).unwrap_or(v))
+                        }
+                        _ => value,
+                    }
+                })
+            };
+            // the dirs and file name of the song inside of the dir of the artist, or the joint dir
+            let place = |artist: Option<&str>| {
+                let relative = render(false, artist);
+                let dirs = relative.parent().unwrap_or(Path::new(""));
+                // only the dirs are romanized, the file name keeps the original artists
+                let release_artists = || match artist {
+                    Some(a) => a.to_owned(),
+                    None => {
+                        layout_field(song, tag_update, "release_artists", "").unwrap_or_default()
+                    }
+                };
+                let dirs = match &options.non_latin_artists {
+                    NonLatinArtists::Keep => dirs.to_owned(),
+                    NonLatinArtists::Romanize { bucket } => {
+                        match romanize::romanize(&release_artists()) {
+                            Some(_) => {
+                                render(true, artist).parent().unwrap_or(Path::new("")).to_owned()
+                            }
+                            None => Path::new(bucket).join(dirs),
+                        }
+                    }
+                    NonLatinArtists::Group { bucket } => {
+                        match romanize::has_non_latin(&release_artists()) {
+                            true => Path::new(bucket).join(dirs),
+                            false => dirs.to_owned(),
+                        }
+                    }
+                };
+                let dirs = match &options.bonus_dir {
+                    Some(bonus_dir) if bonus_tracks.contains(song.path.as_path()) => {
+                        dirs.join(bonus_dir)
+                    }
+                    _ => dirs,
+                };
+                let file_name = relative.file_name().unwrap_or_default().to_owned();
+                #[cfg(feature = "scripting")]
+                let (dirs, file_name) = match &options.script {
+                    Some(script) => {
+                        let mut song = song.clone();
+                        if let Some(u) = tag_update {
+                            u.apply(&mut song);
+                        }
+                        script.path(&song, &dirs, &file_name).unwrap_or((dirs, file_name))
+                    }
+                    None => (dirs, file_name),
+                };
+                let mut file_name = file_name;
+                file_name.push(".");
+                file_name.push(file_extension(&song.path, options));
+                (dirs, file_name)
+            };
+            let places: Vec<_> = match options.collaborations {
+                Collaborations::Linked => [first_artist]
+                    .into_iter()
+                    .chain(other_artists.iter().map(|a| Some(a.as_str())))
+                    .map(place)
+                    .collect(),
+                _ => vec![place(first_artist)],
+            };
+
+            let mut paths = places.into_iter().map(|(dirs, file_name)| {
+                self.create_dirs(fs, options, &mut listings, output_dir, &dirs).join(file_name)
+            });
+            let path = paths.next().unwrap();
+            let links: Vec<PathBuf> = paths.filter(|l| *l != path).collect();
+            if links.contains(&song.path) {
+                debug!(path = %song.path.display(), "link of a collaboration");
+                continue;
+            }
+            // retagging splits the song from its links, so they are re-created afterwards
+            for link in links.into_iter().filter(|l| retagged || !fs.same_file(&song.path, l)) {
+                // other files are never overwritten, the link is reported as a collision instead
+                let taken = fs.exists(&link);
+                let replace = taken && is_stale_link(fs, index, song, &link);
+                if replace {
+                    debug!(link = %link.display(), "replacing stale link");
+                } else if taken {
+                    debug!(link = %link.display(), "link is taken by another file");
+                }
+                self.file_operations.push(FileOperation {
+                    old_path: path.clone(),
+                    new_path: link,
+                    op_type: Some(FileOpType::HardLink),
+                    replace,
+                });
+            }
+
+            if path != song.path {
+                util::update_song_op(&mut self.song_operations, song, |fo| {
+                    fo.new_path = Some(path)
+                });
+            }
+        }
+
+        let moves_images = matches!(options.image_policy, ImagePolicy::Follow | ImagePolicy::Copy);
+        let image_op_type = (options.image_policy == ImagePolicy::Copy).then_some(FileOpType::Copy);
+        let images = index.images.iter().filter(|_| moves_images).map(|i| (i, image_op_type));
+        let companions = index.companions.iter().map(|c| (c, None));
+
+        // images and companion files follow the songs of their directory, covers are renamed
+        // even if the songs stay
+        let cover_renames = cover::cover_renames(index, options.cover_name);
+        for (file, op_type) in images.chain(companions) {
+            let current_dir = file.parent().unwrap();
+            let cover_name = cover_renames.get(file.as_path());
+            let new_song_dir = match self.new_song_dir(index, current_dir, &bonus_tracks) {
+                Some(dir) => Some(dir),
+                None if cover_name.is_some() && self.songs_stay(index, current_dir) => {
+                    Some(current_dir.to_owned())
+                }
+                None => None,
+            };
+            if let Some(new_song_dir) = new_song_dir {
+                let file_name = cover_name.map_or(file.file_name().unwrap(), OsString::as_os_str);
+                let new_path = new_song_dir.join(file_name);
+                self.file_operations.push(FileOperation {
+                    old_path: file.clone(),
+                    new_path,
+                    op_type,
+                    replace: false,
+                });
+            }
+        }
+
+        let video_layout =
+            (options.video_layout.clone()).unwrap_or_else(|| DEFAULT_VIDEO_LAYOUT.parse().unwrap());
+        for video in index.videos.iter() {
+            let relative = video_layout.render(|name| match name {
+                "artists" | "release_artists" => {
+                    Some(video.artists.join(&options.artist_separators.name_join))
+                }
+                "title" => Some(video.title.clone()),
+                "year" => video.year.map(|y| y.to_string()),
+                "genre" => video.genre.clone(),
+                _ => None,
+            });
+            let dirs = relative.parent().unwrap_or(Path::new(""));
+            let mut path = self.create_dirs(fs, options, &mut listings, output_dir, dirs);
+
+            let mut file_name = relative.file_name().unwrap_or_default().to_owned();
+            file_name.push(".");
+            file_name.push(file_extension(&video.path, options));
+            path.push(file_name);
+
+            if path != video.path {
+                self.file_operations.push(FileOperation {
+                    old_path: video.path.clone(),
+                    new_path: path,
+                    op_type: None,
+                    replace: false,
+                });
+            }
+        }
+
+        let audiobook_layout = (options.audiobook_layout.clone())
+            .unwrap_or_else(|| DEFAULT_AUDIOBOOK_LAYOUT.parse().unwrap());
+        for audiobook in index.audiobooks.iter() {
+            let relative = audiobook_layout.render(|name| match name {
+                "release_artists" => {
+                    Some(audiobook.authors.join(&options.artist_separators.name_join))
+                }
+                "artists" => Some(audiobook.artists.join(&options.artist_separators.name_join)),
+                "release" => Some(audiobook.book.clone()),
+                "title" => Some(audiobook.title.clone()),
+                "year" => audiobook.year.map(|y| y.to_string()),
+                "genre" => audiobook.genre.clone(),
+                _ => None,
+            });
+            let dirs = relative.parent().unwrap_or(Path::new(""));
+            let mut path = self.create_dirs(fs, options, &mut listings, output_dir, dirs);
+
+            let mut file_name = relative.file_name().unwrap_or_default().to_owned();
+            file_name.push(".");
+            file_name.push(file_extension(&audiobook.path, options));
+            path.push(file_name);
+
+            if path != audiobook.path {
+                self.file_operations.push(FileOperation {
+                    old_path: audiobook.path.clone(),
+                    new_path: path,
+                    op_type: None,
+                    replace: false,
+                });
+            }
+        }
+
+        // extra files follow the songs of their directory or are collected in the extras dir
+        let extras_dir = output_dir.join("extras");
+        for file in index.extras.iter() {
+            let Some(kind) = file.extension().and_then(ExtraKind::from_extension) else { continue };
+            let new_path = match options.extra_policies.get(kind) {
+                ExtraPolicy::Follow => {
+                    let Some(new_song_dir) =
+                        self.new_song_dir(index, file.parent().unwrap(), &bonus_tracks)
+                    else {
+                        continue;
+                    };
+                    new_song_dir.join(file.file_name().unwrap())
+                }
+                ExtraPolicy::Leave => continue,
+                ExtraPolicy::Collect if file.starts_with(&extras_dir) => continue,
+                ExtraPolicy::Collect => {
+                    let relative = file.strip_prefix(&index.music_dir).unwrap_or(file);
+                    let dirs = Path::new("extras").join(relative.parent().unwrap_or(Path::new("")));
+                    let path = self.create_dirs(fs, options, &mut listings, output_dir, &dirs);
+                    path.join(file.file_name().unwrap())
+                }
+            };
+            self.file_operations.push(FileOperation {
+                old_path: file.clone(),
+                new_path,
+                op_type: None,
+                replace: false,
+            });
+        }
+
+        // images of artist dirs follow the releases inside of them to their new artist dir
+        for image in index.images.iter().filter(|_| moves_images) {
+            let current_dir = image.parent().unwrap();
+            let Some(new_artist_dir) =
+                self.new_artist_dir(index, current_dir, output_dir, &bonus_tracks)
+            else {
+                continue;
+            };
+            let new_path = new_artist_dir.join(image.file_name().unwrap());
+            if fs.exists(&new_path) || self.file_operations.iter().any(|o| o.new_path == new_path) {
+                continue;
+            }
+            self.file_operations.push(FileOperation {
+                old_path: image.clone(),
+                new_path,
+                op_type: image_op_type,
+                replace: false,
+            });
+        }
+
+        // and so do directories of scans or artwork, which are moved as a whole
+        let mut song_dirs: Vec<&Path> =
+            index.songs.iter().filter_map(|s| s.path.parent()).collect();
+        song_dirs.sort();
+        song_dirs.dedup();
+        for dir in song_dirs.into_iter().filter(|_| moves_images) {
+            let Some(new_song_dir) = self.new_song_dir(index, dir, &bonus_tracks) else { continue };
+            for p in fs.read_dir(dir).unwrap_or_default() {
+                let Some(name) = p.file_name() else { continue };
+                let is_asset_dir = ASSET_DIR_NAMES.iter().any(|n| name.eq_ignore_ascii_case(n));
+                let new_path = new_song_dir.join(name);
+                if is_asset_dir && fs.is_dir(&p) && !fs.exists(&new_path) {
+                    self.file_operations.push(FileOperation {
+                        old_path: p,
+                        new_path,
+                        op_type: image_op_type,
+                        replace: false,
+                    });
+                }
+            }
+        }
+
+        if !index.unknown.is_empty() {
+            let unknown_dir = output_dir.join("unknown");
+            self.dir_creation(fs, options, &unknown_dir);
+
+            for unknown in index.unknown.iter() {
+                let new_path = unknown_dir.join(unknown.file_name().unwrap());
+
+                if &new_path != unknown {
+                    self.file_operations.push(FileOperation {
+                        old_path: unknown.clone(),
+                        new_path,
+                        op_type: None,
+                        replace: false,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Only keeps the song operations for which `f` returns true. Operations of images that
+    /// would have followed a removed song into its new release or artist dir, and playlist
+    /// entries referencing a removed song, are removed as well.
+    pub fn retain_song_operations(&mut self, mut f: impl FnMut(&SongOperation) -> bool) {
+        let mut removed_dirs = Vec::new();
+        let mut removed_paths = Vec::new();
+        let mut removed_artist_dirs = Vec::new();
+        let artist_dir = |p: &Path| p.parent().and_then(Path::parent).map(Path::to_owned);
+        self.song_operations.retain(|o| {
+            let keep = f(o);
+            if !keep {
+                removed_dirs.extend(o.song.path.parent().map(Path::to_owned));
+                if let Some(new) = &o.new_path {
+                    removed_paths.push((new.clone(), o.song.path.clone()));
+                    if let (Some(old_dir), Some(new_dir)) =
+                        (artist_dir(&o.song.path), artist_dir(new))
+                    {
+                        removed_artist_dirs.push((old_dir, new_dir));
+                    }
+                }
+            }
+            keep
+        });
+        // songs that aren't moved stay in created playlists, using their current path
+        for c in self.playlist_creations.iter_mut() {
+            for p in c.songs.iter_mut() {
+                if let Some((_, old)) = removed_paths.iter().find(|(new, _)| new == p) {
+                    *p = old.clone();
+                }
+            }
+        }
+        self.file_operations.retain(|o| {
+            let (Some(old_dir), Some(new_dir)) = (o.old_path.parent(), o.new_path.parent()) else {
+                return true;
+            };
+            let follows_release = removed_dirs.iter().any(|d| d == old_dir);
+            let follows_artist =
+                removed_artist_dirs.iter().any(|(old, new)| old == old_dir && new == new_dir);
+            !follows_release && !follows_artist
+        });
+        self.remove_stale_playlist_entries();
+        self.remove_unused_dir_creations();
+    }
+
+    /// Only keeps the file operations for which `f` returns true, see
+    /// [`Self::retain_song_operations`].
+    pub fn retain_file_operations(&mut self, f: impl FnMut(&FileOperation) -> bool) {
+        self.file_operations.retain(f);
+        self.remove_stale_playlist_entries();
+        self.remove_unused_dir_creations();
+    }
+
+    /// The ids of all operations in the order they are executed.
+    pub fn ids(&self) -> Vec<OperationId> {
+        (self.dir_creations.iter().map(DirCreation::id))
+            .chain(self.song_operations.iter().map(SongOperation::id))
+            .chain(self.file_operations.iter().map(FileOperation::id))
+            .chain(self.playlist_updates.iter().map(PlaylistUpdate::id))
+            .chain(self.playlist_creations.iter().map(PlaylistCreation::id))
+            .chain(self.quarantines.iter().map(Quarantine::id))
+            .collect()
+    }
+
+    /// Only the operations with one of the ids, unknown ids are ignored. The dir creations needed
+    /// by the selected operations are kept even if they aren't selected, entries of playlist
+    /// updates referencing songs that aren't selected are removed.
+    pub fn select(&self, ids: &HashSet<OperationId>) -> Self {
+        let mut new = self.clone();
+        new.song_operations.retain(|o| ids.contains(&o.id()));
+        new.file_operations.retain(|o| ids.contains(&o.id()));
+        new.playlist_updates.retain(|u| ids.contains(&u.id()));
+        new.playlist_creations.retain(|c| ids.contains(&c.id()));
+        new.quarantines.retain(|q| ids.contains(&q.id()));
+        new.remove_stale_playlist_entries();
+
+        let song_paths = new.song_operations.iter().filter_map(|o| o.new_path.clone());
+        let file_paths = new.file_operations.iter().map(|o| o.new_path.clone());
+        let playlist_paths = new.playlist_creations.iter().map(|c| c.path.clone());
+        let quarantine_paths = new.quarantines.iter().map(|q| q.new_path.clone());
+        let new_paths: Vec<PathBuf> =
+            (song_paths.chain(file_paths).chain(playlist_paths).chain(quarantine_paths)).collect();
+        new.dir_creations
+            .retain(|d| ids.contains(&d.id()) || new_paths.iter().any(|p| p.starts_with(&d.path)));
+        new
+    }
+
+    /// Removes dir creations that no song or file operation, created playlist or quarantined
+    /// file needs anymore.
+    pub fn remove_unused_dir_creations(&mut self) {
+        let song_paths = self.song_operations.iter().filter_map(|o| o.new_path.as_deref());
+        let file_paths = self.file_operations.iter().map(|o| o.new_path.as_path());
+        let playlist_paths = self.playlist_creations.iter().map(|c| c.path.as_path());
+        let quarantine_paths = self.quarantines.iter().map(|q| q.new_path.as_path());
+        let new_paths: Vec<&Path> =
+            (song_paths.chain(file_paths).chain(playlist_paths).chain(quarantine_paths)).collect();
+        self.dir_creations.retain(|d| new_paths.iter().any(|p| p.starts_with(&d.path)));
+    }
+
+    pub fn execute_dir_creations(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        f: &mut impl FnMut(&DirCreation, std::io::Result<()>),
+    ) {
+        for d in self.dir_creations.iter() {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let r = d.execute(fs);
+            let failed = r.is_err();
+            f(d, r);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    }
+
+    /// Songs moving onto the path of another song are executed after that one was moved away,
+    /// cycles like swaps go through a temporary path. Plans containing such chains are executed
+    /// on a single thread, since their operations depend on each other.
+    pub fn execute_song_operations(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        progress: &mut impl FnMut(&Path, u64, u64),
+        f: &mut impl FnMut(&SongOperation, Result<(), Box<dyn std::error::Error>>),
+    ) {
+        if let (Some(staging_dir), FileOpType::Copy) = (&options.staging_dir, options.op_type) {
+            self.execute_song_operations_staged(fs, options, staging_dir, progress, f);
+            return;
+        }
+        if options.atomic_releases {
+            self.execute_song_operations_by_release(fs, options, progress, f);
+            return;
+        }
+        let moves: Vec<_> = (self.song_operations.iter())
+            .map(|o| Some((o.song.path.as_path(), o.new_path.as_deref()?)))
+            .collect();
+        let blockers = blockers(&moves);
+
+        let threads = options.threads.map_or(1, NonZeroUsize::get);
+        let chained = blockers.iter().any(Option::is_some);
+        if threads > 1 && self.song_operations.len() > 1 && !chained {
+            self.execute_song_operations_concurrently(fs, options, threads, progress, f);
+            return;
+        }
+        execute_ordered(
+            fs,
+            options,
+            &self.song_operations,
+            &moves,
+            &blockers,
+            &mut |o, temp| {
+                let modified = self.modified.get(&o.song.path).copied();
+                check_unchanged(fs, &o.song.path, temp.or(o.new_path.as_deref()), modified)?;
+                let progress = &mut |copied, total| progress(&o.song.path, copied, total);
+                match temp {
+                    Some(temp) => {
+                        let o = SongOperation { new_path: Some(temp.to_owned()), ..o.clone() };
+                        o.execute(fs, options, progress)
+                    }
+                    None => o.execute(fs, options, progress),
+                }
+            },
+            f,
+        );
+    }
+
+    /// Executes the song operations on up to `threads` worker threads and reports the results
+    /// in the order of the operations.
+    fn execute_song_operations_concurrently(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        threads: usize,
+        progress: &mut impl FnMut(&Path, u64, u64),
+        f: &mut impl FnMut(&SongOperation, Result<(), Box<dyn std::error::Error>>),
+    ) {
+        enum Event {
+            Copied(usize, u64, u64),
+            // boxed errors aren't `Send`, only skipped operations keep their error type
+            Done(usize, Result<(), Box<dyn std::error::Error + Send + Sync>>),
+        }
+
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::scope(|s| {
+            for _ in 0..threads.min(self.song_operations.len()) {
+                let sender = sender.clone();
+                let (next, failed) = (&next, &failed);
+                s.spawn(move || loop {
+                    if options.cancel.is_cancelled()
+                        || (options.fail_fast && failed.load(Ordering::Relaxed))
+                    {
+                        break;
+                    }
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(o) = self.song_operations.get(i) else { break };
+                    let modified = self.modified.get(&o.song.path).copied();
+                    let r = match check_unchanged(fs, &o.song.path, o.new_path.as_deref(), modified)
+                    {
+                        Ok(()) => o.execute(fs, options, &mut |copied, total| {
+                            let _ = sender.send(Event::Copied(i, copied, total));
+                        }),
+                        Err(e) => Err(e.into()),
+                    };
+                    if r.as_ref().is_err_and(|e| !e.is::<ChangedOnDisk>()) {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    let r = r.map_err(|e| match e.downcast::<ChangedOnDisk>() {
+                        Ok(c) => c as Box<dyn std::error::Error + Send + Sync>,
+                        Err(e) => e.to_string().into(),
+                    });
+                    let _ = sender.send(Event::Done(i, r));
+                });
+            }
+            drop(sender);
+
+            // every taken operation is done eventually, so the buffer is always flushed
+            let mut done = BTreeMap::new();
+            let mut reported = 0;
+            for event in receiver {
+                match event {
+                    Event::Copied(i, copied, total) => {
+                        progress(&self.song_operations[i].song.path, copied, total)
+                    }
+                    Event::Done(i, r) => {
+                        done.insert(i, r);
+                        while let Some(r) = done.remove(&reported) {
+                            let r = r.map_err(|e| e as Box<dyn std::error::Error>);
+                            f(&self.song_operations[reported], r);
+                            reported += 1;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn execute_file_operations(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        progress: &mut impl FnMut(&Path, u64, u64),
+        f: &mut impl FnMut(&FileOperation, Result<(), Box<dyn std::error::Error>>),
+    ) {
+        let moves: Vec<_> = (self.file_operations.iter())
+            .map(|o| Some((o.old_path.as_path(), o.new_path.as_path())))
+            .collect();
+        execute_ordered(
+            fs,
+            options,
+            &self.file_operations,
+            &moves,
+            &blockers(&moves),
+            &mut |o, temp| {
+                let modified = self.modified.get(&o.old_path).copied();
+                // a replaced link occupies the new path by design
+                let new_path = Some(temp.unwrap_or(&o.new_path)).filter(|_| !o.replace);
+                check_unchanged(fs, &o.old_path, new_path, modified)?;
+                let progress = &mut |copied, total| progress(&o.old_path, copied, total);
+                match temp {
+                    Some(temp) => {
+                        let o = FileOperation { new_path: temp.to_owned(), ..o.clone() };
+                        o.execute(fs, options, progress)
+                    }
+                    None => o.execute(fs, options, progress),
+                }
+            },
+            f,
+        );
+    }
+
+    pub fn execute_playlist_updates(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        f: &mut impl FnMut(&PlaylistUpdate, std::io::Result<()>),
+    ) {
+        for u in self.playlist_updates.iter() {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let r = u.execute(fs);
+            let failed = r.is_err();
+            f(u, r);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    }
+
+    pub fn execute_playlist_creations(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        f: &mut impl FnMut(&PlaylistCreation, std::io::Result<()>),
+    ) {
+        for c in self.playlist_creations.iter() {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let r = c.execute(fs);
+            let failed = r.is_err();
+            f(c, r);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    }
+
+    /// The modification times of the dirs whose songs and files are only renamed, so they can be
+    /// restored after executing, see [`ExecOptions::preserve_dir_mtimes`]. Dirs that songs or
+    /// files are moved into or out of, or that playlists are created in, aren't included.
+    pub(crate) fn dir_mtimes(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+    ) -> Vec<(PathBuf, SystemTime)> {
+        if !options.preserve_dir_mtimes {
+            return Vec::new();
+        }
+        let song_moves = (self.song_operations.iter())
+            .filter_map(|o| Some((o.song.path.as_path(), o.new_path.as_deref()?)));
+        let file_moves =
+            self.file_operations.iter().map(|o| (o.old_path.as_path(), o.new_path.as_path()));
+
+        let mut renamed = BTreeSet::new();
+        let mut changed = HashSet::new();
+        for (old, new) in song_moves.chain(file_moves) {
+            match (old.parent(), new.parent()) {
+                (Some(a), Some(b)) if a == b => {
+                    renamed.insert(a);
+                }
+                (a, b) => changed.extend(a.into_iter().chain(b)),
+            }
+        }
+        let created = (self.playlist_creations.iter().map(|c| &c.path))
+            .chain(self.quarantines.iter().map(|q| &q.path));
+        changed.extend(created.filter_map(|p| p.parent()));
+
+        (renamed.into_iter())
+            .filter(|d| !changed.contains(d))
+            .filter_map(|d| Some((d.to_owned(), fs.dir_modified(d).ok()?)))
+            .collect()
+    }
+
+    /// Restores the modification times returned by [`Changes::dir_mtimes`].
+    pub(crate) fn restore_dir_mtimes(fs: &impl Fs, mtimes: Vec<(PathBuf, SystemTime)>) {
+        for (dir, time) in mtimes {
+            if let Err(e) = fs.set_dir_modified(&dir, time) {
+                warn!(dir = %dir.display(), error = %e, "failed to restore modification time");
+            }
+        }
+    }
+
+    /// The total size in bytes of the songs and files that are moved or copied, as captured by
+    /// the index. Moves on the same filesystem are only renames, but are counted as well. Dirs of
+    /// scans or artwork aren't indexed, so their contents aren't counted.
+    pub fn transfer_size(&self, index: &MusicIndex) -> u64 {
+        let songs = (self.song_operations.iter())
+            .filter(|o| o.new_path.is_some())
+            .map(|o| o.song.path.as_path());
+        let files = self.file_operations.iter().map(|o| o.old_path.as_path());
+        let quarantines = self.quarantines.iter().map(|q| q.path.as_path());
+        (songs.chain(files).chain(quarantines)).filter_map(|p| index.sizes.get(p)).sum()
+    }
+
+    /// The number of songs whose tags or mode are updated for each of the
+    /// [`SongOperation::reasons`].
+    pub fn reason_counts(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for r in self.song_operations.iter().flat_map(|o| o.reasons.iter()) {
+            *counts.entry(r.as_str()).or_default() += 1;
+        }
+        counts
+    }
+
+    /// The files that would be moved into the `unknown` dir or set aside in the quarantine dir
+    /// instead of being organized, sorted by path.
+    pub fn unorganized_files(&self, options: &ChangeOptions) -> Vec<&Path> {
+        let unknown_dir = options.output_dir.join("unknown");
+        let unknown = (self.file_operations.iter())
+            .filter(|o| o.new_path.parent() == Some(unknown_dir.as_path()))
+            .map(|o| o.old_path.as_path());
+        let quarantined = self.quarantines.iter().map(|q| q.path.as_path());
+        let mut files: Vec<&Path> = unknown.chain(quarantined).collect();
+        files.sort();
+        files
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dir_creations.is_empty()
+            && self.song_operations.is_empty()
+            && self.file_operations.is_empty()
+            && self.playlist_updates.is_empty()
+            && self.playlist_creations.is_empty()
+            && self.quarantines.is_empty()
+    }
+}
+
+/// A step of executing operations that move files, see [`order_moves`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MoveStep {
+    Execute(usize),
+    /// Moves to a temporary path first, which breaks a cycle of moves, e.g. a swap.
+    ExecuteToTemp(usize),
+    /// Moves from the temporary path to the new path, once the rest of the cycle is done.
+    Finish(usize),
+}
+
+/// The index of the operation that has to move away from the new path of each operation
+/// before it can be executed. `moves` are the old and new paths of the operations, if they move.
+pub(crate) fn blockers(moves: &[Option<(&Path, &Path)>]) -> Vec<Option<usize>> {
+    let sources: HashMap<&Path, usize> =
+        moves.iter().enumerate().filter_map(|(i, m)| Some(((*m)?.0, i))).collect();
+    (moves.iter().enumerate())
+        .map(|(i, m)| sources.get((*m)?.1).copied().filter(|&j| j != i))
+        .collect()
+}
+
+/// Orders the operations so none of them overwrites a file that is still to be moved away,
+/// otherwise the original order is kept.
+pub(crate) fn order_moves(blockers: &[Option<usize>]) -> Vec<MoveStep> {
+    let mut steps = Vec::with_capacity(blockers.len());
+    let mut done = vec![false; blockers.len()];
+    let mut on_chain = vec![false; blockers.len()];
+    for start in 0..blockers.len() {
+        // follow the operations that have to be executed first
+        let mut chain = Vec::new();
+        let mut cycle = None;
+        let mut next = Some(start);
+        while let Some(i) = next.filter(|&i| !done[i]) {
+            if on_chain[i] {
+                cycle = chain.iter().position(|&c| c == i);
+                break;
+            }
+            on_chain[i] = true;
+            chain.push(i);
+            next = blockers[i];
+        }
+        for &i in chain.iter() {
+            done[i] = true;
+            on_chain[i] = false;
+        }
+
+        match cycle {
+            Some(pos) => {
+                let last = chain.pop().unwrap();
+                steps.push(MoveStep::ExecuteToTemp(last));
+                steps.extend(chain[pos..].iter().rev().map(|&i| MoveStep::Execute(i)));
+                steps.push(MoveStep::Finish(last));
+                steps.extend(chain[..pos].iter().rev().map(|&i| MoveStep::Execute(i)));
+            }
+            None => steps.extend(chain.iter().rev().map(|&i| MoveStep::Execute(i))),
+        }
+    }
+    steps
+}
+
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".music-organizer");
+    path.with_file_name(name)
+}
+
+/// Executes the operations in the order of [`order_moves`]. Operations whose new path is still
+/// occupied, because the operation moving away from it failed, aren't executed.
+pub(crate) fn execute_ordered<O>(
+    fs: &impl Fs,
+    options: &ExecOptions,
+    ops: &[O],
+    moves: &[Option<(&Path, &Path)>],
+    blockers: &[Option<usize>],
+    execute: &mut impl FnMut(&O, Option<&Path>) -> Result<(), Box<dyn std::error::Error>>,
+    f: &mut impl FnMut(&O, Result<(), Box<dyn std::error::Error>>),
+) {
+    let occupied = |new: &Path| format!("{} is still occupied", new.display()).into();
+    let mut vacated = vec![false; ops.len()];
+    let mut temps = HashMap::new();
+    for step in order_moves(blockers) {
+        let (i, r) = match step {
+            MoveStep::Execute(i) => {
+                if options.cancel.is_cancelled() {
+                    break;
+                }
+                let r = match (blockers[i], moves[i]) {
+                    (Some(j), Some((_, new))) if !vacated[j] => Err(occupied(new)),
+                    _ => execute(&ops[i], None),
+                };
+                vacated[i] = r.is_ok();
+                (i, r)
+            }
+            MoveStep::ExecuteToTemp(i) => {
+                if options.cancel.is_cancelled() {
+                    break;
+                }
+                let Some((_, new)) = moves[i] else { continue };
+                let temp = temp_path(new);
+                match execute(&ops[i], Some(&temp)) {
+                    Ok(()) => {
+                        vacated[i] = true;
+                        temps.insert(i, temp);
+                        continue;
+                    }
+                    Err(e) => (i, Err(e)),
+                }
+            }
+            MoveStep::Finish(i) => {
+                let (Some(temp), Some((_, new))) = (temps.remove(&i), moves[i]) else { continue };
+                let r = match blockers[i] {
+                    Some(j) if vacated[j] => {
+                        options.retry.run(|| fs.rename(&temp, new)).map_err(Into::into)
+                    }
+                    _ => Err(format!("{}, left at {}", occupied(new), temp.display()).into()),
+                };
+                (i, r)
+            }
+        };
+        // skipped operations don't stop the execution
+        let failed = r.as_ref().is_err_and(|e| !e.is::<ChangedOnDisk>());
+        f(&ops[i], r);
+        if failed && options.fail_fast {
+            break;
+        }
+    }
+
+    // the cycles interrupted by cancellation or a failure stay at their temporary path
+    for (i, temp) in temps {
+        f(&ops[i], Err(format!("left at {}", temp.display()).into()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{FileOpType, LibraryBuilder, MemFs, Video};
+
+    fn song(path: &str, track: u16, title: &str) -> Song {
+        Song {
+            path: PathBuf::from(path),
+            track_number: Some(track),
+            release_artists: Arc::from(["Foo".to_owned()]),
+            artists: Arc::from(["Foo".to_owned()]),
+            release: Arc::from("Bar"),
+            title: title.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn index(songs: Vec<Song>, images: &[&str]) -> MusicIndex {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = songs;
+        index.images = images.iter().map(PathBuf::from).collect();
+        index
+    }
+
+    fn generate(fs: &MemFs, index: &MusicIndex) -> Changes {
+        let options = ChangeOptions::from(PathBuf::from("/music"));
+        Changes::generate(fs, Checks::from(index), &options)
+    }
+
+    #[test]
+    fn plans_layout_paths() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a.mp3", "a");
+        fs.add_file("/music/cover.jpg", "c");
+        let index = index(vec![song("/music/a.mp3", 1, "One")], &["/music/cover.jpg"]);
+
+        let changes = generate(&fs, &index);
+
+        let dirs: Vec<_> = changes.dir_creations.iter().map(|d| d.path.as_path()).collect();
+        assert_eq!(dirs, [Path::new("/music/Foo"), Path::new("/music/Foo/Bar")]);
+        assert_eq!(
+            changes.song_operations[0].new_path.as_deref(),
+            Some(Path::new("/music/Foo/Bar/01 - Foo - One.mp3")),
+        );
+        assert_eq!(changes.file_operations[0].new_path, Path::new("/music/Foo/Bar/cover.jpg"));
+    }
+
+    #[test]
+    fn sorts_operations_independent_of_index_order() {
+        let fs = MemFs::new();
+        let songs = [song("/music/b.mp3", 2, "Two"), song("/music/a.mp3", 1, "One")];
+        let forward = generate(&fs, &index(songs.to_vec(), &["/music/b.jpg", "/music/a.jpg"]));
+        let reversed = generate(&fs, &index(songs.into_iter().rev().collect(), &[]));
+
+        let paths =
+            |c: &Changes| c.song_operations.iter().map(|o| o.song.path.clone()).collect::<Vec<_>>();
+        let expected = [PathBuf::from("/music/a.mp3"), PathBuf::from("/music/b.mp3")];
+        assert_eq!(paths(&forward), expected);
+        assert_eq!(paths(&reversed), expected);
+        assert_eq!(forward.file_operations[0].old_path, Path::new("/music/a.jpg"));
+    }
+
+    #[test]
+    fn lowercases_extensions() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a.MP3", "a");
+        let index = index(vec![song("/music/a.MP3", 1, "One")], &[]);
+
+        let changes = generate(&fs, &index);
+        assert_eq!(
+            changes.song_operations[0].new_path.as_deref(),
+            Some(Path::new("/music/Foo/Bar/01 - Foo - One.MP3")),
+        );
+
+        let options = ChangeOptions {
+            lowercase_extensions: true,
+            ..ChangeOptions::from(PathBuf::from("/music"))
+        };
+        let changes = Changes::generate(&fs, Checks::from(&index), &options);
+        assert_eq!(
+            changes.song_operations[0].new_path.as_deref(),
+            Some(Path::new("/music/Foo/Bar/01 - Foo - One.mp3")),
+        );
+    }
+
+    #[test]
+    fn splits_and_joins_artists() {
+        let separators = ArtistSeparators {
+            split: vec![";".into(), " feat. ".into()],
+            name_join: " & ".into(),
+            ..Default::default()
+        };
+        let artists = separators.split_artists(vec!["Foo; Baz feat. Qux".into()]);
+        assert_eq!(artists, ["Foo", "Baz", "Qux"]);
+
+        let fs = MemFs::new();
+        fs.add_file("/music/a.mp3", "a");
+        let mut s = song("/music/a.mp3", 1, "One");
+        s.release_artists = Arc::from(artists);
+        let index = index(vec![s], &[]);
+        let options = ChangeOptions {
+            artist_separators: separators,
+            ..ChangeOptions::from(PathBuf::from("/music"))
+        };
+
+        let changes = Changes::generate(&fs, Checks::from(&index), &options);
+
+        assert_eq!(
+            changes.song_operations[0].new_path.as_deref(),
+            Some(Path::new("/music/Foo & Baz & Qux/Bar/01 - Foo - One.mp3")),
+        );
+    }
+
+    #[test]
+    fn files_collaborations_by_policy() {
+        let library = LibraryBuilder::new("/music").release("Foo", "Bar", 1).map(|s| {
+            s.title = "One".to_owned();
+            s.release_artists = Arc::from(["Foo".to_owned(), "Baz".to_owned()]);
+        });
+        let fs = library.fs();
+        let index = library.index();
+        let generate = |collaborations| {
+            let options =
+                ChangeOptions { collaborations, ..ChangeOptions::from(PathBuf::from("/music")) };
+            Changes::generate(&fs, Checks::from(&index), &options)
+        };
+        let new_path = |c: &Changes| c.song_operations[0].new_path.clone().unwrap();
+
+        let joint = generate(Collaborations::Joint);
+        assert_eq!(new_path(&joint), Path::new("/music/Foo, Baz/Bar/01 - Foo - One.mp3"));
+
+        let first_artist = generate(Collaborations::FirstArtist);
+        assert_eq!(
+            new_path(&first_artist),
+            Path::new("/music/Foo/Bar (with Baz)/01 - Foo - One.mp3")
+        );
+
+        let linked = generate(Collaborations::Linked);
+        let song_path = Path::new("/music/Foo/Bar/01 - Foo - One.mp3");
+        assert_eq!(new_path(&linked), song_path);
+        assert_eq!(
+            linked.file_operations,
+            [FileOperation {
+                old_path: song_path.to_owned(),
+                new_path: PathBuf::from("/music/Baz/Bar/01 - Foo - One.mp3"),
+                op_type: Some(FileOpType::HardLink),
+                replace: false,
+            }]
+        );
+
+        let options = ExecOptions::default();
+        linked.execute_dir_creations(&fs, &options, &mut |_, r| r.unwrap());
+        linked.execute_song_operations(&fs, &options, &mut |_, _, _| (), &mut |_, r| r.unwrap());
+        linked.execute_file_operations(&fs, &options, &mut |_, _, _| (), &mut |_, r| r.unwrap());
+        // the link is indexed like any other song on the next run
+        let mut linked_index = index.clone();
+        linked_index.songs[0].path = PathBuf::from("/music/Baz/Bar/01 - Foo - One.mp3");
+        let options = ChangeOptions {
+            collaborations: Collaborations::Linked,
+            ..ChangeOptions::from(PathBuf::from("/music"))
+        };
+        let again = Changes::generate(&fs, Checks::from(&linked_index), &options);
+        assert!(again.song_operations.is_empty());
+        assert!(again.file_operations.is_empty());
+
+        // retagging the song splits it from the link, which is re-created
+        let mut moved_index = index.clone();
+        moved_index.songs[0].path = song_path.to_owned();
+        let mut checks = Checks::from(&moved_index);
+        util::update_tag(
+            &mut checks.song_operations,
+            &checks.retag_excluded,
+            &moved_index.songs[0],
+            "genre",
+            |u| u.genre = Value::Update("Jazz".to_owned()),
+        );
+        let retagged = Changes::generate(&fs, checks, &options);
+        assert!(retagged.file_operations[0].replace);
+
+        // a link that split from the song, e.g. by another program retagging it, is replaced
+        let link = Path::new("/music/Baz/Bar/01 - Foo - One.mp3");
+        let audio = fs.read(song_path).unwrap();
+        fs.remove_file(link).unwrap();
+        fs.add_file(link, [b"ID3\x04\0\0\0\0\0\x01x".as_slice(), &audio].concat());
+        let relinked = Changes::generate(&fs, Checks::from(&moved_index), &options);
+        assert!(relinked.file_operations[0].replace);
+        let exec_options = ExecOptions::default();
+        relinked
+            .execute_file_operations(&fs, &exec_options, &mut |_, _, _| (), &mut |_, r| r.unwrap());
+        assert!(fs.same_file(song_path, link));
+
+        // an unrelated file at the path of the link is never overwritten
+        fs.remove_file(link).unwrap();
+        fs.add_file(link, "another edition");
+        let collision = Changes::generate(&fs, Checks::from(&moved_index), &options);
+        assert!(!collision.file_operations[0].replace);
+        let mut failed = false;
+        collision.execute_file_operations(&fs, &exec_options, &mut |_, _, _| (), &mut |_, r| {
+            failed = r.is_err()
+        });
+        assert!(failed);
+        assert_eq!(fs.read(link).unwrap(), b"another edition");
+    }
+
+    #[test]
+    fn renders_custom_fields() {
+        let fs = MemFs::new();
+        let mut a = song("/music/a.mp3", 1, "One");
+        a.extra.insert("LABEL".into(), "Warp".into());
+        let b = song("/music/b.mp3", 2, "Two");
+        let index = index(vec![a, b], &[]);
+        let mut checks = Checks::from(&index);
+        util::update_tag(
+            &mut checks.song_operations,
+            &checks.retag_excluded,
+            &index.songs[1],
+            "custom field",
+            |u| {
+                u.extra.insert("LABEL".into(), Value::Update("XL".into()));
+            },
+        );
+        let options = ChangeOptions {
+            layout: "{extra.label}/{title}".parse().unwrap(),
+            ..ChangeOptions::from(PathBuf::from("/music"))
+        };
+
+        let changes = Changes::generate(&fs, checks, &options);
+
+        let paths: Vec<_> =
+            changes.song_operations.iter().map(|o| o.new_path.as_deref().unwrap()).collect();
+        assert_eq!(paths, [Path::new("/music/Warp/One.mp3"), Path::new("/music/XL/Two.mp3")]);
+    }
+
+    #[test]
+    fn keeps_same_named_releases_apart() {
+        let fs = MemFs::new();
+        let mut original = song("/music/a.mp3", 1, "One");
+        original.year = Some(1997);
+        let mut remaster = song("/music/b.mp3", 1, "One");
+        remaster.year = Some(2017);
+        let mut other = song("/music/c.mp3", 2, "Two");
+        other.year = Some(2017);
+        let index = index(vec![original, remaster, other], &[]);
+
+        let changes = generate(&fs, &index);
+
+        let paths: Vec<_> =
+            changes.song_operations.iter().map(|o| o.new_path.as_deref().unwrap()).collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("/music/Foo/Bar (1997)/01 - Foo - One.mp3"),
+                Path::new("/music/Foo/Bar (2017)/01 - Foo - One.mp3"),
+                Path::new("/music/Foo/Bar (2017)/02 - Foo - Two.mp3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_existing_dirs_and_sorted_songs() {
+        let fs = MemFs::new();
+        fs.add_file("/music/Foo/Bar/01 - Foo - One.mp3", "a");
+        fs.add_file("/music/b.mp3", "b");
+        let index = index(
+            vec![
+                song("/music/Foo/Bar/01 - Foo - One.mp3", 1, "One"),
+                song("/music/b.mp3", 2, "Two"),
+            ],
+            &[],
+        );
+
+        let changes = generate(&fs, &index);
+
+        assert!(changes.dir_creations.is_empty());
+        assert_eq!(changes.song_operations.len(), 1);
+        assert_eq!(changes.song_operations[0].song.path, Path::new("/music/b.mp3"));
+    }
+
+    #[test]
+    fn executes_moves_and_copies() {
+        for op_type in [FileOpType::Move, FileOpType::Copy] {
+            let fs = MemFs::new();
+            fs.add_file("/music/a.mp3", "a");
+            let index = index(vec![song("/music/a.mp3", 1, "One")], &[]);
+            let changes = generate(&fs, &index);
+            let options = ExecOptions { op_type, ..Default::default() };
+
+            let mut failures = 0;
+            changes.execute_dir_creations(&fs, &options, &mut |_, r| failures += r.is_err() as u32);
+            changes.execute_song_operations(&fs, &options, &mut |_, _, _| (), &mut |_, r| {
+                failures += r.is_err() as u32
+            });
+
+            assert_eq!(failures, 0);
+            let new = Path::new("/music/Foo/Bar/01 - Foo - One.mp3");
+            assert_eq!(fs.file(new).as_deref(), Some(b"a".as_slice()));
+            let old = fs.file(Path::new("/music/a.mp3"));
+            assert_eq!(old.is_some(), op_type == FileOpType::Copy);
+        }
+    }
+
+    #[test]
+    fn reports_concurrent_song_operations_in_order() {
+        let fs = MemFs::new();
+        let songs: Vec<_> = (1..=8)
+            .map(|i| {
+                let path = format!("/music/{i}.mp3");
+                fs.add_file(&path, "a");
+                song(&path, i, &format!("Song {i}"))
+            })
+            .collect();
+        let index = index(songs, &[]);
+        let changes = generate(&fs, &index);
+        let options = ExecOptions { threads: NonZeroUsize::new(3), ..Default::default() };
+
+        changes.execute_dir_creations(&fs, &options, &mut |_, _| ());
+        let mut reported = Vec::new();
+        changes.execute_song_operations(&fs, &options, &mut |_, _, _| (), &mut |o, r| {
+            assert!(r.is_ok());
+            reported.push(o.song.track_number.unwrap());
+        });
+
+        assert_eq!(reported, (1..=8).collect::<Vec<_>>());
+        assert!(fs.file(Path::new("/music/Foo/Bar/08 - Foo - Song 8.mp3")).is_some());
+    }
+
+    #[test]
+    fn selects_operations_by_id() {
+        let fs = MemFs::new();
+        let index =
+            index(vec![song("/music/a.mp3", 1, "One"), song("/music/b.mp3", 2, "Two")], &[]);
+        let changes = generate(&fs, &index);
+        assert_eq!(changes.ids().len(), 5);
+
+        let id = OperationId::SongOperation(PathBuf::from("/music/b.mp3"));
+        let selected = changes.select(&HashSet::from([id.clone()]));
+
+        assert_eq!(selected.song_operations.len(), 1);
+        assert_eq!(selected.song_operations[0].id(), id);
+        assert_eq!(selected.dir_creations.len(), 3);
+        assert!(changes.select(&HashSet::new()).dir_creations.is_empty());
+    }
+
+    #[test]
+    fn executes_chained_moves_and_swaps() {
+        let fs = MemFs::new();
+        let op = |old: &str, new: &str| {
+            fs.add_file(old, old);
+            let mut op = SongOperation::new(song(old, 1, "One"));
+            op.new_path = Some(PathBuf::from(new));
+            op
+        };
+        let changes = Changes {
+            song_operations: vec![
+                op("/music/a.mp3", "/music/b.mp3"),
+                op("/music/b.mp3", "/music/c.mp3"),
+                op("/music/x.mp3", "/music/y.mp3"),
+                op("/music/y.mp3", "/music/x.mp3"),
+            ],
+            ..Default::default()
+        };
+
+        let mut reported = Vec::new();
+        changes.execute_song_operations(
+            &fs,
+            &ExecOptions::default(),
+            &mut |_, _, _| (),
+            &mut |o, r| {
+                assert!(r.is_ok());
+                reported.push(o.song.path.clone());
+            },
+        );
+
+        assert_eq!(reported.len(), 4);
+        let file = |p: &str| fs.file(Path::new(p)).map(String::from_utf8).map(Result::unwrap);
+        assert_eq!(file("/music/a.mp3"), None);
+        assert_eq!(file("/music/b.mp3").as_deref(), Some("/music/a.mp3"));
+        assert_eq!(file("/music/c.mp3").as_deref(), Some("/music/b.mp3"));
+        assert_eq!(file("/music/x.mp3").as_deref(), Some("/music/y.mp3"));
+        assert_eq!(file("/music/y.mp3").as_deref(), Some("/music/x.mp3"));
+        assert_eq!(fs.paths().len(), 5);
+    }
+
+    #[test]
+    fn fail_fast_stops_after_first_failure() {
+        // the dir creations aren't executed, so moving the songs fails
+        let fs = MemFs::new();
+        fs.add_file("/music/a.mp3", "a");
+        fs.add_file("/music/b.mp3", "b");
+        let index =
+            index(vec![song("/music/a.mp3", 1, "One"), song("/music/b.mp3", 2, "Two")], &[]);
+        let changes = generate(&fs, &index);
+        let options = ExecOptions { fail_fast: true, ..Default::default() };
+
+        let mut executed = 0;
+        changes
+            .execute_song_operations(&fs, &options, &mut |_, _, _| (), &mut |_, _| executed += 1);
+
+        assert_eq!(executed, 1);
+    }
+
+    #[test]
+    fn skips_operations_whose_files_changed() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a.mp3", "a");
+        fs.add_file("/music/b.mp3", "b");
+        let songs = ["a", "b", "c"].map(|n| song(&format!("/music/{n}.mp3"), 1, n));
+        let mut index = index(songs.to_vec(), &[]);
+        index.modified =
+            index.songs.iter().map(|s| (s.path.clone(), SystemTime::UNIX_EPOCH)).collect();
+        let changes = generate(&fs, &index);
+
+        fs.set_modified(Path::new("/music/a.mp3"), SystemTime::now());
+        fs.add_file("/music/Foo/Bar/01 - Foo - b.mp3", "download");
+
+        let mut changed = Vec::new();
+        changes.execute_song_operations(
+            &fs,
+            &ExecOptions::default(),
+            &mut |_, _, _| (),
+            &mut |_, r| {
+                changed.push(r.unwrap_err().downcast::<ChangedOnDisk>().unwrap().to_string())
+            },
+        );
+        changed.sort();
+        assert_eq!(
+            changed,
+            [
+                "/music/Foo/Bar/01 - Foo - b.mp3 already exists",
+                "/music/a.mp3 was modified since indexing",
+                "/music/c.mp3 disappeared",
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_companion_files_with_songs() {
+        let fs = MemFs::new();
+        fs.add_file("/music/rip/a.mp3", "a");
+        let mut index = index(vec![song("/music/rip/a.mp3", 1, "One")], &[]);
+        index.companions = vec![PathBuf::from("/music/rip/rip.log"), PathBuf::from("/music/b.cue")];
+
+        let changes = generate(&fs, &index);
+
+        let ops: Vec<_> = changes.file_operations.iter().map(|o| o.new_path.as_path()).collect();
+        assert_eq!(ops, [Path::new("/music/Foo/Bar/rip.log")]);
+    }
+
+    #[test]
+    fn applies_extra_policies() {
+        let fs = MemFs::new();
+        let mut index = index(vec![song("/music/rip/a.mp3", 1, "One")], &[]);
+        index.extras = ["/music/rip/booklet.pdf", "/music/rip/video.mkv", "/music/rip/scans.zip"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let options = ChangeOptions {
+            extra_policies: ExtraPolicies {
+                documents: ExtraPolicy::Follow,
+                videos: ExtraPolicy::Leave,
+                archives: ExtraPolicy::Collect,
+            },
+            ..ChangeOptions::from(PathBuf::from("/music"))
+        };
+
+        let changes = Changes::generate(&fs, Checks::from(&index), &options);
+
+        let ops: Vec<_> = changes.file_operations.iter().map(|o| o.new_path.as_path()).collect();
+        assert_eq!(
+            ops,
+            [Path::new("/music/Foo/Bar/booklet.pdf"), Path::new("/music/extras/rip/scans.zip")]
+        );
+    }
+
+    #[test]
+    fn moves_music_videos_into_the_video_tree() {
+        let fs = MemFs::new();
+        let mut index = index(vec![], &[]);
+        index.videos = vec![Video {
+            path: PathBuf::from("/music/clips/x.m4v"),
+            artists: vec!["Foo".into(), "Baz".into()],
+            title: "One".into(),
+            ..Default::default()
+        }];
+
+        let changes = generate(&fs, &index);
+
+        let ops: Vec<_> = changes.file_operations.iter().map(|o| o.new_path.as_path()).collect();
+        assert_eq!(ops, [Path::new("/music/Videos/Foo, Baz/One.m4v")]);
+    }
+
+    #[test]
+    fn sums_sizes_of_moved_files() {
+        let fs = MemFs::new();
+        let songs = vec![
+            song("/music/a.mp3", 1, "One"),
+            song("/music/Foo/Bar/02 - Foo - Two.mp3", 2, "Two"),
+        ];
+        let mut index = index(songs, &["/music/cover.jpg"]);
+        index.sizes = [
+            ("/music/a.mp3", 3000),
+            ("/music/Foo/Bar/02 - Foo - Two.mp3", 5000),
+            ("/music/cover.jpg", 200),
+        ]
+        .map(|(p, s)| (PathBuf::from(p), s))
+        .into();
+
+        let changes = generate(&fs, &index);
+
+        // the second song is already in place
+        assert_eq!(changes.transfer_size(&index), 3200);
+    }
+
+    #[test]
+    fn lists_files_moved_into_unknown_dir() {
+        let fs = MemFs::new();
+        let mut index = index(vec![song("/music/a.mp3", 1, "One")], &[]);
+        index.unknown = ["/music/b.mp3", "/music/unknown/c.mp3"].map(PathBuf::from).into();
+
+        let changes = generate(&fs, &index);
+
+        let options = ChangeOptions::from(PathBuf::from("/music"));
+        assert_eq!(changes.unorganized_files(&options), [Path::new("/music/b.mp3")]);
+    }
+
+    #[test]
+    fn reuses_dirs_that_differ_by_case() {
+        let fs = MemFs::new();
+        fs.add_dir("/music/FOO/Bar");
+        let mut lower = song("/music/b.mp3", 2, "Two");
+        lower.release = Arc::from("baz");
+        let mut upper = song("/music/c.mp3", 3, "Three");
+        upper.release = Arc::from("Baz");
+        let index = index(vec![song("/music/a.mp3", 1, "One"), lower, upper], &[]);
+
+        let changes = generate(&fs, &index);
+
+        let paths: Vec<_> =
+            (changes.song_operations.iter()).filter_map(|o| o.new_path.as_deref()).collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("/music/FOO/Bar/01 - Foo - One.mp3"),
+                Path::new("/music/FOO/baz/02 - Foo - Two.mp3"),
+                Path::new("/music/FOO/baz/03 - Foo - Three.mp3"),
+            ]
+        );
+        let dirs: Vec<_> = changes.dir_creations.iter().map(|d| d.path.as_path()).collect();
+        assert_eq!(dirs, [Path::new("/music/FOO/baz")]);
+    }
+
+    #[test]
+    fn moves_scans_dirs_with_songs() {
+        let fs = MemFs::new();
+        fs.add_file("/music/rip/a.mp3", "a");
+        fs.add_file("/music/rip/Scans/front.jpg", "f");
+        fs.add_file("/music/rip/other/b.txt", "b");
+        let index = index(vec![song("/music/rip/a.mp3", 1, "One")], &[]);
+
+        let changes = generate(&fs, &index);
+
+        assert_eq!(changes.file_operations.len(), 1);
+        assert_eq!(changes.file_operations[0].old_path, Path::new("/music/rip/Scans"));
+        assert_eq!(changes.file_operations[0].new_path, Path::new("/music/Foo/Bar/Scans"));
+    }
+
+    #[test]
+    fn tag_only_keeps_paths() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a.mp3", "a");
+        let index = index(vec![song("/music/a.mp3", 1, "One")], &["/music/cover.jpg"]);
+        let mut checks = Checks::from(&index);
+        checks.check_file_permissions();
+        let options = ChangeOptions { tag_only: true, ..PathBuf::from("/music").into() };
+
+        let changes = Changes::generate(&fs, checks, &options);
+
+        assert!(changes.dir_creations.is_empty());
+        assert!(changes.file_operations.is_empty());
+        assert!(changes.song_operations.iter().all(|o| o.new_path.is_none()));
+    }
+
+    #[test]
+    fn retains_song_operations_with_their_images_and_dirs() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a.mp3", "a");
+        let mut other = song("/music/other/c.mp3", 1, "Three");
+        other.release = Arc::from("Baz");
+        let index = index(
+            vec![song("/music/a.mp3", 1, "One"), other],
+            &["/music/cover.jpg", "/music/other/cover.jpg"],
+        );
+        let mut changes = generate(&fs, &index);
+        assert_eq!(changes.file_operations.len(), 2);
+
+        changes.retain_song_operations(|o| &*o.song.release == "Bar");
+
+        assert_eq!(changes.song_operations.len(), 1);
+        let images: Vec<_> = changes.file_operations.iter().map(|o| &o.old_path).collect();
+        assert_eq!(images, [Path::new("/music/cover.jpg")]);
+        let dirs: Vec<_> = changes.dir_creations.iter().map(|d| d.path.as_path()).collect();
+        assert_eq!(dirs, [Path::new("/music/Foo"), Path::new("/music/Foo/Bar")]);
+    }
+
+    #[test]
+    fn applies_image_policy() {
+        let fs = MemFs::new();
+        let index = index(vec![song("/music/x/a.mp3", 1, "One")], &["/music/x/cover.jpg"]);
+        let op_types = |image_policy| {
+            let options =
+                ChangeOptions { image_policy, ..ChangeOptions::from(PathBuf::from("/music")) };
+            let changes = Changes::generate(&fs, Checks::from(&index), &options);
+            changes.file_operations.iter().map(|o| o.op_type).collect::<Vec<_>>()
+        };
+
+        assert_eq!(op_types(ImagePolicy::Follow), [None]);
+        assert_eq!(op_types(ImagePolicy::Copy), [Some(FileOpType::Copy)]);
+        assert_eq!(op_types(ImagePolicy::Leave), []);
+        assert_eq!(op_types(ImagePolicy::Delete), []);
+    }
+
+    #[test]
+    fn moves_artist_images_to_the_new_artist_dir() {
+        let fs = MemFs::new();
+        let mut other = song("/music/foo/two/b.mp3", 2, "Two");
+        other.release = Arc::from("Baz");
+        let index = index(
+            vec![song("/music/foo/one/a.mp3", 1, "One"), other],
+            &["/music/foo/artist.jpg", "/music/foo/one/cover.jpg"],
+        );
+        let mut changes = generate(&fs, &index);
+
+        let ops: Vec<_> = (changes.file_operations.iter())
+            .map(|o| (o.old_path.as_path(), o.new_path.as_path()))
+            .collect();
+        assert_eq!(
+            ops,
+            [
+                (Path::new("/music/foo/artist.jpg"), Path::new("/music/Foo/artist.jpg")),
+                (Path::new("/music/foo/one/cover.jpg"), Path::new("/music/Foo/Bar/cover.jpg")),
+            ]
+        );
+
+        changes.retain_song_operations(|o| &*o.song.release == "Bar");
+        let images: Vec<_> = changes.file_operations.iter().map(|o| &o.old_path).collect();
+        assert_eq!(images, [Path::new("/music/foo/one/cover.jpg")]);
+    }
+
+    #[test]
+    fn groups_song_operations_by_artist_and_release() {
+        let fs = MemFs::new();
+        let mut other = song("/music/c.mp3", 1, "Three");
+        other.release = Arc::from("Baz");
+        let index =
+            index(vec![song("/music/b.mp3", 2, "Two"), other, song("/music/a.mp3", 1, "One")], &[]);
+        let changes = generate(&fs, &index);
+
+        let groups = changes.group_song_operations();
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].names, ["Foo"]);
+        assert_eq!(groups[0].num_song_operations(), 3);
+        let releases: Vec<_> = groups[0]
+            .releases
+            .iter()
+            .map(|r| {
+                let titles: Vec<_> = r.song_operations.iter().map(|o| &*o.song.title).collect();
+                (r.name.as_str(), titles)
+            })
+            .collect();
+        assert_eq!(releases, [("Bar", vec!["One", "Two"]), ("Baz", vec!["Three"])]);
+    }
+}
@@ -0,0 +1,573 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use tracing::{debug, info, info_span};
+
+use crate::fs::collapse_whitespace;
+use crate::meta::name_key;
+use crate::mounts::{mount_of, read_mounts};
+use crate::{
+    util, ArtistDistinction, CancelToken, Filter, Mount, MusicIndex, Release, ReleaseArtists, Song,
+    SongOperation, TagUpdate, Value,
+};
+
+/// The permissions [`Checks::check_file_permissions`] applies to songs.
+pub(crate) const FILE_PERMISSIONS: u32 = 0o755;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checks<'a> {
+    pub index: &'a MusicIndex,
+    pub song_operations: Vec<SongOperation>,
+    pub artists: Vec<ReleaseArtists<'a>>,
+    /// Checked before each song or artist, the check returns early if cancelled.
+    pub cancel: CancelToken,
+    pub stats: CheckStats,
+    /// The read-only mounts and mounts without unix permissions
+    /// [`Checks::check_file_permissions`] skipped, with the number of songs on them that have
+    /// the wrong permissions.
+    pub skipped_mounts: Vec<(Mount, usize)>,
+    /// Songs whose tags are never rewritten, see [`Checks::exclude_from_retagging`].
+    pub retag_excluded: HashSet<&'a Path>,
+    /// The release artists [`Checks::check_inconsitent_release_artists`] compares.
+    pub similarity_scope: SimilarityScope,
+}
+
+/// Restricts which release artists are compared with each other, e.g. to the artist of a newly
+/// added release instead of the whole library.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum SimilarityScope {
+    #[default]
+    Library,
+    /// Release artists whose first name starts with the letter, case insensitively.
+    Letter(char),
+    /// Release artists with a name that matches, case insensitively.
+    Artist(String),
+}
+
+impl SimilarityScope {
+    pub fn contains(&self, names: &[String]) -> bool {
+        match self {
+            Self::Library => true,
+            Self::Letter(l) => (names.first().and_then(|n| n.chars().next()))
+                .is_some_and(|c| c.to_lowercase().eq(l.to_lowercase())),
+            Self::Artist(a) => names.iter().any(|n| n.eq_ignore_ascii_case(a)),
+        }
+    }
+}
+
+/// The number of issues a check found and of the songs it queued a tag or mode update for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CheckCount {
+    pub issues: usize,
+    pub updates: usize,
+}
+
+/// The counts of each check that was run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CheckStats {
+    pub embedded_artworks: CheckCount,
+    pub file_permissions: CheckCount,
+    /// Issues are pairs of release artists.
+    pub inconsistent_artists: CheckCount,
+    /// Issues are releases.
+    pub invalid_numbers: CheckCount,
+    /// Issues are releases.
+    pub release_suffixes: CheckCount,
+    /// Issues are releases split over multiple release artists.
+    pub split_releases: CheckCount,
+    /// Issues are releases with multiple discs.
+    pub flattened_discs: CheckCount,
+    /// Issues are songs that lack ReplayGain fields.
+    pub replay_gain: CheckCount,
+    /// Issues are songs with ID3v1 genre numbers or unnormalized genre names.
+    pub genres: CheckCount,
+    /// Issues are releases with many distinct artists but no release artists.
+    pub various_artists: CheckCount,
+    /// Issues are releases with different artists but no release artists.
+    pub release_artists_vote: CheckCount,
+    /// Issues are discs with inconsistent total tracks and releases with inconsistent total
+    /// discs.
+    pub inconsistent_totals: CheckCount,
+    /// Issues are bonus tracks, instrumentals and hidden tracks.
+    pub bonus_tracks: CheckCount,
+    /// Issues are releases whose songs lack values most of the other songs have.
+    pub filled_tags: CheckCount,
+    /// Issues are releases whose name or release artists have surrounding or repeated
+    /// whitespace.
+    pub whitespace: CheckCount,
+    /// Issues are songs whose tag update a script adjusted or vetoed.
+    pub script: CheckCount,
+}
+
+impl<'a> From<&'a MusicIndex> for Checks<'a> {
+    fn from(index: &'a MusicIndex) -> Self {
+        let mut new = Self {
+            index,
+            song_operations: Vec::new(),
+            artists: Vec::new(),
+            cancel: CancelToken::default(),
+            stats: CheckStats::default(),
+            skipped_mounts: Vec::new(),
+            retag_excluded: HashSet::new(),
+            similarity_scope: SimilarityScope::default(),
+        };
+        new.update_index();
+        new
+    }
+}
+
+impl<'a> Checks<'a> {
+    /// Excludes the songs matching any of the filters from tag rewriting, e.g. `format:flac` for
+    /// the files of a trusted ripper. No check queues tag updates for them, but they are still
+    /// moved and renamed.
+    pub fn exclude_from_retagging(&mut self, filters: &[Filter]) {
+        let music_dir = &self.index.music_dir;
+        self.retag_excluded = (self.index.songs.iter())
+            .filter(|s| filters.iter().any(|f| f.matches(s, music_dir)))
+            .map(|s| s.path.as_path())
+            .collect();
+    }
+
+    /// Groups the songs by release artists and release, in the order of the index. The
+    /// [`ReleaseArtists::key`]s are computed once here, so checks can group artists by them.
+    pub fn update_index(&mut self) {
+        self.artists.clear();
+        let mut artists: HashMap<&[String], usize> = HashMap::new();
+        let mut releases: HashMap<(usize, &str), usize> = HashMap::new();
+
+        for s in self.index.songs.iter() {
+            let a = *artists.entry(&s.release_artists).or_insert_with(|| {
+                self.artists.push(ReleaseArtists {
+                    names: Arc::clone(&s.release_artists),
+                    key: s.release_artists.iter().map(|n| name_key(n)).collect(),
+                    releases: Vec::new(),
+                });
+                self.artists.len() - 1
+            });
+            let artist_releases = &mut self.artists[a].releases;
+            let r = *releases.entry((a, &s.release)).or_insert_with(|| {
+                artist_releases.push(Release { name: Arc::clone(&s.release), songs: Vec::new() });
+                artist_releases.len() - 1
+            });
+            artist_releases[r].songs.push(s);
+        }
+    }
+
+    /// Removes embedded artworks, except from songs matching any of the `keep` filters, e.g.
+    /// `release:*Live*` for releases whose artwork isn't available as an image file.
+    pub fn remove_embedded_artworks(&mut self, keep: &[Filter]) {
+        let _span = info_span!("check", check = "embedded_artworks").entered();
+        let music_dir = &self.index.music_dir;
+        for song in self.index.songs.iter() {
+            if self.cancel.is_cancelled() {
+                return;
+            }
+            if song.has_artwork && !keep.iter().any(|f| f.matches(song, music_dir)) {
+                debug!(path = %song.path.display(), "embedded artwork");
+                util::update_tag(
+                    &mut self.song_operations,
+                    &self.retag_excluded,
+                    song,
+                    "artwork removal",
+                    |t| t.artwork = Value::Remove,
+                );
+                self.stats.embedded_artworks.issues += 1;
+                self.stats.embedded_artworks.updates += 1;
+            }
+        }
+    }
+
+    /// Checks for songs without the [`FILE_PERMISSIONS`]. Songs on read-only mounts or mounts
+    /// without unix permissions, like NTFS or SMB shares, are only counted as issues, see
+    /// [`Checks::skipped_mounts`], since changing their permissions would fail or be ignored.
+    pub fn check_file_permissions(&mut self) {
+        let _span = info_span!("check", check = "file_permissions").entered();
+        let mounts = read_mounts();
+        for song in self.index.songs.iter() {
+            if self.cancel.is_cancelled() {
+                return;
+            }
+            if let Some(mode) = song.mode {
+                if mode.permissions() != FILE_PERMISSIONS {
+                    debug!(path = %song.path.display(), mode = mode.0, "wrong permissions");
+                    self.stats.file_permissions.issues += 1;
+                    let mount = mount_of(&mounts, &song.path);
+                    if let Some(mount) = mount.filter(|m| !m.supports_permissions()) {
+                        match self.skipped_mounts.iter_mut().find(|(m, _)| m == mount) {
+                            Some((_, n)) => *n += 1,
+                            None => {
+                                info!(path = %mount.path.display(), fs_type = mount.fs_type, read_only = mount.read_only, "mount without permissions");
+                                self.skipped_mounts.push((mount.clone(), 1));
+                            }
+                        }
+                        continue;
+                    }
+                    util::update_song_op(&mut self.song_operations, song, |op| {
+                        op.add_reason("permissions");
+                        op.mode_update = Some(mode.with_permissions(FILE_PERMISSIONS));
+                    });
+                    self.stats.file_permissions.updates += 1;
+                }
+            }
+        }
+    }
+
+    /// Checks for release artists whose names only differ in case, diacritics or a leading
+    /// article, see [`name_key`]. Artists with different MusicBrainz ids are never suggested to
+    /// be merged.
+    pub fn check_inconsitent_release_artists(
+        &mut self,
+        f: &mut impl FnMut(&ReleaseArtists, &ReleaseArtists) -> Value<Vec<String>>,
+    ) {
+        let _span = info_span!("check", check = "release_artists").entered();
+        // artists are grouped by their keys instead of comparing each pair
+        let mut groups: HashMap<&[String], Vec<usize>> = HashMap::new();
+        for (i, ar) in self.artists.iter().enumerate() {
+            if self.similarity_scope.contains(&ar.names) {
+                groups.entry(&ar.key).or_default().push(i);
+            }
+        }
+        let mut pairs: Vec<(usize, usize)> = (groups.values())
+            .flat_map(|g| {
+                (0..g.len()).flat_map(move |k| g[k + 1..].iter().map(move |&j| (g[k], j)))
+            })
+            .collect();
+        pairs.sort_unstable();
+
+        for (i, j) in pairs {
+            let (ar1, ar2) = (&self.artists[i], &self.artists[j]);
+            if self.cancel.is_cancelled() {
+                return;
+            }
+            if ar1.distinction(ar2) == Some(ArtistDistinction::Ids) {
+                debug!(a = ?ar1.names, b = ?ar2.names, "distinct artist ids");
+                continue;
+            }
+            info!(a = ?ar1.names, b = ?ar2.names, "inconsistent release artists");
+            self.stats.inconsistent_artists.issues += 1;
+            let updates = &mut self.stats.inconsistent_artists.updates;
+            match f(ar1, ar2) {
+                Value::Update(names) => {
+                    if *ar1.names != *names {
+                        for rl in ar1.releases.iter() {
+                            for song in rl.songs.iter() {
+                                util::update_tag(
+                                    &mut self.song_operations,
+                                    &self.retag_excluded,
+                                    song,
+                                    "artist merge",
+                                    |tu| tu.release_artists = Value::Update(names.clone()),
+                                );
+                                *updates += 1;
+                            }
+                        }
+                    }
+
+                    if *ar2.names != *names {
+                        for rl in ar2.releases.iter() {
+                            for song in rl.songs.iter() {
+                                util::update_tag(
+                                    &mut self.song_operations,
+                                    &self.retag_excluded,
+                                    song,
+                                    "artist merge",
+                                    |tu| tu.release_artists = Value::Update(names.clone()),
+                                );
+                                *updates += 1;
+                            }
+                        }
+                    }
+                }
+                Value::Remove => {
+                    for rl in ar1.releases.iter() {
+                        for song in rl.songs.iter() {
+                            util::update_tag(
+                                &mut self.song_operations,
+                                &self.retag_excluded,
+                                song,
+                                "artist merge",
+                                |tu| tu.release_artists = Value::Remove,
+                            );
+                            *updates += 1;
+                        }
+                    }
+
+                    for rl in ar2.releases.iter() {
+                        for song in rl.songs.iter() {
+                            util::update_tag(
+                                &mut self.song_operations,
+                                &self.retag_excluded,
+                                song,
+                                "artist merge",
+                                |tu| tu.release_artists = Value::Remove,
+                            );
+                            *updates += 1;
+                        }
+                    }
+                }
+                Value::Unchanged => (),
+            }
+        }
+    }
+
+    /// Checks for track and disc numbers of 0 and numbers exceeding their total, which produce
+    /// misleading file names. The proposed corrections of each release are only applied if `f`
+    /// accepts them: numbers of 0 are removed and too small totals are raised to the highest
+    /// number of the disc or release.
+    pub fn check_invalid_numbers(
+        &mut self,
+        f: &mut impl FnMut(&ReleaseArtists, &Release, &[(&Song, TagUpdate)]) -> bool,
+    ) {
+        let _span = info_span!("check", check = "invalid_numbers").entered();
+        for ar in self.artists.iter() {
+            for rl in ar.releases.iter() {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                let corrections = number_corrections(rl);
+                if corrections.is_empty() {
+                    continue;
+                }
+                info!(artists = ?ar.names, release = %rl.name, "invalid track or disc numbers");
+                self.stats.invalid_numbers.issues += 1;
+                if !f(ar, rl, &corrections) {
+                    continue;
+                }
+                self.stats.invalid_numbers.updates += corrections.len();
+                for (song, c) in corrections {
+                    util::update_tag(
+                        &mut self.song_operations,
+                        &self.retag_excluded,
+                        song,
+                        "invalid numbers",
+                        |tu| {
+                            let fields = [
+                                (&mut tu.track_number, c.track_number),
+                                (&mut tu.total_tracks, c.total_tracks),
+                                (&mut tu.disc_number, c.disc_number),
+                                (&mut tu.total_discs, c.total_discs),
+                            ];
+                            for (field, value) in fields {
+                                if !value.is_unchanged() {
+                                    *field = value;
+                                }
+                            }
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Checks for release and release artist names with surrounding or repeated whitespace,
+    /// which is trimmed and collapsed in dir names. `f` is called for each affected release, the
+    /// tags are left as they are.
+    pub fn check_whitespace(&mut self, f: &mut impl FnMut(&ReleaseArtists, &Release)) {
+        let _span = info_span!("check", check = "whitespace").entered();
+        let padded = |name: &str| collapse_whitespace(name) != name;
+        for ar in self.artists.iter() {
+            for rl in ar.releases.iter() {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                if !padded(&rl.name) && !ar.names.iter().any(|n| padded(n)) {
+                    continue;
+                }
+                info!(artists = ?ar.names, release = ?rl.name, "whitespace in names");
+                self.stats.whitespace.issues += 1;
+                f(ar, rl);
+            }
+        }
+    }
+
+    //pub fn check_inconsitent_albums(
+    //    &mut self,
+    //    index: &MusicIndex,
+    //    f: fn(&MusicIndex, &ReleaseArtists, &Release, &Release) -> Value<String>,
+    //) {
+    //    for ar in index.artists.iter() {
+    //        let mut offset = 1;
+    //        for al1 in ar.releases.iter() {
+    //            for al2 in ar.releases.iter().skip(offset) {
+    //                if al1.name.eq_ignore_ascii_case(&al2.name) {
+    //                    match f(index, ar, al1, al2) {
+    //                        Value::Update(name) => {
+    //                            if al1.name != name {
+    //                                for song in al1.songs.iter().map(|&si| &index.songs[si]) {
+    //                                    self.update_tag(&song.path, |tu| {
+    //                                        tu.album = Value::Update(name.clone());
+    //                                    });
+    //                                }
+    //                            }
+
+    //                            if al2.name != name {
+    //                                for song in al2.songs.iter().map(|&si| &index.songs[si]) {
+    //                                    self.update_tag(&song.path, |tu| {
+    //                                        tu.album = Value::Update(name.clone());
+    //                                    });
+    //                                }
+    //                            }
+    //                        }
+    //                        Value::Remove => {
+    //                            for song in al1.songs.iter().map(|&si| &index.songs[si]) {
+    //                                self.update_tag(&song.path, |tu| {
+    //                                    tu.album = Value::Remove;
+    //                                });
+    //                            }
+
+    //                            for song in al2.songs.iter().map(|&si| &index.songs[si]) {
+    //                                self.update_tag(&song.path, |tu| {
+    //                                    tu.album = Value::Remove;
+    //                                });
+    //                            }
+    //                        }
+    //                        Value::Unchanged => (),
+    //                    }
+    //                }
+    //            }
+    //            offset += 1;
+    //        }
+    //    }
+    //}
+}
+
+/// The corrections of songs with invalid track or disc numbers. Totals are only raised if a
+/// number exceeds its total, in which case all songs of the disc or release get the new total.
+fn number_corrections<'a>(release: &Release<'a>) -> Vec<(&'a Song, TagUpdate)> {
+    let max_disc = release.songs.iter().filter_map(|s| s.disc_number).max().unwrap_or(0);
+    let max_track = |disc| {
+        let songs = release.songs.iter().filter(|s| s.disc_number == disc);
+        songs.filter_map(|s| s.track_number).max().unwrap_or(0)
+    };
+    let exceeds = |n: Option<u16>, total: Option<u16>| n.zip(total).is_some_and(|(n, t)| n > t);
+    let discs_exceed = release.songs.iter().any(|s| exceeds(s.disc_number, s.total_discs));
+
+    let mut corrections = Vec::new();
+    for &s in release.songs.iter() {
+        let mut c = TagUpdate::default();
+        if s.track_number == Some(0) {
+            c.track_number = Value::Remove;
+        }
+        if s.disc_number == Some(0) {
+            c.disc_number = Value::Remove;
+        }
+        let tracks_exceed = release
+            .songs
+            .iter()
+            .any(|o| o.disc_number == s.disc_number && exceeds(o.track_number, o.total_tracks));
+        let max_track = max_track(s.disc_number);
+        if tracks_exceed && s.total_tracks.is_some_and(|t| t < max_track) {
+            c.total_tracks = Value::Update(max_track);
+        }
+        if discs_exceed && s.total_discs.is_some_and(|t| t < max_disc) {
+            c.total_discs = Value::Update(max_disc);
+        }
+        if c != TagUpdate::default() {
+            corrections.push((s, c));
+        }
+    }
+    corrections
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn excluded_songs_are_not_retagged() {
+        let song = |path: &str| Song {
+            path: PathBuf::from(path),
+            has_artwork: true,
+            ..Default::default()
+        };
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/a.flac"),
+            song("/music/b.mp3"),
+            song("/music/Trusted/c.mp3"),
+            Song { artists: Arc::from(["Kept".to_owned()]), ..song("/music/d.mp3") },
+        ];
+        let mut checks = Checks::from(&index);
+        let filters = ["format:FLAC".parse().unwrap(), "path:trusted/*".parse().unwrap()];
+        checks.exclude_from_retagging(&filters);
+        checks.remove_embedded_artworks(&["artist:Kept".parse().unwrap()]);
+
+        let paths: Vec<&Path> =
+            checks.song_operations.iter().map(|o| o.song.path.as_path()).collect();
+        assert_eq!(paths, [Path::new("/music/b.mp3")]);
+        assert_eq!(checks.song_operations[0].reasons, ["artwork removal"]);
+    }
+
+    #[test]
+    fn compares_artists_within_the_scope() {
+        let song = |path: &str, artist: &str| Song {
+            path: PathBuf::from(path),
+            release_artists: Arc::from([artist.to_owned()]),
+            ..Default::default()
+        };
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/a.mp3", "Foo"),
+            song("/music/b.mp3", "Bar"),
+            song("/music/c.mp3", "foo"),
+            song("/music/d.mp3", "BAR"),
+            song("/music/e.mp3", "FOO"),
+            song("/music/f.mp3", "Bär"),
+        ];
+        let pairs = |scope| {
+            let mut checks = Checks::from(&index);
+            checks.similarity_scope = scope;
+            let mut pairs = Vec::new();
+            checks.check_inconsitent_release_artists(&mut |a, b| {
+                pairs.push((a.names[0].clone(), b.names[0].clone()));
+                Value::Unchanged
+            });
+            pairs
+        };
+        let pair = |a: &str, b: &str| (a.to_owned(), b.to_owned());
+
+        assert_eq!(
+            pairs(SimilarityScope::Library),
+            [
+                pair("Foo", "foo"),
+                pair("Foo", "FOO"),
+                pair("Bar", "BAR"),
+                pair("Bar", "Bär"),
+                pair("foo", "FOO"),
+                pair("BAR", "Bär")
+            ]
+        );
+        assert_eq!(
+            pairs(SimilarityScope::Letter('b')),
+            [pair("Bar", "BAR"), pair("Bar", "Bär"), pair("BAR", "Bär")]
+        );
+        assert_eq!(pairs(SimilarityScope::Artist("bar".into())), [pair("Bar", "BAR")]);
+    }
+
+    #[test]
+    fn reports_whitespace_in_names() {
+        let song = |path: &str, release: &str| Song {
+            path: PathBuf::from(path),
+            release_artists: Arc::from(["Foo".to_owned()]),
+            release: Arc::from(release),
+            ..Default::default()
+        };
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/a.mp3", "Bar "),
+            song("/music/b.mp3", "Baz  Qux"),
+            song("/music/c.mp3", "Fine"),
+        ];
+        let mut checks = Checks::from(&index);
+        let mut releases = Vec::new();
+        checks.check_whitespace(&mut |_, r| releases.push(r.name.to_string()));
+
+        assert_eq!(releases, ["Bar ", "Baz  Qux"]);
+        assert_eq!(checks.stats.whitespace.issues, 2);
+        assert!(checks.song_operations.is_empty());
+        assert_eq!(crate::fs::valid_os_str_dots(" Bar\t  Baz "), "Bar Baz");
+    }
+}
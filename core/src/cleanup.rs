@@ -0,0 +1,241 @@
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, info_span};
+
+use crate::fs::{
+    is_hidden, is_image_extension, matches_glob, DirDeletion, ExecOptions, FileDeletion, Fs,
+};
+use crate::index::foreign_marker;
+use crate::CancelToken;
+
+pub const DEFAULT_JUNK_PATTERNS: [&str; 4] = ["Thumbs.db", ".DS_Store", "desktop.ini", "*.nfo"];
+
+fn is_empty_dir(
+    fs: &impl Fs,
+    cleanup: &mut Cleanup,
+    dir: &Path,
+    depth: usize,
+    f: &mut impl FnMut(&Path),
+) -> bool {
+    if fs.is_file(dir) || cleanup.max_depth.is_some_and(|max| depth > max) {
+        return false;
+    };
+    if let Some(marker) = foreign_marker(&cleanup.foreign_markers, dir, |p| fs.exists(p)) {
+        debug!(path = %dir.display(), marker, "dir managed by another tool");
+        return false;
+    }
+
+    f(dir);
+
+    if let Ok(r) = fs.read_dir(dir) {
+        let mut junk = Vec::new();
+        let mut is_empty = true;
+        for p in r {
+            if fs.is_file(&p) && cleanup.is_junk(&p) {
+                junk.push(FileDeletion { path: p });
+            } else if cleanup.excluded_dirs.contains(&p)
+                || (!cleanup.include_hidden && is_hidden(&p))
+            {
+                is_empty = false;
+            } else {
+                is_empty &= is_empty_dir(fs, cleanup, &p, depth + 1, f);
+            }
+        }
+
+        if is_empty {
+            debug!(path = %dir.display(), junk = junk.len(), "empty dir");
+            cleanup.file_deletions.extend(junk);
+            cleanup.dir_deletions.push(DirDeletion { path: dir.to_owned() });
+            return true;
+        }
+    }
+
+    false
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cleanup {
+    pub dir_deletions: Vec<DirDeletion>,
+    /// Junk files inside of otherwise empty directories.
+    pub file_deletions: Vec<FileDeletion>,
+    pub music_dir: PathBuf,
+    /// Directories inside of the music dir that are never deleted, e.g. a nested output dir.
+    pub excluded_dirs: Vec<PathBuf>,
+    /// Directories containing one of these entries are managed by another tool and never
+    /// deleted, see [`MusicIndex::foreign_markers`](crate::MusicIndex::foreign_markers).
+    pub foreign_markers: Vec<String>,
+    /// Maximum depth of checked directories, directories directly inside of the music dir have a
+    /// depth of 1. Deeper directories are never considered empty.
+    pub max_depth: Option<usize>,
+    /// Check and delete directories whose name starts with a dot, otherwise hidden entries are
+    /// left alone and prevent their parent directory from being deleted.
+    pub include_hidden: bool,
+    /// Glob patterns of files that don't prevent a directory from being considered empty.
+    pub junk_patterns: Vec<String>,
+    /// Images don't prevent a directory from being considered empty and are deleted with it,
+    /// see [`ImagePolicy::Delete`](crate::ImagePolicy::Delete).
+    pub remove_images: bool,
+    /// Checked before each directory inside of the music dir.
+    pub cancel: CancelToken,
+    /// Only the directory directly inside of the music dir that contains this path is checked,
+    /// e.g. when organizing a single release.
+    pub scope: Option<PathBuf>,
+}
+
+impl From<PathBuf> for Cleanup {
+    fn from(music_dir: PathBuf) -> Self {
+        Self { music_dir, ..Default::default() }
+    }
+}
+
+impl Cleanup {
+    pub fn check(&mut self, fs: &impl Fs, f: &mut impl FnMut(&Path)) {
+        let _span = info_span!("cleanup", music_dir = %self.music_dir.display()).entered();
+        if let Ok(r) = fs.read_dir(&self.music_dir) {
+            for p in r {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                if self.excluded_dirs.contains(&p) || (!self.include_hidden && is_hidden(&p)) {
+                    continue;
+                }
+                if self.scope.as_ref().is_some_and(|s| !s.starts_with(&p)) {
+                    continue;
+                }
+                is_empty_dir(fs, self, &p, 1, f);
+            }
+        }
+    }
+
+    fn is_junk(&self, path: &Path) -> bool {
+        if self.remove_images && path.extension().is_some_and(is_image_extension) {
+            return true;
+        }
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else { return false };
+        self.junk_patterns.iter().any(|p| matches_glob(p, name))
+    }
+
+    /// Deletes the junk files, this has to happen before the directories are deleted.
+    pub fn execute_file_deletions(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        f: &mut impl FnMut(&FileDeletion, std::io::Result<()>),
+    ) {
+        for d in self.file_deletions.iter() {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let r = d.execute(fs, options.delete_type);
+            let failed = r.is_err();
+            f(d, r);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    }
+
+    pub fn execute_dir_deletions(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        f: &mut impl FnMut(&DirDeletion, std::io::Result<()>),
+    ) {
+        for d in self.dir_deletions.iter() {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let r = d.execute(fs, options.delete_type);
+            let failed = r.is_err();
+            f(d, r);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dir_deletions.is_empty() && self.file_deletions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeleteType, MemFs};
+
+    fn cleanup() -> Cleanup {
+        let mut cleanup = Cleanup::from(PathBuf::from("/music"));
+        cleanup.junk_patterns = DEFAULT_JUNK_PATTERNS.iter().map(|p| p.to_string()).collect();
+        cleanup
+    }
+
+    #[test]
+    fn finds_empty_dirs_with_junk() {
+        let fs = MemFs::new();
+        fs.add_dir("/music/empty/nested");
+        fs.add_file("/music/junk/Thumbs.db", "");
+        fs.add_file("/music/full/a.mp3", "a");
+        fs.add_file("/music/full/Thumbs.db", "");
+
+        let mut cleanup = cleanup();
+        cleanup.check(&fs, &mut |_| ());
+
+        let dirs: Vec<_> = cleanup.dir_deletions.iter().map(|d| d.path.as_path()).collect();
+        assert_eq!(
+            dirs,
+            [Path::new("/music/empty/nested"), Path::new("/music/empty"), Path::new("/music/junk")]
+        );
+        assert_eq!(cleanup.file_deletions, [FileDeletion { path: "/music/junk/Thumbs.db".into() }]);
+    }
+
+    #[test]
+    fn keeps_dirs_managed_by_other_tools() {
+        let fs = MemFs::new();
+        fs.add_dir("/music/Synced/.stfolder");
+        fs.add_dir("/music/Synced/empty");
+
+        let mut cleanup = Cleanup { include_hidden: true, ..cleanup() };
+        cleanup.foreign_markers = vec![".stfolder".to_owned()];
+        cleanup.check(&fs, &mut |_| ());
+
+        assert!(cleanup.is_empty());
+    }
+
+    #[test]
+    fn only_checks_the_scope() {
+        let fs = MemFs::new();
+        fs.add_dir("/music/Foo/Bar");
+        fs.add_dir("/music/Baz/Qux");
+
+        let mut cleanup = Cleanup { scope: Some("/music/Foo/Bar".into()), ..cleanup() };
+        cleanup.check(&fs, &mut |_| ());
+
+        let dirs: Vec<_> = cleanup.dir_deletions.iter().map(|d| d.path.as_path()).collect();
+        assert_eq!(dirs, [Path::new("/music/Foo/Bar"), Path::new("/music/Foo")]);
+    }
+
+    #[test]
+    fn deletes_files_before_dirs() {
+        for delete_type in [DeleteType::Remove, DeleteType::Trash] {
+            let fs = MemFs::new();
+            fs.add_file("/music/junk/Thumbs.db", "");
+            fs.add_file("/music/full/a.mp3", "a");
+            let mut cleanup = cleanup();
+            cleanup.check(&fs, &mut |_| ());
+            let options = ExecOptions { delete_type, ..Default::default() };
+
+            let mut failures = 0;
+            cleanup
+                .execute_file_deletions(&fs, &options, &mut |_, r| failures += r.is_err() as u32);
+            cleanup.execute_dir_deletions(&fs, &options, &mut |_, r| failures += r.is_err() as u32);
+
+            assert_eq!(failures, 0);
+            assert_eq!(
+                fs.paths(),
+                [Path::new("/music"), Path::new("/music/full"), Path::new("/music/full/a.mp3")]
+            );
+            assert_eq!(fs.trashed().is_empty(), delete_type == DeleteType::Remove);
+        }
+    }
+}
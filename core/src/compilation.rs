@@ -0,0 +1,107 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use tracing::{info, info_span};
+
+use crate::{util, Checks, Song, Value};
+
+/// The release artist of compilations.
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+
+/// The minimum number of distinct artists of a compilation, which also have to make up at least
+/// half of its songs.
+const MIN_ARTISTS: usize = 3;
+
+/// A release whose songs have many distinct artists but no release artists, so each song would
+/// get a dir of its own artists. See [`Checks::check_various_artists`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Compilation<'a> {
+    pub release: &'a str,
+    /// The dir containing the songs.
+    pub dir: &'a Path,
+    /// Sorted by disc and track number.
+    pub songs: Vec<&'a Song>,
+    pub num_artists: usize,
+}
+
+impl Checks<'_> {
+    /// Checks for releases whose songs are in the same dir and have many distinct artists, but
+    /// no release artists, since songs without release artists are grouped by their artists.
+    /// If `f` accepts the compilation the release artists of all songs are set to
+    /// [`VARIOUS_ARTISTS`].
+    pub fn check_various_artists(&mut self, f: &mut impl FnMut(&Compilation) -> bool) {
+        let _span = info_span!("check", check = "various_artists").entered();
+        let mut releases: BTreeMap<(&Path, &str), Vec<&Song>> = BTreeMap::new();
+        for s in self.index.songs.iter() {
+            let Some(dir) = s.path.parent() else { continue };
+            releases.entry((dir, &s.release)).or_default().push(s);
+        }
+
+        for ((dir, release), mut songs) in releases {
+            if self.cancel.is_cancelled() {
+                return;
+            }
+            // the release artists are the song artists if the release artists tag is missing
+            if songs.iter().any(|s| s.release_artists != s.artists) {
+                continue;
+            }
+            let num_artists = songs.iter().map(|s| &s.artists).collect::<HashSet<_>>().len();
+            if num_artists < MIN_ARTISTS || num_artists * 2 < songs.len() {
+                continue;
+            }
+
+            songs.sort_by_key(|s| (s.disc_number, s.track_number));
+            let compilation = Compilation { release, dir, songs, num_artists };
+            info!(release, dir = %dir.display(), num_artists, "compilation without release artists");
+            self.stats.various_artists.issues += 1;
+            if !f(&compilation) {
+                continue;
+            }
+            self.stats.various_artists.updates += compilation.songs.len();
+            for s in compilation.songs {
+                util::update_tag(
+                    &mut self.song_operations,
+                    &self.retag_excluded,
+                    s,
+                    "various artists",
+                    |t| t.release_artists = Value::Update(vec![VARIOUS_ARTISTS.to_owned()]),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::testing::song;
+    use crate::MusicIndex;
+
+    #[test]
+    fn finds_releases_with_many_artists() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/hits/1.mp3", "Hits", "A"),
+            song("/music/hits/2.mp3", "Hits", "B"),
+            song("/music/hits/3.mp3", "Hits", "C"),
+            song("/music/hits/4.mp3", "Hits", "A"),
+            song("/music/album/1.mp3", "Album", "A"),
+            song("/music/album/2.mp3", "Album", "A"),
+            song("/music/album/3.mp3", "Album", "B"),
+        ];
+        let mut checks = Checks::from(&index);
+
+        let mut found = Vec::new();
+        checks.check_various_artists(&mut |c| {
+            found.push((c.release.to_owned(), c.num_artists));
+            true
+        });
+
+        assert_eq!(found, [("Hits".to_owned(), 3)]);
+        assert_eq!(checks.stats.various_artists.updates, 4);
+        let update = checks.song_operations[0].tag_update.as_ref().unwrap();
+        assert_eq!(update.release_artists, Value::Update(vec![VARIOUS_ARTISTS.to_owned()]));
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::Path;
+
+use crate::MusicIndex;
+
+/// Images with these names are covers of their dir, compared case insensitively without the
+/// extension.
+const COVER_STEMS: [&str; 5] = ["cover", "folder", "front", "poster", "albumart"];
+
+/// The name media servers look for first when picking the cover of a release dir.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CoverName {
+    /// Covers keep their name.
+    #[default]
+    Keep,
+    /// `cover.jpg`, preferred by Navidrome.
+    Cover,
+    /// `folder.jpg`, preferred by Kodi and Jellyfin.
+    Folder,
+    /// `poster.jpg`, preferred by Plex.
+    Poster,
+}
+
+impl CoverName {
+    /// The file name without the extension, `None` if covers keep their name.
+    pub fn stem(&self) -> Option<&'static str> {
+        match self {
+            Self::Keep => None,
+            Self::Cover => Some("cover"),
+            Self::Folder => Some("folder"),
+            Self::Poster => Some("poster"),
+        }
+    }
+}
+
+/// The new file names of covers that don't have the [`CoverName`] yet, keeping their extension.
+/// Dirs with multiple covers, e.g. `cover.jpg` and `folder.jpg`, are left as they are, since
+/// renaming one could overwrite the other.
+pub(crate) fn cover_renames(index: &MusicIndex, name: CoverName) -> HashMap<&Path, OsString> {
+    let Some(stem) = name.stem() else { return HashMap::new() };
+    let is_cover = |p: &Path| {
+        let s = p.file_stem().unwrap_or_default().to_string_lossy().to_lowercase();
+        COVER_STEMS.contains(&s.as_str())
+    };
+
+    let mut covers: HashMap<&Path, Vec<&Path>> = HashMap::new();
+    for image in index.images.iter().filter(|i| is_cover(i)) {
+        let Some(dir) = image.parent() else { continue };
+        covers.entry(dir).or_default().push(image);
+    }
+    (covers.into_values())
+        .filter_map(|c| <[&Path; 1]>::try_from(c).ok())
+        .filter(|[c]| c.file_stem().is_some_and(|s| s != stem))
+        .map(|[c]| {
+            let mut file_name = OsString::from(stem);
+            if let Some(ext) = c.extension() {
+                file_name.push(".");
+                file_name.push(ext);
+            }
+            (c, file_name)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{ChangeOptions, Changes, Checks, MemFs, Song};
+
+    #[test]
+    fn renames_covers_for_media_servers() {
+        let song = |path: &str, release: &str| Song {
+            path: PathBuf::from(path),
+            track_number: Some(1),
+            release_artists: Arc::from(["Foo".to_owned()]),
+            artists: Arc::from(["Foo".to_owned()]),
+            release: Arc::from(release),
+            title: "One".into(),
+            ..Default::default()
+        };
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/Bar/a.mp3", "Bar"),
+            song("/music/Foo/Baz/01 - Foo - One.mp3", "Baz"),
+            song("/music/Qux/a.mp3", "Qux"),
+        ];
+        index.images = ["/music/Bar/Cover.PNG", "/music/Foo/Baz/front.jpg", "/music/Qux/cover.jpg"]
+            .into_iter()
+            .chain(["/music/Qux/folder.jpg", "/music/Qux/back.jpg"])
+            .map(PathBuf::from)
+            .collect();
+        let options = ChangeOptions {
+            cover_name: CoverName::Folder,
+            ..ChangeOptions::from(PathBuf::from("/music"))
+        };
+
+        let changes = Changes::generate(&MemFs::new(), Checks::from(&index), &options);
+
+        let new_path = |p: &str| {
+            let op = changes.file_operations.iter().find(|o| o.old_path == Path::new(p));
+            op.map(|o| o.new_path.to_string_lossy().into_owned())
+        };
+        assert_eq!(new_path("/music/Bar/Cover.PNG").unwrap(), "/music/Foo/Bar/folder.PNG");
+        // renamed in place, since the songs stay
+        assert_eq!(new_path("/music/Foo/Baz/front.jpg").unwrap(), "/music/Foo/Baz/folder.jpg");
+        // multiple covers
+        assert_eq!(new_path("/music/Qux/cover.jpg").unwrap(), "/music/Foo/Qux/cover.jpg");
+        assert_eq!(new_path("/music/Qux/back.jpg").unwrap(), "/music/Foo/Qux/back.jpg");
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tracing::{debug, info_span};
+
+use crate::audio::copy_audio;
+use crate::changes::temp_path;
+use crate::{
+    CancelToken, Codec, ExecOptions, FileDeletion, Fs, MusicIndex, Song, TagUpdate, Value,
+};
+
+/// What happens to the copies of a file with identical contents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DedupAction {
+    /// Deletes the copies, using the [`ExecOptions::delete_type`].
+    #[default]
+    Delete,
+    /// Replaces the copies with hard links to the original, so all paths stay valid but the data
+    /// is only stored once. The original and its copies have to be on the same filesystem. Tags
+    /// are merged onto the original before the copies are linked to it.
+    Hardlink,
+}
+
+/// Files of the index with byte-identical contents, regardless of their names. Files are grouped
+/// by size first and only files with the same size are hashed using blake3. Songs with identical
+/// audio data but different tags are found as well, by hashing the audio data of songs with the
+/// same codec and duration. Songs whose tags conflict, e.g. the same recording on an album and on
+/// a compilation, aren't duplicates of each other.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Duplicates {
+    /// Sorted by the path of the original.
+    pub groups: Vec<DuplicateGroup>,
+    /// Checked before each hashed file.
+    pub cancel: CancelToken,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// The size of each file in bytes, copies with different tags can differ slightly.
+    pub size: u64,
+    /// The file that is kept, the first one by path. Of songs with different tags the one with
+    /// artwork and the most tags is kept.
+    pub original: PathBuf,
+    /// Sorted by path, copies that are already hard links to the original aren't included, unless
+    /// tags are merged onto the original, which splits it from its links.
+    pub copies: Vec<PathBuf>,
+    /// Tags that are missing on the original but set on a copy, which are merged onto the
+    /// original before the copies are removed.
+    pub tag_update: Option<TagUpdate>,
+}
+
+impl Duplicates {
+    /// Finds duplicates among all files of the index, previous groups are replaced. `f` is called
+    /// before each file that is hashed.
+    pub fn find(&mut self, fs: &impl Fs, index: &MusicIndex, f: &mut impl FnMut(&Path)) {
+        let _span = info_span!("dedup", music_dir = %index.music_dir.display()).entered();
+        self.groups.clear();
+
+        let paths = (index.songs.iter().map(|s| &s.path))
+            .chain(index.videos.iter().map(|v| &v.path))
+            .chain(index.audiobooks.iter().map(|a| &a.path))
+            .chain(index.images.iter())
+            .chain(index.companions.iter())
+            .chain(index.extras.iter())
+            .chain(index.playlists.iter())
+            .chain(index.unknown.iter());
+        let mut sizes: HashMap<u64, Vec<&Path>> = HashMap::new();
+        for p in paths {
+            match fs.file_info(p) {
+                // empty files are all identical, but never a waste of space
+                Ok((0, _)) => (),
+                Ok((len, _)) => sizes.entry(len).or_default().push(p),
+                Err(e) => debug!(path = %p.display(), error = %e, "skipping unreadable file"),
+            }
+        }
+
+        for (size, paths) in sizes.into_iter().filter(|(_, paths)| paths.len() > 1) {
+            let mut hashes: HashMap<blake3::Hash, Vec<&Path>> = HashMap::new();
+            for p in paths {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                f(p);
+                match hash_file(fs, p) {
+                    Ok(hash) => hashes.entry(hash).or_default().push(p),
+                    Err(e) => debug!(path = %p.display(), error = %e, "skipping unreadable file"),
+                }
+            }
+
+            for mut paths in hashes.into_values().filter(|paths| paths.len() > 1) {
+                paths.sort();
+                let original = paths[0];
+                let copies: Vec<PathBuf> = (paths[1..].iter())
+                    .filter(|p| !fs.same_file(original, p))
+                    .map(|p| p.to_path_buf())
+                    .collect();
+                if !copies.is_empty() {
+                    debug!(original = %original.display(), ?copies, "duplicates");
+                    self.groups.push(DuplicateGroup {
+                        size,
+                        original: original.to_owned(),
+                        copies,
+                        tag_update: None,
+                    });
+                }
+            }
+        }
+
+        self.find_retagged(fs, index, f);
+        self.groups.sort_by(|a, b| a.original.cmp(&b.original));
+    }
+
+    /// Finds songs with identical audio data that aren't byte-identical, because their tags
+    /// differ. Songs without a known duration are skipped.
+    fn find_retagged(&mut self, fs: &impl Fs, index: &MusicIndex, f: &mut impl FnMut(&Path)) {
+        let grouped: HashSet<&Path> = (self.groups.iter())
+            .flat_map(|g| std::iter::once(&g.original).chain(g.copies.iter()))
+            .map(PathBuf::as_path)
+            .collect();
+        let mut streams: HashMap<(Option<Codec>, Duration), Vec<&Song>> = HashMap::new();
+        for s in index.songs.iter().filter(|s| !grouped.contains(s.path.as_path())) {
+            if let Some(duration) = s.audio.duration {
+                streams.entry((s.audio.codec, duration)).or_default().push(s);
+            }
+        }
+
+        for songs in streams.into_values().filter(|songs| songs.len() > 1) {
+            let mut hashes: HashMap<blake3::Hash, Vec<(&Song, u64)>> = HashMap::new();
+            for s in songs {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                f(&s.path);
+                match hash_audio(fs, &s.path) {
+                    Ok((hash, size)) => hashes.entry(hash).or_default().push((s, size)),
+                    Err(e) => {
+                        debug!(path = %s.path.display(), error = %e, "skipping unreadable file")
+                    }
+                }
+            }
+
+            for mut songs in hashes.into_values().filter(|songs| songs.len() > 1) {
+                songs.sort_by(|(a, _), (b, _)| {
+                    (b.has_artwork.cmp(&a.has_artwork))
+                        .then(num_tags(b).cmp(&num_tags(a)))
+                        .then(a.path.cmp(&b.path))
+                });
+                let (original, size) = songs[0];
+                let (links, copies): (Vec<&Song>, Vec<&Song>) = (songs[1..].iter())
+                    .map(|(s, _)| *s)
+                    .partition(|s| fs.same_file(&original.path, &s.path));
+                // copies conflicting with the original or with tags merged from earlier ones
+                let mut merged = original.clone();
+                let copies: Vec<&Song> = (copies.into_iter())
+                    .filter(|c| {
+                        if conflicts(&merged, c) {
+                            debug!(path = %c.path.display(), "copy with conflicting tags");
+                            return false;
+                        }
+                        if let Some(u) = merged_tags(&merged, &[c]) {
+                            u.apply(&mut merged);
+                        }
+                        true
+                    })
+                    .collect();
+                if copies.is_empty() {
+                    continue;
+                }
+                let tag_update = merged_tags(original, &copies);
+                debug!(original = %original.path.display(), ?tag_update, "retagged duplicates");
+                // merging the tags splits the original from its links
+                let links = links.into_iter().filter(|_| tag_update.is_some());
+                let mut copies: Vec<PathBuf> =
+                    copies.iter().copied().chain(links).map(|s| s.path.clone()).collect();
+                copies.sort();
+                self.groups.push(DuplicateGroup {
+                    size,
+                    original: original.path.clone(),
+                    copies,
+                    tag_update,
+                });
+            }
+        }
+    }
+
+    pub fn execute(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        action: DedupAction,
+        f: &mut impl FnMut(&DuplicateGroup, &Path, std::io::Result<()>),
+    ) {
+        for g in self.groups.iter() {
+            if let Some(u) = &g.tag_update {
+                if options.cancel.is_cancelled() {
+                    return;
+                }
+                let r = (u.execute(&g.original, &options.artist_separators))
+                    .map_err(|e| std::io::Error::other(e.to_string()));
+                let failed = r.is_err();
+                f(g, &g.original, r);
+                // the copies are kept, since their tags couldn't be merged
+                if failed && options.fail_fast {
+                    return;
+                } else if failed {
+                    continue;
+                }
+            }
+            for c in g.copies.iter() {
+                if options.cancel.is_cancelled() {
+                    return;
+                }
+                let r = match action {
+                    DedupAction::Delete => {
+                        FileDeletion { path: c.clone() }.execute(fs, options.delete_type)
+                    }
+                    DedupAction::Hardlink => {
+                        // the copy is only replaced once the link was created
+                        let temp = temp_path(c);
+                        fs.hard_link(&g.original, &temp).and_then(|_| fs.rename(&temp, c))
+                    }
+                };
+                let failed = r.is_err();
+                f(g, c, r);
+                if failed && options.fail_fast {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// The number of groups whose tags are merged onto the original.
+    pub fn num_merges(&self) -> usize {
+        self.groups.iter().filter(|g| g.tag_update.is_some()).count()
+    }
+
+    /// The number of copies of all groups.
+    pub fn num_copies(&self) -> usize {
+        self.groups.iter().map(|g| g.copies.len()).sum()
+    }
+
+    /// The number of bytes used by the copies.
+    pub fn wasted_size(&self) -> u64 {
+        self.groups.iter().map(|g| g.size * g.copies.len() as u64).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+/// Hashes the file in chunks, since the candidates include videos of several gigabytes.
+fn hash_file(fs: &impl Fs, path: &Path) -> std::io::Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut fs.open(path)?, &mut hasher)?;
+    Ok(hasher.finalize())
+}
+
+/// Hashes the audio data of a song in chunks like [`hash_file`], files that can't be parsed are
+/// hashed as they are. Returns the hash and the size of the file.
+pub(crate) fn hash_audio(fs: &impl Fs, path: &Path) -> std::io::Result<(blake3::Hash, u64)> {
+    let (size, _) = fs.file_info(path)?;
+    let mut hasher = blake3::Hasher::new();
+    match copy_audio(path, size, &mut fs.open(path)?, &mut hasher) {
+        Some(()) => Ok((hasher.finalize(), size)),
+        None => Ok((hash_file(fs, path)?, size)),
+    }
+}
+
+/// Whether a tag is set on both songs with different values, in which case neither of them is a
+/// copy of the other.
+fn conflicts(a: &Song, b: &Song) -> bool {
+    fn differ<T: PartialEq>(a: Option<T>, b: Option<T>) -> bool {
+        matches!((a, b), (Some(a), Some(b)) if a != b)
+    }
+    fn set<T: ?Sized + AsRef<[U]>, U>(v: &T) -> Option<&[U]> {
+        let v = v.as_ref();
+        (!v.is_empty()).then_some(v)
+    }
+    fn text(s: &str) -> Option<&str> {
+        (!s.is_empty()).then_some(s)
+    }
+
+    let nums = |s: &Song| [s.track_number, s.total_tracks, s.disc_number, s.total_discs];
+    differ(set(&a.release_artists), set(&b.release_artists))
+        || differ(set(&a.artists), set(&b.artists))
+        || differ(text(&a.release), text(&b.release))
+        || differ(text(&a.title), text(&b.title))
+        || differ(a.genre.as_ref(), b.genre.as_ref())
+        || differ(a.year, b.year)
+        || nums(a).into_iter().zip(nums(b)).any(|(a, b)| differ(a, b))
+        || a.extra.iter().any(|(k, v)| differ(Some(v), b.extra.get(k)))
+}
+
+/// The number of tags set on the song, see [`merged_tags`].
+fn num_tags(s: &Song) -> usize {
+    let nums = [s.track_number, s.total_tracks, s.disc_number, s.total_discs];
+    [!s.release_artists.is_empty(), !s.artists.is_empty(), !s.release.is_empty()]
+        .into_iter()
+        .chain([!s.title.is_empty(), s.genre.is_some(), s.year.is_some()])
+        .chain(nums.map(|n| n.is_some()))
+        .filter(|&b| b)
+        .count()
+        + s.extra.len()
+}
+
+/// Fills the tags missing on the original with the value of the first copy that has it, tags
+/// that are set on the original are never changed. Artwork isn't merged, instead the copy with
+/// artwork is kept. Returns `None` if nothing would be merged.
+fn merged_tags(original: &Song, copies: &[&Song]) -> Option<TagUpdate> {
+    fn fill<T>(value: &mut Value<T>, missing: bool, copies: &[&Song], f: fn(&Song) -> Option<T>) {
+        if let Some(v) = copies.iter().filter(|_| missing).find_map(|s| f(s)) {
+            *value = Value::Update(v);
+        }
+    }
+
+    let o = original;
+    let mut u = TagUpdate::default();
+    fill(&mut u.release_artists, o.release_artists.is_empty(), copies, |s| {
+        (!s.release_artists.is_empty()).then(|| s.release_artists.to_vec())
+    });
+    fill(&mut u.artists, o.artists.is_empty(), copies, |s| {
+        (!s.artists.is_empty()).then(|| s.artists.to_vec())
+    });
+    fill(&mut u.release, o.release.is_empty(), copies, |s| {
+        (!s.release.is_empty()).then(|| s.release.to_string())
+    });
+    fill(&mut u.title, o.title.is_empty(), copies, |s| {
+        (!s.title.is_empty()).then(|| s.title.clone())
+    });
+    fill(&mut u.genre, o.genre.is_none(), copies, |s| s.genre.clone());
+    fill(&mut u.year, o.year.is_none(), copies, |s| s.year);
+    fill(&mut u.track_number, o.track_number.is_none(), copies, |s| s.track_number);
+    fill(&mut u.total_tracks, o.total_tracks.is_none(), copies, |s| s.total_tracks);
+    fill(&mut u.disc_number, o.disc_number.is_none(), copies, |s| s.disc_number);
+    fill(&mut u.total_discs, o.total_discs.is_none(), copies, |s| s.total_discs);
+    for (name, value) in copies.iter().flat_map(|s| s.extra.iter()) {
+        if !o.extra.contains_key(name) {
+            u.extra.entry(name.clone()).or_insert_with(|| Value::Update(value.clone()));
+        }
+    }
+
+    (u != TagUpdate::default()).then_some(u)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemFs, Song};
+
+    #[test]
+    fn finds_identical_files_and_deletes_copies() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a/01.mp3", "same");
+        fs.add_file("/music/b/01.mp3", "same");
+        fs.add_file("/music/b/02.mp3", "diff");
+        fs.add_file("/music/a/cover.jpg", "same");
+        fs.add_file("/music/empty.txt", "");
+        fs.add_file("/music/empty.log", "");
+
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = ["/music/b/01.mp3", "/music/a/01.mp3", "/music/b/02.mp3"]
+            .iter()
+            .map(|p| Song { path: PathBuf::from(p), ..Default::default() })
+            .collect();
+        index.images = vec![PathBuf::from("/music/a/cover.jpg")];
+        index.unknown = vec![PathBuf::from("/music/empty.txt"), PathBuf::from("/music/empty.log")];
+
+        let mut duplicates = Duplicates::default();
+        duplicates.find(&fs, &index, &mut |_| ());
+
+        assert_eq!(
+            duplicates.groups,
+            [DuplicateGroup {
+                size: 4,
+                original: PathBuf::from("/music/a/01.mp3"),
+                copies: vec![PathBuf::from("/music/a/cover.jpg"), PathBuf::from("/music/b/01.mp3")],
+                tag_update: None,
+            }]
+        );
+        assert_eq!(duplicates.wasted_size(), 8);
+
+        duplicates
+            .execute(&fs, &ExecOptions::default(), DedupAction::Delete, &mut |_, _, r| r.unwrap());
+        assert!(fs.exists(Path::new("/music/a/01.mp3")));
+        assert!(!fs.exists(Path::new("/music/b/01.mp3")));
+        assert!(!fs.exists(Path::new("/music/a/cover.jpg")));
+    }
+
+    #[test]
+    fn merges_tags_of_copies_with_identical_audio() {
+        let id3 = |tag: &[u8]| {
+            let mut data = [b"ID3\x04\0\0\0\0\0".as_slice(), &[tag.len() as u8], tag].concat();
+            data.extend_from_slice(b"audio frames");
+            data
+        };
+        let fs = MemFs::new();
+        fs.add_file("/music/a.mp3", id3(b"artwork"));
+        fs.add_file("/music/b.mp3", id3(b"year and genre"));
+
+        let song = |path: &str| Song {
+            path: PathBuf::from(path),
+            title: "One".into(),
+            audio: crate::AudioInfo {
+                codec: Some(Codec::Mp3),
+                duration: Some(Duration::from_secs(1)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            Song { has_artwork: true, ..song("/music/a.mp3") },
+            Song { year: Some(2004), genre: Some("Rock".into()), ..song("/music/b.mp3") },
+        ];
+
+        let mut duplicates = Duplicates::default();
+        duplicates.find(&fs, &index, &mut |_| ());
+
+        let expected = TagUpdate {
+            year: Value::Update(2004),
+            genre: Value::Update("Rock".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            duplicates.groups,
+            [DuplicateGroup {
+                size: 29,
+                original: PathBuf::from("/music/a.mp3"),
+                copies: vec![PathBuf::from("/music/b.mp3")],
+                tag_update: Some(expected),
+            }]
+        );
+    }
+
+    #[test]
+    fn keeps_copies_with_conflicting_tags() {
+        let fs = MemFs::new();
+        fs.add_file("/music/album.mp3", b"ID3\x04\0\0\0\0\0\x05Albumaudio frames".to_vec());
+        fs.add_file("/music/compilation.mp3", b"ID3\x04\0\0\0\0\0\x04Hitsaudio frames".to_vec());
+        fs.add_file("/music/untagged.mp3", b"ID3\x04\0\0\0\0\0\0audio frames".to_vec());
+        let song = |path: &str, release: &str, track_number| Song {
+            path: PathBuf::from(path),
+            title: "One".into(),
+            release: release.into(),
+            track_number,
+            audio: crate::AudioInfo {
+                codec: Some(Codec::Mp3),
+                duration: Some(Duration::from_secs(1)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/album.mp3", "Album", Some(3)),
+            song("/music/compilation.mp3", "Hits", Some(12)),
+            song("/music/untagged.mp3", "", None),
+        ];
+
+        let mut duplicates = Duplicates::default();
+        duplicates.find(&fs, &index, &mut |_| ());
+
+        assert_eq!(duplicates.groups.len(), 1);
+        assert_eq!(duplicates.groups[0].original, Path::new("/music/album.mp3"));
+        assert_eq!(duplicates.groups[0].copies, [PathBuf::from("/music/untagged.mp3")]);
+        assert_eq!(duplicates.groups[0].tag_update, None);
+    }
+}
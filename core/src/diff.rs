@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::changes::release_order;
+use crate::{MusicIndex, Song};
+
+/// The songs of two libraries that are missing in the other one, compared by their release
+/// artists, release, disc and track number and title, case insensitively. File names and
+/// formats are ignored, so a library that was reorganized or transcoded has no differences.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LibraryDiff {
+    pub only_in_a: Vec<ReleaseDiff>,
+    pub only_in_b: Vec<ReleaseDiff>,
+}
+
+/// The missing songs of a release, sorted by disc and track number.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReleaseDiff {
+    pub release_artists: Arc<[String]>,
+    pub release: Arc<str>,
+    pub songs: Vec<Song>,
+    /// Whether the release is missing entirely, not just some of its songs.
+    pub complete: bool,
+}
+
+/// Release artists, release, disc and track number and title.
+type SongKey = (String, String, u16, u16, String);
+
+fn song_key(song: &Song) -> SongKey {
+    let (artists, release, disc, track) = release_order(song, None);
+    (artists, release, disc, track, song.title.to_lowercase())
+}
+
+impl LibraryDiff {
+    pub fn generate(a: &MusicIndex, b: &MusicIndex) -> Self {
+        Self { only_in_a: missing_releases(a, b), only_in_b: missing_releases(b, a) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty()
+    }
+}
+
+/// The songs of `index` that aren't in `other`, grouped by release.
+fn missing_releases(index: &MusicIndex, other: &MusicIndex) -> Vec<ReleaseDiff> {
+    let other_songs: HashSet<SongKey> = other.songs.iter().map(song_key).collect();
+    let other_releases: HashSet<(&str, &str)> =
+        other_songs.iter().map(|(artists, release, ..)| (&**artists, &**release)).collect();
+
+    let mut songs: Vec<(SongKey, &Song)> = index.songs.iter().map(|s| (song_key(s), s)).collect();
+    songs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut releases = Vec::new();
+    for release in songs.chunk_by(|(a, _), (b, _)| (&a.0, &a.1) == (&b.0, &b.1)) {
+        let missing: Vec<Song> = release
+            .iter()
+            .filter(|(key, _)| !other_songs.contains(key))
+            .map(|(_, s)| (*s).clone())
+            .collect();
+        let Some(first) = missing.first() else { continue };
+        let (artists, name, ..) = &release[0].0;
+        releases.push(ReleaseDiff {
+            release_artists: Arc::clone(&first.release_artists),
+            release: Arc::clone(&first.release),
+            complete: !other_releases.contains(&(&**artists, &**name)),
+            songs: missing,
+        });
+    }
+    releases
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::testing::{song, SongTags};
+
+    #[test]
+    fn compares_songs_by_tags() {
+        let mut a = MusicIndex::from(PathBuf::from("/a"));
+        a.songs = vec![
+            song("/a/1.mp3", "Bar", "Foo").with_track(1, None).with_title("One"),
+            song("/a/2.mp3", "Bar", "Foo").with_track(2, None).with_title("Two"),
+            song("/a/3.mp3", "Baz", "Foo").with_track(1, None).with_title("Three"),
+        ];
+        let mut b = MusicIndex::from(PathBuf::from("/b"));
+        b.songs = vec![song("/b/Foo/bar/01 - one.flac", "bar", "Foo")
+            .with_track(1, None)
+            .with_title("one")];
+
+        let diff = LibraryDiff::generate(&a, &b);
+        assert!(diff.only_in_b.is_empty());
+        let releases: Vec<_> = diff
+            .only_in_a
+            .iter()
+            .map(|r| (&*r.release, r.complete, r.songs.iter().map(|s| &*s.title).collect()))
+            .collect();
+        assert_eq!(releases, [("Bar", false, vec!["Two"]), ("Baz", true, vec!["Three"])]);
+    }
+}
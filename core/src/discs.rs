@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+
+use tracing::{debug, info_span};
+
+use crate::{util, Checks, Song, SongOperation, TagUpdate, Value};
+
+/// The disc, track and total tracks of a song, considering updates queued by earlier checks.
+fn numbers(song: &Song, song_operations: &[SongOperation]) -> (u16, Option<u16>, Option<u16>) {
+    let update = (song_operations.iter())
+        .find(|o| o.song.path == song.path)
+        .and_then(|o| o.tag_update.as_ref());
+    let value = |f: fn(&TagUpdate) -> &Value<u16>, old: Option<u16>| {
+        let new = update.and_then(|t| f(t).num_value());
+        new.or(old).filter(|&n| n != 0)
+    };
+    (
+        value(|t| &t.disc_number, song.disc_number).unwrap_or(1),
+        value(|t| &t.track_number, song.track_number),
+        value(|t| &t.total_tracks, song.total_tracks),
+    )
+}
+
+impl Checks<'_> {
+    /// Renumbers the tracks of releases with multiple discs sequentially across the discs and
+    /// removes the disc numbers and totals, for players that ignore disc numbers. E.g. track 1
+    /// of disc 2 becomes track 13 if disc 1 has 12 tracks. The tracks of a disc are the highest
+    /// of its total tracks and track numbers, so missing songs don't shift the following discs.
+    /// Releases with songs without track numbers are skipped.
+    pub fn flatten_discs(&mut self) {
+        let _span = info_span!("check", check = "flatten_discs").entered();
+        for ar in self.artists.iter() {
+            'release: for rl in ar.releases.iter() {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                let mut discs: BTreeMap<u16, Vec<(&Song, u16)>> = BTreeMap::new();
+                let mut disc_tracks: BTreeMap<u16, u16> = BTreeMap::new();
+                for &s in rl.songs.iter() {
+                    let (disc, track, total) = numbers(s, &self.song_operations);
+                    let Some(track) = track else { continue 'release };
+                    discs.entry(disc).or_default().push((s, track));
+                    let tracks = disc_tracks.entry(disc).or_default();
+                    *tracks = (*tracks).max(track).max(total.unwrap_or(0));
+                }
+                if discs.len() < 2 {
+                    continue;
+                }
+                debug!(release = %rl.name, discs = discs.len(), "flattening discs");
+                self.stats.flattened_discs.issues += 1;
+
+                let total = disc_tracks.values().fold(0u16, |a, &b| a.saturating_add(b));
+                let mut offset = 0u16;
+                for (disc, songs) in discs {
+                    for (s, track) in songs {
+                        util::update_tag(
+                            &mut self.song_operations,
+                            &self.retag_excluded,
+                            s,
+                            "flattened discs",
+                            |t| {
+                                t.track_number = Value::Update(offset.saturating_add(track));
+                                t.total_tracks = Value::Update(total);
+                                t.disc_number = Value::Remove;
+                                t.total_discs = Value::Remove;
+                            },
+                        );
+                        self.stats.flattened_discs.updates += 1;
+                    }
+                    offset = offset.saturating_add(disc_tracks[&disc]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::testing::{song, SongTags};
+    use crate::MusicIndex;
+
+    #[test]
+    fn renumbers_tracks_across_discs() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        // track 2 of disc 1 is missing
+        index.songs = vec![
+            song("/music/2-1.mp3", "Bar", "Foo").with_disc(2, Some(2)).with_track(1, Some(2)),
+            song("/music/1-1.mp3", "Bar", "Foo").with_disc(1, Some(2)).with_track(1, Some(3)),
+            song("/music/1-3.mp3", "Bar", "Foo").with_disc(1, Some(2)).with_track(3, Some(3)),
+            song("/music/2-2.mp3", "Bar", "Foo").with_disc(2, Some(2)).with_track(2, Some(2)),
+        ];
+        let mut checks = Checks::from(&index);
+        checks.flatten_discs();
+
+        let track = |path: &str| {
+            let op = checks.song_operations.iter().find(|o| o.song.path == Path::new(path));
+            let update = op.unwrap().tag_update.as_ref().unwrap();
+            assert_eq!(update.total_tracks, Value::Update(5));
+            assert_eq!(update.disc_number, Value::Remove);
+            update.track_number
+        };
+        assert_eq!(track("/music/1-3.mp3"), Value::Update(3));
+        assert_eq!(track("/music/2-1.mp3"), Value::Update(4));
+        assert_eq!(track("/music/2-2.mp3"), Value::Update(5));
+        assert_eq!(checks.stats.flattened_discs, crate::CheckCount { issues: 1, updates: 4 });
+    }
+}
@@ -0,0 +1,168 @@
+use tracing::{info, info_span};
+
+use crate::{util, Checks, Release, ReleaseArtists, Value};
+
+/// The kind of edition a release name suffix like `(Remastered 2014)` denotes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edition {
+    /// The year of the remaster, if present.
+    Remaster(Option<u16>),
+    Deluxe,
+    Expanded,
+    Live,
+}
+
+impl Edition {
+    /// Recognizes the text of a suffix without its brackets, e.g. `Remastered 2014`.
+    fn parse(text: &str) -> Option<Self> {
+        let lower = text.trim().to_lowercase();
+        if lower.contains("remaster") {
+            let year = (lower.split(|c: char| !c.is_ascii_digit()))
+                .filter(|n| n.len() == 4)
+                .find_map(|n| n.parse().ok().filter(|y| (1900..2100).contains(y)));
+            Some(Self::Remaster(year))
+        } else if lower.contains("deluxe") {
+            Some(Self::Deluxe)
+        } else if lower.contains("expanded") {
+            Some(Self::Expanded)
+        } else if ["live", "live version", "live album"].contains(&lower.as_str()) {
+            Some(Self::Live)
+        } else {
+            None
+        }
+    }
+
+    /// The standard format of the suffix, e.g. `(Remastered 2014)` or `(Deluxe Edition)`.
+    pub fn standard_suffix(&self) -> String {
+        match self {
+            Self::Remaster(Some(year)) => format!("(Remastered {year})"),
+            Self::Remaster(None) => "(Remastered)".to_owned(),
+            Self::Deluxe => "(Deluxe Edition)".to_owned(),
+            Self::Expanded => "(Expanded Edition)".to_owned(),
+            Self::Live => "(Live)".to_owned(),
+        }
+    }
+}
+
+/// A release name split into its base name and a recognized edition suffix.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReleaseSuffix {
+    pub base: String,
+    /// The suffix as it appears in the name, including its brackets or dash.
+    pub suffix: String,
+    pub edition: Edition,
+}
+
+impl ReleaseSuffix {
+    /// Recognizes suffixes in parentheses, brackets or after a dash, e.g. `Foo (Live)`,
+    /// `Foo [Deluxe]` or `Foo - Remastered 2011`.
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim_end();
+        let (base, suffix, text) = match name.chars().last()? {
+            c @ (')' | ']') => {
+                let open = if c == ')' { '(' } else { '[' };
+                let start = name.rfind(open)?;
+                (&name[..start], &name[start..], &name[start + 1..name.len() - 1])
+            }
+            _ => {
+                let start = name.rfind(" - ")?;
+                (&name[..start], &name[start..], &name[start + 3..])
+            }
+        };
+        let base = base.trim_end().trim_end_matches(" -").trim_end();
+        if base.is_empty() {
+            return None;
+        }
+        let edition = Edition::parse(text)?;
+        Some(Self { base: base.to_owned(), suffix: suffix.trim().to_owned(), edition })
+    }
+
+    /// The release name with the suffix in its standard format.
+    pub fn standardized(&self) -> String {
+        format!("{} {}", self.base, self.edition.standard_suffix())
+    }
+
+    pub fn is_standard(&self) -> bool {
+        self.suffix == self.edition.standard_suffix()
+    }
+}
+
+impl Checks<'_> {
+    /// Checks for release names with edition suffixes that aren't in their standard format.
+    /// The release name returned by `f` is applied to all songs of the release, e.g. the
+    /// [standardized](ReleaseSuffix::standardized) name or the stripped base name.
+    pub fn check_release_suffixes(
+        &mut self,
+        f: &mut impl FnMut(&ReleaseArtists, &Release, &ReleaseSuffix) -> Value<String>,
+    ) {
+        let _span = info_span!("check", check = "release_suffixes").entered();
+        for ar in self.artists.iter() {
+            for rl in ar.releases.iter() {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                let Some(suffix) = ReleaseSuffix::parse(&rl.name) else { continue };
+                if suffix.is_standard() {
+                    continue;
+                }
+                info!(release = %rl.name, suffix = suffix.suffix, "non-standard release suffix");
+                self.stats.release_suffixes.issues += 1;
+                let Value::Update(name) = f(ar, rl, &suffix) else { continue };
+                if *name == *rl.name {
+                    continue;
+                }
+                self.stats.release_suffixes.updates += rl.songs.len();
+                for song in rl.songs.iter() {
+                    util::update_tag(
+                        &mut self.song_operations,
+                        &self.retag_excluded,
+                        song,
+                        "release suffix",
+                        |tu| tu.release = Value::Update(name.clone()),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str) -> Option<(String, String, Edition)> {
+        ReleaseSuffix::parse(name).map(|s| (s.base, s.suffix, s.edition))
+    }
+
+    #[test]
+    fn recognizes_edition_suffixes() {
+        let owned = |b: &str, s: &str, e| Some((b.to_owned(), s.to_owned(), e));
+        assert_eq!(
+            parse("Abbey Road (2019 Remaster)"),
+            owned("Abbey Road", "(2019 Remaster)", Edition::Remaster(Some(2019)))
+        );
+        assert_eq!(
+            parse("Foo - Remastered 2011"),
+            owned("Foo", "- Remastered 2011", Edition::Remaster(Some(2011)))
+        );
+        assert_eq!(parse("Foo [Deluxe]"), owned("Foo", "[Deluxe]", Edition::Deluxe));
+        assert_eq!(parse("Foo (live)"), owned("Foo", "(live)", Edition::Live));
+        assert_eq!(
+            parse("Live at Wembley (Live)"),
+            owned("Live at Wembley", "(Live)", Edition::Live)
+        );
+        assert_eq!(parse("Foo (Live at Wembley)"), None);
+        assert_eq!(parse("Foo (Bar)"), None);
+        assert_eq!(parse("(Deluxe)"), None);
+    }
+
+    #[test]
+    fn standardizes_suffixes() {
+        let suffix = ReleaseSuffix::parse("Foo [Deluxe]").unwrap();
+        assert!(!suffix.is_standard());
+        assert_eq!(suffix.standardized(), "Foo (Deluxe Edition)");
+
+        let suffix = ReleaseSuffix::parse("Foo (Remastered 2014)").unwrap();
+        assert!(suffix.is_standard());
+    }
+}
@@ -0,0 +1,96 @@
+use std::io::Write;
+use std::path::Path;
+
+use crate::MusicIndex;
+
+const CSV_HEADER: [&str; 18] = [
+    "kind",
+    "path",
+    "release_artists",
+    "artists",
+    "release",
+    "title",
+    "track_number",
+    "total_tracks",
+    "disc_number",
+    "total_discs",
+    "year",
+    "genre",
+    "has_artwork",
+    "codec",
+    "bitrate",
+    "sample_rate",
+    "duration",
+    "mode",
+];
+
+/// Separates multiple artists inside of a single CSV field.
+pub(crate) const CSV_LIST_SEPARATOR: &str = "; ";
+
+impl MusicIndex {
+    pub fn export_json(&self, writer: impl Write) -> std::io::Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Writes one row per indexed file, images and unknown files only have the kind and path set.
+    pub fn export_csv(&self, mut writer: impl Write) -> std::io::Result<()> {
+        write_csv_row(&mut writer, CSV_HEADER.iter().map(|h| h.to_string()))?;
+
+        for s in self.songs.iter() {
+            let num = |n: Option<u16>| n.map(|n| n.to_string()).unwrap_or_default();
+            let row = [
+                "song".to_owned(),
+                path_field(&s.path),
+                s.release_artists.join(CSV_LIST_SEPARATOR),
+                s.artists.join(CSV_LIST_SEPARATOR),
+                s.release.to_string(),
+                s.title.clone(),
+                num(s.track_number),
+                num(s.total_tracks),
+                num(s.disc_number),
+                num(s.total_discs),
+                s.year.map(|y| y.to_string()).unwrap_or_default(),
+                s.genre.clone().unwrap_or_default(),
+                s.has_artwork.to_string(),
+                s.audio.codec.map(|c| c.to_string()).unwrap_or_default(),
+                s.audio.bitrate.map(|b| b.to_string()).unwrap_or_default(),
+                s.audio.sample_rate.map(|r| r.to_string()).unwrap_or_default(),
+                s.audio.duration.map(|d| d.as_secs_f64().to_string()).unwrap_or_default(),
+                s.mode.map(|m| format!("{:o}", m.permissions())).unwrap_or_default(),
+            ];
+            write_csv_row(&mut writer, row.into_iter())?;
+        }
+        for (kind, paths) in [("image", &self.images), ("unknown", &self.unknown)] {
+            for p in paths.iter() {
+                let row = [kind.to_owned(), path_field(p)]
+                    .into_iter()
+                    .chain(std::iter::repeat_n(String::new(), CSV_HEADER.len() - 2));
+                write_csv_row(&mut writer, row)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn path_field(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+pub(crate) fn write_csv_row(
+    writer: &mut impl Write,
+    fields: impl Iterator<Item = String>,
+) -> std::io::Result<()> {
+    for (i, f) in fields.enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        if f.contains([',', '"', '\n', '\r']) {
+            write!(writer, "\"{}\"", f.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(f.as_bytes())?;
+        }
+    }
+    writer.write_all(b"\r\n")
+}
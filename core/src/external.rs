@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info_span};
+
+use crate::{util, Checks, Song, TagUpdate, Value};
+
+/// A tag correction generated outside of the organizer, e.g. by a script or exported from a
+/// spreadsheet, see [`Checks::apply_external_updates`]. Serialized as
+/// `{"path": "Foo/bar.mp3", "update": {"year": {"Update": 2001}, "genre": "Remove"}}`, fields
+/// of the update that are left out stay unchanged.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalUpdate {
+    /// The song, either absolute or relative to the music dir.
+    pub path: PathBuf,
+    /// Shown as the reason of the update, defaults to `external update`.
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub update: TagUpdate,
+}
+
+/// Overwrites the values of `update` with the changed values of `external`.
+fn merge(update: &mut TagUpdate, external: &TagUpdate) {
+    fn set<T: Clone>(value: &mut Value<T>, external: &Value<T>) {
+        if !external.is_unchanged() {
+            *value = external.clone();
+        }
+    }
+    set(&mut update.track_number, &external.track_number);
+    set(&mut update.total_tracks, &external.total_tracks);
+    set(&mut update.disc_number, &external.disc_number);
+    set(&mut update.total_discs, &external.total_discs);
+    set(&mut update.artists, &external.artists);
+    set(&mut update.release_artists, &external.release_artists);
+    set(&mut update.release, &external.release);
+    set(&mut update.title, &external.title);
+    set(&mut update.genre, &external.genre);
+    set(&mut update.year, &external.year);
+    set(&mut update.artwork, &external.artwork);
+    set(&mut update.rating, &external.rating);
+    set(&mut update.play_count, &external.play_count);
+    for (name, value) in external.extra.iter() {
+        let value_mut = update.extra.entry(name.to_uppercase()).or_default();
+        set(value_mut, value);
+    }
+}
+
+impl Checks<'_> {
+    /// Queues the updates together with the ones of the checks, values both update are taken
+    /// from the external update. Returns the paths that don't match an indexed song, songs
+    /// excluded from retagging are skipped silently.
+    pub fn apply_external_updates(&mut self, updates: &[ExternalUpdate]) -> Vec<PathBuf> {
+        let _span = info_span!("check", check = "external_updates").entered();
+        let songs: HashMap<&Path, &Song> = self.index.songs.iter().map(|s| (&*s.path, s)).collect();
+        let mut unmatched = Vec::new();
+        for u in updates.iter() {
+            let path = self.index.music_dir.join(&u.path);
+            let Some(song) = songs.get(path.as_path()) else {
+                unmatched.push(u.path.clone());
+                continue;
+            };
+            debug!(path = %path.display(), "applying external update");
+            let reason = u.reason.as_deref().unwrap_or("external update");
+            util::update_tag(&mut self.song_operations, &self.retag_excluded, song, reason, |t| {
+                merge(t, &u.update)
+            });
+        }
+        unmatched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::MusicIndex;
+
+    #[test]
+    fn external_updates_take_precedence() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![Song { path: PathBuf::from("/music/a.mp3"), ..Default::default() }];
+        let mut checks = Checks::from(&index);
+        util::update_tag(&mut checks.song_operations, &HashSet::new(), &index.songs[0], "x", |t| {
+            t.year = Value::Update(1999);
+            t.genre = Value::Update("Rock".to_owned());
+        });
+
+        let updates: Vec<ExternalUpdate> = serde_json::from_str(
+            r#"[
+                {"path": "a.mp3", "update": {"year": {"Update": 2001}}},
+                {"path": "a.mp3", "update": {"extra": {"mood": "Remove"}}},
+                {"path": "/music/b.mp3", "update": {"year": "Remove"}}
+            ]"#,
+        )
+        .unwrap();
+        let unmatched = checks.apply_external_updates(&updates);
+
+        assert_eq!(unmatched, [PathBuf::from("/music/b.mp3")]);
+        let op = &checks.song_operations[0];
+        assert_eq!(op.reasons, ["x", "external update"]);
+        let update = op.tag_update.as_ref().unwrap();
+        assert_eq!(update.year, Value::Update(2001));
+        assert_eq!(update.genre, Value::Update("Rock".to_owned()));
+        assert_eq!(update.extra.get("MOOD"), Some(&Value::Remove));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use tracing::{debug, info_span};
+
+use crate::{util, Checks, Song, SongOperation, TagUpdate, Value};
+
+/// The value more than half of all songs have, including the songs without a value.
+fn majority<T: PartialEq>(songs: &[&Song], value: impl Fn(&Song) -> Option<T>) -> Option<T> {
+    let mut votes: Vec<(T, usize)> = Vec::new();
+    for s in songs {
+        let Some(v) = value(s) else { continue };
+        match votes.iter_mut().find(|(o, _)| *o == v) {
+            Some((_, n)) => *n += 1,
+            None => votes.push((v, 1)),
+        }
+    }
+    let (value, n) = votes.into_iter().max_by_key(|(_, n)| *n)?;
+    (n * 2 > songs.len()).then_some(value)
+}
+
+/// Whether another check already queued an update of the field of the song.
+fn is_queued(
+    song: &Song,
+    song_operations: &[SongOperation],
+    field: impl Fn(&TagUpdate) -> bool,
+) -> bool {
+    (song_operations.iter())
+        .find(|o| o.song.path == song.path)
+        .and_then(|o| o.tag_update.as_ref())
+        .is_some_and(field)
+}
+
+/// The updates of the songs of a release that lack a value most of the other songs have.
+fn fills(songs: &[&Song], song_operations: &[SongOperation]) -> Vec<(usize, TagUpdate)> {
+    let mut updates = vec![TagUpdate::default(); songs.len()];
+    let queued =
+        |i: usize, field: fn(&TagUpdate) -> bool| is_queued(songs[i], song_operations, field);
+
+    if let Some(year) = majority(songs, |s| s.year) {
+        for (i, s) in songs.iter().enumerate() {
+            if s.year.is_none() && !queued(i, |t| !t.year.is_unchanged()) {
+                updates[i].year = Value::Update(year);
+            }
+        }
+    }
+    if let Some(genre) = majority(songs, |s| s.genre.clone()) {
+        for (i, s) in songs.iter().enumerate() {
+            if s.genre.is_none() && !queued(i, |t| !t.genre.is_unchanged()) {
+                updates[i].genre = Value::Update(genre.clone());
+            }
+        }
+    }
+    // songs without the release artists tag fall back to their artists
+    if let Some(artists) = majority(songs, |s| Some(Arc::clone(&s.release_artists))) {
+        for (i, s) in songs.iter().enumerate() {
+            if s.release_artists != artists
+                && s.release_artists == s.artists
+                && !queued(i, |t| !t.release_artists.is_unchanged())
+            {
+                updates[i].release_artists = Value::Update(artists.to_vec());
+            }
+        }
+    }
+
+    let mut discs: BTreeMap<Option<u16>, Vec<usize>> = BTreeMap::new();
+    for (i, s) in songs.iter().enumerate() {
+        discs.entry(s.disc_number).or_default().push(i);
+    }
+    for disc in discs.values() {
+        let disc_songs: Vec<&Song> = disc.iter().map(|&i| songs[i]).collect();
+        let Some(total) = majority(&disc_songs, |s| s.total_tracks) else { continue };
+        for &i in disc {
+            if songs[i].total_tracks.is_none() && !queued(i, |t| !t.total_tracks.is_unchanged()) {
+                updates[i].total_tracks = Value::Update(total);
+            }
+        }
+    }
+
+    (updates.into_iter().enumerate()).filter(|(_, u)| *u != TagUpdate::default()).collect()
+}
+
+impl Checks<'_> {
+    /// Fills in the year, genre, release artists and total tracks of songs that lack a value
+    /// more than half of the songs of their release have, so a single badly tagged song doesn't
+    /// end up in a dir of its own. Songs are grouped by release and dir, since songs without
+    /// release artists are grouped by their artists otherwise. Values another check already
+    /// updates are left alone.
+    pub fn fill_release_tags(&mut self) {
+        let _span = info_span!("check", check = "filled_tags").entered();
+        let mut releases: BTreeMap<(&Path, &str), Vec<&Song>> = BTreeMap::new();
+        for s in self.index.songs.iter() {
+            let Some(dir) = s.path.parent() else { continue };
+            releases.entry((dir, &s.release)).or_default().push(s);
+        }
+
+        for ((dir, release), songs) in releases {
+            if self.cancel.is_cancelled() {
+                return;
+            }
+            if songs.len() < 2 {
+                continue;
+            }
+            let fills = fills(&songs, &self.song_operations);
+            if fills.is_empty() {
+                continue;
+            }
+            debug!(dir = %dir.display(), release, songs = fills.len(), "filling release tags");
+            self.stats.filled_tags.issues += 1;
+            for (i, fill) in fills {
+                self.stats.filled_tags.updates += 1;
+                util::update_tag(
+                    &mut self.song_operations,
+                    &self.retag_excluded,
+                    songs[i],
+                    "filled tags",
+                    |t| {
+                        if fill.year.is_update() {
+                            t.year = fill.year;
+                        }
+                        if fill.genre.is_update() {
+                            t.genre = fill.genre;
+                        }
+                        if fill.release_artists.is_update() {
+                            t.release_artists = fill.release_artists;
+                        }
+                        if fill.total_tracks.is_update() {
+                            t.total_tracks = fill.total_tracks;
+                        }
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LibraryBuilder;
+
+    #[test]
+    fn fills_values_most_songs_of_a_release_have() {
+        let library = LibraryBuilder::new("/music").release("Bar", "Baz", 3).map(|s| {
+            if s.track_number == Some(3) {
+                // only tagged with the artists, which the release artists fall back to
+                s.total_tracks = None;
+                s.genre = Some("Pop".to_owned());
+            } else {
+                s.release_artists = Arc::from(["Foo".to_owned()]);
+                s.year = Some(2001);
+                s.genre = Some("Rock".to_owned());
+            }
+        });
+        let index = library.index();
+        let mut checks = Checks::from(&index);
+        checks.fill_release_tags();
+
+        assert_eq!(checks.song_operations.len(), 1);
+        let update = checks.song_operations[0].tag_update.as_ref().unwrap();
+        assert_eq!(update.year, Value::Update(2001));
+        assert_eq!(update.total_tracks, Value::Update(3));
+        assert_eq!(update.release_artists, Value::Update(vec!["Foo".to_owned()]));
+        assert_eq!(update.genre, Value::Unchanged);
+        assert_eq!(checks.stats.filled_tags, crate::CheckCount { issues: 1, updates: 1 });
+    }
+}
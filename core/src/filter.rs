@@ -0,0 +1,206 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::fs::matches_glob;
+use crate::{MusicIndex, Song};
+
+/// The keys that can be used inside a filter.
+pub const FILTER_KEYS: [&str; 4] = ["artist", "release", "path", "format"];
+
+/// Limits a run to a subset of the index, e.g. `artist:Radiohead`, `path:Incoming/**` or
+/// `format:flac`.
+///
+/// Patterns are case insensitive globs supporting `*` and `?`, where `*` also matches `/`. Paths
+/// are matched relative to the music dir.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// Matches the release artists and artists.
+    Artist(String),
+    Release(String),
+    Path(String),
+    /// Matches the file extension.
+    Format(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilterError {
+    MissingKey,
+    UnknownKey(String),
+    EmptyPattern,
+}
+
+impl std::fmt::Display for FilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingKey => write!(f, "expected 'key:pattern'"),
+            Self::UnknownKey(k) => write!(f, "unknown key '{k}'"),
+            Self::EmptyPattern => write!(f, "empty pattern"),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, pattern) = s.split_once(':').ok_or(FilterError::MissingKey)?;
+        if pattern.is_empty() {
+            return Err(FilterError::EmptyPattern);
+        }
+
+        let pattern = pattern.to_owned();
+        match key {
+            "artist" => Ok(Self::Artist(pattern)),
+            "release" | "album" => Ok(Self::Release(pattern)),
+            "path" => Ok(Self::Path(pattern)),
+            "format" => Ok(Self::Format(pattern)),
+            _ => Err(FilterError::UnknownKey(key.to_owned())),
+        }
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, song: &Song, music_dir: &Path) -> bool {
+        match self {
+            Self::Artist(p) => {
+                song.release_artists.iter().chain(song.artists.iter()).any(|a| matches_glob(p, a))
+            }
+            Self::Release(p) => matches_glob(p, &song.release),
+            Self::Path(p) => matches_path(p, &song.path, music_dir),
+            Self::Format(p) => {
+                let ext = song.path.extension().unwrap_or_default();
+                matches_glob(p, &ext.to_string_lossy())
+            }
+        }
+    }
+
+    fn key(&self) -> &'static str {
+        match self {
+            Self::Artist(_) => "artist",
+            Self::Release(_) => "release",
+            Self::Path(_) => "path",
+            Self::Format(_) => "format",
+        }
+    }
+}
+
+fn matches_path(pattern: &str, path: &Path, music_dir: &Path) -> bool {
+    let path = path.strip_prefix(music_dir).unwrap_or(path);
+    matches_glob(pattern, &path.to_string_lossy())
+}
+
+impl MusicIndex {
+    /// Removes all songs that don't match the filters. A song has to match at least one filter of
+    /// each key. Images, companion files and unknown files are kept if they are inside of a
+    /// directory that still contains a song, or if they match a path filter.
+    pub fn retain_matching(&mut self, filters: &[Filter]) {
+        if filters.is_empty() {
+            return;
+        }
+
+        let music_dir = self.music_dir.clone();
+        self.songs.retain(|s| {
+            FILTER_KEYS.iter().all(|k| {
+                let mut filters = filters.iter().filter(|f| f.key() == *k).peekable();
+                filters.peek().is_none() || filters.any(|f| f.matches(s, &music_dir))
+            })
+        });
+
+        let song_dirs: HashSet<&Path> = self.songs.iter().filter_map(|s| s.path.parent()).collect();
+        let keep = |p: &PathBuf| {
+            let in_song_dir = p.parent().is_some_and(|d| song_dirs.contains(d));
+            in_song_dir
+                || filters.iter().any(|f| match f {
+                    Filter::Path(pattern) => matches_path(pattern, p, &music_dir),
+                    _ => false,
+                })
+        };
+        self.images.retain(keep);
+        self.companions.retain(keep);
+        self.extras.retain(keep);
+        self.videos.retain(|v| keep(&v.path));
+        self.audiobooks.retain(|a| keep(&a.path));
+        self.unknown.retain(keep);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::testing::song;
+
+    fn filters(filters: &[&str]) -> Vec<Filter> {
+        filters.iter().map(|f| f.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!("artist:Foo*".parse(), Ok(Filter::Artist("Foo*".to_owned())));
+        assert_eq!("album:Bar".parse(), Ok(Filter::Release("Bar".to_owned())));
+        assert_eq!("path:a:b/**".parse(), Ok(Filter::Path("a:b/**".to_owned())));
+        assert_eq!("format:flac".parse(), Ok(Filter::Format("flac".to_owned())));
+        assert_eq!("Foo".parse::<Filter>(), Err(FilterError::MissingKey));
+        assert_eq!("artist:".parse::<Filter>(), Err(FilterError::EmptyPattern));
+        assert_eq!("genre:Rock".parse::<Filter>(), Err(FilterError::UnknownKey("genre".into())));
+    }
+
+    #[test]
+    fn matches_songs() {
+        let music_dir = Path::new("/music");
+        let mut s = song("/music/Foo/Bar/01.FLAC", "Bar", "Foo");
+        s.artists = Arc::from(["Baz".to_owned()]);
+        for f in ["artist:foo", "artist:B?z", "release:b*", "path:foo/**", "format:flac"] {
+            assert!(f.parse::<Filter>().unwrap().matches(&s, music_dir), "{f}");
+        }
+        for f in ["artist:Qux", "release:Bar2", "path:/music/*", "format:mp3"] {
+            assert!(!f.parse::<Filter>().unwrap().matches(&s, music_dir), "{f}");
+        }
+    }
+
+    #[test]
+    fn requires_one_match_per_key() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/a/01.mp3", "One", "A"),
+            song("/music/b/01.flac", "Two", "B"),
+            song("/music/c/01.mp3", "Three", "C"),
+        ];
+        index.retain_matching(&filters(&["artist:A", "artist:B", "format:mp3"]));
+
+        let paths: Vec<_> = index.songs.iter().map(|s| s.path.as_path()).collect();
+        assert_eq!(paths, [Path::new("/music/a/01.mp3")]);
+    }
+
+    #[test]
+    fn keeps_files_next_to_matching_songs() {
+        let index = || {
+            let mut index = MusicIndex::from(PathBuf::from("/music"));
+            index.songs =
+                vec![song("/music/a/01.mp3", "One", "A"), song("/music/b/01.mp3", "Two", "B")];
+            index.images =
+                vec![PathBuf::from("/music/a/cover.jpg"), PathBuf::from("/music/b/cover.jpg")];
+            index.companions =
+                vec![PathBuf::from("/music/a/01.lrc"), PathBuf::from("/music/b/01.lrc")];
+            index.unknown =
+                vec![PathBuf::from("/music/b/notes.txt"), PathBuf::from("/music/c/x.txt")];
+            index
+        };
+
+        let mut by_artist = index();
+        by_artist.retain_matching(&filters(&["artist:A"]));
+        assert_eq!(by_artist.images, [PathBuf::from("/music/a/cover.jpg")]);
+        assert_eq!(by_artist.companions, [PathBuf::from("/music/a/01.lrc")]);
+        assert!(by_artist.unknown.is_empty());
+
+        // path filters also match other files
+        let mut by_path = index();
+        by_path.retain_matching(&filters(&["path:c/*"]));
+        assert!(by_path.songs.is_empty());
+        assert_eq!(by_path.unknown, [PathBuf::from("/music/c/x.txt")]);
+    }
+}
@@ -0,0 +1,727 @@
+use std::ffi::OsStr;
+use std::io::{Read, Write};
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::meta::Mode;
+use crate::owner::{self, Owner};
+use crate::update::TagUpdate;
+use crate::{ArtistSeparators, Backup, CancelToken, Song};
+
+/// The filesystem operations used for planning and executing changes, so they can be run
+/// against an in-memory [`MemFs`](crate::MemFs) in tests. Reading tags and writing tag updates
+/// always uses the real filesystem. Song operations may be executed concurrently, see
+/// [`ExecOptions::threads`].
+pub trait Fs: Sync {
+    fn exists(&self, path: &Path) -> bool;
+    fn is_file(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    /// The paths of all entries inside of the directory.
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>>;
+    fn create_dir_all(&self, path: &Path) -> std::io::Result<()>;
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>>;
+    /// Opens a file for reading, e.g. to hash large files without loading them at once.
+    fn open(&self, path: &Path) -> std::io::Result<Box<dyn Read + '_>>;
+    /// The size and modification time of a file.
+    fn file_info(&self, path: &Path) -> std::io::Result<(u64, SystemTime)>;
+    /// The modification time of a directory, which changes when entries are added or removed.
+    fn dir_modified(&self, path: &Path) -> std::io::Result<SystemTime>;
+    fn set_dir_modified(&self, path: &Path, time: SystemTime) -> std::io::Result<()>;
+    /// Replaces the contents of a file, or creates it.
+    fn write(&self, path: &Path, data: &[u8]) -> std::io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()>;
+    /// Copies a file including its permissions, reporting the number of copied and total bytes.
+    fn copy(
+        &self,
+        from: &Path,
+        to: &Path,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> std::io::Result<u64>;
+    fn remove_file(&self, path: &Path) -> std::io::Result<()>;
+    /// Creates `link` as a hard link to `original`, `link` must not exist yet.
+    fn hard_link(&self, original: &Path, link: &Path) -> std::io::Result<()>;
+    /// Whether both paths refer to the same file, e.g. because they are hard links.
+    fn same_file(&self, a: &Path, b: &Path) -> bool;
+    /// Removes an empty directory.
+    fn remove_dir(&self, path: &Path) -> std::io::Result<()>;
+    /// Sends a file or directory to the system trash.
+    fn trash(&self, path: &Path) -> std::io::Result<()>;
+    fn set_permissions(&self, path: &Path, mode: Mode) -> std::io::Result<()>;
+    fn set_owner(&self, path: &Path, owner: Owner) -> std::io::Result<()>;
+    /// Gives `to` the owner of `from`, including its access control lists where supported.
+    fn copy_ownership(&self, from: &Path, to: &Path) -> std::io::Result<()>;
+}
+
+/// The real filesystem.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RealFs;
+
+impl Fs for RealFs {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(path)?.map(|e| e.map(|e| e.path())).collect()
+    }
+
+    fn create_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(path)
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn open(&self, path: &Path) -> std::io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(std::fs::File::open(path)?))
+    }
+
+    fn file_info(&self, path: &Path) -> std::io::Result<(u64, SystemTime)> {
+        let meta = std::fs::metadata(path)?;
+        Ok((meta.len(), meta.modified()?))
+    }
+
+    fn dir_modified(&self, path: &Path) -> std::io::Result<SystemTime> {
+        std::fs::metadata(path)?.modified()
+    }
+
+    fn set_dir_modified(&self, path: &Path, time: SystemTime) -> std::io::Result<()> {
+        std::fs::File::open(path)?.set_modified(time)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> std::io::Result<()> {
+        std::fs::write(path, data)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    fn copy(
+        &self,
+        from: &Path,
+        to: &Path,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> std::io::Result<u64> {
+        copy(from, to, &mut |copied, total| progress(copied, total))
+    }
+
+    fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> std::io::Result<()> {
+        std::fs::hard_link(original, link)
+    }
+
+    #[cfg(unix)]
+    fn same_file(&self, a: &Path, b: &Path) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        let id = |p: &Path| std::fs::metadata(p).map(|m| (m.dev(), m.ino())).ok();
+        id(a).is_some_and(|a| Some(a) == id(b))
+    }
+
+    #[cfg(not(unix))]
+    fn same_file(&self, a: &Path, b: &Path) -> bool {
+        let id = |p: &Path| std::fs::canonicalize(p).ok();
+        id(a).is_some_and(|a| Some(a) == id(b))
+    }
+
+    fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::remove_dir(path)
+    }
+
+    fn trash(&self, path: &Path) -> std::io::Result<()> {
+        trash::delete(path).map_err(std::io::Error::other)
+    }
+
+    fn set_permissions(&self, path: &Path, mode: Mode) -> std::io::Result<()> {
+        mode.write_dir(path)
+    }
+
+    fn set_owner(&self, path: &Path, owner: Owner) -> std::io::Result<()> {
+        owner::set_owner(path, owner)
+    }
+
+    fn copy_ownership(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        owner::copy_ownership(from, to)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirCreation {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub path: PathBuf,
+    pub mode: Option<Mode>,
+    #[serde(default)]
+    pub owner: Option<Owner>,
+}
+
+impl DirCreation {
+    /// Creates the directory including all missing ancestors. A directory that already exists
+    /// isn't considered an error, but the configured mode and owner are still applied to it.
+    pub fn execute(&self, fs: &impl Fs) -> Result<(), std::io::Error> {
+        match fs.create_dir_all(&self.path) {
+            Ok(()) => (),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && fs.is_dir(&self.path) => (),
+            Err(e) => return Err(e),
+        }
+
+        if let Some(mode) = self.mode {
+            fs.set_permissions(&self.path, mode)?;
+        }
+
+        if let Some(owner) = self.owner {
+            fs.set_owner(&self.path, owner)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirDeletion {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub path: PathBuf,
+}
+
+impl DirDeletion {
+    pub fn execute(&self, fs: &impl Fs, delete_type: DeleteType) -> Result<(), std::io::Error> {
+        match delete_type {
+            DeleteType::Remove => fs.remove_dir(&self.path),
+            DeleteType::Trash => fs.trash(&self.path),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileDeletion {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub path: PathBuf,
+}
+
+impl FileDeletion {
+    pub fn execute(&self, fs: &impl Fs, delete_type: DeleteType) -> Result<(), std::io::Error> {
+        match delete_type {
+            DeleteType::Remove => fs.remove_file(&self.path),
+            DeleteType::Trash => fs.trash(&self.path),
+        }
+    }
+}
+
+/// Contains a copy of the song, so plans don't borrow the [`MusicIndex`](crate::MusicIndex).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SongOperation {
+    pub song: Song,
+    pub tag_update: Option<TagUpdate>,
+    pub mode_update: Option<Mode>,
+    #[serde(serialize_with = "crate::serde_path::lossy_option")]
+    pub new_path: Option<PathBuf>,
+    /// Why the tags or mode are updated, e.g. `artwork removal` or `permissions`, in the order
+    /// the checks queued the updates.
+    #[serde(default)]
+    pub reasons: Vec<String>,
+}
+
+impl SongOperation {
+    pub fn new(song: Song) -> Self {
+        Self { song, mode_update: None, tag_update: None, new_path: None, reasons: Vec::new() }
+    }
+
+    pub fn add_reason(&mut self, reason: &str) {
+        if !self.reasons.iter().any(|r| r == reason) {
+            self.reasons.push(reason.to_owned());
+        }
+    }
+
+    pub fn execute(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        progress: &mut impl FnMut(u64, u64),
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let retry = &options.retry;
+        let is_moved = self.new_path.is_some() && options.op_type != FileOpType::Copy;
+        let is_retagged = self.new_path.is_none() && self.tag_update.is_some();
+        if let Some(backup) = options.backup.as_ref().filter(|_| is_moved || is_retagged) {
+            backup.save(fs, &self.song.path)?;
+        }
+        let path = match &self.new_path {
+            Some(new) => {
+                match options.op_type {
+                    FileOpType::Copy => {
+                        retry.run(|| fs.copy(&self.song.path, new, progress))?;
+                        if options.preserve_ownership {
+                            fs.copy_ownership(&self.song.path, new)?;
+                        }
+                    }
+                    FileOpType::Move => {
+                        retry.run(|| fs.rename(&self.song.path, new))?;
+                    }
+                    FileOpType::HardLink => {
+                        retry.run(|| fs.hard_link(&self.song.path, new))?;
+                    }
+                }
+                new
+            }
+            None => &self.song.path,
+        };
+
+        if let Some(u) = &self.tag_update {
+            u.execute(path, &options.artist_separators)?;
+        }
+
+        if let Some(mode) = self.mode_update {
+            fs.set_permissions(path, mode)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileOperation {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub old_path: PathBuf,
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub new_path: PathBuf,
+    /// Overrides the [`ExecOptions::op_type`], e.g. to copy images while songs are moved.
+    #[serde(default)]
+    pub op_type: Option<FileOpType>,
+    /// The new path is a stale hard link of the old one, which split from it, e.g. when it was
+    /// retagged by another program. It's replaced by a new link.
+    #[serde(default)]
+    pub replace: bool,
+}
+
+impl FileOperation {
+    pub fn execute(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        progress: &mut impl FnMut(u64, u64),
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let retry = &options.retry;
+        let op_type = self.op_type.unwrap_or(options.op_type);
+        if let Some(backup) = options.backup.as_ref().filter(|_| op_type == FileOpType::Move) {
+            backup.save(fs, &self.old_path)?;
+        }
+        match op_type {
+            FileOpType::Copy if fs.is_dir(&self.old_path) => {
+                retry.run(|| {
+                    copy_dir(
+                        fs,
+                        &self.old_path,
+                        &self.new_path,
+                        options.preserve_ownership,
+                        progress,
+                    )
+                })?;
+            }
+            FileOpType::Copy => {
+                retry.run(|| fs.copy(&self.old_path, &self.new_path, progress))?;
+                if options.preserve_ownership {
+                    fs.copy_ownership(&self.old_path, &self.new_path)?;
+                }
+            }
+            FileOpType::Move => {
+                retry.run(|| fs.rename(&self.old_path, &self.new_path))?;
+            }
+            FileOpType::HardLink if self.replace => {
+                // the stale link is only replaced once the new one exists
+                let mut name = std::ffi::OsString::from(".");
+                name.push(self.new_path.file_name().unwrap_or_default());
+                name.push(".music-organizer.link");
+                let tmp = self.new_path.with_file_name(name);
+                retry.run(|| fs.hard_link(&self.old_path, &tmp))?;
+                retry.run(|| fs.rename(&tmp, &self.new_path)).inspect_err(|_| {
+                    fs.remove_file(&tmp).ok();
+                })?;
+            }
+            FileOpType::HardLink => {
+                retry.run(|| fs.hard_link(&self.old_path, &self.new_path))?;
+            }
+        };
+        Ok(())
+    }
+}
+
+/// Copies a directory recursively, reporting the progress of each file.
+pub(crate) fn copy_dir(
+    fs: &impl Fs,
+    from: &Path,
+    to: &Path,
+    preserve_ownership: bool,
+    progress: &mut impl FnMut(u64, u64),
+) -> std::io::Result<u64> {
+    fs.create_dir_all(to)?;
+    if preserve_ownership {
+        fs.copy_ownership(from, to)?;
+    }
+    let mut copied = 0;
+    for p in fs.read_dir(from)? {
+        let new = to.join(p.file_name().unwrap_or_default());
+        copied += match fs.is_dir(&p) {
+            true => copy_dir(fs, &p, &new, preserve_ownership, progress)?,
+            false => {
+                let copied = fs.copy(&p, &new, progress)?;
+                if preserve_ownership {
+                    fs.copy_ownership(&p, &new)?;
+                }
+                copied
+            }
+        };
+    }
+    Ok(copied)
+}
+
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Copies a file in chunks reporting the number of copied and total bytes after each chunk, the
+/// permissions are copied as well.
+pub fn copy(from: &Path, to: &Path, progress: &mut impl FnMut(u64, u64)) -> std::io::Result<u64> {
+    let mut src = std::fs::File::open(from)?;
+    let meta = src.metadata()?;
+    let total = meta.len();
+    let mut dst = std::fs::File::create(to)?;
+
+    let mut buf = vec![0; COPY_CHUNK_SIZE];
+    let mut copied = 0;
+    loop {
+        let n = match src.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        dst.write_all(&buf[..n])?;
+        copied += n as u64;
+        progress(copied, total);
+    }
+
+    dst.set_permissions(meta.permissions())?;
+    Ok(copied)
+}
+
+/// How often and how long to wait before retrying a file operation that failed because of a
+/// transient error, which happens sporadically on network filesystems. The delay is doubled
+/// after each attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { retries: 0, delay: Duration::from_millis(100) }
+    }
+}
+
+impl RetryPolicy {
+    pub fn run<T>(&self, mut f: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+        let mut delay = self.delay;
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+                r => return r,
+            }
+        }
+    }
+}
+
+fn is_transient(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+
+    const EIO: i32 = 5;
+    const EBUSY: i32 = 16;
+
+    match error.kind() {
+        ErrorKind::Interrupted
+        | ErrorKind::WouldBlock
+        | ErrorKind::TimedOut
+        | ErrorKind::ResourceBusy => true,
+        _ => cfg!(unix) && matches!(error.raw_os_error(), Some(EIO | EBUSY)),
+    }
+}
+
+/// The filesystem changed underneath a planned operation, e.g. because a download completed into
+/// the music dir during a long run. The operation is skipped instead of failing, see
+/// [`Progress::item_skipped`](crate::Progress::item_skipped).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChangedOnDisk {
+    /// The file was removed or moved away.
+    Missing(PathBuf),
+    /// Another file appeared at the destination.
+    Occupied(PathBuf),
+    /// The file was modified after it was indexed.
+    Modified(PathBuf),
+}
+
+impl std::fmt::Display for ChangedOnDisk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(p) => write!(f, "{} disappeared", p.display()),
+            Self::Occupied(p) => write!(f, "{} already exists", p.display()),
+            Self::Modified(p) => write!(f, "{} was modified since indexing", p.display()),
+        }
+    }
+}
+
+impl std::error::Error for ChangedOnDisk {}
+
+/// Checks right before executing an operation that the file still exists, the destination is
+/// still free and the file wasn't modified since `modified`, if known.
+pub(crate) fn check_unchanged(
+    fs: &impl Fs,
+    path: &Path,
+    new_path: Option<&Path>,
+    modified: Option<SystemTime>,
+) -> Result<(), ChangedOnDisk> {
+    if !fs.exists(path) {
+        return Err(ChangedOnDisk::Missing(path.to_owned()));
+    }
+    // renames that only change the case point to the same file on case insensitive filesystems
+    if let Some(new) = new_path.filter(|n| fs.exists(n) && !fs.same_file(path, n)) {
+        return Err(ChangedOnDisk::Occupied(new.to_owned()));
+    }
+    let current = modified.and_then(|_| fs.file_info(path).ok()).map(|(_, m)| m);
+    if current.is_some() && current != modified {
+        return Err(ChangedOnDisk::Modified(path.to_owned()));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileOpType {
+    #[default]
+    Move,
+    Copy,
+    /// The new path is a hard link of the old one, so tag updates change both.
+    HardLink,
+}
+
+/// Options that control how planned operations are executed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExecOptions {
+    pub op_type: FileOpType,
+    pub delete_type: DeleteType,
+    pub retry: RetryPolicy,
+    /// Stop executing after the first failed operation.
+    pub fail_fast: bool,
+    /// Checked before each operation, operations that already started are completed.
+    pub cancel: CancelToken,
+    /// Joins multiple artists when writing tags.
+    pub artist_separators: ArtistSeparators,
+    /// Number of song operations executed concurrently, since rewriting tags is the slow part.
+    /// Results are still reported in order, defaults to one.
+    pub threads: Option<NonZeroUsize>,
+    /// Files are copied into the backup before they are moved or their tags are rewritten in
+    /// place, an operation fails if its backup fails.
+    pub backup: Option<Backup>,
+    /// Copied files and dirs keep the owner and access control lists of the original, which
+    /// usually requires root.
+    pub preserve_ownership: bool,
+    /// Dirs whose songs and files are only renamed keep their modification time, so backup tools
+    /// and "recently added" views don't pick them up.
+    pub preserve_dir_mtimes: bool,
+    /// Song operations are executed release by release and the executed operations of a release
+    /// are rolled back if one of them fails, so no release is left half organized. Operations
+    /// aren't executed concurrently then.
+    pub atomic_releases: bool,
+    /// Copied songs are staged in this dir, which should be on the same filesystem as the output
+    /// dir, and renamed into place once their whole release is copied, so media servers never
+    /// index half copied releases. Only used when copying.
+    pub staging_dir: Option<PathBuf>,
+}
+
+impl From<bool> for FileOpType {
+    fn from(copy: bool) -> Self {
+        match copy {
+            true => Self::Copy,
+            false => Self::Move,
+        }
+    }
+}
+
+/// Whether deleted files and directories are removed permanently or sent to the system trash.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeleteType {
+    #[default]
+    Remove,
+    Trash,
+}
+
+impl From<bool> for DeleteType {
+    fn from(trash: bool) -> Self {
+        match trash {
+            true => Self::Trash,
+            false => Self::Remove,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref RE: Regex = Regex::new(r#"[<>:"/\\|?*]"#).unwrap();
+}
+
+#[inline]
+pub fn valid_os_str_dots(str: &str) -> String {
+    let mut s = collapse_whitespace(&RE.replace_all(str, ""));
+
+    if s.starts_with('.') {
+        // This is safe because we know that the first byte has to be present and is character of 1 byte length.
+        unsafe {
+            s.as_bytes_mut()[0] = b'_';
+        }
+    }
+    if s.ends_with('.') {
+        s.pop();
+        s.push('_');
+    }
+
+    s
+}
+
+/// Trims and collapses runs of whitespace into single spaces, dirs like `Album ` are easily
+/// mistaken for `Album` and can't be opened on Windows shares.
+pub(crate) fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[inline]
+pub fn valid_os_str(str: &str) -> String {
+    RE.replace_all(str, "").trim().to_string()
+}
+
+const SONG_EXTENSIONS: [&str; 4] = ["m4a", "m4b", "mp3", "flac"];
+#[inline]
+pub fn is_song_extension(s: &OsStr) -> bool {
+    for e in &SONG_EXTENSIONS {
+        if s.eq_ignore_ascii_case(e) {
+            return true;
+        }
+    }
+
+    false
+}
+
+const IMAGE_EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+#[inline]
+pub fn is_image_extension(s: &OsStr) -> bool {
+    for e in &IMAGE_EXTENSIONS {
+        if s.eq_ignore_ascii_case(e) {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Files that belong to a release, like rip logs and cue sheets.
+const COMPANION_EXTENSIONS: [&str; 3] = ["cue", "log", "accurip"];
+#[inline]
+pub fn is_companion_extension(s: &OsStr) -> bool {
+    for e in &COMPANION_EXTENSIONS {
+        if s.eq_ignore_ascii_case(e) {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Videos that are indexed as music videos if they are tagged, otherwise they're extra files.
+const MUSIC_VIDEO_EXTENSIONS: [&str; 2] = ["m4v", "mp4"];
+#[inline]
+pub fn is_music_video_extension(s: &OsStr) -> bool {
+    for e in &MUSIC_VIDEO_EXTENSIONS {
+        if s.eq_ignore_ascii_case(e) {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Groups of extra files inside of the music dir, which aren't songs, images, companion files
+/// or playlists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtraKind {
+    /// Booklets, notes and similar, e.g. `pdf` or `txt` files.
+    Document,
+    Video,
+    Archive,
+}
+
+const DOCUMENT_EXTENSIONS: [&str; 5] = ["pdf", "txt", "nfo", "md", "rtf"];
+const VIDEO_EXTENSIONS: [&str; 6] = ["mp4", "m4v", "mkv", "avi", "mov", "webm"];
+const ARCHIVE_EXTENSIONS: [&str; 5] = ["zip", "rar", "7z", "tar", "gz"];
+
+impl ExtraKind {
+    pub fn from_extension(s: &OsStr) -> Option<Self> {
+        let is = |extensions: &[&str]| extensions.iter().any(|e| s.eq_ignore_ascii_case(e));
+        if is(&DOCUMENT_EXTENSIONS) {
+            Some(Self::Document)
+        } else if is(&VIDEO_EXTENSIONS) {
+            Some(Self::Video)
+        } else if is(&ARCHIVE_EXTENSIONS) {
+            Some(Self::Archive)
+        } else {
+            None
+        }
+    }
+}
+
+const PLAYLIST_EXTENSIONS: [&str; 4] = ["m3u", "m3u8", "pls", "xspf"];
+#[inline]
+pub fn is_playlist_extension(s: &OsStr) -> bool {
+    for e in &PLAYLIST_EXTENSIONS {
+        if s.eq_ignore_ascii_case(e) {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Whether the file name starts with a dot.
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name().is_some_and(|n| n.as_encoded_bytes().starts_with(b"."))
+}
+
+/// Matches a file name against a simple case insensitive glob pattern supporting `*` and `?`.
+pub fn matches_glob(pattern: &str, name: &str) -> bool {
+    fn matches(p: &[char], n: &[char]) -> bool {
+        match p.split_first() {
+            None => n.is_empty(),
+            Some(('*', rest)) => (0..=n.len()).any(|i| matches(rest, &n[i..])),
+            Some(('?', rest)) => !n.is_empty() && matches(rest, &n[1..]),
+            Some((c, rest)) => n.first() == Some(c) && matches(rest, &n[1..]),
+        }
+    }
+
+    let p: Vec<char> = pattern.to_lowercase().chars().collect();
+    let n: Vec<char> = name.to_lowercase().chars().collect();
+    matches(&p, &n)
+}
@@ -0,0 +1,330 @@
+use std::collections::{BTreeMap, HashMap};
+
+use tracing::{debug, info_span};
+
+use crate::{util, Checks, Value};
+
+/// The ID3v1 genres including the Winamp extensions, indexed by their number. Genre 133 is
+/// listed under its modern name.
+pub const ID3V1_GENRES: [&str; 192] = [
+    "Blues",
+    "Classic Rock",
+    "Country",
+    "Dance",
+    "Disco",
+    "Funk",
+    "Grunge",
+    "Hip-Hop",
+    "Jazz",
+    "Metal",
+    "New Age",
+    "Oldies",
+    "Other",
+    "Pop",
+    "R&B",
+    "Rap",
+    "Reggae",
+    "Rock",
+    "Techno",
+    "Industrial",
+    "Alternative",
+    "Ska",
+    "Death Metal",
+    "Pranks",
+    "Soundtrack",
+    "Euro-Techno",
+    "Ambient",
+    "Trip-Hop",
+    "Vocal",
+    "Jazz+Funk",
+    "Fusion",
+    "Trance",
+    "Classical",
+    "Instrumental",
+    "Acid",
+    "House",
+    "Game",
+    "Sound Clip",
+    "Gospel",
+    "Noise",
+    "AlternRock",
+    "Bass",
+    "Soul",
+    "Punk",
+    "Space",
+    "Meditative",
+    "Instrumental Pop",
+    "Instrumental Rock",
+    "Ethnic",
+    "Gothic",
+    "Darkwave",
+    "Techno-Industrial",
+    "Electronic",
+    "Pop-Folk",
+    "Eurodance",
+    "Dream",
+    "Southern Rock",
+    "Comedy",
+    "Cult",
+    "Gangsta",
+    "Top 40",
+    "Christian Rap",
+    "Pop/Funk",
+    "Jungle",
+    "Native American",
+    "Cabaret",
+    "New Wave",
+    "Psychedelic",
+    "Rave",
+    "Showtunes",
+    "Trailer",
+    "Lo-Fi",
+    "Tribal",
+    "Acid Punk",
+    "Acid Jazz",
+    "Polka",
+    "Retro",
+    "Musical",
+    "Rock & Roll",
+    "Hard Rock",
+    "Folk",
+    "Folk-Rock",
+    "National Folk",
+    "Swing",
+    "Fast Fusion",
+    "Bebop",
+    "Latin",
+    "Revival",
+    "Celtic",
+    "Bluegrass",
+    "Avantgarde",
+    "Gothic Rock",
+    "Progressive Rock",
+    "Psychedelic Rock",
+    "Symphonic Rock",
+    "Slow Rock",
+    "Big Band",
+    "Chorus",
+    "Easy Listening",
+    "Acoustic",
+    "Humour",
+    "Speech",
+    "Chanson",
+    "Opera",
+    "Chamber Music",
+    "Sonata",
+    "Symphony",
+    "Booty Bass",
+    "Primus",
+    "Porn Groove",
+    "Satire",
+    "Slow Jam",
+    "Club",
+    "Tango",
+    "Samba",
+    "Folklore",
+    "Ballad",
+    "Power Ballad",
+    "Rhythmic Soul",
+    "Freestyle",
+    "Duet",
+    "Punk Rock",
+    "Drum Solo",
+    "A Cappella",
+    "Euro-House",
+    "Dance Hall",
+    "Goa",
+    "Drum & Bass",
+    "Club-House",
+    "Hardcore Techno",
+    "Terror",
+    "Indie",
+    "BritPop",
+    "Afro-Punk",
+    "Polsk Punk",
+    "Beat",
+    "Christian Gangsta Rap",
+    "Heavy Metal",
+    "Black Metal",
+    "Crossover",
+    "Contemporary Christian",
+    "Christian Rock",
+    "Merengue",
+    "Salsa",
+    "Thrash Metal",
+    "Anime",
+    "JPop",
+    "Synthpop",
+    "Abstract",
+    "Art Rock",
+    "Baroque",
+    "Bhangra",
+    "Big Beat",
+    "Breakbeat",
+    "Chillout",
+    "Downtempo",
+    "Dub",
+    "EBM",
+    "Eclectic",
+    "Electro",
+    "Electroclash",
+    "Emo",
+    "Experimental",
+    "Garage",
+    "Global",
+    "IDM",
+    "Illbient",
+    "Industro-Goth",
+    "Jam Band",
+    "Krautrock",
+    "Leftfield",
+    "Lounge",
+    "Math Rock",
+    "New Romantic",
+    "Nu-Breakz",
+    "Post-Punk",
+    "Post-Rock",
+    "Psytrance",
+    "Shoegaze",
+    "Space Rock",
+    "Trop Rock",
+    "World Music",
+    "Neoclassical",
+    "Audiobook",
+    "Audio Theatre",
+    "Neue Deutsche Welle",
+    "Podcast",
+    "Indie Rock",
+    "G-Funk",
+    "Dubstep",
+    "Garage Rock",
+    "Psybient",
+];
+
+/// Resolves the ID3v1 references of a single genre: plain numbers like `17`, the ID3v2.3 form
+/// `(17)`, which may be followed by a refinement like `(17)Hard Rock` that is preferred, and the
+/// `(RX)` and `(CR)` keywords. A literal leading parenthesis is escaped as `((`.
+fn resolve_id3v1(genre: &str) -> String {
+    let by_number = |n: &str| n.parse::<usize>().ok().and_then(|n| ID3V1_GENRES.get(n).copied());
+    if let Some(name) = by_number(genre) {
+        return name.to_owned();
+    }
+    if let Some(escaped) = genre.strip_prefix("((") {
+        return format!("({escaped}");
+    }
+    let Some((reference, refinement)) =
+        genre.strip_prefix('(').and_then(|rest| rest.split_once(')'))
+    else {
+        return genre.to_owned();
+    };
+    let name = match reference {
+        "RX" => Some("Remix"),
+        "CR" => Some("Cover"),
+        n => by_number(n),
+    };
+    let Some(name) = name else { return genre.to_owned() };
+    match refinement.trim() {
+        "" => name.to_owned(),
+        r => resolve_id3v1(r),
+    }
+}
+
+/// Uppercases the first letter of each word of an all lowercase genre, e.g. `hard rock`.
+fn title_case(genre: &str) -> String {
+    let mut new = String::with_capacity(genre.len());
+    let mut word_start = true;
+    for c in genre.chars() {
+        match word_start {
+            true => new.extend(c.to_uppercase()),
+            false => new.push(c),
+        }
+        word_start = matches!(c, ' ' | '-' | '/' | '&' | '+');
+    }
+    new
+}
+
+/// The normalized form of a genre, ID3v2.4 genres with multiple values separated by `\0` are
+/// normalized one by one. ID3v1 references are resolved first, then the names are looked up
+/// case insensitively in `names`, keyed by their lowercase form, and in the [`ID3V1_GENRES`].
+/// Other genres that are all lowercase are title cased.
+pub fn normalize_genre(genre: &str, names: &HashMap<String, String>) -> String {
+    let mut genres: Vec<String> = Vec::new();
+    for g in genre.split('\0').map(str::trim).filter(|g| !g.is_empty()) {
+        let g = resolve_id3v1(g);
+        let lower = g.to_lowercase();
+        let new = if let Some(name) = names.get(&lower) {
+            name.clone()
+        } else if let Some(name) = ID3V1_GENRES.iter().find(|n| n.to_lowercase() == lower) {
+            (*name).to_owned()
+        } else if g == lower {
+            title_case(&g)
+        } else {
+            g
+        };
+        if !genres.contains(&new) {
+            genres.push(new);
+        }
+    }
+    genres.join("\0")
+}
+
+impl Checks<'_> {
+    /// Checks for genres that aren't in their normalized form, see [`normalize_genre`], and
+    /// updates them. `names` maps genres to the name they're normalized to, e.g. `hiphop` to
+    /// `Hip-Hop`, its keys are compared case insensitively.
+    pub fn check_genres(&mut self, names: &BTreeMap<String, String>) {
+        let _span = info_span!("check", check = "genres").entered();
+        let names: HashMap<String, String> =
+            names.iter().map(|(k, v)| (k.trim().to_lowercase(), v.clone())).collect();
+        for song in self.index.songs.iter() {
+            if self.cancel.is_cancelled() {
+                return;
+            }
+            let Some(genre) = &song.genre else { continue };
+            let new = normalize_genre(genre, &names);
+            if new == *genre {
+                continue;
+            }
+            debug!(path = %song.path.display(), genre, new, "unnormalized genre");
+            self.stats.genres.issues += 1;
+            self.stats.genres.updates += 1;
+            util::update_tag(
+                &mut self.song_operations,
+                &self.retag_excluded,
+                song,
+                "genre normalization",
+                |t| {
+                    t.genre = match new.is_empty() {
+                        true => Value::Remove,
+                        false => Value::Update(new),
+                    }
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_id3v1_numbers_and_casing() {
+        let names = HashMap::from([("hiphop".to_owned(), "Hip-Hop".to_owned())]);
+        let normalize = |g| normalize_genre(g, &names);
+
+        assert_eq!(normalize("17"), "Rock");
+        assert_eq!(normalize("(17)"), "Rock");
+        assert_eq!(normalize("(79)Hard Rock"), "Hard Rock");
+        assert_eq!(normalize("(RX)"), "Remix");
+        assert_eq!(normalize("((Live)"), "(Live)");
+        assert_eq!(normalize("(255)"), "(255)");
+        assert_eq!(normalize("HIPHOP"), "Hip-Hop");
+        assert_eq!(normalize("r&b"), "R&B");
+        assert_eq!(normalize("neo soul"), "Neo Soul");
+        assert_eq!(normalize("Shoegaze"), "Shoegaze");
+        assert_eq!(normalize("EDM"), "EDM");
+        assert_eq!(normalize("17\0Rock"), "Rock");
+        assert_eq!(normalize(" "), "");
+    }
+}
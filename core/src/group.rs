@@ -0,0 +1,124 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::changes::{layout_field, release_order};
+use crate::{Changes, MusicIndex, SongOperation, Value};
+
+/// The custom field containing the name of the group of a release, e.g. of a box set.
+pub const RELEASE_GROUP_FIELD: &str = "RELEASEGROUP";
+
+/// Separate the name of a group from the title of a release inside of it, e.g.
+/// `Der Ring des Nibelungen: Das Rheingold`.
+const GROUP_SEPARATORS: [&str; 2] = [": ", " - "];
+
+/// The group and the title of a release name at the first separator.
+fn split_group(release: &str) -> Option<(&str, &str)> {
+    let (i, sep) = (GROUP_SEPARATORS.iter())
+        .filter_map(|sep| Some((release.find(sep)?, sep)))
+        .min_by_key(|(i, _)| *i)?;
+    let (group, title) = (release[..i].trim(), release[i + sep.len()..].trim());
+    (!group.is_empty() && !title.is_empty()).then_some((group, title))
+}
+
+/// The group a release belongs to and its title inside of the group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ReleaseGroup {
+    pub name: String,
+    pub title: String,
+}
+
+impl Changes {
+    /// The groups of the releases of songs, used by the `release_group` and `release_title`
+    /// layout fields. The group is read from the [`RELEASE_GROUP_FIELD`], otherwise releases
+    /// of the same release artists are grouped if at least two of them share a prefix like
+    /// `Box Set: Title` or `Box Set - Title`.
+    pub(crate) fn release_groups<'a>(
+        &self,
+        index: &'a MusicIndex,
+    ) -> HashMap<&'a Path, ReleaseGroup> {
+        let ops: HashMap<&Path, &SongOperation> =
+            self.song_operations.iter().map(|o| (o.song.path.as_path(), o)).collect();
+
+        let mut groups = HashMap::new();
+        let mut prefixes: HashMap<(String, String), Vec<(&Path, String)>> = HashMap::new();
+        for s in index.songs.iter() {
+            let tag_update = ops.get(s.path.as_path()).and_then(|o| o.tag_update.as_ref());
+            let Some(release) = layout_field(s, tag_update, "release", "") else { continue };
+            let tag = tag_update
+                .and_then(|t| t.extra.get(RELEASE_GROUP_FIELD))
+                .and_then(Value::str_value)
+                .or_else(|| s.extra.get(RELEASE_GROUP_FIELD).map(String::as_str))
+                .filter(|g| !g.is_empty());
+
+            if let Some(name) = tag {
+                let title = match split_group(&release) {
+                    Some((group, title)) if group.eq_ignore_ascii_case(name) => title,
+                    _ => &release,
+                };
+                let group = ReleaseGroup { name: name.to_owned(), title: title.to_owned() };
+                groups.insert(s.path.as_path(), group);
+            } else if let Some((group, title)) = split_group(&release) {
+                let (artists, ..) = release_order(s, tag_update);
+                let songs = prefixes.entry((artists, group.to_owned())).or_default();
+                songs.push((s.path.as_path(), title.to_owned()));
+            }
+        }
+
+        for ((_, name), songs) in prefixes {
+            let titles: BTreeSet<&str> = songs.iter().map(|(_, t)| t.as_str()).collect();
+            if titles.len() < 2 {
+                continue;
+            }
+            for (path, title) in songs {
+                groups.insert(path, ReleaseGroup { name: name.clone(), title });
+            }
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::testing::{song, SongTags};
+    use crate::{ChangeOptions, Checks, MemFs};
+
+    #[test]
+    fn groups_releases_by_prefix_and_tag() {
+        let mut tagged =
+            song("/music/d.mp3", "Tosca", "Wagner").with_track(1, None).with_title("One");
+        tagged.extra.insert(RELEASE_GROUP_FIELD.into(), "Puccini Box".into());
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/a.mp3", "Der Ring des Nibelungen: Das Rheingold", "Wagner")
+                .with_track(1, None)
+                .with_title("One"),
+            song("/music/b.mp3", "Der Ring des Nibelungen: Die Walküre", "Wagner")
+                .with_track(1, None)
+                .with_title("One"),
+            song("/music/c.mp3", "Abbey Road - Remastered", "Wagner")
+                .with_track(1, None)
+                .with_title("One"),
+            tagged,
+        ];
+        let options = ChangeOptions {
+            layout: "{release_artists}/[{release_group}]/{release_title}/{title}".parse().unwrap(),
+            ..ChangeOptions::from(PathBuf::from("/music"))
+        };
+
+        let changes = Changes::generate(&MemFs::new(), Checks::from(&index), &options);
+
+        let path = |p: &str| {
+            let op = changes.song_operations.iter().find(|o| o.song.path == Path::new(p));
+            op.and_then(|o| o.new_path.clone())
+        };
+        let expected = "/music/Wagner/Der Ring des Nibelungen/Das Rheingold/One.mp3";
+        assert_eq!(path("/music/a.mp3"), Some(PathBuf::from(expected)));
+        let expected = "/music/Wagner/Abbey Road - Remastered/One.mp3";
+        assert_eq!(path("/music/c.mp3"), Some(PathBuf::from(expected)));
+        let expected = "/music/Wagner/Puccini Box/Tosca/One.mp3";
+        assert_eq!(path("/music/d.mp3"), Some(PathBuf::from(expected)));
+    }
+}
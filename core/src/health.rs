@@ -0,0 +1,152 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::checks::FILE_PERMISSIONS;
+use crate::{Changes, MusicIndex, Song};
+
+/// How well a library is organized, the share of songs that pass each category and the
+/// releases with the most songs that fail it. Useful to track the progress of a cleanup.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct HealthReport {
+    pub songs: usize,
+    /// The mean percentage of all categories.
+    pub score: f32,
+    pub artwork: HealthScore,
+    pub year: HealthScore,
+    pub genre: HealthScore,
+    /// Songs with the permissions the checks apply, songs without a mode aren't counted.
+    pub permissions: HealthScore,
+    /// Songs that are already at the path of the layout.
+    pub paths: HealthScore,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct HealthScore {
+    pub passed: usize,
+    pub total: usize,
+    /// 100 if there are no songs.
+    pub percentage: f32,
+    /// Sorted by the number of failed songs, most first.
+    pub worst: Vec<HealthOffender>,
+}
+
+/// A release with songs that fail a category.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct HealthOffender {
+    pub release_artists: Arc<[String]>,
+    pub release: Arc<str>,
+    pub failed: usize,
+    pub songs: usize,
+}
+
+impl HealthReport {
+    /// Scores the index, `changes` are the changes generated without any checks and decide
+    /// which paths conform to the layout. `num_worst` is the maximum number of releases listed
+    /// per category.
+    pub fn generate(index: &MusicIndex, changes: &Changes, num_worst: usize) -> Self {
+        let moved: HashSet<&Path> = (changes.song_operations.iter())
+            .filter(|o| o.new_path.is_some())
+            .map(|o| o.song.path.as_path())
+            .collect();
+
+        let artwork = HealthScore::generate(index, num_worst, |s| Some(s.has_artwork));
+        let year = HealthScore::generate(index, num_worst, |s| Some(s.year.is_some()));
+        let genre = HealthScore::generate(index, num_worst, |s| Some(s.genre.is_some()));
+        let permissions = HealthScore::generate(index, num_worst, |s| {
+            s.mode.map(|m| m.permissions() == FILE_PERMISSIONS)
+        });
+        let paths =
+            HealthScore::generate(index, num_worst, |s| Some(!moved.contains(s.path.as_path())));
+
+        let categories = [&artwork, &year, &genre, &permissions, &paths];
+        let score = categories.iter().map(|c| c.percentage).sum::<f32>() / categories.len() as f32;
+
+        Self { songs: index.songs.len(), score, artwork, year, genre, permissions, paths }
+    }
+}
+
+impl HealthScore {
+    /// `passes` returns `None` for songs the category doesn't apply to.
+    fn generate(
+        index: &MusicIndex,
+        num_worst: usize,
+        passes: impl Fn(&Song) -> Option<bool>,
+    ) -> Self {
+        let mut score = Self::default();
+        let mut releases: HashMap<(&Arc<[String]>, &Arc<str>), HealthOffender> = HashMap::new();
+        for s in index.songs.iter() {
+            let Some(passed) = passes(s) else { continue };
+            score.total += 1;
+            score.passed += passed as usize;
+
+            let release = releases.entry((&s.release_artists, &s.release)).or_insert_with(|| {
+                HealthOffender {
+                    release_artists: Arc::clone(&s.release_artists),
+                    release: Arc::clone(&s.release),
+                    ..Default::default()
+                }
+            });
+            release.songs += 1;
+            release.failed += !passed as usize;
+        }
+
+        score.percentage = match score.total {
+            0 => 100.0,
+            total => score.passed as f32 * 100.0 / total as f32,
+        };
+
+        score.worst = releases.into_values().filter(|r| r.failed > 0).collect();
+        score.worst.sort_by(|a, b| {
+            (b.failed.cmp(&a.failed))
+                .then_with(|| a.release_artists.cmp(&b.release_artists))
+                .then_with(|| a.release.cmp(&b.release))
+        });
+        score.worst.truncate(num_worst);
+
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::testing::song;
+    use crate::{Mode, SongOperation};
+
+    #[test]
+    fn scores_songs_and_lists_worst_releases() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/a.mp3", "Bar", "Foo"),
+            song("/music/b.mp3", "Bar", "Foo"),
+            song("/music/c.mp3", "Baz", "Foo"),
+            song("/music/d.mp3", "Baz", "Foo"),
+        ];
+        index.songs.iter_mut().for_each(|s| s.mode = Some(Mode(0o100755)));
+        index.songs[0].has_artwork = true;
+        index.songs[1].mode = Some(Mode(0o100644));
+        let moved = SongOperation {
+            new_path: Some(PathBuf::from("/music/Foo/Baz/d.mp3")),
+            ..SongOperation::new(index.songs[3].clone())
+        };
+        let changes = Changes { song_operations: vec![moved], ..Default::default() };
+
+        let report = HealthReport::generate(&index, &changes, 1);
+
+        assert_eq!(report.songs, 4);
+        assert_eq!((report.artwork.passed, report.artwork.total), (1, 4));
+        assert_eq!(report.artwork.percentage, 25.0);
+        assert_eq!(report.artwork.worst.len(), 1);
+        assert_eq!(&*report.artwork.worst[0].release, "Baz");
+        assert_eq!(report.artwork.worst[0].failed, 2);
+        assert_eq!(report.permissions.passed, 3);
+        assert_eq!(report.paths.passed, 3);
+        assert_eq!(report.year.percentage, 0.0);
+        assert_eq!(report.score, (25.0 + 0.0 + 0.0 + 75.0 + 75.0) / 5.0);
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::export::{write_csv_row, CSV_LIST_SEPARATOR};
+use crate::MusicIndex;
+
+const CSV_HEADER: [&str; 6] =
+    ["release_artists", "release", "disc_number", "total_tracks", "present", "missing"];
+
+/// Releases that are missing tracks, judged by the total tracks of their songs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct IncompleteReport {
+    /// Sorted by release artists, release and disc number.
+    pub releases: Vec<IncompleteRelease>,
+}
+
+/// A disc of a release that is missing tracks.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct IncompleteRelease {
+    pub release_artists: Arc<[String]>,
+    pub release: Arc<str>,
+    pub disc_number: Option<u16>,
+    /// The largest total tracks of the songs of the disc.
+    pub total_tracks: u16,
+    /// Track numbers that are 0 or exceed the total aren't included, see
+    /// [`Checks::check_invalid_numbers`](crate::Checks::check_invalid_numbers).
+    pub present: Vec<u16>,
+    pub missing: Vec<u16>,
+}
+
+impl IncompleteReport {
+    /// Songs without total tracks are ignored, since there is no way to tell what's missing.
+    pub fn generate(index: &MusicIndex) -> Self {
+        type Key<'a> = (&'a Arc<[String]>, &'a Arc<str>, Option<u16>);
+        let mut discs: HashMap<Key, (u16, BTreeSet<u16>)> = HashMap::new();
+        for s in index.songs.iter() {
+            let Some(total) = s.total_tracks else { continue };
+            let key = (&s.release_artists, &s.release, s.disc_number);
+            let (disc_total, tracks) = discs.entry(key).or_default();
+            *disc_total = total.max(*disc_total);
+            tracks.extend(s.track_number);
+        }
+
+        let mut releases: Vec<IncompleteRelease> = (discs.into_iter())
+            .filter_map(|((artists, release, disc_number), (total, tracks))| {
+                let present: Vec<u16> = tracks.range(1..=total).copied().collect();
+                let missing: Vec<u16> = (1..=total).filter(|n| !tracks.contains(n)).collect();
+                (!missing.is_empty()).then(|| IncompleteRelease {
+                    release_artists: Arc::clone(artists),
+                    release: Arc::clone(release),
+                    disc_number,
+                    total_tracks: total,
+                    present,
+                    missing,
+                })
+            })
+            .collect();
+        releases.sort_by_cached_key(|r| {
+            let artists = r.release_artists.join(", ").to_lowercase();
+            (artists, r.release.to_lowercase(), r.disc_number)
+        });
+
+        Self { releases }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.releases.is_empty()
+    }
+
+    pub fn export_json(&self, writer: impl Write) -> std::io::Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Writes one row per disc, the track numbers are separated like multiple artists.
+    pub fn export_csv(&self, mut writer: impl Write) -> std::io::Result<()> {
+        write_csv_row(&mut writer, CSV_HEADER.iter().map(|h| h.to_string()))?;
+
+        let list = |numbers: &[u16]| {
+            numbers.iter().map(u16::to_string).collect::<Vec<_>>().join(CSV_LIST_SEPARATOR)
+        };
+        for r in self.releases.iter() {
+            let row = [
+                r.release_artists.join(CSV_LIST_SEPARATOR),
+                r.release.to_string(),
+                r.disc_number.map(|n| n.to_string()).unwrap_or_default(),
+                r.total_tracks.to_string(),
+                list(&r.present),
+                list(&r.missing),
+            ];
+            write_csv_row(&mut writer, row.into_iter())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::testing::{song, SongTags};
+
+    #[test]
+    fn lists_missing_tracks() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/Bar/1.mp3", "Bar", "Foo").with_track(1, Some(4)),
+            song("/music/Bar/3.mp3", "Bar", "Foo").with_track(3, Some(4)),
+            song("/music/Bar/9.mp3", "Bar", "Foo").with_track(9, Some(4)),
+            song("/music/Baz/1.mp3", "Baz", "Foo").with_track(1, Some(2)),
+            song("/music/Baz/2.mp3", "Baz", "Foo").with_track(2, Some(2)),
+            song("/music/Qux/1.mp3", "Qux", "Foo").with_track(1, None),
+        ];
+
+        let report = IncompleteReport::generate(&index);
+
+        assert_eq!(report.releases.len(), 1);
+        let r = &report.releases[0];
+        assert_eq!(&*r.release, "Bar");
+        assert_eq!(r.total_tracks, 4);
+        assert_eq!(r.present, [1, 3]);
+        assert_eq!(r.missing, [2, 4]);
+
+        let mut csv = Vec::new();
+        report.export_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().nth(1), Some("Foo,Bar,,4,1; 3,2; 4"));
+    }
+}
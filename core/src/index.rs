@@ -0,0 +1,871 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::DirEntry;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crossbeam_channel::{Receiver, Sender};
+use serde::Serialize;
+use tracing::{debug, info, info_span, warn};
+
+use crate::fs::{
+    is_companion_extension, is_hidden, is_image_extension, is_music_video_extension,
+    is_playlist_extension, is_song_extension, ExtraKind,
+};
+use crate::meta::primary_artist;
+use crate::{
+    ArtistSeparators, Audiobook, CacheEntry, CancelToken, IndexCache, Metadata, MetadataError,
+    Mode, Song, Video,
+};
+
+/// Songs with this `TXXX` frame, vorbis comment or freeform atom set to [`SKIP_MARKER_VALUE`] are
+/// left alone, see [`MusicIndex::skipped`].
+pub const SKIP_MARKER_FIELD: &str = "ORGANIZER";
+/// Compared case insensitively.
+pub const SKIP_MARKER_VALUE: &str = "skip";
+
+/// Entries that mark a directory as managed by another tool, whose files shouldn't be moved
+/// underneath it: the `.stfolder` of a Syncthing folder, the `.sync` dir of Resilio Sync and the
+/// import state of beets.
+pub const DEFAULT_FOREIGN_MARKERS: [&str; 3] = [".stfolder", ".sync", "state.pickle"];
+
+/// The first of the `markers` inside of `dir`.
+pub(crate) fn foreign_marker<'a>(
+    markers: &'a [String],
+    dir: &Path,
+    exists: impl Fn(&Path) -> bool,
+) -> Option<&'a str> {
+    markers.iter().map(String::as_str).find(|m| exists(&dir.join(m)))
+}
+
+/// Whether the song is tagged with the [`SKIP_MARKER_FIELD`].
+pub(crate) fn has_skip_marker(m: &Metadata) -> bool {
+    m.extra.get(SKIP_MARKER_FIELD).is_some_and(|v| v.trim().eq_ignore_ascii_case(SKIP_MARKER_VALUE))
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MusicIndex {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub music_dir: PathBuf,
+    /// Directories inside of the music dir that won't be indexed, e.g. a nested output dir.
+    #[serde(serialize_with = "crate::serde_path::lossy_vec")]
+    pub excluded_dirs: Vec<PathBuf>,
+    /// If present the cached metadata is used for unchanged files. After reading the index it
+    /// contains the metadata of all indexed songs.
+    #[serde(skip)]
+    pub cache: Option<IndexCache>,
+    /// Number of threads reading directories and metadata, defaults to the available parallelism.
+    #[serde(skip)]
+    pub threads: Option<NonZeroUsize>,
+    /// Follow symbolic links to files and directories, each directory is only indexed once.
+    pub follow_symlinks: bool,
+    /// Maximum depth of indexed entries, files directly inside of the music dir have a depth of 1.
+    pub max_depth: Option<usize>,
+    /// Index files and directories whose name starts with a dot.
+    pub include_hidden: bool,
+    /// Artists are split on these separators when creating songs from the read metadata. Cached
+    /// metadata is stored unsplit, so changing them doesn't require a rescan.
+    #[serde(skip)]
+    pub artist_separators: ArtistSeparators,
+    /// Names of `TXXX` frames, vorbis comments and freeform atoms that are read into
+    /// [`Song::extra`], compared case insensitively.
+    #[serde(skip)]
+    pub custom_fields: Vec<String>,
+    /// Names of entries that mark a directory inside of the music dir as managed by another
+    /// tool, see [`DEFAULT_FOREIGN_MARKERS`]. Such directories aren't indexed.
+    #[serde(skip)]
+    pub foreign_markers: Vec<String>,
+    /// Checked before each directory entry, after cancelling the index is incomplete.
+    #[serde(skip)]
+    pub cancel: CancelToken,
+    pub songs: Vec<Song>,
+    #[serde(serialize_with = "crate::serde_path::lossy_vec")]
+    pub unknown: Vec<PathBuf>,
+    #[serde(serialize_with = "crate::serde_path::lossy_vec")]
+    pub images: Vec<PathBuf>,
+    /// Rip logs, cue sheets and similar files, which are moved with the songs of their directory
+    /// like images.
+    #[serde(serialize_with = "crate::serde_path::lossy_vec")]
+    pub companions: Vec<PathBuf>,
+    /// Documents, videos and archives, see [`ExtraKind`] and [`ExtraPolicy`](crate::ExtraPolicy).
+    #[serde(serialize_with = "crate::serde_path::lossy_vec")]
+    pub extras: Vec<PathBuf>,
+    /// Tagged music videos, untagged videos are extra files.
+    pub videos: Vec<Video>,
+    /// Tagged m4b files, which are classified after reading the index. Untagged ones are unknown.
+    pub audiobooks: Vec<Audiobook>,
+    /// Playlists are never moved, but their entries are updated when songs are.
+    #[serde(serialize_with = "crate::serde_path::lossy_vec")]
+    pub playlists: Vec<PathBuf>,
+    /// Songs tagged with the [`SKIP_MARKER_FIELD`], which are never moved or retagged, e.g.
+    /// intentionally odd files like hidden track collages.
+    #[serde(serialize_with = "crate::serde_path::lossy_vec")]
+    pub skipped: Vec<PathBuf>,
+    /// Directories that weren't indexed, since they contain one of the
+    /// [`MusicIndex::foreign_markers`].
+    #[serde(serialize_with = "crate::serde_path::lossy_vec")]
+    pub foreign_dirs: Vec<PathBuf>,
+    /// The number of ignored files of each unsupported extension, lowercase and empty for files
+    /// without one, see [`MusicIndex::unsupported_by_count`].
+    pub unsupported: BTreeMap<String, usize>,
+    /// The size in bytes of each indexed file, used to estimate the amount of data a plan moves
+    /// or copies, see [`Changes::transfer_size`](crate::Changes::transfer_size).
+    #[serde(skip)]
+    pub sizes: HashMap<PathBuf, u64>,
+    /// The modification time of each indexed file, so files that are modified before the
+    /// changes are written can be skipped.
+    #[serde(skip)]
+    pub modified: HashMap<PathBuf, SystemTime>,
+}
+
+struct MusicIndexBuilder {
+    job_receiver: Receiver<Job>,
+    job_sender: Sender<Job>,
+    /// Number of jobs that were queued but not yet completed.
+    pending_jobs: Arc<AtomicUsize>,
+    num_threads: usize,
+    item_sender: Sender<Item>,
+    excluded_dirs: Arc<Vec<PathBuf>>,
+    cache: Arc<Option<IndexCache>>,
+    follow_symlinks: bool,
+    max_depth: Option<usize>,
+    include_hidden: bool,
+    artist_separators: Arc<ArtistSeparators>,
+    custom_fields: Arc<Vec<String>>,
+    foreign_markers: Arc<Vec<String>>,
+    visited_dirs: Arc<Mutex<HashSet<DirId>>>,
+    cancel: CancelToken,
+}
+
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(unix)]
+fn dir_id(path: &Path) -> Option<DirId> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(path: &Path) -> Option<DirId> {
+    std::fs::canonicalize(path).ok()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EntryKind {
+    File,
+    Dir,
+}
+
+/// Symbolic links are only resolved if they should be followed, otherwise they are ignored.
+fn entry_kind(e: &DirEntry, follow_symlinks: bool) -> Option<EntryKind> {
+    let mut file_type = e.file_type().ok()?;
+    if file_type.is_symlink() {
+        if !follow_symlinks {
+            return None;
+        }
+        file_type = std::fs::metadata(e.path()).ok()?.file_type();
+    }
+
+    if file_type.is_file() {
+        Some(EntryKind::File)
+    } else if file_type.is_dir() {
+        Some(EntryKind::Dir)
+    } else {
+        None
+    }
+}
+
+enum Job {
+    /// A directory and its depth.
+    Dir(PathBuf, usize),
+    /// A single file, which is indexed without reading its directory.
+    File(PathBuf),
+    Stop,
+}
+
+enum Item {
+    Song(Song),
+    Unknown(PathBuf),
+    Skipped(PathBuf),
+    Image(PathBuf),
+    Companion(PathBuf),
+    Extra(PathBuf),
+    Video(Video),
+    Playlist(PathBuf),
+    /// A file whose extension isn't supported.
+    Unsupported(PathBuf),
+    /// A directory managed by another tool and the marker it contains.
+    Foreign(PathBuf, String),
+    /// The size and modification time of a file.
+    FileInfo(PathBuf, u64, Option<SystemTime>),
+    CacheEntry(CacheEntry),
+    DirScanned(PathBuf),
+    Warning(PathBuf, String),
+}
+
+/// Notifications emitted while reading the index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexEvent<'a> {
+    /// A song, image, companion file, extra file, playlist or unknown file was added to the index.
+    FileIndexed(&'a Path),
+    /// All entries of a directory were read.
+    DirScanned(&'a Path),
+    /// Something couldn't be indexed, indexing continues anyway.
+    Warning { path: &'a Path, reason: &'a str },
+}
+
+/// Completes a job once dropped, even if reading it panicked, so the other workers still stop
+/// after the last job.
+struct JobGuard {
+    pending_jobs: Arc<AtomicUsize>,
+    job_sender: Sender<Job>,
+    num_threads: usize,
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        // The last pending job was completed and no new ones were queued.
+        if self.pending_jobs.fetch_sub(1, Ordering::AcqRel) == 1 {
+            for _ in 0..self.num_threads {
+                let _ = self.job_sender.send(Job::Stop);
+            }
+        }
+    }
+}
+
+impl MusicIndexBuilder {
+    fn start(&mut self) {
+        loop {
+            match self.job_receiver.recv() {
+                Ok(Job::Dir(p, depth)) => {
+                    let _guard = self.job_guard();
+                    self.read(p, depth);
+                }
+                Ok(Job::File(p)) => {
+                    let _guard = self.job_guard();
+                    self.read_file(p);
+                }
+                Ok(Job::Stop) | Err(_) => break,
+            }
+        }
+    }
+
+    fn job_guard(&self) -> JobGuard {
+        JobGuard {
+            pending_jobs: self.pending_jobs.clone(),
+            job_sender: self.job_sender.clone(),
+            num_threads: self.num_threads,
+        }
+    }
+
+    /// Returns true if the directory wasn't visited before. Without following symbolic links a
+    /// directory can't be reached twice.
+    fn visit(&self, dir: &Path) -> bool {
+        if !self.follow_symlinks {
+            return true;
+        }
+        match dir_id(dir) {
+            Some(id) => self.visited_dirs.lock().unwrap().insert(id),
+            None => true,
+        }
+    }
+
+    fn read(&mut self, dir: PathBuf, depth: usize) {
+        if self.max_depth.is_some_and(|max| depth >= max) || !self.visit(&dir) {
+            return;
+        }
+
+        let r = match std::fs::read_dir(&dir) {
+            Ok(r) => r,
+            Err(e) => {
+                let _ = self.item_sender.send(Item::Warning(dir, e.to_string()));
+                return;
+            }
+        };
+        for e in r.into_iter() {
+            let e = match e {
+                Ok(e) => e,
+                Err(e) => {
+                    let _ = self.item_sender.send(Item::Warning(dir.clone(), e.to_string()));
+                    continue;
+                }
+            };
+            if self.cancel.is_cancelled() {
+                return;
+            }
+            let p = e.path();
+            if !self.include_hidden && is_hidden(&p) {
+                continue;
+            }
+
+            let kind = entry_kind(&e, self.follow_symlinks);
+            if kind == Some(EntryKind::File) {
+                self.add_item(p);
+            } else if kind == Some(EntryKind::Dir) {
+                if self.excluded_dirs.contains(&p) {
+                    continue;
+                }
+                let exists = |p: &Path| p.symlink_metadata().is_ok();
+                if let Some(marker) = foreign_marker(&self.foreign_markers, &p, exists) {
+                    let _ = self.item_sender.send(Item::Foreign(p, marker.to_owned()));
+                    continue;
+                }
+                self.pending_jobs.fetch_add(1, Ordering::AcqRel);
+                if let Err(e) = self.job_sender.send(Job::Dir(p.clone(), depth + 1)) {
+                    self.pending_jobs.fetch_sub(1, Ordering::AcqRel);
+                    let _ = self.item_sender.send(Item::Warning(p, e.to_string()));
+                }
+            }
+        }
+        let _ = self.item_sender.send(Item::DirScanned(dir));
+    }
+
+    fn read_file(&mut self, p: PathBuf) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
+        if !p.is_file() {
+            let _ = self.item_sender.send(Item::Warning(p, "not a file".into()));
+            return;
+        }
+        if self.excluded_dirs.iter().any(|d| p.starts_with(d)) {
+            let _ = self.item_sender.send(Item::Warning(p, "inside of an excluded dir".into()));
+            return;
+        }
+
+        let ext = p.extension().unwrap_or_default();
+        if !is_song_extension(ext)
+            && !is_image_extension(ext)
+            && !is_companion_extension(ext)
+            && ExtraKind::from_extension(ext).is_none()
+            && !is_playlist_extension(ext)
+        {
+            let reason = "not a song, image, companion file, extra file or playlist".into();
+            let _ = self.item_sender.send(Item::Warning(p, reason));
+            return;
+        }
+        self.add_item(p);
+    }
+
+    fn add_item(&mut self, p: PathBuf) {
+        let extension = match p.extension() {
+            Some(e) => e,
+            None => {
+                let _ = self.item_sender.send(Item::Unsupported(p));
+                return;
+            }
+        };
+        if let Ok(meta) = std::fs::metadata(&p) {
+            let item = Item::FileInfo(p.clone(), meta.len(), meta.modified().ok());
+            let _ = self.item_sender.send(item);
+        }
+
+        if is_song_extension(extension) {
+            let m = self.read_metadata(&p);
+            self.add_song(p, m);
+        } else if is_image_extension(extension) {
+            let _ = self.item_sender.send(Item::Image(p));
+        } else if is_companion_extension(extension) {
+            let _ = self.item_sender.send(Item::Companion(p));
+        } else if is_music_video_extension(extension) {
+            let m = self.read_metadata(&p);
+            let item = match video_from_metadata(p, m, &self.artist_separators) {
+                Ok(video) => Item::Video(video),
+                Err(p) => Item::Extra(p),
+            };
+            let _ = self.item_sender.send(item);
+        } else if ExtraKind::from_extension(extension).is_some() {
+            let _ = self.item_sender.send(Item::Extra(p));
+        } else if is_playlist_extension(extension) {
+            let _ = self.item_sender.send(Item::Playlist(p));
+        } else {
+            let _ = self.item_sender.send(Item::Unsupported(p));
+        }
+    }
+
+    fn read_metadata(&mut self, p: &Path) -> Metadata {
+        match read_metadata(self.cache.as_ref().as_ref(), p) {
+            Ok((metadata, entry)) => {
+                if let Some(entry) = entry {
+                    let _ = self.item_sender.send(Item::CacheEntry(entry));
+                }
+                metadata
+            }
+            Err(e) => {
+                let _ = self.item_sender.send(Item::Warning(p.to_owned(), e.to_string()));
+                Metadata::default()
+            }
+        }
+    }
+
+    fn add_song(&mut self, p: PathBuf, m: Metadata) {
+        if has_skip_marker(&m) {
+            let _ = self.item_sender.send(Item::Skipped(p));
+            return;
+        }
+        let item = match song_from_metadata(p, m, &self.artist_separators, &self.custom_fields) {
+            Ok(song) => Item::Song(song),
+            Err(p) => Item::Unknown(p),
+        };
+        let _ = self.item_sender.send(item);
+    }
+}
+
+/// Reads the metadata of a song or takes it from the cache if the file is unchanged. If a cache
+/// is used a new entry is also returned. Files whose tags can't be read aren't cached, so they
+/// are reported again by the next run.
+pub(crate) fn read_metadata(
+    cache: Option<&IndexCache>,
+    p: &Path,
+) -> Result<(Metadata, Option<CacheEntry>), MetadataError> {
+    let Some(cache) = cache else { return Ok((Metadata::try_read_from(p)?, None)) };
+    let Ok(fs_meta) = std::fs::metadata(p) else { return Ok((Metadata::try_read_from(p)?, None)) };
+    let Ok(modified) = fs_meta.modified() else { return Ok((Metadata::try_read_from(p)?, None)) };
+    let size = fs_meta.len();
+
+    let metadata = match cache.get(p, size, modified) {
+        Some(m) => {
+            let mut m = m.clone();
+            m.mode = Some(Mode::from_metadata(&fs_meta));
+            m
+        }
+        None => Metadata::try_read_from(p)?,
+    };
+
+    let entry = CacheEntry { path: p.to_owned(), size, modified, metadata: metadata.clone() };
+    Ok((metadata, Some(entry)))
+}
+
+/// Songs without artists, release or title are unknown and the path is returned as error.
+pub(crate) fn song_from_metadata(
+    p: PathBuf,
+    mut m: Metadata,
+    separators: &ArtistSeparators,
+    custom_fields: &[String],
+) -> Result<Song, PathBuf> {
+    m.artists = separators.split_artists(m.artists);
+    m.release_artists = separators.split_artists(m.release_artists);
+    let Some(release_artists) = m.release_artists() else { return Err(p) };
+    // group on the primary artist, the featuring credit is kept in the song artists
+    let mut release_artists: Vec<String> =
+        release_artists.iter().map(|a| primary_artist(a).to_owned()).collect();
+    release_artists.dedup();
+    let Some(song_artists) = m.song_artists() else { return Err(p) };
+    let Some(release) = &m.release else { return Err(p) };
+    let Some(title) = &m.title else { return Err(p) };
+
+    Ok(Song {
+        mode: m.mode,
+        track_number: m.track_number,
+        total_tracks: m.total_tracks,
+        disc_number: m.disc_number,
+        total_discs: m.total_discs,
+        release_artists: Arc::from(release_artists),
+        artists: Arc::from(song_artists),
+        release: Arc::from(release.as_str()),
+        title: title.to_owned(),
+        year: m.year,
+        genre: m.genre.clone(),
+        has_artwork: m.has_artwork,
+        audio: m.audio,
+        extra: m
+            .extra
+            .into_iter()
+            .filter(|(k, _)| custom_fields.iter().any(|f| f.eq_ignore_ascii_case(k)))
+            .collect(),
+        path: p,
+    })
+}
+
+/// Videos without artists or title are extra files and the path is returned as error.
+pub(crate) fn video_from_metadata(
+    p: PathBuf,
+    mut m: Metadata,
+    separators: &ArtistSeparators,
+) -> Result<Video, PathBuf> {
+    m.artists = separators.split_artists(m.artists);
+    m.release_artists = separators.split_artists(m.release_artists);
+    let Some(artists) = m.song_artists().map(<[String]>::to_vec) else { return Err(p) };
+    let Some(title) = m.title else { return Err(p) };
+
+    Ok(Video { artists, title, year: m.year, genre: m.genre, path: p })
+}
+
+/// Deduplicates artist and release names, so songs of the same release share one allocation.
+/// This also speeds up comparisons, since equal [`Arc`]s are compared by pointer first.
+#[derive(Default)]
+pub(crate) struct Interner {
+    names: HashSet<Arc<[String]>>,
+    strs: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub(crate) fn intern_song(&mut self, song: &mut Song) {
+        song.release_artists = self.intern_names(&song.release_artists);
+        song.artists = self.intern_names(&song.artists);
+        song.release = self.intern_str(&song.release);
+    }
+
+    fn intern_names(&mut self, names: &Arc<[String]>) -> Arc<[String]> {
+        match self.names.get(names) {
+            Some(n) => Arc::clone(n),
+            None => {
+                self.names.insert(Arc::clone(names));
+                Arc::clone(names)
+            }
+        }
+    }
+
+    fn intern_str(&mut self, str: &Arc<str>) -> Arc<str> {
+        match self.strs.get(str) {
+            Some(s) => Arc::clone(s),
+            None => {
+                self.strs.insert(Arc::clone(str));
+                Arc::clone(str)
+            }
+        }
+    }
+}
+
+impl MusicIndex {
+    /// Quickly counts the files that would be indexed using only their names, without reading
+    /// any metadata.
+    pub fn count_files(&self) -> usize {
+        fn count(
+            index: &MusicIndex,
+            visited: &mut HashSet<DirId>,
+            dir: &Path,
+            depth: usize,
+        ) -> usize {
+            if index.max_depth.is_some_and(|max| depth >= max) {
+                return 0;
+            }
+            if index.follow_symlinks && dir_id(dir).is_some_and(|id| !visited.insert(id)) {
+                return 0;
+            }
+            let Ok(r) = std::fs::read_dir(dir) else { return 0 };
+
+            let mut n = 0;
+            for e in r.into_iter().filter_map(|e| e.ok()) {
+                let p = e.path();
+                if !index.include_hidden && is_hidden(&p) {
+                    continue;
+                }
+                match entry_kind(&e, index.follow_symlinks) {
+                    Some(EntryKind::File) => {
+                        let ext = p.extension().unwrap_or_default();
+                        if is_song_extension(ext)
+                            || is_image_extension(ext)
+                            || is_companion_extension(ext)
+                            || ExtraKind::from_extension(ext).is_some()
+                            || is_playlist_extension(ext)
+                        {
+                            n += 1;
+                        }
+                    }
+                    Some(EntryKind::Dir) if !index.excluded_dirs.contains(&p) => {
+                        let exists = |p: &Path| p.symlink_metadata().is_ok();
+                        if foreign_marker(&index.foreign_markers, &p, exists).is_none() {
+                            n += count(index, visited, &p, depth + 1);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            n
+        }
+
+        count(self, &mut HashSet::new(), &self.music_dir, 0)
+    }
+
+    pub fn read(&mut self, f: &mut impl FnMut(IndexEvent)) {
+        let _span = info_span!("index", music_dir = %self.music_dir.display()).entered();
+        self.run_jobs(vec![Job::Dir(self.music_dir.clone(), 0)], f);
+    }
+
+    /// Indexes only the given files instead of walking the music dir. Relative paths are resolved
+    /// against the current directory, files outside of the music dir are skipped with a warning.
+    pub fn read_files(
+        &mut self,
+        files: impl IntoIterator<Item = PathBuf>,
+        f: &mut impl FnMut(IndexEvent),
+    ) {
+        let _span = info_span!("index", music_dir = %self.music_dir.display()).entered();
+        let mut jobs = Vec::new();
+        for p in files {
+            let p = std::path::absolute(&p).unwrap_or(p);
+            let reason = if !p.starts_with(&self.music_dir) {
+                "outside of the music dir"
+            } else if self.foreign_ancestor(&p).is_some() {
+                "inside of a dir managed by another tool"
+            } else {
+                jobs.push(Job::File(p));
+                continue;
+            };
+            warn!(path = %p.display(), reason);
+            f(IndexEvent::Warning { path: &p, reason });
+        }
+        self.run_jobs(jobs, f);
+    }
+
+    /// The dir between the music dir and `path` that contains one of the foreign markers.
+    fn foreign_ancestor<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        let exists = |p: &Path| p.symlink_metadata().is_ok();
+        (path.ancestors().skip(1))
+            .take_while(|a| *a != self.music_dir && a.starts_with(&self.music_dir))
+            .find(|a| foreign_marker(&self.foreign_markers, a, exists).is_some())
+    }
+
+    fn run_jobs(&mut self, jobs: Vec<Job>, f: &mut impl FnMut(IndexEvent)) {
+        let mut f = |e: IndexEvent| {
+            match e {
+                IndexEvent::FileIndexed(p) => debug!(path = %p.display(), "file indexed"),
+                IndexEvent::DirScanned(p) => debug!(path = %p.display(), "dir scanned"),
+                IndexEvent::Warning { path, reason } => warn!(path = %path.display(), reason),
+            }
+            f(e);
+        };
+        let (item_sender, item_receiver) = crossbeam_channel::unbounded();
+        let (job_sender, job_receiver) = crossbeam_channel::unbounded();
+        let pending_jobs = Arc::new(AtomicUsize::new(jobs.len()));
+
+        let excluded_dirs = Arc::new(self.excluded_dirs.clone());
+        let artist_separators = Arc::new(self.artist_separators.clone());
+        let custom_fields = Arc::new(self.custom_fields.clone());
+        let foreign_markers = Arc::new(self.foreign_markers.clone());
+        let cache = Arc::new(self.cache.take());
+        let visited_dirs = Arc::new(Mutex::new(HashSet::new()));
+        let mut new_cache = cache.as_ref().as_ref().map(|_| IndexCache::default());
+
+        let num_threads = self
+            .threads
+            .or_else(|| std::thread::available_parallelism().ok())
+            .map_or(8, NonZeroUsize::get);
+
+        let mut threads = Vec::new();
+        for _ in 0..num_threads {
+            let mut builder = MusicIndexBuilder {
+                job_receiver: job_receiver.clone(),
+                job_sender: job_sender.clone(),
+                pending_jobs: Arc::clone(&pending_jobs),
+                num_threads,
+                item_sender: item_sender.clone(),
+                excluded_dirs: Arc::clone(&excluded_dirs),
+                cache: Arc::clone(&cache),
+                follow_symlinks: self.follow_symlinks,
+                max_depth: self.max_depth,
+                include_hidden: self.include_hidden,
+                artist_separators: Arc::clone(&artist_separators),
+                custom_fields: Arc::clone(&custom_fields),
+                foreign_markers: Arc::clone(&foreign_markers),
+                visited_dirs: Arc::clone(&visited_dirs),
+                cancel: self.cancel.clone(),
+            };
+            let t = std::thread::spawn(move || {
+                builder.start();
+            });
+            threads.push(t);
+        }
+
+        if jobs.is_empty() {
+            for _ in 0..num_threads {
+                let _ = job_sender.send(Job::Stop);
+            }
+        }
+        for job in jobs {
+            if let Err(e) = job_sender.send(job) {
+                let reason = e.to_string();
+                f(IndexEvent::Warning { path: &self.music_dir, reason: &reason });
+            }
+        }
+
+        drop(job_sender);
+        drop(item_sender);
+
+        let mut interner = Interner::default();
+        while let Ok(i) = item_receiver.recv() {
+            match i {
+                Item::Song(mut s) => {
+                    interner.intern_song(&mut s);
+                    f(IndexEvent::FileIndexed(&s.path));
+                    self.songs.push(s);
+                }
+                Item::Unknown(p) => {
+                    f(IndexEvent::FileIndexed(&p));
+                    self.unknown.push(p);
+                }
+                Item::Skipped(p) => {
+                    debug!(path = %p.display(), "skipped by marker");
+                    f(IndexEvent::FileIndexed(&p));
+                    self.skipped.push(p);
+                }
+                Item::Image(p) => {
+                    f(IndexEvent::FileIndexed(&p));
+                    self.images.push(p);
+                }
+                Item::Companion(p) => {
+                    f(IndexEvent::FileIndexed(&p));
+                    self.companions.push(p);
+                }
+                Item::Extra(p) => {
+                    f(IndexEvent::FileIndexed(&p));
+                    self.extras.push(p);
+                }
+                Item::Video(v) => {
+                    f(IndexEvent::FileIndexed(&v.path));
+                    self.videos.push(v);
+                }
+                Item::Playlist(p) => {
+                    f(IndexEvent::FileIndexed(&p));
+                    self.playlists.push(p);
+                }
+                Item::Unsupported(p) => {
+                    debug!(path = %p.display(), "unsupported");
+                    self.add_unsupported(&p);
+                }
+                Item::Foreign(p, marker) => {
+                    info!(path = %p.display(), marker, "skipped dir managed by another tool");
+                    self.foreign_dirs.push(p);
+                }
+                Item::FileInfo(p, size, modified) => {
+                    if let Some(modified) = modified {
+                        self.modified.insert(p.clone(), modified);
+                    }
+                    self.sizes.insert(p, size);
+                }
+                Item::DirScanned(p) => f(IndexEvent::DirScanned(&p)),
+                Item::Warning(p, reason) => f(IndexEvent::Warning { path: &p, reason: &reason }),
+                Item::CacheEntry(e) => {
+                    if let Some(c) = &mut new_cache {
+                        c.insert(e);
+                    }
+                }
+            }
+        }
+        self.cache = new_cache;
+
+        for t in threads {
+            if t.join().is_err() {
+                let reason = "index builder thread panicked";
+                f(IndexEvent::Warning { path: &self.music_dir, reason });
+            }
+        }
+
+        self.classify_audiobooks();
+        info!(
+            songs = self.songs.len(),
+            audiobooks = self.audiobooks.len(),
+            images = self.images.len(),
+            companions = self.companions.len(),
+            unknown = self.unknown.len(),
+            playlists = self.playlists.len(),
+            "indexed"
+        );
+    }
+}
+
+impl MusicIndex {
+    pub(crate) fn add_unsupported(&mut self, path: &Path) {
+        let ext = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+        *self.unsupported.entry(ext).or_default() += 1;
+    }
+
+    /// The unsupported extensions and their number of files, the most common first.
+    pub fn unsupported_by_count(&self) -> Vec<(&str, usize)> {
+        let mut counts: Vec<_> = self.unsupported.iter().map(|(e, &n)| (e.as_str(), n)).collect();
+        counts.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+        counts
+    }
+}
+
+impl From<PathBuf> for MusicIndex {
+    fn from(music_dir: PathBuf) -> Self {
+        Self { music_dir, ..Default::default() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_workers_after_a_panicking_job() {
+        let (job_sender, job_receiver) = crossbeam_channel::unbounded();
+        let pending_jobs = Arc::new(AtomicUsize::new(1));
+        let guard = JobGuard { pending_jobs: pending_jobs.clone(), job_sender, num_threads: 2 };
+
+        let r = std::panic::catch_unwind(move || {
+            let _guard = guard;
+            panic!("reading failed");
+        });
+
+        assert!(r.is_err());
+        assert_eq!(pending_jobs.load(Ordering::Acquire), 0);
+        let stops = job_receiver.try_iter().filter(|j| matches!(j, Job::Stop)).count();
+        assert_eq!(stops, 2);
+    }
+
+    #[test]
+    fn detects_skip_marker() {
+        let metadata = |value: &str| Metadata {
+            extra: [(SKIP_MARKER_FIELD.to_owned(), value.to_owned())].into(),
+            ..Default::default()
+        };
+        assert!(has_skip_marker(&metadata("Skip ")));
+        assert!(!has_skip_marker(&metadata("keep")));
+        assert!(!has_skip_marker(&Metadata::default()));
+    }
+
+    #[test]
+    fn counts_unsupported_extensions() {
+        let dir =
+            std::env::temp_dir().join(format!("music-organizer-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["a.ogg", "b.OGG", "c.wma", "d", "cover.jpg"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let mut index = MusicIndex::from(dir.clone());
+        index.read(&mut |_| ());
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(index.unsupported_by_count(), [("ogg", 2), ("", 1), ("wma", 1)]);
+        assert_eq!(index.images.len(), 1);
+    }
+
+    #[test]
+    fn skips_dirs_managed_by_other_tools() {
+        let dir = std::env::temp_dir()
+            .join(format!("music-organizer-index-foreign-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("Synced/.stfolder")).unwrap();
+        std::fs::create_dir_all(dir.join("Foo")).unwrap();
+        std::fs::create_dir_all(dir.join(".stfolder")).unwrap();
+        for name in ["Synced/cover.jpg", "Foo/cover.jpg"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let mut index = MusicIndex::from(dir.clone());
+        index.foreign_markers = DEFAULT_FOREIGN_MARKERS.map(str::to_owned).to_vec();
+        let count = index.count_files();
+        index.read(&mut |_| ());
+        let mut warnings = Vec::new();
+        index.read_files([dir.join("Synced/cover.jpg")], &mut |e| {
+            if let IndexEvent::Warning { reason, .. } = e {
+                warnings.push(reason.to_owned());
+            }
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(count, 1);
+        assert_eq!(index.images, [dir.join("Foo/cover.jpg")]);
+        assert_eq!(index.foreign_dirs, [dir.join("Synced")]);
+        assert_eq!(warnings, ["inside of a dir managed by another tool"]);
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use tracing::debug;
+
+use crate::fs::valid_os_str_dots;
+use crate::playlist::{percent_decode, unescape_xml};
+use crate::{util, ChangeOptions, Changes, Checks, Fs, MusicIndex, PlaylistCreation, Song, Value};
+
+/// The tracks and playlists of an iTunes or Apple Music `Library.xml` export.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ItunesLibrary {
+    /// The media folder of the library, used to map tracks into the music dir if their location
+    /// doesn't match an indexed song.
+    pub music_folder: Option<PathBuf>,
+    pub tracks: Vec<ItunesTrack>,
+    pub playlists: Vec<ItunesPlaylist>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ItunesTrack {
+    pub id: u64,
+    pub location: PathBuf,
+    /// From 0 to 100 in steps of 20 per star, ratings computed from the album rating are left
+    /// out.
+    pub rating: Option<u8>,
+    pub play_count: Option<u32>,
+}
+
+/// A user created or smart playlist, the library itself and folders are left out.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ItunesPlaylist {
+    pub name: String,
+    pub track_ids: Vec<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ItunesError {
+    /// The byte offset at which parsing failed.
+    InvalidXml(usize),
+    MissingTracks,
+}
+
+impl std::fmt::Display for ItunesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidXml(pos) => write!(f, "invalid plist at byte {pos}"),
+            Self::MissingTracks => write!(f, "missing 'Tracks' dict"),
+        }
+    }
+}
+
+impl std::error::Error for ItunesError {}
+
+impl FromStr for ItunesLibrary {
+    type Err = ItunesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { s, pos: 0 };
+        let root = loop {
+            match parser.next_tag()? {
+                Tag { name: "plist", closing: false, .. } => break parser.next_value()?,
+                _ => continue,
+            }
+        };
+
+        let music_folder = root.get("Music Folder").and_then(Plist::as_str).and_then(file_url);
+        let Some(Plist::Dict(entries)) = root.get("Tracks") else {
+            return Err(ItunesError::MissingTracks);
+        };
+        let mut tracks = Vec::new();
+        for (_, t) in entries.iter() {
+            let id = t.get("Track ID").and_then(Plist::as_int);
+            let location = t.get("Location").and_then(Plist::as_str).and_then(file_url);
+            let (Some(id), Some(location)) = (id, location) else { continue };
+            let computed = t.get("Rating Computed").is_some_and(|c| *c == Plist::Bool(true));
+            let rating = t.get("Rating").and_then(Plist::as_int).filter(|_| !computed);
+            tracks.push(ItunesTrack {
+                id: id as u64,
+                location,
+                rating: rating.map(|r| r.clamp(0, 100) as u8),
+                play_count: t.get("Play Count").and_then(Plist::as_int).map(|c| c as u32),
+            });
+        }
+
+        let mut playlists = Vec::new();
+        if let Some(Plist::Array(entries)) = root.get("Playlists") {
+            for p in entries.iter() {
+                let special = ["Master", "Folder"]
+                    .iter()
+                    .any(|k| p.get(k).is_some_and(|v| *v == Plist::Bool(true)))
+                    || p.get("Distinguished Kind").is_some();
+                let Some(name) = p.get("Name").and_then(Plist::as_str).filter(|_| !special) else {
+                    continue;
+                };
+                let track_ids = match p.get("Playlist Items") {
+                    Some(Plist::Array(items)) => items
+                        .iter()
+                        .filter_map(|i| i.get("Track ID")?.as_int())
+                        .map(|id| id as u64)
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                playlists.push(ItunesPlaylist { name: name.to_owned(), track_ids });
+            }
+        }
+
+        Ok(Self { music_folder, tracks, playlists })
+    }
+}
+
+impl ItunesLibrary {
+    /// Maps track ids to indexed songs, either by their location or, if the library was moved,
+    /// by their location relative to the music folder of the library.
+    pub fn songs<'a>(&self, index: &'a MusicIndex) -> HashMap<u64, &'a Song> {
+        let songs: HashMap<&Path, &Song> = index.songs.iter().map(|s| (&*s.path, s)).collect();
+        let mut mapped = HashMap::new();
+        for t in self.tracks.iter() {
+            let relocated = (self.music_folder.as_ref())
+                .and_then(|f| t.location.strip_prefix(f).ok())
+                .map(|p| index.music_dir.join(p));
+            let song = songs
+                .get(t.location.as_path())
+                .or_else(|| songs.get(relocated.as_deref()?))
+                .copied();
+            match song {
+                Some(s) => {
+                    mapped.insert(t.id, s);
+                }
+                None => debug!(location = %t.location.display(), "unmatched itunes track"),
+            }
+        }
+        mapped
+    }
+}
+
+impl Checks<'_> {
+    /// Carries the ratings and play counts of the library over into the tags of the matching
+    /// songs.
+    pub fn import_itunes(&mut self, library: &ItunesLibrary) {
+        let songs = library.songs(self.index);
+        for t in library.tracks.iter() {
+            let Some(song) = songs.get(&t.id) else { continue };
+            let rating = t.rating.filter(|r| *r > 0);
+            let play_count = t.play_count.filter(|c| *c > 0);
+            if rating.is_none() && play_count.is_none() {
+                continue;
+            }
+            util::update_tag(
+                &mut self.song_operations,
+                &self.retag_excluded,
+                song,
+                "itunes import",
+                |u| {
+                    if let Some(r) = rating {
+                        u.rating = Value::Update(r);
+                    }
+                    if let Some(c) = play_count {
+                        u.play_count = Value::Update(c);
+                    }
+                },
+            );
+        }
+    }
+}
+
+impl Changes {
+    /// Plans an m3u playlist for each playlist of the library inside of `playlists` in the output
+    /// dir, using the paths of the songs after all changes. Tracks that aren't indexed are left
+    /// out, as are playlists that already exist with the same content.
+    pub fn generate_itunes_playlists(
+        &mut self,
+        fs: &impl Fs,
+        index: &MusicIndex,
+        library: &ItunesLibrary,
+        options: &ChangeOptions,
+    ) {
+        let songs = library.songs(index);
+        let dir = options.output_dir.join("playlists");
+        let mut dir_needed = false;
+        for p in library.playlists.iter() {
+            let songs: Vec<PathBuf> = p
+                .track_ids
+                .iter()
+                .filter_map(|id| songs.get(id))
+                .map(|s| self.new_song_path(s).to_owned())
+                .collect();
+            let path = dir.join(format!("{}.m3u", valid_os_str_dots(&p.name)));
+            if songs.is_empty() || self.playlist_creations.iter().any(|c| c.path == path) {
+                continue;
+            }
+            let creation = PlaylistCreation { path, songs };
+            if fs.read(&creation.path).ok() != Some(creation.content().into_bytes()) {
+                self.playlist_creations.push(creation);
+                dir_needed = true;
+            }
+        }
+        if dir_needed {
+            self.dir_creation(fs, options, &options.output_dir);
+            self.dir_creation(fs, options, &dir);
+        }
+    }
+}
+
+/// `file://localhost/Users/...` or `file:///C:/Users/...`
+fn file_url(url: &str) -> Option<PathBuf> {
+    let path = url.strip_prefix("file://")?;
+    let path = path.strip_prefix("localhost").unwrap_or(path);
+    Some(PathBuf::from(percent_decode(path)?))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Plist {
+    Dict(Vec<(String, Plist)>),
+    Array(Vec<Plist>),
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    /// Dates, data and reals, which aren't needed.
+    Other,
+}
+
+impl Plist {
+    fn get(&self, key: &str) -> Option<&Plist> {
+        match self {
+            Self::Dict(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    closing: bool,
+    self_closing: bool,
+}
+
+/// Just enough of an XML parser for the plists written by iTunes, which don't use attributes on
+/// value elements or mixed content.
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self) -> ItunesError {
+        ItunesError::InvalidXml(self.pos)
+    }
+
+    /// Skips text, the XML declaration, comments and the doctype.
+    fn next_tag(&mut self) -> Result<Tag<'a>, ItunesError> {
+        loop {
+            let start = self.pos + self.s[self.pos..].find('<').ok_or(self.error())?;
+            let rest = &self.s[start..];
+            let end = if rest.starts_with("<?") {
+                rest.find("?>").map(|e| e + 2)
+            } else if rest.starts_with("<!--") {
+                rest.find("-->").map(|e| e + 3)
+            } else {
+                rest.find('>').map(|e| e + 1)
+            };
+            self.pos = start;
+            self.pos += end.ok_or(self.error())?;
+            if rest.starts_with("<?") || rest.starts_with("<!") {
+                continue;
+            }
+
+            let inner = &self.s[start + 1..self.pos - 1];
+            let closing = inner.starts_with('/');
+            let self_closing = inner.ends_with('/');
+            let inner = inner.trim_matches('/');
+            let name = inner.split_whitespace().next().ok_or(self.error())?;
+            return Ok(Tag { name, closing, self_closing });
+        }
+    }
+
+    fn text(&mut self, tag: &Tag) -> Result<String, ItunesError> {
+        if tag.self_closing {
+            return Ok(String::new());
+        }
+        let close = format!("</{}>", tag.name);
+        let len = self.s[self.pos..].find(&close).ok_or(self.error())?;
+        let text = unescape_xml(&self.s[self.pos..self.pos + len]);
+        self.pos += len + close.len();
+        Ok(text)
+    }
+
+    fn next_value(&mut self) -> Result<Plist, ItunesError> {
+        let tag = self.next_tag()?;
+        self.value(tag)
+    }
+
+    fn value(&mut self, tag: Tag) -> Result<Plist, ItunesError> {
+        if tag.closing {
+            return Err(self.error());
+        }
+        let value = match tag.name {
+            "dict" if tag.self_closing => Plist::Dict(Vec::new()),
+            "dict" => {
+                let mut entries = Vec::new();
+                loop {
+                    let tag = self.next_tag()?;
+                    match tag {
+                        Tag { name: "dict", closing: true, .. } => break,
+                        Tag { name: "key", closing: false, .. } => {
+                            let key = self.text(&tag)?;
+                            entries.push((key, self.next_value()?));
+                        }
+                        _ => return Err(self.error()),
+                    }
+                }
+                Plist::Dict(entries)
+            }
+            "array" if tag.self_closing => Plist::Array(Vec::new()),
+            "array" => {
+                let mut items = Vec::new();
+                loop {
+                    let tag = self.next_tag()?;
+                    match tag {
+                        Tag { name: "array", closing: true, .. } => break,
+                        tag => items.push(self.value(tag)?),
+                    }
+                }
+                Plist::Array(items)
+            }
+            "string" => Plist::String(self.text(&tag)?),
+            "integer" => {
+                let text = self.text(&tag)?;
+                Plist::Integer(text.trim().parse().map_err(|_| self.error())?)
+            }
+            "true" => Plist::Bool(true),
+            "false" => Plist::Bool(false),
+            _ => {
+                self.text(&tag)?;
+                Plist::Other
+            }
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{song, SongTags};
+    use crate::MemFs;
+
+    const LIBRARY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple Computer//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>Major Version</key><integer>1</integer>
+	<key>Date</key><date>2024-01-01T00:00:00Z</date>
+	<key>Music Folder</key><string>file://localhost/Users/foo/Music/iTunes/iTunes%20Media/</string>
+	<key>Tracks</key>
+	<dict>
+		<key>10</key>
+		<dict>
+			<key>Track ID</key><integer>10</integer>
+			<key>Name</key><string>One &amp; Only</string>
+			<key>Rating</key><integer>80</integer>
+			<key>Play Count</key><integer>12</integer>
+			<key>Location</key><string>file://localhost/Users/foo/Music/iTunes/iTunes%20Media/Music/a.mp3</string>
+		</dict>
+		<key>11</key>
+		<dict>
+			<key>Track ID</key><integer>11</integer>
+			<key>Rating</key><integer>60</integer>
+			<key>Rating Computed</key><true/>
+			<key>Location</key><string>file://localhost/Users/foo/Music/iTunes/iTunes%20Media/Music/b.mp3</string>
+		</dict>
+	</dict>
+	<key>Playlists</key>
+	<array>
+		<dict>
+			<key>Name</key><string>Library</string>
+			<key>Master</key><true/>
+			<key>Playlist Items</key>
+			<array>
+				<dict><key>Track ID</key><integer>10</integer></dict>
+				<dict><key>Track ID</key><integer>11</integer></dict>
+			</array>
+		</dict>
+		<dict>
+			<key>Name</key><string>Favorites</string>
+			<key>Smart Info</key><data>AQEAAwAAAAIAAAAZAAAAAAAAAAcAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=</data>
+			<key>Playlist Items</key>
+			<array>
+				<dict><key>Track ID</key><integer>11</integer></dict>
+				<dict><key>Track ID</key><integer>10</integer></dict>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn parses_tracks_and_playlists() {
+        let library: ItunesLibrary = LIBRARY.parse().unwrap();
+        let folder = PathBuf::from("/Users/foo/Music/iTunes/iTunes Media/");
+        assert_eq!(library.music_folder, Some(folder));
+        assert_eq!(library.tracks.len(), 2);
+        assert_eq!(library.tracks[0].rating, Some(80));
+        assert_eq!(library.tracks[0].play_count, Some(12));
+        assert_eq!(library.tracks[1].rating, None);
+        let favorites = ItunesPlaylist { name: "Favorites".into(), track_ids: vec![11, 10] };
+        assert_eq!(library.playlists, vec![favorites]);
+    }
+
+    #[test]
+    fn imports_ratings_and_playlists_of_moved_library() {
+        let library: ItunesLibrary = LIBRARY.parse().unwrap();
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/Music/a.mp3", "Bar", "Foo").with_title("One"),
+            song("/music/Music/b.mp3", "Bar", "Foo").with_title("One"),
+        ];
+
+        let mut checks = Checks::from(&index);
+        checks.import_itunes(&library);
+        assert_eq!(checks.song_operations.len(), 1);
+        let update = checks.song_operations[0].tag_update.as_ref().unwrap();
+        assert_eq!((update.rating, update.play_count), (Value::Update(80), Value::Update(12)));
+
+        let fs = MemFs::default();
+        let options =
+            ChangeOptions { tag_only: true, ..ChangeOptions::from(PathBuf::from("/out")) };
+        let mut changes = Changes::generate(&fs, checks, &options);
+        changes.generate_itunes_playlists(&fs, &index, &library, &options);
+        let songs = vec![PathBuf::from("/music/Music/b.mp3"), PathBuf::from("/music/Music/a.mp3")];
+        let path = PathBuf::from("/out/playlists/Favorites.m3u");
+        assert_eq!(changes.playlist_creations, vec![PlaylistCreation { path, songs }]);
+        let dirs: Vec<_> = changes.dir_creations.iter().map(|d| d.path.as_path()).collect();
+        assert_eq!(dirs, [Path::new("/out"), Path::new("/out/playlists")]);
+    }
+}
@@ -0,0 +1,288 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::fs::{valid_os_str, valid_os_str_dots};
+
+/// The layout used if none is specified, e.g. `Artist/Album/1 05 - Artist - Title`.
+pub const DEFAULT_LAYOUT: &str =
+    "{release_artists}/{release}/[{disc} ]{track:02} - {artists} - {title}";
+
+/// Music videos are kept in a separate tree, only the artists, title, year and genre fields have
+/// values.
+pub const DEFAULT_VIDEO_LAYOUT: &str = "Videos/{artists}/{title}";
+
+/// The fields that can be used inside a layout template. `release_group` and `release_title` are
+/// the group of a release, e.g. a box set, and the release name without the group, see
+/// [`RELEASE_GROUP_FIELD`](crate::RELEASE_GROUP_FIELD).
+pub const LAYOUT_FIELDS: [&str; 15] = [
+    "release_artists",
+    "artists",
+    "release",
+    "release_group",
+    "release_title",
+    "title",
+    "track",
+    "total_tracks",
+    "disc",
+    "total_discs",
+    "codec",
+    "bitrate",
+    "sample_rate",
+    "duration",
+    "quality",
+];
+
+/// Wider padding is most likely a typo, which would allocate huge strings when rendering.
+const MAX_WIDTH: usize = 8;
+
+/// Custom fields are used with this prefix, e.g. `{extra.label}`, see
+/// [`MusicIndex::custom_fields`](crate::MusicIndex).
+pub const EXTRA_FIELD_PREFIX: &str = "extra.";
+
+/// A path template relative to the output directory, without the file extension.
+///
+/// Components are separated by `/`, the last one is the file name. `{field}` is replaced by the
+/// value of the field, `{field:02}` pads numbers with zeros to a width of 2. Text inside `[...]`
+/// is omitted if any field inside it has no value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    template: String,
+    components: Vec<Vec<Segment>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Segment {
+    Text(String),
+    Field { name: String, width: usize },
+    Optional(Vec<Segment>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LayoutError {
+    EmptyComponent,
+    UnknownField(String),
+    InvalidWidth(String),
+    Unclosed(char),
+    Unexpected(char),
+}
+
+impl std::fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyComponent => write!(f, "empty path component"),
+            Self::UnknownField(n) => write!(f, "unknown field '{n}'"),
+            Self::InvalidWidth(w) => write!(f, "invalid width '{w}'"),
+            Self::Unclosed(c) => write!(f, "unclosed '{c}'"),
+            Self::Unexpected(c) => write!(f, "unexpected '{c}'"),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+impl Default for Layout {
+    fn default() -> Self {
+        DEFAULT_LAYOUT.parse().unwrap()
+    }
+}
+
+impl std::fmt::Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+impl FromStr for Layout {
+    type Err = LayoutError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let components = template
+            .split('/')
+            .map(|c| match c.is_empty() {
+                true => Err(LayoutError::EmptyComponent),
+                false => parse_segments(c, &LAYOUT_FIELDS),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { template: template.to_owned(), components })
+    }
+}
+
+/// Parses `{field}`, `{field:02}` and `[...]` sections, custom fields are always allowed.
+pub(crate) fn parse_segments(text: &str, fields: &[&str]) -> Result<Vec<Segment>, LayoutError> {
+    let mut stack: Vec<Vec<Segment>> = vec![Vec::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let segments = stack.last_mut().unwrap();
+        match c {
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => field.push(c),
+                        None => return Err(LayoutError::Unclosed('{')),
+                    }
+                }
+                let (name, width) = match field.split_once(':') {
+                    Some((n, w)) => {
+                        let width = (w.parse().ok())
+                            .filter(|w| *w <= MAX_WIDTH)
+                            .ok_or_else(|| LayoutError::InvalidWidth(w.into()))?;
+                        (n, width)
+                    }
+                    None => (field.as_str(), 0),
+                };
+                let extra = name.strip_prefix(EXTRA_FIELD_PREFIX).is_some_and(|n| !n.is_empty());
+                if !extra && !fields.contains(&name) {
+                    return Err(LayoutError::UnknownField(name.to_owned()));
+                }
+                segments.push(Segment::Field { name: name.to_owned(), width });
+            }
+            '[' => stack.push(Vec::new()),
+            ']' => {
+                if stack.len() == 1 {
+                    return Err(LayoutError::Unexpected(']'));
+                }
+                let optional = stack.pop().unwrap();
+                stack.last_mut().unwrap().push(Segment::Optional(optional));
+            }
+            '}' => return Err(LayoutError::Unexpected('}')),
+            _ => match segments.last_mut() {
+                Some(Segment::Text(t)) => t.push(c),
+                _ => segments.push(Segment::Text(c.to_string())),
+            },
+        }
+    }
+
+    if stack.len() > 1 {
+        return Err(LayoutError::Unclosed('['));
+    }
+    Ok(stack.pop().unwrap())
+}
+
+/// Renders the segments into `out`, returns false if any field has no value.
+pub(crate) fn render_segments(
+    segments: &[Segment],
+    field: &impl Fn(&str) -> Option<String>,
+    out: &mut String,
+) -> bool {
+    let mut complete = true;
+    for s in segments {
+        match s {
+            Segment::Text(t) => out.push_str(t),
+            Segment::Field { name, width } => match field(name) {
+                Some(value) => {
+                    if value.chars().all(|c| c.is_ascii_digit()) {
+                        out.push_str(&format!("{value:0>width$}"));
+                    } else {
+                        out.push_str(&value);
+                    }
+                }
+                None => complete = false,
+            },
+            Segment::Optional(segments) => {
+                let mut optional = String::new();
+                if render_segments(segments, field, &mut optional) {
+                    out.push_str(&optional);
+                }
+            }
+        }
+    }
+    complete
+}
+
+impl Layout {
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Renders the path relative to the output directory, the file extension is not included.
+    /// Missing fields outside of optional sections are left empty.
+    pub fn render(&self, field: impl Fn(&str) -> Option<String>) -> PathBuf {
+        let field = |name: &str| field(name).map(|v| valid_os_str(&v));
+        let mut path = PathBuf::new();
+        let last = self.components.len() - 1;
+        for (i, segments) in self.components.iter().enumerate() {
+            let mut component = String::new();
+            render_segments(segments, &field, &mut component);
+            if i == last {
+                path.push(valid_os_str(&component));
+            } else {
+                path.push(valid_os_str_dots(&component));
+            }
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn render(template: &str, field: impl Fn(&str) -> Option<String>) -> PathBuf {
+        template.parse::<Layout>().unwrap().render(field)
+    }
+
+    #[test]
+    fn renders_fields_and_pads_numbers() {
+        let path = render(DEFAULT_LAYOUT, |name| match name {
+            "release_artists" | "artists" => Some("Foo".to_owned()),
+            "release" => Some("Bar".to_owned()),
+            "title" => Some("Baz".to_owned()),
+            "track" => Some("5".to_owned()),
+            _ => None,
+        });
+        assert_eq!(path, Path::new("Foo/Bar/05 - Foo - Baz"));
+
+        // only numbers are padded
+        let path = render("{title:04}", |_| Some("ab".to_owned()));
+        assert_eq!(path, Path::new("ab"));
+    }
+
+    #[test]
+    fn omits_optional_sections_with_missing_fields() {
+        let field = |name: &str| match name {
+            "track" => Some("3".to_owned()),
+            "title" => Some("One".to_owned()),
+            _ => None,
+        };
+        assert_eq!(render("[{disc} ]{track} {title}", field), Path::new("3 One"));
+        assert_eq!(render("[{track}[ of {total_tracks}]] {title}", field), Path::new("3 One"));
+        assert_eq!(render("[{disc}[ {track}]]{title}", field), Path::new("One"));
+        // missing fields outside of optional sections are left empty
+        assert_eq!(render("{release}{title}", field), Path::new("One"));
+    }
+
+    #[test]
+    fn allows_custom_fields() {
+        let path = render("{extra.label}/{title}", |name| Some(name.to_owned()));
+        assert_eq!(path, Path::new("extra.label/title"));
+        assert_eq!(
+            "{extra.}".parse::<Layout>(),
+            Err(LayoutError::UnknownField("extra.".to_owned()))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        let err = |template: &str| template.parse::<Layout>().unwrap_err();
+        assert_eq!(err("{title"), LayoutError::Unclosed('{'));
+        assert_eq!(err("[{title}"), LayoutError::Unclosed('['));
+        assert_eq!(err("{title}]"), LayoutError::Unexpected(']'));
+        assert_eq!(err("title}"), LayoutError::Unexpected('}'));
+        assert_eq!(err("{album}"), LayoutError::UnknownField("album".to_owned()));
+        assert_eq!(err("{track:x}"), LayoutError::InvalidWidth("x".to_owned()));
+        assert_eq!(err("a//{title}"), LayoutError::EmptyComponent);
+    }
+
+    #[test]
+    fn rejects_huge_widths() {
+        assert!("{track:08}".parse::<Layout>().is_ok());
+        assert_eq!(
+            "{track:99999999999}".parse::<Layout>(),
+            Err(LayoutError::InvalidWidth("99999999999".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Indexes a music library, checks it for inconsistent metadata and plans and executes the
+//! changes needed to reorganize it using a [`Layout`].
+//!
+//! The supported public surface is everything re-exported from the crate root. A typical front
+//! end goes through these steps:
+//!
+//! 1. [`MusicIndex::read`] collects songs, images, playlists and unknown files.
+//! 2. [`Checks`] finds inconsistencies and collects tag updates as [`SongOperation`]s.
+//! 3. [`Changes::generate`] plans dir creations, song operations, file operations and playlist
+//!    updates, which own their data and can be serialized or kept after the index is dropped.
+//! 4. [`Changes::execute`] and [`Cleanup::execute`] run the plans against a [`Fs`], reporting to
+//!    a [`Progress`]. [`Changes::simulate`] applies them to a [`VirtualTree`] without any I/O
+//!    instead.
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//!
+//! use music_organizer_core::{
+//!     ChangeOptions, Changes, Checks, ExecOptions, MusicIndex, Progress, RealFs,
+//! };
+//!
+//! struct Quiet;
+//! impl Progress for Quiet {}
+//!
+//! let mut index = MusicIndex::from(PathBuf::from("music"));
+//! index.read(&mut |_| ());
+//!
+//! let checks = Checks::from(&index);
+//! let changes = Changes::generate(&RealFs, checks, &ChangeOptions::from(PathBuf::from("out")));
+//! changes.execute(&RealFs, &ExecOptions::default(), &mut Quiet);
+//! ```
+//!
+//! # Features
+//!
+//! - `mp3`, `mp4` and `flac` (default): the tag backends, files without an enabled backend are
+//!   treated as unknown files.
+//! - `sqlite`: an `IndexDb` to persist the index and a `BeetsDb` to work alongside beets.
+//! - `tokio`: async variants of indexing and execution.
+//! - `replay-gain`: [`Checks::compute_replay_gain`], which decodes songs to measure their
+//!   loudness.
+//! - `scripting`: a rhai [`Script`] to adjust the paths and tag updates of songs.
+//! - `test-util`: a `LibraryBuilder` for synthetic libraries and `assert_golden_plan`, which
+//!   compares the planned changes with a golden file, to test layouts and options. The builder
+//!   also writes tiny mp3, m4a and flac fixtures to the disk, which `run_pipeline` indexes,
+//!   checks, organizes and cleans up. `cargo test -p music-organizer-core --test pipeline` runs
+//!   it as a smoke test of a build.
+
+#[cfg(feature = "tokio")]
+mod asynchronous;
+mod audio;
+mod audiobook;
+mod backup;
+#[cfg(feature = "sqlite")]
+mod beets;
+mod bonus;
+mod cache;
+mod cancel;
+mod changes;
+mod checks;
+mod cleanup;
+mod compilation;
+mod cover;
+mod dedup;
+mod diff;
+mod discs;
+mod edition;
+mod export;
+mod external;
+mod fill;
+mod filter;
+mod fs;
+mod genre;
+mod group;
+mod health;
+mod incomplete;
+mod index;
+mod itunes;
+mod layout;
+#[cfg(feature = "replay-gain")]
+mod loudness;
+mod manifest;
+mod memfs;
+mod meta;
+mod mirror;
+mod mounts;
+mod nfo;
+mod owner;
+mod playlist;
+mod progress;
+mod provenance;
+mod quarantine;
+mod question;
+mod rollback;
+mod romanize;
+#[cfg(feature = "scripting")]
+mod script;
+mod serde_path;
+mod simulation;
+mod snapshot;
+mod split;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod staging;
+mod stats;
+#[cfg(any(test, feature = "test-util"))]
+mod testing;
+mod totals;
+mod transcode;
+mod unknown;
+mod update;
+mod util;
+mod vote;
+
+#[cfg(feature = "tokio")]
+pub use asynchronous::{ExecEvent, OwnedIndexEvent};
+pub use audio::{AudioInfo, Codec};
+pub use audiobook::{Audiobook, Chapter, DEFAULT_AUDIOBOOK_LAYOUT};
+pub use backup::{prune_backups, Backup};
+#[cfg(feature = "sqlite")]
+pub use beets::{BeetsDb, BeetsItem};
+pub use bonus::DEFAULT_BONUS_DIR;
+pub use cache::{CacheEntry, IndexCache};
+pub use cancel::CancelToken;
+pub use changes::{
+    ArtistChanges, ChangeOptions, Changes, Collaborations, ExtraPolicies, ExtraPolicy, ImagePolicy,
+    OperationId, ReleaseChanges,
+};
+pub use checks::{CheckCount, CheckStats, Checks, SimilarityScope};
+pub use cleanup::{Cleanup, DEFAULT_JUNK_PATTERNS};
+pub use compilation::{Compilation, VARIOUS_ARTISTS};
+pub use cover::CoverName;
+pub use dedup::{DedupAction, DuplicateGroup, Duplicates};
+pub use diff::{LibraryDiff, ReleaseDiff};
+pub use edition::{Edition, ReleaseSuffix};
+pub use external::ExternalUpdate;
+pub use filter::{Filter, FilterError, FILTER_KEYS};
+pub use fs::{
+    is_music_video_extension, is_song_extension, ChangedOnDisk, DeleteType, DirCreation,
+    DirDeletion, ExecOptions, ExtraKind, FileDeletion, FileOpType, FileOperation, Fs, RealFs,
+    RetryPolicy, SongOperation,
+};
+pub use genre::{normalize_genre, ID3V1_GENRES};
+pub use group::RELEASE_GROUP_FIELD;
+pub use health::{HealthOffender, HealthReport, HealthScore};
+pub use incomplete::{IncompleteRelease, IncompleteReport};
+pub use index::{
+    IndexEvent, MusicIndex, DEFAULT_FOREIGN_MARKERS, SKIP_MARKER_FIELD, SKIP_MARKER_VALUE,
+};
+pub use itunes::{ItunesError, ItunesLibrary, ItunesPlaylist, ItunesTrack};
+pub use layout::{
+    Layout, LayoutError, DEFAULT_LAYOUT, DEFAULT_VIDEO_LAYOUT, EXTRA_FIELD_PREFIX, LAYOUT_FIELDS,
+};
+#[cfg(feature = "replay-gain")]
+pub use loudness::{LoudnessError, REPLAY_GAIN_FIELDS};
+pub use manifest::{PathManifest, PathMove};
+pub use memfs::MemFs;
+pub use meta::{
+    name_key, ArtistDistinction, ArtistSeparators, Metadata, MetadataError, Mode, Release,
+    ReleaseArtists, Song, Video, ARTIST_ID_FIELDS,
+};
+pub use mirror::{Mirror, NamingProfile, SelectionOrder, SizeLimit};
+pub use mounts::Mount;
+pub use nfo::{
+    NfoTemplate, ReleaseNfo, DEFAULT_NFO_TEMPLATE, DEFAULT_NFO_TRACK_TEMPLATE, NFO_FIELDS,
+};
+pub use owner::Owner;
+pub use playlist::{EntryUpdate, PlaylistCreation, PlaylistUpdate};
+pub use progress::{Item, Phase, PhaseTotals, Progress};
+pub use provenance::{Provenance, PROVENANCE_FIELD};
+pub use quarantine::{Quarantine, QuarantineReason, QUARANTINE_DIR_NAME};
+pub use question::{Question, Resolution, Resolver};
+pub use rollback::RolledBack;
+pub use romanize::{romanize, NonLatinArtists};
+#[cfg(feature = "scripting")]
+pub use script::{Script, ScriptError};
+pub use simulation::{Collision, Simulation, VirtualTree};
+pub use snapshot::PlanSnapshot;
+pub use split::SplitRelease;
+#[cfg(feature = "sqlite")]
+pub use sqlite::IndexDb;
+pub use staging::STAGING_DIR_NAME;
+pub use stats::{DecadeStats, FormatStats, GenreStats, LibraryStats, ReleaseStats};
+#[cfg(any(test, feature = "test-util"))]
+pub use testing::{
+    assert_golden_plan, render_plan, run_pipeline, LibraryBuilder, PipelineRun, ScriptedAnswers,
+    FIXTURE_FLAC, FIXTURE_M4A, FIXTURE_MP3, UPDATE_GOLDEN_ENV,
+};
+pub use totals::{InconsistentTotals, TotalKind, TotalsPolicy};
+pub use transcode::{Transcode, TranscodeCodec};
+pub use unknown::MissingTags;
+pub use update::{TagUpdate, Value};
+pub use util::*;
+pub use vote::ArtistVote;
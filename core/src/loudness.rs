@@ -0,0 +1,283 @@
+use std::fs::File;
+use std::path::Path;
+
+use ebur128::{EbuR128, Mode as MeterMode};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tracing::{debug, info_span};
+
+use crate::{util, Checks, Song, Value};
+
+/// The custom fields [`Checks::compute_replay_gain`] writes. They have to be part of the
+/// [`MusicIndex::custom_fields`](crate::MusicIndex) to tell which songs already have them.
+pub const REPLAY_GAIN_FIELDS: [&str; 4] = [
+    "REPLAYGAIN_TRACK_GAIN",
+    "REPLAYGAIN_TRACK_PEAK",
+    "REPLAYGAIN_ALBUM_GAIN",
+    "REPLAYGAIN_ALBUM_PEAK",
+];
+
+/// The loudness ReplayGain 2.0 normalizes to, in LUFS.
+const REFERENCE_LOUDNESS: f64 = -18.0;
+
+#[derive(Debug)]
+pub enum LoudnessError {
+    Io(std::io::Error),
+    Decode(DecodeError),
+    /// The file has no audio track, or no samples could be decoded.
+    NoAudio,
+    Meter(ebur128::Error),
+}
+
+impl std::fmt::Display for LoudnessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Decode(e) => write!(f, "decoding failed: {e}"),
+            Self::NoAudio => write!(f, "no audio"),
+            Self::Meter(e) => write!(f, "measuring failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for LoudnessError {}
+
+impl From<std::io::Error> for LoudnessError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<DecodeError> for LoudnessError {
+    fn from(e: DecodeError) -> Self {
+        Self::Decode(e)
+    }
+}
+
+impl From<ebur128::Error> for LoudnessError {
+    fn from(e: ebur128::Error) -> Self {
+        Self::Meter(e)
+    }
+}
+
+/// Decodes the whole file and measures its integrated loudness and sample peak.
+fn measure(path: &Path) -> Result<EbuR128, LoudnessError> {
+    let source = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        source,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let mut format = probed.format;
+    let track = (format.tracks().iter())
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(LoudnessError::NoAudio)?;
+    let track_id = track.id;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut meter: Option<EbuR128> = None;
+    let mut samples: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(p) => p,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(d) => d,
+            // a corrupt frame only affects this packet
+            Err(DecodeError::DecodeError(e)) => {
+                debug!(path = %path.display(), error = e, "skipping corrupt frame");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let spec = *decoded.spec();
+        let meter = match &mut meter {
+            Some(m) => m,
+            None => {
+                let channels = spec.channels.count() as u32;
+                let mode = MeterMode::I | MeterMode::SAMPLE_PEAK;
+                meter.insert(EbuR128::new(channels, spec.rate, mode)?)
+            }
+        };
+        let frames = decoded.capacity() as u64;
+        let buf = match &mut samples {
+            Some(s) if s.capacity() >= frames as usize * spec.channels.count() => s,
+            _ => samples.insert(SampleBuffer::new(frames, spec)),
+        };
+        buf.copy_interleaved_ref(decoded);
+        meter.add_frames_f32(buf.samples())?;
+    }
+
+    meter.ok_or(LoudnessError::NoAudio)
+}
+
+/// The maximum sample peak of all channels.
+fn peak(meter: &EbuR128) -> Result<f64, ebur128::Error> {
+    (0..meter.channels()).try_fold(0.0, |max: f64, c| Ok(max.max(meter.sample_peak(c)?)))
+}
+
+/// The integrated loudness and peak of a track, or of multiple tracks as an album. `None` if
+/// they are silent.
+fn loudness_and_peak(meters: &[&EbuR128]) -> Option<(f64, f64)> {
+    let loudness = EbuR128::loudness_global_multiple(meters.iter().copied()).ok()?;
+    let peak = (meters.iter()).try_fold(0.0, |max: f64, m| peak(m).map(|p| max.max(p))).ok()?;
+    loudness.is_finite().then_some((loudness, peak))
+}
+
+fn format_gain(loudness: f64) -> String {
+    format!("{:.2} dB", REFERENCE_LOUDNESS - loudness)
+}
+
+fn format_peak(peak: f64) -> String {
+    format!("{peak:.6}")
+}
+
+impl Checks<'_> {
+    /// Measures the loudness of the songs of each release with a song that lacks one of the
+    /// [`REPLAY_GAIN_FIELDS`], and sets the missing fields. Fields that are present are kept.
+    /// The album gain is only set if all songs of the release could be measured. `f` is called
+    /// after each measured song, with the error if it couldn't be measured.
+    pub fn compute_replay_gain(&mut self, f: &mut impl FnMut(&Song, Option<&LoudnessError>)) {
+        let _span = info_span!("check", check = "replay_gain").entered();
+        let lacks = |s: &Song, fields: &[&str]| fields.iter().any(|f| !s.extra.contains_key(*f));
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+        for artist in self.artists.iter() {
+            for release in artist.releases.iter() {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                if !release.songs.iter().any(|s| lacks(s, &REPLAY_GAIN_FIELDS)) {
+                    continue;
+                }
+
+                let mut meters = Vec::with_capacity(release.songs.len());
+                for chunk in release.songs.chunks(threads) {
+                    let measured: Vec<_> = std::thread::scope(|scope| {
+                        let handles: Vec<_> =
+                            (chunk.iter()).map(|s| scope.spawn(|| measure(&s.path))).collect();
+                        handles.into_iter().map(|h| h.join().unwrap()).collect()
+                    });
+                    for (s, m) in chunk.iter().zip(measured) {
+                        f(s, m.as_ref().err());
+                        meters.push(m);
+                    }
+                }
+
+                let album = match meters.iter().map(Result::as_ref).collect::<Result<Vec<_>, _>>() {
+                    Ok(meters) => loudness_and_peak(&meters),
+                    Err(_) => None,
+                };
+
+                for (s, m) in release.songs.iter().zip(meters) {
+                    if !lacks(s, &REPLAY_GAIN_FIELDS) {
+                        continue;
+                    }
+                    self.stats.replay_gain.issues += 1;
+
+                    let track = m.ok().and_then(|m| loudness_and_peak(&[&m]));
+                    let mut fields = Vec::new();
+                    if let Some((loudness, peak)) = track {
+                        fields.push((REPLAY_GAIN_FIELDS[0], format_gain(loudness)));
+                        fields.push((REPLAY_GAIN_FIELDS[1], format_peak(peak)));
+                    }
+                    if let Some((loudness, peak)) = album {
+                        fields.push((REPLAY_GAIN_FIELDS[2], format_gain(loudness)));
+                        fields.push((REPLAY_GAIN_FIELDS[3], format_peak(peak)));
+                    }
+                    fields.retain(|(name, _)| !s.extra.contains_key(*name));
+                    if fields.is_empty() {
+                        continue;
+                    }
+
+                    debug!(path = %s.path.display(), ?fields, "computed replay gain");
+                    util::update_tag(
+                        &mut self.song_operations,
+                        &self.retag_excluded,
+                        s,
+                        "replay gain",
+                        |t| {
+                            for (name, value) in fields {
+                                t.extra.insert(name.to_owned(), Value::Update(value));
+                            }
+                        },
+                    );
+                    self.stats.replay_gain.updates += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::MusicIndex;
+
+    fn sine(amplitude: f32) -> EbuR128 {
+        let mode = MeterMode::I | MeterMode::SAMPLE_PEAK;
+        let mut meter = EbuR128::new(2, 44100, mode).unwrap();
+        let frames: Vec<f32> = (0..44100 * 2)
+            .flat_map(|i| {
+                let s =
+                    amplitude * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 44100.0).sin();
+                [s, s]
+            })
+            .collect();
+        meter.add_frames_f32(&frames).unwrap();
+        meter
+    }
+
+    #[test]
+    fn measures_track_and_album_gain() {
+        let (quiet, loud) = (sine(0.1), sine(0.5));
+        let (loudness, peak) = loudness_and_peak(&[&quiet]).unwrap();
+        assert!((REFERENCE_LOUDNESS - loudness - 2.0).abs() < 0.05);
+        assert!((peak - 0.1).abs() < 0.001);
+
+        // the quiet track is below the relative gate of the album
+        let (album, album_peak) = loudness_and_peak(&[&quiet, &loud]).unwrap();
+        assert_eq!(format_gain(album), format_gain(loudness_and_peak(&[&loud]).unwrap().0));
+        assert!((album_peak - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn skips_album_gain_of_releases_with_unreadable_songs() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![crate::Song {
+            path: PathBuf::from("/music/missing.flac"),
+            release_artists: Arc::from(["Foo".to_owned()]),
+            release: Arc::from("Bar"),
+            ..Default::default()
+        }];
+        let mut checks = Checks::from(&index);
+
+        let mut errors = 0;
+        checks.compute_replay_gain(&mut |_, e| errors += e.is_some() as usize);
+
+        assert_eq!(errors, 1);
+        assert_eq!(checks.stats.replay_gain.issues, 1);
+        assert_eq!(checks.stats.replay_gain.updates, 0);
+        assert!(checks.song_operations.is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::export::write_csv_row;
+use crate::{Changes, FileOpType};
+
+const CSV_HEADER: [&str; 2] = ["old_path", "new_path"];
+
+/// Where every moved or renamed song and file of a run ended up, so external databases can
+/// update their references.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PathManifest {
+    pub moves: Vec<PathMove>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PathMove {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub old_path: PathBuf,
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub new_path: PathBuf,
+}
+
+impl PathManifest {
+    pub fn generate(changes: &Changes) -> Self {
+        let songs = (changes.song_operations.iter())
+            .filter_map(|o| Some((o.song.path.clone(), o.new_path.clone()?)));
+        // hard links don't move anything
+        let files = (changes.file_operations.iter())
+            .filter(|o| o.op_type != Some(FileOpType::HardLink))
+            .map(|o| (o.old_path.clone(), o.new_path.clone()));
+        let moves = songs
+            .chain(files)
+            .map(|(old_path, new_path)| PathMove { old_path, new_path })
+            .collect();
+        Self { moves }
+    }
+
+    pub fn export_json(&self, writer: impl Write) -> std::io::Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    pub fn export_csv(&self, mut writer: impl Write) -> std::io::Result<()> {
+        write_csv_row(&mut writer, CSV_HEADER.iter().map(|h| h.to_string()))?;
+
+        for m in self.moves.iter() {
+            let row = [m.old_path.to_string_lossy(), m.new_path.to_string_lossy()];
+            write_csv_row(&mut writer, row.into_iter().map(|p| p.into_owned()))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileOperation, Song, SongOperation};
+
+    #[test]
+    fn maps_moved_songs_and_files() {
+        let song = |path: &str| Song { path: PathBuf::from(path), ..Default::default() };
+        let changes = Changes {
+            song_operations: vec![
+                SongOperation {
+                    new_path: Some(PathBuf::from("/out/a, b.mp3")),
+                    ..SongOperation::new(song("/music/a.mp3"))
+                },
+                SongOperation::new(song("/music/c.mp3")),
+            ],
+            file_operations: vec![FileOperation {
+                old_path: PathBuf::from("/music/cover.jpg"),
+                new_path: PathBuf::from("/out/cover.jpg"),
+                op_type: None,
+                replace: false,
+            }],
+            ..Default::default()
+        };
+
+        let manifest = PathManifest::generate(&changes);
+        assert_eq!(manifest.moves.len(), 2);
+
+        let mut csv = Vec::new();
+        manifest.export_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().nth(1), Some("/music/a.mp3,\"/out/a, b.mp3\""));
+        assert_eq!(csv.lines().nth(2), Some("/music/cover.jpg,/out/cover.jpg"));
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::{Fs, Mode, Owner};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Entry {
+    Dir {
+        mode: Mode,
+        modified: SystemTime,
+    },
+    /// Hard links share the `id` of their file, like an inode.
+    File {
+        id: u64,
+        data: Vec<u8>,
+        mode: Mode,
+        modified: SystemTime,
+    },
+}
+
+fn next_file_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+const DEFAULT_DIR_MODE: Mode = Mode(0o755);
+const DEFAULT_FILE_MODE: Mode = Mode(0o644);
+
+/// An in-memory [`Fs`] for testing planning and execution without touching the disk. The root
+/// directory always exists. Trashed entries are removed and remembered, see [`MemFs::trashed`].
+/// Entries have no owner until one is set.
+#[derive(Debug, Default)]
+pub struct MemFs {
+    entries: Mutex<BTreeMap<PathBuf, Entry>>,
+    owners: Mutex<BTreeMap<PathBuf, Owner>>,
+    trashed: Mutex<Vec<PathBuf>>,
+}
+
+fn not_found(path: &Path) -> Error {
+    Error::new(ErrorKind::NotFound, format!("{} not found", path.display()))
+}
+
+fn is_root(path: &Path) -> bool {
+    path.parent().is_none()
+}
+
+impl MemFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file and all missing ancestor directories.
+    pub fn add_file(&self, path: impl Into<PathBuf>, data: impl Into<Vec<u8>>) {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            self.add_dir(parent);
+        }
+        let entry = Entry::File {
+            id: next_file_id(),
+            data: data.into(),
+            mode: DEFAULT_FILE_MODE,
+            modified: SystemTime::UNIX_EPOCH,
+        };
+        self.entries.lock().unwrap().insert(path, entry);
+    }
+
+    /// Adds a directory and all missing ancestors.
+    pub fn add_dir(&self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        let mut entries = self.entries.lock().unwrap();
+        for p in path.ancestors().filter(|p| !is_root(p)) {
+            let entry = Entry::Dir { mode: DEFAULT_DIR_MODE, modified: SystemTime::UNIX_EPOCH };
+            entries.entry(p.to_owned()).or_insert(entry);
+        }
+    }
+
+    /// The contents of a file.
+    pub fn file(&self, path: &Path) -> Option<Vec<u8>> {
+        match self.entries.lock().unwrap().get(path) {
+            Some(Entry::File { data, .. }) => Some(data.clone()),
+            _ => None,
+        }
+    }
+
+    /// Files are added with a modification time of [`SystemTime::UNIX_EPOCH`], written files
+    /// get the current time and copies keep the time of the original.
+    pub fn set_modified(&self, path: &Path, time: SystemTime) {
+        if let Some(Entry::File { modified, .. }) = self.entries.lock().unwrap().get_mut(path) {
+            *modified = time;
+        }
+    }
+
+    pub fn mode(&self, path: &Path) -> Option<Mode> {
+        match self.entries.lock().unwrap().get(path) {
+            Some(Entry::File { mode, .. } | Entry::Dir { mode, .. }) => Some(*mode),
+            None => None,
+        }
+    }
+
+    pub fn owner(&self, path: &Path) -> Option<Owner> {
+        self.owners.lock().unwrap().get(path).copied()
+    }
+
+    /// All paths that were sent to the trash, in order.
+    pub fn trashed(&self) -> Vec<PathBuf> {
+        self.trashed.lock().unwrap().clone()
+    }
+
+    /// All files and directories sorted by path.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.entries.lock().unwrap().keys().cloned().collect()
+    }
+
+    fn check_parent(entries: &BTreeMap<PathBuf, Entry>, path: &Path) -> std::io::Result<()> {
+        match path.parent() {
+            Some(p) if !is_root(p) && !matches!(entries.get(p), Some(Entry::Dir { .. })) => {
+                Err(not_found(p))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Updates the modification time of the parent dir, after an entry was added or removed.
+    fn touch_parent(entries: &mut BTreeMap<PathBuf, Entry>, path: &Path) {
+        let parent = path.parent().and_then(|p| entries.get_mut(p));
+        if let Some(Entry::Dir { modified, .. }) = parent {
+            *modified = SystemTime::now();
+        }
+    }
+}
+
+impl Fs for MemFs {
+    fn exists(&self, path: &Path) -> bool {
+        is_root(path) || self.entries.lock().unwrap().contains_key(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        matches!(self.entries.lock().unwrap().get(path), Some(Entry::File { .. }))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        is_root(path) || matches!(self.entries.lock().unwrap().get(path), Some(Entry::Dir { .. }))
+    }
+
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(not_found(path));
+        }
+        let entries = self.entries.lock().unwrap();
+        Ok(entries.keys().filter(|p| p.parent() == Some(path)).cloned().collect())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        if path.ancestors().any(|p| self.is_file(p)) {
+            return Err(Error::new(ErrorKind::AlreadyExists, "a file is in the way"));
+        }
+        self.add_dir(path);
+        Ok(())
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        self.file(path).ok_or_else(|| not_found(path))
+    }
+
+    fn open(&self, path: &Path) -> std::io::Result<Box<dyn std::io::Read + '_>> {
+        Ok(Box::new(std::io::Cursor::new(self.read(path)?)))
+    }
+
+    fn file_info(&self, path: &Path) -> std::io::Result<(u64, SystemTime)> {
+        match self.entries.lock().unwrap().get(path) {
+            Some(Entry::File { data, modified, .. }) => Ok((data.len() as u64, *modified)),
+            _ => Err(not_found(path)),
+        }
+    }
+
+    fn dir_modified(&self, path: &Path) -> std::io::Result<SystemTime> {
+        match self.entries.lock().unwrap().get(path) {
+            Some(Entry::Dir { modified, .. }) => Ok(*modified),
+            _ => Err(not_found(path)),
+        }
+    }
+
+    fn set_dir_modified(&self, path: &Path, time: SystemTime) -> std::io::Result<()> {
+        match self.entries.lock().unwrap().get_mut(path) {
+            Some(Entry::Dir { modified, .. }) => {
+                *modified = time;
+                Ok(())
+            }
+            _ => Err(not_found(path)),
+        }
+    }
+
+    fn write(&self, path: &Path, new: &[u8]) -> std::io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        Self::check_parent(&entries, path)?;
+        match entries.get_mut(path) {
+            Some(Entry::File { data, modified, .. }) => {
+                *data = new.to_vec();
+                *modified = SystemTime::now();
+            }
+            Some(Entry::Dir { .. }) => {
+                return Err(Error::new(ErrorKind::IsADirectory, "is a directory"));
+            }
+            None => {
+                let entry = Entry::File {
+                    id: next_file_id(),
+                    data: new.to_vec(),
+                    mode: DEFAULT_FILE_MODE,
+                    modified: SystemTime::now(),
+                };
+                entries.insert(path.to_owned(), entry);
+                Self::touch_parent(&mut entries, path);
+            }
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        Self::check_parent(&entries, to)?;
+        let moved: Vec<PathBuf> = entries.keys().filter(|p| p.starts_with(from)).cloned().collect();
+        if moved.is_empty() {
+            return Err(not_found(from));
+        }
+        for p in moved {
+            let entry = entries.remove(&p).unwrap();
+            let new = to.join(p.strip_prefix(from).unwrap());
+            entries.insert(new.components().collect(), entry);
+        }
+        Self::touch_parent(&mut entries, from);
+        Self::touch_parent(&mut entries, to);
+        Ok(())
+    }
+
+    fn copy(
+        &self,
+        from: &Path,
+        to: &Path,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> std::io::Result<u64> {
+        let mut entries = self.entries.lock().unwrap();
+        Self::check_parent(&entries, to)?;
+        let Some(entry @ Entry::File { data, .. }) = entries.get(from) else {
+            return Err(not_found(from));
+        };
+        let len = data.len() as u64;
+        let mut entry = entry.clone();
+        if let Entry::File { id, .. } = &mut entry {
+            *id = next_file_id();
+        }
+        entries.insert(to.to_owned(), entry);
+        Self::touch_parent(&mut entries, to);
+        progress(len, len);
+        Ok(len)
+    }
+
+    fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(path) {
+            Some(Entry::File { .. }) => {
+                entries.remove(path);
+                Self::touch_parent(&mut entries, path);
+                Ok(())
+            }
+            Some(Entry::Dir { .. }) => Err(Error::new(ErrorKind::IsADirectory, "is a directory")),
+            None => Err(not_found(path)),
+        }
+    }
+
+    /// The link shares the id of the original, but not later writes of its contents.
+    fn hard_link(&self, original: &Path, link: &Path) -> std::io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.contains_key(link) {
+            return Err(Error::new(ErrorKind::AlreadyExists, "already exists"));
+        }
+        Self::check_parent(&entries, link)?;
+        let Some(entry @ Entry::File { .. }) = entries.get(original) else {
+            return Err(not_found(original));
+        };
+        let entry = entry.clone();
+        entries.insert(link.to_owned(), entry);
+        Self::touch_parent(&mut entries, link);
+        Ok(())
+    }
+
+    fn same_file(&self, a: &Path, b: &Path) -> bool {
+        let entries = self.entries.lock().unwrap();
+        match (entries.get(a), entries.get(b)) {
+            (Some(Entry::File { id: a, .. }), Some(Entry::File { id: b, .. })) => a == b,
+            (Some(_), Some(_)) => a == b,
+            _ => a == b && is_root(a),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> std::io::Result<()> {
+        if !self.read_dir(path)?.is_empty() {
+            return Err(Error::new(ErrorKind::DirectoryNotEmpty, "directory not empty"));
+        }
+        self.entries.lock().unwrap().remove(path);
+        Ok(())
+    }
+
+    fn trash(&self, path: &Path) -> std::io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        let trashed: Vec<PathBuf> =
+            entries.keys().filter(|p| p.starts_with(path)).cloned().collect();
+        if trashed.is_empty() {
+            return Err(not_found(path));
+        }
+        for p in trashed {
+            entries.remove(&p);
+        }
+        self.trashed.lock().unwrap().push(path.to_owned());
+        Ok(())
+    }
+
+    fn set_permissions(&self, path: &Path, new: Mode) -> std::io::Result<()> {
+        match self.entries.lock().unwrap().get_mut(path) {
+            Some(Entry::File { mode, .. } | Entry::Dir { mode, .. }) => {
+                *mode = new;
+                Ok(())
+            }
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn set_owner(&self, path: &Path, new: Owner) -> std::io::Result<()> {
+        if !self.exists(path) {
+            return Err(not_found(path));
+        }
+        let mut owners = self.owners.lock().unwrap();
+        let owner = owners.entry(path.to_owned()).or_default();
+        owner.uid = new.uid.or(owner.uid);
+        owner.gid = new.gid.or(owner.gid);
+        Ok(())
+    }
+
+    fn copy_ownership(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        if !self.exists(from) {
+            return Err(not_found(from));
+        }
+        if !self.exists(to) {
+            return Err(not_found(to));
+        }
+        let mut owners = self.owners.lock().unwrap();
+        match owners.get(from).copied() {
+            Some(owner) => owners.insert(to.to_owned(), owner),
+            None => owners.remove(to),
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,663 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{File, Permissions};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+#[cfg(feature = "flac")]
+use std::time::Duration;
+
+#[cfg(feature = "mp3")]
+use id3::TagLike;
+use serde::{Deserialize, Serialize};
+
+#[cfg(any(feature = "mp3", feature = "mp4", feature = "flac"))]
+use crate::audio;
+use crate::audio::AudioInfo;
+#[cfg(feature = "flac")]
+use crate::audio::Codec;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReleaseArtists<'a> {
+    pub names: Arc<[String]>,
+    /// The [`name_key`] of each name, release artists with the same keys are probably the same.
+    pub key: Vec<String>,
+    pub releases: Vec<Release<'a>>,
+}
+
+/// Latin letters with diacritics and the letter they are compared as.
+const DIACRITICS: [(char, &str); 19] = [
+    ('a', "àáâãäåāăą"),
+    ('c', "çćĉċč"),
+    ('d', "ďđ"),
+    ('e', "èéêëēĕėęě"),
+    ('g', "ĝğġģ"),
+    ('h', "ĥħ"),
+    ('i', "ìíîïĩīĭįı"),
+    ('j', "ĵ"),
+    ('k', "ķ"),
+    ('l', "ĺļľŀł"),
+    ('n', "ñńņň"),
+    ('o', "òóôõöøōŏő"),
+    ('r', "ŕŗř"),
+    ('s', "śŝşšș"),
+    ('t', "ţťŧț"),
+    ('u', "ùúûüũūŭůűų"),
+    ('w', "ŵ"),
+    ('y', "ýÿŷ"),
+    ('z', "źżž"),
+];
+
+/// Articles that are ignored at the start of names, e.g. `The Beatles` and `Beatles`.
+const ARTICLES: [&str; 3] = ["the ", "a ", "an "];
+
+/// The name lowercased, without diacritics and without a leading article, to group names that
+/// are probably spelled differently instead of comparing each pair.
+pub fn name_key(name: &str) -> String {
+    let key: String = (name.trim().chars().flat_map(char::to_lowercase))
+        .map(|c| DIACRITICS.iter().find(|(_, d)| d.contains(c)).map_or(c, |(base, _)| *base))
+        .collect();
+    match ARTICLES.iter().find_map(|a| key.strip_prefix(a)) {
+        Some(rest) if !rest.is_empty() => rest.to_owned(),
+        _ => key,
+    }
+}
+
+/// Custom fields containing the MusicBrainz ids of the release artists, as written by Picard
+/// to `TXXX` frames and freeform atoms and to vorbis comments.
+pub const ARTIST_ID_FIELDS: [&str; 2] =
+    ["MusicBrainz Album Artist Id", "MUSICBRAINZ_ALBUMARTISTID"];
+
+/// Why two release artists with similar names are probably different artists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArtistDistinction {
+    /// Their MusicBrainz artist ids differ.
+    Ids,
+    /// Their songs have no genre in common.
+    Genres,
+    /// Their releases are from years that don't overlap.
+    Years,
+}
+
+impl ReleaseArtists<'_> {
+    fn songs(&self) -> impl Iterator<Item = &Song> {
+        self.releases.iter().flat_map(|r| r.songs.iter().copied())
+    }
+
+    /// The MusicBrainz artist ids of all songs, see [`ARTIST_ID_FIELDS`].
+    pub fn artist_ids(&self) -> HashSet<&str> {
+        let fields = ARTIST_ID_FIELDS.map(str::to_uppercase);
+        (self.songs())
+            .flat_map(|s| fields.iter().filter_map(|f| s.extra.get(f)))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Checks whether these are probably not the same artists as `other`. Ids are only
+    /// compared if both have them, genres and years only if both have any.
+    pub fn distinction(&self, other: &Self) -> Option<ArtistDistinction> {
+        let (ids, other_ids) = (self.artist_ids(), other.artist_ids());
+        if !ids.is_empty() && !other_ids.is_empty() && ids.is_disjoint(&other_ids) {
+            return Some(ArtistDistinction::Ids);
+        }
+
+        let genres = |a: &Self| -> HashSet<String> {
+            a.songs().filter_map(|s| s.genre.as_deref()).map(str::to_lowercase).collect()
+        };
+        let (genres, other_genres) = (genres(self), genres(other));
+        if !genres.is_empty() && !other_genres.is_empty() && genres.is_disjoint(&other_genres) {
+            return Some(ArtistDistinction::Genres);
+        }
+
+        let years = |a: &Self| {
+            let years = a.songs().filter_map(|s| s.year);
+            years.fold(None, |r, y| match r {
+                Some((min, max)) => Some((y.min(min), y.max(max))),
+                None => Some((y, y)),
+            })
+        };
+        if let (Some((min, max)), Some((other_min, other_max))) = (years(self), years(other)) {
+            if max < other_min || other_max < min {
+                return Some(ArtistDistinction::Years);
+            }
+        }
+
+        None
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Release<'a> {
+    pub name: Arc<str>,
+    pub songs: Vec<&'a Song>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Song {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub path: PathBuf,
+    pub mode: Option<Mode>,
+    pub track_number: Option<u16>,
+    pub total_tracks: Option<u16>,
+    pub disc_number: Option<u16>,
+    pub total_discs: Option<u16>,
+    /// Shared between all songs with the same release artists, see [`MusicIndex`](crate::MusicIndex).
+    pub release_artists: Arc<[String]>,
+    /// Shared between all songs with the same artists.
+    pub artists: Arc<[String]>,
+    /// Shared between all songs with the same release name.
+    pub release: Arc<str>,
+    pub title: String,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    pub has_artwork: bool,
+    pub audio: AudioInfo,
+    /// The custom fields declared in [`MusicIndex::custom_fields`](crate::MusicIndex), keyed by
+    /// their uppercase name.
+    #[serde(default)]
+    pub extra: BTreeMap<String, String>,
+}
+
+/// A tagged music video, which is organized using the
+/// [`ChangeOptions::video_layout`](crate::ChangeOptions::video_layout).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Video {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub path: PathBuf,
+    pub artists: Vec<String>,
+    pub title: String,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+}
+
+/// How multiple artists are stored inside of a single tag value and joined in names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArtistSeparators {
+    /// Artist values are additionally split on these when indexing, e.g. `;`, `/` or ` feat. `.
+    /// The null character separating id3v2.4 values is always split on.
+    pub split: Vec<String>,
+    /// Joins multiple artists when writing mp3 tags, defaults to the null character which writes
+    /// them as separate id3v2.4 values. Mp4 and flac tags always store separate values.
+    pub tag_join: String,
+    /// Joins multiple artists in directory and file names.
+    pub name_join: String,
+}
+
+impl Default for ArtistSeparators {
+    fn default() -> Self {
+        Self { split: Vec::new(), tag_join: "\u{0}".into(), name_join: ", ".into() }
+    }
+}
+
+impl ArtistSeparators {
+    /// Splits each artist on all separators, whitespace around the parts is trimmed and empty
+    /// parts are dropped.
+    pub fn split_artists(&self, artists: Vec<String>) -> Vec<String> {
+        let mut artists = artists;
+        for sep in self.split.iter().filter(|s| !s.is_empty()) {
+            artists = artists
+                .iter()
+                .flat_map(|a| a.split(sep.as_str()))
+                .map(|a| a.trim())
+                .filter(|a| !a.is_empty())
+                .map(str::to_owned)
+                .collect();
+        }
+        artists
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub mode: Option<Mode>,
+    pub track_number: Option<u16>,
+    pub total_tracks: Option<u16>,
+    pub disc_number: Option<u16>,
+    pub total_discs: Option<u16>,
+    pub artists: Vec<String>,
+    pub release_artists: Vec<String>,
+    pub release: Option<String>,
+    pub title: Option<String>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    pub has_artwork: bool,
+    pub audio: AudioInfo,
+    /// All `TXXX` frames, vorbis comments or freeform atoms, keyed by their uppercase name. Only
+    /// the declared custom fields are kept when creating songs, see
+    /// [`MusicIndex::custom_fields`](crate::MusicIndex).
+    #[serde(default)]
+    pub extra: BTreeMap<String, String>,
+}
+
+/// Why the tags of a file couldn't be read, the file is indexed without tags.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MetadataError {
+    Open(String),
+    InvalidTag(String),
+    /// A tag parser panicked on a malformed file.
+    Panicked,
+}
+
+impl std::fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Open(e) => write!(f, "can't open file: {e}"),
+            Self::InvalidTag(e) => write!(f, "invalid tag: {e}"),
+            Self::Panicked => write!(f, "tag parser panicked"),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {}
+
+#[cfg(any(feature = "mp3", feature = "mp4", feature = "flac"))]
+fn invalid_tag(e: impl std::fmt::Display) -> MetadataError {
+    MetadataError::InvalidTag(e.to_string())
+}
+
+impl Metadata {
+    /// Reads the tags of a song or video, the metadata is empty if they can't be read.
+    pub fn read_from(path: &Path) -> Self {
+        Self::try_read_from(path).unwrap_or_default()
+    }
+
+    /// Reads the tags of a song or video. Files without a tag and formats without an enabled
+    /// backend have empty metadata, files that can't be opened or contain a malformed tag are
+    /// errors. Panics of the tag parsers are caught, so malformed files can't abort indexing.
+    #[cfg_attr(
+        not(any(feature = "mp3", feature = "mp4", feature = "flac")),
+        allow(unused_mut, unused_variables)
+    )]
+    pub fn try_read_from(path: &Path) -> Result<Self, MetadataError> {
+        let mut file = File::open(path).map_err(|e| MetadataError::Open(e.to_string()))?;
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        let read = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            match ext.to_ascii_lowercase().as_str() {
+                #[cfg(feature = "mp3")]
+                "mp3" => Self::read_mp3(&mut file),
+                #[cfg(feature = "mp4")]
+                "m4a" | "m4b" | "m4v" | "mp4" => Self::read_mp4(&mut file),
+                #[cfg(feature = "flac")]
+                "flac" => Self::read_flac(&mut file),
+                _ => Ok(None),
+            }
+        }));
+        match read {
+            Ok(meta) => Ok(meta?.unwrap_or_default()),
+            Err(_) => Err(MetadataError::Panicked),
+        }
+    }
+
+    #[cfg(feature = "mp3")]
+    fn read_mp3(file: &mut File) -> Result<Option<Self>, MetadataError> {
+        let tag = id3::no_tag_ok(id3::Tag::read_from2(&*file)).map_err(invalid_tag)?;
+        let Some(tag) = tag else { return Ok(None) };
+        let number_pair = |id| {
+            let text = tag.get(id).and_then(|f| f.content().text());
+            let (number, total) = text.map_or((None, None), parse_number_pair);
+            (zero_none(number), zero_none(total))
+        };
+        let (track_number, total_tracks) = number_pair("TRCK");
+        let (disc_number, total_discs) = number_pair("TPOS");
+
+        Ok(Some(Self {
+            mode: Mode::read(file),
+            track_number,
+            total_tracks,
+            disc_number,
+            total_discs,
+            artists: tag
+                .artist()
+                .map(|s| s.split('\u{0}').map(|s| s.to_string()).collect())
+                .unwrap_or_default(),
+            release_artists: tag
+                .album_artist()
+                .map(|s| s.split('\u{0}').map(|s| s.to_string()).collect())
+                .unwrap_or_default(),
+            release: tag.album().map(|s| s.to_string()),
+            title: tag.title().map(|s| s.to_string()),
+            year: tag.year().or_else(|| tag.date_recorded().map(|d| d.year)),
+            // ID3v1 genre numbers are kept, so they can be normalized by a check
+            genre: tag.genre().map(|g| g.to_owned()),
+            has_artwork: tag.pictures().count() > 0,
+            audio: audio::read_mp3(file).unwrap_or_default(),
+            extra: tag
+                .extended_texts()
+                .map(|t| (t.description.to_uppercase(), t.value.clone()))
+                .collect(),
+        }))
+    }
+
+    #[cfg(feature = "mp4")]
+    fn read_mp4(file: &mut File) -> Result<Option<Self>, MetadataError> {
+        let mut tag = match mp4ameta::Tag::read_from(file) {
+            Ok(tag) => tag,
+            Err(e) if matches!(e.kind, mp4ameta::ErrorKind::NoTag) => return Ok(None),
+            Err(e) => return Err(invalid_tag(e)),
+        };
+        let audio = AudioInfo {
+            codec: audio::read_mp4_codec(file),
+            duration: tag.duration(),
+            bitrate: tag.avg_bitrate().map(|b| b / 1000),
+            sample_rate: tag.sample_rate().map(|s| s.hz()),
+        };
+        let extra = tag
+            .data()
+            .filter_map(|(ident, data)| match (ident, data) {
+                (mp4ameta::DataIdent::Freeform { name, .. }, mp4ameta::Data::Utf8(s)) => {
+                    Some((name.to_uppercase(), s.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        Ok(Some(Self {
+            mode: Mode::read(file),
+            track_number: tag.track_number(),
+            total_tracks: tag.total_tracks(),
+            disc_number: tag.disc_number(),
+            total_discs: tag.total_discs(),
+            artists: tag.take_artists().collect(),
+            release_artists: tag.take_album_artists().collect(),
+            release: tag.take_album(),
+            title: tag.take_title(),
+            year: tag.year().and_then(parse_year),
+            genre: tag.take_genre(),
+            has_artwork: tag.artwork().is_some(),
+            audio,
+            extra,
+        }))
+    }
+
+    #[cfg(feature = "flac")]
+    fn read_flac(file: &mut File) -> Result<Option<Self>, MetadataError> {
+        let tag = metaflac::Tag::read_from(file).map_err(invalid_tag)?;
+        let Some(vorbis) = tag.vorbis_comments() else { return Ok(None) };
+        let audio = tag.get_streaminfo().map_or_else(AudioInfo::default, |info| {
+            let duration = (info.sample_rate != 0).then(|| {
+                Duration::from_secs_f64(info.total_samples as f64 / info.sample_rate as f64)
+            });
+            let file_len = file.metadata().map(|m| m.len()).ok();
+            AudioInfo {
+                codec: Some(Codec::Flac),
+                duration,
+                bitrate: file_len.zip(duration).and_then(|(l, d)| audio::avg_bitrate(l, d)),
+                sample_rate: Some(info.sample_rate),
+            }
+        });
+
+        // a separate total field takes precedence over the one of an `N/M` value
+        let first = |key: &str| vorbis.get(key).and_then(|v| v.first()).map(String::as_str);
+        let number_pair = |key, total_keys: [&str; 2]| {
+            let (number, total) = first(key).map_or((None, None), parse_number_pair);
+            let total = (total_keys.iter().filter_map(|k| first(k)))
+                .find_map(|t| t.trim().parse().ok())
+                .or(total);
+            (zero_none(number), zero_none(total))
+        };
+        let (track_number, total_tracks) =
+            number_pair("TRACKNUMBER", ["TOTALTRACKS", "TRACKTOTAL"]);
+        let (disc_number, total_discs) = number_pair("DISCNUMBER", ["TOTALDISCS", "DISCTOTAL"]);
+
+        Ok(Some(Self {
+            mode: Mode::read(file),
+            track_number,
+            total_tracks,
+            disc_number,
+            total_discs,
+            artists: vorbis.artist().map_or_else(Vec::new, |v| v.to_owned()),
+            release_artists: vorbis.album_artist().map_or_else(Vec::new, |v| v.to_owned()),
+            release: vorbis.album().and_then(|v| v.first().cloned()),
+            title: vorbis.title().and_then(|v| v.first().cloned()),
+            year: first("DATE").and_then(parse_year),
+            genre: vorbis.genre().and_then(|v| v.first().cloned()),
+            has_artwork: tag.pictures().count() > 0,
+            audio,
+            extra: vorbis
+                .comments
+                .iter()
+                .filter_map(|(k, v)| Some((k.to_uppercase(), v.first()?.clone())))
+                .collect(),
+        }))
+    }
+
+    pub fn release_artists(&self) -> Option<&[String]> {
+        if !self.release_artists.is_empty() {
+            Some(&self.release_artists)
+        } else if !self.artists.is_empty() {
+            Some(&self.artists)
+        } else {
+            None
+        }
+    }
+
+    pub fn song_artists(&self) -> Option<&[String]> {
+        if !self.artists.is_empty() {
+            Some(&self.artists)
+        } else if !self.release_artists.is_empty() {
+            Some(&self.release_artists)
+        } else {
+            None
+        }
+    }
+}
+
+/// The rating of a song from 1 to 100 as written by [`TagUpdate::rating`](crate::TagUpdate),
+/// read from the `POPM` frame of mp3 files and from the `RATING` field otherwise.
+pub(crate) fn read_rating(path: &Path) -> Option<u8> {
+    let ext = path.extension()?;
+    #[cfg(feature = "mp3")]
+    if ext.eq_ignore_ascii_case("mp3") {
+        let tag = id3::Tag::read_from_path(path).ok()?;
+        let popm = tag.get("POPM")?.content().popularimeter()?;
+        // the star ranges most players use
+        return match popm.rating {
+            0 => None,
+            1..=31 => Some(20),
+            32..=95 => Some(40),
+            96..=159 => Some(60),
+            160..=223 => Some(80),
+            _ => Some(100),
+        };
+    }
+    if !crate::is_song_extension(ext) {
+        return None;
+    }
+    let rating = Metadata::read_from(path).extra.get("RATING")?.trim().parse::<u8>().ok()?;
+    (rating > 0).then_some(rating.min(100))
+}
+
+/// Whether a song can't be parsed at all, e.g. because it's truncated: an mp3 file without a
+/// valid frame, or a flac or mp4 file whose container can't be read. Files that can't be opened
+/// and formats that aren't supported aren't considered corrupt.
+#[cfg_attr(
+    not(any(feature = "mp3", feature = "mp4", feature = "flac")),
+    allow(unused_mut, unused_variables)
+)]
+pub(crate) fn is_corrupt(path: &Path) -> bool {
+    let Ok(mut file) = File::open(path) else { return false };
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    match ext.to_ascii_lowercase().as_str() {
+        #[cfg(feature = "mp3")]
+        "mp3" => audio::read_mp3(&mut file).is_none(),
+        #[cfg(feature = "mp4")]
+        "m4a" | "m4b" | "m4v" | "mp4" => mp4ameta::Tag::read_from(&mut file).is_err(),
+        #[cfg(feature = "flac")]
+        "flac" => metaflac::Tag::read_from(&mut file).is_err(),
+        _ => false,
+    }
+}
+
+/// Strips a featuring credit like ` feat. B`, ` ft. B` or ` (featuring B)` from an artist name,
+/// so `A feat. B` is grouped with the releases of `A`.
+pub(crate) fn primary_artist(artist: &str) -> &str {
+    const MARKERS: [&str; 9] =
+        [" feat. ", " feat ", " ft. ", " ft ", " featuring ", " (feat", " (ft", " [feat", " [ft"];
+    let lower = artist.to_lowercase();
+    // lowercasing may change byte lengths of non-ascii chars, the markers can't be found then
+    if lower.len() != artist.len() {
+        return artist;
+    }
+    let start = MARKERS.iter().filter_map(|m| lower.find(m)).min();
+    match start {
+        Some(start) if !artist[..start].trim().is_empty() => artist[..start].trim_end(),
+        _ => artist,
+    }
+}
+
+/// On unix this is the `st_mode` of a file. Other platforms only know a read-only attribute, which
+/// is modeled as a mode of `0o555` for read-only and `0o755` for writable files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mode(pub u32);
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn write_permissions(
+            f: &mut std::fmt::Formatter<'_>,
+            mode: u32,
+            offset: u32,
+        ) -> std::fmt::Result {
+            let r = if mode & (0o4 << offset) == 0 { '-' } else { 'r' };
+            let w = if mode & (0o2 << offset) == 0 { '-' } else { 'w' };
+            let x = if mode & (0o1 << offset) == 0 { '-' } else { 'x' };
+            write!(f, "{r}{w}{x}")
+        }
+        write_permissions(f, self.0, 6)?;
+        write_permissions(f, self.0, 3)?;
+        write_permissions(f, self.0, 0)?;
+        Ok(())
+    }
+}
+
+impl Mode {
+    pub fn read(file: &File) -> Option<Mode> {
+        let meta = file.metadata().ok()?;
+        Some(Mode::from_metadata(&meta))
+    }
+
+    #[cfg(unix)]
+    pub fn from_metadata(meta: &std::fs::Metadata) -> Mode {
+        use std::os::unix::fs::MetadataExt;
+
+        Mode(meta.mode())
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_metadata(meta: &std::fs::Metadata) -> Mode {
+        match meta.permissions().readonly() {
+            true => Mode(0o555),
+            false => Mode(0o755),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let file = File::open(path)?;
+        file.set_permissions(self.to_permissions(file.metadata()?.permissions()))?;
+        Ok(())
+    }
+
+    pub fn write_dir(&self, path: &Path) -> std::io::Result<()> {
+        let permissions = std::fs::metadata(path)?.permissions();
+        std::fs::set_permissions(path, self.to_permissions(permissions))
+    }
+
+    #[cfg(unix)]
+    fn to_permissions(self, _current: Permissions) -> Permissions {
+        use std::os::unix::fs::PermissionsExt;
+
+        Permissions::from_mode(self.0)
+    }
+
+    #[cfg(not(unix))]
+    fn to_permissions(self, mut current: Permissions) -> Permissions {
+        current.set_readonly(self.0 & 0o200 == 0);
+        current
+    }
+
+    pub fn permissions(&self) -> u32 {
+        self.0 & 0o777
+    }
+
+    pub fn with_permissions(&self, permissions: u32) -> Self {
+        Self((self.0 & !0o777) | (permissions & 0o777))
+    }
+}
+
+/// Parses track and disc values like `3`, `03/12` or ` 3 / 12 ` into the number and the total.
+/// Either is `None` if it's missing or not a number, so the total of `/12` is still kept. Id3
+/// values may also separate them with a null character.
+#[cfg(any(feature = "mp3", feature = "flac"))]
+fn parse_number_pair(s: &str) -> (Option<u16>, Option<u16>) {
+    let mut split = s.splitn(2, ['/', '\u{0}']);
+    let mut next = || split.next().and_then(|n| n.trim().parse().ok());
+    let number = next();
+    (number, next())
+}
+
+/// Parses the year of dates like `2004` or `2004-05-12`.
+#[cfg(any(feature = "mp4", feature = "flac"))]
+fn parse_year(date: &str) -> Option<i32> {
+    date.get(0..4)?.parse().ok()
+}
+
+#[inline]
+#[cfg(any(feature = "mp3", feature = "flac"))]
+pub fn zero_none(n: Option<u16>) -> Option<u16> {
+    n.and_then(|n| match n {
+        0 => None,
+        _ => Some(n),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_name_keys() {
+        assert_eq!(name_key("Motörhead"), "motorhead");
+        assert_eq!(name_key("The Beatles"), name_key("beatles"));
+        assert_eq!(name_key("Sigur Rós"), "sigur ros");
+        assert_eq!(name_key("The"), "the");
+    }
+
+    #[test]
+    fn strips_featuring_credits() {
+        assert_eq!(primary_artist("A feat. B"), "A");
+        assert_eq!(primary_artist("A Ft. B & C"), "A");
+        assert_eq!(primary_artist("A (featuring B)"), "A");
+        assert_eq!(primary_artist("A [feat. B]"), "A");
+        assert_eq!(primary_artist("Daft Punk"), "Daft Punk");
+        assert_eq!(primary_artist("Left Boy"), "Left Boy");
+        assert_eq!(primary_artist("feat. B"), "feat. B");
+    }
+
+    #[test]
+    #[cfg(any(feature = "mp3", feature = "flac"))]
+    fn parses_number_pairs() {
+        assert_eq!(parse_number_pair("3"), (Some(3), None));
+        assert_eq!(parse_number_pair("03/12"), (Some(3), Some(12)));
+        assert_eq!(parse_number_pair(" 1 / 2 "), (Some(1), Some(2)));
+        assert_eq!(parse_number_pair("3\u{0}12"), (Some(3), Some(12)));
+        assert_eq!(parse_number_pair("3/"), (Some(3), None));
+        assert_eq!(parse_number_pair("/12"), (None, Some(12)));
+        assert_eq!(parse_number_pair("A1"), (None, None));
+        assert_eq!(parse_number_pair(""), (None, None));
+    }
+
+    #[test]
+    #[cfg(feature = "mp3")]
+    fn reports_unreadable_tags() {
+        let dir = std::env::temp_dir().join(format!("music-organizer-meta-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let malformed = dir.join("malformed.MP3");
+        std::fs::write(&malformed, b"ID3\x09\x00\x00\x00\x00\x00\x10garbage").unwrap();
+        let no_extension = dir.join("song");
+        std::fs::write(&no_extension, b"").unwrap();
+
+        let read = Metadata::try_read_from(&malformed);
+        assert!(matches!(read, Err(MetadataError::InvalidTag(_))), "{read:?}");
+        assert_eq!(Metadata::try_read_from(&no_extension), Ok(Metadata::default()));
+        let read = Metadata::try_read_from(&dir.join("missing.mp3"));
+        assert!(matches!(read, Err(MetadataError::Open(_))), "{read:?}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
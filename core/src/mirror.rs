@@ -0,0 +1,557 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use tracing::{debug, info_span};
+
+use crate::fs::{is_hidden, valid_os_str};
+use crate::meta::read_rating;
+use crate::playlist::playlist_entries;
+use crate::transcode::is_lossless;
+use crate::{
+    CancelToken, DirCreation, DirDeletion, ExecOptions, FileDeletion, FileOpType, FileOperation,
+    Fs, Mode, Transcode,
+};
+
+/// FAT stores modification times with a resolution of two seconds.
+const MODIFIED_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// The file names a destination filesystem supports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NamingProfile {
+    /// Names are kept as they are.
+    #[default]
+    Posix,
+    /// FAT32 and exFAT, used by most phones and USB drives. Reserved characters are removed,
+    /// trailing dots and spaces are trimmed and names are case insensitive.
+    Fat,
+}
+
+impl NamingProfile {
+    /// The name of a file or directory on the destination.
+    pub fn file_name(&self, name: &OsStr) -> OsString {
+        match self {
+            Self::Posix => name.to_owned(),
+            Self::Fat => {
+                let name = valid_os_str(&name.to_string_lossy());
+                let name = name.trim_end_matches(['.', ' ']);
+                match name.is_empty() {
+                    true => OsString::from("_"),
+                    false => OsString::from(name),
+                }
+            }
+        }
+    }
+
+    fn same_name(&self, a: &OsStr, b: &OsStr) -> bool {
+        match self {
+            Self::Posix => a == b,
+            Self::Fat => a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase(),
+        }
+    }
+}
+
+/// Limits the total size of the mirrored files, e.g. to fill an SD card with a selection of the
+/// library instead of failing once it's full.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SizeLimit {
+    /// In bytes.
+    pub max_size: u64,
+    pub order: SelectionOrder,
+    /// Directories, e.g. of artists, and playlists whose files are selected before all others, in
+    /// the given order. Relative paths are relative to the source dir.
+    pub preferred: Vec<PathBuf>,
+}
+
+/// The order files are selected in, until no more fit into the [`SizeLimit`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SelectionOrder {
+    /// The most recently modified files first.
+    #[default]
+    Recent,
+    /// The highest rated songs first, followed by the unrated songs and all other files, the most
+    /// recently modified first.
+    Rated,
+}
+
+/// Mirrors a directory, usually the organized output dir, into a destination like a phone or a
+/// NAS. New files and files whose size differs or that were modified after their copy are copied,
+/// and optionally files that don't exist in the source anymore are deleted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Mirror {
+    pub dir_creations: Vec<DirCreation>,
+    /// Copies from the source to the destination.
+    pub copies: Vec<FileOperation>,
+    /// Lossless songs that are transcoded instead of copied, see [`Mirror::transcode`].
+    pub transcodes: Vec<FileOperation>,
+    pub file_deletions: Vec<FileDeletion>,
+    /// Ordered so directories are deleted after their contents.
+    pub dir_deletions: Vec<DirDeletion>,
+    pub source_dir: PathBuf,
+    pub dest_dir: PathBuf,
+    pub profile: NamingProfile,
+    /// Delete files and directories of the destination that aren't in the source.
+    pub delete: bool,
+    /// Sync entries whose name starts with a dot, otherwise they are neither copied nor deleted.
+    pub include_hidden: bool,
+    /// Mode applied to newly created directories, if none is set the process umask is used.
+    pub dir_mode: Option<Mode>,
+    /// Transcode lossless songs instead of copying them. They are only transcoded again if the
+    /// source was modified after the destination was written, since their sizes always differ.
+    pub transcode: Option<Transcode>,
+    /// Only mirror the files that fit into the limit, the others are treated like they weren't in
+    /// the source. Transcoded songs are counted with their estimated size.
+    pub size_limit: Option<SizeLimit>,
+    /// Files of the source that were left out because of the [`Mirror::size_limit`].
+    pub excluded: Vec<PathBuf>,
+    /// Checked before each directory of the source.
+    pub cancel: CancelToken,
+}
+
+impl Mirror {
+    pub fn new(source_dir: PathBuf, dest_dir: PathBuf) -> Self {
+        Self { source_dir, dest_dir, ..Default::default() }
+    }
+
+    /// Compares the source with the destination and plans the copies and deletions, previous
+    /// plans are replaced.
+    pub fn check(&mut self, fs: &impl Fs, f: &mut impl FnMut(&Path)) {
+        let _span = info_span!("mirror", dest_dir = %self.dest_dir.display()).entered();
+        self.dir_creations.clear();
+        self.copies.clear();
+        self.transcodes.clear();
+        self.file_deletions.clear();
+        self.dir_deletions.clear();
+        self.excluded.clear();
+
+        let selected = self.size_limit.is_some().then(|| self.select(fs));
+        let (source, dest) = (self.source_dir.clone(), self.dest_dir.clone());
+        self.check_dir(fs, &source, &dest, selected.as_ref(), f);
+    }
+
+    /// The files of the source that fit into the size limit and their parent directories.
+    fn select(&mut self, fs: &impl Fs) -> HashSet<PathBuf> {
+        let Some(limit) = &self.size_limit else { return HashSet::new() };
+        let mut files = Vec::new();
+        collect_files(fs, &self.source_dir, self.include_hidden, &mut files);
+
+        let preferred: Vec<(PathBuf, Option<HashSet<PathBuf>>)> = (limit.preferred.iter())
+            .map(|p| {
+                let p = self.source_dir.join(p);
+                let entries = fs.is_file(&p).then(|| playlist_entries(fs, &p)).flatten();
+                (p, entries.map(HashSet::from_iter))
+            })
+            .collect();
+        let preference = |file: &Path| {
+            preferred.iter().position(|(p, entries)| match entries {
+                Some(entries) => file == p || entries.contains(file),
+                None => file.starts_with(p),
+            })
+        };
+
+        let mut candidates: Vec<_> = (files.into_iter())
+            .map(|p| {
+                let (len, modified) = fs.file_info(&p).unwrap_or((0, SystemTime::UNIX_EPOCH));
+                let size = match self.transcode.as_ref().filter(|_| is_lossless(&p)) {
+                    Some(t) => t.estimated_size(&p, len),
+                    None => len,
+                };
+                let rating = match limit.order {
+                    SelectionOrder::Recent => None,
+                    SelectionOrder::Rated => read_rating(&p),
+                };
+                let key =
+                    (preference(&p).unwrap_or(usize::MAX), Reverse(rating), Reverse(modified));
+                (key, size, p)
+            })
+            .collect();
+        candidates.sort();
+
+        let mut selected = HashSet::new();
+        let mut total = 0;
+        for (_, size, p) in candidates {
+            if total + size > limit.max_size {
+                self.excluded.push(p);
+                continue;
+            }
+            total += size;
+            for dir in p.ancestors().skip(1) {
+                if dir == self.source_dir || !selected.insert(dir.to_path_buf()) {
+                    break;
+                }
+            }
+            selected.insert(p);
+        }
+        debug!(total, excluded = self.excluded.len(), "selected files");
+        self.excluded.sort();
+        selected
+    }
+
+    fn check_dir(
+        &mut self,
+        fs: &impl Fs,
+        source: &Path,
+        dest: &Path,
+        selected: Option<&HashSet<PathBuf>>,
+        f: &mut impl FnMut(&Path),
+    ) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
+        f(source);
+
+        let dest_exists = fs.is_dir(dest);
+        if !dest_exists {
+            self.dir_creations.push(DirCreation {
+                path: dest.to_owned(),
+                mode: self.dir_mode,
+                owner: None,
+            });
+        }
+
+        let mut names: Vec<OsString> = Vec::new();
+        for p in fs.read_dir(source).unwrap_or_default() {
+            if !self.include_hidden && is_hidden(&p) {
+                continue;
+            }
+            if selected.is_some_and(|s| !s.contains(&p)) {
+                continue;
+            }
+            let mut name = self.profile.file_name(p.file_name().unwrap_or_default());
+            let transcode = self.transcode.as_ref().filter(|_| is_lossless(&p));
+            if let Some(t) = transcode {
+                name = Path::new(&name).with_extension(t.codec.extension()).into_os_string();
+            }
+            if names.iter().any(|n| self.profile.same_name(n, &name)) {
+                debug!(path = %p.display(), "name already taken on the destination");
+                continue;
+            }
+            let new_path = dest.join(&name);
+            names.push(name);
+
+            if fs.is_dir(&p) {
+                self.check_dir(fs, &p, &new_path, selected, f);
+            } else if transcode.is_some() {
+                if !dest_exists || is_modified(fs, &p, &new_path) {
+                    self.transcodes.push(FileOperation {
+                        old_path: p,
+                        new_path,
+                        op_type: None,
+                        replace: false,
+                    });
+                }
+            } else if !dest_exists || is_changed(fs, &p, &new_path) {
+                self.copies.push(FileOperation {
+                    old_path: p,
+                    new_path,
+                    op_type: None,
+                    replace: false,
+                });
+            }
+        }
+
+        if self.delete && dest_exists {
+            for p in fs.read_dir(dest).unwrap_or_default() {
+                let name = p.file_name().unwrap_or_default();
+                let kept = names.iter().any(|n| self.profile.same_name(n, name));
+                if !kept && (self.include_hidden || !is_hidden(&p)) {
+                    self.delete_recursive(fs, p);
+                }
+            }
+        }
+    }
+
+    fn delete_recursive(&mut self, fs: &impl Fs, path: PathBuf) {
+        if fs.is_dir(&path) {
+            for p in fs.read_dir(&path).unwrap_or_default() {
+                self.delete_recursive(fs, p);
+            }
+            self.dir_deletions.push(DirDeletion { path });
+        } else {
+            self.file_deletions.push(FileDeletion { path });
+        }
+    }
+
+    pub fn execute_dir_creations(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        f: &mut impl FnMut(&DirCreation, std::io::Result<()>),
+    ) {
+        for d in self.dir_creations.iter() {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let r = d.execute(fs);
+            let failed = r.is_err();
+            f(d, r);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    }
+
+    /// Files are always copied, regardless of the operation type of the options.
+    pub fn execute_copies(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        progress: &mut impl FnMut(&Path, u64, u64),
+        f: &mut impl FnMut(&FileOperation, Result<(), Box<dyn std::error::Error>>),
+    ) {
+        let options = ExecOptions { op_type: FileOpType::Copy, ..options.clone() };
+        for o in self.copies.iter() {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let r =
+                o.execute(fs, &options, &mut |copied, total| progress(&o.old_path, copied, total));
+            let failed = r.is_err();
+            f(o, r);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    }
+
+    /// Transcodes on the real filesystem regardless of `fs`, since ffmpeg writes the files.
+    pub fn execute_transcodes(
+        &self,
+        options: &ExecOptions,
+        f: &mut impl FnMut(&FileOperation, Result<(), Box<dyn std::error::Error>>),
+    ) {
+        let Some(transcode) = &self.transcode else { return };
+        for o in self.transcodes.iter() {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let r = transcode.execute(&o.old_path, &o.new_path).map_err(Into::into);
+            let failed = r.is_err();
+            f(o, r);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    }
+
+    pub fn execute_file_deletions(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        f: &mut impl FnMut(&FileDeletion, std::io::Result<()>),
+    ) {
+        for d in self.file_deletions.iter() {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let r = d.execute(fs, options.delete_type);
+            let failed = r.is_err();
+            f(d, r);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    }
+
+    pub fn execute_dir_deletions(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        f: &mut impl FnMut(&DirDeletion, std::io::Result<()>),
+    ) {
+        for d in self.dir_deletions.iter() {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let r = d.execute(fs, options.delete_type);
+            let failed = r.is_err();
+            f(d, r);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dir_creations.is_empty()
+            && self.copies.is_empty()
+            && self.transcodes.is_empty()
+            && self.file_deletions.is_empty()
+            && self.dir_deletions.is_empty()
+    }
+}
+
+fn collect_files(fs: &impl Fs, dir: &Path, include_hidden: bool, files: &mut Vec<PathBuf>) {
+    for p in fs.read_dir(dir).unwrap_or_default() {
+        if !include_hidden && is_hidden(&p) {
+            continue;
+        }
+        if fs.is_dir(&p) {
+            collect_files(fs, &p, include_hidden, files);
+        } else {
+            files.push(p);
+        }
+    }
+}
+
+/// Whether the size differs or the source was modified after the destination was written.
+fn is_changed(fs: &impl Fs, source: &Path, dest: &Path) -> bool {
+    let Ok((dest_len, _)) = fs.file_info(dest) else { return true };
+    let Ok((source_len, _)) = fs.file_info(source) else { return true };
+    source_len != dest_len || is_modified(fs, source, dest)
+}
+
+/// Whether the source was modified after the destination was written.
+fn is_modified(fs: &impl Fs, source: &Path, dest: &Path) -> bool {
+    let Ok((_, dest_modified)) = fs.file_info(dest) else { return true };
+    let Ok((_, source_modified)) = fs.file_info(source) else { return true };
+    source_modified > dest_modified + MODIFIED_TOLERANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::MemFs;
+
+    fn check(fs: &MemFs, profile: NamingProfile, delete: bool) -> Mirror {
+        let mut mirror = Mirror::new(PathBuf::from("/music"), PathBuf::from("/phone"));
+        mirror.profile = profile;
+        mirror.delete = delete;
+        mirror.check(fs, &mut |_| ());
+        mirror
+    }
+
+    fn copies(mirror: &Mirror) -> Vec<(&Path, &Path)> {
+        mirror.copies.iter().map(|c| (c.old_path.as_path(), c.new_path.as_path())).collect()
+    }
+
+    #[test]
+    fn copies_new_and_changed_files() {
+        let fs = MemFs::new();
+        fs.add_file("/music/Foo/Bar/01 - One.mp3", "one");
+        fs.add_file("/music/Foo/Bar/02 - Two.mp3", "two");
+        fs.add_file("/music/Foo/Bar/03 - Three.mp3", "three");
+        fs.add_file("/phone/Foo/Bar/01 - One.mp3", "one");
+        fs.add_file("/phone/Foo/Bar/02 - Two.mp3", "2");
+        fs.add_file("/phone/Foo/Bar/03 - Three.mp3", "three");
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+        fs.set_modified(Path::new("/music/Foo/Bar/03 - Three.mp3"), modified);
+
+        let mirror = check(&fs, NamingProfile::Posix, false);
+        assert_eq!(
+            copies(&mirror),
+            [
+                (
+                    Path::new("/music/Foo/Bar/02 - Two.mp3"),
+                    Path::new("/phone/Foo/Bar/02 - Two.mp3")
+                ),
+                (
+                    Path::new("/music/Foo/Bar/03 - Three.mp3"),
+                    Path::new("/phone/Foo/Bar/03 - Three.mp3")
+                ),
+            ]
+        );
+
+        mirror.execute(&fs, &ExecOptions::default(), &mut Quiet);
+        assert!(check(&fs, NamingProfile::Posix, false).is_empty());
+        assert!(fs.exists(Path::new("/music/Foo/Bar/02 - Two.mp3")));
+    }
+
+    #[test]
+    fn deletes_removed_files_and_uses_fat_names() {
+        let fs = MemFs::new();
+        fs.add_file("/music/AC: DC/Back in Black./01 - Hells Bells.mp3", "one");
+        fs.add_file("/phone/AC DC/old/01 - Old.mp3", "old");
+        fs.add_file("/phone/.hidden", "");
+
+        let mirror = check(&fs, NamingProfile::Fat, true);
+        let dirs: Vec<&Path> = mirror.dir_creations.iter().map(|d| d.path.as_path()).collect();
+        assert_eq!(dirs, [Path::new("/phone/AC DC/Back in Black")]);
+        assert_eq!(
+            copies(&mirror),
+            [(
+                Path::new("/music/AC: DC/Back in Black./01 - Hells Bells.mp3"),
+                Path::new("/phone/AC DC/Back in Black/01 - Hells Bells.mp3"),
+            )]
+        );
+        let files: Vec<&Path> = mirror.file_deletions.iter().map(|d| d.path.as_path()).collect();
+        assert_eq!(files, [Path::new("/phone/AC DC/old/01 - Old.mp3")]);
+        let dirs: Vec<&Path> = mirror.dir_deletions.iter().map(|d| d.path.as_path()).collect();
+        assert_eq!(dirs, [Path::new("/phone/AC DC/old")]);
+    }
+
+    #[test]
+    fn transcodes_lossless_songs() {
+        let fs = MemFs::new();
+        fs.add_file("/music/Foo/Bar/01 - One.flac", "one");
+        fs.add_file("/music/Foo/Bar/02 - Two.mp3", "two");
+        fs.add_file("/music/Foo/Bar/03 - Three.flac", "three");
+        fs.add_file("/phone/Foo/Bar/03 - Three.opus", "3");
+        fs.set_modified(Path::new("/music/Foo/Bar/03 - Three.flac"), SystemTime::UNIX_EPOCH);
+
+        let mut mirror = Mirror::new(PathBuf::from("/music"), PathBuf::from("/phone"));
+        mirror.transcode = Some(Transcode::new(crate::TranscodeCodec::Opus, 128));
+        mirror.delete = true;
+        mirror.check(&fs, &mut |_| ());
+
+        assert_eq!(
+            copies(&mirror),
+            [(Path::new("/music/Foo/Bar/02 - Two.mp3"), Path::new("/phone/Foo/Bar/02 - Two.mp3"))]
+        );
+        let transcodes: Vec<(&Path, &Path)> = mirror
+            .transcodes
+            .iter()
+            .map(|c| (c.old_path.as_path(), c.new_path.as_path()))
+            .collect();
+        assert_eq!(
+            transcodes,
+            [(
+                Path::new("/music/Foo/Bar/01 - One.flac"),
+                Path::new("/phone/Foo/Bar/01 - One.opus")
+            )]
+        );
+        assert!(mirror.file_deletions.is_empty());
+    }
+
+    #[test]
+    fn selects_preferred_and_recent_files_within_size_limit() {
+        let fs = MemFs::new();
+        fs.add_file("/music/Foo/Bar/01 - One.mp3", "1111");
+        fs.add_file("/music/Foo/Bar/02 - Two.mp3", "2222");
+        fs.add_file("/music/Baz/Qux/01 - Old.mp3", "3333");
+        fs.add_file("/music/Baz/Qux/02 - New.mp3", "4444");
+        fs.add_file("/music/mix.m3u", "Foo/Bar/02 - Two.mp3\n");
+        let new = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+        fs.set_modified(Path::new("/music/Baz/Qux/02 - New.mp3"), new);
+
+        let mut mirror = Mirror::new(PathBuf::from("/music"), PathBuf::from("/phone"));
+        mirror.size_limit = Some(SizeLimit {
+            max_size: 8,
+            order: SelectionOrder::Recent,
+            preferred: vec![PathBuf::from("mix.m3u")],
+        });
+        mirror.check(&fs, &mut |_| ());
+
+        let mut sources: Vec<&Path> = mirror.copies.iter().map(|c| c.old_path.as_path()).collect();
+        sources.sort();
+        assert_eq!(
+            sources,
+            [Path::new("/music/Baz/Qux/02 - New.mp3"), Path::new("/music/Foo/Bar/02 - Two.mp3")]
+        );
+        assert_eq!(
+            mirror.excluded,
+            [
+                Path::new("/music/Baz/Qux/01 - Old.mp3"),
+                Path::new("/music/Foo/Bar/01 - One.mp3"),
+                Path::new("/music/mix.m3u"),
+            ]
+        );
+    }
+
+    struct Quiet;
+    impl crate::Progress for Quiet {}
+}
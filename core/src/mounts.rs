@@ -0,0 +1,95 @@
+use std::path::{Path, PathBuf};
+
+/// Filesystems without unix permissions, chmod fails or is silently ignored on them.
+const NO_PERMISSION_FS_TYPES: [&str; 9] =
+    ["vfat", "msdos", "exfat", "ntfs", "ntfs3", "fuseblk", "cifs", "smb3", "smbfs"];
+
+/// A mounted filesystem as listed in `/proc/mounts`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mount {
+    pub path: PathBuf,
+    pub fs_type: String,
+    pub read_only: bool,
+}
+
+impl Mount {
+    /// Whether the permissions of files on it can be changed.
+    pub fn supports_permissions(&self) -> bool {
+        !self.read_only && !NO_PERMISSION_FS_TYPES.contains(&self.fs_type.as_str())
+    }
+}
+
+/// The mounted filesystems, empty on platforms other than Linux or if they can't be read.
+pub(crate) fn read_mounts() -> Vec<Mount> {
+    if !cfg!(target_os = "linux") {
+        return Vec::new();
+    }
+    std::fs::read_to_string("/proc/mounts").map_or_else(|_| Vec::new(), |s| parse_mounts(&s))
+}
+
+/// Parses lines like `/dev/sdb1 /mnt/music ntfs3 rw,relatime 0 0`, spaces in paths are escaped
+/// as `\040`.
+fn parse_mounts(mounts: &str) -> Vec<Mount> {
+    let mut parsed = Vec::new();
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace().skip(1);
+        let (Some(path), Some(fs_type), Some(options)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        parsed.push(Mount {
+            path: PathBuf::from(unescape(path)),
+            fs_type: fs_type.to_owned(),
+            read_only: options.split(',').any(|o| o == "ro"),
+        });
+    }
+    parsed
+}
+
+/// Replaces octal escapes like `\040`.
+fn unescape(s: &str) -> String {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let octal = tail.get(..3).and_then(|o| std::str::from_utf8(o).ok());
+        match octal.and_then(|o| u8::from_str_radix(o, 8).ok()) {
+            Some(c) if b == b'\\' => {
+                bytes.push(c);
+                rest = &tail[3..];
+            }
+            _ => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// The mount containing the path, the one mounted last wins if multiple are mounted at the same
+/// path.
+pub(crate) fn mount_of<'a>(mounts: &'a [Mount], path: &Path) -> Option<&'a Mount> {
+    mounts.iter().filter(|m| path.starts_with(&m.path)).max_by_key(|m| m.path.as_os_str().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_mount_of_a_path() {
+        let mounts = parse_mounts(
+            "/dev/sda1 / ext4 rw,relatime 0 0\n\
+            /dev/sdb1 /mnt/my\\040music ntfs3 rw,relatime 0 0\n\
+            /dev/sr0 /mnt/cd iso9660 ro,nosuid 0 0\n",
+        );
+
+        let mount = |p: &str| mount_of(&mounts, Path::new(p)).unwrap();
+        assert!(mount("/home/foo/a.mp3").supports_permissions());
+        assert_eq!(mount("/mnt/my music/a.mp3").fs_type, "ntfs3");
+        assert!(!mount("/mnt/my music/a.mp3").supports_permissions());
+        assert!(mount("/mnt/cd/a.mp3").read_only);
+        assert_eq!(mount("/mnt/my musical/a.mp3").path, Path::new("/"));
+    }
+}
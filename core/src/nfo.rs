@@ -0,0 +1,178 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::changes::{layout_field, release_order};
+use crate::fs::valid_os_str_dots;
+use crate::layout::{parse_segments, render_segments, Segment};
+use crate::playlist::common_dir;
+use crate::{ChangeOptions, Changes, Fs, LayoutError, MusicIndex, SongOperation, LAYOUT_FIELDS};
+
+/// The template of a whole release, `{tracks}` is replaced by the track lines.
+pub const DEFAULT_NFO_TEMPLATE: &str = "{release_artists} - {release}[ ({year})]\\n\
+    [Genre: {genre}\\n]Format: {formats}\\nLength: {duration}\\n\\n{tracks}\\n";
+
+/// The template of a single line of the track list, it has the same fields as a layout.
+pub const DEFAULT_NFO_TRACK_TEMPLATE: &str =
+    "[{disc}-]{track:02}. {artists} - {title}[ ({duration})]";
+
+/// The fields that can be used inside the release template.
+pub const NFO_FIELDS: [&str; 9] = [
+    "release_artists",
+    "release",
+    "year",
+    "genre",
+    "formats",
+    "duration",
+    "total_tracks",
+    "total_discs",
+    "tracks",
+];
+
+/// Templates of the text file written into each release dir, using the syntax of a
+/// [`Layout`](crate::Layout). Since newlines are hard to pass as arguments, `\n` is replaced by
+/// a newline.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NfoTemplate {
+    release: Vec<Segment>,
+    track: Vec<Segment>,
+}
+
+impl Default for NfoTemplate {
+    fn default() -> Self {
+        Self::new(DEFAULT_NFO_TEMPLATE, DEFAULT_NFO_TRACK_TEMPLATE).unwrap()
+    }
+}
+
+impl NfoTemplate {
+    pub fn new(release: &str, track: &str) -> Result<Self, LayoutError> {
+        let unescape = |s: &str| s.replace("\\n", "\n");
+        Ok(Self {
+            release: parse_segments(&unescape(release), &NFO_FIELDS)?,
+            track: parse_segments(&unescape(track), &LAYOUT_FIELDS)?,
+        })
+    }
+}
+
+/// A summary of a release, written next to its songs as `<release>.nfo`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReleaseNfo {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+impl ReleaseNfo {
+    /// Creates or replaces the file.
+    pub fn execute(&self, fs: &impl Fs) -> Result<(), std::io::Error> {
+        fs.write(&self.path, self.content.as_bytes())
+    }
+}
+
+impl Changes {
+    /// Renders the summaries of all releases, using the tags and paths of the songs after all
+    /// changes. Files that already exist with the same content are skipped.
+    pub fn release_nfos(
+        &self,
+        fs: &impl Fs,
+        index: &MusicIndex,
+        template: &NfoTemplate,
+        options: &ChangeOptions,
+    ) -> Vec<ReleaseNfo> {
+        let join = &options.artist_separators.name_join;
+        let ops: HashMap<&Path, &SongOperation> =
+            self.song_operations.iter().map(|o| (o.song.path.as_path(), o)).collect();
+        let mut songs: Vec<_> = index
+            .songs
+            .iter()
+            .map(|s| {
+                let op = ops.get(s.path.as_path());
+                let tag_update = op.and_then(|o| o.tag_update.as_ref());
+                let path = op.and_then(|o| o.new_path.as_deref()).unwrap_or(&s.path);
+                (release_order(s, tag_update), s, tag_update, path)
+            })
+            .collect();
+        songs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut nfos = Vec::new();
+        for release in songs.chunk_by(|a, b| (a.0).0 == (b.0).0 && (a.0).1 == (b.0).1) {
+            let paths: Vec<&Path> = release.iter().map(|s| s.3).collect();
+            let Some(dir) = common_dir(&paths) else { continue };
+            let (_, first, first_update, _) = release[0];
+            let first_field = |name: &str| layout_field(first, first_update, name, join);
+            let Some(name) = first_field("release") else { continue };
+
+            let mut tracks = String::new();
+            for (_, song, tag_update, _) in release.iter() {
+                let field = |name: &str| layout_field(song, *tag_update, name, join);
+                render_segments(&template.track, &field, &mut tracks);
+                tracks.push('\n');
+            }
+            let durations = release.iter().map(|s| s.1.audio.duration);
+            let duration = durations.sum::<Option<Duration>>().map(|d| {
+                let secs = d.as_secs();
+                format!("{}m{:02}s", secs / 60, secs % 60)
+            });
+            let codecs: BTreeSet<String> =
+                release.iter().filter_map(|s| Some(s.1.audio.codec?.to_string())).collect();
+            let genre = first_update
+                .and_then(|t| t.genre.str_value())
+                .or(first.genre.as_deref())
+                .filter(|g| !g.is_empty())
+                .map(|g| g.replace('\0', ", "));
+
+            let field = |name: &str| match name {
+                "year" => first.year.map(|y| y.to_string()),
+                "genre" => genre.clone(),
+                "formats" => (!codecs.is_empty())
+                    .then(|| codecs.iter().cloned().collect::<Vec<_>>().join(", ")),
+                "duration" => duration.clone(),
+                "tracks" => Some(tracks.trim_end().to_owned()),
+                _ => first_field(name),
+            };
+            let mut content = String::new();
+            render_segments(&template.release, &field, &mut content);
+
+            let path = dir.join(format!("{}.nfo", valid_os_str_dots(&name)));
+            if fs.read(&path).ok() != Some(content.clone().into_bytes()) {
+                nfos.push(ReleaseNfo { path, content });
+            }
+        }
+        nfos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{song, SongTags};
+    use crate::{AudioInfo, Codec, MemFs};
+
+    #[test]
+    fn renders_release_summary() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/Foo/Bar/2.flac", "Bar", "Foo").with_track(2, None).with_title("Two"),
+            song("/music/Foo/Bar/1.flac", "Bar", "Foo").with_track(1, None).with_title("One"),
+        ];
+        for (s, secs) in index.songs.iter_mut().zip([65, 190]) {
+            s.year = Some(2001);
+            s.audio = AudioInfo {
+                codec: Some(Codec::Flac),
+                duration: Some(Duration::from_secs(secs)),
+                ..Default::default()
+            };
+        }
+        let nfos = Changes::default().release_nfos(
+            &MemFs::default(),
+            &index,
+            &NfoTemplate::default(),
+            &ChangeOptions::default(),
+        );
+
+        assert_eq!(nfos.len(), 1);
+        assert_eq!(nfos[0].path, PathBuf::from("/music/Foo/Bar/Bar.nfo"));
+        let expected = "Foo - Bar (2001)\nFormat: FLAC\nLength: 4m15s\n\n\
+            01. Foo - One (3m10s)\n02. Foo - Two (1m05s)\n";
+        assert_eq!(nfos[0].content, expected);
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// The user and group owning a file, ids that aren't set are left unchanged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Owner {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+#[cfg(unix)]
+pub(crate) fn set_owner(path: &Path, owner: Owner) -> std::io::Result<()> {
+    std::os::unix::fs::chown(path, owner.uid, owner.gid)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn set_owner(_path: &Path, _owner: Owner) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "owners are only supported on unix"))
+}
+
+/// Changes the owner of `to` to the one of `from`, on Linux the POSIX ACLs are copied as well.
+#[cfg(unix)]
+pub(crate) fn copy_ownership(from: &Path, to: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = std::fs::symlink_metadata(from)?;
+    set_owner(to, Owner { uid: Some(meta.uid()), gid: Some(meta.gid()) })?;
+    #[cfg(target_os = "linux")]
+    acl::copy(from, to)?;
+    Ok(())
+}
+
+/// Other platforms don't have unix owners.
+#[cfg(not(unix))]
+pub(crate) fn copy_ownership(_from: &Path, _to: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// POSIX ACLs are stored as extended attributes, copying them as is preserves them without
+/// having to parse them.
+#[cfg(target_os = "linux")]
+mod acl {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    const ACL_XATTRS: [&str; 2] = ["system.posix_acl_access", "system.posix_acl_default"];
+
+    pub(super) fn copy(from: &Path, to: &Path) -> std::io::Result<()> {
+        let (from, to) = (c_path(from)?, c_path(to)?);
+        for name in ACL_XATTRS {
+            let name = CString::new(name).unwrap();
+            if let Some(value) = get(&from, &name)? {
+                set(&to, &name, &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn c_path(path: &Path) -> std::io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes()).map_err(std::io::Error::other)
+    }
+
+    /// Missing attributes and filesystems without them aren't an error.
+    fn is_unsupported(error: &std::io::Error) -> bool {
+        matches!(error.raw_os_error(), Some(libc::ENODATA | libc::EOPNOTSUPP))
+    }
+
+    fn get(path: &CString, name: &CString) -> std::io::Result<Option<Vec<u8>>> {
+        // SAFETY: both strings are null terminated and a null buffer only queries the size
+        let len = unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+        if len < 0 {
+            let error = std::io::Error::last_os_error();
+            return if is_unsupported(&error) { Ok(None) } else { Err(error) };
+        }
+        let mut value = vec![0_u8; len as usize];
+        // SAFETY: the buffer is valid for `value.len()` bytes
+        let len = unsafe {
+            libc::getxattr(path.as_ptr(), name.as_ptr(), value.as_mut_ptr().cast(), value.len())
+        };
+        if len < 0 {
+            let error = std::io::Error::last_os_error();
+            return if is_unsupported(&error) { Ok(None) } else { Err(error) };
+        }
+        value.truncate(len as usize);
+        Ok(Some(value))
+    }
+
+    fn set(path: &CString, name: &CString, value: &[u8]) -> std::io::Result<()> {
+        // SAFETY: both strings are null terminated and the value is valid for its length
+        let res = unsafe {
+            libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0)
+        };
+        if res < 0 {
+            let error = std::io::Error::last_os_error();
+            if !is_unsupported(&error) {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{DirCreation, ExecOptions, FileOpType, FileOperation, Fs, MemFs};
+
+    #[test]
+    fn sets_the_owner_of_dirs_and_preserves_it_when_copying() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a.jpg", "image");
+        let owner = Owner { uid: Some(1000), gid: Some(100) };
+        fs.set_owner(Path::new("/music/a.jpg"), owner).unwrap();
+
+        let media = Owner { uid: None, gid: Some(999) };
+        let dir = DirCreation { path: PathBuf::from("/out/A"), owner: Some(media), mode: None };
+        dir.execute(&fs).unwrap();
+        assert_eq!(fs.owner(Path::new("/out/A")), Some(media));
+
+        let op = FileOperation {
+            old_path: PathBuf::from("/music/a.jpg"),
+            new_path: PathBuf::from("/out/A/a.jpg"),
+            op_type: None,
+            replace: false,
+        };
+        let options = ExecOptions { op_type: FileOpType::Copy, ..Default::default() };
+        op.execute(&fs, &options, &mut |_, _| ()).unwrap();
+        assert_eq!(fs.owner(Path::new("/out/A/a.jpg")), None);
+
+        let options = ExecOptions { preserve_ownership: true, ..options };
+        op.execute(&fs, &options, &mut |_, _| ()).unwrap();
+        assert_eq!(fs.owner(Path::new("/out/A/a.jpg")), Some(owner));
+    }
+}
@@ -0,0 +1,448 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::changes::release_order;
+use crate::fs::valid_os_str_dots;
+use crate::{ChangeOptions, Changes, Fs, MusicIndex, SongOperation};
+
+/// Rewrites the entries of a playlist that reference moved or renamed files.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaylistUpdate {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub path: PathBuf,
+    pub entries: Vec<EntryUpdate>,
+}
+
+/// A single entry of a playlist, relative entries stay relative and absolute ones absolute.
+/// The entry text is written in the format of the playlist, e.g. as a URI inside of a
+/// `<location>` element of an XSPF playlist.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryUpdate {
+    /// The zero based line of the entry.
+    pub line: usize,
+    pub old: String,
+    pub new: String,
+    /// The file the entry referenced before it was moved.
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub old_path: PathBuf,
+}
+
+impl PlaylistUpdate {
+    /// Replaces the entries, fails without writing anything if the playlist was changed after
+    /// the update was planned.
+    pub fn execute(&self, fs: &impl Fs) -> Result<(), std::io::Error> {
+        let content = read_playlist(fs, &self.path)?;
+        let mut lines: Vec<String> = content.split_inclusive('\n').map(str::to_owned).collect();
+        for e in self.entries.iter() {
+            match lines.get_mut(e.line) {
+                Some(l) if l.contains(&e.old) => *l = l.replacen(&e.old, &e.new, 1),
+                _ => {
+                    let msg = format!("playlist entry '{}' changed", e.old);
+                    return Err(std::io::Error::other(msg));
+                }
+            }
+        }
+        fs.write(&self.path, lines.concat().as_bytes())
+    }
+}
+
+/// An m3u playlist of a release or of all releases of an artist, listing the songs at their new
+/// paths.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaylistCreation {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub path: PathBuf,
+    /// Absolute paths in playlist order, written relative to the playlist.
+    #[serde(serialize_with = "crate::serde_path::lossy_vec")]
+    pub songs: Vec<PathBuf>,
+}
+
+impl PlaylistCreation {
+    /// The m3u content, songs whose path isn't valid UTF-8 are left out.
+    pub fn content(&self) -> String {
+        let dir = self.path.parent().unwrap_or(Path::new(""));
+        let mut content = String::from("#EXTM3U\n");
+        for p in self.songs.iter() {
+            if let Some(p) = relative_path(dir, p).to_str() {
+                content.push_str(p);
+                content.push('\n');
+            }
+        }
+        content
+    }
+
+    /// Creates or replaces the playlist.
+    pub fn execute(&self, fs: &impl Fs) -> Result<(), std::io::Error> {
+        fs.write(&self.path, self.content().as_bytes())
+    }
+}
+
+impl Changes {
+    /// Plans playlists of releases and artists, using the paths of the songs after all changes.
+    /// Playlists that already exist with the same content are skipped.
+    pub(crate) fn generate_playlist_creations(
+        &mut self,
+        fs: &impl Fs,
+        index: &MusicIndex,
+        options: &ChangeOptions,
+    ) {
+        let ops: HashMap<&Path, &SongOperation> =
+            self.song_operations.iter().map(|o| (o.song.path.as_path(), o)).collect();
+        let mut songs: Vec<_> = index
+            .songs
+            .iter()
+            .map(|s| {
+                let op = ops.get(s.path.as_path());
+                let tag_update = op.and_then(|o| o.tag_update.as_ref());
+                let path = op.and_then(|o| o.new_path.as_deref()).unwrap_or(&s.path);
+                let names = tag_update
+                    .and_then(|t| t.release_artists.slice_value())
+                    .unwrap_or(&s.release_artists);
+                let release = tag_update.and_then(|t| t.release.str_value()).unwrap_or(&s.release);
+                (release_order(s, tag_update), names, release, path)
+            })
+            .collect();
+        songs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut creations = Vec::new();
+        let mut push = |dir: &Path, name: &str, paths: Vec<&Path>| {
+            let path = dir.join(format!("{}.m3u", valid_os_str_dots(name)));
+            if creations.iter().any(|c: &PlaylistCreation| c.path == path) {
+                return;
+            }
+            let songs = paths.into_iter().map(Path::to_owned).collect();
+            let creation = PlaylistCreation { path, songs };
+            if fs.read(&creation.path).ok() != Some(creation.content().into_bytes()) {
+                creations.push(creation);
+            }
+        };
+
+        for artist in songs.chunk_by(|a, b| a.1 == b.1) {
+            let mut release_dirs = Vec::new();
+            for release in artist.chunk_by(|a, b| a.2 == b.2) {
+                let paths: Vec<&Path> = release.iter().map(|s| s.3).collect();
+                let Some(dir) = common_dir(&paths) else { continue };
+                if options.release_playlists {
+                    push(&dir, release[0].2, paths);
+                }
+                release_dirs.push(dir);
+            }
+            // the artist playlist is placed next to the release dirs
+            let release_dirs: Vec<&Path> = release_dirs.iter().map(PathBuf::as_path).collect();
+            if let Some(dir) = common_dir(&release_dirs).filter(|_| options.artist_playlists) {
+                let name = artist[0].1.join(&options.artist_separators.name_join);
+                push(&dir, &name, artist.iter().map(|s| s.3).collect());
+            }
+        }
+        self.playlist_creations = creations;
+    }
+
+    /// Plans updates of the m3u, pls and xspf playlists in the index, for entries referencing
+    /// songs or files that are moved. Playlists that aren't valid UTF-8 are skipped.
+    pub(crate) fn generate_playlist_updates(&mut self, fs: &impl Fs, index: &MusicIndex) {
+        let song_paths = self
+            .song_operations
+            .iter()
+            .filter_map(|o| Some((o.song.path.as_path(), o.new_path.as_deref()?)));
+        let file_paths = self.file_operations.iter().map(|o| (&*o.old_path, &*o.new_path));
+        let new_paths: HashMap<&Path, &Path> = song_paths.chain(file_paths).collect();
+        if new_paths.is_empty() {
+            return;
+        }
+
+        let mut updates = Vec::new();
+        for playlist in index.playlists.iter() {
+            let Some(format) = Format::from_path(playlist) else { continue };
+            let Ok(content) = read_playlist(fs, playlist) else { continue };
+            let dir = playlist.parent().unwrap_or(Path::new(""));
+
+            let mut entries = Vec::new();
+            for (i, line) in content.split_inclusive('\n').enumerate() {
+                let Some((entry, entry_path)) = format.entry(line) else { continue };
+                let old_path = normalize(&dir.join(&entry_path));
+                let Some(new_path) = new_paths.get(old_path.as_path()) else { continue };
+
+                let new = match entry_path.is_absolute() {
+                    true => new_path.to_path_buf(),
+                    false => relative_path(dir, new_path),
+                };
+                let Some(new) = format.encode(&new) else { continue };
+                entries.push(EntryUpdate { line: i, old: entry.to_owned(), new, old_path });
+            }
+
+            if !entries.is_empty() {
+                updates.push(PlaylistUpdate { path: playlist.clone(), entries });
+            }
+        }
+        self.playlist_updates = updates;
+    }
+
+    /// Removes playlist entries whose file isn't moved anymore.
+    pub(crate) fn remove_stale_playlist_entries(&mut self) {
+        let moved: Vec<&Path> = self
+            .song_operations
+            .iter()
+            .filter(|o| o.new_path.is_some())
+            .map(|o| o.song.path.as_path())
+            .chain(self.file_operations.iter().map(|o| o.old_path.as_path()))
+            .collect();
+        for u in self.playlist_updates.iter_mut() {
+            u.entries.retain(|e| moved.contains(&e.old_path.as_path()));
+        }
+        self.playlist_updates.retain(|u| !u.entries.is_empty());
+    }
+}
+
+/// The local files a playlist references, `None` if it isn't a playlist or can't be read.
+pub(crate) fn playlist_entries(fs: &impl Fs, path: &Path) -> Option<Vec<PathBuf>> {
+    let format = Format::from_path(path)?;
+    let content = read_playlist(fs, path).ok()?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let entries = content.lines().filter_map(|l| format.entry(l));
+    Some(entries.map(|(_, p)| normalize(&dir.join(p))).collect())
+}
+
+fn read_playlist(fs: &impl Fs, path: &Path) -> std::io::Result<String> {
+    let data = fs.read(path)?;
+    String::from_utf8(data).map_err(std::io::Error::other)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// One path per line, lines starting with `#` are comments.
+    M3u,
+    /// An ini file with `File1=path` entries.
+    Pls,
+    /// XML with a `<location>` URI per track, which has to be on a single line.
+    Xspf,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "m3u" | "m3u8" => Some(Self::M3u),
+            "pls" => Some(Self::Pls),
+            "xspf" => Some(Self::Xspf),
+            _ => None,
+        }
+    }
+
+    /// The text of the entry on a line and the local path it references. URLs of other schemes
+    /// are ignored.
+    fn entry<'a>(&self, line: &'a str) -> Option<(&'a str, PathBuf)> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        let entry = match self {
+            Self::M3u => line.trim_start(),
+            Self::Pls => {
+                let (key, value) = line.split_once('=')?;
+                let index = key.trim().strip_prefix("File")?;
+                if index.parse::<u32>().is_err() {
+                    return None;
+                }
+                value.trim()
+            }
+            Self::Xspf => {
+                let start = line.find("<location>")? + "<location>".len();
+                let len = line[start..].find("</location>")?;
+                let uri = line[start..start + len].trim();
+                let uri = unescape_xml(uri);
+                let path = uri.strip_prefix("file://").unwrap_or(&uri);
+                if path.contains("://") {
+                    return None;
+                }
+                let path = percent_decode(path)?;
+                return Some((&line[start..start + len], PathBuf::from(path)));
+            }
+        };
+        if entry.is_empty() || entry.starts_with('#') || entry.contains("://") {
+            return None;
+        }
+        Some((entry, PathBuf::from(entry)))
+    }
+
+    fn encode(&self, path: &Path) -> Option<String> {
+        let path = path.to_str()?;
+        match self {
+            Self::M3u | Self::Pls => Some(path.to_owned()),
+            Self::Xspf => {
+                let uri = percent_encode(path);
+                match path.starts_with('/') {
+                    true => Some(format!("file://{uri}")),
+                    false => Some(uri),
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        match b {
+            b'%' => {
+                let hex = [iter.next()?, iter.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Encodes everything except unreserved characters and `/`, so the result is also valid XML.
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(b as char)
+            }
+            b => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// The deepest directory containing all paths, excluding the paths themselves.
+pub(crate) fn common_dir(paths: &[&Path]) -> Option<PathBuf> {
+    let mut dir = paths.first()?.parent()?.to_path_buf();
+    for p in paths.iter().skip(1) {
+        while !p.starts_with(&dir) {
+            if !dir.pop() {
+                return None;
+            }
+        }
+    }
+    Some(dir)
+}
+
+/// The path of `to` relative to the directory `from`, both have to be absolute.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(to.iter()).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    relative.extend(&to[common..]);
+    relative
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{ChangeOptions, Checks, MemFs, Song};
+
+    /// Moves `/music/a.mp3` to `/music/Foo/Bar/01 - Foo - One.mp3` and returns the updated
+    /// playlist.
+    fn rewrite(path: &str, playlist: &str) -> String {
+        let fs = MemFs::new();
+        fs.add_file("/music/a.mp3", "a");
+        fs.add_file(path, playlist);
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![Song {
+            path: PathBuf::from("/music/a.mp3"),
+            track_number: Some(1),
+            release_artists: Arc::from(["Foo".to_owned()]),
+            artists: Arc::from(["Foo".to_owned()]),
+            release: Arc::from("Bar"),
+            title: "One".to_owned(),
+            ..Default::default()
+        }];
+        index.playlists = vec![PathBuf::from(path)];
+        let options = ChangeOptions { update_playlists: true, ..PathBuf::from("/music").into() };
+
+        let changes = Changes::generate(&fs, Checks::from(&index), &options);
+        for u in changes.playlist_updates.iter() {
+            u.execute(&fs).unwrap();
+        }
+        String::from_utf8(fs.file(Path::new(path)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn rewrites_relative_and_absolute_entries() {
+        let playlist = "#EXTM3U\r\n../a.mp3\r\n/music/a.mp3\nhttp://radio/a.mp3\n../b.mp3\n";
+        assert_eq!(
+            rewrite("/music/lists/mix.m3u", playlist),
+            "#EXTM3U\r\n../Foo/Bar/01 - Foo - One.mp3\r\n/music/Foo/Bar/01 - Foo - One.mp3\n\
+            http://radio/a.mp3\n../b.mp3\n",
+        );
+    }
+
+    #[test]
+    fn rewrites_pls_and_xspf_entries() {
+        let playlist = "[playlist]\nFile1=a.mp3\nTitle1=One\nNumberOfEntries=1\n";
+        assert_eq!(
+            rewrite("/music/mix.pls", playlist),
+            "[playlist]\nFile1=Foo/Bar/01 - Foo - One.mp3\nTitle1=One\nNumberOfEntries=1\n",
+        );
+
+        let playlist = "<track>\n  <location>file:///music/a.mp3</location>\n</track>\n";
+        assert_eq!(
+            rewrite("/music/mix.xspf", playlist),
+            "<track>\n  <location>file:///music/Foo/Bar/01%20-%20Foo%20-%20One.mp3</location>\n\
+            </track>\n",
+        );
+    }
+
+    #[test]
+    fn creates_release_and_artist_playlists() {
+        let fs = MemFs::new();
+        let song = |path: &str, track, title: &str| Song {
+            path: PathBuf::from(path),
+            track_number: Some(track),
+            release_artists: Arc::from(["Foo".to_owned()]),
+            artists: Arc::from(["Foo".to_owned()]),
+            release: Arc::from("Bar"),
+            title: title.to_owned(),
+            ..Default::default()
+        };
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![song("/music/b.mp3", 2, "Two"), song("/music/a.mp3", 1, "One")];
+        let options = ChangeOptions {
+            release_playlists: true,
+            artist_playlists: true,
+            ..PathBuf::from("/music").into()
+        };
+
+        let changes = Changes::generate(&fs, Checks::from(&index), &options);
+
+        let paths: Vec<_> = changes.playlist_creations.iter().map(|c| c.path.as_path()).collect();
+        assert_eq!(paths, [Path::new("/music/Foo/Bar/Bar.m3u"), Path::new("/music/Foo/Foo.m3u")]);
+        assert_eq!(
+            changes.playlist_creations[1].content(),
+            "#EXTM3U\nBar/01 - Foo - One.mp3\nBar/02 - Foo - Two.mp3\n",
+        );
+    }
+}
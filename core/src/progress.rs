@@ -0,0 +1,366 @@
+use std::path::Path;
+
+use tracing::{debug, error, info, info_span, warn};
+
+use crate::{
+    ChangedOnDisk, Changes, Cleanup, DirCreation, DirDeletion, ExecOptions, FileDeletion,
+    FileOperation, Fs, Mirror, PlaylistCreation, PlaylistUpdate, Quarantine, SongOperation,
+};
+
+/// The phases of executing [`Changes`] and a [`Cleanup`], in the order they are run. A [`Mirror`]
+/// reuses the dir creation, file operation and deletion phases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Phase {
+    DirCreations,
+    SongOperations,
+    FileOperations,
+    PlaylistUpdates,
+    PlaylistCreations,
+    Quarantines,
+    FileDeletions,
+    DirDeletions,
+}
+
+/// An operation that was executed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Item<'a> {
+    DirCreation(&'a DirCreation),
+    SongOperation(&'a SongOperation),
+    FileOperation(&'a FileOperation),
+    PlaylistUpdate(&'a PlaylistUpdate),
+    PlaylistCreation(&'a PlaylistCreation),
+    Quarantine(&'a Quarantine),
+    FileDeletion(&'a FileDeletion),
+    DirDeletion(&'a DirDeletion),
+}
+
+impl Item<'_> {
+    /// The path the operation was executed on, for song and file operations this is the old path.
+    pub fn path(&self) -> &Path {
+        match self {
+            Item::DirCreation(d) => &d.path,
+            Item::SongOperation(o) => &o.song.path,
+            Item::FileOperation(o) => &o.old_path,
+            Item::PlaylistUpdate(u) => &u.path,
+            Item::PlaylistCreation(c) => &c.path,
+            Item::Quarantine(q) => &q.path,
+            Item::FileDeletion(d) => &d.path,
+            Item::DirDeletion(d) => &d.path,
+        }
+    }
+}
+
+/// The number of operations of a phase. Operations that were skipped because execution was
+/// cancelled, stopped after a failure or the filesystem changed underneath them are neither
+/// processed nor failed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PhaseTotals {
+    pub total: usize,
+    pub processed: usize,
+    pub failed: usize,
+}
+
+impl PhaseTotals {
+    pub fn skipped(&self) -> usize {
+        self.total - self.processed - self.failed
+    }
+}
+
+/// Receives progress while executing [`Changes`] and a [`Cleanup`], so front ends don't need to
+/// handle the callbacks of each `execute_*` method. Every method does nothing by default.
+pub trait Progress {
+    /// A phase is started even if it has no operations.
+    fn phase_started(&mut self, _phase: Phase, _total: usize) {}
+    /// Bytes copied of a file, only reported when copying.
+    fn bytes_copied(&mut self, _path: &Path, _copied: u64, _total: u64) {}
+    fn item_processed(&mut self, _item: Item) {}
+    fn item_failed(&mut self, _item: Item, _error: &dyn std::error::Error) {}
+    /// A song or file operation wasn't executed, since its file or destination changed after the
+    /// changes were planned.
+    fn item_skipped(&mut self, _item: Item, _change: &ChangedOnDisk) {}
+    fn phase_finished(&mut self, _phase: Phase, _totals: PhaseTotals) {}
+}
+
+/// Counts the operations of a phase and forwards the result to the [`Progress`].
+fn run_phase<P: Progress>(
+    progress: &mut P,
+    phase: Phase,
+    total: usize,
+    execute: impl FnOnce(&mut P, &mut PhaseTotals),
+) -> PhaseTotals {
+    let mut totals = PhaseTotals { total, ..Default::default() };
+    progress.phase_started(phase, total);
+    execute(progress, &mut totals);
+    info!(?phase, total, processed = totals.processed, failed = totals.failed, "phase finished");
+    progress.phase_finished(phase, totals);
+    totals
+}
+
+fn report(
+    progress: &mut impl Progress,
+    totals: &mut PhaseTotals,
+    item: Item,
+    result: Result<(), impl Into<Box<dyn std::error::Error>>>,
+) {
+    match result {
+        Ok(()) => {
+            debug!(path = %item.path().display(), "processed");
+            totals.processed += 1;
+            progress.item_processed(item);
+        }
+        Err(e) => {
+            let e = e.into();
+            if let Some(change) = e.downcast_ref::<ChangedOnDisk>() {
+                warn!(path = %item.path().display(), %change, "skipped");
+                progress.item_skipped(item, change);
+                return;
+            }
+            error!(path = %item.path().display(), error = %e, "failed");
+            totals.failed += 1;
+            progress.item_failed(item, &*e);
+        }
+    }
+}
+
+/// Whether the next phase shouldn't be started.
+fn stop(options: &ExecOptions, totals: &PhaseTotals) -> bool {
+    options.cancel.is_cancelled() || (options.fail_fast && totals.failed > 0)
+}
+
+impl Changes {
+    /// Executes the dir creations, song operations, file operations, playlist updates, playlist
+    /// creations and quarantines in that order.
+    pub fn execute(&self, fs: &impl Fs, options: &ExecOptions, progress: &mut impl Progress) {
+        let _span = info_span!("write").entered();
+        let mtimes = self.dir_mtimes(fs, options);
+        self.execute_phases(fs, options, progress);
+        Self::restore_dir_mtimes(fs, mtimes);
+    }
+
+    fn execute_phases(&self, fs: &impl Fs, options: &ExecOptions, progress: &mut impl Progress) {
+        let totals =
+            run_phase(progress, Phase::DirCreations, self.dir_creations.len(), |p, totals| {
+                self.execute_dir_creations(fs, options, &mut |d, r| {
+                    report(p, totals, Item::DirCreation(d), r)
+                });
+            });
+        if stop(options, &totals) {
+            return;
+        }
+
+        let totals =
+            run_phase(progress, Phase::SongOperations, self.song_operations.len(), |p, totals| {
+                // the progress is borrowed by both callbacks
+                let p = std::cell::RefCell::new(p);
+                self.execute_song_operations(
+                    fs,
+                    options,
+                    &mut |path, copied, total| p.borrow_mut().bytes_copied(path, copied, total),
+                    &mut |o, r| report(&mut **p.borrow_mut(), totals, Item::SongOperation(o), r),
+                );
+            });
+        if stop(options, &totals) {
+            return;
+        }
+
+        let totals =
+            run_phase(progress, Phase::FileOperations, self.file_operations.len(), |p, totals| {
+                let p = std::cell::RefCell::new(p);
+                self.execute_file_operations(
+                    fs,
+                    options,
+                    &mut |path, copied, total| p.borrow_mut().bytes_copied(path, copied, total),
+                    &mut |o, r| report(&mut **p.borrow_mut(), totals, Item::FileOperation(o), r),
+                );
+            });
+        if stop(options, &totals) {
+            return;
+        }
+
+        let totals = run_phase(
+            progress,
+            Phase::PlaylistUpdates,
+            self.playlist_updates.len(),
+            |p, totals| {
+                self.execute_playlist_updates(fs, options, &mut |u, r| {
+                    report(p, totals, Item::PlaylistUpdate(u), r)
+                });
+            },
+        );
+        if stop(options, &totals) {
+            return;
+        }
+
+        let total = self.playlist_creations.len();
+        let totals = run_phase(progress, Phase::PlaylistCreations, total, |p, totals| {
+            self.execute_playlist_creations(fs, options, &mut |c, r| {
+                report(p, totals, Item::PlaylistCreation(c), r)
+            });
+        });
+        if stop(options, &totals) {
+            return;
+        }
+
+        run_phase(progress, Phase::Quarantines, self.quarantines.len(), |p, totals| {
+            self.execute_quarantines(fs, options, &mut |q, r| {
+                report(p, totals, Item::Quarantine(q), r)
+            });
+        });
+    }
+}
+
+impl Mirror {
+    /// Executes the dir creations, copies and transcodes, file deletions and dir deletions in
+    /// that order, the copies and transcodes are reported as file operations.
+    pub fn execute(&self, fs: &impl Fs, options: &ExecOptions, progress: &mut impl Progress) {
+        let _span = info_span!("mirror").entered();
+        let totals =
+            run_phase(progress, Phase::DirCreations, self.dir_creations.len(), |p, totals| {
+                self.execute_dir_creations(fs, options, &mut |d, r| {
+                    report(p, totals, Item::DirCreation(d), r)
+                });
+            });
+        if stop(options, &totals) {
+            return;
+        }
+
+        let num_copies = self.copies.len() + self.transcodes.len();
+        let totals = run_phase(progress, Phase::FileOperations, num_copies, |p, totals| {
+            let p = std::cell::RefCell::new(p);
+            self.execute_copies(
+                fs,
+                options,
+                &mut |path, copied, total| p.borrow_mut().bytes_copied(path, copied, total),
+                &mut |o, r| report(&mut **p.borrow_mut(), totals, Item::FileOperation(o), r),
+            );
+            if !stop(options, totals) {
+                self.execute_transcodes(options, &mut |o, r| {
+                    report(&mut **p.borrow_mut(), totals, Item::FileOperation(o), r)
+                });
+            }
+        });
+        if stop(options, &totals) {
+            return;
+        }
+
+        let totals =
+            run_phase(progress, Phase::FileDeletions, self.file_deletions.len(), |p, totals| {
+                self.execute_file_deletions(fs, options, &mut |d, r| {
+                    report(p, totals, Item::FileDeletion(d), r)
+                });
+            });
+        if stop(options, &totals) {
+            return;
+        }
+
+        run_phase(progress, Phase::DirDeletions, self.dir_deletions.len(), |p, totals| {
+            self.execute_dir_deletions(fs, options, &mut |d, r| {
+                report(p, totals, Item::DirDeletion(d), r)
+            });
+        });
+    }
+}
+
+impl Cleanup {
+    /// Executes the file deletions followed by the dir deletions.
+    pub fn execute(&self, fs: &impl Fs, options: &ExecOptions, progress: &mut impl Progress) {
+        let _span = info_span!("cleanup").entered();
+        let totals =
+            run_phase(progress, Phase::FileDeletions, self.file_deletions.len(), |p, totals| {
+                self.execute_file_deletions(fs, options, &mut |d, r| {
+                    report(p, totals, Item::FileDeletion(d), r)
+                });
+            });
+        if stop(options, &totals) {
+            return;
+        }
+
+        run_phase(progress, Phase::DirDeletions, self.dir_deletions.len(), |p, totals| {
+            self.execute_dir_deletions(fs, options, &mut |d, r| {
+                report(p, totals, Item::DirDeletion(d), r)
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::{MemFs, Song, SongOperation};
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl Progress for Recorder {
+        fn phase_started(&mut self, phase: Phase, total: usize) {
+            self.events.push(format!("start {phase:?} {total}"));
+        }
+
+        fn item_processed(&mut self, item: Item) {
+            self.events.push(format!("ok {}", item.path().display()));
+        }
+
+        fn item_failed(&mut self, item: Item, _error: &dyn std::error::Error) {
+            self.events.push(format!("err {}", item.path().display()));
+        }
+
+        fn phase_finished(&mut self, phase: Phase, totals: PhaseTotals) {
+            self.events.push(format!("finish {phase:?} {}", totals.skipped()));
+        }
+    }
+
+    #[test]
+    fn stops_after_failed_phase() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a/Thumbs.db", "");
+        let cleanup = Cleanup {
+            file_deletions: vec![
+                FileDeletion { path: PathBuf::from("/music/missing") },
+                FileDeletion { path: PathBuf::from("/music/a/Thumbs.db") },
+            ],
+            dir_deletions: vec![DirDeletion { path: PathBuf::from("/music/a") }],
+            ..Default::default()
+        };
+        let options = ExecOptions { fail_fast: true, ..Default::default() };
+
+        let mut recorder = Recorder::default();
+        cleanup.execute(&fs, &options, &mut recorder);
+
+        assert_eq!(
+            recorder.events,
+            ["start FileDeletions 2", "err /music/missing", "finish FileDeletions 1"]
+        );
+    }
+
+    #[test]
+    fn preserves_mtimes_of_dirs_with_only_renames() {
+        let fs = MemFs::new();
+        fs.add_file("/music/Foo/Bar/a.mp3", "a");
+        fs.add_file("/music/Foo/Baz/b.mp3", "b");
+        fs.add_file("/music/c.mp3", "c");
+        let op = |old: &str, new: &str| SongOperation {
+            new_path: Some(PathBuf::from(new)),
+            ..SongOperation::new(Song { path: PathBuf::from(old), ..Default::default() })
+        };
+        let changes = Changes {
+            song_operations: vec![
+                op("/music/Foo/Bar/a.mp3", "/music/Foo/Bar/01 - Foo - One.mp3"),
+                op("/music/Foo/Baz/b.mp3", "/music/Foo/Baz/01 - Foo - Two.mp3"),
+                op("/music/c.mp3", "/music/Foo/Baz/02 - Foo - Three.mp3"),
+            ],
+            ..Default::default()
+        };
+        let options = ExecOptions { preserve_dir_mtimes: true, ..Default::default() };
+
+        changes.execute(&fs, &options, &mut Recorder::default());
+
+        let modified = |p: &str| fs.dir_modified(Path::new(p)).unwrap();
+        assert_eq!(modified("/music/Foo/Bar"), SystemTime::UNIX_EPOCH);
+        assert_ne!(modified("/music/Foo/Baz"), SystemTime::UNIX_EPOCH);
+    }
+}
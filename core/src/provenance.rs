@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::backup::format_time;
+use crate::{Changes, Value};
+
+/// The custom field the [`Provenance`] is stored in, see [`Changes::add_provenance_tags`].
+pub const PROVENANCE_FIELD: &str = "MUSIC_ORGANIZER_PROVENANCE";
+
+/// Which version of the organizer changed a file, when and where the file was before.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub version: String,
+    /// In UTC, e.g. `2024-05-17T08:30:00Z`.
+    pub date: String,
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub original_path: PathBuf,
+    /// Only set in manifests, since a tag is stored in the file at this path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "crate::serde_path::lossy_option")]
+    pub new_path: Option<PathBuf>,
+}
+
+impl Changes {
+    /// The provenance of every song and file that is moved, renamed or retagged.
+    pub fn provenance(&self, version: &str, time: SystemTime) -> Vec<Provenance> {
+        let date = format!("{}Z", format_time(time, ':'));
+        let provenance = |original_path: &PathBuf, new_path: Option<&PathBuf>| Provenance {
+            version: version.to_owned(),
+            date: date.clone(),
+            original_path: original_path.clone(),
+            new_path: new_path.cloned(),
+        };
+        let songs = (self.song_operations.iter())
+            .filter(|o| o.new_path.is_some() || o.tag_update.is_some())
+            .map(|o| provenance(&o.song.path, Some(o.new_path.as_ref().unwrap_or(&o.song.path))));
+        let files = self.file_operations.iter().map(|o| provenance(&o.old_path, Some(&o.new_path)));
+        songs.chain(files).collect()
+    }
+
+    /// Stores the [`Provenance`] as json in the [`PROVENANCE_FIELD`] of every song that is
+    /// moved, renamed or retagged, replacing the provenance of earlier runs. Songs excluded from
+    /// retagging are skipped, see [`Checks::retag_excluded`](crate::Checks::retag_excluded).
+    pub fn add_provenance_tags(
+        &mut self,
+        version: &str,
+        time: SystemTime,
+        retag_excluded: &HashSet<&Path>,
+    ) {
+        let provenance = self.provenance(version, time);
+        let ops = self
+            .song_operations
+            .iter_mut()
+            .filter(|o| o.new_path.is_some() || o.tag_update.is_some());
+        for (o, mut p) in ops.zip(provenance) {
+            if retag_excluded.contains(o.song.path.as_path()) {
+                continue;
+            }
+            p.new_path = None;
+            let value = serde_json::to_string(&p).expect("serializing provenance");
+            o.add_reason("provenance");
+            let tag_update = o.tag_update.get_or_insert_with(Default::default);
+            tag_update.extra.insert(PROVENANCE_FIELD.to_owned(), Value::Update(value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+    use crate::{FileOperation, Song, SongOperation};
+
+    #[test]
+    fn tags_moved_and_retagged_songs() {
+        let song = |path: &str| Song { path: PathBuf::from(path), ..Default::default() };
+        let mut changes = Changes {
+            song_operations: vec![
+                SongOperation {
+                    new_path: Some(PathBuf::from("/out/a.mp3")),
+                    ..SongOperation::new(song("/music/a.mp3"))
+                },
+                SongOperation::new(song("/music/b.mp3")),
+            ],
+            file_operations: vec![FileOperation {
+                old_path: PathBuf::from("/music/cover.jpg"),
+                new_path: PathBuf::from("/out/cover.jpg"),
+                op_type: None,
+                replace: false,
+            }],
+            ..Default::default()
+        };
+        let time = UNIX_EPOCH + Duration::from_secs(1715934600);
+
+        let provenance = changes.provenance("0.2.0", time);
+        assert_eq!(provenance.len(), 2);
+        assert_eq!(provenance[0].date, "2024-05-17T08:30:00Z");
+        assert_eq!(provenance[1].new_path.as_deref(), Some(Path::new("/out/cover.jpg")));
+
+        changes.add_provenance_tags("0.2.0", time, &HashSet::new());
+        let tag = &changes.song_operations[0].tag_update.as_ref().unwrap().extra[PROVENANCE_FIELD];
+        let expected =
+            r#"{"version":"0.2.0","date":"2024-05-17T08:30:00Z","original_path":"/music/a.mp3"}"#;
+        assert_eq!(tag, &Value::Update(expected.to_owned()));
+        assert_eq!(changes.song_operations[1].tag_update, None);
+    }
+}
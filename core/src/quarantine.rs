@@ -0,0 +1,257 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::meta::is_corrupt;
+use crate::{
+    ChangeOptions, Changes, Duplicates, ExecOptions, FileOpType, FileOperation, Fs, MusicIndex,
+    VirtualTree,
+};
+
+/// The dir inside of the output dir problem files are set aside in.
+pub const QUARANTINE_DIR_NAME: &str = "quarantine";
+
+/// Why a file is quarantined, each reason has its own subdir of the quarantine dir.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuarantineReason {
+    /// An unknown song that can't be parsed at all, e.g. because it's truncated.
+    Corrupt,
+    /// A copy of another file with identical contents, see [`Duplicates`].
+    Duplicate,
+    /// A file that would overwrite another one at its new path.
+    Collision,
+}
+
+impl QuarantineReason {
+    pub fn dir_name(&self) -> &'static str {
+        match self {
+            Self::Corrupt => "corrupt",
+            Self::Duplicate => "duplicate",
+            Self::Collision => "collision",
+        }
+    }
+}
+
+impl std::fmt::Display for QuarantineReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.dir_name())
+    }
+}
+
+/// The file name with ` (i)` inserted before the extension, keeping the bytes of names that
+/// aren't valid UTF-8.
+fn numbered_file_name(path: &Path, i: usize) -> OsString {
+    let mut name = OsString::from(path.file_stem().unwrap_or_default());
+    name.push(format!(" ({i})"));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    name
+}
+
+/// Moves a problem file into the subdir of its reason inside of the quarantine dir, instead of
+/// organizing it. A sidecar text file next to it explains why and where it came from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quarantine {
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub path: PathBuf,
+    #[serde(serialize_with = "crate::serde_path::lossy")]
+    pub new_path: PathBuf,
+    pub reason: QuarantineReason,
+    /// Details written to the sidecar, e.g. the file a collision would have overwritten.
+    pub message: String,
+}
+
+impl Quarantine {
+    /// The new path with `.txt` appended.
+    pub fn sidecar_path(&self) -> PathBuf {
+        let mut name = OsString::from(self.new_path.file_name().unwrap_or_default());
+        name.push(".txt");
+        self.new_path.with_file_name(name)
+    }
+
+    pub fn sidecar(&self) -> String {
+        format!("reason: {}\npath: {}\n\n{}\n", self.reason, self.path.display(), self.message)
+    }
+
+    /// Moves or copies the file according to the options, then writes the sidecar.
+    pub fn execute(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let op = FileOperation {
+            old_path: self.path.clone(),
+            new_path: self.new_path.clone(),
+            op_type: None,
+            replace: false,
+        };
+        op.execute(fs, options, &mut |_, _| ())?;
+        fs.write(&self.sidecar_path(), self.sidecar().as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Changes {
+    /// Plans quarantining a file instead of organizing it, its song or file operation is
+    /// removed. The file keeps its name unless another file with that name is quarantined for the
+    /// same reason, then a number is appended.
+    pub fn quarantine(
+        &mut self,
+        fs: &impl Fs,
+        options: &ChangeOptions,
+        path: &Path,
+        reason: QuarantineReason,
+        message: impl Into<String>,
+    ) {
+        if self.quarantines.iter().any(|q| q.path == path) {
+            return;
+        }
+        self.retain_song_operations(|o| o.song.path != path);
+        self.retain_file_operations(|o| o.old_path != path);
+
+        let dir = options.output_dir.join(QUARANTINE_DIR_NAME).join(reason.dir_name());
+        self.dir_creation(fs, options, &dir);
+        let new_path = (1..)
+            .map(|i| match i {
+                1 => dir.join(path.file_name().unwrap_or_default()),
+                i => dir.join(numbered_file_name(path, i)),
+            })
+            .find(|p| !fs.exists(p) && !self.quarantines.iter().any(|q| q.new_path == *p))
+            .unwrap();
+
+        let message = message.into();
+        debug!(path = %path.display(), new_path = %new_path.display(), %reason, message, "quarantine");
+        self.quarantines.push(Quarantine { path: path.to_owned(), new_path, reason, message });
+    }
+
+    /// Quarantines the unknown songs of the index that can't be parsed, instead of moving them
+    /// into the unknown dir. Reads the files regardless of `fs`.
+    pub(crate) fn quarantine_corrupt(
+        &mut self,
+        fs: &impl Fs,
+        index: &MusicIndex,
+        options: &ChangeOptions,
+    ) {
+        for p in index.unknown_songs().filter(|p| is_corrupt(p)) {
+            let message = "The file can't be parsed, it's probably truncated or not a song.";
+            self.quarantine(fs, options, p, QuarantineReason::Corrupt, message);
+        }
+    }
+
+    /// Quarantines the files that would overwrite another file, the first file keeps its path.
+    pub(crate) fn quarantine_collisions(
+        &mut self,
+        fs: &impl Fs,
+        index: &MusicIndex,
+        options: &ChangeOptions,
+    ) {
+        let simulation = self.simulate(&VirtualTree::from_index(index), FileOpType::Move);
+        for c in simulation.collisions {
+            let is_moved = self.song_operations.iter().any(|o| o.song.path == c.by)
+                || self.file_operations.iter().any(|o| o.old_path == c.by);
+            if !is_moved {
+                continue;
+            }
+            let message =
+                format!("Would overwrite {} at {}.", c.overwritten.display(), c.path.display());
+            self.quarantine(fs, options, &c.by, QuarantineReason::Collision, message);
+        }
+    }
+
+    /// Quarantines the copies of the duplicates instead of deleting them.
+    pub fn quarantine_duplicates(
+        &mut self,
+        fs: &impl Fs,
+        duplicates: &Duplicates,
+        options: &ChangeOptions,
+    ) {
+        for g in duplicates.groups.iter() {
+            for c in g.copies.iter() {
+                let message = format!("Identical to {}.", g.original.display());
+                self.quarantine(fs, options, c, QuarantineReason::Duplicate, message);
+            }
+        }
+    }
+
+    pub fn execute_quarantines(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        f: &mut impl FnMut(&Quarantine, Result<(), Box<dyn std::error::Error>>),
+    ) {
+        for q in self.quarantines.iter() {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let r = q.execute(fs, options);
+            let failed = r.is_err();
+            f(q, r);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{song, SongTags};
+    use crate::{Checks, MemFs};
+
+    #[test]
+    fn quarantines_colliding_songs() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a/1.mp3", "one");
+        fs.add_file("/music/b/1.mp3", "other");
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/a/1.mp3", "Bar", "Foo").with_track(1, None).with_title("One"),
+            song("/music/b/1.mp3", "Bar", "Foo").with_track(1, None).with_title("One"),
+        ];
+
+        let options =
+            ChangeOptions { quarantine: true, ..ChangeOptions::from(PathBuf::from("/out")) };
+        let changes = Changes::generate(&fs, Checks::from(&index), &options);
+
+        assert_eq!(changes.song_operations.len(), 1);
+        assert_eq!(changes.quarantines.len(), 1);
+        let q = &changes.quarantines[0];
+        assert_eq!(q.path, Path::new("/music/b/1.mp3"));
+        assert_eq!(q.new_path, Path::new("/out/quarantine/collision/1.mp3"));
+        assert_eq!(q.reason, QuarantineReason::Collision);
+
+        changes.execute(&fs, &ExecOptions::default(), &mut Quiet);
+        assert_eq!(fs.read(Path::new("/out/quarantine/collision/1.mp3")).unwrap(), b"other");
+        let sidecar = fs.read(Path::new("/out/quarantine/collision/1.mp3.txt")).unwrap();
+        assert!(String::from_utf8(sidecar).unwrap().starts_with("reason: collision\n"));
+    }
+
+    #[test]
+    fn numbers_files_with_the_same_name() {
+        let fs = MemFs::new();
+        fs.add_file("/out/quarantine/duplicate/1.mp3", "");
+        let mut changes = Changes::default();
+        let options = ChangeOptions::from(PathBuf::from("/out"));
+        for p in ["/music/a/1.mp3", "/music/b/1.mp3"] {
+            changes.quarantine(&fs, &options, Path::new(p), QuarantineReason::Duplicate, "");
+        }
+
+        let paths: Vec<&Path> = changes.quarantines.iter().map(|q| q.new_path.as_path()).collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("/out/quarantine/duplicate/1 (2).mp3"),
+                Path::new("/out/quarantine/duplicate/1 (3).mp3"),
+            ]
+        );
+    }
+
+    struct Quiet;
+    impl crate::Progress for Quiet {}
+}
@@ -0,0 +1,193 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{ArtistDistinction, Checks, ReleaseArtists, TagUpdate, Value};
+
+/// A question asked by one of the checks, which owns its data so it can be serialized and
+/// answered by any front end, see [`Checks::resolve_questions`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Question {
+    /// Two sets of release artists whose names only differ in case, see
+    /// [`Checks::check_inconsitent_release_artists`]. The proposals are the names of `a` and `b`.
+    InconsistentArtists {
+        a: Vec<String>,
+        b: Vec<String>,
+        /// Why the artists are probably distinct, if there is a hint.
+        distinction: Option<ArtistDistinction>,
+        proposals: Vec<Vec<String>>,
+    },
+    /// Track or disc numbers of a release that are 0 or exceed their total, see
+    /// [`Checks::check_invalid_numbers`]. The only proposal are the corrections.
+    InvalidNumbers { artists: Vec<String>, release: String, corrections: Vec<(PathBuf, TagUpdate)> },
+    /// A release name with an edition suffix in a non-standard format, see
+    /// [`Checks::check_release_suffixes`]. The proposals are the standardized name and the name
+    /// without the suffix.
+    ReleaseSuffix { artists: Vec<String>, release: String, suffix: String, proposals: Vec<String> },
+    /// A release whose songs are tagged with different release artists, see
+    /// [`Checks::check_split_releases`]. The proposals are the release artists of the parts.
+    SplitRelease {
+        release: String,
+        /// The release artists and the number of their songs.
+        parts: Vec<(Vec<String>, usize)>,
+        proposals: Vec<Vec<String>>,
+    },
+}
+
+/// The answer to a [`Question`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum Resolution {
+    /// Leaves the songs unchanged, valid for every question.
+    Skip,
+    /// Applies the proposal with this index.
+    Proposal(usize),
+    /// Sets the release artists of an [`Question::InconsistentArtists`] or a
+    /// [`Question::SplitRelease`].
+    Artists(Vec<String>),
+    /// Removes the release artists of an [`Question::InconsistentArtists`], so the artists of
+    /// the songs are used instead.
+    RemoveArtists,
+    /// Sets the release name of a [`Question::ReleaseSuffix`].
+    Release(String),
+}
+
+/// Answers the questions of the checks, e.g. by asking the user or looking up an answers file.
+pub trait Resolver {
+    fn resolve(&mut self, question: &Question) -> Resolution;
+}
+
+impl<F: FnMut(&Question) -> Resolution> Resolver for F {
+    fn resolve(&mut self, question: &Question) -> Resolution {
+        self(question)
+    }
+}
+
+fn invalid(question: &Question, resolution: &Resolution) {
+    warn!(?question, ?resolution, "resolution doesn't answer the question");
+}
+
+impl Checks<'_> {
+    /// Runs all checks that ask questions, in the same order the CLI does, and applies the
+    /// resolutions. Resolutions that don't fit their question leave the songs unchanged.
+    pub fn resolve_questions(&mut self, resolver: &mut impl Resolver) {
+        let names = |a: &ReleaseArtists| a.names.to_vec();
+
+        self.check_inconsitent_release_artists(&mut |a, b| {
+            let proposals = vec![names(a), names(b)];
+            let question = Question::InconsistentArtists {
+                a: names(a),
+                b: names(b),
+                distinction: a.distinction(b),
+                proposals: proposals.clone(),
+            };
+            match resolver.resolve(&question) {
+                Resolution::Skip => Value::Unchanged,
+                Resolution::Proposal(i) if i < proposals.len() => {
+                    Value::Update(proposals[i].clone())
+                }
+                Resolution::Artists(names) => Value::Update(names),
+                Resolution::RemoveArtists => Value::Remove,
+                r => {
+                    invalid(&question, &r);
+                    Value::Unchanged
+                }
+            }
+        });
+
+        self.check_invalid_numbers(&mut |a, r, corrections| {
+            let question = Question::InvalidNumbers {
+                artists: names(a),
+                release: r.name.to_string(),
+                corrections: (corrections.iter())
+                    .map(|(s, c)| (s.path.clone(), c.clone()))
+                    .collect(),
+            };
+            match resolver.resolve(&question) {
+                Resolution::Skip => false,
+                Resolution::Proposal(0) => true,
+                r => {
+                    invalid(&question, &r);
+                    false
+                }
+            }
+        });
+
+        self.check_split_releases(&mut |s| {
+            let proposals: Vec<Vec<String>> = s.parts.iter().map(|(n, _)| n.to_vec()).collect();
+            let question = Question::SplitRelease {
+                release: s.release.to_owned(),
+                parts: s.parts.iter().map(|(n, songs)| (n.to_vec(), songs.len())).collect(),
+                proposals: proposals.clone(),
+            };
+            match resolver.resolve(&question) {
+                Resolution::Skip => Value::Unchanged,
+                Resolution::Proposal(i) if i < proposals.len() => {
+                    Value::Update(proposals[i].clone())
+                }
+                Resolution::Artists(names) => Value::Update(names),
+                r => {
+                    invalid(&question, &r);
+                    Value::Unchanged
+                }
+            }
+        });
+
+        self.check_release_suffixes(&mut |a, r, suffix| {
+            let proposals = vec![suffix.standardized(), suffix.base.clone()];
+            let question = Question::ReleaseSuffix {
+                artists: names(a),
+                release: r.name.to_string(),
+                suffix: suffix.suffix.clone(),
+                proposals: proposals.clone(),
+            };
+            match resolver.resolve(&question) {
+                Resolution::Skip => Value::Unchanged,
+                Resolution::Proposal(i) if i < proposals.len() => {
+                    Value::Update(proposals[i].clone())
+                }
+                Resolution::Release(name) => Value::Update(name),
+                r => {
+                    invalid(&question, &r);
+                    Value::Unchanged
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::song;
+    use crate::{CheckCount, MusicIndex};
+
+    #[test]
+    fn resolves_questions() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs =
+            vec![song("/music/a.mp3", "Bar", "Foo"), song("/music/b.mp3", "Baz [Deluxe]", "foo")];
+        let mut checks = Checks::from(&index);
+
+        let mut questions = Vec::new();
+        checks.resolve_questions(&mut |q: &Question| {
+            questions.push(q.clone());
+            match q {
+                Question::InconsistentArtists { .. } => Resolution::Proposal(0),
+                _ => Resolution::Release("Baz".into()),
+            }
+        });
+
+        assert_eq!(questions.len(), 2);
+        let count = CheckCount { issues: 1, updates: 1 };
+        assert_eq!(checks.stats.inconsistent_artists, count);
+        assert_eq!(checks.stats.release_suffixes, count);
+        let ops = &checks.song_operations;
+        assert_eq!(ops.len(), 1);
+        let update = ops[0].tag_update.as_ref().unwrap();
+        assert_eq!(update.release_artists, Value::Update(vec!["Foo".to_owned()]));
+        assert_eq!(update.release, Value::Update("Baz".to_owned()));
+    }
+}
@@ -0,0 +1,255 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use tracing::{info, warn};
+
+use crate::changes::{blockers, execute_ordered, temp_path};
+use crate::fs::check_unchanged;
+use crate::{ChangedOnDisk, Changes, ExecOptions, FileOpType, Fs, SongOperation};
+
+/// The error of a song operation that was rolled back or never executed, since another
+/// operation of its release failed, see [`ExecOptions::atomic_releases`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RolledBack {
+    /// The song whose operation failed.
+    pub failed: PathBuf,
+}
+
+impl std::fmt::Display for RolledBack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rolled back, since {} failed", self.failed.display())
+    }
+}
+
+impl std::error::Error for RolledBack {}
+
+/// An executed song operation and what is needed to undo it.
+struct JournalEntry<'a> {
+    op: &'a SongOperation,
+    /// The temporary path the song was moved to while a cycle of moves wasn't finished.
+    temp: Option<PathBuf>,
+    /// A copy of the song before its tags or mode were updated, unless only a copy of it was
+    /// modified.
+    original: Option<PathBuf>,
+}
+
+fn original_path(path: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".music-organizer-original");
+    path.with_file_name(name)
+}
+
+impl Changes {
+    /// Executes the song operations release by release. Once an operation of a release fails,
+    /// the remaining ones aren't executed and the executed ones are undone, so no release is
+    /// left half organized.
+    pub(crate) fn execute_song_operations_by_release(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        progress: &mut impl FnMut(&Path, u64, u64),
+        f: &mut impl FnMut(&SongOperation, Result<(), Box<dyn std::error::Error>>),
+    ) {
+        let releases = self.group_song_operations();
+        for release in releases.iter().flat_map(|a| a.releases.iter()) {
+            if options.cancel.is_cancelled() {
+                break;
+            }
+            let ops = &release.song_operations;
+            let moves: Vec<_> = (ops.iter())
+                .map(|o| Some((o.song.path.as_path(), o.new_path.as_deref()?)))
+                .collect();
+            let failed: RefCell<Option<PathBuf>> = RefCell::new(None);
+            let mut journal = Vec::new();
+            let mut results = Vec::new();
+            execute_ordered(
+                fs,
+                options,
+                ops,
+                &moves,
+                &blockers(&moves),
+                &mut |o, temp| {
+                    if let Some(failed) = failed.borrow().clone() {
+                        return Err(RolledBack { failed }.into());
+                    }
+                    let modified = self.modified.get(&o.song.path).copied();
+                    let dest = temp.or(o.new_path.as_deref());
+                    check_unchanged(fs, &o.song.path, dest, modified)?;
+
+                    let modifies_original = (o.tag_update.is_some() || o.mode_update.is_some())
+                        && (o.new_path.is_none() || options.op_type != FileOpType::Copy);
+                    let original = match modifies_original {
+                        true => {
+                            let original = original_path(&o.song.path);
+                            fs.copy(&o.song.path, &original, &mut |_, _| ())?;
+                            Some(original)
+                        }
+                        false => None,
+                    };
+                    let progress = &mut |copied, total| progress(&o.song.path, copied, total);
+                    let r = match temp {
+                        Some(temp) => {
+                            let o =
+                                SongOperation { new_path: Some(temp.to_owned()), ..(*o).clone() };
+                            o.execute(fs, options, progress)
+                        }
+                        None => o.execute(fs, options, progress),
+                    };
+                    // the song may have been moved or copied before updating its tags failed
+                    if r.is_ok() || dest.is_some_and(|d| fs.exists(d)) {
+                        journal.push(JournalEntry {
+                            op: o,
+                            temp: temp.map(Path::to_owned),
+                            original,
+                        });
+                    } else if let Some(original) = original {
+                        fs.remove_file(&original).ok();
+                    }
+                    r
+                },
+                &mut |o, r| {
+                    let is_failure = r
+                        .as_ref()
+                        .is_err_and(|e| !e.is::<ChangedOnDisk>() && !e.is::<RolledBack>());
+                    if is_failure && failed.borrow().is_none() {
+                        *failed.borrow_mut() = Some(o.song.path.clone());
+                    }
+                    results.push((*o, r));
+                },
+            );
+
+            let Some(failed) = failed.into_inner() else {
+                for e in journal.iter() {
+                    if let Some(original) = &e.original {
+                        fs.remove_file(original).ok();
+                    }
+                }
+                for (o, r) in results {
+                    f(o, r);
+                }
+                continue;
+            };
+
+            info!(release = %release.name, failed = %failed.display(), "rolling back release");
+            let errors = rollback(fs, options, &journal);
+            for (o, r) in results {
+                let r = match (r, errors.get(o.song.path.as_path())) {
+                    (Ok(()), None) => Err(RolledBack { failed: failed.clone() }.into()),
+                    (Ok(()), Some(e)) => Err(format!("rolling back failed: {e}").into()),
+                    (Err(e), Some(re)) => Err(format!("{e}, rolling back failed: {re}").into()),
+                    (r @ Err(_), None) => r,
+                };
+                f(o, r);
+            }
+            if options.fail_fast {
+                break;
+            }
+        }
+    }
+}
+
+/// Undoes the executed operations and returns the errors of the songs that couldn't be restored.
+/// Moved songs are first moved to temporary paths, so songs that swapped places don't overwrite
+/// each other when moving back.
+fn rollback<'a>(
+    fs: &impl Fs,
+    options: &ExecOptions,
+    journal: &[JournalEntry<'a>],
+) -> HashMap<&'a Path, String> {
+    let mut errors = HashMap::new();
+    let mut restores = Vec::new();
+    for e in journal.iter().rev() {
+        let path = e.op.song.path.as_path();
+        let current = [e.temp.as_deref(), e.op.new_path.as_deref()]
+            .into_iter()
+            .flatten()
+            .find(|p| fs.exists(p));
+        let r = match current {
+            Some(c) if options.op_type != FileOpType::Move || e.original.is_some() => {
+                fs.remove_file(c)
+            }
+            Some(c) => {
+                let temp = temp_path(c);
+                let r = options.retry.run(|| fs.rename(c, &temp));
+                if r.is_ok() {
+                    restores.push((path, temp));
+                }
+                r
+            }
+            None => Ok(()),
+        };
+        if let Err(err) = r {
+            warn!(path = %path.display(), error = %err, "rollback failed");
+            errors.insert(path, err.to_string());
+        }
+        if let Some(original) = &e.original {
+            restores.push((path, original.clone()));
+        }
+    }
+
+    for (path, from) in restores {
+        if let Err(err) = options.retry.run(|| fs.rename(&from, path)) {
+            warn!(path = %path.display(), error = %err, "rollback failed");
+            errors.insert(path, format!("left at {}: {err}", from.display()));
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{MemFs, Mode, Song};
+
+    fn op(path: &str, new_path: &str, track: u16) -> SongOperation {
+        let song = Song {
+            path: PathBuf::from(path),
+            track_number: Some(track),
+            release_artists: Arc::from(["Foo".to_owned()]),
+            release: Arc::from("Bar"),
+            ..Default::default()
+        };
+        SongOperation { new_path: Some(PathBuf::from(new_path)), ..SongOperation::new(song) }
+    }
+
+    #[test]
+    fn rolls_back_releases_with_a_failed_operation() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a.mp3", "a");
+        fs.add_file("/music/b.mp3", "b");
+        fs.add_file("/music/c.mp3", "c");
+        fs.add_dir("/music/Foo");
+        let mut a = op("/music/a.mp3", "/music/Foo/a.mp3", 1);
+        a.mode_update = Some(Mode(0o600));
+        let changes = Changes {
+            song_operations: vec![
+                a,
+                op("/music/b.mp3", "/music/Missing/b.mp3", 2),
+                op("/music/c.mp3", "/music/Foo/c.mp3", 3),
+            ],
+            ..Default::default()
+        };
+        let options = ExecOptions { atomic_releases: true, ..Default::default() };
+
+        let mut results = Vec::new();
+        changes.execute_song_operations(&fs, &options, &mut |_, _, _| (), &mut |o, r| {
+            results.push((o.song.path.clone(), r.map_err(|e| e.to_string())));
+        });
+
+        let rolled_back = Err("rolled back, since /music/b.mp3 failed".to_owned());
+        assert_eq!(results[0], (PathBuf::from("/music/a.mp3"), rolled_back.clone()));
+        assert!(results[1].1.is_err());
+        assert_eq!(results[2], (PathBuf::from("/music/c.mp3"), rolled_back));
+        let mut paths = fs.paths();
+        paths.retain(|p| fs.is_file(p));
+        assert_eq!(
+            paths,
+            [Path::new("/music/a.mp3"), Path::new("/music/b.mp3"), Path::new("/music/c.mp3")]
+        );
+        assert_ne!(fs.mode(Path::new("/music/a.mp3")), Some(Mode(0o600)));
+    }
+}
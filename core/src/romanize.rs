@@ -0,0 +1,328 @@
+/// How the directory names of artists written in non-Latin scripts are chosen. Tags and file
+/// names always keep the original names.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum NonLatinArtists {
+    /// Artist dirs are named like the artists.
+    #[default]
+    Keep,
+    /// Artist dirs are transliterated from Cyrillic, Greek, Japanese kana and Korean Hangul.
+    /// Names containing characters that would need a dictionary, like Chinese characters and
+    /// kanji, are grouped in the bucket dir instead.
+    Romanize { bucket: String },
+    /// Artist dirs with non-Latin names are grouped in the bucket dir, e.g. `#`.
+    Group { bucket: String },
+}
+
+/// The Revised Romanization of the initial consonants, vowels and final consonants of Hangul
+/// syllables, in the order of their Unicode composition.
+const HANGUL_INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+const HANGUL_VOWELS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+const HANGUL_FINALS: [&str; 28] = [
+    "", "k", "k", "k", "n", "n", "n", "t", "l", "k", "m", "l", "l", "l", "p", "l", "m", "p", "p",
+    "t", "t", "ng", "t", "t", "k", "t", "p", "t",
+];
+
+/// Whether the char is a letter of a script other than Latin.
+pub(crate) fn is_non_latin(c: char) -> bool {
+    c.is_alphabetic()
+        && !matches!(c,
+            '\0'..='\u{24F}'
+            | '\u{1E00}'..='\u{1EFF}'
+            | '\u{2C60}'..='\u{2C7F}'
+            | '\u{A720}'..='\u{A7FF}'
+            | '\u{FF21}'..='\u{FF3A}'
+            | '\u{FF41}'..='\u{FF5A}')
+}
+
+pub(crate) fn has_non_latin(s: &str) -> bool {
+    s.chars().any(is_non_latin)
+}
+
+fn cyrillic(c: char) -> Option<&'static str> {
+    Some(match c {
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' | 'ґ' => "g",
+        'д' => "d",
+        'е' | 'э' => "e",
+        'ё' => "yo",
+        'ж' => "zh",
+        'з' => "z",
+        'и' | 'і' => "i",
+        'й' | 'ы' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' | 'ў' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ю' => "yu",
+        'я' => "ya",
+        'ї' => "yi",
+        'є' => "ye",
+        'ђ' => "dj",
+        'ј' => "j",
+        'љ' => "lj",
+        'њ' => "nj",
+        'ћ' => "c",
+        'џ' | 'ѕ' => "dz",
+        'ѓ' => "gj",
+        'ќ' => "kj",
+        _ => return None,
+    })
+}
+
+fn greek(c: char) -> Option<&'static str> {
+    Some(match c {
+        'α' | 'ά' => "a",
+        'β' => "v",
+        'γ' => "g",
+        'δ' => "d",
+        'ε' | 'έ' => "e",
+        'ζ' => "z",
+        'η' | 'ή' | 'ι' | 'ί' | 'ϊ' | 'ΐ' => "i",
+        'θ' => "th",
+        'κ' => "k",
+        'λ' => "l",
+        'μ' => "m",
+        'ν' => "n",
+        'ξ' => "x",
+        'ο' | 'ό' | 'ω' | 'ώ' => "o",
+        'π' => "p",
+        'ρ' => "r",
+        'σ' | 'ς' => "s",
+        'τ' => "t",
+        'υ' | 'ύ' | 'ϋ' | 'ΰ' => "y",
+        'φ' => "f",
+        'χ' => "ch",
+        'ψ' => "ps",
+        _ => return None,
+    })
+}
+
+/// The Hepburn romanization of a hiragana, katakana are mapped onto hiragana first.
+fn kana(c: char) -> Option<&'static str> {
+    let c = match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60)?,
+        _ => c,
+    };
+    Some(match c {
+        'あ' | 'ぁ' => "a",
+        'い' | 'ぃ' => "i",
+        'う' | 'ぅ' => "u",
+        'え' | 'ぇ' => "e",
+        'お' | 'ぉ' | 'を' => "o",
+        'か' | 'ゕ' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' | 'ゖ' => "ke",
+        'こ' => "ko",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'ざ' => "za",
+        'じ' | 'ぢ' => "ji",
+        'ず' | 'づ' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'だ' => "da",
+        'で' => "de",
+        'ど' => "do",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' => "ya",
+        'ゆ' => "yu",
+        'よ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' | 'ゎ' => "wa",
+        'ゐ' => "wi",
+        'ゑ' => "we",
+        'ん' => "n",
+        'ゔ' => "vu",
+        _ => return None,
+    })
+}
+
+/// Appends the romanization of a kana to the word, considering the small `ゃ`, `ゅ`, `ょ` that
+/// palatalize the previous syllable, the small vowels that replace its vowel, like `ティ`, the
+/// small `っ` that doubles the next consonant and the long vowel mark.
+fn push_kana(word: &mut String, c: char, geminate: &mut bool) -> bool {
+    let hiragana = match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    };
+    match hiragana {
+        'ゃ' | 'ゅ' | 'ょ' => {
+            let vowel = match hiragana {
+                'ゃ' => 'a',
+                'ゅ' => 'u',
+                _ => 'o',
+            };
+            // `kiゃ` becomes `kya`, `shiゃ` becomes `sha`
+            if word.ends_with('i') {
+                word.pop();
+                if !["sh", "ch", "j"].iter().any(|s| word.ends_with(s)) {
+                    word.push('y');
+                }
+            } else {
+                word.push('y');
+            }
+            word.push(vowel);
+        }
+        'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' if word.ends_with(|c| "aeiou".contains(c)) => {
+            // `ウィ` becomes `wi`, `フィ` becomes `fi`
+            if word.pop() == Some('u') && word.is_empty() {
+                word.push('w');
+            }
+            word.push_str(kana(hiragana).unwrap_or_default());
+        }
+        'っ' => *geminate = true,
+        'ー' => {
+            if let Some(v) = word.chars().last().filter(|c| "aeiou".contains(*c)) {
+                word.push(v);
+            }
+        }
+        _ => {
+            let Some(r) = kana(c) else { return false };
+            if std::mem::take(geminate) {
+                match r.strip_prefix("ch") {
+                    Some(_) => word.push('t'),
+                    None => word.extend(r.chars().next().filter(|c| !"aeiou".contains(*c))),
+                }
+            }
+            word.push_str(r);
+        }
+    }
+    true
+}
+
+fn push_hangul(word: &mut String, c: char) -> bool {
+    let Some(i) = (c as u32).checked_sub(0xAC00).filter(|i| *i < 11172) else { return false };
+    let i = i as usize;
+    word.push_str(HANGUL_INITIALS[i / 588]);
+    word.push_str(HANGUL_VOWELS[i % 588 / 28]);
+    word.push_str(HANGUL_FINALS[i % 28]);
+    true
+}
+
+/// Uppercases the first char of the string.
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Transliterates the Cyrillic, Greek, kana and Hangul letters of a name into Latin letters.
+/// Transliterated words are capitalized like `Kyary Pamyu Pamyu`. Returns `None` if the name
+/// contains other non-Latin letters, e.g. Chinese characters.
+pub fn romanize(name: &str) -> Option<String> {
+    let mut new = String::with_capacity(name.len());
+    let mut word = String::new();
+    let mut geminate = false;
+    for c in name.chars() {
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        let pushed = match cyrillic(lower).or_else(|| greek(lower)) {
+            Some(r) if c.is_uppercase() && word.is_empty() => {
+                word.push_str(&capitalize(r));
+                true
+            }
+            Some(r) => {
+                word.push_str(r);
+                true
+            }
+            None if ('\u{3041}'..='\u{30FC}').contains(&c) && c != '・' => {
+                push_kana(&mut word, c, &mut geminate)
+            }
+            None => push_hangul(&mut word, c),
+        };
+        if pushed {
+            continue;
+        }
+        if is_non_latin(c) {
+            return None;
+        }
+        new.push_str(&capitalize(&std::mem::take(&mut word)));
+        new.push(if c == '・' { ' ' } else { c });
+    }
+    new.push_str(&capitalize(&word));
+    Some(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn romanizes_cyrillic_greek_kana_and_hangul() {
+        assert_eq!(romanize("Кино").as_deref(), Some("Kino"));
+        assert_eq!(romanize("Щедрин и Ёлка").as_deref(), Some("Shchedrin I Yolka"));
+        assert_eq!(romanize("Βαγγέλης").as_deref(), Some("Vaggelis"));
+        assert_eq!(romanize("きゃりーぱみゅぱみゅ").as_deref(), Some("Kyariipamyupamyu"));
+        assert_eq!(romanize("ポルノグラフィティ").as_deref(), Some("Porunogurafiti"));
+        assert_eq!(romanize("ゆず・ザ・バンド").as_deref(), Some("Yuzu Za Bando"));
+        assert_eq!(romanize("マッチ").as_deref(), Some("Matchi"));
+        assert_eq!(romanize("방탄소년단").as_deref(), Some("Bangtansonyeondan"));
+        assert_eq!(romanize("Björk").as_deref(), Some("Björk"));
+        assert_eq!(romanize("周杰倫"), None);
+        assert_eq!(romanize("椎名林檎"), None);
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST, INT};
+use tracing::{debug, info_span, warn};
+
+use crate::fs::{valid_os_str, valid_os_str_dots};
+use crate::{Checks, Song, TagUpdate, Value};
+
+/// A [rhai](https://rhai.rs) script for rules that don't fit a layout or a check, like dirs per
+/// label or catalog numbers. It can define these functions, both are called for each song:
+///
+/// - `path(song, components)` receives the dirs and the file name without extension of the song
+///   inside of the output dir as an array of strings and returns the adjusted array.
+/// - `update(song, update)` receives the tag update the checks queued and returns the adjusted
+///   update, or `()` to veto it.
+///
+/// Songs are maps of their tags with the updates of the checks applied, missing tags are `()`.
+/// Updates are maps of the changed tags, removed tags are `()`. Custom fields are in a nested
+/// `extra` map in both, songs only have the ones in [`crate::MusicIndex::custom_fields`].
+/// Errors are logged and leave the song unchanged.
+pub struct Script {
+    source: String,
+    engine: Engine,
+    ast: AST,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptError(pub String);
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl std::fmt::Debug for Script {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Script").field("source", &self.source).finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for Script {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Script {}
+
+impl Script {
+    pub fn compile(source: &str) -> Result<Self, ScriptError> {
+        let engine = Engine::new();
+        let ast = engine.compile(source).map_err(|e| ScriptError(e.to_string()))?;
+        let script = Self { source: source.to_owned(), engine, ast };
+        if !script.defines("path") && !script.defines("update") {
+            let e = "defines neither path(song, components) nor update(song, update)";
+            return Err(ScriptError(e.to_owned()));
+        }
+        Ok(script)
+    }
+
+    fn defines(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name && f.params.len() == 2)
+    }
+
+    fn call(&self, name: &str, song: &Song, arg: Dynamic) -> Option<Dynamic> {
+        let args = (song_map(song), arg);
+        match self.engine.call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, args) {
+            Ok(r) => Some(r),
+            Err(e) => {
+                warn!(path = %song.path.display(), function = name, error = %e, "script failed");
+                None
+            }
+        }
+    }
+
+    /// The dirs and file name returned by the `path` function, if it is defined and succeeds.
+    pub(crate) fn path(
+        &self,
+        song: &Song,
+        dirs: &Path,
+        file_name: &OsString,
+    ) -> Option<(PathBuf, OsString)> {
+        if !self.defines("path") {
+            return None;
+        }
+        let components: Array = (dirs.iter().chain([file_name.as_os_str()]))
+            .map(|c| Dynamic::from(c.to_string_lossy().into_owned()))
+            .collect();
+        let r = self.call("path", song, Dynamic::from_array(components))?;
+        let components = strings(&r).filter(|c| !c.is_empty()).or_else(|| {
+            warn!(path = %song.path.display(), "script path isn't a non-empty array of strings");
+            None
+        })?;
+        let (file_name, dirs) = components.split_last()?;
+        let dirs = dirs.iter().map(|c| valid_os_str_dots(c)).filter(|c| !c.is_empty()).collect();
+        Some((dirs, OsString::from(valid_os_str(file_name))))
+    }
+
+    /// The update returned by the `update` function, `None` if the update was vetoed. Errors
+    /// keep the update.
+    fn update(&self, song: &Song, update: &TagUpdate) -> Option<TagUpdate> {
+        let Some(r) = self.call("update", song, Dynamic::from_map(update_map(update))) else {
+            return Some(update.clone());
+        };
+        if r.is_unit() {
+            return None;
+        }
+        let r = r.try_cast::<Map>().ok_or("not a map").and_then(|m| tag_update(&m, update));
+        match r {
+            Ok(u) => Some(u),
+            Err(e) => {
+                warn!(path = %song.path.display(), error = e, "invalid script update");
+                Some(update.clone())
+            }
+        }
+    }
+}
+
+impl Checks<'_> {
+    /// Passes the tag update of each song, or an empty one, through the `update` function of
+    /// the script, so it can adjust or veto the updates of the checks. Run after the checks.
+    pub fn apply_script(&mut self, script: &Script) {
+        let _span = info_span!("check", check = "script").entered();
+        if !script.defines("update") {
+            return;
+        }
+        for song in self.index.songs.iter() {
+            if self.cancel.is_cancelled() {
+                return;
+            }
+            if self.retag_excluded.contains(song.path.as_path()) {
+                continue;
+            }
+            let op = self.song_operations.iter().position(|o| o.song.path == song.path);
+            let current = op.and_then(|i| self.song_operations[i].tag_update.clone());
+            let mut updated_song = song.clone();
+            if let Some(u) = &current {
+                u.apply(&mut updated_song);
+            }
+            let update = current.clone().unwrap_or_default();
+            let new = script.update(&updated_song, &update).filter(|u| *u != TagUpdate::default());
+            if new == current {
+                continue;
+            }
+            debug!(path = %song.path.display(), ?new, "script changed update");
+            self.stats.script.issues += 1;
+            self.stats.script.updates += 1;
+            match (op, new) {
+                (Some(i), new) => {
+                    let op = &mut self.song_operations[i];
+                    op.tag_update = new;
+                    op.add_reason("script");
+                    if op.tag_update.is_none() && op.mode_update.is_none() {
+                        self.song_operations.remove(i);
+                    }
+                }
+                (None, Some(new)) => {
+                    crate::util::update_tag(
+                        &mut self.song_operations,
+                        &self.retag_excluded,
+                        song,
+                        "script",
+                        |t| *t = new,
+                    );
+                }
+                (None, None) => (),
+            }
+        }
+    }
+}
+
+fn num(n: Option<impl Into<INT>>) -> Dynamic {
+    n.map_or(Dynamic::UNIT, |n| Dynamic::from_int(n.into()))
+}
+
+fn string(s: Option<&str>) -> Dynamic {
+    s.map_or(Dynamic::UNIT, |s| Dynamic::from(s.to_owned()))
+}
+
+fn array(names: &[String]) -> Dynamic {
+    Dynamic::from_array(names.iter().map(|n| Dynamic::from(n.clone())).collect())
+}
+
+fn strings(d: &Dynamic) -> Option<Vec<String>> {
+    let array = d.read_lock::<Array>()?;
+    array.iter().map(|v| v.clone().into_string().ok()).collect()
+}
+
+fn song_map(song: &Song) -> Map {
+    let extra: Map =
+        song.extra.iter().map(|(k, v)| (k.as_str().into(), Dynamic::from(v.clone()))).collect();
+    Map::from_iter([
+        ("path".into(), Dynamic::from(song.path.to_string_lossy().into_owned())),
+        ("track_number".into(), num(song.track_number)),
+        ("total_tracks".into(), num(song.total_tracks)),
+        ("disc_number".into(), num(song.disc_number)),
+        ("total_discs".into(), num(song.total_discs)),
+        ("artists".into(), array(&song.artists)),
+        ("release_artists".into(), array(&song.release_artists)),
+        ("release".into(), Dynamic::from(song.release.to_string())),
+        ("title".into(), Dynamic::from(song.title.clone())),
+        ("year".into(), num(song.year)),
+        ("genre".into(), string(song.genre.as_deref())),
+        ("extra".into(), Dynamic::from_map(extra)),
+    ])
+}
+
+fn insert<T>(map: &mut Map, name: &str, value: &Value<T>, f: impl Fn(&T) -> Dynamic) {
+    match value {
+        Value::Update(v) => map.insert(name.into(), f(v)),
+        Value::Remove => map.insert(name.into(), Dynamic::UNIT),
+        Value::Unchanged => None,
+    };
+}
+
+fn update_map(u: &TagUpdate) -> Map {
+    let mut map = Map::new();
+    let int = |n: &u16| Dynamic::from_int(INT::from(*n));
+    insert(&mut map, "track_number", &u.track_number, int);
+    insert(&mut map, "total_tracks", &u.total_tracks, int);
+    insert(&mut map, "disc_number", &u.disc_number, int);
+    insert(&mut map, "total_discs", &u.total_discs, int);
+    insert(&mut map, "artists", &u.artists, |a| array(a));
+    insert(&mut map, "release_artists", &u.release_artists, |a| array(a));
+    insert(&mut map, "release", &u.release, |s| Dynamic::from(s.clone()));
+    insert(&mut map, "title", &u.title, |s| Dynamic::from(s.clone()));
+    insert(&mut map, "genre", &u.genre, |s| Dynamic::from(s.clone()));
+    insert(&mut map, "year", &u.year, |y| Dynamic::from_int(INT::from(*y)));
+    insert(&mut map, "rating", &u.rating, |r| Dynamic::from_int(INT::from(*r)));
+    insert(&mut map, "play_count", &u.play_count, |c| Dynamic::from_int(INT::from(*c)));
+    if !u.extra.is_empty() {
+        let mut extra = Map::new();
+        for (name, value) in u.extra.iter() {
+            insert(&mut extra, name, value, |s| Dynamic::from(s.clone()));
+        }
+        map.insert("extra".into(), Dynamic::from_map(extra));
+    }
+    map
+}
+
+fn value<T>(d: &Dynamic, f: impl Fn(&Dynamic) -> Option<T>) -> Option<Value<T>> {
+    match d.is_unit() {
+        true => Some(Value::Remove),
+        false => f(d).map(Value::Update),
+    }
+}
+
+fn int<T: TryFrom<INT>>(d: &Dynamic) -> Option<T> {
+    d.as_int().ok().and_then(|i| T::try_from(i).ok())
+}
+
+fn text(d: &Dynamic) -> Option<String> {
+    d.clone().into_string().ok()
+}
+
+/// Parses the update returned by a script, the artwork isn't exposed and taken from `original`.
+fn tag_update(map: &Map, original: &TagUpdate) -> Result<TagUpdate, &'static str> {
+    let mut u = TagUpdate { artwork: original.artwork.clone(), ..Default::default() };
+    for (name, d) in map.iter() {
+        let valid = match name.as_str() {
+            "track_number" => value(d, int).map(|v| u.track_number = v),
+            "total_tracks" => value(d, int).map(|v| u.total_tracks = v),
+            "disc_number" => value(d, int).map(|v| u.disc_number = v),
+            "total_discs" => value(d, int).map(|v| u.total_discs = v),
+            "artists" => value(d, strings).map(|v| u.artists = v),
+            "release_artists" => value(d, strings).map(|v| u.release_artists = v),
+            "release" => value(d, text).map(|v| u.release = v),
+            "title" => value(d, text).map(|v| u.title = v),
+            "genre" => value(d, text).map(|v| u.genre = v),
+            "year" => value(d, int).map(|v| u.year = v),
+            "rating" => value(d, int).map(|v| u.rating = v),
+            "play_count" => value(d, int).map(|v| u.play_count = v),
+            "extra" => d.read_lock::<Map>().and_then(|extra| {
+                let extra: Option<BTreeMap<_, _>> = (extra.iter())
+                    .map(|(k, d)| Some((k.to_uppercase(), value(d, text)?)))
+                    .collect();
+                extra.map(|e| u.extra = e)
+            }),
+            _ => return Err("unknown field"),
+        };
+        if valid.is_none() {
+            return Err("invalid value");
+        }
+    }
+    Ok(u)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::MusicIndex;
+
+    #[test]
+    fn adjusts_paths_and_updates() {
+        let script = Script::compile(
+            r#"
+            fn path(song, components) {
+                if song.extra.LABEL != () {
+                    components.insert(0, song.extra.LABEL);
+                }
+                components
+            }
+            fn update(song, update) {
+                if song.title == "Skit" {
+                    return ();
+                }
+                update.extra = #{ CATALOG: "CAT-" + song.track_number };
+                update
+            }
+            "#,
+        )
+        .unwrap();
+
+        let song = |path: &str, title: &str, track: u16| Song {
+            path: PathBuf::from(path),
+            track_number: Some(track),
+            release: Arc::from("Bar"),
+            title: title.to_owned(),
+            extra: BTreeMap::from([("LABEL".to_owned(), "Warp/".to_owned())]),
+            ..Default::default()
+        };
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![song("/music/a.mp3", "One", 1), song("/music/b.mp3", "Skit", 2)];
+        let mut checks = Checks::from(&index);
+        crate::util::update_tag(
+            &mut checks.song_operations,
+            &checks.retag_excluded,
+            &index.songs[1],
+            "x",
+            |t| t.year = Value::Update(2001),
+        );
+        checks.apply_script(&script);
+
+        assert_eq!(checks.song_operations.len(), 1);
+        let update = checks.song_operations[0].tag_update.as_ref().unwrap();
+        assert_eq!(update.extra.get("CATALOG"), Some(&Value::Update("CAT-1".to_owned())));
+        assert_eq!(checks.stats.script, crate::CheckCount { issues: 2, updates: 2 });
+
+        let (dirs, file_name) =
+            script.path(&index.songs[0], Path::new("Foo/Bar"), &"01 - One".into()).unwrap();
+        assert_eq!(dirs, Path::new("Warp/Foo/Bar"));
+        assert_eq!(file_name, "01 - One");
+    }
+}
@@ -0,0 +1,59 @@
+//! Serializes paths that aren't valid UTF-8 lossily instead of failing, for reports and exports
+//! which are only read by people or other tools. Operations always use the original bytes.
+
+use std::path::{Path, PathBuf};
+
+use serde::ser::SerializeSeq;
+use serde::Serializer;
+
+pub(crate) fn lossy<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+pub(crate) fn lossy_option<S: Serializer>(
+    path: &Option<PathBuf>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match path {
+        Some(p) => serializer.serialize_some(&p.to_string_lossy()),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub(crate) fn lossy_vec<S: Serializer>(
+    paths: &[PathBuf],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(paths.len()))?;
+    for p in paths {
+        seq.serialize_element(&p.to_string_lossy())?;
+    }
+    seq.end()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Paths {
+        #[serde(serialize_with = "lossy")]
+        path: PathBuf,
+        #[serde(serialize_with = "lossy_vec")]
+        paths: Vec<PathBuf>,
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serializes_invalid_utf8_lossily() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = PathBuf::from(OsStr::from_bytes(b"/music/caf\xe9.mp3"));
+        let paths = Paths { path: path.clone(), paths: vec![path] };
+        let json = serde_json::to_string(&paths).unwrap();
+        assert_eq!(json, r#"{"path":"/music/caf�.mp3","paths":["/music/caf�.mp3"]}"#);
+    }
+}
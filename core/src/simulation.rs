@@ -0,0 +1,242 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::changes::{blockers, order_moves, temp_path, MoveStep};
+use crate::{Changes, FileOpType, MusicIndex};
+
+/// The files and dirs of a library, which [`Changes::simulate`] applies changes to without any
+/// I/O.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VirtualTree {
+    /// The files by their path, with the path they had before the changes.
+    pub files: BTreeMap<PathBuf, PathBuf>,
+    pub dirs: BTreeSet<PathBuf>,
+}
+
+impl VirtualTree {
+    /// Contains all indexed files and their dirs inside of the music dir. The contents of scans
+    /// or artwork dirs aren't indexed, so they are missing.
+    pub fn from_index(index: &MusicIndex) -> Self {
+        let mut tree = Self::default();
+        tree.dirs.insert(index.music_dir.clone());
+        let paths = (index.songs.iter().map(|s| &s.path))
+            .chain(index.videos.iter().map(|v| &v.path))
+            .chain(index.audiobooks.iter().map(|a| &a.path))
+            .chain(index.images.iter())
+            .chain(index.companions.iter())
+            .chain(index.extras.iter())
+            .chain(index.unknown.iter())
+            .chain(index.playlists.iter());
+        for p in paths {
+            tree.add_file(p);
+            for dir in p.ancestors().skip(1).take_while(|d| d.starts_with(&index.music_dir)) {
+                tree.dirs.insert(dir.to_owned());
+            }
+        }
+        tree
+    }
+
+    pub fn add_file(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        self.files.insert(path.clone(), path);
+    }
+
+    pub fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    pub fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.contains(path)
+    }
+
+    /// Moves or copies a file or a dir with all its entries, returns the files that were
+    /// overwritten, or `None` if there is nothing at `from`.
+    fn transfer(&mut self, from: &Path, to: &Path, copy: bool) -> Option<Vec<Collision>> {
+        let files: Vec<(PathBuf, PathBuf)> = (self.files.range(from.to_owned()..))
+            .take_while(|(p, _)| p.starts_with(from))
+            .map(|(p, origin)| (p.clone(), origin.clone()))
+            .collect();
+        let is_dir = self.dirs.contains(from);
+        if files.is_empty() && !is_dir {
+            return None;
+        }
+
+        let mut collisions = Vec::new();
+        for (p, origin) in files {
+            if !copy {
+                self.files.remove(&p);
+            }
+            // joining an empty path would append a trailing slash
+            let relative = p.strip_prefix(from).unwrap();
+            let new = match relative.as_os_str().is_empty() {
+                true => to.to_owned(),
+                false => to.join(relative),
+            };
+            if let Some(overwritten) = self.files.insert(new.clone(), origin.clone()) {
+                collisions.push(Collision { path: new, overwritten, by: origin });
+            }
+        }
+        if is_dir {
+            let dirs: Vec<PathBuf> = (self.dirs.range(from.to_owned()..))
+                .take_while(|p| p.starts_with(from))
+                .cloned()
+                .collect();
+            for p in dirs {
+                if !copy {
+                    self.dirs.remove(&p);
+                }
+                self.dirs.insert(to.join(p.strip_prefix(from).unwrap()));
+            }
+        }
+        Some(collisions)
+    }
+}
+
+/// A file that is overwritten by a song or file operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Collision {
+    pub path: PathBuf,
+    /// The path the overwritten file had before the changes.
+    pub overwritten: PathBuf,
+    /// The path the file that overwrites it had before the changes.
+    pub by: PathBuf,
+}
+
+/// The result of [`Changes::simulate`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Simulation {
+    pub tree: VirtualTree,
+    pub collisions: Vec<Collision>,
+    /// Sources of song and file operations that aren't in the tree, they are placed at their
+    /// new path anyway.
+    pub missing: Vec<PathBuf>,
+}
+
+impl Simulation {
+    fn transfer(&mut self, from: &Path, to: &Path, copy: bool) {
+        match self.tree.transfer(from, to, copy) {
+            Some(collisions) => self.collisions.extend(collisions),
+            None => {
+                self.missing.push(from.to_owned());
+                self.tree.files.insert(to.to_owned(), from.to_owned());
+            }
+        }
+    }
+
+    /// Executes the moves of one phase in the same order as the actual execution does.
+    fn transfer_ordered(&mut self, moves: &[Option<(&Path, &Path, bool)>]) {
+        let paths: Vec<_> = moves.iter().map(|m| m.map(|(old, new, _)| (old, new))).collect();
+        for step in order_moves(&blockers(&paths)) {
+            let (i, temp) = match step {
+                MoveStep::Execute(i) => (i, None),
+                MoveStep::ExecuteToTemp(i) => (i, Some(true)),
+                MoveStep::Finish(i) => (i, Some(false)),
+            };
+            let Some((old, new, copy)) = moves[i] else { continue };
+            match temp {
+                None => self.transfer(old, new, copy),
+                Some(true) => self.transfer(old, &temp_path(new), copy),
+                Some(false) => self.transfer(&temp_path(new), new, false),
+            }
+        }
+    }
+}
+
+impl Changes {
+    /// Applies the changes to a copy of the tree without any I/O, e.g. to find collisions
+    /// before executing them. Tag and permission updates don't change the tree.
+    pub fn simulate(&self, tree: &VirtualTree, op_type: FileOpType) -> Simulation {
+        let mut sim = Simulation { tree: tree.clone(), ..Default::default() };
+
+        for d in self.dir_creations.iter() {
+            sim.tree.dirs.extend(d.path.ancestors().map(Path::to_path_buf));
+        }
+
+        let song_moves: Vec<_> = (self.song_operations.iter())
+            .map(|o| {
+                let new = o.new_path.as_deref()?;
+                Some((o.song.path.as_path(), new, op_type != FileOpType::Move))
+            })
+            .collect();
+        sim.transfer_ordered(&song_moves);
+
+        let file_moves: Vec<_> = (self.file_operations.iter())
+            .map(|o| {
+                let copy = o.op_type.unwrap_or(op_type) != FileOpType::Move;
+                Some((o.old_path.as_path(), o.new_path.as_path(), copy))
+            })
+            .collect();
+        sim.transfer_ordered(&file_moves);
+        // stale links are replaced on purpose
+        let replaced: Vec<&Path> = (self.file_operations.iter())
+            .filter(|o| o.replace)
+            .map(|o| o.new_path.as_path())
+            .collect();
+        sim.collisions.retain(|c| !replaced.contains(&c.path.as_path()));
+
+        for c in self.playlist_creations.iter() {
+            if let Some(overwritten) = sim.tree.files.insert(c.path.clone(), c.path.clone()) {
+                let collision = Collision { path: c.path.clone(), overwritten, by: c.path.clone() };
+                sim.collisions.push(collision);
+            }
+        }
+
+        for q in self.quarantines.iter() {
+            sim.transfer(&q.path, &q.new_path, op_type != FileOpType::Move);
+            let sidecar = q.sidecar_path();
+            sim.tree.files.insert(sidecar.clone(), sidecar);
+        }
+
+        sim
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileOperation, Song, SongOperation};
+
+    fn song_op(old: &str, new: &str) -> SongOperation {
+        let song = Song { path: PathBuf::from(old), ..Default::default() };
+        SongOperation { new_path: Some(PathBuf::from(new)), ..SongOperation::new(song) }
+    }
+
+    #[test]
+    fn simulates_moves_and_finds_collisions() {
+        let mut tree = VirtualTree::default();
+        for p in ["/music/a.mp3", "/music/b.mp3", "/music/c.mp3", "/music/cover.jpg"] {
+            tree.add_file(p);
+        }
+        let changes = Changes {
+            song_operations: vec![
+                song_op("/music/a.mp3", "/music/b.mp3"),
+                song_op("/music/b.mp3", "/music/a.mp3"),
+                song_op("/music/c.mp3", "/music/cover.jpg"),
+            ],
+            file_operations: vec![FileOperation {
+                old_path: PathBuf::from("/music/scans"),
+                new_path: PathBuf::from("/music/Foo/scans"),
+                op_type: None,
+                replace: false,
+            }],
+            ..Default::default()
+        };
+
+        let sim = changes.simulate(&tree, FileOpType::Move);
+
+        let origin = |p: &str| sim.tree.files.get(Path::new(p)).and_then(|o| o.to_str());
+        assert_eq!(origin("/music/a.mp3"), Some("/music/b.mp3"));
+        assert_eq!(origin("/music/b.mp3"), Some("/music/a.mp3"));
+        assert_eq!(origin("/music/c.mp3"), None);
+        assert_eq!(
+            sim.collisions,
+            [Collision {
+                path: PathBuf::from("/music/cover.jpg"),
+                overwritten: PathBuf::from("/music/cover.jpg"),
+                by: PathBuf::from("/music/c.mp3"),
+            }]
+        );
+        assert_eq!(sim.missing, [PathBuf::from("/music/scans")]);
+        assert_eq!(sim.tree.files.len(), 4);
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Changes, OperationId};
+
+/// The operations of a plan and a hash of their contents, stored after a plan is applied, to
+/// tell which operations of a later plan are new since then.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanSnapshot {
+    pub operations: Vec<(OperationId, u64)>,
+}
+
+fn hash(op: &impl Serialize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(op).expect("serializing operation").hash(&mut hasher);
+    hasher.finish()
+}
+
+impl PlanSnapshot {
+    /// Operations with paths that aren't valid UTF-8 can't be stored, so they are always new.
+    pub fn new(changes: &Changes) -> Self {
+        let operations = (changes.dir_creations.iter().map(|o| (o.id(), hash(o))))
+            .chain(changes.song_operations.iter().map(|o| (o.id(), hash(o))))
+            .chain(changes.file_operations.iter().map(|o| (o.id(), hash(o))))
+            .chain(changes.playlist_updates.iter().map(|o| (o.id(), hash(o))))
+            .chain(changes.playlist_creations.iter().map(|o| (o.id(), hash(o))))
+            .chain(changes.quarantines.iter().map(|o| (o.id(), hash(o))))
+            .filter(|(id, _)| serde_json::to_string(id).is_ok())
+            .collect();
+        Self { operations }
+    }
+
+    /// The ids of operations that aren't part of the `previous` snapshot, or that changed since
+    /// then, e.g. a song that is moved to a different path.
+    pub fn new_since(&self, previous: &PlanSnapshot) -> HashSet<OperationId> {
+        let previous: HashMap<&OperationId, u64> =
+            previous.operations.iter().map(|(id, h)| (id, *h)).collect();
+        (self.operations.iter())
+            .filter(|(id, h)| previous.get(id) != Some(h))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{Song, SongOperation};
+
+    fn op(path: &str, new_path: &str) -> SongOperation {
+        let song = Song { path: PathBuf::from(path), ..Default::default() };
+        SongOperation { new_path: Some(PathBuf::from(new_path)), ..SongOperation::new(song) }
+    }
+
+    #[test]
+    fn detects_new_and_changed_operations() {
+        let old = Changes {
+            song_operations: vec![
+                op("/music/a.mp3", "/out/a.mp3"),
+                op("/music/b.mp3", "/out/b.mp3"),
+            ],
+            ..Default::default()
+        };
+        let new = Changes {
+            song_operations: vec![
+                op("/music/a.mp3", "/out/a.mp3"),
+                op("/music/b.mp3", "/out/x/b.mp3"),
+                op("/music/c.mp3", "/out/c.mp3"),
+            ],
+            ..Default::default()
+        };
+
+        let new = PlanSnapshot::new(&new).new_since(&PlanSnapshot::new(&old));
+        let expected = HashSet::from([
+            OperationId::SongOperation(PathBuf::from("/music/b.mp3")),
+            OperationId::SongOperation(PathBuf::from("/music/c.mp3")),
+        ]);
+        assert_eq!(new, expected);
+    }
+}
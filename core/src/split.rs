@@ -0,0 +1,175 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use tracing::{info, info_span};
+
+use crate::{util, Checks, Release, Song, Value};
+
+/// A release whose songs are tagged with different release artists, so it's split into multiple
+/// dirs, e.g. `Daft Punk` and `Daft Punk, Pharrell Williams`. See
+/// [`Checks::check_split_releases`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SplitRelease<'a> {
+    pub release: &'a str,
+    /// The release artists and their songs, sorted by the number of songs, descending.
+    pub parts: Vec<(&'a Arc<[String]>, &'a [&'a Song])>,
+}
+
+impl SplitRelease<'_> {
+    pub fn num_songs(&self) -> usize {
+        self.parts.iter().map(|(_, songs)| songs.len()).sum()
+    }
+}
+
+/// Release artists and their songs of a release name.
+type Part<'a> = (&'a Arc<[String]>, &'a Release<'a>);
+
+/// Names are also compared in parts, since multiple artists are often stored in a single value
+/// like `Daft Punk, Pharrell Williams`.
+fn share_artist(a: &[String], b: &[String]) -> bool {
+    let parts = |names: &[String]| -> Vec<String> {
+        (names.iter())
+            .flat_map(|n| n.split([',', ';', '&', '/']))
+            .map(|n| n.trim().to_lowercase())
+            .filter(|n| !n.is_empty())
+            .collect()
+    };
+    let b = parts(b);
+    parts(a).iter().any(|a| b.contains(a))
+}
+
+/// Whether the songs look like the tracks of a single release: every song has a track number,
+/// no disc and track number is used twice and the total tracks of each disc agree.
+fn is_single_release(parts: &[Part]) -> bool {
+    let mut numbers = HashSet::new();
+    let mut totals = HashMap::new();
+    for s in parts.iter().flat_map(|(_, r)| r.songs.iter()) {
+        let Some(track) = s.track_number else { return false };
+        if !numbers.insert((s.disc_number, track)) {
+            return false;
+        }
+        if let Some(total) = s.total_tracks {
+            if *totals.entry(s.disc_number).or_insert(total) != total {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+impl Checks<'_> {
+    /// Checks for releases with the same name under release artists that share an artist, whose
+    /// songs together form a single track list. The release artists returned by `f` are applied
+    /// to all songs of the release, so it's organized into a single dir.
+    pub fn check_split_releases(
+        &mut self,
+        f: &mut impl FnMut(&SplitRelease) -> Value<Vec<String>>,
+    ) {
+        let _span = info_span!("check", check = "split_releases").entered();
+        let mut releases: BTreeMap<&str, Vec<Part>> = BTreeMap::new();
+        for ar in self.artists.iter() {
+            for rl in ar.releases.iter() {
+                releases.entry(&rl.name).or_default().push((&ar.names, rl));
+            }
+        }
+
+        for (release, parts) in releases.into_iter().filter(|(_, p)| p.len() > 1) {
+            let mut groups: Vec<Vec<Part>> = Vec::new();
+            for part in parts {
+                let group = groups.iter_mut().find(|g| g.iter().any(|p| share_artist(p.0, part.0)));
+                match group {
+                    Some(g) => g.push(part),
+                    None => groups.push(vec![part]),
+                }
+            }
+
+            for mut group in groups.into_iter().filter(|g| g.len() > 1) {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                if !is_single_release(&group) {
+                    continue;
+                }
+                // stable, ties keep the order of the index
+                group.sort_by_key(|(_, r)| Reverse(r.songs.len()));
+                let parts = group.iter().map(|(names, r)| (*names, r.songs.as_slice())).collect();
+                let split = SplitRelease { release, parts };
+                info!(release, parts = split.parts.len(), "split release");
+                self.stats.split_releases.issues += 1;
+
+                let Value::Update(names) = f(&split) else { continue };
+                for (_, songs) in split.parts.iter().filter(|(n, _)| ***n != *names) {
+                    self.stats.split_releases.updates += songs.len();
+                    for song in songs.iter() {
+                        util::update_tag(
+                            &mut self.song_operations,
+                            &self.retag_excluded,
+                            song,
+                            "split release",
+                            |tu| tu.release_artists = Value::Update(names.clone()),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::testing::{song, SongTags};
+    use crate::MusicIndex;
+
+    #[test]
+    fn merges_release_split_by_featured_artist() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song(
+                "/music/Daft Punk/Random Access Memories/1.mp3",
+                "Random Access Memories",
+                "Daft Punk",
+            )
+            .with_track(1, Some(3)),
+            song(
+                "/music/Daft Punk, Pharrell Williams/Random Access Memories/2.mp3",
+                "Random Access Memories",
+                "Daft Punk, Pharrell Williams",
+            )
+            .with_track(2, Some(3)),
+            song(
+                "/music/Daft Punk/Random Access Memories/3.mp3",
+                "Random Access Memories",
+                "Daft Punk",
+            )
+            .with_track(3, Some(3)),
+            // same name but unrelated artists
+            song("/music/Foo/Greatest Hits/1.mp3", "Greatest Hits", "Foo").with_track(1, Some(3)),
+            song("/music/Bar/Greatest Hits/2.mp3", "Greatest Hits", "Bar").with_track(2, Some(3)),
+            // same track twice
+            song("/music/Baz/Live/1.mp3", "Live", "Baz").with_track(1, Some(3)),
+            song("/music/Baz/Live/1.mp3", "Live", "Baz")
+                .with_track(1, Some(3))
+                .with_release_artists(&["Baz", "Qux"]),
+        ];
+        let mut checks = Checks::from(&index);
+
+        let mut asked = Vec::new();
+        checks.check_split_releases(&mut |s| {
+            asked.push(s.release.to_owned());
+            assert_eq!(s.num_songs(), 3);
+            Value::Update(s.parts[0].0.to_vec())
+        });
+
+        assert_eq!(asked, ["Random Access Memories"]);
+        assert_eq!(checks.stats.split_releases.updates, 1);
+        let ops = &checks.song_operations;
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].song.track_number, Some(2));
+        let update = ops[0].tag_update.as_ref().unwrap();
+        assert_eq!(update.release_artists, Value::Update(vec!["Daft Punk".to_owned()]));
+    }
+}
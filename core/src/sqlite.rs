@@ -0,0 +1,219 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use rusqlite::{params, Connection, Row};
+
+use crate::{AudioInfo, CacheEntry, Changes, Fs, IndexCache, Metadata, Mode, MusicIndex, Song};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS files (
+    path TEXT PRIMARY KEY NOT NULL,
+    kind TEXT NOT NULL,
+    size INTEGER,
+    modified_secs INTEGER,
+    modified_nanos INTEGER,
+    mode INTEGER,
+    track_number INTEGER,
+    total_tracks INTEGER,
+    disc_number INTEGER,
+    total_discs INTEGER,
+    artists TEXT,
+    release_artists TEXT,
+    release TEXT,
+    title TEXT,
+    year INTEGER,
+    genre TEXT,
+    has_artwork INTEGER,
+    codec TEXT,
+    duration REAL,
+    bitrate INTEGER,
+    sample_rate INTEGER,
+    extra TEXT
+);
+";
+
+/// A SQLite database containing the index, which can be used instead of an [`IndexCache`] file
+/// and queried by other tools.
+///
+/// The `files` table contains one row per indexed file. `kind` is either `song`, `image` or
+/// `unknown`. Songs and unknown songs also contain their metadata, `artists` and
+/// `release_artists` are JSON arrays, `extra` is a JSON object of the custom fields.
+pub struct IndexDb {
+    conn: Connection,
+}
+
+impl IndexDb {
+    /// Opens or creates the database.
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        // databases created before custom fields were stored lack the column
+        if conn.prepare("SELECT extra FROM files LIMIT 0").is_err() {
+            conn.execute("ALTER TABLE files ADD COLUMN extra TEXT", [])?;
+        }
+        Ok(Self { conn })
+    }
+
+    /// Loads all rows with a size and modification time as cache entries.
+    pub fn load_cache(&self) -> rusqlite::Result<IndexCache> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, size, modified_secs, modified_nanos, mode, track_number, total_tracks,
+                disc_number, total_discs, artists, release_artists, release, title, year, genre,
+                has_artwork,
+                codec, duration, bitrate, sample_rate, extra
+            FROM files WHERE size IS NOT NULL AND modified_secs IS NOT NULL",
+        )?;
+        let entries = stmt.query_map([], read_cache_entry)?;
+
+        let mut cache = IndexCache::default();
+        for e in entries {
+            cache.insert(e?);
+        }
+        Ok(cache)
+    }
+
+    /// Points the rows of moved songs to their new paths, see [`IndexCache::update_paths`].
+    /// Returns the number of updated rows.
+    pub fn update_paths(&mut self, fs: &impl Fs, changes: &Changes) -> rusqlite::Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut updated = 0;
+        {
+            let mut stmt = tx.prepare("UPDATE files SET path = ?1 WHERE path = ?2")?;
+            for o in changes.song_operations.iter() {
+                let Some(new_path) = &o.new_path else { continue };
+                if !fs.exists(new_path) || fs.exists(&o.song.path) {
+                    continue;
+                }
+                let (Some(new), Some(old)) = (new_path.to_str(), o.song.path.to_str()) else {
+                    continue;
+                };
+                updated += stmt.execute([new, old])?;
+            }
+        }
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    /// Replaces the contents of the database with the index. The metadata of songs is taken from
+    /// the index cache if present. Paths that aren't valid UTF-8 are skipped.
+    pub fn save(&mut self, index: &MusicIndex) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM files", [])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO files VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19,
+                ?20, ?21, ?22)",
+            )?;
+            let cache = index.cache.as_ref();
+            let mut insert = |kind: &str, path: &Path, metadata: Option<&Metadata>| {
+                let Some(path_str) = path.to_str() else { return Ok(0) };
+                let entry = cache.and_then(|c| c.entry(path));
+                let m = entry.map(|e| &e.metadata).or(metadata);
+                let modified =
+                    entry.and_then(|e| e.modified.duration_since(SystemTime::UNIX_EPOCH).ok());
+                stmt.execute(params![
+                    path_str,
+                    kind,
+                    entry.map(|e| e.size as i64),
+                    modified.map(|d| d.as_secs() as i64),
+                    modified.map(|d| d.subsec_nanos()),
+                    m.and_then(|m| m.mode).map(|m| m.0),
+                    m.and_then(|m| m.track_number),
+                    m.and_then(|m| m.total_tracks),
+                    m.and_then(|m| m.disc_number),
+                    m.and_then(|m| m.total_discs),
+                    m.map(|m| json_list(&m.artists)),
+                    m.map(|m| json_list(&m.release_artists)),
+                    m.and_then(|m| m.release.as_deref()),
+                    m.and_then(|m| m.title.as_deref()),
+                    m.and_then(|m| m.year),
+                    m.and_then(|m| m.genre.as_deref()),
+                    m.map(|m| m.has_artwork),
+                    m.and_then(|m| m.audio.codec).map(|c| c.to_string()),
+                    m.and_then(|m| m.audio.duration).map(|d| d.as_secs_f64()),
+                    m.and_then(|m| m.audio.bitrate),
+                    m.and_then(|m| m.audio.sample_rate),
+                    m.map(|m| serde_json::to_string(&m.extra).unwrap_or_default()),
+                ])
+            };
+
+            for s in index.songs.iter() {
+                insert("song", &s.path, Some(&song_metadata(s)))?;
+            }
+            for p in index.unknown.iter() {
+                insert("unknown", p, None)?;
+            }
+            for p in index.images.iter() {
+                insert("image", p, None)?;
+            }
+        }
+        tx.commit()
+    }
+}
+
+fn song_metadata(song: &Song) -> Metadata {
+    Metadata {
+        mode: song.mode,
+        track_number: song.track_number,
+        total_tracks: song.total_tracks,
+        disc_number: song.disc_number,
+        total_discs: song.total_discs,
+        artists: song.artists.to_vec(),
+        release_artists: song.release_artists.to_vec(),
+        release: Some(song.release.to_string()),
+        title: Some(song.title.clone()),
+        year: song.year,
+        genre: song.genre.clone(),
+        has_artwork: song.has_artwork,
+        audio: song.audio,
+        extra: song.extra.clone(),
+    }
+}
+
+fn json_list(list: &[String]) -> String {
+    serde_json::to_string(list).unwrap_or_default()
+}
+
+fn read_cache_entry(row: &Row) -> rusqlite::Result<CacheEntry> {
+    let list = |i: usize| -> rusqlite::Result<Vec<String>> {
+        let json: Option<String> = row.get(i)?;
+        Ok(json.and_then(|j| serde_json::from_str(&j).ok()).unwrap_or_default())
+    };
+    let path: String = row.get(0)?;
+    let size: i64 = row.get(1)?;
+    let secs: i64 = row.get(2)?;
+    let nanos: Option<u32> = row.get(3)?;
+    let codec: Option<String> = row.get(16)?;
+    let duration: Option<f64> = row.get(17)?;
+    let extra: Option<String> = row.get(20)?;
+
+    let metadata = Metadata {
+        mode: row.get::<_, Option<u32>>(4)?.map(Mode),
+        track_number: row.get(5)?,
+        total_tracks: row.get(6)?,
+        disc_number: row.get(7)?,
+        total_discs: row.get(8)?,
+        artists: list(9)?,
+        release_artists: list(10)?,
+        release: row.get(11)?,
+        title: row.get(12)?,
+        year: row.get(13)?,
+        genre: row.get(14)?,
+        has_artwork: row.get::<_, Option<bool>>(15)?.unwrap_or(false),
+        audio: AudioInfo {
+            codec: codec.and_then(|c| c.parse().ok()),
+            duration: duration.and_then(|d| Duration::try_from_secs_f64(d).ok()),
+            bitrate: row.get(18)?,
+            sample_rate: row.get(19)?,
+        },
+        extra: extra.and_then(|e| serde_json::from_str(&e).ok()).unwrap_or_default(),
+    };
+
+    Ok(CacheEntry {
+        path: PathBuf::from(path),
+        size: size as u64,
+        modified: SystemTime::UNIX_EPOCH + Duration::new(secs as u64, nanos.unwrap_or(0)),
+        metadata,
+    })
+}
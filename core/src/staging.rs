@@ -0,0 +1,169 @@
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, info};
+
+use crate::fs::check_unchanged;
+use crate::{ChangedOnDisk, Changes, ExecOptions, Fs, RolledBack, SongOperation};
+
+/// The prefix of the staging dir inside of the output dir, see [`ExecOptions::staging_dir`]. The
+/// dir is hidden, so it's neither indexed nor picked up by media servers.
+pub const STAGING_DIR_NAME: &str = ".music-organizer-staging";
+
+impl Changes {
+    /// Copies the songs of each release into the staging dir first and renames them to their
+    /// new paths once all songs of the release are copied. With
+    /// [`ExecOptions::atomic_releases`] the copies of a release are discarded if one of them
+    /// failed, otherwise the others are still renamed into place.
+    pub(crate) fn execute_song_operations_staged(
+        &self,
+        fs: &impl Fs,
+        options: &ExecOptions,
+        staging_dir: &Path,
+        progress: &mut impl FnMut(&Path, u64, u64),
+        f: &mut impl FnMut(&SongOperation, Result<(), Box<dyn std::error::Error>>),
+    ) {
+        if let Err(e) = fs.create_dir_all(staging_dir) {
+            let e = format!("creating staging dir {}: {e}", staging_dir.display());
+            for o in self.song_operations.iter() {
+                f(o, Err(e.clone().into()));
+            }
+            return;
+        }
+
+        let mut staged_files = 0;
+        let releases = self.group_song_operations();
+        'releases: for release in releases.iter().flat_map(|a| a.releases.iter()) {
+            let mut results = Vec::new();
+            let mut failed = None;
+            for &o in release.song_operations.iter() {
+                if options.cancel.is_cancelled() {
+                    break;
+                }
+                let modified = self.modified.get(&o.song.path).copied();
+                let r = check_unchanged(fs, &o.song.path, o.new_path.as_deref(), modified)
+                    .map_err(Into::into)
+                    .and_then(|()| {
+                        let progress = &mut |copied, total| progress(&o.song.path, copied, total);
+                        let Some(new) = &o.new_path else {
+                            return o.execute(fs, options, progress).map(|()| None);
+                        };
+                        let mut name = staged_files.to_string();
+                        name.push('-');
+                        let mut name = std::ffi::OsString::from(name);
+                        name.push(new.file_name().unwrap_or_default());
+                        staged_files += 1;
+                        let staged = staging_dir.join(name);
+                        let staged_op =
+                            SongOperation { new_path: Some(staged.clone()), ..o.clone() };
+                        let r = staged_op.execute(fs, options, progress);
+                        if r.is_err() {
+                            // updating the tags of the staged copy may have failed
+                            fs.remove_file(&staged).ok();
+                        }
+                        r.map(|()| Some(staged))
+                    });
+                if r.as_ref().is_err_and(|e| !e.is::<ChangedOnDisk>()) && failed.is_none() {
+                    failed = Some(o.song.path.clone());
+                }
+                results.push((o, r));
+                if failed.is_some() && (options.fail_fast || options.atomic_releases) {
+                    break;
+                }
+            }
+
+            let discard = failed.as_ref().filter(|_| options.atomic_releases);
+            if let Some(failed) = discard {
+                info!(release = %release.name, failed = %failed.display(), "discarding release");
+            }
+            for (o, r) in results {
+                let r = match (r, discard) {
+                    (Ok(Some(staged)), Some(failed)) => {
+                        fs.remove_file(&staged).ok();
+                        Err(RolledBack { failed: failed.clone() }.into())
+                    }
+                    (Ok(Some(staged)), None) => place(fs, options, &staged, o),
+                    (Ok(None), _) => Ok(()),
+                    (Err(e), _) => Err(e),
+                };
+                f(o, r);
+            }
+            if failed.is_some() && options.fail_fast || options.cancel.is_cancelled() {
+                break 'releases;
+            }
+        }
+
+        // only fails if files couldn't be renamed into place, which are reported
+        fs.remove_dir(staging_dir).ok();
+    }
+}
+
+/// Renames a staged copy to the new path of its operation, unless another file appeared there
+/// in the meantime.
+fn place(
+    fs: &impl Fs,
+    options: &ExecOptions,
+    staged: &Path,
+    o: &SongOperation,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let new: &PathBuf = o.new_path.as_ref().unwrap();
+    if fs.exists(new) {
+        fs.remove_file(staged).ok();
+        return Err(ChangedOnDisk::Occupied(new.clone()).into());
+    }
+    debug!(staged = %staged.display(), new = %new.display(), "placing staged copy");
+    options
+        .retry
+        .run(|| fs.rename(staged, new))
+        .map_err(|e| format!("moving the staged copy {} into place: {e}", staged.display()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileOpType, MemFs, Song, TagUpdate, Value};
+
+    #[test]
+    fn copies_into_the_staging_dir_first() {
+        let fs = MemFs::new();
+        fs.add_file("/music/a.mp3", "a");
+        fs.add_file("/music/b.mp3", "b");
+        fs.add_dir("/out/Foo");
+        let op = |path: &str, new_path: &str| SongOperation {
+            new_path: Some(PathBuf::from(new_path)),
+            ..SongOperation::new(Song { path: PathBuf::from(path), ..Default::default() })
+        };
+        // tags are written to the disk, so updating the tags of the staged copy fails
+        let mut b = op("/music/b.mp3", "/out/Foo/b.mp3");
+        b.tag_update = Some(TagUpdate { year: Value::Update(2001), ..Default::default() });
+        let changes = Changes {
+            song_operations: vec![op("/music/a.mp3", "/out/Foo/a.mp3"), b],
+            ..Default::default()
+        };
+        let staging_dir = Path::new("/out").join(STAGING_DIR_NAME);
+        let options = ExecOptions {
+            op_type: FileOpType::Copy,
+            staging_dir: Some(staging_dir.clone()),
+            ..Default::default()
+        };
+
+        let mut results = Vec::new();
+        changes.execute_song_operations(&fs, &options, &mut |_, _, _| (), &mut |_, r| {
+            results.push(r.is_ok())
+        });
+
+        assert_eq!(results, [true, false]);
+        assert!(!fs.exists(Path::new("/out/Foo/b.mp3")));
+        assert_eq!(fs.file(Path::new("/out/Foo/a.mp3")).as_deref(), Some(&b"a"[..]));
+        assert!(!fs.exists(&staging_dir));
+
+        let atomic = ExecOptions { atomic_releases: true, ..options };
+        fs.remove_file(Path::new("/out/Foo/a.mp3")).unwrap();
+        let mut results = Vec::new();
+        changes.execute_song_operations(&fs, &atomic, &mut |_, _, _| (), &mut |_, r| {
+            results.push(r.map_err(|e| e.to_string()))
+        });
+        assert_eq!(results[0], Err("rolled back, since /music/b.mp3 failed".to_owned()));
+        assert!(!fs.exists(Path::new("/out/Foo/a.mp3")));
+        assert!(!fs.exists(&staging_dir));
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::MusicIndex;
+
+/// Aggregated information about an indexed library.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LibraryStats {
+    pub artists: usize,
+    pub releases: usize,
+    pub songs: usize,
+    pub images: usize,
+    pub unknown: usize,
+    /// Size of all indexed files in bytes.
+    pub total_size: u64,
+    pub total_duration: Duration,
+    /// Sorted by the number of songs, most common first.
+    pub formats: Vec<FormatStats>,
+    /// Sorted by the number of songs, most common first. Songs without a genre aren't included.
+    pub genres: Vec<GenreStats>,
+    /// Sorted by decade, oldest first. Songs without a year aren't included.
+    pub decades: Vec<DecadeStats>,
+    pub missing_artwork: usize,
+    pub missing_year: usize,
+    pub missing_genre: usize,
+    /// Sorted by size, largest first.
+    pub largest_releases: Vec<ReleaseStats>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FormatStats {
+    /// The codec, or the file extension if the codec is unknown.
+    pub name: String,
+    pub songs: usize,
+    pub size: u64,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GenreStats {
+    pub name: String,
+    pub songs: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecadeStats {
+    /// The first year of the decade, e.g. 1990.
+    pub decade: i32,
+    pub songs: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReleaseStats {
+    pub release_artists: Arc<[String]>,
+    pub release: Arc<str>,
+    pub songs: usize,
+    pub size: u64,
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |m| m.len())
+}
+
+impl LibraryStats {
+    /// Aggregates the index, `num_largest` is the maximum number of largest releases kept.
+    pub fn generate(index: &MusicIndex, num_largest: usize) -> Self {
+        let mut stats = Self {
+            songs: index.songs.len(),
+            images: index.images.len(),
+            unknown: index.unknown.len(),
+            ..Default::default()
+        };
+
+        let mut artists = HashSet::new();
+        let mut formats: HashMap<String, FormatStats> = HashMap::new();
+        let mut genres: HashMap<&str, usize> = HashMap::new();
+        let mut decades: HashMap<i32, usize> = HashMap::new();
+        let mut releases: HashMap<(&Arc<[String]>, &Arc<str>), ReleaseStats> = HashMap::new();
+        for s in index.songs.iter() {
+            let size = file_size(&s.path);
+            let duration = s.audio.duration.unwrap_or_default();
+            stats.total_size += size;
+            stats.total_duration += duration;
+            stats.missing_artwork += !s.has_artwork as usize;
+            stats.missing_year += s.year.is_none() as usize;
+            stats.missing_genre += s.genre.is_none() as usize;
+
+            artists.insert(&s.release_artists);
+            if let Some(genre) = &s.genre {
+                *genres.entry(genre.as_str()).or_default() += 1;
+            }
+            if let Some(year) = s.year {
+                *decades.entry(year.div_euclid(10) * 10).or_default() += 1;
+            }
+
+            let name = match s.audio.codec {
+                Some(c) => c.to_string(),
+                None => s.path.extension().unwrap_or_default().to_string_lossy().to_uppercase(),
+            };
+            let format = formats
+                .entry(name.clone())
+                .or_insert_with(|| FormatStats { name, ..Default::default() });
+            format.songs += 1;
+            format.size += size;
+            format.duration += duration;
+
+            let release =
+                releases.entry((&s.release_artists, &s.release)).or_insert_with(|| ReleaseStats {
+                    release_artists: Arc::clone(&s.release_artists),
+                    release: Arc::clone(&s.release),
+                    ..Default::default()
+                });
+            release.songs += 1;
+            release.size += size;
+        }
+        for p in index.images.iter().chain(index.unknown.iter()) {
+            stats.total_size += file_size(p);
+        }
+
+        stats.artists = artists.len();
+        stats.releases = releases.len();
+
+        stats.formats = formats.into_values().collect();
+        stats.formats.sort_by(|a, b| b.songs.cmp(&a.songs).then_with(|| a.name.cmp(&b.name)));
+
+        stats.genres = (genres.into_iter())
+            .map(|(name, songs)| GenreStats { name: name.to_owned(), songs })
+            .collect();
+        stats.genres.sort_by(|a, b| b.songs.cmp(&a.songs).then_with(|| a.name.cmp(&b.name)));
+
+        stats.decades =
+            decades.into_iter().map(|(decade, songs)| DecadeStats { decade, songs }).collect();
+        stats.decades.sort_by_key(|d| d.decade);
+
+        stats.largest_releases = releases.into_values().collect();
+        stats.largest_releases.sort_by_key(|r| std::cmp::Reverse(r.size));
+        stats.largest_releases.truncate(num_largest);
+
+        stats
+    }
+}
@@ -0,0 +1,350 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{
+    ArtistSeparators, ChangeOptions, ChangedOnDisk, Changes, Checks, Cleanup, ExecOptions, Item,
+    MemFs, MusicIndex, Progress, RealFs, Song, TagUpdate, Value,
+};
+
+/// Set to update golden files instead of comparing against them, see [`assert_golden_plan`].
+pub const UPDATE_GOLDEN_ENV: &str = "UPDATE_GOLDEN";
+
+/// A tiny valid mp3 file without a tag: four silent frames.
+pub const FIXTURE_MP3: &[u8] = include_bytes!("../tests/fixtures/silence.mp3");
+/// A tiny valid m4a file without metadata: a file type and a movie header.
+pub const FIXTURE_M4A: &[u8] = include_bytes!("../tests/fixtures/silence.m4a");
+/// A tiny valid flac file without vorbis comments: the stream info and one silent frame.
+pub const FIXTURE_FLAC: &[u8] = include_bytes!("../tests/fixtures/silence.flac");
+
+/// Builds a synthetic library with generated tags, to test the changes planned for it. The songs
+/// are placed directly inside of the music dir, named after their tags, so a layout moves them.
+#[derive(Clone, Debug, Default)]
+pub struct LibraryBuilder {
+    music_dir: PathBuf,
+    songs: Vec<Song>,
+}
+
+impl LibraryBuilder {
+    pub fn new(music_dir: impl Into<PathBuf>) -> Self {
+        Self { music_dir: music_dir.into(), songs: Vec::new() }
+    }
+
+    /// Adds a release with the tracks `Track 1`, `Track 2`... of the artist.
+    pub fn release(mut self, artist: &str, release: &str, tracks: u16) -> Self {
+        for track in 1..=tracks {
+            let name = format!("{artist} - {release} - {track:02}.mp3");
+            self.songs.push(Song {
+                path: self.music_dir.join(name),
+                track_number: Some(track),
+                total_tracks: Some(tracks),
+                artists: Arc::from([artist.to_owned()]),
+                release_artists: Arc::from([artist.to_owned()]),
+                release: Arc::from(release),
+                title: format!("Track {track}"),
+                ..Default::default()
+            });
+        }
+        self
+    }
+
+    /// Adds a song with custom tags, a relative path is joined with the music dir.
+    pub fn song(mut self, mut song: Song) -> Self {
+        song.path = self.music_dir.join(song.path);
+        self.songs.push(song);
+        self
+    }
+
+    /// Changes the tags of all songs added so far, e.g. to set a year or genre.
+    pub fn map(mut self, f: impl FnMut(&mut Song)) -> Self {
+        self.songs.iter_mut().for_each(f);
+        self
+    }
+
+    /// An index of the songs, as if it was read from the music dir.
+    pub fn index(&self) -> MusicIndex {
+        let mut index = MusicIndex::from(self.music_dir.clone());
+        index.songs = self.songs.clone();
+        index
+    }
+
+    /// Writes a fixture tagged like the song for every song to the disk, so reading the music
+    /// dir yields the songs of [`Self::index`]. The fixture is chosen by the extension, songs
+    /// with other extensions are written as empty files.
+    pub fn write(&self, separators: &ArtistSeparators) -> Result<(), Box<dyn std::error::Error>> {
+        for s in self.songs.iter() {
+            if let Some(parent) = s.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let ext = s.path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+            let fixture = match ext.to_ascii_lowercase().as_str() {
+                "mp3" => FIXTURE_MP3,
+                "m4a" => FIXTURE_M4A,
+                "flac" => FIXTURE_FLAC,
+                _ => &[],
+            };
+            std::fs::write(&s.path, fixture)?;
+            tags(s)
+                .execute(&s.path, separators)
+                .map_err(|e| format!("writing the tags of {}: {e}", s.path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// A [`MemFs`] containing an empty file for every song.
+    pub fn fs(&self) -> MemFs {
+        let fs = MemFs::new();
+        fs.add_dir(&self.music_dir);
+        for s in self.songs.iter() {
+            fs.add_file(&s.path, Vec::new());
+        }
+        fs
+    }
+}
+
+/// A song with only the tags needed to group it by release and artist, for unit tests that need
+/// songs in specific dirs, other tests build a library using [`LibraryBuilder`].
+#[cfg(test)]
+pub(crate) fn song(path: &str, release: &str, artist: &str) -> Song {
+    let artists: Arc<[String]> = Arc::from([artist.to_owned()]);
+    Song {
+        path: PathBuf::from(path),
+        release_artists: artists.clone(),
+        artists,
+        release: Arc::from(release),
+        ..Default::default()
+    }
+}
+
+/// Chainable setters for the tags of a [`song`] that unit tests commonly need, e.g.
+/// `song("/music/1.mp3", "Bar", "Foo").with_track(1, Some(12))`, other tags are set using the
+/// struct update syntax.
+#[cfg(test)]
+pub(crate) trait SongTags {
+    fn with_track(self, track: u16, total: Option<u16>) -> Self;
+    fn with_disc(self, disc: u16, total: Option<u16>) -> Self;
+    fn with_title(self, title: &str) -> Self;
+    fn with_release_artists(self, artists: &[&str]) -> Self;
+}
+
+#[cfg(test)]
+impl SongTags for Song {
+    fn with_track(self, track: u16, total: Option<u16>) -> Self {
+        Self { track_number: Some(track), total_tracks: total, ..self }
+    }
+
+    fn with_disc(self, disc: u16, total: Option<u16>) -> Self {
+        Self { disc_number: Some(disc), total_discs: total, ..self }
+    }
+
+    fn with_title(self, title: &str) -> Self {
+        Self { title: title.to_owned(), ..self }
+    }
+
+    fn with_release_artists(self, artists: &[&str]) -> Self {
+        let artists: Vec<String> = artists.iter().map(|&a| a.to_owned()).collect();
+        Self { release_artists: Arc::from(artists), ..self }
+    }
+}
+
+/// A tag update that sets every tag of the song.
+fn tags(song: &Song) -> TagUpdate {
+    fn value<T>(v: Option<T>) -> Value<T> {
+        v.map_or(Value::Unchanged, Value::Update)
+    }
+    let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_owned());
+    TagUpdate {
+        track_number: value(song.track_number),
+        total_tracks: value(song.total_tracks),
+        disc_number: value(song.disc_number),
+        total_discs: value(song.total_discs),
+        artists: value((!song.artists.is_empty()).then(|| song.artists.to_vec())),
+        release_artists: value(
+            (!song.release_artists.is_empty()).then(|| song.release_artists.to_vec()),
+        ),
+        release: value(non_empty(&song.release)),
+        title: value(non_empty(&song.title)),
+        genre: value(song.genre.clone()),
+        year: value(song.year),
+        extra: (song.extra.iter()).map(|(k, v)| (k.clone(), Value::Update(v.clone()))).collect(),
+        ..Default::default()
+    }
+}
+
+/// The answers to the questions of the checks run by [`run_pipeline`], like an answers file of
+/// the command line interface.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptedAnswers {
+    /// The spelling inconsistent release artists are changed to, unchanged keeps both.
+    pub release_artists: Value<Vec<String>>,
+    /// Whether the corrections of invalid track and disc numbers are applied.
+    pub fix_numbers: bool,
+}
+
+/// The outcome of [`run_pipeline`].
+#[derive(Debug)]
+pub struct PipelineRun {
+    pub changes: Changes,
+    pub cleanup: Cleanup,
+    /// The operations that failed or were skipped, with their path and error.
+    pub failures: Vec<(PathBuf, String)>,
+    /// The output dir, indexed again after the changes were written.
+    pub index: MusicIndex,
+}
+
+#[derive(Default)]
+struct Failures(Vec<(PathBuf, String)>);
+
+impl Progress for Failures {
+    fn item_failed(&mut self, item: Item, error: &dyn std::error::Error) {
+        self.0.push((item.path().to_owned(), error.to_string()));
+    }
+
+    fn item_skipped(&mut self, item: Item, change: &ChangedOnDisk) {
+        self.0.push((item.path().to_owned(), change.to_string()));
+    }
+}
+
+/// Runs the whole pipeline against the real files of the music dir, e.g. written by
+/// [`LibraryBuilder::write`]: indexing, the checks with the scripted answers, planning and
+/// writing the changes and cleaning up empty dirs. Meant as a smoke test of a build, so
+/// packagers can check that the enabled tag backends read and write actual files.
+pub fn run_pipeline(
+    music_dir: &Path,
+    options: &ChangeOptions,
+    exec_options: &ExecOptions,
+    answers: &ScriptedAnswers,
+) -> PipelineRun {
+    let mut index = MusicIndex::from(music_dir.to_owned());
+    index.read(&mut |_| ());
+
+    let mut checks = Checks::from(&index);
+    checks.fill_release_tags();
+    checks.check_whitespace(&mut |_, _| ());
+    checks.check_inconsitent_release_artists(&mut |_, _| answers.release_artists.clone());
+    checks.check_invalid_numbers(&mut |_, _, _| answers.fix_numbers);
+
+    let changes = Changes::generate(&RealFs, checks, options);
+    let mut failures = Failures::default();
+    changes.execute(&RealFs, exec_options, &mut failures);
+
+    let mut cleanup = Cleanup::from(music_dir.to_owned());
+    cleanup.check(&RealFs, &mut |_| ());
+    cleanup.execute(&RealFs, exec_options, &mut failures);
+
+    let mut index = MusicIndex::from(options.output_dir.clone());
+    index.read(&mut |_| ());
+    PipelineRun { changes, cleanup, failures: failures.0, index }
+}
+
+/// Renders the operations of the plan, one per line and in the order they are executed, with
+/// paths relative to the music dir. Tag updates only list the changed fields.
+pub fn render_plan(changes: &Changes, music_dir: &Path) -> String {
+    let path = |p: &Path| p.strip_prefix(music_dir).unwrap_or(p).display().to_string();
+    let mut plan = String::new();
+    for d in changes.dir_creations.iter() {
+        writeln!(plan, "create {}", path(&d.path)).ok();
+    }
+    for o in changes.song_operations.iter() {
+        match &o.new_path {
+            Some(new_path) => writeln!(plan, "song {} -> {}", path(&o.song.path), path(new_path)),
+            None => writeln!(plan, "song {}", path(&o.song.path)),
+        }
+        .ok();
+        if let Some(mode) = o.mode_update {
+            writeln!(plan, "  mode {mode}").ok();
+        }
+        if let Some(tag_update) = &o.tag_update {
+            render_tag_update(&mut plan, tag_update);
+        }
+    }
+    for f in changes.file_operations.iter() {
+        writeln!(plan, "file {} -> {}", path(&f.old_path), path(&f.new_path)).ok();
+    }
+    for u in changes.playlist_updates.iter() {
+        writeln!(plan, "update {}", path(&u.path)).ok();
+        for e in u.entries.iter() {
+            writeln!(plan, "  {}: {} -> {}", e.line, e.old, e.new).ok();
+        }
+    }
+    for c in changes.playlist_creations.iter() {
+        writeln!(plan, "playlist {}", path(&c.path)).ok();
+        for s in c.songs.iter() {
+            writeln!(plan, "  {}", path(s)).ok();
+        }
+    }
+    for q in changes.quarantines.iter() {
+        writeln!(plan, "quarantine {} {} -> {}", q.reason, path(&q.path), path(&q.new_path)).ok();
+    }
+    plan
+}
+
+fn render_tag_update(plan: &mut String, tag_update: &TagUpdate) {
+    let fields = serde_json::to_value(tag_update).expect("serializing tag update");
+    let serde_json::Value::Object(mut fields) = fields else { return };
+    let extra = fields.remove("extra");
+    let extra = extra.as_ref().and_then(serde_json::Value::as_object).into_iter().flatten();
+    for (name, value) in fields.iter().chain(extra) {
+        let update = value.get("Update");
+        match (value.as_str(), update) {
+            (Some("Remove"), _) => writeln!(plan, "  {name} removed"),
+            (_, Some(serde_json::Value::Array(a))) if name == "artwork" => {
+                writeln!(plan, "  {name} = {} bytes", a.len())
+            }
+            (_, Some(new)) => writeln!(plan, "  {name} = {new}"),
+            _ => Ok(()),
+        }
+        .ok();
+    }
+}
+
+/// Compares the [rendered](render_plan) plan with the golden file, or writes it if the
+/// [`UPDATE_GOLDEN_ENV`] variable is set or the file doesn't exist yet.
+///
+/// # Panics
+///
+/// If the plan differs from the golden file or the file can't be read or written.
+#[track_caller]
+pub fn assert_golden_plan(changes: &Changes, music_dir: &Path, golden: impl AsRef<Path>) {
+    let golden = golden.as_ref();
+    let plan = render_plan(changes, music_dir);
+    if std::env::var_os(UPDATE_GOLDEN_ENV).is_some() || !golden.exists() {
+        if let Some(parent) = golden.parent() {
+            std::fs::create_dir_all(parent).expect("creating golden dir");
+        }
+        std::fs::write(golden, plan).expect("writing golden file");
+        return;
+    }
+    let expected = std::fs::read_to_string(golden).expect("reading golden file");
+    assert!(
+        plan == expected,
+        "plan differs from {}, set {UPDATE_GOLDEN_ENV}=1 to update it\n\
+        --- expected\n{expected}\n--- actual\n{plan}",
+        golden.display(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChangeOptions, Checks};
+
+    #[test]
+    fn default_layout_plan() {
+        let library = LibraryBuilder::new("/music")
+            .release("Daft Punk", "Discovery", 2)
+            .release("Air", "Moon Safari", 1)
+            .map(|s| s.year = Some(2001))
+            .song(Song {
+                path: PathBuf::from("untagged.mp3"),
+                title: "untagged".to_owned(),
+                ..Default::default()
+            });
+        let index = library.index();
+        let options = ChangeOptions::from(PathBuf::from("/music"));
+        let changes = Changes::generate(&library.fs(), Checks::from(&index), &options);
+
+        let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/default_layout.plan");
+        assert_golden_plan(&changes, Path::new("/music"), golden);
+    }
+}
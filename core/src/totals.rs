@@ -0,0 +1,227 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use tracing::{debug, info, info_span};
+
+use crate::{util, Checks, Release, ReleaseArtists, Song, SongOperation, TagUpdate, Value};
+
+/// How [`Checks::check_inconsistent_totals`] chooses the total all songs of a disc or release
+/// get.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TotalsPolicy {
+    /// The highest total or number.
+    #[default]
+    Max,
+    /// The number of songs of the disc or the number of discs of the release.
+    Count,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TotalKind {
+    /// The total tracks of a disc, songs without a disc number are a disc of their own.
+    Tracks {
+        disc: Option<u16>,
+    },
+    Discs,
+}
+
+/// Songs of a disc or release whose total tracks or total discs differ or are missing on some
+/// of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InconsistentTotals<'a> {
+    pub kind: TotalKind,
+    /// Sorted by disc and track number.
+    pub songs: Vec<&'a Song>,
+    /// The highest total or number.
+    pub max: u16,
+    /// The number of songs or discs.
+    pub count: u16,
+}
+
+impl InconsistentTotals<'_> {
+    pub fn total(&self, song: &Song) -> Option<u16> {
+        match self.kind {
+            TotalKind::Tracks { .. } => song.total_tracks,
+            TotalKind::Discs => song.total_discs,
+        }
+    }
+
+    /// The distinct totals that are present, ascending.
+    pub fn totals(&self) -> BTreeSet<u16> {
+        self.songs.iter().filter_map(|s| self.total(s)).collect()
+    }
+
+    pub fn consensus(&self, policy: TotalsPolicy) -> u16 {
+        match policy {
+            TotalsPolicy::Max => self.max,
+            TotalsPolicy::Count => self.count,
+        }
+    }
+
+    /// Whether the present totals disagree and the policies would choose different totals, so
+    /// neither is clearly right, e.g. the release might be incomplete.
+    pub fn is_ambiguous(&self) -> bool {
+        self.totals().len() > 1 && self.max != self.count
+    }
+
+    fn field<'b>(&self, t: &'b mut TagUpdate) -> &'b mut Value<u16> {
+        match self.kind {
+            TotalKind::Tracks { .. } => &mut t.total_tracks,
+            TotalKind::Discs => &mut t.total_discs,
+        }
+    }
+
+    /// Whether another check already queued an update of the total of one of the songs.
+    fn is_queued(&self, song_operations: &[SongOperation]) -> bool {
+        let mut updates = (song_operations.iter())
+            .filter(|o| self.songs.iter().any(|s| s.path == o.song.path))
+            .filter_map(|o| o.tag_update.as_ref());
+        updates.any(|t| match self.kind {
+            TotalKind::Tracks { .. } => !t.total_tracks.is_unchanged(),
+            TotalKind::Discs => !t.total_discs.is_unchanged(),
+        })
+    }
+}
+
+fn to_u16(n: usize) -> u16 {
+    u16::try_from(n).unwrap_or(u16::MAX)
+}
+
+/// The discs and the release if their songs have different totals, or the totals are only
+/// missing on some songs.
+fn inconsistent_totals<'a>(release: &Release<'a>) -> Vec<InconsistentTotals<'a>> {
+    let mut discs: BTreeMap<Option<u16>, Vec<&Song>> = BTreeMap::new();
+    for &s in release.songs.iter() {
+        discs.entry(s.disc_number).or_default().push(s);
+    }
+
+    let mut inconsistent = Vec::new();
+    for (&disc, songs) in discs.iter() {
+        let totals: BTreeSet<Option<u16>> = songs.iter().map(|s| s.total_tracks).collect();
+        if totals.len() < 2 {
+            continue;
+        }
+        let numbers = songs.iter().filter_map(|s| s.total_tracks.max(s.track_number));
+        inconsistent.push(InconsistentTotals {
+            kind: TotalKind::Tracks { disc },
+            songs: songs.clone(),
+            max: numbers.max().unwrap_or(0),
+            count: to_u16(songs.len()),
+        });
+    }
+
+    let totals: BTreeSet<Option<u16>> = release.songs.iter().map(|s| s.total_discs).collect();
+    if totals.len() > 1 {
+        let numbers = release.songs.iter().filter_map(|s| s.total_discs.max(s.disc_number));
+        inconsistent.push(InconsistentTotals {
+            kind: TotalKind::Discs,
+            songs: release.songs.clone(),
+            max: numbers.max().unwrap_or(0),
+            count: to_u16(discs.keys().flatten().count().max(1)),
+        });
+    }
+
+    for t in inconsistent.iter_mut() {
+        t.songs.sort_by_key(|s| (s.disc_number, s.track_number));
+    }
+    inconsistent
+}
+
+impl Checks<'_> {
+    /// Checks for discs whose songs have different or partially missing total tracks and
+    /// releases whose songs have different or partially missing total discs. All songs get the
+    /// total chosen by the policy, `f` is only called if the totals are ambiguous, see
+    /// [`InconsistentTotals::is_ambiguous`], and may return the total. Totals another check
+    /// already updates, e.g. [`Checks::check_invalid_numbers`], are skipped.
+    pub fn check_inconsistent_totals(
+        &mut self,
+        policy: TotalsPolicy,
+        f: &mut impl FnMut(&ReleaseArtists, &Release, &InconsistentTotals) -> Value<u16>,
+    ) {
+        let _span = info_span!("check", check = "inconsistent_totals").entered();
+        for ar in self.artists.iter() {
+            for rl in ar.releases.iter() {
+                if self.cancel.is_cancelled() {
+                    return;
+                }
+                for totals in inconsistent_totals(rl) {
+                    if totals.is_queued(&self.song_operations) {
+                        continue;
+                    }
+                    self.stats.inconsistent_totals.issues += 1;
+                    let value = match totals.is_ambiguous() {
+                        true => {
+                            info!(artists = ?ar.names, release = %rl.name, kind = ?totals.kind, "ambiguous totals");
+                            f(ar, rl, &totals)
+                        }
+                        false => Value::Update(totals.consensus(policy)),
+                    };
+                    debug!(artists = ?ar.names, release = %rl.name, kind = ?totals.kind, ?value, "inconsistent totals");
+                    for &s in totals.songs.iter() {
+                        let new = match value {
+                            Value::Update(t) if totals.total(s) != Some(t) => Value::Update(t),
+                            Value::Remove if totals.total(s).is_some() => Value::Remove,
+                            _ => continue,
+                        };
+                        self.stats.inconsistent_totals.updates += 1;
+                        util::update_tag(
+                            &mut self.song_operations,
+                            &self.retag_excluded,
+                            s,
+                            "inconsistent totals",
+                            |t| *totals.field(t) = new,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::testing::{song, SongTags};
+    use crate::MusicIndex;
+
+    #[test]
+    fn normalizes_totals_and_asks_if_ambiguous() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            // missing on one song
+            song("/music/A/1-1.mp3", "A", "Foo").with_disc(1, Some(1)).with_track(1, Some(2)),
+            song("/music/A/1-2.mp3", "A", "Foo").with_disc(1, Some(1)).with_track(2, None),
+            // 12 or 10, the release might be incomplete
+            song("/music/B/1-1.mp3", "B", "Foo").with_disc(1, Some(1)).with_track(1, Some(12)),
+            song("/music/B/1-2.mp3", "B", "Foo").with_disc(1, Some(1)).with_track(2, Some(10)),
+            // consistent per disc, missing total discs on one song
+            song("/music/C/1-1.mp3", "C", "Foo").with_disc(1, Some(2)).with_track(1, Some(1)),
+            song("/music/C/2-1.mp3", "C", "Foo").with_disc(2, Some(2)).with_track(1, Some(2)),
+            song("/music/C/2-2.mp3", "C", "Foo").with_disc(2, None).with_track(2, Some(2)),
+        ];
+        let mut checks = Checks::from(&index);
+
+        let mut asked = Vec::new();
+        checks.check_inconsistent_totals(TotalsPolicy::Count, &mut |_, r, t| {
+            asked.push((r.name.to_string(), t.max, t.count));
+            Value::Unchanged
+        });
+
+        assert_eq!(asked, [("B".to_owned(), 12, 2)]);
+        assert_eq!(checks.stats.inconsistent_totals.issues, 3);
+        let updates: Vec<(&str, Value<u16>, Value<u16>)> = (checks.song_operations.iter())
+            .map(|o| {
+                let t = o.tag_update.as_ref().unwrap();
+                (o.song.path.to_str().unwrap(), t.total_tracks, t.total_discs)
+            })
+            .collect();
+        assert_eq!(
+            updates,
+            [
+                ("/music/A/1-2.mp3", Value::Update(2), Value::Unchanged),
+                ("/music/C/2-2.mp3", Value::Unchanged, Value::Update(2)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use tracing::debug;
+
+use crate::changes::temp_path;
+use crate::Metadata;
+
+/// Extensions of lossless files that are transcoded. ALAC in m4a files isn't detected, since
+/// mirroring doesn't read the files, so they are copied as they are.
+const LOSSLESS_EXTENSIONS: [&str; 4] = ["flac", "wav", "aiff", "ape"];
+
+/// Transcodes lossless songs to a lossy codec using ffmpeg, e.g. to fit a library on a phone.
+/// Lossy songs are copied as they are.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transcode {
+    pub codec: TranscodeCodec,
+    /// Bitrate in kbit/s.
+    pub bitrate: u32,
+    /// The ffmpeg executable, looked up in the `PATH` if it's just a name.
+    pub ffmpeg: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TranscodeCodec {
+    Opus,
+    Mp3,
+}
+
+impl std::str::FromStr for TranscodeCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "opus" => Ok(Self::Opus),
+            "mp3" => Ok(Self::Mp3),
+            _ => Err(format!("Unknown codec {s}, expected opus or mp3")),
+        }
+    }
+}
+
+impl TranscodeCodec {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Opus => "opus",
+            Self::Mp3 => "mp3",
+        }
+    }
+
+    fn encoder(&self) -> &'static str {
+        match self {
+            Self::Opus => "libopus",
+            Self::Mp3 => "libmp3lame",
+        }
+    }
+}
+
+pub(crate) fn is_lossless(path: &Path) -> bool {
+    let ext = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+    LOSSLESS_EXTENSIONS.iter().any(|e| e.eq_ignore_ascii_case(ext))
+}
+
+impl Transcode {
+    pub fn new(codec: TranscodeCodec, bitrate: u32) -> Self {
+        Self { codec, bitrate, ffmpeg: PathBuf::from("ffmpeg") }
+    }
+
+    /// The size of the transcoded song, estimated from its duration and the bitrate. `len` is the
+    /// size of the source, which is used if the duration is unknown.
+    pub(crate) fn estimated_size(&self, source: &Path, len: u64) -> u64 {
+        let duration = Metadata::read_from(source).audio.duration;
+        let estimate = duration.map(|d| (d.as_secs_f64() * self.bitrate as f64 * 125.0) as u64);
+        estimate.map_or(len, |e| e.min(len))
+    }
+
+    /// Writes to a temporary file next to `dest` first, so an interrupted transcode never leaves
+    /// a partial file behind. Tags are kept, embedded artworks aren't.
+    pub(crate) fn execute(&self, source: &Path, dest: &Path) -> std::io::Result<()> {
+        let temp = temp_path(dest);
+        let output = Command::new(&self.ffmpeg)
+            .args(["-nostdin", "-y", "-loglevel", "error", "-i"])
+            .arg(source)
+            .args(["-map", "0:a:0", "-map_metadata", "0", "-c:a", self.codec.encoder()])
+            .arg("-b:a")
+            .arg(format!("{}k", self.bitrate))
+            .args(["-f", self.codec.extension()])
+            .arg(&temp)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| {
+                let message = format!("running {}: {e}", self.ffmpeg.display());
+                std::io::Error::new(e.kind(), message)
+            })?;
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&temp);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.lines().last().unwrap_or("ffmpeg failed").trim().to_owned();
+            return Err(std::io::Error::other(message));
+        }
+        debug!(source = %source.display(), dest = %dest.display(), "transcoded");
+        std::fs::rename(&temp, dest)
+    }
+}
@@ -0,0 +1,228 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::fs::is_song_extension;
+use crate::index::song_from_metadata;
+use crate::{Metadata, MusicIndex, Song, SongOperation, TagUpdate, Value};
+
+/// Tags supplied for an unknown song, which is missing release artists, artists, release or
+/// title.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MissingTags {
+    pub release_artists: Vec<String>,
+    /// Defaults to the release artists if empty.
+    pub artists: Vec<String>,
+    pub release: String,
+    pub title: String,
+    pub track_number: Option<u16>,
+}
+
+impl MissingTags {
+    /// Fills missing values with the ones present in the metadata.
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        Self {
+            release_artists: metadata.release_artists().unwrap_or_default().to_vec(),
+            artists: metadata.artists.clone(),
+            release: metadata.release.clone().unwrap_or_default(),
+            title: metadata.title.clone().unwrap_or_default(),
+            track_number: metadata.track_number,
+        }
+    }
+
+    /// Infers tags from a path relative to the music dir, using common naming patterns:
+    /// - `Artist/Release/01 - Title.mp3`
+    /// - `Artist/Release/01 - Artist - Title.mp3`
+    /// - `Release/Artist - 01 - Title.mp3`
+    /// - `Artist - Release - 01 - Title.mp3`
+    ///
+    /// Leading track numbers may also be separated by a space or a dot, like `01. Title`.
+    pub fn infer_from_path(path: &Path) -> Self {
+        let mut dirs = path.parent().into_iter().flat_map(Path::iter).rev();
+        let dir_release = dirs.next().map(|d| d.to_string_lossy().trim().to_owned());
+        let dir_artist = dirs.next().map(|d| d.to_string_lossy().trim().to_owned());
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let parts: Vec<&str> = stem.split(" - ").map(str::trim).collect();
+
+        let mut new = Self::default();
+        let mut artist = None;
+        let mut release = None;
+        match parts[..] {
+            [a, r, n, t] if parse_track(n).is_some() => {
+                (artist, release) = (Some(a), Some(r));
+                (new.track_number, new.title) = (parse_track(n), t.to_owned());
+            }
+            [n, a, t] if parse_track(n).is_some() => {
+                new.artists = vec![a.to_owned()];
+                (new.track_number, new.title) = (parse_track(n), t.to_owned());
+            }
+            [a, n, t] if parse_track(n).is_some() => {
+                artist = Some(a);
+                (new.track_number, new.title) = (parse_track(n), t.to_owned());
+            }
+            [n, t] if parse_track(n).is_some() => {
+                (new.track_number, new.title) = (parse_track(n), t.to_owned());
+            }
+            _ => match split_leading_track(&stem) {
+                Some((n, t)) => (new.track_number, new.title) = (Some(n), t.to_owned()),
+                None => new.title = stem.trim().to_owned(),
+            },
+        }
+
+        new.release = release.map(str::to_owned).or(dir_release).unwrap_or_default();
+        let artist = artist.map(str::to_owned).or(dir_artist).or(new.artists.first().cloned());
+        new.release_artists.extend(artist);
+        new
+    }
+
+    /// Fills missing values with the ones inferred from the path, see [`Self::infer_from_path`].
+    pub fn guess_from_path(&mut self, path: &Path) {
+        let inferred = Self::infer_from_path(path);
+        if self.release_artists.is_empty() {
+            self.release_artists = inferred.release_artists;
+        }
+        if self.artists.is_empty() {
+            self.artists = inferred.artists;
+        }
+        if self.release.is_empty() {
+            self.release = inferred.release;
+        }
+        if self.title.is_empty() {
+            self.title = inferred.title;
+        }
+        if self.track_number.is_none() {
+            self.track_number = inferred.track_number;
+        }
+    }
+
+    /// Whether release artists, release and title are present.
+    pub fn is_complete(&self) -> bool {
+        !self.release_artists.is_empty() && !self.release.is_empty() && !self.title.is_empty()
+    }
+}
+
+impl MusicIndex {
+    /// Unknown files that are songs, which are missing some tags.
+    pub fn unknown_songs(&self) -> impl Iterator<Item = &PathBuf> {
+        self.unknown.iter().filter(|p| p.extension().is_some_and(is_song_extension))
+    }
+
+    /// Moves an unknown song into the songs, using the tags. The returned operation writes the
+    /// tags and has to be added to the [`Checks`](crate::Checks) created from this index. Returns
+    /// `None` if the path isn't unknown or the tags are incomplete.
+    pub fn resolve_unknown(
+        &mut self,
+        path: &Path,
+        metadata: Metadata,
+        tags: MissingTags,
+    ) -> Option<SongOperation> {
+        if !tags.is_complete() {
+            return None;
+        }
+        let pos = self.unknown.iter().position(|p| p == path)?;
+        let path = self.unknown.remove(pos);
+
+        let artists = match tags.artists.is_empty() {
+            true => tags.release_artists.clone(),
+            false => tags.artists.clone(),
+        };
+        let old = Song {
+            path: path.clone(),
+            mode: metadata.mode,
+            track_number: metadata.track_number,
+            total_tracks: metadata.total_tracks,
+            disc_number: metadata.disc_number,
+            total_discs: metadata.total_discs,
+            release_artists: Arc::from(metadata.release_artists().unwrap_or_default()),
+            artists: Arc::from(metadata.song_artists().unwrap_or_default()),
+            release: Arc::from(metadata.release.as_deref().unwrap_or_default()),
+            title: metadata.title.clone().unwrap_or_default(),
+            year: metadata.year,
+            genre: metadata.genre.clone(),
+            has_artwork: metadata.has_artwork,
+            audio: metadata.audio,
+            extra: metadata.extra.clone(),
+        };
+
+        let update = TagUpdate {
+            release_artists: value(
+                *old.release_artists != tags.release_artists,
+                &tags.release_artists,
+            ),
+            artists: value(*old.artists != artists, &artists),
+            release: value(*old.release != tags.release, &tags.release),
+            title: value(old.title != tags.title, &tags.title),
+            track_number: match tags.track_number {
+                Some(n) if old.track_number != Some(n) => Value::Update(n),
+                _ => Value::Unchanged,
+            },
+            ..Default::default()
+        };
+
+        let metadata = Metadata {
+            release_artists: tags.release_artists,
+            artists,
+            release: Some(tags.release),
+            title: Some(tags.title),
+            track_number: tags.track_number.or(metadata.track_number),
+            ..metadata
+        };
+        let song = song_from_metadata(path, metadata, &self.artist_separators, &self.custom_fields)
+            .ok()?;
+        self.songs.push(song);
+
+        let mut op = SongOperation::new(old);
+        op.tag_update = Some(update);
+        op.add_reason("unknown song");
+        Some(op)
+    }
+}
+
+fn value<T: Clone>(changed: bool, new: &T) -> Value<T> {
+    match changed {
+        true => Value::Update(new.clone()),
+        false => Value::Unchanged,
+    }
+}
+
+fn parse_track(s: &str) -> Option<u16> {
+    s.trim_end_matches('.').parse().ok().filter(|n| *n > 0)
+}
+
+/// `01 Title` or `01. Title`
+fn split_leading_track(s: &str) -> Option<(u16, &str)> {
+    let (n, title) = s.trim().split_once(' ')?;
+    Some((parse_track(n)?, title.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer(path: &str) -> (Vec<String>, Vec<String>, String, String, Option<u16>) {
+        let t = MissingTags::infer_from_path(Path::new(path));
+        (t.release_artists, t.artists, t.release, t.title, t.track_number)
+    }
+
+    fn strs(s: &[&str]) -> Vec<String> {
+        s.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn infers_tags_from_common_patterns() {
+        let expected = (strs(&["Foo"]), strs(&[]), "Bar".into(), "One".into(), Some(1));
+        assert_eq!(infer("Foo/Bar/01 - One.mp3"), expected);
+        assert_eq!(infer("Foo/Bar/01. One.mp3"), expected);
+        assert_eq!(infer("Foo - Bar - 01 - One.mp3"), expected);
+        assert_eq!(infer("Bar/Foo - 01 - One.mp3"), expected);
+
+        let featured = (strs(&["Foo"]), strs(&["Baz"]), "Bar".into(), "One".into(), Some(1));
+        assert_eq!(infer("Foo/Bar/01 - Baz - One.mp3"), featured);
+    }
+
+    #[test]
+    fn incomplete_without_release() {
+        let tags = MissingTags::infer_from_path(Path::new("01 - One.mp3"));
+        assert_eq!(tags.title, "One");
+        assert!(!tags.is_complete());
+    }
+}
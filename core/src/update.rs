@@ -0,0 +1,595 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "mp3")]
+use id3::frame::{ExtendedText, Picture, PictureType as Id3PictureType, Popularimeter};
+#[cfg(feature = "mp3")]
+use id3::TagLike;
+#[cfg(feature = "flac")]
+use metaflac::block::PictureType as FlacPictureType;
+#[cfg(feature = "mp4")]
+use mp4ameta::{Data, FreeformIdent, Img};
+use serde::{Deserialize, Serialize};
+
+use crate::{ArtistSeparators, Song};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagUpdate {
+    pub track_number: Value<u16>,
+    pub total_tracks: Value<u16>,
+    pub disc_number: Value<u16>,
+    pub total_discs: Value<u16>,
+    pub artists: Value<Vec<String>>,
+    pub release_artists: Value<Vec<String>>,
+    pub release: Value<String>,
+    pub title: Value<String>,
+    /// Multiple genres are separated by `\0`, like in ID3v2.4 frames.
+    #[serde(default)]
+    pub genre: Value<String>,
+    #[serde(default)]
+    pub year: Value<i32>,
+    pub artwork: Value<Vec<u8>>,
+    /// From 0 to 100, stored as a `POPM` frame in mp3 files and as a `RATING` field otherwise.
+    #[serde(default)]
+    pub rating: Value<u8>,
+    #[serde(default)]
+    pub play_count: Value<u32>,
+    /// Custom fields keyed by their uppercase name, stored as `TXXX` frames in mp3 files, as
+    /// freeform `com.apple.iTunes` atoms in mp4 files and as vorbis comments in flac files.
+    #[serde(default)]
+    pub extra: BTreeMap<String, Value<String>>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Value<T> {
+    Update(T),
+    Remove,
+    #[default]
+    Unchanged,
+}
+
+impl<T> Value<Vec<T>> {
+    pub fn slice_value(&self) -> Option<&[T]> {
+        match self {
+            Self::Update(v) => Some(v.as_slice()),
+            Self::Remove => Some(&[]),
+            Self::Unchanged => None,
+        }
+    }
+}
+
+impl Value<String> {
+    pub fn str_value(&self) -> Option<&str> {
+        match self {
+            Self::Update(s) => Some(s.as_str()),
+            Self::Remove => Some(""),
+            Self::Unchanged => None,
+        }
+    }
+}
+
+impl Value<u16> {
+    pub fn num_value(&self) -> Option<u16> {
+        match self {
+            Self::Update(n) => Some(*n),
+            Self::Remove => Some(0),
+            Self::Unchanged => None,
+        }
+    }
+}
+
+impl<T> Value<T> {
+    pub fn is_update(&self) -> bool {
+        matches!(self, Self::Update(_))
+    }
+
+    pub fn is_remove(&self) -> bool {
+        matches!(self, Self::Remove)
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, Self::Unchanged)
+    }
+}
+
+impl TagUpdate {
+    /// Applies the values to the indexed song, so it matches the file after the update was
+    /// executed.
+    pub fn apply(&self, song: &mut Song) {
+        let num = |v: &Value<u16>, old: Option<u16>| match v {
+            Value::Update(n) => Some(*n),
+            Value::Remove => None,
+            Value::Unchanged => old,
+        };
+        song.track_number = num(&self.track_number, song.track_number);
+        song.total_tracks = num(&self.total_tracks, song.total_tracks);
+        song.disc_number = num(&self.disc_number, song.disc_number);
+        song.total_discs = num(&self.total_discs, song.total_discs);
+        if let Some(a) = self.release_artists.slice_value() {
+            song.release_artists = a.into();
+        }
+        if let Some(a) = self.artists.slice_value() {
+            song.artists = a.into();
+        }
+        if let Some(r) = self.release.str_value() {
+            song.release = r.into();
+        }
+        if let Some(t) = self.title.str_value() {
+            song.title = t.to_owned();
+        }
+        match &self.genre {
+            Value::Update(g) => song.genre = Some(g.clone()),
+            Value::Remove => song.genre = None,
+            Value::Unchanged => (),
+        }
+        match self.year {
+            Value::Update(y) => song.year = Some(y),
+            Value::Remove => song.year = None,
+            Value::Unchanged => (),
+        }
+        if !self.artwork.is_unchanged() {
+            song.has_artwork = self.artwork.is_update();
+        }
+        for (name, value) in self.extra.iter() {
+            match value {
+                Value::Update(v) => {
+                    song.extra.insert(name.clone(), v.clone());
+                }
+                Value::Remove => {
+                    song.extra.remove(name);
+                }
+                Value::Unchanged => (),
+            }
+        }
+    }
+
+    /// Writes the tag to a temporary copy of the file inside of the same directory which is then
+    /// renamed over the original, so a crash never leaves a partially written file behind. This
+    /// splits a hard linked file from its other links, the links known to the plan, e.g. of
+    /// [`Collaborations::Linked`](crate::Collaborations::Linked), are re-created afterwards.
+    pub fn execute(
+        &self,
+        path: &Path,
+        separators: &ArtistSeparators,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.execute_with(path, separators, |from, to| std::fs::copy(from, to).map(|_| ()))
+    }
+
+    /// `copy` creates the temporary copy, the original is never touched until it's replaced.
+    fn execute_with(
+        &self,
+        path: &Path,
+        separators: &ArtistSeparators,
+        copy: impl FnOnce(&Path, &Path) -> std::io::Result<()>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_path = tmp_path(path);
+        let r = (copy(path, &tmp_path).map_err(Into::into))
+            .and_then(|_| self.write(path, &tmp_path, separators))
+            .and_then(|_| {
+                preserve_owner(path, &tmp_path)?;
+                std::fs::rename(&tmp_path, path)?;
+                Ok(())
+            });
+        if r.is_err() {
+            std::fs::remove_file(&tmp_path).ok();
+        }
+
+        r
+    }
+
+    #[cfg_attr(not(feature = "mp3"), allow(unused_variables))]
+    fn write(
+        &self,
+        path: &Path,
+        tmp_path: &Path,
+        separators: &ArtistSeparators,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        match ext.to_ascii_lowercase().as_str() {
+            #[cfg(feature = "mp3")]
+            "mp3" => self.write_mp3(tmp_path, &separators.tag_join)?,
+            #[cfg(feature = "mp4")]
+            "m4a" => self.write_mp4(tmp_path)?,
+            #[cfg(feature = "flac")]
+            "flac" => self.write_flac(tmp_path)?,
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mp3")]
+    fn write_mp3(&self, path: &Path, join: &str) -> Result<(), Box<dyn std::error::Error>> {
+        // files without a tag get a new one with the updated values
+        let read = id3::Tag::read_from_path(path).or_else(|e| match e.kind {
+            id3::ErrorKind::NoTag => Ok(id3::Tag::new()),
+            _ => Err(e),
+        });
+        let tag = match read {
+            Ok(mut tag) => {
+                match &self.release_artists {
+                    Value::Update(a) => tag.set_album_artist(a.join(join)),
+                    Value::Remove => tag.remove_album_artist(),
+                    Value::Unchanged => (),
+                }
+                match &self.artists {
+                    Value::Update(a) => tag.set_artist(a.join(join)),
+                    Value::Remove => tag.remove_artist(),
+                    Value::Unchanged => (),
+                }
+                match &self.release {
+                    Value::Update(a) => tag.set_album(a),
+                    Value::Remove => tag.remove_album(),
+                    Value::Unchanged => (),
+                }
+                match &self.title {
+                    Value::Update(t) => tag.set_title(t),
+                    Value::Remove => tag.remove_title(),
+                    Value::Unchanged => (),
+                }
+                match &self.genre {
+                    Value::Update(g) => tag.set_genre(g),
+                    Value::Remove => tag.remove_genre(),
+                    Value::Unchanged => (),
+                }
+                match &self.year {
+                    Value::Update(y) => tag.set_year(*y),
+                    Value::Remove => tag.remove_year(),
+                    Value::Unchanged => (),
+                }
+                match &self.track_number {
+                    Value::Update(t) => tag.set_track(*t as u32),
+                    Value::Remove => tag.remove_track(),
+                    Value::Unchanged => (),
+                }
+                match &self.total_tracks {
+                    Value::Update(t) => tag.set_total_tracks(*t as u32),
+                    Value::Remove => tag.remove_total_tracks(),
+                    Value::Unchanged => (),
+                }
+                match &self.disc_number {
+                    Value::Update(d) => tag.set_disc(*d as u32),
+                    Value::Remove => tag.remove_disc(),
+                    Value::Unchanged => (),
+                }
+                match &self.total_discs {
+                    Value::Update(d) => tag.set_total_discs(*d as u32),
+                    Value::Remove => tag.remove_total_discs(),
+                    Value::Unchanged => (),
+                }
+                match &self.artwork {
+                    Value::Update(d) => {
+                        tag.remove_all_pictures();
+                        tag.add_frame(Picture {
+                            mime_type: "image/png".to_string(),
+                            picture_type: Id3PictureType::CoverFront,
+                            description: "".to_string(),
+                            data: d.clone(),
+                        });
+                    }
+                    Value::Remove => tag.remove_all_pictures(),
+                    Value::Unchanged => (),
+                }
+                if !self.rating.is_unchanged() || !self.play_count.is_unchanged() {
+                    let mut popm = (tag.get("POPM").and_then(|f| f.content().popularimeter()))
+                        .cloned()
+                        .unwrap_or(Popularimeter { user: String::new(), rating: 0, counter: 0 });
+                    match &self.rating {
+                        Value::Update(r) => popm.rating = popm_rating(*r),
+                        Value::Remove => popm.rating = 0,
+                        Value::Unchanged => (),
+                    }
+                    match &self.play_count {
+                        Value::Update(c) => popm.counter = *c as u64,
+                        Value::Remove => popm.counter = 0,
+                        Value::Unchanged => (),
+                    }
+                    tag.remove("POPM");
+                    if popm.rating != 0 || popm.counter != 0 {
+                        tag.add_frame(popm);
+                    }
+                }
+                for (name, value) in self.extra.iter() {
+                    if value.is_unchanged() {
+                        continue;
+                    }
+                    // descriptions are matched case insensitively like when reading
+                    let old: Vec<String> = tag
+                        .extended_texts()
+                        .filter(|t| t.description.eq_ignore_ascii_case(name))
+                        .map(|t| t.description.clone())
+                        .collect();
+                    for d in old.iter() {
+                        tag.remove_extended_text(Some(d), None);
+                    }
+                    if let Value::Update(v) = value {
+                        tag.add_frame(ExtendedText { description: name.clone(), value: v.clone() });
+                    }
+                }
+
+                tag
+            }
+            Err(_) => id3::Tag::default(),
+        };
+
+        tag.write_to_path(path, id3::Version::Id3v24)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mp4")]
+    fn write_mp4(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let tag = match mp4ameta::Tag::read_from_path(path) {
+            Ok(mut tag) => {
+                match &self.release_artists {
+                    Value::Update(a) => tag.set_album_artists(a.clone()),
+                    Value::Remove => tag.remove_album_artists(),
+                    Value::Unchanged => (),
+                }
+                match &self.artists {
+                    Value::Update(a) => tag.set_artists(a.clone()),
+                    Value::Remove => tag.remove_artists(),
+                    Value::Unchanged => (),
+                }
+                match &self.release {
+                    Value::Update(a) => tag.set_album(a),
+                    Value::Remove => tag.remove_album(),
+                    Value::Unchanged => (),
+                }
+                match &self.title {
+                    Value::Update(t) => tag.set_title(t),
+                    Value::Remove => tag.remove_title(),
+                    Value::Unchanged => (),
+                }
+                match &self.genre {
+                    Value::Update(g) => tag.set_genres(g.split('\0').map(str::to_owned)),
+                    Value::Remove => tag.remove_genres(),
+                    Value::Unchanged => (),
+                }
+                match &self.year {
+                    Value::Update(y) => tag.set_year(y.to_string()),
+                    Value::Remove => tag.remove_year(),
+                    Value::Unchanged => (),
+                }
+                match &self.track_number {
+                    Value::Update(t) => tag.set_track_number(*t),
+                    Value::Remove => tag.remove_track_number(),
+                    Value::Unchanged => (),
+                }
+                match &self.total_tracks {
+                    Value::Update(t) => tag.set_total_tracks(*t),
+                    Value::Remove => tag.remove_total_tracks(),
+                    Value::Unchanged => (),
+                }
+                match &self.disc_number {
+                    Value::Update(d) => tag.set_disc_number(*d),
+                    Value::Remove => tag.remove_disc_number(),
+                    Value::Unchanged => (),
+                }
+                match &self.total_discs {
+                    Value::Update(d) => tag.set_total_discs(*d),
+                    Value::Remove => tag.remove_total_discs(),
+                    Value::Unchanged => (),
+                }
+                match &self.artwork {
+                    Value::Update(d) => tag.set_artwork(Img::png(d.clone())),
+                    Value::Remove => tag.remove_artworks(),
+                    Value::Unchanged => (),
+                }
+                let rating = FreeformIdent::new("com.apple.iTunes", "RATING");
+                match &self.rating {
+                    Value::Update(r) => tag.set_data(rating, Data::Utf8(r.to_string())),
+                    Value::Remove => tag.remove_data_of(&rating),
+                    Value::Unchanged => (),
+                }
+                let play_count = FreeformIdent::new("com.apple.iTunes", "PLAYCOUNT");
+                match &self.play_count {
+                    Value::Update(c) => tag.set_data(play_count, Data::Utf8(c.to_string())),
+                    Value::Remove => tag.remove_data_of(&play_count),
+                    Value::Unchanged => (),
+                }
+                for (name, value) in self.extra.iter() {
+                    let ident = FreeformIdent::new("com.apple.iTunes", name);
+                    match value {
+                        Value::Update(v) => tag.set_data(ident, Data::Utf8(v.clone())),
+                        Value::Remove => tag.remove_data_of(&ident),
+                        Value::Unchanged => (),
+                    }
+                }
+
+                tag
+            }
+            Err(_) => mp4ameta::Tag::default(),
+        };
+
+        tag.write_to_path(path)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "flac")]
+    fn write_flac(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut tag = match metaflac::Tag::read_from_path(path) {
+            Ok(mut tag) => {
+                let vorbis = tag.vorbis_comments_mut();
+
+                match &self.release_artists {
+                    Value::Update(a) => vorbis.set_album_artist(a.clone()),
+                    Value::Remove => vorbis.remove_album_artist(),
+                    Value::Unchanged => (),
+                }
+                match &self.artists {
+                    Value::Update(a) => vorbis.set_artist(a.clone()),
+                    Value::Remove => vorbis.remove_artist(),
+                    Value::Unchanged => (),
+                }
+                match &self.release {
+                    Value::Update(a) => vorbis.set_album(vec![a]),
+                    Value::Remove => vorbis.remove_album(),
+                    Value::Unchanged => (),
+                }
+                match &self.title {
+                    Value::Update(t) => vorbis.set_title(vec![t]),
+                    Value::Remove => vorbis.remove_title(),
+                    Value::Unchanged => (),
+                }
+                match &self.genre {
+                    Value::Update(g) => vorbis.set_genre(g.split('\0').collect()),
+                    Value::Remove => vorbis.remove_genre(),
+                    Value::Unchanged => (),
+                }
+                match &self.year {
+                    Value::Update(y) => vorbis.set("DATE", vec![y.to_string()]),
+                    Value::Remove => vorbis.remove("DATE"),
+                    Value::Unchanged => (),
+                }
+                match &self.track_number {
+                    Value::Update(t) => vorbis.set_track(*t as u32),
+                    Value::Remove => vorbis.remove_track(),
+                    Value::Unchanged => (),
+                }
+                match &self.total_tracks {
+                    Value::Update(t) => vorbis.set_total_tracks(*t as u32),
+                    Value::Remove => vorbis.remove_total_tracks(),
+                    Value::Unchanged => (),
+                }
+                match &self.disc_number {
+                    Value::Update(d) => vorbis.set("DISCNUMBER", vec![d.to_string()]),
+                    Value::Remove => vorbis.remove("DISCNUMBER"),
+                    Value::Unchanged => (),
+                }
+                match &self.total_discs {
+                    Value::Update(d) => vorbis.set("TOTALDISCS", vec![d.to_string()]),
+                    Value::Remove => vorbis.remove("TOTALDISCS"),
+                    Value::Unchanged => (),
+                }
+                match &self.rating {
+                    Value::Update(r) => vorbis.set("RATING", vec![r.to_string()]),
+                    Value::Remove => vorbis.remove("RATING"),
+                    Value::Unchanged => (),
+                }
+                match &self.play_count {
+                    Value::Update(c) => vorbis.set("PLAYCOUNT", vec![c.to_string()]),
+                    Value::Remove => vorbis.remove("PLAYCOUNT"),
+                    Value::Unchanged => (),
+                }
+                for (name, value) in self.extra.iter() {
+                    match value {
+                        Value::Update(v) => vorbis.set(name.as_str(), vec![v.clone()]),
+                        Value::Remove => vorbis.remove(name),
+                        Value::Unchanged => (),
+                    }
+                }
+                match &self.artwork {
+                    Value::Update(d) => {
+                        tag.add_picture("image/png", FlacPictureType::CoverFront, d.clone())
+                    }
+                    Value::Remove => tag.remove_picture_type(FlacPictureType::CoverFront),
+                    Value::Unchanged => (),
+                }
+
+                tag
+            }
+            Err(_) => metaflac::Tag::default(),
+        };
+
+        tag.write_to_path(path)?;
+
+        Ok(())
+    }
+}
+
+/// Maps a rating from 0 to 100 onto the 1 to 255 scale of `POPM` frames, the way most players
+/// convert stars.
+#[cfg(feature = "mp3")]
+fn popm_rating(rating: u8) -> u8 {
+    match rating {
+        0 => 0,
+        1..=20 => 1,
+        21..=40 => 64,
+        41..=60 => 128,
+        61..=80 => 196,
+        _ => 255,
+    }
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".music-organizer.tmp");
+    path.with_file_name(name)
+}
+
+/// The copy is created by the current user, so the owner of the original file has to be restored.
+#[cfg(unix)]
+fn preserve_owner(original: &Path, copy: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = std::fs::metadata(original)?;
+    let copy_meta = std::fs::metadata(copy)?;
+    if meta.uid() != copy_meta.uid() || meta.gid() != copy_meta.gid() {
+        std::os::unix::fs::chown(copy, Some(meta.uid()), Some(meta.gid()))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn preserve_owner(_original: &Path, _copy: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Metadata;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("music-organizer-update-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "mp3"))]
+    fn retagging_splits_hard_links() {
+        let dir = temp_dir("links");
+        let a = dir.join("a.mp3");
+        let b = dir.join("b.mp3");
+        std::fs::write(&a, crate::FIXTURE_MP3).unwrap();
+        std::fs::hard_link(&a, &b).unwrap();
+
+        let update = TagUpdate { title: Value::Update("Foo".to_owned()), ..Default::default() };
+        update.execute(&a, &ArtistSeparators::default()).unwrap();
+
+        assert_eq!(Metadata::read_from(&a).title.as_deref(), Some("Foo"));
+        assert_eq!(std::fs::read(&b).unwrap(), crate::FIXTURE_MP3);
+        assert!(!tmp_path(&a).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_copy_keeps_original() {
+        let dir = temp_dir("copy");
+        let a = dir.join("a.mp3");
+        let b = dir.join("b.mp3");
+        std::fs::write(&a, b"abcdefgh").unwrap();
+        std::fs::hard_link(&a, &b).unwrap();
+
+        let update = TagUpdate { title: Value::Update("Foo".to_owned()), ..Default::default() };
+        let r = update.execute_with(&a, &ArtistSeparators::default(), |from, to| {
+            let data = std::fs::read(from)?;
+            std::fs::write(to, &data[..data.len() / 2])?;
+            Err(std::io::ErrorKind::StorageFull.into())
+        });
+
+        assert!(r.is_err());
+        for p in [&a, &b] {
+            assert_eq!(std::fs::read(p).unwrap(), b"abcdefgh", "{}", p.display());
+        }
+        assert!(!tmp_path(&a).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
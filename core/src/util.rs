@@ -0,0 +1,47 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::{Song, SongOperation, TagUpdate};
+
+pub fn update_song_op(
+    song_operations: &mut Vec<SongOperation>,
+    song: &Song,
+    f: impl FnOnce(&mut SongOperation),
+) {
+    match song_operations.iter_mut().find(|o| o.song.path == song.path) {
+        Some(o) => f(o),
+        None => {
+            let mut o = SongOperation::new(song.clone());
+            f(&mut o);
+            song_operations.push(o);
+        }
+    }
+}
+
+/// Queues a tag update, unless the song is excluded from retagging, see
+/// [`Checks::exclude_from_retagging`](crate::Checks::exclude_from_retagging). The `reason` is
+/// added to the [`SongOperation::reasons`].
+pub fn update_tag(
+    song_operations: &mut Vec<SongOperation>,
+    retag_excluded: &HashSet<&Path>,
+    song: &Song,
+    reason: &str,
+    f: impl FnOnce(&mut TagUpdate),
+) {
+    if retag_excluded.contains(song.path.as_path()) {
+        return;
+    }
+    update_song_op(song_operations, song, |op| {
+        op.add_reason(reason);
+        match &mut op.tag_update {
+            Some(t) => f(t),
+            None => {
+                let mut t = TagUpdate::default();
+
+                f(&mut t);
+
+                op.tag_update = Some(t);
+            }
+        }
+    });
+}
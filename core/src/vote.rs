@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tracing::{debug, info, info_span};
+
+use crate::{util, Checks, Metadata, MissingTags, MusicIndex, Song, SongOperation, Value};
+
+/// The artists of the songs of a release without release artists, counted by song. See
+/// [`Checks::check_release_artists_vote`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArtistVote<'a> {
+    pub release: &'a str,
+    /// The dir containing the songs.
+    pub dir: &'a Path,
+    /// Sorted by disc and track number.
+    pub songs: Vec<&'a Song>,
+    /// Sorted by the number of songs, descending.
+    pub votes: Vec<(Arc<[String]>, usize)>,
+}
+
+impl ArtistVote<'_> {
+    /// The artists of more than half of the songs.
+    pub fn majority(&self) -> Option<&Arc<[String]>> {
+        let (artists, n) = self.votes.first()?;
+        (n * 2 > self.songs.len()).then_some(artists)
+    }
+}
+
+fn count_votes<'a>(songs: impl Iterator<Item = &'a Song>) -> Vec<(Arc<[String]>, usize)> {
+    let mut votes: Vec<(Arc<[String]>, usize)> = Vec::new();
+    for s in songs {
+        match votes.iter_mut().find(|(a, _)| *a == s.artists) {
+            Some((_, n)) => *n += 1,
+            None => votes.push((s.artists.clone(), 1)),
+        }
+    }
+    // stable, ties keep the order of the first song
+    votes.sort_by(|(_, a), (_, b)| b.cmp(a));
+    votes
+}
+
+impl Checks<'_> {
+    /// Checks for releases whose songs are in the same dir and have different artists, but no
+    /// release artists, since songs without release artists are grouped by their artists. The
+    /// release artists are set to the artists of the majority of the songs, `f` is only called
+    /// if there is no majority and may return the release artists. Songs that already have a
+    /// release artists update queued, e.g. by [`Checks::check_various_artists`], don't vote and
+    /// aren't updated.
+    pub fn check_release_artists_vote(
+        &mut self,
+        f: &mut impl FnMut(&ArtistVote) -> Value<Vec<String>>,
+    ) {
+        let _span = info_span!("check", check = "release_artists_vote").entered();
+        let mut releases: BTreeMap<(&Path, &str), Vec<&Song>> = BTreeMap::new();
+        for s in self.index.songs.iter() {
+            let Some(dir) = s.path.parent() else { continue };
+            releases.entry((dir, &s.release)).or_default().push(s);
+        }
+
+        for ((dir, release), mut songs) in releases {
+            if self.cancel.is_cancelled() {
+                return;
+            }
+            // the release artists are the song artists if the release artists tag is missing
+            if songs.iter().any(|s| s.release_artists != s.artists) {
+                continue;
+            }
+            songs.retain(|s| {
+                !self.song_operations.iter().any(|o| {
+                    o.song.path == s.path
+                        && o.tag_update.as_ref().is_some_and(|t| t.release_artists.is_update())
+                })
+            });
+            let votes = count_votes(songs.iter().copied());
+            if votes.len() < 2 {
+                continue;
+            }
+
+            songs.sort_by_key(|s| (s.disc_number, s.track_number));
+            let vote = ArtistVote { release, dir, songs, votes };
+            self.stats.release_artists_vote.issues += 1;
+            let names = match vote.majority() {
+                Some(artists) => artists.to_vec(),
+                None => {
+                    info!(release, dir = %dir.display(), "ambiguous release artists vote");
+                    match f(&vote) {
+                        Value::Update(names) => names,
+                        Value::Unchanged | Value::Remove => continue,
+                    }
+                }
+            };
+            debug!(release, dir = %dir.display(), ?names, "release artists vote");
+            self.stats.release_artists_vote.updates += vote.songs.len();
+            for s in vote.songs {
+                util::update_tag(
+                    &mut self.song_operations,
+                    &self.retag_excluded,
+                    s,
+                    "release artists vote",
+                    |t| t.release_artists = Value::Update(names.clone()),
+                );
+            }
+        }
+    }
+}
+
+impl MusicIndex {
+    /// Resolves the unknown songs that only lack artists using the artists of the majority of
+    /// the songs of their release in the same dir. The returned operations write the tags and
+    /// have to be added to the [`Checks`] created from this index. Reads the files.
+    pub fn resolve_unknown_by_vote(&mut self) -> Vec<SongOperation> {
+        let mut resolved: Vec<(PathBuf, Metadata, MissingTags)> = Vec::new();
+        let mut releases: HashMap<(&Path, String), Option<Arc<[String]>>> = HashMap::new();
+        for p in self.unknown_songs() {
+            let metadata = Metadata::read_from(p);
+            let (Some(dir), Some(release)) = (p.parent(), metadata.release.as_deref()) else {
+                continue;
+            };
+            if metadata.release_artists().is_some() || metadata.title.is_none() {
+                continue;
+            }
+            let majority = releases.entry((dir, release.to_owned())).or_insert_with(|| {
+                let songs: Vec<&Song> = (self.songs.iter())
+                    .filter(|s| s.path.parent() == Some(dir) && *s.release == *release)
+                    .collect();
+                let votes = count_votes(songs.iter().copied());
+                let (artists, n) = votes.into_iter().next()?;
+                (n * 2 > songs.len()).then_some(artists)
+            });
+            let Some(artists) = majority.clone() else { continue };
+
+            let mut tags = MissingTags::from_metadata(&metadata);
+            tags.release_artists = artists.to_vec();
+            resolved.push((p.clone(), metadata, tags));
+        }
+
+        let mut ops = Vec::new();
+        for (path, metadata, tags) in resolved {
+            debug!(path = %path.display(), artists = ?tags.release_artists, "unknown song vote");
+            ops.extend(self.resolve_unknown(&path, metadata, tags));
+        }
+        ops
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::song;
+
+    #[test]
+    fn votes_for_the_majority_and_asks_otherwise() {
+        let mut index = MusicIndex::from(PathBuf::from("/music"));
+        index.songs = vec![
+            song("/music/a/1.mp3", "A", "Foo"),
+            song("/music/a/2.mp3", "A", "Foo"),
+            song("/music/a/3.mp3", "A", "Bar"),
+            song("/music/b/1.mp3", "B", "Foo"),
+            song("/music/b/2.mp3", "B", "Bar"),
+        ];
+        let mut checks = Checks::from(&index);
+
+        let mut asked = Vec::new();
+        checks.check_release_artists_vote(&mut |v| {
+            asked.push(v.release.to_owned());
+            Value::Unchanged
+        });
+
+        assert_eq!(asked, ["B"]);
+        assert_eq!(checks.stats.release_artists_vote.issues, 2);
+        assert_eq!(checks.song_operations.len(), 3);
+        for o in checks.song_operations.iter() {
+            let update = o.tag_update.as_ref().unwrap();
+            assert_eq!(update.release_artists, Value::Update(vec!["Foo".to_owned()]));
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use music_organizer_core::{
+    run_pipeline, ArtistSeparators, ChangeOptions, ExecOptions, LibraryBuilder, Metadata,
+    ScriptedAnswers, Value,
+};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("music-organizer-pipeline-{name}-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    dir
+}
+
+#[test]
+fn organizes_real_files() {
+    let dir = temp_dir("organize");
+    let incoming = dir.join("incoming");
+    let library = LibraryBuilder::new(&dir)
+        .release("Daft Punk", "Discovery", 2)
+        .release("Air", "Moon Safari", 1)
+        .release("Justice", "Cross", 2)
+        .map(|s| {
+            s.year = Some(2001);
+            let ext = match &*s.release {
+                "Moon Safari" => "m4a",
+                "Cross" => "flac",
+                _ => "mp3",
+            };
+            s.path = incoming.join(s.path.file_name().unwrap()).with_extension(ext);
+            if s.title == "Track 2" && &*s.release == "Cross" {
+                s.release_artists = Arc::from(["justice".to_owned()]);
+            }
+        });
+    library.write(&ArtistSeparators::default()).unwrap();
+
+    let answers = ScriptedAnswers {
+        release_artists: Value::Update(vec!["Justice".to_owned()]),
+        ..Default::default()
+    };
+    let run =
+        run_pipeline(&dir, &ChangeOptions::from(dir.clone()), &ExecOptions::default(), &answers);
+
+    assert_eq!(run.failures, []);
+    assert!(!incoming.exists(), "the emptied dir wasn't cleaned up");
+    let mut paths: Vec<_> =
+        run.index.songs.iter().map(|s| s.path.strip_prefix(&dir).unwrap().to_owned()).collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [
+            Path::new("Air/Moon Safari/01 - Air - Track 1.m4a"),
+            Path::new("Daft Punk/Discovery/01 - Daft Punk - Track 1.mp3"),
+            Path::new("Daft Punk/Discovery/02 - Daft Punk - Track 2.mp3"),
+            Path::new("Justice/Cross/01 - Justice - Track 1.flac"),
+            Path::new("Justice/Cross/02 - Justice - Track 2.flac"),
+        ]
+    );
+    for song in run.index.songs.iter() {
+        let meta = Metadata::try_read_from(&song.path).unwrap();
+        assert_eq!(meta.year, Some(2001), "{}", song.path.display());
+        assert_eq!(meta.title.as_deref(), Some(&*song.title), "{}", song.path.display());
+    }
+    let justice = dir.join("Justice/Cross/02 - Justice - Track 2.flac");
+    assert_eq!(Metadata::read_from(&justice).release_artists, ["Justice"]);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
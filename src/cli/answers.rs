@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use music_organizer_core::{
+    ArtistVote, Compilation, InconsistentTotals, Release, ReleaseArtists, ReleaseSuffix,
+    SplitRelease, TotalKind, Value,
+};
+
+/// Separates multiple names inside of an answer like `names:A;B`.
+const NAME_SEPARATOR: char = ';';
+
+/// Pre-recorded answers to check dialogs, keyed by the question, e.g.
+/// `inconsistent-artist:Radiohead|radiohead` with the answer `first`.
+///
+/// Answers are loaded from a JSON file, answers passed on the command line take precedence. New
+/// answers given interactively are recorded, so they can be written back to the file.
+///
+/// Dialogs that were answered with "never ask again" are skipped in all later runs, their keys
+/// are stored in the decisions file in the same format, which is always used.
+#[derive(Debug, Default)]
+pub struct Answers {
+    path: Option<PathBuf>,
+    answers: BTreeMap<String, String>,
+    changed: bool,
+    decisions_path: PathBuf,
+    decisions: BTreeMap<String, String>,
+    decisions_changed: bool,
+}
+
+fn read_answers(path: &Path) -> std::io::Result<BTreeMap<String, String>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let file = std::fs::File::open(path)?;
+    Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+}
+
+fn write_answers(path: &Path, answers: &BTreeMap<String, String>) -> std::io::Result<()> {
+    let file = std::fs::File::create(path)?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), answers)?;
+    Ok(())
+}
+
+impl Answers {
+    /// Loads the answers and decisions files if they exist and adds the answers passed on the
+    /// command line.
+    pub fn load(
+        path: Option<&Path>,
+        decisions_path: &Path,
+        answers: &[(String, String)],
+    ) -> std::io::Result<Self> {
+        let mut new = Self {
+            path: path.map(Path::to_owned),
+            decisions_path: decisions_path.to_owned(),
+            decisions: read_answers(decisions_path)?,
+            ..Default::default()
+        };
+        if let Some(path) = path {
+            new.answers = read_answers(path)?;
+        }
+        new.answers.extend(answers.iter().cloned());
+        Ok(new)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.answers.get(key).or_else(|| self.decisions.get(key)).map(String::as_str)
+    }
+
+    pub fn record(&mut self, key: String, answer: String) {
+        self.answers.insert(key, answer);
+        self.changed = true;
+    }
+
+    /// Records an answer that is used in all later runs, e.g. `none` to never ask again.
+    pub fn decide(&mut self, key: String, answer: String) {
+        self.decisions.insert(key, answer);
+        self.decisions_changed = true;
+    }
+
+    /// Writes the answers back to the answers file, if one is used and an answer was recorded,
+    /// and the decisions to the decisions file, if a decision was made.
+    pub fn save(&self) -> std::io::Result<()> {
+        if let Some(path) = self.path.as_ref().filter(|_| self.changed) {
+            write_answers(path, &self.answers)?;
+        }
+        if self.decisions_changed {
+            if let Some(dir) = self.decisions_path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            write_answers(&self.decisions_path, &self.decisions)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `key=answer`, as passed to `--answer`.
+pub fn parse_answer(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, answer)) if !key.is_empty() => Ok((key.to_owned(), answer.to_owned())),
+        _ => Err(format!("expected 'question=answer', got '{s}'")),
+    }
+}
+
+pub fn inconsistent_artists_key(a: &ReleaseArtists, b: &ReleaseArtists) -> String {
+    format!("inconsistent-artist:{}|{}", a.names.join(", "), b.names.join(", "))
+}
+
+pub fn invalid_numbers_key(a: &ReleaseArtists, r: &Release) -> String {
+    format!("invalid-numbers:{}|{}", a.names.join(", "), r.name)
+}
+
+pub fn various_artists_key(c: &Compilation) -> String {
+    format!("various-artists:{}|{}", c.release, c.dir.display())
+}
+
+pub fn release_artists_vote_key(v: &ArtistVote) -> String {
+    format!("release-artists-vote:{}|{}", v.release, v.dir.display())
+}
+
+/// `none` or `names:A;B`.
+pub fn parse_names_answer(answer: &str) -> Option<Value<Vec<String>>> {
+    match answer {
+        "none" => Some(Value::Unchanged),
+        _ => {
+            let names = answer.strip_prefix("names:")?;
+            let names = names.split(NAME_SEPARATOR).map(|n| n.trim().to_owned()).collect();
+            Some(Value::Update(names))
+        }
+    }
+}
+
+pub fn format_names_answer(value: &Value<Vec<String>>) -> String {
+    match value {
+        Value::Update(names) => format!("names:{}", names.join(&NAME_SEPARATOR.to_string())),
+        Value::Unchanged | Value::Remove => "none".to_owned(),
+    }
+}
+
+pub fn inconsistent_totals_key(a: &ReleaseArtists, r: &Release, t: &InconsistentTotals) -> String {
+    let kind = match t.kind {
+        TotalKind::Tracks { disc: Some(d) } => format!("tracks:{d}"),
+        TotalKind::Tracks { disc: None } => "tracks".to_owned(),
+        TotalKind::Discs => "discs".to_owned(),
+    };
+    format!("inconsistent-totals:{}|{}|{kind}", a.names.join(", "), r.name)
+}
+
+/// `none` or the total.
+pub fn parse_total_answer(answer: &str) -> Option<Value<u16>> {
+    match answer {
+        "none" => Some(Value::Unchanged),
+        _ => answer.parse().ok().map(Value::Update),
+    }
+}
+
+pub fn format_total_answer(value: &Value<u16>) -> String {
+    match value {
+        Value::Update(t) => t.to_string(),
+        Value::Unchanged | Value::Remove => "none".to_owned(),
+    }
+}
+
+/// `fix` or `none`.
+pub fn parse_fix_answer(answer: &str) -> Option<bool> {
+    match answer {
+        "fix" => Some(true),
+        "none" => Some(false),
+        _ => None,
+    }
+}
+
+pub fn format_fix_answer(fix: bool) -> String {
+    match fix {
+        true => "fix".to_owned(),
+        false => "none".to_owned(),
+    }
+}
+
+/// The release artists are sorted, so the key doesn't depend on the number of songs of each.
+pub fn split_release_key(s: &SplitRelease) -> String {
+    let mut names: Vec<String> = s.parts.iter().map(|(n, _)| n.join(", ")).collect();
+    names.sort();
+    format!("split-release:{}|{}", s.release, names.join("|"))
+}
+
+pub fn release_suffix_key(a: &ReleaseArtists, r: &Release) -> String {
+    format!("release-suffix:{}|{}", a.names.join(", "), r.name)
+}
+
+/// `none`, `standard` or `strip`.
+pub fn parse_suffix_answer(answer: &str, suffix: &ReleaseSuffix) -> Option<Value<String>> {
+    match answer {
+        "none" => Some(Value::Unchanged),
+        "standard" => Some(Value::Update(suffix.standardized())),
+        "strip" => Some(Value::Update(suffix.base.clone())),
+        _ => None,
+    }
+}
+
+pub fn format_suffix_answer(value: &Value<String>, suffix: &ReleaseSuffix) -> String {
+    match value {
+        Value::Update(name) if *name == suffix.base => "strip".to_owned(),
+        Value::Update(_) => "standard".to_owned(),
+        Value::Unchanged | Value::Remove => "none".to_owned(),
+    }
+}
+
+/// `none`, `first`, `second` or `names:A;B`.
+pub fn parse_artists_answer(
+    answer: &str,
+    a: &ReleaseArtists,
+    b: &ReleaseArtists,
+) -> Option<Value<Vec<String>>> {
+    match answer {
+        "none" => Some(Value::Unchanged),
+        "first" => Some(Value::Update(a.names.to_vec())),
+        "second" => Some(Value::Update(b.names.to_vec())),
+        _ => {
+            let names = answer.strip_prefix("names:")?;
+            let names = names.split(NAME_SEPARATOR).map(|n| n.trim().to_owned()).collect();
+            Some(Value::Update(names))
+        }
+    }
+}
+
+pub fn format_artists_answer(
+    value: &Value<Vec<String>>,
+    a: &ReleaseArtists,
+    b: &ReleaseArtists,
+) -> String {
+    match value {
+        Value::Update(names) if **names == *a.names => "first".to_owned(),
+        Value::Update(names) if **names == *b.names => "second".to_owned(),
+        Value::Update(names) => format!("names:{}", names.join(&NAME_SEPARATOR.to_string())),
+        Value::Unchanged | Value::Remove => "none".to_owned(),
+    }
+}
@@ -1,11 +1,36 @@
-use clap::{crate_authors, crate_version, value_parser, Arg, ColorChoice, Command, ValueHint};
+use clap::builder::{BoolishValueParser, ValueRange};
+use clap::{
+    crate_authors, crate_version, value_parser, Arg, ArgAction, ColorChoice, Command, ValueHint,
+};
 use clap_complete::generate;
 use clap_complete::shells::{Bash, Elvish, Fish, PowerShell, Zsh};
-use music_organizer::FileOpType;
-use std::path::PathBuf;
+#[cfg(feature = "scripting")]
+use music_organizer_core::Script;
+use music_organizer_core::{
+    ArtistSeparators, Backup, CancelToken, Collaborations, CoverName, DedupAction, DeleteType,
+    ExecOptions, ExtraKind, ExtraPolicies, ExtraPolicy, FileOpType, Filter, ImagePolicy, Layout,
+    Mode, NamingProfile, NfoTemplate, NonLatinArtists, Owner, RetryPolicy, SelectionOrder,
+    SimilarityScope, SizeLimit, TotalsPolicy, Transcode, TranscodeCodec, DEFAULT_AUDIOBOOK_LAYOUT,
+    DEFAULT_JUNK_PATTERNS, DEFAULT_LAYOUT, DEFAULT_NFO_TEMPLATE, DEFAULT_NFO_TRACK_TEMPLATE,
+    DEFAULT_VIDEO_LAYOUT, EXTRA_FIELD_PREFIX, FILTER_KEYS, LAYOUT_FIELDS, NFO_FIELDS,
+    PROVENANCE_FIELD, RELEASE_GROUP_FIELD, STAGING_DIR_NAME,
+};
+#[cfg(feature = "server")]
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
+#[cfg(feature = "scripting")]
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::answers;
+use crate::config::{self, Config};
+use crate::guard;
 
 const BIN_NAME: &str = "music-organizer";
+/// Prefix of the environment variables that options can be set through.
+const ENV_PREFIX: &str = "MUSIC_ORGANIZER_";
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum Shell {
@@ -31,16 +56,341 @@ impl FromStr for Shell {
     }
 }
 
+/// Format of the log written to stderr in addition to the regular output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err("Unknown log format"),
+        }
+    }
+}
+
+/// Format of the health report, JSON is written to stdout while all other output goes to stderr.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err("Unknown report format"),
+        }
+    }
+}
+
+/// How tags inferred from the paths of unknown songs are applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InferPolicy {
+    Ask,
+    Apply,
+}
+
+impl FromStr for InferPolicy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ask" => Ok(InferPolicy::Ask),
+            "apply" => Ok(InferPolicy::Apply),
+            _ => Err("Unknown infer policy"),
+        }
+    }
+}
+
+/// How a check applies its fixes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckPolicy {
+    Ask,
+    Apply,
+    Skip,
+}
+
+impl FromStr for CheckPolicy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ask" => Ok(CheckPolicy::Ask),
+            "apply" => Ok(CheckPolicy::Apply),
+            "skip" => Ok(CheckPolicy::Skip),
+            _ => Err("Unknown check policy"),
+        }
+    }
+}
+
+/// What happens to bonus tracks, instrumentals and hidden tracks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BonusPolicy {
+    /// They are only reported by the check.
+    Flag,
+    /// They are placed in the
+    /// [`DEFAULT_BONUS_DIR`](music_organizer_core::DEFAULT_BONUS_DIR) of their release dir.
+    Extras,
+}
+
 pub struct Args {
+    pub config: Config,
+    /// The config file that was loaded or the default path if none exists yet.
+    pub config_path: PathBuf,
     pub music_dir: PathBuf,
     pub output_dir: PathBuf,
+    /// The output dir if it is nested inside of the music dir.
+    pub excluded_dirs: Vec<PathBuf>,
+    /// Printed once the theme and locale are set up.
+    pub warnings: Vec<String>,
     pub verbosity: u8,
-    pub op_type: FileOpType,
+    pub color: ColorChoice,
+    pub log_format: Option<LogFormat>,
+    pub log_file: Option<PathBuf>,
+    pub metrics_file: Option<PathBuf>,
+    pub provenance_tag: bool,
+    pub provenance_manifest: Option<PathBuf>,
+    pub path_manifest: Option<PathBuf>,
+    /// The plan of the last applied run, to mark new operations of dry runs.
+    pub plan_snapshot: Option<PathBuf>,
+    /// Write a summary of each release into its dir.
+    pub release_nfo: Option<NfoTemplate>,
+    pub exec: ExecOptions,
+    pub backup_dir: Option<PathBuf>,
+    pub keep_backups: usize,
     pub assume_yes: bool,
+    pub confirm_releases: bool,
     pub dry_run: bool,
+    /// The separator of the new paths that are printed to stdout, while all other output goes to
+    /// stderr.
+    pub print_new_paths: Option<u8>,
+    /// Print nothing but a single summary line, implies `assume_yes`.
+    pub quiet: bool,
     pub no_check: bool,
+    /// No file contents are modified, all songs are excluded from retagging and the tag and mode
+    /// updates of the plan are dropped.
+    pub rename_only: bool,
+    pub answers_file: Option<PathBuf>,
+    /// Answers of dialogs that should never be asked again.
+    pub decisions_file: PathBuf,
+    pub answers: Vec<(String, String)>,
+    pub infer_tags: Option<InferPolicy>,
+    pub various_artists: CheckPolicy,
+    pub vote_release_artists: bool,
+    pub similarity_scope: SimilarityScope,
+    pub normalize_totals: Option<TotalsPolicy>,
+    pub flatten_discs: bool,
+    pub fill_release_tags: bool,
+    pub bonus_tracks: Option<BonusPolicy>,
     pub keep_embedded_artworks: bool,
+    pub keep_playlists: bool,
+    pub release_playlists: bool,
+    pub artist_playlists: bool,
     pub no_cleanup: bool,
+    pub tag_only: bool,
+    pub junk_patterns: Vec<String>,
+    pub image_policy: ImagePolicy,
+    pub cover_name: CoverName,
+    pub extra_policies: ExtraPolicies,
+    pub non_latin_artists: NonLatinArtists,
+    pub collaborations: Collaborations,
+    pub lowercase_extensions: bool,
+    pub dir_mode: Option<Mode>,
+    pub dir_owner: Option<Owner>,
+    pub layout: Layout,
+    pub video_layout: Option<Layout>,
+    pub audiobook_layout: Option<Layout>,
+    pub artist_separators: ArtistSeparators,
+    pub filters: Vec<Filter>,
+    /// Songs whose tags are never rewritten, from the config and the command line.
+    pub no_retag: Vec<Filter>,
+    pub keep_artwork: Vec<Filter>,
+    pub index_cache: Option<PathBuf>,
+    pub export_index: Option<PathBuf>,
+    pub itunes_library: Option<PathBuf>,
+    pub updates_from: Option<PathBuf>,
+    pub check_report: Option<PathBuf>,
+    /// The dirs of the `diff` subcommand, which compares two libraries instead of organizing.
+    pub diff: Option<[PathBuf; 2]>,
+    pub sync_dir: Option<PathBuf>,
+    pub sync_delete: bool,
+    pub naming: NamingProfile,
+    pub transcode: Option<Transcode>,
+    pub sync_limit: Option<SizeLimit>,
+    /// `-` means stdin.
+    pub files_from: Option<PathBuf>,
+    /// Only the release in this dir inside of the music dir is organized.
+    pub release_dir: Option<PathBuf>,
+    pub watch: bool,
+    pub settle: Duration,
+    /// Serve the index on this unix socket while watching.
+    pub index_socket: Option<PathBuf>,
+    #[cfg(feature = "sqlite")]
+    pub index_db: Option<PathBuf>,
+    #[cfg(feature = "sqlite")]
+    pub beets_db: Option<PathBuf>,
+    #[cfg(feature = "notifications")]
+    pub notify: bool,
+    #[cfg(feature = "server")]
+    pub serve: Option<SocketAddr>,
+    #[cfg(feature = "replay-gain")]
+    pub replay_gain: bool,
+    #[cfg(feature = "scripting")]
+    pub script: Option<Arc<Script>>,
+    pub threads: Option<NonZeroUsize>,
+    pub precount: bool,
+    pub stats: bool,
+    pub health: Option<ReportFormat>,
+    /// Print the report if there is no file.
+    pub incomplete_report: Option<Option<PathBuf>>,
+    pub dedup: Option<DedupAction>,
+    pub quarantine: bool,
+    pub strict: bool,
+    pub follow_symlinks: bool,
+    pub max_depth: Option<usize>,
+    pub include_hidden: bool,
+}
+
+fn parse_mode(s: &str) -> Result<Mode, String> {
+    match u32::from_str_radix(s, 8) {
+        Ok(m) if m <= 0o777 => Ok(Mode(m)),
+        _ => Err(format!("Not a valid octal mode: {s}")),
+    }
+}
+
+/// An owner like `jellyfin`, `jellyfin:media`, `:media` or `1000:100`.
+fn parse_owner(s: &str) -> Result<Owner, String> {
+    let (user, group) = s.split_once(':').unwrap_or((s, ""));
+    let id = |name: &str, kind: &str, db: &str| match name {
+        "" => Ok(None),
+        _ => match name.parse() {
+            Ok(id) => Ok(Some(id)),
+            Err(_) => lookup_id(db, name).map(Some).ok_or_else(|| format!("Unknown {kind} {name}")),
+        },
+    };
+    let owner =
+        Owner { uid: id(user, "user", "/etc/passwd")?, gid: id(group, "group", "/etc/group")? };
+    if owner == Owner::default() {
+        return Err("Expected <user>[:<group>]".to_owned());
+    }
+    Ok(owner)
+}
+
+/// The id of a name in `/etc/passwd` or `/etc/group`, whose lines look like `name:x:id:...`.
+fn lookup_id(db: &str, name: &str) -> Option<u32> {
+    let db = std::fs::read_to_string(db).ok()?;
+    db.lines().find_map(|l| {
+        let mut fields = l.split(':');
+        (fields.next() == Some(name)).then(|| fields.nth(1)?.parse().ok()).flatten()
+    })
+}
+
+/// A size like `64G`, `500MB` or `1.5TiB`, units without `i` are powers of 1000.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("Not a valid size: {s}"))?;
+    let unit = unit.trim().to_ascii_lowercase();
+    let unit = unit.strip_suffix('b').unwrap_or(&unit);
+    let (prefix, base) = match unit.strip_suffix('i') {
+        Some(prefix) => (prefix, 1024_f64),
+        None => (unit, 1000_f64),
+    };
+    let exponent = match prefix {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        _ => return Err(format!("Unknown unit in {s}, expected K, M, G or T")),
+    };
+    Ok((number * base.powi(exponent)) as u64)
+}
+
+fn parse_similarity_scope(s: &str) -> Result<SimilarityScope, String> {
+    match s.split_once(':') {
+        None if s == "library" => Ok(SimilarityScope::Library),
+        Some(("letter", l)) => {
+            let mut chars = l.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(SimilarityScope::Letter(c)),
+                _ => Err(format!("Expected a single letter, got '{l}'")),
+            }
+        }
+        Some(("artist", a)) if !a.is_empty() => Ok(SimilarityScope::Artist(a.to_owned())),
+        _ => Err("Expected library, letter:<letter> or artist:<name>".to_owned()),
+    }
+}
+
+fn parse_extra_policy(s: &str) -> Result<(ExtraKind, ExtraPolicy), String> {
+    let (kind, policy) = s.split_once('=').ok_or("Expected <group>=<policy>")?;
+    let kind = match kind {
+        "documents" => ExtraKind::Document,
+        "videos" => ExtraKind::Video,
+        "archives" => ExtraKind::Archive,
+        _ => return Err(format!("Unknown group {kind}, expected documents, videos or archives")),
+    };
+    let policy = match policy {
+        "follow" => ExtraPolicy::Follow,
+        "leave" => ExtraPolicy::Leave,
+        "collect" => ExtraPolicy::Collect,
+        _ => return Err(format!("Unknown policy {policy}, expected follow, leave or collect")),
+    };
+    Ok((kind, policy))
+}
+
+/// Reads the argument from an environment variable like `MUSIC_ORGANIZER_ASSUME_YES` if it isn't
+/// passed, flags accept boolish values like `1` or `yes`.
+/// Parses the filters of a config option, invalid filters are fatal.
+fn config_filters(option: &str, filters: &[String], config_path: &Path) -> Vec<Filter> {
+    let parse = |f: &String| {
+        f.parse::<Filter>().unwrap_or_else(|e| {
+            println!("Error: invalid {option} filter '{f}' in {}: {e}", config_path.display());
+            std::process::exit(crate::EXIT_FATAL);
+        })
+    };
+    filters.iter().map(parse).collect()
+}
+
+/// Resolves `..` and symlinks of the part of the path that exists, the rest is appended as is,
+/// so an output dir that isn't created yet can be compared with the music dir.
+fn canonicalize_existing(path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        if let Ok(canonical) = ancestor.canonicalize() {
+            return canonical.join(path.strip_prefix(ancestor).unwrap_or(path));
+        }
+    }
+    path.to_owned()
+}
+
+fn with_env(arg: Arg) -> Arg {
+    if arg.get_id() == "generate-completion" {
+        return arg;
+    }
+    let id = arg.get_id().as_str().to_ascii_uppercase().replace('-', "_");
+    let name = format!("{ENV_PREFIX}{id}");
+    if arg.get_num_args() != Some(ValueRange::EMPTY) {
+        return arg.env(name);
+    }
+    // clap considers flags set to false present, so they would conflict with other arguments
+    let value = std::env::var(&name).unwrap_or_default().to_ascii_lowercase();
+    let arg = arg.action(ArgAction::SetTrue).value_parser(BoolishValueParser::new());
+    match value.as_str() {
+        "n" | "no" | "f" | "false" | "off" | "0" => arg,
+        _ => arg.env(name),
+    }
 }
 
 pub fn parse_args() -> Args {
@@ -49,6 +399,26 @@ pub fn parse_args() -> Args {
         .version(crate_version!())
         .author(crate_authors!())
         .about("Moves/copies, renames and retags Music files using their metadata.")
+        .subcommand(
+            Command::new("diff")
+                .about("Lists the songs of two libraries that are missing in the other one")
+                .long_about(
+                    "Lists the songs of two libraries that are missing in the other one. Songs \
+                    are compared by their release artists, release, disc and track number and \
+                    title, so differing file names or formats don't matter. Exits with 0 if \
+                    the libraries match, 3 if they differ and 2 if one couldn't be read.",
+                )
+                .arg(Arg::new("dir-a").required(true).value_hint(ValueHint::DirPath))
+                .arg(Arg::new("dir-b").required(true).value_hint(ValueHint::DirPath)),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("file")
+                .help("TOML config file [default: ~/.config/music-organizer/config.toml]")
+                .num_args(1)
+                .value_hint(ValueHint::FilePath),
+        )
         .arg(
             Arg::new("music-dir")
                 .short('m')
@@ -74,6 +444,375 @@ pub fn parse_args() -> Args {
                 .num_args(0)
                 .requires("output-dir"),
         )
+        .arg(
+            Arg::new("index-cache")
+                .long("index-cache")
+                .value_name("file")
+                .help("Cache file used to skip reading the metadata of unchanged files")
+                .num_args(1)
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("export-index")
+                .long("export-index")
+                .value_name("file")
+                .help("Write the index to a JSON file, or a CSV file if the extension is csv")
+                .num_args(1)
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("itunes-library")
+                .long("itunes-library")
+                .value_name("file")
+                .help("Import ratings, play counts and playlists from an iTunes Library.xml")
+                .long_help(
+                    "Import ratings, play counts and playlists from an iTunes or Apple Music \
+                    Library.xml. Ratings and play counts are written into the tags of the \
+                    matching songs, playlists are created as m3u files inside of playlists in \
+                    the output dir. Tracks are matched by their location, or by their location \
+                    inside of the music folder of the library if it was moved into the music \
+                    dir.",
+                )
+                .num_args(1)
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("check-report")
+                .long("check-report")
+                .value_name("file")
+                .help("Write the findings of the checks to a JSON file without asking questions")
+                .long_help(
+                    "Write the findings of the checks to a JSON file. Questions that aren't \
+                    answered by the answers file or --answer are listed with their key and \
+                    valid answers instead of being asked, so an answers file can be prepared \
+                    from the report. Also lists the tag and permission updates of the checks. \
+                    Only works in a dry run.",
+                )
+                .requires("dryrun")
+                .conflicts_with("nocheck")
+                .num_args(1)
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("updates-from")
+                .long("updates-from")
+                .value_name("file")
+                .help("Apply tag updates generated by other tools from a JSON file")
+                .long_help(
+                    "Apply tag updates generated by other tools, e.g. a script or a spreadsheet \
+                    export, together with the changes of the checks. The JSON file contains a \
+                    list of {\"path\": ..., \"update\": {...}, \"reason\": ...} objects. Paths are \
+                    absolute or relative to the music dir, updates contain only the changed \
+                    fields like {\"year\": {\"Update\": 2001}, \"genre\": \"Remove\"}, and values \
+                    also updated by a check are taken from the file.",
+                )
+                .num_args(1)
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("files-from")
+                .long("files-from")
+                .value_name("file")
+                .help("Only index the files listed in the file, one per line, or stdin if it is -")
+                .long_help(
+                    "Only index the files listed in the file, one per line, or stdin if it is -. \
+                    The music dir isn't walked, but it is still cleaned up.\n\
+                    Questions are also answered through stdin, so use --assume-yes or --dryrun \
+                    when reading the list from stdin.",
+                )
+                .num_args(1)
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("release-dir")
+                .long("release-dir")
+                .value_name("dir")
+                .help("Only organize the release in this dir inside of the music dir")
+                .long_help(
+                    "Only organize the release in this dir inside of the music dir, e.g. from a \
+                    file manager. Only the files inside of the dir are indexed and moved to \
+                    their path of the layout, the checks are skipped since they compare releases \
+                    across the library, and only the dir inside of the music dir containing it is \
+                    cleaned up.",
+                )
+                .num_args(1)
+                .conflicts_with("files-from")
+                .value_hint(ValueHint::DirPath),
+        )
+        .arg(
+            Arg::new("sync")
+                .long("sync")
+                .value_name("dir")
+                .help("Mirror the organized output dir into another dir after organizing")
+                .long_help(
+                    "Mirror the organized output dir into another dir after organizing, e.g. a \
+                    phone, USB drive or NAS. New files and files whose size changed or that were \
+                    modified after their last sync are copied.",
+                )
+                .num_args(1)
+                .value_hint(ValueHint::DirPath),
+        )
+        .arg(
+            Arg::new("sync-delete")
+                .long("sync-delete")
+                .help("Delete files from the sync dir that aren't in the output dir anymore")
+                .num_args(0)
+                .requires("sync"),
+        )
+        .arg(
+            Arg::new("naming")
+                .long("naming")
+                .value_name("profile")
+                .help("File names supported by the sync dir")
+                .long_help(
+                    "File names supported by the sync dir. fat removes characters that are \
+                    reserved on FAT32 and exFAT, trims trailing dots and spaces and compares \
+                    names case insensitively.",
+                )
+                .num_args(1)
+                .value_parser(["posix", "fat"])
+                .default_value("posix")
+                .requires("sync"),
+        )
+        .arg(
+            Arg::new("transcode")
+                .long("transcode")
+                .value_name("codec")
+                .help("Transcode lossless songs when syncing, lossy ones are copied as they are")
+                .long_help(
+                    "Transcode lossless songs to opus or mp3 using ffmpeg when syncing, lossy \
+                    songs are copied as they are. Transcoded songs keep their names and tags, \
+                    but not their embedded artworks.",
+                )
+                .num_args(1)
+                .value_parser(value_parser!(TranscodeCodec))
+                .requires("sync"),
+        )
+        .arg(
+            Arg::new("transcode-bitrate")
+                .long("transcode-bitrate")
+                .value_name("kbit/s")
+                .help("Bitrate of transcoded songs")
+                .num_args(1)
+                .value_parser(value_parser!(u32).range(8..=512))
+                .default_value("128")
+                .requires("transcode"),
+        )
+        .arg(
+            Arg::new("ffmpeg")
+                .long("ffmpeg")
+                .value_name("path")
+                .help("The ffmpeg executable used for transcoding")
+                .num_args(1)
+                .value_parser(value_parser!(PathBuf))
+                .default_value("ffmpeg")
+                .value_hint(ValueHint::ExecutablePath)
+                .requires("transcode"),
+        )
+        .arg(
+            Arg::new("sync-max-size")
+                .long("sync-max-size")
+                .value_name("size")
+                .help("Only sync as many files as fit into the size, e.g. 64G")
+                .long_help(
+                    "Only sync as many files as fit into the size, e.g. 64G or 500MiB. Files are \
+                    selected in the order of --sync-order, files of --sync-prefer first. Files \
+                    that don't fit are skipped and deleted from the sync dir with --sync-delete.",
+                )
+                .num_args(1)
+                .value_parser(parse_size)
+                .requires("sync"),
+        )
+        .arg(
+            Arg::new("sync-order")
+                .long("sync-order")
+                .value_name("order")
+                .help("Which files are synced first if the size is limited")
+                .long_help(
+                    "Which files are synced first if the size is limited. recent selects the most \
+                    recently modified files first, rated the highest rated songs first.",
+                )
+                .num_args(1)
+                .value_parser(["recent", "rated"])
+                .default_value("recent")
+                .requires("sync-max-size"),
+        )
+        .arg(
+            Arg::new("sync-prefer")
+                .long("sync-prefer")
+                .value_name("path")
+                .help("A dir, e.g. of an artist, or a playlist whose files are synced first")
+                .long_help(
+                    "A dir, e.g. of an artist, or a playlist whose files are synced first if the \
+                    size is limited. Relative paths are relative to the output dir, can be passed \
+                    multiple times.",
+                )
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_hint(ValueHint::AnyPath)
+                .requires("sync-max-size"),
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .help("Keep running and organize new files in the music dir once they settle")
+                .long_help(
+                    "Keep running and organize new or modified files in the music dir once no \
+                    changes happened for the settle time. Implies --assume-yes, check dialogs \
+                    without a recorded answer are skipped.",
+                )
+                .num_args(0)
+                .conflicts_with_all(["files-from", "release-dir", "stats", "dryrun"]),
+        )
+        .arg(
+            Arg::new("settle")
+                .long("settle")
+                .value_name("secs")
+                .help("Seconds without changes before new files are organized in watch mode")
+                .value_parser(value_parser!(u64))
+                .default_value("10")
+                .requires("watch"),
+        )
+        .arg(
+            Arg::new("index-socket")
+                .long("index-socket")
+                .value_name("file")
+                .help("Serve the index on a unix socket in watch mode, so other tools can query it")
+                .long_help(
+                    "Serve a read-only view of the index on a unix socket in watch mode, so other \
+                    tools can query it without reading the music dir. The index is read again \
+                    after each run. Each request is a line, each response a line of json:\n\
+                    artists              the names of all release artists\n\
+                    releases <artist>    the releases of the release artist\n\
+                    songs <release>      the songs of all releases with the name\n\
+                    search <text>        songs whose title, artists or release contain the text\n\
+                    Only supported on unix.",
+                )
+                .value_hint(ValueHint::FilePath)
+                .num_args(1)
+                .requires("watch"),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .value_name("count")
+                .help("Number of threads used for indexing [default: available parallelism]")
+                .value_parser(value_parser!(NonZeroUsize)),
+        )
+        .arg(
+            Arg::new("write-threads")
+                .long("write-threads")
+                .value_name("count")
+                .help("Number of songs that are moved and retagged concurrently [default: 1]")
+                .value_parser(value_parser!(NonZeroUsize)),
+        )
+        .arg(
+            Arg::new("follow-symlinks")
+                .long("follow-symlinks")
+                .help("Follow symbolic links when indexing")
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("max-depth")
+                .long("max-depth")
+                .value_name("depth")
+                .help("Only index and clean up entries up to this depth inside of the music dir")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("include-hidden")
+                .long("include-hidden")
+                .help("Include files and dirs whose name starts with a dot")
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .help("Print statistics about the library after indexing and exit")
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("health")
+                .long("health")
+                .value_name("format")
+                .help("Print a health report of the library after indexing and exit")
+                .long_help(
+                    "Print a health report of the library after indexing and exit.\n\
+                    Scores the share of songs with artwork, year, genre, correct permissions and \
+                    a path that conforms to the layout, and lists the releases with the most \
+                    failing songs per category. The json format is written to stdout, while all \
+                    other output goes to stderr.",
+                )
+                .value_parser(["text", "json"])
+                .num_args(0..=1)
+                .default_missing_value("text")
+                .conflicts_with("stats"),
+        )
+        .arg(
+            Arg::new("incomplete-report")
+                .long("incomplete-report")
+                .value_name("file")
+                .help("Report the releases that are missing tracks after indexing and exit")
+                .long_help(
+                    "Report the releases that are missing tracks after indexing and exit.\n\
+                    Missing tracks are found using the total tracks of each disc. The report is \
+                    printed, or written to a JSON file, or a CSV file if the extension is csv.",
+                )
+                .num_args(0..=1)
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .conflicts_with_all(["stats", "health"]),
+        )
+        .arg(
+            Arg::new("dedup")
+                .long("dedup")
+                .value_name("action")
+                .help("Find files with identical contents and delete or hardlink the copies")
+                .long_help(
+                    "Find files with identical contents after indexing, regardless of their \
+                    names, and delete the copies or replace them with hard links to the \
+                    original, which is the first file by path. Files are compared by size and \
+                    then by a hash of their contents.\n\
+                    Songs with identical audio but different tags are found as well, the one with \
+                    artwork and the most tags is kept and tags it's missing are merged from the \
+                    copies first.",
+                )
+                .value_parser(["delete", "hardlink"])
+                .num_args(0..=1)
+                .default_missing_value("delete"),
+        )
+        .arg(
+            Arg::new("quarantine")
+                .long("quarantine")
+                .help("Set problem files aside in the quarantine dir of the output dir")
+                .long_help(
+                    "Set problem files aside in the quarantine dir of the output dir, instead of \
+                    organizing them. Corrupt unknown songs are moved to quarantine/corrupt, files \
+                    that would overwrite another file to quarantine/collision and with --dedup the \
+                    copies to quarantine/duplicate. A text file next to each file explains why.",
+                )
+                .num_args(0)
+                .conflicts_with("tag-only"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Abort before writing if any file would be moved to unknown or quarantine")
+                .long_help(
+                    "Abort before writing if any file would be moved into the unknown dir or set \
+                    aside in the quarantine dir, after listing them. For keeping a library fully \
+                    organized by fixing the tags of those files first. Also exits with an error \
+                    in a dryrun.",
+                )
+                .num_args(0)
+                .conflicts_with("tag-only"),
+        )
+        .arg(
+            Arg::new("precount")
+                .long("precount")
+                .help("Count the files before indexing to show the progress")
+                .num_args(0),
+        )
         .arg(
             Arg::new("nocheck")
                 .short('n')
@@ -81,6 +820,180 @@ pub fn parse_args() -> Args {
                 .help("Don't check for inconsistencies")
                 .num_args(0),
         )
+        .arg(
+            Arg::new("rename-only")
+                .long("rename-only")
+                .help("Only move and rename files, without modifying their contents")
+                .long_help(
+                    "Only move and rename files, without modifying their contents.\n\
+                    Skips all checks, including embedded artwork removal, permission fixes and \
+                    tag updates. Tags of imports, external updates, scripts and provenance tags \
+                    are only used to organize the songs and duplicates whose tags would have to \
+                    be merged are kept.",
+                )
+                .num_args(0)
+                .conflicts_with_all(["tag-only", "infer-tags"]),
+        )
+        .arg(
+            Arg::new("answers")
+                .long("answers")
+                .value_name("file")
+                .help("JSON file of answers to check dialogs, new answers are added to it")
+                .num_args(1)
+                .value_hint(ValueHint::FilePath)
+                .conflicts_with("nocheck"),
+        )
+        .arg(
+            Arg::new("decisions")
+                .long("decisions")
+                .value_name("file")
+                .help("JSON file of dialogs that are never asked again")
+                .long_help(
+                    "JSON file of dialogs that were answered with never ask again, in the format \
+                    of the answers file. Defaults to decisions.json next to the config file.",
+                )
+                .num_args(1)
+                .value_hint(ValueHint::FilePath)
+                .conflicts_with("nocheck"),
+        )
+        .arg(
+            Arg::new("answer")
+                .long("answer")
+                .value_name("question=answer")
+                .help("Answer a check dialog e.g. 'inconsistent-artist:Radiohead|radiohead=first'")
+                .long_help(
+                    "Answer a check dialog e.g. 'inconsistent-artist:Radiohead|radiohead=first', \
+                    takes precedence over the answers file.\n\
+                    Inconsistent artists are answered with none, first, second or names:A;B, \
+                    invalid track or disc numbers and compilations with fix or none, release suffixes with \
+                    standard, strip or none, release artist votes and split releases with none or \
+                    names:A;B and inconsistent totals with none or the total",
+                )
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(answers::parse_answer)
+                .conflicts_with("nocheck"),
+        )
+        .arg(
+            Arg::new("infer-tags")
+                .long("infer-tags")
+                .value_name("policy")
+                .help(
+                    "Infer missing tags of unknown songs from their path, ask before applying them",
+                )
+                .long_help(
+                    "Infer missing tags of unknown songs from their path, like \
+                    Artist/Release/01 - Title.mp3 or Artist - Release - 01 - Title.mp3.\n\
+                    ask confirms the tags of each song, apply applies them without asking.",
+                )
+                .value_parser(["ask", "apply"])
+                .num_args(0..=1)
+                .default_missing_value("ask")
+                .conflicts_with("nocheck"),
+        )
+        .arg(
+            Arg::new("various-artists")
+                .long("various-artists")
+                .value_name("policy")
+                .help("Set the release artist of compilations without one to Various Artists")
+                .long_help(
+                    "Set the release artist of releases without one, whose songs have many \
+                    distinct artists, to Various Artists. Otherwise each song is organized into \
+                    the dir of its own artists.\n\
+                    ask asks for each release, apply sets it without asking and skip leaves \
+                    them as they are.",
+                )
+                .value_parser(["ask", "apply", "skip"])
+                .default_value("ask"),
+        )
+        .arg(
+            Arg::new("normalize-totals")
+                .long("normalize-totals")
+                .value_name("policy")
+                .help("Give all songs of a disc or release the same total tracks and total discs")
+                .long_help(
+                    "Give all songs of a disc or release the same total tracks and total discs, \
+                    if they differ or are missing on some songs.\n\
+                    max uses the highest total or number, count the number of songs of the disc \
+                    or the number of discs. Only asks if the totals differ and max and count \
+                    disagree, e.g. because the release is incomplete.",
+                )
+                .value_parser(["max", "count"])
+                .num_args(0..=1)
+                .default_missing_value("max")
+                .conflicts_with("nocheck"),
+        )
+        .arg(
+            Arg::new("vote-release-artists")
+                .long("vote-release-artists")
+                .help("Set missing release artists to the artists of most songs of the release")
+                .long_help(
+                    "Set missing release artists to the artists of most songs of the release, \
+                    instead of organizing each song into the dir of its own artists. Unknown \
+                    songs that only lack artists get them the same way. Only asks if no \
+                    artists have a majority.",
+                )
+                .num_args(0)
+                .conflicts_with("nocheck"),
+        )
+        .arg(
+            Arg::new("similarity-scope")
+                .long("similarity-scope")
+                .value_name("scope")
+                .help("Which artists are compared when looking for inconsistent names")
+                .long_help(
+                    "Which release artists are compared when looking for names that only differ \
+                    in case, accents or a leading article: library compares all of them, \
+                    letter:<letter> those starting with the letter and artist:<name> those named \
+                    like the artist, which keeps large libraries fast when only a part of them \
+                    changed.",
+                )
+                .value_parser(parse_similarity_scope)
+                .default_value("library")
+                .conflicts_with("nocheck"),
+        )
+        .arg(
+            Arg::new("bonus-tracks")
+                .long("bonus-tracks")
+                .value_name("policy")
+                .help("Flag bonus tracks or place them in an Extras dir of their release")
+                .long_help(
+                    "Check for bonus tracks, instrumentals and hidden tracks, which have a track \
+                    number beyond the total tracks or a suffix like (Bonus Track) in their \
+                    title.\n\
+                    flag only reports them, extras places them in an Extras dir inside of their \
+                    release dir, keeping the release dir clean. Releases that only consist of \
+                    such tracks are left as they are.",
+                )
+                .value_parser(["flag", "extras"])
+                .num_args(0..=1)
+                .default_missing_value("flag")
+                .conflicts_with("nocheck"),
+        )
+        .arg(
+            Arg::new("fill-release-tags")
+                .long("fill-release-tags")
+                .help("Copy tags most songs of a release have to the songs lacking them")
+                .long_help(
+                    "Copy the year, genre, album artist and total tracks most songs of a release \
+                    have to the songs of the release lacking them, so a single badly ripped song \
+                    doesn't end up in a dir of its own.",
+                )
+                .num_args(0)
+                .conflicts_with("nocheck"),
+        )
+        .arg(
+            Arg::new("flatten-discs")
+                .long("flatten-discs")
+                .help("Number the tracks of releases with multiple discs across all discs")
+                .long_help(
+                    "Number the tracks of releases with multiple discs sequentially across all \
+                    discs and remove the disc numbers, for players that ignore them. Track 1 of \
+                    disc 2 becomes track 13 if disc 1 has 12 tracks.",
+                )
+                .num_args(0)
+                .conflicts_with("nocheck"),
+        )
         .arg(
             Arg::new("keep embedded artworks")
                 .short('e')
@@ -88,12 +1001,484 @@ pub fn parse_args() -> Args {
                 .help("Keep embedded artworks")
                 .num_args(0),
         )
+        .arg(
+            Arg::new("keep-artwork")
+                .long("keep-artwork")
+                .value_name("key:pattern")
+                .help("Keep the embedded artwork of songs matching the filter e.g. artist:Foo")
+                .long_help(format!(
+                    "Keep the embedded artwork of songs matching the filter e.g. artist:Foo or \
+                    'release:*Live*', while removing it from all other songs. Added to the \
+                    keep_artwork filters of the config.\n\
+                    Patterns are case insensitive globs, paths are relative to the music dir. \
+                    A song keeps its artwork if it matches any of the filters.\n\
+                    Keys: {}",
+                    FILTER_KEYS.join(", ")
+                ))
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(|s: &str| s.parse::<Filter>().map_err(|e| e.to_string()))
+                .conflicts_with_all(["keep embedded artworks", "nocheck"]),
+        )
+        .arg(
+            Arg::new("keep-playlists")
+                .long("keep-playlists")
+                .help("Don't rewrite m3u, pls and xspf playlists that reference moved songs")
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("release-playlists")
+                .long("release-playlists")
+                .help("Create an m3u playlist of each release inside of its directory")
+                .num_args(0)
+                .conflicts_with("tag-only"),
+        )
+        .arg(
+            Arg::new("release-nfo")
+                .long("release-nfo")
+                .help("Write a text summary of each release into its directory")
+                .long_help(
+                    "Write a text summary of each release into its directory, named after the \
+                    release with the nfo extension. It's rendered using --nfo-template and \
+                    --nfo-track-template.",
+                )
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("nfo-template")
+                .long("nfo-template")
+                .value_name("template")
+                .help("Template of the release summary")
+                .long_help(format!(
+                    "Template of the release summary, using the syntax of --layout. \\n is \
+                    replaced by a newline and {{tracks}} by the track list.\n\
+                    Fields: {}",
+                    NFO_FIELDS.join(", ")
+                ))
+                .num_args(1)
+                .value_parser(|s: &str| {
+                    NfoTemplate::new(s, "").map(|_| s.to_owned()).map_err(|e| e.to_string())
+                })
+                .default_value(DEFAULT_NFO_TEMPLATE)
+                .requires("release-nfo"),
+        )
+        .arg(
+            Arg::new("nfo-track-template")
+                .long("nfo-track-template")
+                .value_name("template")
+                .help("Template of each line of the track list in the release summary")
+                .long_help(
+                    "Template of each line of the track list in the release summary, using the \
+                    syntax and fields of --layout.",
+                )
+                .num_args(1)
+                .value_parser(|s: &str| {
+                    NfoTemplate::new("", s).map(|_| s.to_owned()).map_err(|e| e.to_string())
+                })
+                .default_value(DEFAULT_NFO_TRACK_TEMPLATE)
+                .requires("release-nfo"),
+        )
+        .arg(
+            Arg::new("artist-playlists")
+                .long("artist-playlists")
+                .help("Create an m3u playlist of all releases of each artist")
+                .num_args(0)
+                .conflicts_with("tag-only"),
+        )
         .arg(
             Arg::new("nocleanup")
                 .long("nocleanup")
                 .help("Don't remove empty directories")
                 .num_args(0),
         )
+        .arg(
+            Arg::new("tag-only")
+                .long("tag-only")
+                .help("Only retag files where they are, without moving, renaming or cleaning up")
+                .num_args(0)
+                .conflicts_with_all([
+                    "output-dir",
+                    "copy",
+                    "layout",
+                    "video-layout",
+                    "audiobook-layout",
+                    "dir-mode",
+                    "dir-owner",
+                    "remove-junk",
+                ]),
+        )
+        .arg(
+            Arg::new("remove-junk")
+                .long("remove-junk")
+                .help("Remove junk files like .DS_Store when cleaning up otherwise empty dirs")
+                .num_args(0)
+                .conflicts_with("nocleanup"),
+        )
+        .arg(
+            Arg::new("junk-pattern")
+                .long("junk-pattern")
+                .value_name("pattern")
+                .help("Glob pattern of a junk file, replaces the default patterns")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .requires("remove-junk"),
+        )
+        .arg(
+            Arg::new("images")
+                .long("images")
+                .value_name("policy")
+                .help("What happens to images and artwork dirs next to moved songs")
+                .long_help(
+                    "What happens to images and scan or artwork dirs next to moved songs.\n\
+                    follow moves or copies them with their songs, copy always copies them, leave \
+                    leaves them where they are and delete removes them with their otherwise empty \
+                    dirs.",
+                )
+                .value_parser(["follow", "copy", "leave", "delete"])
+                .default_value("follow")
+                .conflicts_with("tag-only"),
+        )
+        .arg(
+            Arg::new("cover-name")
+                .long("cover-name")
+                .value_name("name")
+                .help("Rename covers next to songs to the name a media server prefers")
+                .long_help(
+                    "Rename images named cover, folder, front, poster or albumart next to songs \
+                    to the name a media server prefers, keeping their extension. Covers are \
+                    renamed even if their songs stay in place, dirs with multiple covers are \
+                    left as they are.\n\
+                    Names are cover, folder and poster or the presets navidrome (cover), kodi \
+                    (folder) and plex (poster).",
+                )
+                .value_parser(["keep", "cover", "folder", "poster", "navidrome", "kodi", "plex"])
+                .default_value("keep")
+                .conflicts_with("tag-only"),
+        )
+        .arg(
+            Arg::new("non-latin-artists")
+                .long("non-latin-artists")
+                .value_name("policy")
+                .help("How dirs of artists with non-Latin names are named")
+                .long_help(
+                    "How dirs of artists with Cyrillic, Greek, Japanese, Korean, Chinese or other \
+                    non-Latin names are named, the tags and file names keep the original names.\n\
+                    keep keeps the names, romanize transliterates Cyrillic, Greek, kana and \
+                    Hangul and groups other names in the bucket dir and group groups all of them \
+                    in the bucket dir.",
+                )
+                .value_parser(["keep", "romanize", "group"])
+                .default_value("keep")
+                .conflicts_with("tag-only"),
+        )
+        .arg(
+            Arg::new("collaborations")
+                .long("collaborations")
+                .value_name("policy")
+                .help("Where releases of multiple release artists, like A & B, are filed")
+                .long_help(
+                    "Where releases of multiple release artists, like A & B, are filed.\n\
+                    joint files them in a joint dir of all release artists, linked files them \
+                    under the first release artist and hard links the songs into the dirs of the \
+                    others and first-artist files them under the first release artist with the \
+                    others noted in the release name, e.g. Release (with B).",
+                )
+                .value_parser(["joint", "linked", "first-artist"])
+                .default_value("joint")
+                .conflicts_with("tag-only"),
+        )
+        .arg(
+            Arg::new("lowercase-extensions")
+                .long("lowercase-extensions")
+                .help("Lowercase the extensions of renamed files, e.g. .MP3 to .mp3")
+                .num_args(0)
+                .conflicts_with("tag-only"),
+        )
+        .arg(
+            Arg::new("non-latin-bucket")
+                .long("non-latin-bucket")
+                .value_name("name")
+                .help("The dir grouping artists with non-Latin names")
+                .num_args(1)
+                .default_value("#"),
+        )
+        .arg(
+            Arg::new("extras")
+                .long("extras")
+                .value_name("group=policy")
+                .help("What happens to documents, videos and archives, e.g. videos=leave")
+                .long_help(
+                    "What happens to documents, videos and archives next to songs, e.g. \
+                    videos=leave. Groups are documents, videos and archives.\n\
+                    follow moves or copies them with their songs, leave leaves them where they \
+                    are and collect moves them into the extras dir of the output dir.",
+                )
+                .value_parser(parse_extra_policy)
+                .action(ArgAction::Append)
+                .conflicts_with("tag-only"),
+        )
+        .arg(
+            Arg::new("trash")
+                .long("trash")
+                .help("Move deleted directories to the trash instead of removing them permanently")
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("dir-mode")
+                .long("dir-mode")
+                .value_name("mode")
+                .help("Octal permissions applied to created directories e.g. 755")
+                .num_args(1)
+                .value_parser(parse_mode),
+        )
+        .arg(
+            Arg::new("dir-owner")
+                .long("dir-owner")
+                .value_name("user[:group]")
+                .help("Owner of created directories, names or numeric ids, usually requires root")
+                .num_args(1)
+                .value_parser(parse_owner),
+        )
+        .arg(
+            Arg::new("preserve-ownership")
+                .long("preserve-ownership")
+                .help("Copied files keep their owner and ACLs, usually requires root")
+                .num_args(0)
+                .requires("copy"),
+        )
+        .arg(
+            Arg::new("preserve-dir-mtimes")
+                .long("preserve-dir-mtimes")
+                .help("Dirs whose files are only renamed keep their modification time")
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("layout")
+                .long("layout")
+                .value_name("template")
+                .help("Path template of songs inside the output dir, without the extension")
+                .long_help(format!(
+                    "Path template of songs inside the output dir, without the extension.\n\
+                    {{field}} is replaced by the field, {{field:02}} pads numbers with zeros and \
+                    text inside [...] is omitted if a field inside it has no value.\n\
+                    Fields: {}\n\
+                    Custom fields declared in the config are used as {{{EXTRA_FIELD_PREFIX}name}}\n\
+                    Releases are grouped, e.g. into box sets, by the {RELEASE_GROUP_FIELD} tag or \
+                    if releases of an artist share a prefix like 'Box Set: Title'. The group and \
+                    the title are used as e.g. \
+                    {{release_artists}}/[{{release_group}}]/{{release_title}}/{{title}}",
+                    LAYOUT_FIELDS.join(", ")
+                ))
+                .num_args(1)
+                .value_parser(|s: &str| s.parse::<Layout>().map_err(|e| e.to_string()))
+                .default_value(DEFAULT_LAYOUT),
+        )
+        .arg(
+            Arg::new("video-layout")
+                .long("video-layout")
+                .value_name("template")
+                .help("Path template of tagged music videos inside the output dir")
+                .long_help(
+                    "Path template of tagged mp4 and m4v music videos inside the output dir, \
+                    without the extension. Only the artists, title, year and genre fields have \
+                    values.",
+                )
+                .num_args(1)
+                .value_parser(|s: &str| s.parse::<Layout>().map_err(|e| e.to_string()))
+                .default_value(DEFAULT_VIDEO_LAYOUT),
+        )
+        .arg(
+            Arg::new("audiobook-layout")
+                .long("audiobook-layout")
+                .value_name("template")
+                .help("Path template of audiobooks inside the output dir")
+                .long_help(
+                    "Path template of tagged m4b audiobooks inside the output dir, without the \
+                    extension. Only the release_artists (the authors), artists, release (the \
+                    book), title, year and genre fields have values. Audiobooks are never \
+                    checked like songs.",
+                )
+                .num_args(1)
+                .value_parser(|s: &str| s.parse::<Layout>().map_err(|e| e.to_string()))
+                .default_value(DEFAULT_AUDIOBOOK_LAYOUT),
+        )
+        .arg(
+            Arg::new("artist-separator")
+                .long("artist-separator")
+                .value_name("sep")
+                .help("Split artist tags on this separator e.g. ';', '/' or ' feat. '")
+                .num_args(1)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("artist-join")
+                .long("artist-join")
+                .value_name("sep")
+                .help("Join multiple artists in directory and file names with this separator")
+                .num_args(1)
+                .default_value(", "),
+        )
+        .arg(
+            Arg::new("artist-tag-join")
+                .long("artist-tag-join")
+                .value_name("sep")
+                .help("Join multiple artists in mp3 tags with this separator")
+                .long_help(
+                    "Join multiple artists in mp3 tags with this separator. By default they are \
+                    written as separate id3v2.4 values, which not all players support.",
+                ),
+        )
+        .arg(
+            Arg::new("filter")
+                .long("filter")
+                .value_name("key:pattern")
+                .help("Only check and change songs matching the filter e.g. artist:Radiohead")
+                .long_help(format!(
+                    "Only check and change songs matching the filter e.g. artist:Radiohead or \
+                    'path:Incoming/*'.\n\
+                    Patterns are case insensitive globs, paths are relative to the music dir. \
+                    A song has to match one of the filters of each key.\n\
+                    Keys: {}",
+                    FILTER_KEYS.join(", ")
+                ))
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(|s: &str| s.parse::<Filter>().map_err(|e| e.to_string())),
+        )
+        .arg(
+            Arg::new("no-retag")
+                .long("no-retag")
+                .value_name("key:pattern")
+                .help("Never rewrite the tags of songs matching the filter e.g. format:flac")
+                .long_help(format!(
+                    "Never rewrite the tags of songs matching the filter e.g. format:flac or \
+                    'path:Trusted/*', they are only moved and renamed. Added to the no_retag \
+                    filters of the config.\n\
+                    Patterns are case insensitive globs, paths are relative to the music dir. \
+                    A song is excluded if it matches any of the filters.\n\
+                    Keys: {}",
+                    FILTER_KEYS.join(", ")
+                ))
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(|s: &str| s.parse::<Filter>().map_err(|e| e.to_string()))
+                .conflicts_with("nocheck"),
+        )
+        .arg(
+            Arg::new("backup-dir")
+                .long("backup-dir")
+                .value_name("dir")
+                .help("Back up files before they are moved or retagged")
+                .long_help(
+                    "Back up files before they are moved or retagged, into a dir named after \
+                    the time of the run inside of this dir. Songs that are only copied aren't \
+                    backed up.",
+                )
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("keep-backups")
+                .long("keep-backups")
+                .value_name("count")
+                .help("The number of backups that are kept, older ones are removed after a run")
+                .value_parser(value_parser!(usize))
+                .default_value("10")
+                .requires("backup-dir"),
+        )
+        .arg(
+            Arg::new("provenance-tag")
+                .long("provenance-tag")
+                .help("Record the version, date and original path in a tag of organized songs")
+                .long_help(format!(
+                    "Record the version, date and original path as json in the {PROVENANCE_FIELD} \
+                    tag of songs that are moved, renamed or retagged. Songs that would only be \
+                    moved are retagged as well."
+                ))
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("provenance-manifest")
+                .long("provenance-manifest")
+                .value_name("file")
+                .help("Append the version, date, original and new path of organized files to a file")
+                .long_help(
+                    "Append the version, date, original and new path of every file that was \
+                    moved, renamed or retagged to this file, one json object per line.",
+                )
+                .value_hint(ValueHint::FilePath)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("plan-snapshot")
+                .long("plan-snapshot")
+                .value_name("file")
+                .help("Mark operations of dry runs that are new since the last applied plan")
+                .long_help(
+                    "Store the planned operations in this file when they are applied. Dry runs \
+                    read it and mark the operations that are new or changed since then, so only \
+                    those need to be reviewed.",
+                )
+                .value_hint(ValueHint::FilePath)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("path-manifest")
+                .long("path-manifest")
+                .value_name("file")
+                .help("Write the old and new paths of moved files to a JSON or CSV file")
+                .long_help(
+                    "Write the old and new path of every file that was moved or renamed to a JSON \
+                    file, or a CSV file if the extension is csv, at the end of the run. The file \
+                    is replaced on every run.",
+                )
+                .value_hint(ValueHint::FilePath)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .value_name("count")
+                .help("Retry file operations that failed because of a transient error")
+                .value_parser(value_parser!(u32))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("retry-delay")
+                .long("retry-delay")
+                .value_name("millis")
+                .help("Delay before the first retry, which is doubled after each attempt")
+                .value_parser(value_parser!(u64))
+                .default_value("100"),
+        )
+        .arg(
+            Arg::new("fail-fast")
+                .long("fail-fast")
+                .help("Stop at the first failed operation")
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("atomic-releases")
+                .long("atomic-releases")
+                .help("Roll back the songs of a release if one of them fails")
+                .long_help(
+                    "Organize songs release by release and roll back the already organized songs \
+                    of a release if one of them fails, so no release is left half in the old and \
+                    half in the new layout. Songs aren't written concurrently then.",
+                )
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("staging")
+                .long("staging")
+                .help("Copy songs into a staging dir and move them into place release by release")
+                .long_help(
+                    "Copy songs into a hidden staging dir inside the output dir first and move \
+                    them into place once all songs of their release are copied, so media servers \
+                    never pick up half copied releases. The staging dir is removed afterwards.",
+                )
+                .requires("copy")
+                .num_args(0),
+        )
         .arg(
             Arg::new("assume-yes")
                 .short('y')
@@ -101,6 +1486,24 @@ pub fn parse_args() -> Args {
                 .help("Assumes yes as a answer for questions")
                 .num_args(0),
         )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .help("Organize dirs that look dangerous, like / or the home dir")
+                .long_help(
+                    "Organize dirs that look dangerous, like / or the home dir, or a music dir \
+                    that is a mount point with mostly other files than music. Every file that \
+                    isn't a song would be moved into the unknown dir.",
+                )
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("confirm-releases")
+                .long("confirm-releases")
+                .help("Ask for approval of the changes of each release, instead of all at once")
+                .num_args(0)
+                .conflicts_with_all(["assume-yes", "watch"]),
+        )
         .arg(
             Arg::new("dryrun")
                 .short('d')
@@ -109,15 +1512,91 @@ pub fn parse_args() -> Args {
                 .num_args(0)
                 .conflicts_with("assume-yes"),
         )
+        .arg(
+            Arg::new("print-new-paths")
+                .long("print-new-paths")
+                .help(
+                    "Print the new paths of all written files to stdout, one per line. \
+                    All other output goes to stderr",
+                )
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("print0")
+                .long("print0")
+                .help("Like --print-new-paths, but separate the paths by NUL characters")
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Print nothing but a single summary line at the end, implies --assume-yes")
+                .long_help(
+                    "Print nothing but a single summary line at the end, implies --assume-yes, \
+                    check dialogs without a recorded answer are skipped. Fatal errors are still \
+                    printed to stderr. The exit code is 0 if there was nothing to do or all \
+                    changes were applied, 1 if some operations failed, 2 if a fatal error \
+                    stopped the run and 130 if it was cancelled. The diff subcommand exits \
+                    with 3 if the libraries differ.",
+                )
+                .num_args(0)
+                .conflicts_with_all(["confirm-releases", "stats", "health", "incomplete-report"]),
+        )
         .arg(
             Arg::new("verbosity")
                 .short('v')
                 .long("verbosity")
                 .value_name("level")
-                .help("Verbosity level of the output. 0 means least 2 means most verbose ouput.")
-                .value_parser(value_parser!(u8).range(0..=2))
+                .help(
+                    "Verbosity level of the output. 0 means least 2 means most verbose ouput, \
+                    3 also dumps the parsed tags of each file and the tag updates of each song",
+                )
+                .value_parser(value_parser!(u8).range(0..=3))
                 .default_value("1"),
         )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .value_name("when")
+                .help("When to use colors, auto checks NO_COLOR and whether stdout is a terminal")
+                .value_parser(value_parser!(ColorChoice))
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("log-format")
+                .long("log-format")
+                .value_name("format")
+                .help("Also write a log to stderr, filtered by verbosity unless RUST_LOG is set")
+                .value_parser(["text", "json"])
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .value_name("file")
+                .help("Append the log to this file instead of stderr, as json by default")
+                .long_help(
+                    "Append the log to this file instead of stderr, as json by default. Each \
+                    line is one event, e.g. an organized file, a question that was skipped in \
+                    watch mode or a failed operation, so log collectors can ingest them.",
+                )
+                .value_hint(ValueHint::FilePath)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("metrics-file")
+                .long("metrics-file")
+                .value_name("file")
+                .help("Write Prometheus metrics of all runs to this file after each run")
+                .long_help(
+                    "Write Prometheus metrics of all runs to this file after each run, e.g. for \
+                    the textfile collector of the node exporter. Counts organized files, \
+                    errors and unknown files and the duration of the last run.",
+                )
+                .value_hint(ValueHint::FilePath)
+                .num_args(1),
+        )
         .arg(
             Arg::new("generate-completion")
                 .short('g')
@@ -127,6 +1606,101 @@ pub fn parse_args() -> Args {
                 .conflicts_with("music-dir")
                 .value_parser(value_parser!(Shell)),
         );
+    #[cfg(feature = "sqlite")]
+    {
+        app = app.arg(
+            Arg::new("index-db")
+                .long("index-db")
+                .value_name("file")
+                .help("SQLite database used as index cache, which is updated after indexing")
+                .num_args(1)
+                .conflicts_with("index-cache")
+                .value_hint(ValueHint::FilePath),
+        );
+        app = app.arg(
+            Arg::new("beets-db")
+                .long("beets-db")
+                .value_name("file")
+                .help("Use the names of a beets library and update its paths after moving songs")
+                .long_help(
+                    "Use the release artist, artist, release and title names of a beets \
+                    library.db for the songs it tracks. After moving, the paths of the moved \
+                    songs are updated in the database, so beets keeps tracking them.",
+                )
+                .num_args(1)
+                .value_hint(ValueHint::FilePath),
+        );
+    }
+    #[cfg(feature = "replay-gain")]
+    {
+        app = app.arg(
+            Arg::new("replay-gain")
+                .long("replay-gain")
+                .help("Compute the ReplayGain tags of songs that lack them")
+                .long_help(
+                    "Compute the ReplayGain tags of songs that lack them.\n\
+                    Decodes all songs of each release with a song that lacks one of the track \
+                    or album gain and peak tags, and adds the missing tags. This is slow, since \
+                    the entire audio has to be decoded.",
+                )
+                .num_args(0)
+                .conflicts_with("nocheck"),
+        );
+    }
+    #[cfg(feature = "server")]
+    {
+        app = app.arg(
+            Arg::new("serve")
+                .long("serve")
+                .value_name("addr")
+                .help("Serve an HTTP API to scan, answer questions and apply changes remotely")
+                .long_help(
+                    "Serve an HTTP API to scan, answer questions and apply changes remotely, \
+                    e.g. 127.0.0.1:8080. There is no authentication, only bind to addresses \
+                    that trusted clients can reach. Requests from other sites are rejected and \
+                    POST requests need the content type application/json.",
+                )
+                .num_args(1)
+                .value_parser(value_parser!(SocketAddr))
+                // inferring tags asks on the terminal and strict runs exit
+                .conflicts_with_all(["watch", "infer-tags", "strict"]),
+        );
+    }
+    #[cfg(feature = "scripting")]
+    {
+        app = app.arg(
+            Arg::new("script")
+                .long("script")
+                .value_name("file")
+                .help("Adjust the paths and tag updates of songs with a rhai script")
+                .long_help(
+                    "Adjust the paths and tag updates of songs with a rhai script, for rules \
+                    that don't fit the layout, like dirs per label. The script can define \
+                    path(song, components), which receives the dirs and file name of a song \
+                    inside of the output dir as an array and returns the adjusted array, and \
+                    update(song, update), which receives the tag update of the checks as a map \
+                    and returns the adjusted map, or () to veto it. Songs are maps of their tags, \
+                    missing and removed tags are (). Custom fields of the config are in a nested \
+                    extra map.",
+                )
+                .num_args(1)
+                .value_hint(ValueHint::FilePath),
+        );
+    }
+    #[cfg(feature = "notifications")]
+    {
+        app = app.arg(
+            Arg::new("notify")
+                .long("notify")
+                .help("Send a desktop notification when a run finishes")
+                .num_args(0),
+        );
+    }
+    app = app.mut_args(with_env).after_long_help(
+        "Every option can also be set through an environment variable named after it, e.g. \
+        MUSIC_ORGANIZER_MUSIC_DIR for --music-dir. Flags accept true, false, yes, no, on, off, 1 \
+        and 0. Command line arguments take precedence over environment variables.",
+    );
 
     let matches = app.clone().get_matches();
 
@@ -143,36 +1717,382 @@ pub fn parse_args() -> Args {
         std::process::exit(0);
     }
 
+    let diff = matches.subcommand_matches("diff").map(|m| {
+        ["dir-a", "dir-b"].map(|id| {
+            let dir = shellexpand::tilde(m.get_one::<String>(id).unwrap());
+            let path = PathBuf::from(dir.as_ref());
+            if !path.is_dir() {
+                println!("Not a valid dir path: {}", dir);
+                std::process::exit(crate::EXIT_FATAL)
+            }
+            std::path::absolute(&path).unwrap_or(path)
+        })
+    });
+
     let music_dir = {
         let dir = shellexpand::tilde(matches.get_one::<String>("music-dir").unwrap());
         let path = PathBuf::from(dir.as_ref());
-        if !path.exists() {
+        if !path.exists() && diff.is_none() {
             println!("Not a valid music dir path: {}", dir);
-            std::process::exit(1)
+            std::process::exit(crate::EXIT_FATAL)
         }
-        path
+        std::path::absolute(&path).unwrap_or(path)
     };
 
+    let release_dir = matches.get_one::<String>("release-dir").map(|s| {
+        let path = PathBuf::from(shellexpand::tilde(s).as_ref());
+        let path = std::path::absolute(&path).unwrap_or(path);
+        if !path.is_dir() || !path.starts_with(&music_dir) || path == music_dir {
+            println!("Not a release dir inside of the music dir: {}", path.display());
+            std::process::exit(crate::EXIT_FATAL)
+        }
+        path
+    });
+
     let output_dir = match matches.get_one::<String>("output-dir") {
         Some(s) => {
             let dir = shellexpand::tilde(s);
-            PathBuf::from(dir.as_ref())
+            let path = PathBuf::from(dir.as_ref());
+            std::path::absolute(&path).unwrap_or(path)
         }
         None => music_dir.clone(),
     };
 
+    if diff.is_none() && !matches.get_flag("force") {
+        for (dir, is_music_dir) in [(&music_dir, true), (&output_dir, false)] {
+            if let Some(danger) = guard::danger(dir, is_music_dir) {
+                println!(
+                    "Refusing to organize {}, it is {danger}. Pass --force to do it anyway",
+                    dir.display()
+                );
+                std::process::exit(crate::EXIT_FATAL)
+            }
+        }
+    }
+
+    let backup_dir = matches.get_one::<String>("backup-dir").map(|s| {
+        let path = PathBuf::from(shellexpand::tilde(s).as_ref());
+        std::path::absolute(&path).unwrap_or(path)
+    });
+
+    let backup =
+        (backup_dir.as_ref()).map(|d| Backup::new(d, music_dir.clone(), SystemTime::now()));
+    let staging_dir = (matches.get_flag("staging"))
+        .then(|| output_dir.join(format!("{STAGING_DIR_NAME}-{}", std::process::id())));
+
+    let mut excluded_dirs = Vec::new();
+    if let Some(dir) = backup_dir.as_ref().filter(|d| d.starts_with(&music_dir)) {
+        excluded_dirs.push(dir.clone());
+    }
+    let mut warnings = Vec::new();
+    let canonical_music_dir = canonicalize_existing(&music_dir);
+    let canonical_output_dir = canonicalize_existing(&output_dir);
+    if canonical_output_dir != canonical_music_dir {
+        // the output dir as it's reached when indexing, and through a symlink inside of it
+        let nested: Vec<PathBuf> = (canonical_output_dir.strip_prefix(&canonical_music_dir).ok())
+            .map(|rel| music_dir.join(rel))
+            .into_iter()
+            .chain(Some(output_dir.clone()).filter(|d| {
+                d.starts_with(&music_dir) && !d.components().any(|c| c == Component::ParentDir)
+            }))
+            .collect();
+        for dir in nested.iter() {
+            if !excluded_dirs.contains(dir) {
+                excluded_dirs.push(dir.clone());
+            }
+        }
+        if !nested.is_empty() {
+            warnings.push("the output dir is inside of the music dir and will be excluded".into());
+        } else if canonical_music_dir.starts_with(&canonical_output_dir) {
+            warnings.push("the music dir is inside of the output dir".into());
+        }
+    }
+
+    let junk_patterns = match matches.get_many::<String>("junk-pattern") {
+        Some(patterns) => patterns.cloned().collect(),
+        None if matches.get_flag("remove-junk") => {
+            DEFAULT_JUNK_PATTERNS.iter().map(|p| p.to_string()).collect()
+        }
+        None => Vec::new(),
+    };
+
+    let mut extra_policies = ExtraPolicies::default();
+    for (kind, policy) in matches.get_many("extras").into_iter().flatten() {
+        extra_policies.set(*kind, *policy);
+    }
+
+    let mut artist_separators = ArtistSeparators {
+        split: matches
+            .get_many::<String>("artist-separator")
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect(),
+        name_join: matches.get_one::<String>("artist-join").unwrap().clone(),
+        ..Default::default()
+    };
+    if let Some(join) = matches.get_one::<String>("artist-tag-join") {
+        artist_separators.tag_join = join.clone();
+    }
+
+    let config_path = match matches.get_one::<String>("config") {
+        Some(path) => PathBuf::from(shellexpand::tilde(path).as_ref()),
+        None => config::default_path(),
+    };
+    let config = match matches.contains_id("config") || config_path.exists() {
+        true => Config::load(&config_path).unwrap_or_else(|e| {
+            println!("Error: loading config {}: {e}", config_path.display());
+            std::process::exit(crate::EXIT_FATAL);
+        }),
+        false => Config::default(),
+    };
+
+    let mut no_retag = config_filters("no_retag", &config.no_retag, &config_path);
+    no_retag.extend(matches.get_many::<Filter>("no-retag").into_iter().flatten().cloned());
+    let mut keep_artwork = config_filters("keep_artwork", &config.keep_artwork, &config_path);
+    keep_artwork.extend(matches.get_many::<Filter>("keep-artwork").into_iter().flatten().cloned());
+
+    let decisions_file = match matches.get_one::<String>("decisions") {
+        Some(path) => PathBuf::from(shellexpand::tilde(path).as_ref()),
+        None => config_path.with_file_name("decisions.json"),
+    };
+
+    #[cfg(not(feature = "notifications"))]
+    if config.notify {
+        println!("Warning: notifications require the notifications feature and are disabled");
+    }
+
     Args {
+        #[cfg(feature = "notifications")]
+        notify: config.notify || matches.get_flag("notify"),
+        #[cfg(feature = "server")]
+        serve: matches.get_one::<SocketAddr>("serve").copied(),
+        #[cfg(feature = "replay-gain")]
+        replay_gain: matches.get_flag("replay-gain"),
+        #[cfg(feature = "scripting")]
+        script: matches.get_one::<String>("script").map(|s| {
+            let path = PathBuf::from(shellexpand::tilde(s).as_ref());
+            let script = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|s| Script::compile(&s).map_err(|e| e.to_string()));
+            match script {
+                Ok(script) => Arc::new(script),
+                Err(e) => {
+                    println!("Invalid script {}: {e}", path.display());
+                    std::process::exit(crate::EXIT_FATAL)
+                }
+            }
+        }),
+        config,
+        config_path,
         music_dir,
         output_dir,
+        excluded_dirs,
+        warnings,
         verbosity: *matches.get_one::<u8>("verbosity").unwrap(),
-        op_type: match matches.get_flag("copy") {
-            true => FileOpType::Copy,
-            false => FileOpType::Move,
+        color: *matches.get_one::<ColorChoice>("color").unwrap(),
+        log_format: match matches.get_one::<String>("log-format") {
+            Some(s) => Some(LogFormat::from_str(s).unwrap()),
+            None => matches.contains_id("log-file").then_some(LogFormat::Json),
+        },
+        log_file: matches
+            .get_one::<String>("log-file")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        provenance_tag: matches.get_flag("provenance-tag"),
+        provenance_manifest: matches
+            .get_one::<String>("provenance-manifest")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        release_nfo: matches.get_flag("release-nfo").then(|| {
+            let release = matches.get_one::<String>("nfo-template").unwrap();
+            let track = matches.get_one::<String>("nfo-track-template").unwrap();
+            NfoTemplate::new(release, track).unwrap()
+        }),
+        path_manifest: matches
+            .get_one::<String>("path-manifest")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        plan_snapshot: matches
+            .get_one::<String>("plan-snapshot")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        metrics_file: matches
+            .get_one::<String>("metrics-file")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        exec: ExecOptions {
+            op_type: FileOpType::from(matches.get_flag("copy")),
+            delete_type: DeleteType::from(matches.get_flag("trash")),
+            retry: RetryPolicy {
+                retries: *matches.get_one::<u32>("retries").unwrap(),
+                delay: Duration::from_millis(*matches.get_one::<u64>("retry-delay").unwrap()),
+            },
+            fail_fast: matches.get_flag("fail-fast"),
+            cancel: CancelToken::default(),
+            artist_separators: artist_separators.clone(),
+            threads: matches.get_one::<NonZeroUsize>("write-threads").copied(),
+            backup,
+            preserve_ownership: matches.get_flag("preserve-ownership"),
+            preserve_dir_mtimes: matches.get_flag("preserve-dir-mtimes"),
+            atomic_releases: matches.get_flag("atomic-releases"),
+            staging_dir,
         },
-        assume_yes: matches.get_flag("assume-yes"),
-        no_check: matches.get_flag("nocheck"),
+        keep_backups: *matches.get_one::<usize>("keep-backups").unwrap(),
+        backup_dir,
+        assume_yes: matches.get_flag("assume-yes")
+            || matches.get_flag("watch")
+            || matches.get_flag("quiet"),
+        confirm_releases: matches.get_flag("confirm-releases"),
+        no_check: matches.get_flag("nocheck")
+            || matches.get_flag("rename-only")
+            || release_dir.is_some(),
+        rename_only: matches.get_flag("rename-only"),
+        answers_file: matches
+            .get_one::<String>("answers")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        decisions_file,
+        infer_tags: matches
+            .get_one::<String>("infer-tags")
+            .map(|s| InferPolicy::from_str(s).unwrap()),
+        vote_release_artists: matches.get_flag("vote-release-artists"),
+        similarity_scope: matches.get_one::<SimilarityScope>("similarity-scope").unwrap().clone(),
+        normalize_totals: matches.get_one::<String>("normalize-totals").map(|s| match s.as_str() {
+            "count" => TotalsPolicy::Count,
+            _ => TotalsPolicy::Max,
+        }),
+        various_artists: matches
+            .get_one::<String>("various-artists")
+            .map(|s| CheckPolicy::from_str(s).unwrap())
+            .unwrap(),
+        answers: matches
+            .get_many::<(String, String)>("answer")
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect(),
+        flatten_discs: matches.get_flag("flatten-discs"),
+        fill_release_tags: matches.get_flag("fill-release-tags"),
+        bonus_tracks: matches.get_one::<String>("bonus-tracks").map(|s| match s.as_str() {
+            "extras" => BonusPolicy::Extras,
+            _ => BonusPolicy::Flag,
+        }),
         keep_embedded_artworks: matches.get_flag("keep embedded artworks"),
-        no_cleanup: matches.get_flag("nocleanup"),
+        keep_playlists: matches.get_flag("keep-playlists"),
+        release_playlists: matches.get_flag("release-playlists"),
+        artist_playlists: matches.get_flag("artist-playlists"),
+        no_cleanup: matches.get_flag("nocleanup") || matches.get_flag("tag-only"),
+        tag_only: matches.get_flag("tag-only"),
+        junk_patterns,
+        image_policy: match matches.get_one::<String>("images").unwrap().as_str() {
+            "copy" => ImagePolicy::Copy,
+            "leave" => ImagePolicy::Leave,
+            "delete" => ImagePolicy::Delete,
+            _ => ImagePolicy::Follow,
+        },
+        cover_name: match matches.get_one::<String>("cover-name").unwrap().as_str() {
+            "cover" | "navidrome" => CoverName::Cover,
+            "folder" | "kodi" => CoverName::Folder,
+            "poster" | "plex" => CoverName::Poster,
+            _ => CoverName::Keep,
+        },
+        extra_policies,
+        non_latin_artists: {
+            let bucket = matches.get_one::<String>("non-latin-bucket").unwrap().clone();
+            match matches.get_one::<String>("non-latin-artists").unwrap().as_str() {
+                "romanize" => NonLatinArtists::Romanize { bucket },
+                "group" => NonLatinArtists::Group { bucket },
+                _ => NonLatinArtists::Keep,
+            }
+        },
+        collaborations: match matches.get_one::<String>("collaborations").unwrap().as_str() {
+            "linked" => Collaborations::Linked,
+            "first-artist" => Collaborations::FirstArtist,
+            _ => Collaborations::Joint,
+        },
+        lowercase_extensions: matches.get_flag("lowercase-extensions"),
         dry_run: matches.get_flag("dryrun"),
+        print_new_paths: match (matches.get_flag("print0"), matches.get_flag("print-new-paths")) {
+            (true, _) => Some(b'\0'),
+            (false, true) => Some(b'\n'),
+            (false, false) => None,
+        },
+        quiet: matches.get_flag("quiet"),
+        dir_mode: matches.get_one::<Mode>("dir-mode").copied(),
+        dir_owner: matches.get_one::<Owner>("dir-owner").copied(),
+        layout: matches.get_one::<Layout>("layout").cloned().unwrap_or_default(),
+        video_layout: matches.get_one::<Layout>("video-layout").cloned(),
+        audiobook_layout: matches.get_one::<Layout>("audiobook-layout").cloned(),
+        artist_separators,
+        filters: matches.get_many::<Filter>("filter").into_iter().flatten().cloned().collect(),
+        no_retag,
+        keep_artwork,
+        precount: matches.get_flag("precount"),
+        stats: matches.get_flag("stats"),
+        health: matches.get_one::<String>("health").map(|s| ReportFormat::from_str(s).unwrap()),
+        dedup: matches.get_one::<String>("dedup").map(|s| match s.as_str() {
+            "hardlink" => DedupAction::Hardlink,
+            _ => DedupAction::Delete,
+        }),
+        quarantine: matches.get_flag("quarantine"),
+        strict: matches.get_flag("strict"),
+        incomplete_report: matches
+            .contains_id("incomplete-report")
+            .then(|| matches.get_one::<PathBuf>("incomplete-report").cloned()),
+        follow_symlinks: matches.get_flag("follow-symlinks"),
+        max_depth: matches.get_one::<usize>("max-depth").copied(),
+        include_hidden: matches.get_flag("include-hidden"),
+        threads: matches.get_one::<NonZeroUsize>("threads").copied(),
+        index_cache: matches
+            .get_one::<String>("index-cache")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        export_index: matches
+            .get_one::<String>("export-index")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        itunes_library: matches
+            .get_one::<String>("itunes-library")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        updates_from: matches
+            .get_one::<String>("updates-from")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        check_report: matches
+            .get_one::<String>("check-report")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        diff,
+        sync_dir: matches
+            .get_one::<String>("sync")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        sync_delete: matches.get_flag("sync-delete"),
+        naming: match matches.get_one::<String>("naming").map(String::as_str) {
+            Some("fat") => NamingProfile::Fat,
+            _ => NamingProfile::Posix,
+        },
+        transcode: matches.get_one::<TranscodeCodec>("transcode").map(|codec| Transcode {
+            codec: *codec,
+            bitrate: *matches.get_one::<u32>("transcode-bitrate").unwrap(),
+            ffmpeg: matches.get_one::<PathBuf>("ffmpeg").unwrap().clone(),
+        }),
+        sync_limit: matches.get_one::<u64>("sync-max-size").map(|max_size| SizeLimit {
+            max_size: *max_size,
+            order: match matches.get_one::<String>("sync-order").map(String::as_str) {
+                Some("rated") => SelectionOrder::Rated,
+                _ => SelectionOrder::Recent,
+            },
+            preferred: (matches.get_many::<String>("sync-prefer").into_iter().flatten())
+                .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref()))
+                .collect(),
+        }),
+        watch: matches.get_flag("watch"),
+        settle: Duration::from_secs(*matches.get_one::<u64>("settle").unwrap()),
+        index_socket: matches
+            .get_one::<String>("index-socket")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        files_from: matches
+            .get_one::<String>("files-from")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        release_dir,
+        #[cfg(feature = "sqlite")]
+        index_db: matches
+            .get_one::<String>("index-db")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
+        #[cfg(feature = "sqlite")]
+        beets_db: matches
+            .get_one::<String>("beets-db")
+            .map(|s| PathBuf::from(shellexpand::tilde(s).as_ref())),
     }
 }
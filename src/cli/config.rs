@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::hooks::Hook;
+use crate::schedule::Schedule;
+use crate::theme::ThemeConfig;
+
+/// Settings that don't fit on the command line, read from a TOML file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Run after a successful write phase.
+    pub hooks: Vec<Hook>,
+    /// Send a desktop notification when a run finishes, requires the `notifications` feature.
+    pub notify: bool,
+    /// Names of `TXXX` frames, vorbis comments and freeform atoms to index, e.g. `LABEL`. They can
+    /// be used in the layout as `{extra.label}`.
+    pub custom_fields: Vec<String>,
+    /// Names of files or dirs that mark a dir as managed by another tool, like the `.stfolder` of
+    /// Syncthing. Such dirs are neither indexed nor cleaned up. Defaults to
+    /// `DEFAULT_FOREIGN_MARKERS`, an empty list organizes them like any other dir.
+    pub foreign_markers: Option<Vec<String>>,
+    /// Release artists with similar names that are never suggested to be merged.
+    pub distinct_artists: Vec<DistinctArtists>,
+    /// Genre names keyed by the genres that are normalized to them, e.g. `hiphop = "Hip-Hop"`.
+    /// Keys are compared case insensitively.
+    pub genres: BTreeMap<String, String>,
+    pub theme: ThemeConfig,
+    /// The language of titles and dialogs, e.g. `de`. Defaults to `LC_ALL`, `LC_MESSAGES` or
+    /// `LANG`.
+    pub locale: Option<String>,
+    /// Periodic runs in watch mode, in addition to organizing new files.
+    pub schedules: Vec<Schedule>,
+    /// Songs whose tags are never rewritten, as filters like `format:flac` or
+    /// `path:Trusted/**`. They are still moved and renamed.
+    pub no_retag: Vec<String>,
+    /// Songs whose embedded artwork is kept even if embedded artworks are removed, as filters
+    /// like `artist:Radiohead` or `release:*Live*`.
+    pub keep_artwork: Vec<String>,
+}
+
+/// A pair of release artists, multiple names of one are joined with `, `.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DistinctArtists {
+    pub names: [String; 2],
+}
+
+impl DistinctArtists {
+    pub fn matches(&self, a: &str, b: &str) -> bool {
+        let [x, y] = &self.names;
+        (x == a && y == b) || (x == b && y == a)
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let str = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        toml::from_str(&str).map_err(|e| e.to_string())
+    }
+}
+
+/// Appends a pair of distinct artists to the config file, which is created if it doesn't exist.
+/// The pair is appended as a new table, so the rest of the file is left as is.
+pub fn append_distinct_artists(path: &Path, a: &str, b: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    let names = toml::Value::Array(vec![a.into(), b.into()]);
+    writeln!(file, "\n[[distinct_artists]]\nnames = {names}")
+}
+
+/// `$XDG_CONFIG_HOME/music-organizer/config.toml`, which defaults to `~/.config`.
+pub fn default_path() -> PathBuf {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(shellexpand::tilde("~/.config").as_ref()),
+    };
+    config_dir.join("music-organizer").join("config.toml")
+}
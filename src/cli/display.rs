@@ -1,13 +1,22 @@
+use std::collections::HashSet;
 use std::fmt::Display;
 use std::path::Path;
 
 use colored::Colorize;
-use music_organizer::{Song, SongOperation, TagUpdate, Value};
+use music_organizer_core::{
+    ArtistChanges, AudioInfo, Mode, OperationId, Song, SongOperation, TagUpdate, Value,
+};
+
+use crate::progress;
+use crate::theme::Themed;
+
+/// Operations are printed on a single line if they fit, when stdout isn't a terminal.
+const DEFAULT_WIDTH: usize = 180;
 
 pub struct SongOp<'a>(
     pub &'a Path,
     pub &'a Path,
-    pub &'a SongOperation<'a>,
+    pub &'a SongOperation,
     pub &'a str,
     pub &'a str,
     pub u8,
@@ -19,6 +28,14 @@ impl Display for SongOp<'_> {
     }
 }
 
+/// Marks operations that are new since the last applied plan, see `--plan-snapshot`.
+pub fn new_marker(new_ops: Option<&HashSet<OperationId>>, id: &OperationId) -> String {
+    match new_ops {
+        Some(ops) if ops.contains(id) => format!("{} ", "new".success()),
+        _ => String::new(),
+    }
+}
+
 pub struct FileOp<'a>(
     pub &'a Path,
     pub &'a Path,
@@ -34,6 +51,67 @@ impl Display for FileOp<'_> {
     }
 }
 
+/// Song operations grouped by release artists and release, with the number of operations in each
+/// group. Songs are numbered continuously across groups.
+pub struct GroupedSongOps<'a> {
+    pub music_dir: &'a Path,
+    pub output_dir: &'a Path,
+    pub groups: &'a [ArtistChanges<'a>],
+    pub op_type_str: &'a str,
+    pub rename_str: &'a str,
+    pub verbosity: u8,
+    pub new_ops: Option<&'a HashSet<OperationId>>,
+}
+
+impl Display for GroupedSongOps<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut i = 0;
+        for artist in self.groups {
+            let names = match artist.names.is_empty() {
+                true => "unknown artist".to_owned(),
+                false => artist.names.join(", "),
+            };
+            writeln!(
+                f,
+                "{} ({})",
+                names.bold(),
+                artist.num_song_operations().to_string().number()
+            )?;
+
+            for release in &artist.releases {
+                let name = match release.name.is_empty() {
+                    true => "unknown release",
+                    false => &release.name,
+                };
+                writeln!(
+                    f,
+                    "  {} ({})",
+                    name.bold(),
+                    release.song_operations.len().to_string().number()
+                )?;
+
+                for o in &release.song_operations {
+                    i += 1;
+                    let op = SongOp(
+                        self.music_dir,
+                        self.output_dir,
+                        o,
+                        self.op_type_str,
+                        self.rename_str,
+                        self.verbosity,
+                    )
+                    .to_string();
+                    let num = i.to_string();
+                    let indent = format!("\n    {:1$}", "", num.len() + 1);
+                    let new = new_marker(self.new_ops, &o.id());
+                    writeln!(f, "    {} {new}{}", num.number(), op.replace('\n', &indent))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 /// TODO: proper mode formatting
 fn format_song_op(
     f: &mut impl std::fmt::Write,
@@ -45,8 +123,31 @@ fn format_song_op(
     verbosity: u8,
 ) -> std::fmt::Result {
     if let Some(mode) = song_op.mode_update {
-        println!("mode {mode} ");
+        writeln!(f, "mode {} ", ModeDisplay(mode))?;
+    }
+    format_song_paths(f, music_dir, output_dir, song_op, op_type_str, rename_str, verbosity)?;
+    if !song_op.reasons.is_empty() {
+        let reasons = format!("because of {}", song_op.reasons.join(", "));
+        write!(f, "\n{}", reasons.secondary())?;
+    }
+    if verbosity >= 2 && song_op.song.audio != AudioInfo::default() {
+        write!(f, "\n    {}", AudioInfoDisplay(&song_op.song.audio).to_string().secondary())?;
     }
+    if let Some(tag_update) = song_op.tag_update.as_ref().filter(|_| verbosity >= 3) {
+        write!(f, "\n{}", format!("{tag_update:#?}").secondary())?;
+    }
+    Ok(())
+}
+
+fn format_song_paths(
+    f: &mut impl std::fmt::Write,
+    music_dir: &Path,
+    output_dir: &Path,
+    song_op: &SongOperation,
+    op_type_str: &str,
+    rename_str: &str,
+    verbosity: u8,
+) -> std::fmt::Result {
     match (&song_op.new_path, &song_op.tag_update) {
         (Some(new_path), Some(tag_update)) => {
             format_file_op(
@@ -59,11 +160,11 @@ fn format_song_op(
                 rename_str,
             )?;
             f.write_char('\n')?;
-            format_tag_update(f, song_op.song, tag_update, verbosity)
+            format_tag_update(f, &song_op.song, tag_update, verbosity)
         }
         (None, Some(tag_update)) => {
-            format_tag_update(f, song_op.song, tag_update, verbosity)?;
-            write!(f, " {}", strip_dir(&song_op.song.path, music_dir).green())
+            writeln!(f, "{}", strip_dir(&song_op.song.path, music_dir).success())?;
+            format_tag_update(f, &song_op.song, tag_update, verbosity)
         }
         (Some(new_path), None) => format_file_op(
             f,
@@ -78,6 +179,46 @@ fn format_song_op(
     }
 }
 
+/// Colors the permissions of a mode like `ls -l`.
+pub struct ModeDisplay(pub Mode);
+
+impl Display for ModeDisplay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for c in self.0.to_string().chars() {
+            match c {
+                'r' => write!(f, "{}", "r".highlight())?,
+                'w' => write!(f, "{}", "w".error())?,
+                'x' => write!(f, "{}", "x".success())?,
+                c => write!(f, "{}", c.to_string().secondary())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// e.g. `FLAC 44.1 kHz 912 kbit/s 3:45`
+pub struct AudioInfoDisplay<'a>(pub &'a AudioInfo);
+
+impl Display for AudioInfoDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(codec) = self.0.codec {
+            parts.push(codec.to_string());
+        }
+        if let Some(sample_rate) = self.0.sample_rate {
+            parts.push(format!("{} kHz", sample_rate as f32 / 1000.0));
+        }
+        if let Some(bitrate) = self.0.bitrate {
+            parts.push(format!("{bitrate} kbit/s"));
+        }
+        if let Some(duration) = self.0.duration {
+            let secs = duration.as_secs();
+            parts.push(format!("{}:{:02}", secs / 60, secs % 60));
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
 fn format_file_op(
     f: &mut impl std::fmt::Write,
     music_dir: &Path,
@@ -87,7 +228,7 @@ fn format_file_op(
     op_type_str: &str,
     rename_str: &str,
 ) -> std::fmt::Result {
-    let old = strip_dir(old_path, music_dir).yellow();
+    let old = strip_dir(old_path, music_dir).highlight();
 
     let mut just_rename = false;
     let release_dir = old_path.parent().unwrap();
@@ -95,16 +236,17 @@ fn format_file_op(
         Some(p) => {
             if p.components().count() == 1 {
                 just_rename = true;
-                p.display().to_string().green()
+                p.display().to_string().success()
             } else {
-                strip_dir(new_path, output_dir).green()
+                strip_dir(new_path, output_dir).success()
             }
         }
-        None => strip_dir(new_path, output_dir).green(),
+        None => strip_dir(new_path, output_dir).success(),
     };
 
     let operation = if just_rename { rename_str } else { op_type_str };
-    if operation.len() + old.len() + new.len() + 5 <= 180 {
+    let width = progress::terminal_width().unwrap_or(DEFAULT_WIDTH);
+    if operation.len() + old.len() + new.len() + 5 <= width {
         write!(f, "{operation} {old} to {new}")?;
     } else {
         write!(f, "{operation} {old}\n    to {new}")?;
@@ -113,92 +255,145 @@ fn format_file_op(
     Ok(())
 }
 
-/// TODO: prettier tag update
-fn format_tag_update(
-    f: &mut impl std::fmt::Write,
-    s: &Song,
-    u: &TagUpdate,
-    _verbosity: u8,
-) -> std::fmt::Result {
-    format_string_vec(f, "release artists", &s.release_artists, &u.release_artists)?;
-    format_string_vec(f, "artists", &s.artists, &u.artists)?;
-    format_string(f, "release", &s.release, &u.release)?;
-    format_string(f, "title", &s.title, &u.title)?;
-    format_u16(f, "track number", s.track_number, u.track_number)?;
-    format_u16(f, "total tracks", s.total_tracks, u.total_tracks)?;
-    format_u16(f, "disc number", s.disc_number, u.track_number)?;
-    format_u16(f, "total discs", s.total_discs, u.total_discs)?;
-    format_value(f, "artwork", s.has_artwork, &u.artwork)?;
+/// The names of the tags that are changed by the update, e.g. `genre, year`.
+pub fn changed_tags(u: &TagUpdate) -> Vec<String> {
+    let fields = [
+        ("release artists", u.release_artists.is_unchanged()),
+        ("artists", u.artists.is_unchanged()),
+        ("release", u.release.is_unchanged()),
+        ("title", u.title.is_unchanged()),
+        ("genre", u.genre.is_unchanged()),
+        ("year", u.year.is_unchanged()),
+        ("track number", u.track_number.is_unchanged()),
+        ("total tracks", u.total_tracks.is_unchanged()),
+        ("disc number", u.disc_number.is_unchanged()),
+        ("total discs", u.total_discs.is_unchanged()),
+        ("artwork", u.artwork.is_unchanged()),
+        ("rating", u.rating.is_unchanged()),
+        ("play count", u.play_count.is_unchanged()),
+    ];
+    let names = fields.into_iter().filter(|(_, unchanged)| !unchanged).map(|(n, _)| n.to_owned());
+    let extra = u.extra.iter().filter(|(_, v)| !v.is_unchanged()).map(|(n, _)| n.to_lowercase());
+    names.chain(extra).collect()
+}
 
-    Ok(())
+/// A changed tag, `None` if the old value isn't set or known, or if the new value is removed.
+struct TagDiff {
+    name: String,
+    old: Option<String>,
+    new: Option<String>,
 }
 
-fn format_u16(
-    f: &mut impl std::fmt::Write,
-    name: &str,
-    old: Option<u16>,
-    new: Value<u16>,
-) -> Result<bool, std::fmt::Error> {
-    match (old, new) {
-        (Some(old), Value::Update(new)) => {
-            write!(f, "change {name}: {} to {}", old.to_string().yellow(), new.to_string().green())?
-        }
-        (None, Value::Update(new)) => write!(f, "add {name}: {}", new.to_string().green())?,
-        (Some(old), Value::Remove) => write!(f, "remove {name}: {}", old.to_string().red())?,
-        _ => return Ok(false),
+impl TagDiff {
+    fn new(name: &str, old: Option<String>, new: Option<String>) -> Self {
+        let old = old.filter(|o| !o.is_empty());
+        Self { name: name.to_owned(), old, new }
     }
+}
 
-    Ok(true)
+fn string_diff(name: &str, old: &str, new: &Value<String>) -> Option<TagDiff> {
+    value_diff(name, Some(old.to_owned()), new, String::clone)
 }
 
-fn format_string(
-    f: &mut impl std::fmt::Write,
+fn string_vec_diff(name: &str, old: &[String], new: &Value<Vec<String>>) -> Option<TagDiff> {
+    value_diff(name, Some(old.join(", ")), new, |n| n.join(", "))
+}
+
+fn num_diff<T: Display>(name: &str, old: Option<T>, new: Value<T>) -> Option<TagDiff> {
+    value_diff(name, old.map(|o| o.to_string()), &new, T::to_string)
+}
+
+fn value_diff<T>(
     name: &str,
-    old: &str,
-    new: &Value<String>,
-) -> Result<bool, std::fmt::Error> {
+    old: Option<String>,
+    new: &Value<T>,
+    display: impl FnOnce(&T) -> String,
+) -> Option<TagDiff> {
     match new {
-        Value::Update(new) => write!(f, "change {name}: {} to {}", old.yellow(), new.green())?,
-        Value::Remove => write!(f, "remove {name}: {}", old.red())?,
-        Value::Unchanged => return Ok(false),
+        Value::Update(new) => Some(TagDiff::new(name, old, Some(display(new)))),
+        Value::Remove => Some(TagDiff::new(name, old, None)),
+        Value::Unchanged => None,
     }
+}
 
-    Ok(true)
+/// The changed tags, in the order of [`changed_tags`].
+fn tag_diffs(s: &Song, u: &TagUpdate) -> Vec<TagDiff> {
+    let embedded = s.has_artwork.then(|| "embedded".to_owned());
+    let diffs = [
+        string_vec_diff("release artists", &s.release_artists, &u.release_artists),
+        string_vec_diff("artists", &s.artists, &u.artists),
+        string_diff("release", &s.release, &u.release),
+        string_diff("title", &s.title, &u.title),
+        string_diff("genre", s.genre.as_deref().unwrap_or_default(), &u.genre),
+        num_diff("year", s.year, u.year),
+        num_diff("track number", s.track_number, u.track_number),
+        num_diff("total tracks", s.total_tracks, u.total_tracks),
+        num_diff("disc number", s.disc_number, u.disc_number),
+        num_diff("total discs", s.total_discs, u.total_discs),
+        value_diff("artwork", embedded, &u.artwork, |_| "new".to_owned()),
+        // not part of the index, so only the new value is known
+        num_diff("rating", None, u.rating),
+        num_diff("play count", None, u.play_count),
+    ];
+    let extra = u.extra.iter().filter_map(|(name, value)| {
+        string_diff(&name.to_lowercase(), s.extra.get(name).map_or("", String::as_str), value)
+    });
+    diffs.into_iter().flatten().chain(extra).collect()
 }
 
-fn format_string_vec(
+/// One line per changed tag with the field names aligned, e.g. `title  Foo → Bar`. Added values
+/// are prefixed with `+` and removed ones with `-`. At verbosity 0 only the names of the changed
+/// tags are listed.
+fn format_tag_update(
     f: &mut impl std::fmt::Write,
-    name: &str,
-    old: &[String],
-    new: &Value<Vec<String>>,
-) -> Result<bool, std::fmt::Error> {
-    match new {
-        Value::Update(new) => {
-            write!(f, "change {name}: {} to {}", old.join(", ").yellow(), new.join(", ").green())?
+    s: &Song,
+    u: &TagUpdate,
+    verbosity: u8,
+) -> std::fmt::Result {
+    if verbosity == 0 {
+        return write!(f, "  retag {}", changed_tags(u).join(", "));
+    }
+
+    let diffs = tag_diffs(s, u);
+    let width = diffs.iter().map(|d| d.name.chars().count()).max().unwrap_or(0);
+    for (i, d) in diffs.iter().enumerate() {
+        if i > 0 {
+            f.write_char('\n')?;
+        }
+        write!(f, "  {:width$}  ", d.name)?;
+        match (&d.old, &d.new) {
+            (Some(old), Some(new)) => write!(f, "{} → {}", old.highlight(), new.success())?,
+            (None, Some(new)) => write!(f, "{} {}", "+".success(), new.success())?,
+            (Some(old), None) => write!(f, "{} {}", "-".error(), old.error())?,
+            (None, None) => write!(f, "{}", "-".error())?,
         }
-        Value::Remove => write!(f, "remove {name}: {}", old.join(", ").red())?,
-        Value::Unchanged => return Ok(false),
     }
+    Ok(())
+}
 
-    Ok(true)
+/// e.g. `3:04:05`
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
 }
 
-fn format_value<T>(
-    f: &mut impl std::fmt::Write,
-    name: &str,
-    old: bool,
-    new: &Value<T>,
-) -> Result<bool, std::fmt::Error> {
-    match (old, new) {
-        (true, Value::Update(_)) => write!(f, "change {name}")?,
-        (false, Value::Update(_)) => write!(f, "add {name}")?,
-        (true, Value::Remove) => write!(f, "remove {name}")?,
-        _ => return Ok(false),
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
     }
 
-    Ok(true)
+    match unit {
+        0 => format!("{bytes} {}", UNITS[0]),
+        _ => format!("{value:.1} {}", UNITS[unit]),
+    }
 }
 
+/// Falls back to the whole path if it isn't inside of the dir.
 pub fn strip_dir(path: &Path, dir: &Path) -> String {
-    path.strip_prefix(dir).unwrap().display().to_string()
+    path.strip_prefix(dir).unwrap_or(path).display().to_string()
 }
@@ -0,0 +1,80 @@
+use std::collections::VecDeque;
+use std::path::Path;
+
+use music_organizer_core::{is_music_video_extension, is_song_extension};
+
+/// The number of files that are sampled to guess whether a mount point is a music library.
+const SAMPLE_SIZE: usize = 1000;
+/// Fewer sampled files are fine, e.g. a freshly mounted drive with a small library.
+const MIN_SAMPLE_SIZE: usize = 100;
+/// The minimum percentage of songs and music videos of the sampled files.
+const MIN_MUSIC_PERCENT: usize = 20;
+
+/// Why organizing the dir would probably wreak havoc, since every file that isn't a song ends
+/// up in the unknown dir. The music dir may also be a mount point that mostly contains other
+/// files, which doesn't matter for the output dir.
+pub fn danger(dir: &Path, is_music_dir: bool) -> Option<String> {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_owned());
+    if dir.parent().is_none() {
+        return Some("the root dir".to_owned());
+    }
+    let home = std::env::var_os("HOME").map(|h| Path::new(&h).to_owned());
+    if home.is_some_and(|h| h.canonicalize().unwrap_or(h) == dir) {
+        return Some("the home dir".to_owned());
+    }
+    if is_music_dir && is_mount_point(&dir) {
+        let (music, total) = sample_files(&dir);
+        if total >= MIN_SAMPLE_SIZE && music * 100 < total * MIN_MUSIC_PERCENT {
+            return Some(format!(
+                "a mount point and only {music} of {total} sampled files are music"
+            ));
+        }
+    }
+    None
+}
+
+#[cfg(unix)]
+fn is_mount_point(dir: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let Some(parent) = dir.parent() else { return true };
+    match (dir.metadata(), parent.metadata()) {
+        (Ok(d), Ok(p)) => d.dev() != p.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_mount_point(dir: &Path) -> bool {
+    dir.parent().is_none()
+}
+
+/// Counts songs and music videos among the first files found breadth first, returns the count
+/// and the number of sampled files. Hidden entries are skipped.
+fn sample_files(dir: &Path) -> (usize, usize) {
+    let mut dirs = VecDeque::from([dir.to_owned()]);
+    let (mut music, mut total) = (0, 0);
+    while let Some(d) = dirs.pop_front() {
+        let Ok(entries) = std::fs::read_dir(&d) else { continue };
+        for e in entries.flatten() {
+            if e.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(file_type) = e.file_type() else { continue };
+            if file_type.is_dir() {
+                dirs.push_back(e.path());
+                continue;
+            }
+            let path = e.path();
+            let ext = path.extension().unwrap_or_default();
+            if is_song_extension(ext) || is_music_video_extension(ext) {
+                music += 1;
+            }
+            total += 1;
+            if total >= SAMPLE_SIZE {
+                return (music, total);
+            }
+        }
+    }
+    (music, total)
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::process::Command;
+
+use serde::Deserialize;
+
+/// A command or HTTP request that is run at some point of a run, by default after a successful
+/// write phase, e.g. to let a music player refresh its library.
+///
+/// ```toml
+/// [[hooks]]
+/// name = "mpd"
+/// command = "mpc update"
+///
+/// [[hooks]]
+/// name = "jellyfin"
+/// url = "http://localhost:8096/Library/Refresh"
+/// headers = { "X-Emby-Token" = "..." }
+///
+/// [[hooks]]
+/// name = "transcode"
+/// when = "per-file"
+/// command = "transcode.sh \"$MO_NEW_PATH\""
+/// ```
+///
+/// Commands get information through environment variables: `MO_MUSIC_DIR` and `MO_OUTPUT_DIR`,
+/// and for [`HookEvent::PerFile`] also `MO_OLD_PATH`, `MO_NEW_PATH`, `MO_ARTIST`, `MO_RELEASE` and
+/// `MO_TITLE`. The last three are only set for songs.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Hook {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub when: HookEvent,
+    /// Exit if the hook fails, instead of only reporting the failure.
+    #[serde(default)]
+    pub abort_on_failure: bool,
+    #[serde(flatten)]
+    pub action: HookAction,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEvent {
+    BeforeRun,
+    #[default]
+    AfterWrite,
+    AfterRun,
+    /// After each moved, copied or renamed file.
+    PerFile,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum HookAction {
+    /// Run by `sh -c`.
+    Command { command: String },
+    Http {
+        url: String,
+        #[serde(default = "default_method")]
+        method: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+    },
+}
+
+fn default_method() -> String {
+    "POST".into()
+}
+
+impl Hook {
+    pub fn run(&self, env: &[(&str, &OsStr)]) -> Result<(), String> {
+        match &self.action {
+            HookAction::Command { command } => {
+                let mut cmd = Command::new("sh");
+                cmd.arg("-c").arg(command).envs(env.iter().copied());
+                let status = cmd.status().map_err(|e| e.to_string())?;
+                match status.success() {
+                    true => Ok(()),
+                    false => Err(format!("command exited with {status}")),
+                }
+            }
+            HookAction::Http { url, method, headers } => {
+                let method = method.parse::<ureq::http::Method>().map_err(|e| e.to_string())?;
+                let mut request = ureq::http::Request::builder().method(method).uri(url);
+                for (name, value) in headers {
+                    request = request.header(name, value);
+                }
+                let request = request.body(()).map_err(|e| e.to_string())?;
+                ureq::run(request).map_err(|e| e.to_string())?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The enabled hooks that are run at the event.
+pub fn enabled(hooks: &[Hook], when: HookEvent) -> impl Iterator<Item = &Hook> {
+    hooks.iter().filter(move |h| h.enabled && h.when == when)
+}
@@ -0,0 +1,531 @@
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// A language of the titles, labels, notes, dialogs and tenses. Other output, like the details
+/// of errors and warnings, is always english.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+}
+
+impl Locale {
+    /// Uses the first of the configured locale, `LC_ALL`, `LC_MESSAGES` and `LANG` that is set,
+    /// e.g. `de` or `de_DE.UTF-8`. Unsupported languages fall back to english.
+    pub fn detect(config: Option<&str>) -> Self {
+        let env = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|v| std::env::var(v).ok().filter(|v| !v.is_empty()));
+        let locale = config.map(str::to_owned).into_iter().chain(env).next();
+        match locale.as_deref().and_then(|l| l.split(['_', '.', '-']).next()) {
+            Some("de") => Self::De,
+            _ => Self::En,
+        }
+    }
+
+    pub fn messages(self) -> &'static Messages {
+        match self {
+            Self::En => &EN,
+            Self::De => &DE,
+        }
+    }
+}
+
+static MESSAGES: OnceLock<&'static Messages> = OnceLock::new();
+
+/// Sets the locale, only the first call has an effect.
+pub fn init(locale: Locale) {
+    let _ = MESSAGES.set(locale.messages());
+}
+
+pub fn get() -> &'static Messages {
+    MESSAGES.get_or_init(|| Locale::En.messages())
+}
+
+/// Replaces each `{}` of the template with the next argument.
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    let mut str = parts.next().unwrap_or_default().to_owned();
+    for part in parts {
+        if let Some(arg) = args.next() {
+            str.push_str(&arg.to_string());
+        }
+        str.push_str(part);
+    }
+    str
+}
+
+/// A message with a singular and a plural form.
+pub struct Plural(pub &'static str, pub &'static str);
+
+impl Plural {
+    pub fn get(&self, n: usize) -> &'static str {
+        if n == 1 {
+            self.0
+        } else {
+            self.1
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Tenses {
+    pub sim_pres: &'static str,
+    pub pres_prog: &'static str,
+    pub sim_past: &'static str,
+}
+
+pub struct Messages {
+    pub titles: Titles,
+    pub subtitles: Subtitles,
+    pub move_tenses: Tenses,
+    pub copy_tenses: Tenses,
+    pub link_tenses: Tenses,
+    pub rename_tenses: Tenses,
+    pub delete_tenses: Tenses,
+    /// Merging the tags of duplicates into the original, followed by its path.
+    pub merge_tenses: Tenses,
+    pub labels: Labels,
+    pub notes: Notes,
+    pub dialogs: Dialogs,
+}
+
+/// The prefixes of error and warning lines.
+pub struct Labels {
+    pub error: &'static str,
+    pub warning: &'static str,
+    pub cancelled: &'static str,
+}
+
+/// Lines printed between the sections, `{}` is replaced using [`fill`].
+pub struct Notes {
+    pub skip_writing: &'static str,
+    pub skip_deduplicating: &'static str,
+    pub skip_cleaning_up: &'static str,
+    pub skip_syncing: &'static str,
+    pub songs_updated_because: &'static str,
+}
+
+pub struct Titles {
+    pub indexing: &'static str,
+    pub checking: &'static str,
+    pub changes: &'static str,
+    pub writing: &'static str,
+    pub hooks: &'static str,
+    pub cleanup: &'static str,
+    pub deletions: &'static str,
+    pub cleaning: &'static str,
+    pub sync: &'static str,
+    pub syncing: &'static str,
+    pub failures: &'static str,
+    pub stats: &'static str,
+    pub health: &'static str,
+    pub incomplete: &'static str,
+    pub duplicates: &'static str,
+    pub diff: &'static str,
+    pub summary: &'static str,
+    pub watching: &'static str,
+    pub serving: &'static str,
+}
+
+impl Titles {
+    /// Titles are padded to the width of the longest one.
+    pub fn max_width(&self) -> usize {
+        [
+            self.indexing,
+            self.checking,
+            self.changes,
+            self.writing,
+            self.hooks,
+            self.cleanup,
+            self.deletions,
+            self.cleaning,
+            self.sync,
+            self.syncing,
+            self.failures,
+            self.stats,
+            self.health,
+            self.incomplete,
+            self.duplicates,
+            self.diff,
+            self.summary,
+            self.watching,
+            self.serving,
+        ]
+        .iter()
+        .map(|t| t.chars().count())
+        .max()
+        .unwrap_or(0)
+    }
+}
+
+pub struct Subtitles {
+    pub dirs: &'static str,
+    pub files: &'static str,
+    pub songs: &'static str,
+    pub others: &'static str,
+    pub playlists: &'static str,
+    pub delete: &'static str,
+    pub only_a: &'static str,
+    pub only_b: &'static str,
+    pub formats: &'static str,
+    pub missing: &'static str,
+    pub genres: &'static str,
+    pub decades: &'static str,
+    pub excluded: &'static str,
+    pub largest: &'static str,
+    pub quarantine: &'static str,
+}
+
+impl Subtitles {
+    /// Subtitles are padded to the width of the longest one.
+    pub fn max_width(&self) -> usize {
+        [
+            self.dirs,
+            self.files,
+            self.songs,
+            self.others,
+            self.playlists,
+            self.delete,
+            self.only_a,
+            self.only_b,
+            self.formats,
+            self.missing,
+            self.genres,
+            self.decades,
+            self.excluded,
+            self.largest,
+            self.quarantine,
+        ]
+        .iter()
+        .map(|t| t.chars().count())
+        .max()
+        .unwrap_or(0)
+    }
+}
+
+/// Messages of questions and confirmations, `{}` is replaced using [`fill`].
+pub struct Dialogs {
+    pub proceed: &'static str,
+    pub invalid_input: &'static str,
+    pub no_input: &'static str,
+    pub exiting: &'static str,
+    pub ok: &'static str,
+    pub dont_do_anything: &'static str,
+
+    pub apply_release_changes: Plural,
+    pub apply_file_changes: Plural,
+
+    pub unknown_songs: Plural,
+    pub move_to_unknown: &'static str,
+    pub edit_tags_of_each_song: &'static str,
+    pub edit_tags: &'static str,
+    pub edit_again: &'static str,
+    pub release_artists_input: &'static str,
+    pub artists_input: &'static str,
+    pub release_input: &'static str,
+    pub title_input: &'static str,
+    pub track_number_input: &'static str,
+    pub required_tags: &'static str,
+    pub apply_inferred_tags: &'static str,
+
+    pub invalid_numbers: &'static str,
+    pub disc_change: &'static str,
+    pub track_change: &'static str,
+    pub fix_numbers: &'static str,
+
+    pub various_artists: &'static str,
+    pub set_release_artist: &'static str,
+
+    pub ambiguous_release_artists: &'static str,
+
+    pub inconsistent_total_tracks: &'static str,
+    pub inconsistent_total_discs: &'static str,
+    pub set_total: &'static str,
+
+    pub split_release: &'static str,
+
+    pub release_suffix: &'static str,
+    pub rename_to: &'static str,
+
+    pub similar_artists: &'static str,
+    pub genres_differ: &'static str,
+    pub years_differ: &'static str,
+    pub rename_first_to_second: &'static str,
+    pub rename_second_to_first: &'static str,
+    pub enter_new_names: &'static str,
+    pub never_ask_again: &'static str,
+    pub never_ask_again_release: &'static str,
+    pub renaming_first_to_second: &'static str,
+    pub renaming_second_to_first: &'static str,
+    pub enter_new_name: &'static str,
+    pub new_names: &'static str,
+    pub reenter_name: &'static str,
+    pub add_another: &'static str,
+    pub dismiss: &'static str,
+}
+
+const EN: Messages = Messages {
+    titles: Titles {
+        indexing: "INDEXING",
+        checking: "CHECKING",
+        changes: "CHANGES",
+        writing: "WRITING",
+        hooks: "HOOKS",
+        cleanup: "CLEANUP",
+        deletions: "DELETIONS",
+        cleaning: "CLEANING",
+        sync: "SYNC",
+        syncing: "SYNCING",
+        failures: "FAILURES",
+        stats: "STATS",
+        health: "HEALTH",
+        incomplete: "INCOMPLETE",
+        duplicates: "DUPLICATES",
+        diff: "DIFF",
+        summary: "SUMMARY",
+        watching: "WATCHING",
+        serving: "SERVING",
+    },
+    subtitles: Subtitles {
+        dirs: "dirs",
+        files: "files",
+        songs: "songs",
+        others: "others",
+        playlists: "lists",
+        delete: "delete",
+        only_a: "only a",
+        only_b: "only b",
+        formats: "format",
+        missing: "tags",
+        genres: "genre",
+        decades: "decade",
+        excluded: "skip",
+        largest: "large",
+        quarantine: "aside",
+    },
+    move_tenses: Tenses { sim_pres: "move", pres_prog: "moving", sim_past: "moved" },
+    copy_tenses: Tenses { sim_pres: "copy", pres_prog: "copying", sim_past: "copied" },
+    link_tenses: Tenses { sim_pres: "link", pres_prog: "linking", sim_past: "linked" },
+    rename_tenses: Tenses { sim_pres: "rename", pres_prog: "renaming", sim_past: "renamed" },
+    delete_tenses: Tenses { sim_pres: "delete", pres_prog: "deleting", sim_past: "deleted" },
+    merge_tenses: Tenses {
+        sim_pres: "merge tags into",
+        pres_prog: "merging tags into",
+        sim_past: "merged tags into",
+    },
+    labels: Labels { error: "error", warning: "warning", cancelled: "cancelled" },
+    notes: Notes {
+        skip_writing: "skip writing dryrun...",
+        skip_deduplicating: "skip deduplicating dryrun...",
+        skip_cleaning_up: "skip cleaning up dryrun...",
+        skip_syncing: "skip syncing dryrun...",
+        songs_updated_because: "songs updated because of {}",
+    },
+    dialogs: Dialogs {
+        proceed: "continue",
+        invalid_input: "invalid input",
+        no_input: "no input",
+        exiting: "exiting...",
+        ok: "ok",
+        dont_do_anything: "don't do anything",
+
+        apply_release_changes: Plural(
+            "apply {} change for {} - {}",
+            "apply {} changes for {} - {}",
+        ),
+        apply_file_changes: Plural(
+            "apply {} change to other files",
+            "apply {} changes to other files",
+        ),
+
+        unknown_songs: Plural(
+            "{} song is missing tags and will be moved to unknown",
+            "{} songs are missing tags and will be moved to unknown",
+        ),
+        move_to_unknown: "move to unknown",
+        edit_tags_of_each_song: "edit tags of each song",
+        edit_tags: "edit tags",
+        edit_again: "edit again",
+        release_artists_input: "release artists, separated by ;",
+        artists_input: "artists, separated by ;",
+        release_input: "release",
+        title_input: "title",
+        track_number_input: "track number",
+        required_tags: "release artists, release and title are required",
+        apply_inferred_tags: "apply inferred tags",
+
+        invalid_numbers: "These songs of {} - {} have invalid track or disc numbers:",
+        disc_change: "disc  {} to {}",
+        track_change: "track {} to {}",
+        fix_numbers: "fix the numbers",
+
+        various_artists: "The songs of {} in {} have {} different artists but no release artist:",
+        set_release_artist: "set the release artist to {}",
+
+        inconsistent_total_tracks: "These songs of {} - {} have different total tracks:",
+        inconsistent_total_discs: "These songs of {} - {} have different total discs:",
+        set_total: "set the total to {}",
+
+        ambiguous_release_artists:
+            "The songs of {} in {} have no release artist and no artist has a majority:",
+
+        split_release: "The songs of the release {} are split over these release artists:",
+
+        release_suffix: "The release {} - {} has an edition suffix in a non-standard format",
+        rename_to: "rename to {}",
+
+        similar_artists: "These two artists are named similarly:",
+        genres_differ: "They might be different artists, their genres differ",
+        years_differ: "They might be different artists, their years differ",
+        rename_first_to_second: "rename first to second",
+        rename_second_to_first: "rename second to first",
+        enter_new_names: "enter new name[s]",
+        never_ask_again: "never ask again for these artists",
+        never_ask_again_release: "never ask again for this release",
+        renaming_first_to_second: "renaming first to second",
+        renaming_second_to_first: "renaming second to first",
+        enter_new_name: "enter new name:",
+        new_names: "new name[s]:",
+        reenter_name: "reenter name",
+        add_another: "add another",
+        dismiss: "dismiss",
+    },
+};
+
+const DE: Messages = Messages {
+    titles: Titles {
+        indexing: "INDIZIEREN",
+        checking: "PRÜFEN",
+        changes: "ÄNDERUNGEN",
+        writing: "SCHREIBEN",
+        hooks: "HOOKS",
+        cleanup: "AUFRÄUMEN",
+        deletions: "LÖSCHEN",
+        cleaning: "BEREINIGEN",
+        sync: "ABGLEICH",
+        syncing: "ABGLEICHEN",
+        failures: "FEHLER",
+        stats: "STATISTIK",
+        health: "ZUSTAND",
+        incomplete: "LÜCKEN",
+        duplicates: "DUPLIKATE",
+        diff: "VERGLEICH",
+        summary: "ÜBERSICHT",
+        watching: "BEOBACHTEN",
+        serving: "SERVER",
+    },
+    subtitles: Subtitles {
+        dirs: "Ordner",
+        files: "Dateien",
+        songs: "Lieder",
+        others: "Andere",
+        playlists: "Listen",
+        delete: "Löschen",
+        only_a: "nur a",
+        only_b: "nur b",
+        formats: "Format",
+        missing: "Tags",
+        genres: "Genre",
+        decades: "Dekade",
+        excluded: "Überspringen",
+        largest: "Größte",
+        quarantine: "Beiseite",
+    },
+    move_tenses: Tenses {
+        sim_pres: "verschieben",
+        pres_prog: "verschiebe",
+        sim_past: "verschoben",
+    },
+    copy_tenses: Tenses { sim_pres: "kopieren", pres_prog: "kopiere", sim_past: "kopiert" },
+    link_tenses: Tenses { sim_pres: "verlinken", pres_prog: "verlinke", sim_past: "verlinkt" },
+    rename_tenses: Tenses {
+        sim_pres: "umbenennen",
+        pres_prog: "benenne um",
+        sim_past: "umbenannt",
+    },
+    delete_tenses: Tenses { sim_pres: "löschen", pres_prog: "lösche", sim_past: "gelöscht" },
+    merge_tenses: Tenses {
+        sim_pres: "Tags übertragen auf",
+        pres_prog: "übertrage Tags auf",
+        sim_past: "Tags übertragen auf",
+    },
+    labels: Labels { error: "Fehler", warning: "Warnung", cancelled: "abgebrochen" },
+    notes: Notes {
+        skip_writing: "Probelauf, überspringe Schreiben...",
+        skip_deduplicating: "Probelauf, überspringe Deduplizieren...",
+        skip_cleaning_up: "Probelauf, überspringe Aufräumen...",
+        skip_syncing: "Probelauf, überspringe Abgleichen...",
+        songs_updated_because: "Lieder aktualisiert wegen {}",
+    },
+    dialogs: Dialogs {
+        proceed: "fortfahren",
+        invalid_input: "ungültige Eingabe",
+        no_input: "keine Eingabe",
+        exiting: "beende...",
+        ok: "ok",
+        dont_do_anything: "nichts tun",
+
+        apply_release_changes: Plural(
+            "{} Änderung für {} - {} übernehmen",
+            "{} Änderungen für {} - {} übernehmen",
+        ),
+        apply_file_changes: Plural(
+            "{} Änderung an anderen Dateien übernehmen",
+            "{} Änderungen an anderen Dateien übernehmen",
+        ),
+
+        unknown_songs: Plural(
+            "{} Lied fehlen Tags, es wird nach unknown verschoben",
+            "{} Liedern fehlen Tags, sie werden nach unknown verschoben",
+        ),
+        move_to_unknown: "nach unknown verschieben",
+        edit_tags_of_each_song: "Tags jedes Lieds bearbeiten",
+        edit_tags: "Tags bearbeiten",
+        edit_again: "erneut bearbeiten",
+        release_artists_input: "Album-Interpreten, getrennt durch ;",
+        artists_input: "Interpreten, getrennt durch ;",
+        release_input: "Album",
+        title_input: "Titel",
+        track_number_input: "Titelnummer",
+        required_tags: "Album-Interpreten, Album und Titel sind erforderlich",
+        apply_inferred_tags: "abgeleitete Tags übernehmen",
+
+        invalid_numbers: "Diese Lieder von {} - {} haben ungültige Titel- oder CD-Nummern:",
+        disc_change: "CD    {} zu {}",
+        track_change: "Titel {} zu {}",
+        fix_numbers: "die Nummern korrigieren",
+
+        various_artists: "Die Lieder von {} in {} haben {} verschiedene Interpreten, aber keinen Album-Interpreten:",
+        set_release_artist: "den Album-Interpreten auf {} setzen",
+
+        inconsistent_total_tracks: "Diese Lieder von {} - {} haben unterschiedliche Titelanzahlen:",
+        inconsistent_total_discs: "Diese Lieder von {} - {} haben unterschiedliche CD-Anzahlen:",
+        set_total: "die Anzahl auf {} setzen",
+
+        ambiguous_release_artists: "Die Lieder von {} in {} haben keinen Album-Interpreten und kein Interpret hat eine Mehrheit:",
+
+        split_release: "Die Lieder des Albums {} sind auf diese Album-Interpreten verteilt:",
+
+        release_suffix: "Das Album {} - {} hat einen Editionszusatz in einem unüblichen Format",
+        rename_to: "umbenennen in {}",
+
+        similar_artists: "Diese zwei Interpreten haben ähnliche Namen:",
+        genres_differ: "Es könnten verschiedene Interpreten sein, ihre Genres unterscheiden sich",
+        years_differ: "Es könnten verschiedene Interpreten sein, ihre Jahre unterscheiden sich",
+        rename_first_to_second: "ersten in zweiten umbenennen",
+        rename_second_to_first: "zweiten in ersten umbenennen",
+        enter_new_names: "neue[n] Namen eingeben",
+        never_ask_again: "für diese Interpreten nie wieder fragen",
+        never_ask_again_release: "für dieses Album nie wieder fragen",
+        renaming_first_to_second: "benenne ersten in zweiten um",
+        renaming_second_to_first: "benenne zweiten in ersten um",
+        enter_new_name: "neuen Namen eingeben:",
+        new_names: "neue[r] Name[n]:",
+        reenter_name: "Namen erneut eingeben",
+        add_another: "weiteren hinzufügen",
+        dismiss: "verwerfen",
+    },
+};
@@ -0,0 +1,67 @@
+use std::fs::{File, TryLockError};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// Held while a run writes to the music dir, so runs of the watcher, its schedules, the server
+/// and other processes don't overlap. The lock file in the temp dir is locked by the operating
+/// system, so the lock is released when the holding process exits, even if it crashed. The file
+/// is kept and contains the id of the process that locked it last.
+pub struct RunLock {
+    _file: File,
+}
+
+pub enum LockError {
+    /// Another run holds the lock, the id of its process if it was written already.
+    Running {
+        pid: Option<u32>,
+    },
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for LockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockError::Running { pid: Some(pid) } => {
+                write!(f, "another run is in progress (process {pid})")
+            }
+            LockError::Running { pid: None } => write!(f, "another run is in progress"),
+            LockError::Io(e) => write!(f, "locking the music dir: {e}"),
+        }
+    }
+}
+
+impl RunLock {
+    pub fn acquire(music_dir: &Path) -> Result<Self, LockError> {
+        let path = lock_path(music_dir);
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(LockError::Io)?;
+        match file.try_lock() {
+            Ok(()) => (),
+            Err(TryLockError::WouldBlock) => {
+                let mut pid = String::new();
+                file.read_to_string(&mut pid).ok();
+                return Err(LockError::Running { pid: pid.trim().parse().ok() });
+            }
+            Err(TryLockError::Error(e)) => return Err(LockError::Io(e)),
+        }
+
+        file.set_len(0).map_err(LockError::Io)?;
+        file.rewind().map_err(LockError::Io)?;
+        write!(file, "{}", std::process::id()).map_err(LockError::Io)?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// The music dir is canonicalized, so different paths to the same dir share a lock.
+fn lock_path(music_dir: &Path) -> PathBuf {
+    let dir = music_dir.canonicalize().unwrap_or_else(|_| music_dir.to_owned());
+    let mut hasher = DefaultHasher::new();
+    dir.hash(&mut hasher);
+    std::env::temp_dir().join(format!("music-organizer-{:016x}.lock", hasher.finish()))
+}
@@ -1,43 +1,146 @@
-use colored::Colorize;
-use music_organizer::{Changes, Checks, Cleanup, FileOpType, MusicIndex, ReleaseArtists, Value};
+use clap::ColorChoice;
+use indicatif::ProgressBar;
+use music_organizer_core::{
+    is_music_video_extension, is_song_extension, prune_backups, ArtistDistinction, ArtistVote,
+    ChangeOptions, ChangedOnDisk, Changes, Checks, Cleanup, Compilation, DedupAction, Duplicates,
+    ExternalUpdate, FileOpType, FileOperation, HealthReport, ImagePolicy, IncompleteReport,
+    InconsistentTotals, IndexCache, IndexEvent, Item, ItunesLibrary, LibraryDiff, LibraryStats,
+    Metadata, Mirror, MissingTags, MusicIndex, NfoTemplate, OperationId, PathManifest,
+    PlanSnapshot, Progress, RealFs, Release, ReleaseArtists, ReleaseSuffix, Song, SongOperation,
+    SplitRelease, TagUpdate, TotalKind, Value, VirtualTree, ARTIST_ID_FIELDS, DEFAULT_BONUS_DIR,
+    DEFAULT_FOREIGN_MARKERS, RELEASE_GROUP_FIELD, VARIOUS_ARTISTS,
+};
+use std::collections::{BTreeSet, HashSet};
 use std::fmt::Write as _;
 use std::io::Write as _;
-
-use crate::args::Args;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+use tracing::{info, warn};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::EnvFilter;
+
+use crate::answers::Answers;
+use crate::args::{Args, BonusPolicy, CheckPolicy, InferPolicy, LogFormat, ReportFormat};
 use crate::display::strip_dir;
+use crate::hooks::HookEvent;
+use crate::locale::{fill, Labels, Locale, Subtitles, Tenses, Titles};
+use crate::lock::RunLock;
+use crate::progress::{Eta, PathMessage};
+use crate::report::{CheckReport, Details};
+use crate::summary::Summary;
+use crate::theme::Themed;
+
+/// Nothing to do, or all changes were applied.
+const EXIT_SUCCESS: i32 = 0;
+/// Changes were applied, but some operations failed.
+const EXIT_ERRORS: i32 = 1;
+/// Nothing was applied, e.g. because a required file couldn't be read or a hook aborted the run.
+const EXIT_FATAL: i32 = 2;
+/// The libraries compared by the `diff` subcommand differ.
+const EXIT_DIFFERS: i32 = 3;
+/// The run was cancelled by Ctrl-C.
+const EXIT_CANCELLED: i32 = 130;
+
+/// Set if stdout is reserved for the new paths, see [`Args::print_new_paths`].
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+/// Set if nothing but the summary line is printed, see [`Args::quiet`].
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+// all other output goes to stderr while stdout is reserved
+macro_rules! println {
+    ($($arg:tt)*) => {
+        match (
+            $crate::QUIET.load(std::sync::atomic::Ordering::Relaxed),
+            $crate::STDOUT_RESERVED.load(std::sync::atomic::Ordering::Relaxed),
+        ) {
+            (true, _) => (),
+            (false, true) => std::eprintln!($($arg)*),
+            (false, false) => std::println!($($arg)*),
+        }
+    };
+}
+
+macro_rules! print {
+    ($($arg:tt)*) => {
+        match (
+            $crate::QUIET.load(std::sync::atomic::Ordering::Relaxed),
+            $crate::STDOUT_RESERVED.load(std::sync::atomic::Ordering::Relaxed),
+        ) {
+            (true, _) => (),
+            (false, true) => std::eprint!($($arg)*),
+            (false, false) => std::print!($($arg)*),
+        }
+    };
+}
 
+mod answers;
 mod args;
+mod config;
 mod display;
+mod guard;
+mod hooks;
+mod locale;
+mod lock;
+mod metrics;
+#[cfg(feature = "notifications")]
+mod notification;
+mod pager;
+mod progress;
+mod report;
+mod schedule;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(unix)]
+mod socket;
+mod summary;
+mod theme;
+mod watch;
 
 const VERBOSE: u8 = 2;
-const MAX_TITLE_WITH: usize = 9;
-const TITLE_INDEXING: &str = "INDEXING";
-const TITLE_CHECKING: &str = "CHECKING";
-const TITLE_CHANGES: &str = "CHANGES";
-const TITLE_WRITING: &str = "WRITING";
-const TITLE_CLEANUP: &str = "CLEANUP";
-const TITLE_DELETIONS: &str = "DELETIONS";
-const TITLE_CLEANING: &str = "CLEANING";
-
-const MAX_SUBTITLE_WITH: usize = 6;
-const SUBTITLE_DIRS: &str = "dirs";
-const SUBTITLE_SONGS: &str = "songs";
-const SUBTITLE_OTHERS: &str = "others";
-
-const RENAME_TENSES: Tenses =
-    Tenses { sim_pres: "rename", pres_prog: "renaming", sim_past: "renamed" };
-const MOVE_TENSES: Tenses = Tenses { sim_pres: "move", pres_prog: "moving", sim_past: "moved" };
-const COPY_TENSES: Tenses = Tenses { sim_pres: "copy", pres_prog: "copying", sim_past: "copied" };
+
+/// Number of largest releases listed in the stats.
+const NUM_LARGEST_RELEASES: usize = 10;
+/// Number of releases with the most failing songs listed per category of the health report.
+const NUM_WORST_RELEASES: usize = 5;
+
+/// Files of at least this size display the number of copied bytes.
+const LARGE_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+struct Failure {
+    path: PathBuf,
+    error: String,
+}
 
 struct Dict {
     op_type: Tenses,
     rename: Tenses,
 }
 
-struct Tenses {
-    sim_pres: &'static str,
-    pres_prog: &'static str,
-    sim_past: &'static str,
+impl Dict {
+    /// The tenses of a file operation, which may override the op type, e.g. copied images.
+    fn file_op_type(&self, f: &FileOperation) -> &Tenses {
+        match f.op_type {
+            Some(FileOpType::Copy) => &locale::get().copy_tenses,
+            Some(FileOpType::Move) => &locale::get().move_tenses,
+            Some(FileOpType::HardLink) => &locale::get().link_tenses,
+            None => &self.op_type,
+        }
+    }
+}
+
+fn titles() -> &'static Titles {
+    &locale::get().titles
+}
+
+fn subtitles() -> &'static Subtitles {
+    &locale::get().subtitles
+}
+
+fn labels() -> &'static Labels {
+    &locale::get().labels
 }
 
 fn print_title_verbose(verbose: bool, title: &str) {
@@ -47,463 +150,3078 @@ fn print_title_verbose(verbose: bool, title: &str) {
 }
 
 fn print_title(title: &str) {
-    let padding = MAX_TITLE_WITH - title.len() + 1;
-    println!("{} ", format!(" {title}{:padding$}", "").purple().on_black());
+    println!("{}", format_title(title));
+}
+
+fn format_title(title: &str) -> String {
+    let padding = titles().max_width() - title.chars().count() + 1;
+    format!("{} ", format!(" {title}{:padding$}", "").title())
 }
 
 fn print_subtitle(title: &str) {
-    let padding = MAX_SUBTITLE_WITH - title.len() + 1;
-    println!("{} ", format!(" {title}{:padding$}", "").cyan().on_black());
+    println!("{}", format_subtitle(title));
+}
+
+fn format_subtitle(title: &str) -> String {
+    let padding = subtitles().max_width() - title.chars().count() + 1;
+    format!("{} ", format!(" {title}{:padding$}", "").subtitle())
 }
 
 macro_rules! print_verbose {
     ($verbose:expr, $title:expr, $pat:expr, $($args:expr),*) => {{
         if $verbose {
             println!($pat $(,$args)*);
-        } else {
-            print!("\x1b[2K\r");
-            let padding = MAX_TITLE_WITH - $title.len() + 1;
-            print!("{} ", format!(" {}{:padding$}", $title, "").purple().on_black());
-            print!($pat $(,$args)*);
+        } else if progress::stdout_is_terminal() {
+            let line = format!("{}{}", format_title($title), format!($pat $(,$args)*));
+            print!("\x1b[2K\r{}", progress::fit_last_line(&line));
             std::io::stdout().flush().ok();
+        } else {
+            // lines can't be overwritten
+            print!("{}", format_title($title));
+            println!($pat $(,$args)*);
         }
     }}
 }
 
 fn main() {
     let args = args::parse_args();
+    let json_report = args.health == Some(ReportFormat::Json);
+    STDOUT_RESERVED.store(args.print_new_paths.is_some() || json_report, Ordering::Relaxed);
+    QUIET.store(args.quiet, Ordering::Relaxed);
+    theme::init(theme::Theme::from(&args.config.theme));
+    locale::init(Locale::detect(args.config.locale.as_deref()));
+    for w in args.warnings.iter() {
+        println!("{} {w}", labels().warning.highlight());
+    }
     let dict = Dict {
-        op_type: match args.op_type {
-            FileOpType::Move => MOVE_TENSES,
-            FileOpType::Copy => COPY_TENSES,
+        op_type: match args.exec.op_type {
+            FileOpType::Move => locale::get().move_tenses,
+            FileOpType::Copy => locale::get().copy_tenses,
+            FileOpType::HardLink => locale::get().link_tenses,
         },
-        rename: RENAME_TENSES,
+        rename: locale::get().rename_tenses,
+    };
+
+    init_color(args.color);
+    if let Some(format) = args.log_format {
+        init_logging(format, args.verbosity, args.log_file.as_deref());
+    }
+
+    let cancel = args.exec.cancel.clone();
+    let handler = ctrlc::set_handler(move || {
+        // a second Ctrl-C kills the process
+        if cancel.is_cancelled() {
+            std::process::exit(EXIT_CANCELLED);
+        }
+        cancel.cancel();
+    });
+    if let Err(e) = handler {
+        println!("{} setting Ctrl-C handler: {}", labels().error.error(), e.to_string().error());
+    }
+
+    if let Some([a, b]) = &args.diff {
+        let differs = display_diff(a, b, &args);
+        std::process::exit(if differs { EXIT_DIFFERS } else { EXIT_SUCCESS });
+    }
+
+    if args.watch {
+        watch::watch(&args, &dict);
+        return;
+    }
+
+    #[cfg(feature = "server")]
+    if let Some(addr) = args.serve {
+        server::serve(&args, addr);
+        return;
+    }
+
+    let mut files = args
+        .files_from
+        .as_ref()
+        .map(|path| read_file_list(path).unwrap_or_else(|e| exit_fatal("reading file list", e)));
+    if let Some(dir) = &args.release_dir {
+        let mut release_files = BTreeSet::new();
+        watch::collect_files(dir, &mut release_files);
+        files = Some(release_files.into_iter().collect());
+    }
+    let failures = run(&args, &dict, files, &mut BTreeSet::new());
+    if !failures.is_empty() {
+        std::process::exit(EXIT_ERRORS);
+    }
+}
+
+/// Indexes, checks, writes and cleans up the music dir once, returns the failures. The paths
+/// written by the plan are added to `written`.
+fn run(
+    args: &Args,
+    dict: &Dict,
+    files: Option<Vec<PathBuf>>,
+    written: &mut BTreeSet<PathBuf>,
+) -> Vec<Failure> {
+    let mut failures = Vec::new();
+    let start = Instant::now();
+
+    // reports only read the music dir
+    let is_report = args.stats || args.health.is_some() || args.incomplete_report.is_some();
+    let writes = !args.dry_run && !is_report;
+    let _lock = match writes.then(|| RunLock::acquire(&args.music_dir)).transpose() {
+        Ok(lock) => lock,
+        Err(e) => {
+            failures.push(Failure { path: args.music_dir.clone(), error: e.to_string() });
+            write_metrics(args, &Summary::default(), &failures, start);
+            // while watching, the next change is organized once the other run is done
+            if !args.watch {
+                exit_fatal("locking music dir", e);
+            }
+            println!("{} {}", labels().error.error(), e.to_string().error());
+            return failures;
+        }
     };
 
+    display_hooks(args, HookEvent::BeforeRun);
+
     // indexing
-    let mut index = MusicIndex::from(args.music_dir.clone());
-    display_indexing(&mut index, &args);
+    let mut index = new_index(args);
+    if let Some(path) = &args.index_cache {
+        let cache = IndexCache::load(path).unwrap_or_else(|e| {
+            println!("{} loading index cache: {}", labels().error.error(), e.to_string().error());
+            IndexCache::default()
+        });
+        index.cache = Some(cache);
+    }
+    #[cfg(feature = "sqlite")]
+    let mut index_db = args.index_db.as_ref().and_then(|path| {
+        let db = music_organizer_core::IndexDb::open(path).and_then(|db| {
+            index.cache = Some(db.load_cache()?);
+            Ok(db)
+        });
+        db.map_err(|e| {
+            println!("{} opening index db: {}", labels().error.error(), e.to_string().error())
+        })
+        .ok()
+    });
+    display_indexing(&mut index, files, args);
+    let mut summary = Summary {
+        indexed: index.songs.len()
+            + index.images.len()
+            + index.companions.len()
+            + index.extras.len()
+            + index.videos.len()
+            + index.audiobooks.len()
+            + index.unknown.len()
+            + index.playlists.len()
+            + index.skipped.len(),
+        unknown: index.unknown.len(),
+        ..Default::default()
+    };
+    exit_if_cancelled(args, dict, &summary, &failures);
+    #[cfg(feature = "sqlite")]
+    if let Some(db) = &mut index_db {
+        if let Err(e) = db.save(&index) {
+            println!("{} saving index db: {}", labels().error.error(), e.to_string().error());
+        }
+    }
+    if let (Some(path), Some(cache)) = (&args.index_cache, &index.cache) {
+        if let Err(e) = cache.save(path) {
+            println!("{} saving index cache: {}", labels().error.error(), e.to_string().error());
+        }
+    }
+    if let Some(path) = &args.export_index {
+        if let Err(e) = export_index(&index, path) {
+            println!("{} exporting index: {}", labels().error.error(), e.to_string().error());
+        }
+    }
+
+    if args.stats {
+        display_stats(&index);
+        return failures;
+    }
+    if let Some(format) = args.health {
+        display_health(&index, args, format);
+        return failures;
+    }
+    if let Some(path) = &args.incomplete_report {
+        display_incomplete(&index, path.as_deref());
+        return failures;
+    }
+
+    index.retain_matching(&args.filters);
+
+    let mut duplicates = Duplicates { cancel: args.exec.cancel.clone(), ..Default::default() };
+    if let Some(action) = args.dedup {
+        // duplicates
+        display_duplicates(&mut duplicates, &index, args, action);
+        exit_if_cancelled(args, dict, &summary, &failures);
+
+        if args.quarantine {
+            // the copies are set aside with the other changes
+            let copies = duplicates.groups.iter().flat_map(|g| g.copies.iter().cloned());
+            remove_from_index(&mut index, &copies.collect());
+        } else if !duplicates.is_empty()
+            && (args.assume_yes || args.dry_run || confirm_input(locale::get().dialogs.proceed))
+        {
+            display_dedup(&duplicates, &mut index, args, action, &mut summary, &mut failures);
+            exit_if_cancelled(args, dict, &summary, &failures);
+        }
+    }
+
+    let itunes = args.itunes_library.as_deref().map(|path| {
+        let library = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| s.parse::<ItunesLibrary>().map_err(|e| e.to_string()));
+        library.unwrap_or_else(|e| exit_fatal("loading itunes library", e))
+    });
+    let external_updates = load_external_updates(args);
+    #[cfg(feature = "sqlite")]
+    let mut beets = args.beets_db.as_ref().map(|path| {
+        let db = music_organizer_core::BeetsDb::open(path).and_then(|db| {
+            let items = db.load_items()?;
+            Ok((db, items))
+        });
+        db.unwrap_or_else(|e| exit_fatal("opening beets db", e))
+    });
+
+    // unknown songs
+    let mut unknown_ops = Vec::new();
+    if let Some(policy) = args.infer_tags {
+        unknown_ops = display_inference(&mut index, args, policy);
+    }
+    if args.vote_release_artists {
+        unknown_ops.extend(index.resolve_unknown_by_vote());
+    }
+    if !args.no_check && !args.assume_yes && index.unknown_songs().next().is_some() {
+        unknown_ops.extend(display_unknown(&mut index, args));
+    }
 
     // checking
     let mut checks = Checks::from(&index);
+    exclude_from_retagging(&mut checks, &index, args);
+    // inferred tags of excluded songs are only used to organize them
+    let unknown_ops = unknown_ops.into_iter();
+    (checks.song_operations)
+        .extend(unknown_ops.filter(|o| !checks.retag_excluded.contains(o.song.path.as_path())));
+    checks.cancel = args.exec.cancel.clone();
+    checks.similarity_scope = args.similarity_scope.clone();
+    if let Some(library) = &itunes {
+        checks.import_itunes(library);
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some((_, items)) = &beets {
+        checks.import_beets(items);
+    }
     if !args.no_check {
-        display_checking(&mut checks, &args);
+        let mut answers =
+            Answers::load(args.answers_file.as_deref(), &args.decisions_file, &args.answers)
+                .unwrap_or_else(|e| exit_fatal("loading answers", e));
+        let mut report = args.check_report.as_ref().map(|_| CheckReport::default());
+        display_checking(&mut checks, &mut answers, &mut report, args);
+        summary.checks_applied = checks.song_operations.len();
+        if let (Some(path), Some(mut report)) = (&args.check_report, report) {
+            report.add_updates(&checks.song_operations);
+            if let Err(e) = report.write(path) {
+                println!(
+                    "{} writing check report: {}",
+                    labels().error.error(),
+                    e.to_string().error()
+                );
+            }
+        }
+        if let Err(e) = answers.save() {
+            println!("{} saving answers: {}", labels().error.error(), e.to_string().error());
+        }
+        exit_if_cancelled(args, dict, &summary, &failures);
+    }
+    if let Some(updates) = &external_updates {
+        for path in checks.apply_external_updates(updates) {
+            println!(
+                "{} skipping tag update of {}, which isn't an indexed song",
+                labels().warning.highlight(),
+                path.display().to_string().highlight(),
+            );
+        }
     }
 
     // changes
-    let changes = Changes::generate(checks, &args.output_dir);
-    display_changes(&changes, &args, &dict);
+    let options = change_options(args);
+    let retag_excluded = checks.retag_excluded.clone();
+    let mut changes = Changes::generate(&RealFs, checks, &options);
+    if let Some(library) = &itunes {
+        changes.generate_itunes_playlists(&RealFs, &index, library, &options);
+    }
+    if args.quarantine {
+        changes.quarantine_duplicates(&RealFs, &duplicates, &options);
+    }
+    changes.sort();
+    let snapshot = PlanSnapshot::new(&changes);
+    let new_ops = match &args.plan_snapshot {
+        Some(path) if args.dry_run => match load_plan_snapshot(path) {
+            Ok(previous) => Some(snapshot.new_since(&previous)),
+            Err(e) => {
+                println!(
+                    "{} reading plan snapshot: {}",
+                    labels().error.error(),
+                    e.to_string().error()
+                );
+                None
+            }
+        },
+        _ => None,
+    };
+    let time = SystemTime::now();
+    if args.provenance_tag {
+        changes.add_provenance_tags(env!("CARGO_PKG_VERSION"), time, &retag_excluded);
+    }
+    drop_content_updates(&mut changes, args);
+    display_changes(&changes, &index, new_ops.as_ref(), args, dict);
+    if args.strict {
+        display_strict(&changes, &options, args);
+    }
 
     if !changes.is_empty() {
         // writing
-        if !args.assume_yes && !args.dry_run {
-            let ok = confirm_input("continue");
+        if args.confirm_releases && !args.dry_run {
+            confirm_releases(&mut changes);
+        } else if !args.assume_yes && !args.dry_run {
+            let ok = confirm_input(locale::get().dialogs.proceed);
             if !ok {
                 successfull_early_exit();
             }
         }
-        display_writing(&changes, &args, &dict)
+        if args.dry_run {
+            display_collisions(&index, &changes, args);
+        }
+        display_writing(&changes, args, dict, &mut summary, &mut failures);
+        if !args.dry_run {
+            written.extend(written_paths(&changes));
+        }
+        if let Some(path) = args.provenance_manifest.as_ref().filter(|_| !args.dry_run) {
+            if let Err(e) = append_provenance(path, &changes, &failures, time) {
+                println!(
+                    "{} writing provenance: {}",
+                    labels().error.error(),
+                    e.to_string().error()
+                );
+            }
+        }
+        if let Some(path) = args.path_manifest.as_ref().filter(|_| !args.dry_run) {
+            if let Err(e) = write_path_manifest(path, &changes, &failures) {
+                println!(
+                    "{} writing path manifest: {}",
+                    labels().error.error(),
+                    e.to_string().error()
+                );
+            }
+        }
+        if let Some(dir) = args.backup_dir.as_ref().filter(|_| !args.dry_run) {
+            if let Err(e) = prune_backups(&RealFs, dir, args.keep_backups) {
+                println!("{} pruning backups: {}", labels().error.error(), e.to_string().error());
+            }
+        }
+        if !args.dry_run && args.exec.op_type == FileOpType::Move {
+            update_index_paths(args, &mut index, &changes);
+            #[cfg(feature = "sqlite")]
+            if let Some(db) = &mut index_db {
+                if let Err(e) = db.update_paths(&RealFs, &changes) {
+                    println!(
+                        "{} updating index db: {}",
+                        labels().error.error(),
+                        e.to_string().error()
+                    );
+                }
+            }
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some((db, _)) = &mut beets {
+            if !args.dry_run && args.exec.op_type == FileOpType::Move {
+                if let Err(e) = db.update_paths(&RealFs, &changes) {
+                    println!(
+                        "{} updating beets db: {}",
+                        labels().error.error(),
+                        e.to_string().error()
+                    );
+                }
+            }
+        }
+        exit_if_cancelled(args, dict, &summary, &failures);
+
+        if !args.dry_run && failures.is_empty() {
+            display_hooks(args, HookEvent::AfterWrite);
+        }
+    }
+
+    if let Some(path) = args.plan_snapshot.as_ref().filter(|_| !args.dry_run) {
+        if let Err(e) = save_plan_snapshot(path, &snapshot) {
+            println!("{} writing plan snapshot: {}", labels().error.error(), e.to_string().error());
+        }
+    }
+
+    if let Some(template) = &args.release_nfo {
+        if !args.dry_run && !failed_fast(args, &failures) {
+            write_release_nfos(&changes, &index, template, &options, args);
+        }
     }
 
-    if !args.no_cleanup {
+    if !args.no_cleanup && !failed_fast(args, &failures) {
         // cleanup
-        let mut cleanup = Cleanup::from(args.music_dir.clone());
-        display_cleanup(&mut cleanup, &args);
+        let mut cleanup = new_cleanup(args, &index.skipped);
+        display_cleanup(&mut cleanup, args);
+        exit_if_cancelled(args, dict, &summary, &failures);
 
         // deletions
-        display_deletions(&cleanup, &args);
+        display_deletions(&cleanup, args);
 
         if !cleanup.is_empty() {
             // cleaning
             if !args.assume_yes && !args.dry_run {
-                let ok = confirm_input("continue");
+                let ok = confirm_input(locale::get().dialogs.proceed);
                 if !ok {
                     successfull_early_exit();
                 }
             }
-            display_cleaning(&cleanup, &args);
+            display_cleaning(&cleanup, args, &mut summary, &mut failures);
+            exit_if_cancelled(args, dict, &summary, &failures);
+        }
+    }
+
+    if let Some(sync_dir) = args.sync_dir.as_ref().filter(|_| !failed_fast(args, &failures)) {
+        // sync
+        let mut mirror = Mirror::new(args.output_dir.clone(), sync_dir.clone());
+        mirror.profile = args.naming;
+        mirror.delete = args.sync_delete;
+        mirror.include_hidden = args.include_hidden;
+        mirror.dir_mode = args.dir_mode;
+        mirror.transcode = args.transcode.clone();
+        mirror.size_limit = args.sync_limit.clone();
+        mirror.cancel = args.exec.cancel.clone();
+        display_sync(&mut mirror, args);
+        exit_if_cancelled(args, dict, &summary, &failures);
+
+        if !mirror.is_empty() {
+            // syncing
+            if !args.assume_yes && !args.dry_run {
+                let ok = confirm_input(locale::get().dialogs.proceed);
+                if !ok {
+                    successfull_early_exit();
+                }
+            }
+            display_syncing(&mirror, args, &mut failures);
+            exit_if_cancelled(args, dict, &summary, &failures);
+        }
+    }
+
+    if !failures.is_empty() {
+        display_failures(&failures, args);
+    }
+    display_hooks(args, HookEvent::AfterRun);
+
+    print_summary(dict, &summary, &failures);
+    write_metrics(args, &summary, &failures, start);
+    #[cfg(feature = "notifications")]
+    if args.notify && !args.dry_run {
+        if let Err(e) = notification::send(&summary, failures.len()) {
+            println!("{} sending notification: {}", labels().error.error(), e.to_string().error());
+        }
+    }
+    failures
+}
+
+fn write_metrics(args: &Args, summary: &Summary, failures: &[Failure], start: Instant) {
+    let Some(path) = &args.metrics_file else { return };
+    if let Err(e) = metrics::record(path, summary, failures.len(), start.elapsed()) {
+        println!("{} writing metrics: {}", labels().error.error(), e.to_string().error());
+    }
+}
+
+/// Questions can't be asked in watch mode, the checks leave the songs unchanged instead.
+fn fix_answers() -> Vec<String> {
+    ["fix", "none"].map(str::to_owned).to_vec()
+}
+
+/// `none` and `names:A;B` with each of the proposed names.
+fn names_answers<'a>(proposals: impl Iterator<Item = &'a Vec<String>>) -> Vec<String> {
+    let proposals = proposals.map(|n| answers::format_names_answer(&Value::Update(n.clone())));
+    std::iter::once("none".to_owned()).chain(proposals).collect()
+}
+
+fn skip_unanswered(key: &str) {
+    warn!(question = key, "skipping unanswered question");
+    println!("{} skipping unanswered question {key}", labels().warning.highlight());
+}
+
+/// An empty index of the music dir configured by the args.
+fn new_index(args: &Args) -> MusicIndex {
+    let mut index = MusicIndex::from(args.music_dir.clone());
+    index.excluded_dirs = args.excluded_dirs.clone();
+    index.threads = args.threads;
+    index.follow_symlinks = args.follow_symlinks;
+    index.max_depth = args.max_depth;
+    index.include_hidden = args.include_hidden;
+    index.artist_separators = args.artist_separators.clone();
+    index.custom_fields = args.config.custom_fields.clone();
+    index.custom_fields.extend(ARTIST_ID_FIELDS.map(str::to_owned));
+    index.custom_fields.push(RELEASE_GROUP_FIELD.to_owned());
+    #[cfg(feature = "replay-gain")]
+    if args.replay_gain {
+        index.custom_fields.extend(music_organizer_core::REPLAY_GAIN_FIELDS.map(str::to_owned));
+    }
+    index.foreign_markers = foreign_markers(args);
+    index.cancel = args.exec.cancel.clone();
+    index
+}
+
+fn foreign_markers(args: &Args) -> Vec<String> {
+    match &args.config.foreign_markers {
+        Some(markers) => markers.clone(),
+        None => DEFAULT_FOREIGN_MARKERS.map(str::to_owned).to_vec(),
+    }
+}
+
+/// A cleanup of the music dir configured by the args. The dirs of `skipped` songs, which are
+/// tagged to be left alone, aren't cleaned up.
+fn new_cleanup(args: &Args, skipped: &[PathBuf]) -> Cleanup {
+    let mut cleanup = Cleanup::from(args.music_dir.clone());
+    cleanup.excluded_dirs = args.excluded_dirs.clone();
+    cleanup.excluded_dirs.extend(skipped.iter().filter_map(|p| p.parent()).map(Path::to_owned));
+    cleanup.max_depth = args.max_depth;
+    cleanup.include_hidden = args.include_hidden;
+    cleanup.junk_patterns = args.junk_patterns.clone();
+    cleanup.foreign_markers = foreign_markers(args);
+    cleanup.remove_images = args.image_policy == ImagePolicy::Delete;
+    cleanup.cancel = args.exec.cancel.clone();
+    cleanup.scope = args.release_dir.clone();
+    cleanup
+}
+
+/// Appends the provenance of the files that were organized successfully as json lines.
+fn append_provenance(
+    path: &Path,
+    changes: &Changes,
+    failures: &[Failure],
+    time: SystemTime,
+) -> std::io::Result<()> {
+    let mut lines = String::new();
+    for p in changes.provenance(env!("CARGO_PKG_VERSION"), time) {
+        if !failures.iter().any(|f| f.path == p.original_path) {
+            lines.push_str(&serde_json::to_string(&p)?);
+            lines.push('\n');
+        }
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(lines.as_bytes())
+}
+
+/// Writes the old and new paths of the files that were moved successfully.
+fn write_path_manifest(
+    path: &Path,
+    changes: &Changes,
+    failures: &[Failure],
+) -> std::io::Result<()> {
+    let mut manifest = PathManifest::generate(changes);
+    manifest.moves.retain(|m| !failures.iter().any(|f| f.path == m.old_path));
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    match path.extension() {
+        Some(e) if e.eq_ignore_ascii_case("csv") => manifest.export_csv(&mut writer)?,
+        _ => manifest.export_json(&mut writer)?,
+    }
+    writer.flush()
+}
+
+/// A missing snapshot is empty, so every operation of the first run is new.
+fn load_plan_snapshot(path: &Path) -> std::io::Result<PlanSnapshot> {
+    match std::fs::File::open(path) {
+        Ok(file) => Ok(serde_json::from_reader(std::io::BufReader::new(file))?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(PlanSnapshot::default()),
+        Err(e) => Err(e),
+    }
+}
+
+fn save_plan_snapshot(path: &Path, snapshot: &PlanSnapshot) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    serde_json::to_writer(&mut writer, snapshot)?;
+    writer.flush()
+}
+
+fn write_release_nfos(
+    changes: &Changes,
+    index: &MusicIndex,
+    template: &NfoTemplate,
+    options: &ChangeOptions,
+    args: &Args,
+) {
+    for nfo in changes.release_nfos(&RealFs, index, template, options) {
+        match nfo.execute(&RealFs) {
+            Ok(()) if args.verbosity >= 2 => {
+                println!("wrote {}", strip_dir(&nfo.path, &args.output_dir));
+            }
+            Ok(()) => (),
+            Err(e) => println!(
+                "{} writing {}: {}",
+                labels().error.error(),
+                strip_dir(&nfo.path, &args.output_dir),
+                e.to_string().error()
+            ),
+        }
+    }
+}
+
+/// Moves the cache entries of moved songs, so the next run doesn't read their metadata again.
+fn update_index_paths(args: &Args, index: &mut MusicIndex, changes: &Changes) {
+    let (Some(path), Some(cache)) = (&args.index_cache, &mut index.cache) else { return };
+    cache.update_paths(&RealFs, changes);
+    if let Err(e) = cache.save(path) {
+        println!("{} saving index cache: {}", labels().error.error(), e.to_string().error());
+    }
+}
+
+/// The paths that executing the changes creates, modifies or moves away from.
+fn written_paths(changes: &Changes) -> impl Iterator<Item = PathBuf> + '_ {
+    let songs = (changes.song_operations.iter())
+        .flat_map(|o| std::iter::once(o.song.path.clone()).chain(o.new_path.clone()));
+    let files =
+        (changes.file_operations.iter()).flat_map(|o| [o.old_path.clone(), o.new_path.clone()]);
+    let quarantines = (changes.quarantines.iter())
+        .flat_map(|q| [q.path.clone(), q.new_path.clone(), q.sidecar_path()]);
+    songs
+        .chain(files)
+        .chain(quarantines)
+        .chain(changes.dir_creations.iter().map(|d| d.path.clone()))
+        .chain(changes.playlist_updates.iter().map(|u| u.path.clone()))
+        .chain(changes.playlist_creations.iter().map(|c| c.path.clone()))
+}
+
+fn export_index(index: &MusicIndex, path: &Path) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    match path.extension() {
+        Some(e) if e.eq_ignore_ascii_case("csv") => index.export_csv(&mut writer)?,
+        _ => index.export_json(&mut writer)?,
+    }
+    writer.flush()
+}
+
+/// Reads paths separated by newlines from a file, or from stdin if the path is `-`.
+fn read_file_list(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let list = match path.as_os_str() == "-" {
+        true => std::io::read_to_string(std::io::stdin())?,
+        false => std::fs::read_to_string(path)?,
+    };
+    let files = list.lines().filter(|l| !l.is_empty()).map(PathBuf::from).collect();
+    Ok(files)
+}
+
+fn display_indexing(index: &mut MusicIndex, files: Option<Vec<PathBuf>>, args: &Args) {
+    let verbose = args.verbosity >= 2;
+    let dump_tags = args.verbosity >= 3;
+    print_title_verbose(verbose, titles().indexing);
+
+    let eta = match &files {
+        Some(files) => Some(Eta::new(files.len())),
+        None => args.precount.then(|| Eta::new(index.count_files())),
+    };
+    let bar =
+        progress::phase_bar(format_title(titles().indexing), eta.as_ref().map(Eta::total), verbose);
+
+    let mut i = 1;
+    let mut f = |event: IndexEvent| {
+        let p = match event {
+            IndexEvent::FileIndexed(p) => p,
+            IndexEvent::DirScanned(_) => return,
+            IndexEvent::Warning { path, reason } => {
+                bar.suspend(|| {
+                    println!(
+                        "{} {}: {}",
+                        labels().warning.highlight(),
+                        strip_dir(path, &args.music_dir).highlight(),
+                        reason.highlight()
+                    )
+                });
+                return;
+            }
+        };
+        if verbose {
+            let counter = match &eta {
+                Some(eta) => eta.progress(i).to_string(),
+                None => i.to_string(),
+            };
+            println!("{} {}", counter.number(), strip_dir(p, &args.music_dir).highlight());
+            let ext = p.extension().unwrap_or_default();
+            if dump_tags && (is_song_extension(ext) || is_music_video_extension(ext)) {
+                println!("{}", format!("{:#?}", Metadata::read_from(p)).secondary());
+            }
+        } else {
+            bar.inc(1);
+            bar.set_path(strip_dir(p, &args.music_dir));
+        }
+        i += 1;
+    };
+    match files {
+        Some(files) => index.read_files(files, &mut f),
+        None => index.read(&mut f),
+    }
+    bar.finish_and_clear();
+    if !verbose {
+        let skipped = match index.skipped.len() {
+            0 => String::new(),
+            n => format!(", {} {}", n.to_string().number(), "left alone by marker tag".secondary()),
+        };
+        print_verbose!(
+            verbose,
+            titles().indexing,
+            "{} {}{}",
+            (i - 1).to_string().number(),
+            "files indexed".success(),
+            skipped
+        );
+    }
+    let unsupported = index.unsupported_by_count();
+    if !unsupported.is_empty() {
+        let counts: Vec<String> = (unsupported.iter())
+            .map(|(ext, n)| match ext.is_empty() {
+                true => format!("{n} without extension"),
+                false => format!("{n} .{ext}"),
+            })
+            .collect();
+        println!(
+            "{} skipped {} {}",
+            labels().warning.highlight(),
+            counts.join(", ").highlight(),
+            "— unsupported".secondary()
+        );
+    }
+    if !index.foreign_dirs.is_empty() {
+        let dirs: Vec<String> =
+            (index.foreign_dirs.iter()).map(|d| strip_dir(d, &args.music_dir)).collect();
+        println!(
+            "{} skipped {} {}",
+            labels().warning.highlight(),
+            dirs.join(", ").highlight(),
+            "— managed by another tool".secondary()
+        );
+    }
+    println!();
+}
+
+fn display_checking(
+    checks: &mut Checks,
+    answers: &mut Answers,
+    report: &mut Option<CheckReport>,
+    args: &Args,
+) {
+    let verbose = args.verbosity >= 2;
+    print_title_verbose(verbose, titles().checking);
+
+    if !args.keep_embedded_artworks {
+        print_verbose!(verbose, titles().checking, "{}", "embedded artworks".highlight());
+        checks.remove_embedded_artworks(&args.keep_artwork);
+    }
+
+    print_verbose!(verbose, titles().checking, "{}", "file permissions".highlight());
+    checks.check_file_permissions();
+    for (mount, songs) in checks.skipped_mounts.iter() {
+        let reason = match mount.read_only {
+            true => "read-only".to_owned(),
+            false => format!("{} has no unix permissions", mount.fs_type),
+        };
+        println!(
+            "{} not fixing the permissions of {} {} on {}, {reason}",
+            "notice".highlight(),
+            songs.to_string().number(),
+            if *songs == 1 { "song" } else { "songs" },
+            mount.path.display(),
+        );
+    }
+
+    print_verbose!(verbose, titles().checking, "{}", "genres".highlight());
+    checks.check_genres(&args.config.genres);
+
+    if args.fill_release_tags {
+        print_verbose!(verbose, titles().checking, "{}", "filled tags".highlight());
+        checks.fill_release_tags();
+    }
+
+    print_verbose!(verbose, titles().checking, "{}", "whitespace".highlight());
+    checks.check_whitespace(&mut |a, r| {
+        if verbose {
+            let (artists, release) = (format!("{:?}", a.names.join(", ")), format!("{:?}", r.name));
+            println!("  {} - {}", artists.highlight(), release.highlight());
+        }
+    });
+
+    print_verbose!(verbose, titles().checking, "{}", "inconsistent artists".highlight());
+    checks.check_inconsitent_release_artists(&mut |a, b| {
+        let (names_a, names_b) = (a.names.join(", "), b.names.join(", "));
+        if args.config.distinct_artists.iter().any(|d| d.matches(&names_a, &names_b)) {
+            return Value::Unchanged;
+        }
+        let key = answers::inconsistent_artists_key(a, b);
+        if let Some(answer) = answers.get(&key) {
+            match answers::parse_artists_answer(answer, a, b) {
+                Some(value) => return value,
+                None => {
+                    println!("{} invalid answer '{answer}' to {key}", labels().warning.highlight())
+                }
+            }
+        }
+
+        if let Some(report) = report {
+            let answers = ["first", "second", "none", "names:A;B"].map(str::to_owned).to_vec();
+            let details = Details::InconsistentArtists { a: a.names.to_vec(), b: b.names.to_vec() };
+            report.add(key, answers, details);
+            return Value::Unchanged;
+        }
+        if args.watch || args.quiet {
+            skip_unanswered(&key);
+            return Value::Unchanged;
+        }
+
+        let Some(value) = inconsitent_artists_dialog(a, b) else {
+            let r = config::append_distinct_artists(&args.config_path, &names_a, &names_b);
+            if let Err(e) = r {
+                println!("{} saving config: {}", labels().error.error(), e.to_string().error());
+            }
+            return Value::Unchanged;
+        };
+        answers.record(key, answers::format_artists_answer(&value, a, b));
+        value
+    });
+
+    print_verbose!(verbose, titles().checking, "{}", "invalid numbers".highlight());
+    checks.check_invalid_numbers(&mut |a, r, corrections| {
+        let key = answers::invalid_numbers_key(a, r);
+        if let Some(answer) = answers.get(&key) {
+            match answers::parse_fix_answer(answer) {
+                Some(fix) => return fix,
+                None => {
+                    println!("{} invalid answer '{answer}' to {key}", labels().warning.highlight())
+                }
+            }
+        }
+
+        if let Some(report) = report {
+            let details = Details::InvalidNumbers {
+                artists: a.names.to_vec(),
+                release: r.name.to_string(),
+                corrections: (corrections.iter())
+                    .map(|(s, c)| (s.path.clone(), c.clone()))
+                    .collect(),
+            };
+            report.add(key, fix_answers(), details);
+            return false;
+        }
+        if args.watch || args.quiet {
+            skip_unanswered(&key);
+            return false;
+        }
+
+        let Some(fix) = invalid_numbers_dialog(a, r, corrections) else {
+            answers.decide(key, answers::format_fix_answer(false));
+            return false;
+        };
+        answers.record(key, answers::format_fix_answer(fix));
+        fix
+    });
+
+    if let Some(policy) = args.normalize_totals {
+        print_verbose!(verbose, titles().checking, "{}", "inconsistent totals".highlight());
+        checks.check_inconsistent_totals(policy, &mut |a, r, t| {
+            let key = answers::inconsistent_totals_key(a, r, t);
+            if let Some(answer) = answers.get(&key) {
+                match answers::parse_total_answer(answer) {
+                    Some(value) => return value,
+                    None => {
+                        println!(
+                            "{} invalid answer '{answer}' to {key}",
+                            labels().warning.highlight()
+                        )
+                    }
+                }
+            }
+
+            if let Some(report) = report {
+                let (total, disc) = match t.kind {
+                    TotalKind::Tracks { disc } => ("tracks", disc),
+                    TotalKind::Discs => ("discs", None),
+                };
+                let totals = t.totals().into_iter().collect();
+                let answers = ["none".to_owned(), t.max.to_string(), t.count.to_string()].to_vec();
+                let details = Details::InconsistentTotals {
+                    artists: a.names.to_vec(),
+                    release: r.name.to_string(),
+                    total,
+                    disc,
+                    totals,
+                    max: t.max,
+                    count: t.count,
+                };
+                report.add(key, answers, details);
+                return Value::Unchanged;
+            }
+            if args.watch || args.quiet {
+                skip_unanswered(&key);
+                return Value::Unchanged;
+            }
+
+            let Some(value) = inconsistent_totals_dialog(a, r, t) else {
+                answers.decide(key, answers::format_total_answer(&Value::Unchanged));
+                return Value::Unchanged;
+            };
+            answers.record(key, answers::format_total_answer(&value));
+            value
+        });
+    }
+
+    print_verbose!(verbose, titles().checking, "{}", "various artists".highlight());
+    checks.check_various_artists(&mut |c| {
+        match args.various_artists {
+            CheckPolicy::Apply => return true,
+            CheckPolicy::Skip => return false,
+            CheckPolicy::Ask => (),
+        }
+        let key = answers::various_artists_key(c);
+        if let Some(answer) = answers.get(&key) {
+            match answers::parse_fix_answer(answer) {
+                Some(fix) => return fix,
+                None => {
+                    println!("{} invalid answer '{answer}' to {key}", labels().warning.highlight())
+                }
+            }
+        }
+
+        if let Some(report) = report {
+            let details = Details::VariousArtists {
+                release: c.release.to_owned(),
+                dir: c.dir.to_owned(),
+                num_artists: c.num_artists,
+            };
+            report.add(key, fix_answers(), details);
+            return false;
+        }
+        if args.watch || args.quiet {
+            skip_unanswered(&key);
+            return false;
+        }
+
+        let Some(fix) = various_artists_dialog(c, args) else {
+            answers.decide(key, answers::format_fix_answer(false));
+            return false;
+        };
+        answers.record(key, answers::format_fix_answer(fix));
+        fix
+    });
+
+    if args.vote_release_artists {
+        print_verbose!(verbose, titles().checking, "{}", "release artists vote".highlight());
+        checks.check_release_artists_vote(&mut |v| {
+            let key = answers::release_artists_vote_key(v);
+            if let Some(answer) = answers.get(&key) {
+                match answers::parse_names_answer(answer) {
+                    Some(value) => return value,
+                    None => {
+                        println!(
+                            "{} invalid answer '{answer}' to {key}",
+                            labels().warning.highlight()
+                        )
+                    }
+                }
+            }
+
+            if let Some(report) = report {
+                let votes: Vec<_> = v.votes.iter().map(|(n, c)| (n.to_vec(), *c)).collect();
+                let answers = names_answers(votes.iter().map(|(n, _)| n));
+                let details = Details::ReleaseArtistsVote {
+                    release: v.release.to_owned(),
+                    dir: v.dir.to_owned(),
+                    votes,
+                };
+                report.add(key, answers, details);
+                return Value::Unchanged;
+            }
+            if args.watch || args.quiet {
+                skip_unanswered(&key);
+                return Value::Unchanged;
+            }
+
+            let Some(value) = release_artists_vote_dialog(v, args) else {
+                answers.decide(key, answers::format_names_answer(&Value::Unchanged));
+                return Value::Unchanged;
+            };
+            answers.record(key, answers::format_names_answer(&value));
+            value
+        });
+    }
+
+    print_verbose!(verbose, titles().checking, "{}", "split releases".highlight());
+    checks.check_split_releases(&mut |s| {
+        let key = answers::split_release_key(s);
+        if let Some(answer) = answers.get(&key) {
+            match answers::parse_names_answer(answer) {
+                Some(value) => return value,
+                None => {
+                    println!("{} invalid answer '{answer}' to {key}", labels().warning.highlight())
+                }
+            }
+        }
+
+        if let Some(report) = report {
+            let parts: Vec<_> =
+                s.parts.iter().map(|(n, songs)| (n.to_vec(), songs.len())).collect();
+            let answers = names_answers(parts.iter().map(|(n, _)| n));
+            let details = Details::SplitRelease { release: s.release.to_owned(), parts };
+            report.add(key, answers, details);
+            return Value::Unchanged;
+        }
+        if args.watch || args.quiet {
+            skip_unanswered(&key);
+            return Value::Unchanged;
+        }
+
+        let Some(value) = split_release_dialog(s) else {
+            answers.decide(key, answers::format_names_answer(&Value::Unchanged));
+            return Value::Unchanged;
+        };
+        answers.record(key, answers::format_names_answer(&value));
+        value
+    });
+
+    print_verbose!(verbose, titles().checking, "{}", "release suffixes".highlight());
+    checks.check_release_suffixes(&mut |a, r, suffix| {
+        let key = answers::release_suffix_key(a, r);
+        if let Some(answer) = answers.get(&key) {
+            match answers::parse_suffix_answer(answer, suffix) {
+                Some(value) => return value,
+                None => {
+                    println!("{} invalid answer '{answer}' to {key}", labels().warning.highlight())
+                }
+            }
+        }
+
+        if let Some(report) = report {
+            let answers = ["standard", "strip", "none"].map(str::to_owned).to_vec();
+            let details = Details::ReleaseSuffix {
+                artists: a.names.to_vec(),
+                release: r.name.to_string(),
+                suffix: suffix.suffix.clone(),
+            };
+            report.add(key, answers, details);
+            return Value::Unchanged;
+        }
+        if args.watch || args.quiet {
+            skip_unanswered(&key);
+            return Value::Unchanged;
+        }
+
+        let Some(value) = release_suffix_dialog(a, r, suffix) else {
+            answers.decide(key, answers::format_suffix_answer(&Value::Unchanged, suffix));
+            return Value::Unchanged;
+        };
+        answers.record(key, answers::format_suffix_answer(&value, suffix));
+        value
+    });
+    if args.flatten_discs {
+        print_verbose!(verbose, titles().checking, "{}", "flatten discs".highlight());
+        checks.flatten_discs();
+    }
+    if args.bonus_tracks.is_some() {
+        print_verbose!(verbose, titles().checking, "{}", "bonus tracks".highlight());
+        checks.check_bonus_tracks(&mut |s| {
+            if verbose {
+                println!("  {}", strip_dir(&s.path, &args.music_dir).highlight());
+            }
+        });
+    }
+    #[cfg(feature = "replay-gain")]
+    if args.replay_gain {
+        display_replay_gain(checks, args);
+    }
+    #[cfg(feature = "scripting")]
+    if let Some(script) = &args.script {
+        print_verbose!(verbose, titles().checking, "{}", "script".highlight());
+        checks.apply_script(script);
+    }
+
+    //changes.check_inconsitent_albums(inconsitent_albums_dialog);
+
+    let stats = &checks.stats;
+    let counts = [
+        ("bonus tracks", stats.bonus_tracks, "moved"),
+        ("embedded artworks", stats.embedded_artworks, "updated"),
+        ("file permissions", stats.file_permissions, "fixed"),
+        ("filled tags", stats.filled_tags, "updated"),
+        ("flatten discs", stats.flattened_discs, "renumbered"),
+        ("genres", stats.genres, "updated"),
+        ("inconsistent artists", stats.inconsistent_artists, "updated"),
+        ("inconsistent totals", stats.inconsistent_totals, "updated"),
+        ("invalid numbers", stats.invalid_numbers, "updated"),
+        ("release artists vote", stats.release_artists_vote, "updated"),
+        ("release suffixes", stats.release_suffixes, "updated"),
+        ("split releases", stats.split_releases, "updated"),
+        ("various artists", stats.various_artists, "updated"),
+        ("whitespace", stats.whitespace, "updated"),
+        ("replay gain", stats.replay_gain, "tagged"),
+        ("script", stats.script, "updated"),
+    ];
+    let mut line = String::new();
+    for (name, count, updated) in counts.iter().filter(|(_, c, _)| c.issues > 0) {
+        if !line.is_empty() {
+            line.push_str(", ");
+        }
+        write!(
+            line,
+            "{}: {} {}",
+            name.highlight(),
+            count.issues.to_string().number(),
+            "found".success()
+        )
+        .ok();
+        if count.updates > 0 {
+            let songs = if count.updates == 1 { "song" } else { "songs" };
+            let updates = count.updates.to_string().number();
+            write!(line, " ({} {} {})", updates, songs.success(), updated.success()).ok();
+        }
+    }
+    if line.is_empty() {
+        line = "no issues".success().to_string();
+    }
+    if verbose {
+        println!("{line}");
+    } else {
+        print_verbose!(verbose, titles().checking, "{}", line);
+    }
+
+    println!();
+}
+
+#[cfg(feature = "replay-gain")]
+fn display_replay_gain(checks: &mut Checks, args: &Args) {
+    let verbose = args.verbosity >= 2;
+    print_verbose!(verbose, titles().checking, "{}", "replay gain".highlight());
+
+    let bar = progress::phase_bar(format_title(titles().checking), None, verbose);
+    checks.compute_replay_gain(&mut |song, error| {
+        let path = strip_dir(&song.path, &args.music_dir);
+        if let Some(e) = error {
+            bar.suspend(|| {
+                println!(
+                    "{} measuring {}: {}",
+                    labels().warning.highlight(),
+                    path.highlight(),
+                    e.to_string().highlight()
+                )
+            });
+        } else if verbose {
+            println!("{} {}", "measured".success(), path);
+        }
+        bar.inc(1);
+        bar.set_path(path);
+    });
+    bar.finish_and_clear();
+}
+
+/// Hashes the files of the index and lists the groups of identical files.
+fn display_duplicates(
+    duplicates: &mut Duplicates,
+    index: &MusicIndex,
+    args: &Args,
+    action: DedupAction,
+) {
+    let verbose = args.verbosity >= 2;
+    print_title_verbose(verbose, titles().duplicates);
+
+    let bar = progress::phase_bar(format_title(titles().duplicates), None, verbose);
+    duplicates.find(&RealFs, index, &mut |p| {
+        bar.inc(1);
+        bar.set_path(strip_dir(p, &args.music_dir));
+    });
+    bar.finish_and_clear();
+    if args.rename_only {
+        // the copies would take tags the original is missing with them
+        duplicates.groups.retain(|g| g.tag_update.is_none());
+    }
+
+    if duplicates.is_empty() {
+        print_verbose!(verbose, titles().duplicates, "{}\n", "no duplicates".success());
+        return;
+    }
+
+    let verbose = args.verbosity >= 1;
+    if verbose {
+        // the title was already printed before hashing
+        print_title_verbose(args.verbosity == 1, titles().duplicates);
+        let verb = match action {
+            _ if args.quarantine => "set aside",
+            DedupAction::Delete => "delete",
+            DedupAction::Hardlink => "link",
+        };
+        let mut listing = String::new();
+        for (i, g) in duplicates.groups.iter().enumerate() {
+            writeln!(
+                listing,
+                "{} keep {} {}",
+                (i + 1).to_string().number(),
+                strip_dir(&g.original, &args.music_dir).success(),
+                format!("({})", display::format_bytes(g.size)).secondary(),
+            )
+            .ok();
+            if let Some(u) = g.tag_update.as_ref().filter(|_| !args.quarantine) {
+                let tags = display::changed_tags(u).join(", ");
+                writeln!(listing, "  merge {} from the copies", tags.highlight()).ok();
+            }
+            for c in g.copies.iter() {
+                writeln!(listing, "  {verb} {}", strip_dir(c, &args.music_dir).error()).ok();
+            }
+        }
+        writeln!(listing).ok();
+        pager::print_paged(&listing, !args.watch);
+    }
+
+    let num_copies = duplicates.num_copies();
+    print_verbose!(
+        verbose,
+        titles().duplicates,
+        "{} {} using {}",
+        num_copies.to_string().number(),
+        if num_copies == 1 { "copy" } else { "copies" },
+        display::format_bytes(duplicates.wasted_size()).number()
+    );
+    println!();
+}
+
+/// Deletes or links the copies, deleted copies are removed from the index.
+fn display_dedup(
+    duplicates: &Duplicates,
+    index: &mut MusicIndex,
+    args: &Args,
+    action: DedupAction,
+    summary: &mut Summary,
+    failures: &mut Vec<Failure>,
+) {
+    if args.dry_run {
+        println!("{}", locale::get().notes.skip_deduplicating);
+        return;
+    }
+
+    let verbose = args.verbosity >= 2;
+    print_title_verbose(verbose, titles().duplicates);
+
+    let total = duplicates.num_copies() + duplicates.num_merges();
+    let bar = progress::phase_bar(format_title(titles().duplicates), Some(total), verbose);
+    let tenses = match action {
+        DedupAction::Delete => locale::get().delete_tenses,
+        DedupAction::Hardlink => locale::get().link_tenses,
+    };
+    let merge_tenses = locale::get().merge_tenses;
+    let (verb, verb_past) = (tenses.pres_prog, tenses.sim_past);
+    let mut deleted = HashSet::new();
+    let mut num_done = 0;
+    let mut num_errors = 0;
+    duplicates.execute(&RealFs, &args.exec, action, &mut |g, path, r| {
+        let stripped = strip_dir(path, &args.music_dir);
+        let merged = path == g.original;
+        let verb = if merged { merge_tenses.pres_prog } else { verb };
+        bar.inc(1);
+        match r {
+            Ok(()) if merged => {
+                if verbose {
+                    println!("{} {}", merge_tenses.sim_past, stripped.success());
+                }
+                let song = index.songs.iter_mut().find(|s| s.path == g.original);
+                if let (Some(song), Some(u)) = (song, &g.tag_update) {
+                    u.apply(song);
+                }
+                refresh_modified(index, path);
+                summary.retagged += 1;
+            }
+            Ok(()) => {
+                num_done += 1;
+                if verbose {
+                    println!("{verb_past} {}", stripped.error());
+                }
+                match action {
+                    DedupAction::Delete => {
+                        summary.files_deleted += 1;
+                        deleted.insert(path.to_owned());
+                    }
+                    DedupAction::Hardlink => refresh_modified(index, path),
+                }
+            }
+            Err(e) => {
+                bar.suspend(|| {
+                    print_verbose!(
+                        false,
+                        titles().duplicates,
+                        "{} {verb} {}: {}\n",
+                        labels().error.error(),
+                        stripped,
+                        e.to_string().error()
+                    )
+                });
+                failures.push(Failure { path: path.to_owned(), error: e.to_string() });
+                num_errors += 1;
+            }
+        }
+        bar.set_path(stripped);
+    });
+    bar.finish_and_clear();
+
+    remove_from_index(index, &deleted);
+
+    if !verbose {
+        print_verbose!(
+            verbose,
+            titles().duplicates,
+            "{} {} {verb_past}",
+            num_done.to_string().number(),
+            if num_done == 1 { "copy" } else { "copies" }
+        );
+        if num_errors > 0 {
+            print!(" {} {}", num_errors.to_string().number(), "failed".error());
+        }
+    }
+    println!();
+}
+
+/// Lists the files that wouldn't be organized and exits with [`EXIT_FATAL`] if there are any.
+fn display_strict(changes: &Changes, options: &ChangeOptions, args: &Args) {
+    let files = changes.unorganized_files(options);
+    if files.is_empty() {
+        return;
+    }
+
+    let mut listing = String::new();
+    for (i, f) in files.iter().enumerate() {
+        let num = (i + 1).to_string().number();
+        writeln!(listing, "{num} {}", strip_dir(f, &args.music_dir).error()).ok();
+    }
+    if !QUIET.load(Ordering::Relaxed) {
+        pager::print_paged(&listing, !args.watch);
+    }
+    let noun = if files.len() == 1 { "file" } else { "files" };
+    let msg = format!("{} {noun} would be moved to unknown or quarantine", files.len());
+    exit_fatal("strict", msg);
+}
+
+/// Updates the modification time of a file written after indexing, otherwise its operations
+/// would be skipped as modified since indexing.
+fn refresh_modified(index: &mut MusicIndex, path: &Path) {
+    if let Ok(modified) = std::fs::metadata(path).and_then(|m| m.modified()) {
+        index.modified.insert(path.to_owned(), modified);
+    }
+}
+
+/// Removes the paths from all file lists of the index.
+fn remove_from_index(index: &mut MusicIndex, paths: &HashSet<PathBuf>) {
+    index.songs.retain(|s| !paths.contains(&s.path));
+    index.videos.retain(|v| !paths.contains(&v.path));
+    index.audiobooks.retain(|a| !paths.contains(&a.path));
+    for list in [
+        &mut index.images,
+        &mut index.companions,
+        &mut index.extras,
+        &mut index.playlists,
+        &mut index.unknown,
+    ] {
+        list.retain(|p| !paths.contains(p));
+    }
+}
+
+/// Operations in `new_ops` are marked as new since the last applied plan.
+fn display_changes(
+    changes: &Changes,
+    index: &MusicIndex,
+    new_ops: Option<&HashSet<OperationId>>,
+    args: &Args,
+    dict: &Dict,
+) {
+    if changes.is_empty() {
+        let verbose = args.verbosity >= 2;
+        print_title_verbose(verbose, titles().changes);
+        print_verbose!(verbose, titles().changes, "{}\n", "nothing to do".success());
+        return;
+    }
+
+    let verbose = args.verbosity >= 1;
+    print_title_verbose(verbose, titles().changes);
+
+    if verbose {
+        let mut listing = String::new();
+        if !changes.dir_creations.is_empty() {
+            writeln!(listing, "{}", format_subtitle(subtitles().dirs)).ok();
+            for (i, d) in changes.dir_creations.iter().enumerate() {
+                writeln!(
+                    listing,
+                    "{} {}create {}",
+                    (i + 1).to_string().number(),
+                    display::new_marker(new_ops, &d.id()),
+                    format!("{}", d.path.display()).highlight()
+                )
+                .ok();
+            }
+            writeln!(listing).ok();
+        }
+        if !changes.song_operations.is_empty() {
+            writeln!(listing, "{}", format_subtitle(subtitles().songs)).ok();
+            let groups = changes.group_song_operations();
+            writeln!(
+                listing,
+                "{}",
+                display::GroupedSongOps {
+                    music_dir: &args.music_dir,
+                    output_dir: &args.output_dir,
+                    groups: &groups,
+                    op_type_str: dict.op_type.sim_pres,
+                    rename_str: dict.rename.sim_pres,
+                    verbosity: args.verbosity,
+                    new_ops,
+                }
+            )
+            .ok();
+        }
+        if !changes.file_operations.is_empty() {
+            writeln!(listing, "{}", format_subtitle(subtitles().others)).ok();
+            for (i, f) in changes.file_operations.iter().enumerate() {
+                writeln!(
+                    listing,
+                    "{} {}{}",
+                    (i + 1).to_string().number(),
+                    display::new_marker(new_ops, &f.id()),
+                    display::FileOp(
+                        &args.music_dir,
+                        &args.output_dir,
+                        &f.old_path,
+                        &f.new_path,
+                        dict.file_op_type(f).sim_pres,
+                        dict.rename.sim_pres,
+                    )
+                )
+                .ok();
+            }
+            writeln!(listing).ok();
+        }
+        if !changes.playlist_updates.is_empty() || !changes.playlist_creations.is_empty() {
+            writeln!(listing, "{}", format_subtitle(subtitles().playlists)).ok();
+            let mut i = 0;
+            for u in changes.playlist_updates.iter() {
+                i += 1;
+                let n = u.entries.len();
+                writeln!(
+                    listing,
+                    "{} {}update {} ({} {})",
+                    i.to_string().number(),
+                    display::new_marker(new_ops, &u.id()),
+                    strip_dir(&u.path, &args.music_dir).highlight(),
+                    n.to_string().number(),
+                    if n == 1 { "entry" } else { "entries" },
+                )
+                .ok();
+                if args.verbosity >= 2 {
+                    for e in u.entries.iter() {
+                        writeln!(listing, "  {}\n  {} {}", e.old, "to".success(), e.new).ok();
+                    }
+                }
+            }
+            for c in changes.playlist_creations.iter() {
+                i += 1;
+                let n = c.songs.len();
+                writeln!(
+                    listing,
+                    "{} {}create {} ({} {})",
+                    i.to_string().number(),
+                    display::new_marker(new_ops, &c.id()),
+                    strip_dir(&c.path, &args.output_dir).highlight(),
+                    n.to_string().number(),
+                    if n == 1 { "song" } else { "songs" },
+                )
+                .ok();
+            }
+            writeln!(listing).ok();
+        }
+        if !changes.quarantines.is_empty() {
+            writeln!(listing, "{}", format_subtitle(subtitles().quarantine)).ok();
+            for (i, q) in changes.quarantines.iter().enumerate() {
+                writeln!(
+                    listing,
+                    "{} {}{} {}\n  {} {}",
+                    (i + 1).to_string().number(),
+                    display::new_marker(new_ops, &q.id()),
+                    q.reason.to_string().error(),
+                    strip_dir(&q.path, &args.music_dir),
+                    "to".success(),
+                    strip_dir(&q.new_path, &args.output_dir).highlight(),
+                )
+                .ok();
+                if args.verbosity >= 2 {
+                    writeln!(listing, "  {}", q.message.secondary()).ok();
+                }
+            }
+            writeln!(listing).ok();
+        }
+        pager::print_paged(&listing, !args.watch);
+    }
+
+    let num_dir_creations = changes.dir_creations.len();
+    let num_file_ops =
+        changes.song_operations.len() + changes.file_operations.len() + changes.quarantines.len();
+    let transfer_size = changes.transfer_size(index);
+    print_verbose!(
+        verbose,
+        titles().changes,
+        "{} {} will be created{}{} {} will be {}{}{}",
+        num_dir_creations.to_string().number(),
+        if num_dir_creations == 1 { "dir" } else { "dirs" },
+        if verbose { '\n' } else { ' ' },
+        num_file_ops.to_string().number(),
+        if num_file_ops == 1 { "file" } else { "files" },
+        dict.op_type.sim_past,
+        match transfer_size {
+            0 => String::new(),
+            n => format!(" ({})", display::format_bytes(n).number()),
+        },
+        new_ops.map_or(String::new(), |ops| format!(
+            ", {} new since the last applied plan",
+            ops.len().to_string().number()
+        ))
+    );
+    let reasons = changes.reason_counts();
+    if verbose && !reasons.is_empty() {
+        let reasons: Vec<String> =
+            reasons.iter().map(|(r, n)| format!("{} {r}", n.to_string().number())).collect();
+        println!("{}", fill(locale::get().notes.songs_updated_because, &[&reasons.join(", ")]));
+    }
+
+    println!();
+}
+
+/// Asks for approval of the song operations of each release, and once for all other files.
+fn confirm_releases(changes: &mut Changes) {
+    let mut rejected = Vec::new();
+    let mut answer = None;
+    for artist in changes.group_song_operations() {
+        let artist_names = artist.names.join(", ");
+        for release in artist.releases {
+            let paths = release.song_operations.iter().map(|o| o.song.path.clone());
+            let approved = match answer {
+                Some(a) => a,
+                None => {
+                    let n = release.song_operations.len();
+                    let msg = fill(
+                        locale::get().dialogs.apply_release_changes.get(n),
+                        &[
+                            &n.to_string().number(),
+                            &artist_names.success(),
+                            &release.name.success(),
+                        ],
+                    );
+                    match release_input(&msg) {
+                        ReleaseAnswer::Yes => true,
+                        ReleaseAnswer::No => false,
+                        ReleaseAnswer::All => *answer.insert(true),
+                        ReleaseAnswer::Quit => *answer.insert(false),
+                    }
+                }
+            };
+            if !approved {
+                rejected.extend(paths);
+            }
+        }
+    }
+    changes.retain_song_operations(|o| !rejected.contains(&o.song.path));
+
+    if !changes.file_operations.is_empty() {
+        let n = changes.file_operations.len();
+        let msg = fill(locale::get().dialogs.apply_file_changes.get(n), &[&n.to_string().number()]);
+        if answer == Some(false) || !confirm_input(&msg) {
+            changes.retain_file_operations(|_| false);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReleaseAnswer {
+    Yes,
+    No,
+    /// Yes to this and all remaining releases.
+    All,
+    /// No to this and all remaining releases.
+    Quit,
+}
+
+fn release_input(str: &str) -> ReleaseAnswer {
+    loop {
+        print!("{str} [y/n/a/q]?");
+        let mut input = String::with_capacity(2);
+
+        let _ = std::io::stdout().flush().is_ok();
+
+        match std::io::stdin().read_line(&mut input) {
+            Ok(0) => {
+                // stdin was closed, nobody is able to answer
+                println!("{}", locale::get().dialogs.no_input.error());
+                std::process::exit(EXIT_FATAL);
+            }
+            Ok(_) => {
+                input.retain(|c| c != '\r' && c != '\n');
+                input.make_ascii_lowercase();
+
+                match input.as_str() {
+                    "y" => return ReleaseAnswer::Yes,
+                    "n" => return ReleaseAnswer::No,
+                    "a" => return ReleaseAnswer::All,
+                    "q" => return ReleaseAnswer::Quit,
+                    _ => println!("{}", locale::get().dialogs.invalid_input),
+                }
+            }
+            Err(e) => println!("{}:\n {e}", labels().error),
+        }
+    }
+}
+
+struct WritingProgress<'a> {
+    args: &'a Args,
+    dict: &'a Dict,
+    summary: &'a mut Summary,
+    failures: &'a mut Vec<Failure>,
+    verbose: bool,
+    bar: ProgressBar,
+    eta: Eta,
+    done: usize,
+    dir_creation_idx: usize,
+    file_operation_idx: usize,
+}
+
+impl Progress for WritingProgress<'_> {
+    fn bytes_copied(&mut self, path: &Path, copied: u64, total: u64) {
+        if !self.verbose && total >= LARGE_FILE_SIZE {
+            self.bar.set_message(format!(
+                "{} {} {}/{}",
+                self.dict.op_type.pres_prog,
+                strip_dir(path, &self.args.music_dir).highlight(),
+                display::format_bytes(copied).number(),
+                display::format_bytes(total).number()
+            ));
+        }
+    }
+
+    fn item_processed(&mut self, item: Item) {
+        let (args, dict) = (self.args, self.dict);
+        self.summary.record(item);
+        self.done += 1;
+        self.bar.inc(1);
+        self.bar.set_path(match item {
+            Item::DirCreation(d) => d.path.display().to_string(),
+            _ => strip_dir(item.path(), &args.music_dir),
+        });
+        let progress = self.eta.progress(self.done).to_string().number();
+        match item {
+            Item::DirCreation(d) => {
+                print_verbose!(
+                    self.verbose,
+                    titles().writing,
+                    "{} created dir {}",
+                    progress,
+                    d.path.display()
+                );
+                self.dir_creation_idx += 1;
+            }
+            Item::SongOperation(o) => {
+                let display_obj = display::SongOp(
+                    &args.music_dir,
+                    &args.output_dir,
+                    o,
+                    dict.op_type.sim_past,
+                    dict.rename.sim_past,
+                    args.verbosity,
+                );
+                print_verbose!(self.verbose, titles().writing, "{} {}", progress, display_obj);
+                self.file_operation_idx += 1;
+                match &o.new_path {
+                    Some(new) => {
+                        info!(old = %o.song.path.display(), new = %new.display(), "organized")
+                    }
+                    None => info!(path = %o.song.path.display(), "retagged"),
+                }
+                print_new_path(args, o.new_path.as_ref().unwrap_or(&o.song.path));
+                if let Some(new_path) = &o.new_path {
+                    self.bar
+                        .suspend(|| run_file_hooks(args, &o.song.path, new_path, Some(&o.song)));
+                }
+            }
+            Item::FileOperation(f) => {
+                let display_obj = display::FileOp(
+                    &args.music_dir,
+                    &args.output_dir,
+                    &f.old_path,
+                    &f.new_path,
+                    dict.file_op_type(f).sim_past,
+                    dict.rename.sim_past,
+                );
+                print_verbose!(self.verbose, titles().writing, "{} {}", progress, display_obj);
+                self.file_operation_idx += 1;
+                info!(old = %f.old_path.display(), new = %f.new_path.display(), "organized");
+                print_new_path(args, &f.new_path);
+                self.bar.suspend(|| run_file_hooks(args, &f.old_path, &f.new_path, None));
+            }
+            Item::PlaylistUpdate(u) => {
+                print_verbose!(
+                    self.verbose,
+                    titles().writing,
+                    "{} updated playlist {}",
+                    progress,
+                    strip_dir(&u.path, &args.music_dir)
+                );
+            }
+            Item::PlaylistCreation(c) => {
+                print_verbose!(
+                    self.verbose,
+                    titles().writing,
+                    "{} created playlist {}",
+                    progress,
+                    strip_dir(&c.path, &args.output_dir)
+                );
+                print_new_path(args, &c.path);
+            }
+            Item::Quarantine(q) => {
+                print_verbose!(
+                    self.verbose,
+                    titles().writing,
+                    "{} set aside {} {}",
+                    progress,
+                    strip_dir(&q.path, &args.music_dir),
+                    format!("({})", q.reason).error()
+                );
+                self.file_operation_idx += 1;
+            }
+            Item::FileDeletion(_) | Item::DirDeletion(_) => (),
+        }
+    }
+
+    fn item_failed(&mut self, item: Item, error: &dyn std::error::Error) {
+        self.done += 1;
+        self.bar.inc(1);
+        let bar = self.bar.clone();
+        bar.suspend(|| self.print_failure(item, error));
+        self.failures.push(Failure { path: item.path().to_owned(), error: error.to_string() });
+    }
+
+    fn item_skipped(&mut self, item: Item, change: &ChangedOnDisk) {
+        self.done += 1;
+        self.bar.inc(1);
+        self.file_operation_idx += 1;
+        let progress = self.eta.progress(self.done).to_string().number();
+        self.bar.suspend(|| {
+            print_verbose!(
+                false,
+                titles().writing,
+                "{} skipped {}: {}\n",
+                progress,
+                strip_dir(item.path(), &self.args.music_dir),
+                change.to_string().secondary()
+            )
+        });
+    }
+}
+
+impl WritingProgress<'_> {
+    fn print_failure(&mut self, item: Item, error: &dyn std::error::Error) {
+        let (args, dict) = (self.args, self.dict);
+        match item {
+            Item::DirCreation(d) => {
+                print_verbose!(
+                    false,
+                    titles().writing,
+                    "{} {} creating dir {}: {}\n",
+                    self.dir_creation_idx.to_string().number(),
+                    labels().error.error(),
+                    d.path.display(),
+                    error.to_string().error()
+                );
+                self.dir_creation_idx += 1;
+            }
+            Item::SongOperation(o) => {
+                println!(
+                    "{} {} {}:\n{}",
+                    self.file_operation_idx.to_string().number(),
+                    labels().error.error(),
+                    display::SongOp(
+                        &args.music_dir,
+                        &args.output_dir,
+                        o,
+                        dict.op_type.pres_prog,
+                        dict.rename.pres_prog,
+                        VERBOSE
+                    ),
+                    error.to_string().error(),
+                );
+                self.file_operation_idx += 1;
+            }
+            Item::FileOperation(f) => {
+                print!(
+                    "{} {} {}:\n{}",
+                    self.file_operation_idx.to_string().number(),
+                    labels().error.error(),
+                    display::FileOp(
+                        &args.music_dir,
+                        &args.output_dir,
+                        &f.old_path,
+                        &f.new_path,
+                        dict.op_type.pres_prog,
+                        dict.rename.pres_prog,
+                    ),
+                    error.to_string().error(),
+                );
+                self.file_operation_idx += 1;
+            }
+            Item::PlaylistUpdate(u) => {
+                println!(
+                    "{} updating playlist {}: {}",
+                    labels().error.error(),
+                    strip_dir(&u.path, &args.music_dir),
+                    error.to_string().error()
+                );
+            }
+            Item::PlaylistCreation(c) => {
+                println!(
+                    "{} creating playlist {}: {}",
+                    labels().error.error(),
+                    strip_dir(&c.path, &args.output_dir),
+                    error.to_string().error()
+                );
+            }
+            Item::Quarantine(q) => {
+                println!(
+                    "{} {} setting aside {}: {}",
+                    self.file_operation_idx.to_string().number(),
+                    labels().error.error(),
+                    strip_dir(&q.path, &args.music_dir),
+                    error.to_string().error()
+                );
+                self.file_operation_idx += 1;
+            }
+            Item::FileDeletion(_) | Item::DirDeletion(_) => (),
+        }
+    }
+}
+
+/// Warns about files that would be overwritten, found by simulating the changes.
+fn display_collisions(index: &MusicIndex, changes: &Changes, args: &Args) {
+    let simulation = changes.simulate(&VirtualTree::from_index(index), args.exec.op_type);
+    for c in simulation.collisions.iter() {
+        println!(
+            "{} {} would overwrite {}",
+            labels().warning.highlight(),
+            c.by.display(),
+            c.overwritten.display()
+        );
+    }
+}
+
+fn display_writing(
+    changes: &Changes,
+    args: &Args,
+    dict: &Dict,
+    summary: &mut Summary,
+    failures: &mut Vec<Failure>,
+) {
+    if args.dry_run {
+        println!("{}", locale::get().notes.skip_writing);
+        return;
+    }
+
+    let verbose = args.verbosity >= 2;
+    print_title_verbose(verbose, titles().writing);
+
+    let total = changes.dir_creations.len()
+        + changes.song_operations.len()
+        + changes.file_operations.len()
+        + changes.playlist_updates.len()
+        + changes.playlist_creations.len()
+        + changes.quarantines.len();
+    let mut progress = WritingProgress {
+        args,
+        dict,
+        summary,
+        failures,
+        verbose,
+        bar: progress::phase_bar(format_title(titles().writing), Some(total), verbose),
+        eta: Eta::new(total),
+        done: 0,
+        dir_creation_idx: 1,
+        file_operation_idx: 1,
+    };
+    changes.execute(&RealFs, &args.exec, &mut progress);
+    progress.bar.finish_and_clear();
+
+    if !verbose {
+        let num_dir_creations = progress.dir_creation_idx - 1;
+        let num_file_ops = progress.file_operation_idx - 1;
+        print_verbose!(
+            verbose,
+            titles().writing,
+            "{} {} {} {} {}",
+            num_dir_creations.to_string().number(),
+            if num_dir_creations == 1 { "dir created" } else { "dirs created" }.success(),
+            num_file_ops.to_string().number(),
+            if num_file_ops == 1 { "file" } else { "files" }.success(),
+            dict.op_type.sim_past.success()
+        );
+    }
+
+    println!();
+}
+
+fn display_hooks(args: &Args, when: HookEvent) {
+    if args.dry_run {
+        return;
+    }
+    let mut hooks = hooks::enabled(&args.config.hooks, when).peekable();
+    if hooks.peek().is_none() {
+        return;
+    }
+
+    print_title(titles().hooks);
+    let env = [
+        ("MO_MUSIC_DIR", args.music_dir.as_os_str()),
+        ("MO_OUTPUT_DIR", args.output_dir.as_os_str()),
+    ];
+    for h in hooks {
+        match h.run(&env) {
+            Ok(()) => println!("{} {}", h.name.highlight(), "done".success()),
+            Err(e) => {
+                println!("{} {}: {}", h.name.highlight(), labels().error.error(), e.error());
+                if h.abort_on_failure {
+                    std::process::exit(EXIT_FATAL);
+                }
+            }
+        }
+    }
+    println!();
+}
+
+/// Writes the raw path followed by the separator, so paths containing newlines can be used with
+/// `--print0`.
+fn print_new_path(args: &Args, path: &Path) {
+    let Some(separator) = args.print_new_paths else { return };
+    let mut stdout = std::io::stdout().lock();
+    let _ = stdout.write_all(path.as_os_str().as_encoded_bytes());
+    let _ = stdout.write_all(&[separator]);
+}
+
+/// Runs the per file hooks, only failures are printed.
+fn run_file_hooks(args: &Args, old_path: &Path, new_path: &Path, song: Option<&Song>) {
+    if args.dry_run {
+        return;
+    }
+    let mut env = vec![
+        ("MO_MUSIC_DIR", args.music_dir.as_os_str()),
+        ("MO_OUTPUT_DIR", args.output_dir.as_os_str()),
+        ("MO_OLD_PATH", old_path.as_os_str()),
+        ("MO_NEW_PATH", new_path.as_os_str()),
+    ];
+    let artists = song.map(|s| s.release_artists.join(", "));
+    if let (Some(s), Some(artists)) = (song, &artists) {
+        env.push(("MO_ARTIST", artists.as_ref()));
+        env.push(("MO_RELEASE", s.release.as_ref().as_ref()));
+        env.push(("MO_TITLE", s.title.as_ref()));
+    }
+
+    for h in hooks::enabled(&args.config.hooks, HookEvent::PerFile) {
+        if let Err(e) = h.run(&env) {
+            println!(
+                "{} {} {}: {}",
+                h.name.highlight(),
+                labels().error.error(),
+                strip_dir(new_path, &args.output_dir),
+                e.error()
+            );
+            if h.abort_on_failure {
+                std::process::exit(EXIT_FATAL);
+            }
+        }
+    }
+}
+
+fn display_cleanup(cleanup: &mut Cleanup, args: &Args) {
+    let verbose = args.verbosity >= 2;
+    print_title_verbose(verbose, titles().cleanup);
+
+    let bar = progress::phase_bar(format_title(titles().cleanup), None, verbose);
+    let mut i = 1;
+    cleanup.check(&RealFs, &mut |p| {
+        if verbose {
+            println!("{} {}", i.to_string().number(), strip_dir(p, &args.music_dir).highlight());
+        } else {
+            bar.inc(1);
+            bar.set_path(strip_dir(p, &args.music_dir));
+        }
+
+        i += 1;
+    });
+    bar.finish_and_clear();
+
+    if !verbose {
+        print_verbose!(
+            verbose,
+            titles().cleanup,
+            "{} {}",
+            (i - 1).to_string().number(),
+            "dirs checked".success()
+        );
+    }
+
+    println!();
+}
+
+fn display_deletions(cleanup: &Cleanup, args: &Args) {
+    if cleanup.is_empty() {
+        let verbose = args.verbosity >= 2;
+        print_title_verbose(verbose, titles().deletions);
+        print_verbose!(verbose, titles().deletions, "{}\n", "nothing to cleanup".success());
+    } else {
+        let verbose = args.verbosity >= 1;
+        print_title_verbose(verbose, titles().deletions);
+
+        if verbose {
+            let mut listing = String::new();
+            if !cleanup.file_deletions.is_empty() {
+                writeln!(listing, "{}", format_subtitle(subtitles().files)).ok();
+
+                for (i, d) in cleanup.file_deletions.iter().enumerate() {
+                    writeln!(
+                        listing,
+                        "{} delete {}",
+                        (i + 1).to_string().number(),
+                        strip_dir(&d.path, &args.music_dir).error(),
+                    )
+                    .ok();
+                }
+                writeln!(listing).ok();
+            }
+
+            writeln!(listing, "{}", format_subtitle(subtitles().dirs)).ok();
+
+            for (i, d) in cleanup.dir_deletions.iter().enumerate() {
+                writeln!(
+                    listing,
+                    "{} delete {}",
+                    (i + 1).to_string().number(),
+                    strip_dir(&d.path, &args.music_dir).error(),
+                )
+                .ok();
+            }
+            writeln!(listing).ok();
+            pager::print_paged(&listing, !args.watch);
+        }
+
+        let num_dir_deletions = cleanup.dir_deletions.len();
+        let num_file_deletions = cleanup.file_deletions.len();
+        print_verbose!(
+            verbose,
+            titles().deletions,
+            "{} {} and {} {} will be deleted",
+            num_dir_deletions.to_string().number(),
+            if num_dir_deletions == 1 { "dir" } else { "dirs" },
+            num_file_deletions.to_string().number(),
+            if num_file_deletions == 1 { "junk file" } else { "junk files" }
+        );
+
+        println!();
+    }
+}
+
+struct CleaningProgress<'a> {
+    args: &'a Args,
+    summary: &'a mut Summary,
+    failures: &'a mut Vec<Failure>,
+    verbose: bool,
+    bar: ProgressBar,
+    i: usize,
+    num_errors: usize,
+}
+
+impl Progress for CleaningProgress<'_> {
+    fn item_processed(&mut self, item: Item) {
+        self.summary.record(item);
+        let path = strip_dir(item.path(), &self.args.music_dir);
+        if self.verbose {
+            println!("{} deleted {}", self.i.to_string().number(), path.error());
+        } else {
+            self.bar.inc(1);
+            self.bar.set_path(path);
+        }
+        self.i += 1;
+    }
+
+    fn item_failed(&mut self, item: Item, error: &dyn std::error::Error) {
+        self.bar.inc(1);
+        self.bar.suspend(|| {
+            print_verbose!(
+                false,
+                titles().cleaning,
+                "{} {} deleting {}: {}\n",
+                self.i.to_string().number(),
+                labels().error.error(),
+                strip_dir(item.path(), &self.args.music_dir),
+                error.to_string().error()
+            )
+        });
+        self.failures.push(Failure { path: item.path().to_owned(), error: error.to_string() });
+        self.num_errors += 1;
+        self.i += 1;
+    }
+}
+
+fn display_cleaning(
+    cleanup: &Cleanup,
+    args: &Args,
+    summary: &mut Summary,
+    failures: &mut Vec<Failure>,
+) {
+    if args.dry_run {
+        println!("{}", locale::get().notes.skip_cleaning_up);
+        return;
+    }
+
+    let verbose = args.verbosity >= 2;
+    print_title_verbose(verbose, titles().cleaning);
+
+    let total = cleanup.file_deletions.len() + cleanup.dir_deletions.len();
+    let bar = progress::phase_bar(format_title(titles().cleaning), Some(total), verbose);
+    let mut progress =
+        CleaningProgress { args, summary, failures, verbose, bar, i: 1, num_errors: 0 };
+    cleanup.execute(&RealFs, &args.exec, &mut progress);
+    progress.bar.finish_and_clear();
+
+    if !verbose {
+        let num_errors = progress.num_errors;
+        let num_deletions = progress.i - 1 - num_errors;
+        print_verbose!(
+            verbose,
+            titles().cleaning,
+            "{} {}",
+            num_deletions.to_string().number(),
+            if num_deletions == 1 { "entry deleted" } else { "entries deleted" }.success()
+        );
+        if num_errors > 0 {
+            print!(" {} {}", num_errors.to_string().number(), "failed".error());
+        }
+    }
+    println!();
+}
+
+/// Checks the output dir against the sync dir and lists the planned copies and deletions.
+fn display_sync(mirror: &mut Mirror, args: &Args) {
+    let verbose = args.verbosity >= 2;
+    print_title_verbose(verbose, titles().sync);
+
+    let bar = progress::phase_bar(format_title(titles().sync), None, verbose);
+    mirror.check(&RealFs, &mut |p| {
+        bar.inc(1);
+        bar.set_path(strip_dir(p, &args.output_dir));
+    });
+    bar.finish_and_clear();
+
+    if mirror.is_empty() {
+        let excluded = format_excluded(mirror);
+        print_verbose!(verbose, titles().sync, "{}{excluded}\n", "nothing to sync".success());
+        return;
+    }
+
+    let verbose = args.verbosity >= 1;
+    if verbose {
+        let mut listing = String::new();
+        if !mirror.copies.is_empty() || !mirror.transcodes.is_empty() {
+            writeln!(listing, "{}", format_subtitle(subtitles().files)).ok();
+            let copies = mirror.copies.iter().map(|c| ("copy", c));
+            let transcodes = mirror.transcodes.iter().map(|c| ("transcode", c));
+            for (i, (action, c)) in copies.chain(transcodes).enumerate() {
+                writeln!(
+                    listing,
+                    "{} {action} {}",
+                    (i + 1).to_string().number(),
+                    strip_dir(&c.old_path, &args.output_dir).success(),
+                )
+                .ok();
+            }
+            writeln!(listing).ok();
+        }
+        if args.verbosity >= 2 && !mirror.excluded.is_empty() {
+            writeln!(listing, "{}", format_subtitle(subtitles().excluded)).ok();
+            for (i, p) in mirror.excluded.iter().enumerate() {
+                writeln!(
+                    listing,
+                    "{} skip {}",
+                    (i + 1).to_string().number(),
+                    strip_dir(p, &args.output_dir).secondary(),
+                )
+                .ok();
+            }
+            writeln!(listing).ok();
+        }
+        if !mirror.file_deletions.is_empty() || !mirror.dir_deletions.is_empty() {
+            writeln!(listing, "{}", format_subtitle(subtitles().delete)).ok();
+            let file_paths = mirror.file_deletions.iter().map(|d| &d.path);
+            let dir_paths = mirror.dir_deletions.iter().map(|d| &d.path);
+            for (i, p) in file_paths.chain(dir_paths).enumerate() {
+                writeln!(
+                    listing,
+                    "{} delete {}",
+                    (i + 1).to_string().number(),
+                    strip_dir(p, &mirror.dest_dir).error(),
+                )
+                .ok();
+            }
+            writeln!(listing).ok();
+        }
+        pager::print_paged(&listing, !args.watch);
+    }
+
+    let num_copies = mirror.copies.len() + mirror.transcodes.len();
+    let num_deletions = mirror.file_deletions.len() + mirror.dir_deletions.len();
+    let excluded = format_excluded(mirror);
+    print_verbose!(
+        verbose,
+        titles().sync,
+        "{} {} will be copied and {} {} deleted{excluded}",
+        num_copies.to_string().number(),
+        if num_copies == 1 { "file" } else { "files" },
+        num_deletions.to_string().number(),
+        if num_deletions == 1 { "entry" } else { "entries" }
+    );
+    println!();
+}
+
+/// Files that exceed the size limit, appended to the sync summary.
+fn format_excluded(mirror: &Mirror) -> String {
+    match mirror.excluded.len() {
+        0 => String::new(),
+        1 => format!(", {} file doesn't fit", "1".number()),
+        n => format!(", {} files don't fit", n.to_string().number()),
+    }
+}
+
+struct SyncingProgress<'a> {
+    args: &'a Args,
+    dest_dir: &'a Path,
+    failures: &'a mut Vec<Failure>,
+    verbose: bool,
+    bar: ProgressBar,
+    i: usize,
+    num_copies: usize,
+    num_errors: usize,
+}
+
+impl SyncingProgress<'_> {
+    fn describe(&self, item: Item) -> (&'static str, String) {
+        match item {
+            Item::DirCreation(d) => ("created dir", strip_dir(&d.path, self.dest_dir)),
+            Item::FileOperation(o) => {
+                let transcoded = o.old_path.extension() != o.new_path.extension();
+                let action = if transcoded { "transcoded" } else { "copied" };
+                (action, strip_dir(&o.old_path, &self.args.output_dir))
+            }
+            _ => ("deleted", strip_dir(item.path(), self.dest_dir)),
+        }
+    }
+}
+
+impl Progress for SyncingProgress<'_> {
+    fn item_processed(&mut self, item: Item) {
+        let (action, path) = self.describe(item);
+        if self.verbose {
+            println!("{} {action} {}", self.i.to_string().number(), path.highlight());
+        } else {
+            self.bar.inc(1);
+            self.bar.set_path(path);
+        }
+        self.num_copies += matches!(item, Item::FileOperation(_)) as usize;
+        self.i += 1;
+    }
+
+    fn item_failed(&mut self, item: Item, error: &dyn std::error::Error) {
+        let (action, path) = self.describe(item);
+        self.bar.inc(1);
+        self.bar.suspend(|| {
+            print_verbose!(
+                false,
+                titles().syncing,
+                "{} {} {action} {}: {}\n",
+                self.i.to_string().number(),
+                labels().error.error(),
+                path,
+                error.to_string().error()
+            )
+        });
+        self.failures.push(Failure { path: item.path().to_owned(), error: error.to_string() });
+        self.num_errors += 1;
+        self.i += 1;
+    }
+}
+
+fn display_syncing(mirror: &Mirror, args: &Args, failures: &mut Vec<Failure>) {
+    if args.dry_run {
+        println!("{}", locale::get().notes.skip_syncing);
+        return;
+    }
+
+    let verbose = args.verbosity >= 2;
+    print_title_verbose(verbose, titles().syncing);
+
+    let total = mirror.dir_creations.len()
+        + mirror.copies.len()
+        + mirror.transcodes.len()
+        + mirror.file_deletions.len()
+        + mirror.dir_deletions.len();
+    let bar = progress::phase_bar(format_title(titles().syncing), Some(total), verbose);
+    let mut progress = SyncingProgress {
+        args,
+        dest_dir: &mirror.dest_dir,
+        failures,
+        verbose,
+        bar,
+        i: 1,
+        num_copies: 0,
+        num_errors: 0,
+    };
+    mirror.execute(&RealFs, &args.exec, &mut progress);
+    progress.bar.finish_and_clear();
+
+    if !verbose {
+        let num_copies = progress.num_copies;
+        print_verbose!(
+            verbose,
+            titles().syncing,
+            "{} {}",
+            num_copies.to_string().number(),
+            if num_copies == 1 { "file copied" } else { "files copied" }.success()
+        );
+        if progress.num_errors > 0 {
+            print!(" {} {}", progress.num_errors.to_string().number(), "failed".error());
+        }
+    }
+    println!();
+}
+
+fn init_color(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                && progress::stdout_is_terminal()
+        }
+    };
+    colored::control::set_override(enabled);
+}
+
+/// Maps the verbosity to the default level of the log filter.
+fn init_logging(format: LogFormat, verbosity: u8, file: Option<&Path>) {
+    let level = match verbosity {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let filter = EnvFilter::builder().with_default_directive(level.into()).from_env_lossy();
+    let writer = match file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new().create(true).append(true).open(path);
+            let file = file.unwrap_or_else(|e| exit_fatal("opening log file", e));
+            BoxMakeWriter::new(Mutex::new(file))
+        }
+        None => BoxMakeWriter::new(std::io::stderr),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(file.is_none())
+        .with_writer(writer);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+fn failed_fast(args: &Args, failures: &[Failure]) -> bool {
+    args.exec.fail_fast && !failures.is_empty()
+}
+
+/// Indexes both dirs and lists the releases and songs missing in the other one, returns whether
+/// there are any.
+fn display_diff(a: &Path, b: &Path, args: &Args) -> bool {
+    let read = |dir: &Path| {
+        let mut index = MusicIndex::from(dir.to_owned());
+        index.follow_symlinks = args.follow_symlinks;
+        index.max_depth = args.max_depth;
+        index.include_hidden = args.include_hidden;
+        index.artist_separators = args.artist_separators.clone();
+        index.custom_fields = args.config.custom_fields.clone();
+        index.cancel = args.exec.cancel.clone();
+
+        let bar = progress::phase_bar(format_title(titles().indexing), None, false);
+        index.read(&mut |e| {
+            if let IndexEvent::FileIndexed(p) = e {
+                bar.inc(1);
+                bar.set_path(strip_dir(p, dir));
+            }
+        });
+        bar.finish_and_clear();
+        if index.cancel.is_cancelled() {
+            std::process::exit(EXIT_CANCELLED);
+        }
+        index
+    };
+    let (index_a, index_b) = (read(a), read(b));
+    let diff = LibraryDiff::generate(&index_a, &index_b);
+
+    print_title(titles().diff);
+    println!("{} {}", "a".number(), a.display());
+    println!("{} {}", "b".number(), b.display());
+    println!();
+    for (subtitle, releases) in
+        [(subtitles().only_a, &diff.only_in_a), (subtitles().only_b, &diff.only_in_b)]
+    {
+        if releases.is_empty() {
+            continue;
+        }
+        print_subtitle(subtitle);
+        for r in releases.iter() {
+            let n = r.songs.len();
+            let missing = match (r.complete, n) {
+                (true, 1) => "the only song".to_owned(),
+                (true, _) => format!("all {n} songs"),
+                (false, 1) => "1 song".to_owned(),
+                (false, _) => format!("{n} songs"),
+            };
+            println!(
+                "{} - {} ({missing})",
+                r.release_artists.join(", ").highlight(),
+                r.release.success()
+            );
+            if !r.complete || args.verbosity >= 2 {
+                for s in r.songs.iter() {
+                    let track = s.track_number.map(|n| format!("{n:02} ")).unwrap_or_default();
+                    println!("    {}{}", track.number(), s.title);
+                }
+            }
+        }
+        println!();
+    }
+
+    let num_a: usize = diff.only_in_a.iter().map(|r| r.songs.len()).sum();
+    let num_b: usize = diff.only_in_b.iter().map(|r| r.songs.len()).sum();
+    match diff.is_empty() {
+        true => println!("{}", "no differences".success()),
+        false => println!(
+            "{} {} only in a, {} only in b",
+            num_a.to_string().number(),
+            if num_a == 1 { "song" } else { "songs" },
+            num_b.to_string().number()
+        ),
+    }
+    !diff.is_empty()
+}
+
+fn display_stats(index: &MusicIndex) {
+    let stats = LibraryStats::generate(index, NUM_LARGEST_RELEASES);
+    let print_count = |count: usize, name: &str| println!("{} {name}", count.to_string().number());
+
+    print_title(titles().stats);
+    print_count(stats.artists, "artists");
+    print_count(stats.releases, "releases");
+    print_count(stats.songs, "songs");
+    print_count(stats.images, "images");
+    print_count(stats.unknown, "unknown files");
+    println!("{} total size", display::format_bytes(stats.total_size).number());
+    println!("{} total duration", display::format_duration(stats.total_duration).number());
+    println!();
+
+    print_subtitle(subtitles().formats);
+    for f in stats.formats.iter() {
+        println!(
+            "{} {} {} {}",
+            f.songs.to_string().number(),
+            f.name.success(),
+            display::format_bytes(f.size),
+            display::format_duration(f.duration)
+        );
+    }
+    println!();
+
+    print_subtitle(subtitles().genres);
+    for g in stats.genres.iter() {
+        println!("{} {}", g.songs.to_string().number(), g.name.success());
+    }
+    println!();
+
+    print_subtitle(subtitles().decades);
+    for d in stats.decades.iter() {
+        println!("{} {}", d.songs.to_string().number(), format!("{}s", d.decade).success());
+    }
+    println!();
+
+    print_subtitle(subtitles().missing);
+    print_count(stats.missing_artwork, "songs without artwork");
+    print_count(stats.missing_year, "songs without year");
+    print_count(stats.missing_genre, "songs without genre");
+    println!();
+
+    print_subtitle(subtitles().largest);
+    for r in stats.largest_releases.iter() {
+        println!(
+            "{} {} - {} ({} songs)",
+            display::format_bytes(r.size).number(),
+            r.release_artists.join(", ").highlight(),
+            r.release.success(),
+            r.songs
+        );
+    }
+}
+
+fn display_health(index: &MusicIndex, args: &Args, format: ReportFormat) {
+    // only the song paths are needed to tell which ones conform to the layout
+    let options = ChangeOptions {
+        tag_only: false,
+        update_playlists: false,
+        release_playlists: false,
+        artist_playlists: false,
+        quarantine: false,
+        ..change_options(args)
+    };
+    let changes = Changes::generate(&RealFs, Checks::from(index), &options);
+    let report = HealthReport::generate(index, &changes, NUM_WORST_RELEASES);
+
+    if format == ReportFormat::Json {
+        let mut stdout = std::io::stdout().lock();
+        let res = serde_json::to_writer_pretty(&mut stdout, &report)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writeln!(stdout));
+        if let Err(e) = res {
+            println!("{} writing health report: {}", labels().error.error(), e.to_string().error());
+        }
+        return;
+    }
+
+    print_title(titles().health);
+    println!(
+        "{} score of {} songs",
+        format!("{:.0}%", report.score).number(),
+        report.songs.to_string().number()
+    );
+    println!();
+    for (name, score) in [
+        ("with artwork", &report.artwork),
+        ("with year", &report.year),
+        ("with genre", &report.genre),
+        ("with correct permissions", &report.permissions),
+        ("at their layout path", &report.paths),
+    ] {
+        println!(
+            "{} {name} ({} of {} songs)",
+            format!("{:.0}%", score.percentage).number(),
+            score.passed,
+            score.total
+        );
+        for r in score.worst.iter() {
+            println!(
+                "    {} - {} ({} of {} songs)",
+                r.release_artists.join(", ").highlight(),
+                r.release.success(),
+                r.failed.to_string().number(),
+                r.songs
+            );
+        }
+    }
+}
+
+fn display_incomplete(index: &MusicIndex, path: Option<&Path>) {
+    let report = IncompleteReport::generate(index);
+
+    if let Some(path) = path {
+        let res = std::fs::File::create(path).and_then(|file| {
+            let mut writer = std::io::BufWriter::new(file);
+            match path.extension() {
+                Some(e) if e.eq_ignore_ascii_case("csv") => report.export_csv(&mut writer)?,
+                _ => report.export_json(&mut writer)?,
+            }
+            writer.flush()
+        });
+        match res {
+            Ok(()) => println!(
+                "{} incomplete releases written to {}",
+                report.releases.len().to_string().number(),
+                path.display()
+            ),
+            Err(e) => {
+                println!(
+                    "{} writing incomplete report: {}",
+                    labels().error.error(),
+                    e.to_string().error()
+                )
+            }
         }
+        return;
     }
-}
-
-fn display_indexing(index: &mut MusicIndex, args: &Args) {
-    let verbose = args.verbosity >= 2;
-    print_title_verbose(verbose, TITLE_INDEXING);
 
-    let mut i = 1;
-    index.read(&mut |p| {
-        print_verbose!(
-            verbose,
-            TITLE_INDEXING,
-            "{} {}",
-            i.to_string().blue(),
-            strip_dir(p, &args.music_dir).yellow()
-        );
-        i += 1;
-    });
-    if !verbose {
-        print_verbose!(
-            verbose,
-            TITLE_INDEXING,
-            "{} {}",
-            (i - 1).to_string().blue(),
-            "files indexed".green()
+    print_title(titles().incomplete);
+    if report.is_empty() {
+        println!("no releases are missing tracks");
+        return;
+    }
+    for r in report.releases.iter() {
+        let disc = r.disc_number.map(|n| format!(" (disc {n})")).unwrap_or_default();
+        let missing = r.missing.iter().map(u16::to_string).collect::<Vec<_>>().join(", ");
+        println!(
+            "{} - {}{disc} {} of {} tracks, missing {}",
+            r.release_artists.join(", ").highlight(),
+            r.release.success(),
+            r.present.len().to_string().number(),
+            r.total_tracks.to_string().number(),
+            missing.error()
         );
     }
-    println!();
 }
 
-fn display_checking(checks: &mut Checks, args: &Args) {
-    let verbose = args.verbosity >= 2;
-    print_title_verbose(verbose, TITLE_CHECKING);
+fn load_external_updates(args: &Args) -> Option<Vec<ExternalUpdate>> {
+    args.updates_from.as_deref().map(|path| {
+        let updates = std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|s| {
+            serde_json::from_str::<Vec<ExternalUpdate>>(&s).map_err(|e| e.to_string())
+        });
+        updates.unwrap_or_else(|e| exit_fatal("loading tag updates", e))
+    })
+}
 
-    if !args.keep_embedded_artworks {
-        print_verbose!(verbose, TITLE_CHECKING, "{}", "embedded artworks".yellow());
-        checks.remove_embedded_artworks();
+/// Excludes the songs of `--no-retag`, or all of them with `--rename-only`.
+fn exclude_from_retagging<'a>(checks: &mut Checks<'a>, index: &'a MusicIndex, args: &Args) {
+    checks.exclude_from_retagging(&args.no_retag);
+    if args.rename_only {
+        checks.retag_excluded = index.songs.iter().map(|s| s.path.as_path()).collect();
     }
+}
 
-    print_verbose!(verbose, TITLE_CHECKING, "{}", "file permissions".yellow());
-    checks.check_file_permissions();
+/// Drops the updates that modify file contents with `--rename-only`, e.g. updates of a script
+/// aren't bound to the excluded songs.
+fn drop_content_updates(changes: &mut Changes, args: &Args) {
+    if args.rename_only {
+        for o in changes.song_operations.iter_mut() {
+            o.tag_update = None;
+            o.mode_update = None;
+        }
+        changes.song_operations.retain(|o| o.new_path.is_some());
+    }
+}
 
-    print_verbose!(verbose, TITLE_CHECKING, "{}", "inconsistent artists".yellow());
-    checks.check_inconsitent_release_artists(inconsitent_artists_dialog);
-    //changes.check_inconsitent_albums(inconsitent_albums_dialog);
-    //changes.check_inconsitent_total_tracks(inconsitent_total_tracks_dialog);
-    //changes.check_inconsitent_total_discs(inconsitent_total_discs_dialog);
+fn change_options(args: &Args) -> ChangeOptions {
+    ChangeOptions {
+        output_dir: args.output_dir.clone(),
+        dir_mode: args.dir_mode,
+        dir_owner: args.dir_owner,
+        layout: args.layout.clone(),
+        video_layout: args.video_layout.clone(),
+        audiobook_layout: args.audiobook_layout.clone(),
+        tag_only: args.tag_only,
+        update_playlists: !args.keep_playlists && args.exec.op_type == FileOpType::Move,
+        release_playlists: args.release_playlists,
+        artist_playlists: args.artist_playlists,
+        artist_separators: args.artist_separators.clone(),
+        image_policy: args.image_policy,
+        cover_name: args.cover_name,
+        extra_policies: args.extra_policies,
+        non_latin_artists: args.non_latin_artists.clone(),
+        collaborations: args.collaborations,
+        quarantine: args.quarantine,
+        bonus_dir: (args.bonus_tracks == Some(BonusPolicy::Extras))
+            .then(|| DEFAULT_BONUS_DIR.to_owned()),
+        lowercase_extensions: args.lowercase_extensions,
+        #[cfg(feature = "scripting")]
+        script: args.script.clone(),
+    }
+}
 
-    if !verbose {
-        print_verbose!(verbose, TITLE_CHECKING, "{}", "done".green());
+/// Stops after a phase that was cancelled, the failures and a summary of what was done up to
+/// that point are still reported. Operations are never interrupted halfway, so no partially
+/// written files are left behind.
+fn exit_if_cancelled(args: &Args, dict: &Dict, summary: &Summary, failures: &[Failure]) {
+    if !args.exec.cancel.is_cancelled() {
+        return;
     }
 
-    println!();
+    println!("\n{}", labels().cancelled.error());
+    if !failures.is_empty() {
+        display_failures(failures, args);
+    }
+    print_summary(dict, summary, failures);
+    std::process::exit(EXIT_CANCELLED);
 }
 
-fn display_changes(changes: &Changes, args: &Args, dict: &Dict) {
-    if changes.is_empty() {
-        let verbose = args.verbosity >= 2;
-        print_title_verbose(verbose, TITLE_CHANGES);
-        print_verbose!(verbose, TITLE_CHANGES, "{}\n", "nothing to do".green());
+/// The summary table, or a single line in quiet mode.
+fn print_summary(dict: &Dict, summary: &Summary, failures: &[Failure]) {
+    if !QUIET.load(Ordering::Relaxed) {
+        print_title(titles().summary);
+        summary.print(dict.op_type.sim_past, failures.len());
         return;
     }
+    let line = summary.line(dict.op_type.sim_past, failures.len());
+    match STDOUT_RESERVED.load(Ordering::Relaxed) {
+        true => std::eprintln!("{line}"),
+        false => std::println!("{line}"),
+    }
+}
 
-    let verbose = args.verbosity >= 1;
-    print_title_verbose(verbose, TITLE_CHANGES);
+/// Prints the error, even in quiet mode, and exits with [`EXIT_FATAL`].
+fn exit_fatal(context: &str, error: impl std::fmt::Display) -> ! {
+    let msg = format!("{} {context}: {}", labels().error.error(), error.to_string().error());
+    match QUIET.load(Ordering::Relaxed) {
+        true => std::eprintln!("{msg}"),
+        false => println!("{msg}"),
+    }
+    std::process::exit(EXIT_FATAL);
+}
 
-    if verbose {
-        if !changes.dir_creations.is_empty() {
-            print_subtitle(SUBTITLE_DIRS);
-            for (i, d) in changes.dir_creations.iter().enumerate() {
-                println!(
-                    "{} create {}",
-                    (i + 1).to_string().blue(),
-                    format!("{}", d.path.display()).yellow()
-                );
-            }
-            println!();
+fn display_failures(failures: &[Failure], args: &Args) {
+    print_title(titles().failures);
+    for (i, f) in failures.iter().enumerate() {
+        let path = match f.path.strip_prefix(&args.music_dir) {
+            Ok(p) => p.display(),
+            Err(_) => f.path.display(),
+        };
+        println!(
+            "{} {}: {}",
+            (i + 1).to_string().number(),
+            path.to_string().highlight(),
+            f.error.error()
+        );
+    }
+    println!();
+    println!(
+        "{} {} failed",
+        failures.len().to_string().number(),
+        if failures.len() == 1 { "operation" } else { "operations" }
+    );
+}
+
+/// Infers the missing tags of unknown songs from their paths. Songs whose tags can't be inferred
+/// completely or that aren't confirmed stay unknown.
+fn display_inference(
+    index: &mut MusicIndex,
+    args: &Args,
+    policy: InferPolicy,
+) -> Vec<SongOperation> {
+    let ask = policy == InferPolicy::Ask && !args.assume_yes;
+    let unknown: Vec<PathBuf> = index.unknown_songs().cloned().collect();
+
+    let mut ops = Vec::new();
+    for path in unknown {
+        let metadata = Metadata::read_from(&path);
+        let mut tags = MissingTags::from_metadata(&metadata);
+        let relative = path.strip_prefix(&args.music_dir).unwrap_or(&path);
+        tags.guess_from_path(relative);
+        if !tags.is_complete() {
+            continue;
         }
-        if !changes.song_operations.is_empty() {
-            print_subtitle(SUBTITLE_SONGS);
-            for (i, o) in changes.song_operations.iter().enumerate() {
-                println!(
-                    "{} {}",
-                    (i + 1).to_string().blue(),
-                    display::SongOp(
-                        &args.music_dir,
-                        &args.output_dir,
-                        o,
-                        dict.op_type.sim_pres,
-                        dict.rename.sim_pres,
-                        args.verbosity,
-                    )
-                );
+
+        if ask {
+            println!("\n{}", relative.display().to_string().highlight());
+            println!("release artists: {}", tags.release_artists.join(", ").success());
+            if !tags.artists.is_empty() {
+                println!("artists:         {}", tags.artists.join(", ").success());
             }
-            println!();
-        }
-        if !changes.file_operations.is_empty() {
-            print_subtitle(SUBTITLE_OTHERS);
-            for (i, f) in changes.file_operations.iter().enumerate() {
-                println!(
-                    "{} {}",
-                    (i + 1).to_string().blue(),
-                    display::FileOp(
-                        &args.music_dir,
-                        &args.output_dir,
-                        f.old_path,
-                        &f.new_path,
-                        dict.op_type.sim_pres,
-                        dict.rename.sim_pres,
-                    )
-                );
+            println!("release:         {}", tags.release.success());
+            println!("title:           {}", tags.title.success());
+            if let Some(n) = tags.track_number {
+                println!("track number:    {}", n.to_string().success());
+            }
+            if !confirm_input(locale::get().dialogs.apply_inferred_tags) {
+                continue;
             }
-            println!();
         }
+        ops.extend(index.resolve_unknown(&path, metadata, tags));
     }
 
-    let num_dir_creations = changes.dir_creations.len();
-    let num_file_ops = changes.song_operations.len() + changes.file_operations.len();
+    let num_inferred = ops.len();
+    let verbose = args.verbosity >= 2;
     print_verbose!(
         verbose,
-        TITLE_CHANGES,
-        "{} {} will be created{}{} {} will be {}",
-        num_dir_creations.to_string().blue(),
-        if num_dir_creations == 1 { "dir" } else { "dirs" },
-        if verbose { '\n' } else { ' ' },
-        num_file_ops.to_string().blue(),
-        if num_file_ops == 1 { "file" } else { "files" },
-        dict.op_type.sim_past
+        titles().checking,
+        "{} {}\n",
+        num_inferred.to_string().number(),
+        if num_inferred == 1 {
+            "song tagged from its path"
+        } else {
+            "songs tagged from their paths"
+        }
     );
-
-    println!();
+    ops
 }
 
-fn display_writing(changes: &Changes, args: &Args, dict: &Dict) {
-    if args.dry_run {
-        println!("skip writing dryrun...");
-        return;
+/// Offers to supply the missing tags of unknown songs, which are organized like other songs
+/// afterwards.
+fn display_unknown(index: &mut MusicIndex, args: &Args) -> Vec<SongOperation> {
+    let unknown: Vec<PathBuf> = index.unknown_songs().cloned().collect();
+    let dialogs = &locale::get().dialogs;
+    let msg =
+        fill(dialogs.unknown_songs.get(unknown.len()), &[&unknown.len().to_string().number()]);
+    if options_input(&msg, &[dialogs.move_to_unknown, dialogs.edit_tags_of_each_song]) == 0 {
+        println!();
+        return Vec::new();
     }
 
-    let verbose = args.verbosity >= 2;
-    print_title_verbose(verbose, TITLE_WRITING);
+    let mut ops = Vec::new();
+    for path in unknown {
+        let metadata = Metadata::read_from(&path);
+        let mut tags = MissingTags::from_metadata(&metadata);
+        tags.guess_from_path(path.strip_prefix(&args.music_dir).unwrap_or(&path));
 
-    let mut dir_creation_idx = 1;
-    changes.execute_dir_creations(&mut |d, r| {
-        match r {
-            Ok(_) => {
-                print_verbose!(
-                    verbose,
-                    TITLE_WRITING,
-                    "{} created dir {}",
-                    dir_creation_idx.to_string().blue(),
-                    d.path.display()
-                );
-            }
-            Err(e) => {
-                print_verbose!(
-                    false,
-                    TITLE_WRITING,
-                    "{} {} creating dir {}: {}\n",
-                    dir_creation_idx.to_string().blue(),
-                    "error".red(),
-                    d.path.display(),
-                    e.to_string().red()
-                );
-            }
+        if let Some(tags) = unknown_song_dialog(&path, args, tags) {
+            ops.extend(index.resolve_unknown(&path, metadata, tags));
         }
+    }
+    println!();
+    ops
+}
 
-        dir_creation_idx += 1;
-    });
+fn unknown_song_dialog(path: &Path, args: &Args, mut tags: MissingTags) -> Option<MissingTags> {
+    let dialogs = &locale::get().dialogs;
+    println!("\n{}", strip_dir(path, &args.music_dir).highlight());
+    if options_input("", &[dialogs.move_to_unknown, dialogs.edit_tags]) == 0 {
+        return None;
+    }
 
-    let mut file_operation_idx = 1;
-    changes.execute_song_operations(args.op_type, &mut |o, r| {
-        match r {
-            Ok(_) => {
-                let display_obj = display::SongOp(
-                    &args.music_dir,
-                    &args.output_dir,
-                    o,
-                    dict.op_type.sim_past,
-                    dict.rename.sim_past,
-                    args.verbosity,
-                );
-                print_verbose!(
-                    verbose,
-                    TITLE_WRITING,
-                    "{} {}",
-                    file_operation_idx.to_string().blue(),
-                    display_obj
-                );
-            }
-            Err(e) => {
-                println!(
-                    "{} {} {}:\n{}",
-                    file_operation_idx.to_string().blue(),
-                    "error".red(),
-                    display::SongOp(
-                        &args.music_dir,
-                        &args.output_dir,
-                        o,
-                        dict.op_type.pres_prog,
-                        dict.rename.pres_prog,
-                        VERBOSE
-                    ),
-                    e.to_string().red(),
-                );
-            }
+    loop {
+        let names = default_input(dialogs.release_artists_input, &tags.release_artists.join(";"));
+        tags.release_artists = split_names(&names);
+        let names = default_input(dialogs.artists_input, &tags.artists.join(";"));
+        tags.artists = split_names(&names);
+        tags.release = default_input(dialogs.release_input, &tags.release);
+        tags.title = default_input(dialogs.title_input, &tags.title);
+        let track = tags.track_number.map(|n| n.to_string()).unwrap_or_default();
+        tags.track_number = default_input(dialogs.track_number_input, &track).parse().ok();
+
+        if !tags.is_complete() {
+            println!("{}", dialogs.required_tags.error());
+            continue;
         }
-
-        file_operation_idx += 1;
-    });
-
-    changes.execute_file_operations(args.op_type, &mut |f, r| {
-        match r {
-            Ok(_) => {
-                let display_obj = display::FileOp(
-                    &args.music_dir,
-                    &args.output_dir,
-                    f.old_path,
-                    &f.new_path,
-                    dict.op_type.sim_past,
-                    dict.rename.sim_past,
-                );
-                print_verbose!(
-                    verbose,
-                    TITLE_WRITING,
-                    "{} {}",
-                    file_operation_idx.to_string().blue(),
-                    display_obj
-                );
-            }
-            Err(e) => {
-                print!(
-                    "{} {} {}:\n{}",
-                    file_operation_idx.to_string().blue(),
-                    "error".red(),
-                    display::FileOp(
-                        &args.music_dir,
-                        &args.output_dir,
-                        f.old_path,
-                        &f.new_path,
-                        dict.op_type.pres_prog,
-                        dict.rename.pres_prog,
-                    ),
-                    e.to_string().red(),
-                );
-            }
+        match options_input("", &[dialogs.ok, dialogs.edit_again, dialogs.move_to_unknown]) {
+            0 => return Some(tags),
+            1 => continue,
+            _ => return None,
         }
+    }
+}
 
-        file_operation_idx += 1;
-    });
+fn split_names(names: &str) -> Vec<String> {
+    names.split(';').map(str::trim).filter(|n| !n.is_empty()).map(String::from).collect()
+}
 
-    if !verbose {
-        let num_dir_creations = dir_creation_idx - 1;
-        let num_file_ops = file_operation_idx - 1;
-        print_verbose!(
-            verbose,
-            TITLE_WRITING,
-            "{} {} {} {} {}",
-            num_dir_creations.to_string().blue(),
-            if num_dir_creations == 1 { "dir created" } else { "dirs created" }.green(),
-            num_file_ops.to_string().blue(),
-            if num_file_ops == 1 { "file" } else { "files" }.green(),
-            dict.op_type.sim_past.green()
-        );
+/// Returns the default if the input is empty.
+fn default_input(str: &str, default: &str) -> String {
+    let input = string_input(&format!("{str} [{}]:", default.success()));
+    match input.trim() {
+        "" => default.to_owned(),
+        i => i.to_owned(),
     }
-
-    println!();
 }
 
-fn display_cleanup(cleanup: &mut Cleanup, args: &Args) {
-    let verbose = args.verbosity >= 2;
-    print_title_verbose(verbose, TITLE_CLEANUP);
+/// Returns none if this dialog should never be shown again for the release, like all following
+/// dialogs.
+fn invalid_numbers_dialog(
+    a: &ReleaseArtists,
+    r: &Release,
+    corrections: &[(&Song, TagUpdate)],
+) -> Option<bool> {
+    fn format_pair(n: Option<u16>, total: Option<u16>) -> String {
+        let n = n.map_or("-".to_owned(), |n| n.to_string());
+        match total {
+            Some(t) => format!("{n}/{t}"),
+            None => n,
+        }
+    }
+    fn new_value(old: Option<u16>, value: Value<u16>) -> Option<u16> {
+        match value {
+            Value::Update(n) => Some(n),
+            Value::Remove => None,
+            Value::Unchanged => old,
+        }
+    }
 
-    let mut i = 1;
-    cleanup.check(&mut |p| {
-        print_verbose!(
-            verbose,
-            TITLE_CLEANUP,
-            "{} {}",
-            i.to_string().blue(),
-            strip_dir(p, &args.music_dir).yellow()
+    let dialogs = &locale::get().dialogs;
+    let artists = a.names.join(", ");
+    println!("\n{}", fill(dialogs.invalid_numbers, &[&artists.highlight(), &r.name.highlight()]));
+    for (s, c) in corrections {
+        println!("   {}:", s.title);
+        let disc = format_pair(s.disc_number, s.total_discs);
+        let new_disc = format_pair(
+            new_value(s.disc_number, c.disc_number),
+            new_value(s.total_discs, c.total_discs),
         );
-
-        i += 1;
-    });
-
-    if !verbose {
-        print_verbose!(
-            verbose,
-            TITLE_CLEANUP,
-            "{} {}",
-            (i - 1).to_string().blue(),
-            "dirs checked".green()
+        if disc != new_disc {
+            println!("      {}", fill(dialogs.disc_change, &[&disc.error(), &new_disc.success()]));
+        }
+        let track = format_pair(s.track_number, s.total_tracks);
+        let new_track = format_pair(
+            new_value(s.track_number, c.track_number),
+            new_value(s.total_tracks, c.total_tracks),
         );
+        if track != new_track {
+            let change = fill(dialogs.track_change, &[&track.error(), &new_track.success()]);
+            println!("      {change}");
+        }
     }
-
     println!();
+
+    match options_input(
+        "",
+        &[dialogs.dont_do_anything, dialogs.fix_numbers, dialogs.never_ask_again_release],
+    ) {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
 }
 
-fn display_deletions(cleanup: &Cleanup, args: &Args) {
-    if cleanup.is_empty() {
-        let verbose = args.verbosity >= 2;
-        print_title_verbose(verbose, TITLE_DELETIONS);
-        print_verbose!(verbose, TITLE_DELETIONS, "{}\n", "nothing to cleanup".green());
-    } else {
-        let verbose = args.verbosity >= 1;
-        print_title_verbose(verbose, TITLE_DELETIONS);
+fn various_artists_dialog(c: &Compilation, args: &Args) -> Option<bool> {
+    let dialogs = &locale::get().dialogs;
+    let dir = strip_dir(c.dir, &args.music_dir);
+    let num_artists = c.num_artists.to_string();
+    println!(
+        "\n{}",
+        fill(
+            dialogs.various_artists,
+            &[&c.release.highlight(), &dir.highlight(), &num_artists.number()]
+        )
+    );
+    for s in c.songs.iter() {
+        println!("   {} - {}", s.artists.join(", ").secondary(), s.title);
+    }
+    println!();
 
-        if verbose {
-            print_subtitle(SUBTITLE_DIRS);
+    let set = fill(dialogs.set_release_artist, &[&VARIOUS_ARTISTS.success()]);
+    match options_input("", &[dialogs.dont_do_anything, &set, dialogs.never_ask_again_release]) {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
+}
 
-            for (i, d) in cleanup.dir_deletions.iter().enumerate() {
-                println!(
-                    "{} delete {}",
-                    (i + 1).to_string().blue(),
-                    strip_dir(&d.path, &args.music_dir).red(),
-                );
-            }
-            println!();
-        }
+fn release_artists_vote_dialog(v: &ArtistVote, args: &Args) -> Option<Value<Vec<String>>> {
+    let dialogs = &locale::get().dialogs;
+    let dir = strip_dir(v.dir, &args.music_dir);
+    println!(
+        "\n{}",
+        fill(dialogs.ambiguous_release_artists, &[&v.release.highlight(), &dir.highlight()])
+    );
+    for s in v.songs.iter() {
+        println!("   {} - {}", s.artists.join(", ").secondary(), s.title);
+    }
+    println!();
 
-        let num_dir_deletions = cleanup.dir_deletions.len();
-        print_verbose!(
-            verbose,
-            TITLE_DELETIONS,
-            "{} {} will be deleted",
-            num_dir_deletions.to_string().blue(),
-            if num_dir_deletions == 1 { "dir" } else { "dirs" }
-        );
+    let options: Vec<String> = (v.votes.iter())
+        .map(|(a, _)| fill(dialogs.set_release_artist, &[&a.join(", ").success()]))
+        .collect();
+    let options: Vec<&str> = [dialogs.dont_do_anything]
+        .into_iter()
+        .chain(options.iter().map(String::as_str))
+        .chain([dialogs.never_ask_again_release])
+        .collect();
+    match options_input("", &options) {
+        0 => Some(Value::Unchanged),
+        i if i <= v.votes.len() => Some(Value::Update(v.votes[i - 1].0.to_vec())),
+        _ => None,
+    }
+}
 
-        println!();
+fn inconsistent_totals_dialog(
+    a: &ReleaseArtists,
+    r: &Release,
+    t: &InconsistentTotals,
+) -> Option<Value<u16>> {
+    let dialogs = &locale::get().dialogs;
+    let artists = a.names.join(", ");
+    let msg = match t.kind {
+        TotalKind::Tracks { .. } => dialogs.inconsistent_total_tracks,
+        TotalKind::Discs => dialogs.inconsistent_total_discs,
+    };
+    println!("\n{}", fill(msg, &[&artists.highlight(), &r.name.highlight()]));
+    for s in t.songs.iter() {
+        let total = t.total(s).map_or("-".to_owned(), |t| t.to_string());
+        println!("   {}: {}", s.title, total.number());
+    }
+    println!();
+
+    let max = fill(dialogs.set_total, &[&t.max.to_string().success()]);
+    let count = fill(dialogs.set_total, &[&t.count.to_string().success()]);
+    match options_input(
+        "",
+        &[dialogs.dont_do_anything, &max, &count, dialogs.never_ask_again_release],
+    ) {
+        0 => Some(Value::Unchanged),
+        1 => Some(Value::Update(t.max)),
+        2 => Some(Value::Update(t.count)),
+        _ => None,
     }
 }
 
-fn display_cleaning(cleanup: &Cleanup, args: &Args) {
-    if args.dry_run {
-        println!("skip cleaning up dryrun...");
-    } else {
-        let verbose = args.verbosity >= 2;
-        print_title_verbose(verbose, TITLE_CLEANING);
+fn split_release_dialog(s: &SplitRelease) -> Option<Value<Vec<String>>> {
+    let dialogs = &locale::get().dialogs;
+    println!("\n{}", fill(dialogs.split_release, &[&s.release.highlight()]));
+    for (names, songs) in s.parts.iter() {
+        println!("   {}: {}", names.join(", "), songs.len().to_string().number());
+    }
+    println!();
 
-        let mut i = 1;
-        cleanup.excecute(&mut |p| {
-            print_verbose!(
-                verbose,
-                TITLE_CLEANING,
-                "{} deleted {}",
-                i.to_string().blue(),
-                strip_dir(p, &args.music_dir).red()
-            );
-            i += 1;
-        });
+    let options: Vec<String> = (s.parts.iter())
+        .map(|(n, _)| fill(dialogs.set_release_artist, &[&n.join(", ").success()]))
+        .collect();
+    let options: Vec<&str> = [dialogs.dont_do_anything]
+        .into_iter()
+        .chain(options.iter().map(String::as_str))
+        .chain([dialogs.never_ask_again_release])
+        .collect();
+    match options_input("", &options) {
+        0 => Some(Value::Unchanged),
+        i if i <= s.parts.len() => Some(Value::Update(s.parts[i - 1].0.to_vec())),
+        _ => None,
+    }
+}
 
-        if !verbose {
-            print_verbose!(
-                verbose,
-                TITLE_CLEANING,
-                "{} {}",
-                (i - 1).to_string().blue(),
-                if i == 1 { "dir deleted" } else { "dirs deleted" }.green()
-            );
-        }
-        println!();
+fn release_suffix_dialog(
+    a: &ReleaseArtists,
+    r: &Release,
+    suffix: &ReleaseSuffix,
+) -> Option<Value<String>> {
+    let dialogs = &locale::get().dialogs;
+    let artists = a.names.join(", ");
+    println!("\n{}", fill(dialogs.release_suffix, &[&artists.highlight(), &r.name.highlight()]));
+    println!();
+
+    let standard = fill(dialogs.rename_to, &[&suffix.standardized().success()]);
+    let strip = fill(dialogs.rename_to, &[&suffix.base.success()]);
+    match options_input(
+        "",
+        &[dialogs.dont_do_anything, &standard, &strip, dialogs.never_ask_again_release],
+    ) {
+        0 => Some(Value::Unchanged),
+        1 => Some(Value::Update(suffix.standardized())),
+        2 => Some(Value::Update(suffix.base.clone())),
+        _ => None,
     }
 }
 
-fn inconsitent_artists_dialog(a: &ReleaseArtists, b: &ReleaseArtists) -> Value<Vec<String>> {
+/// Returns none if the artists should never be suggested to be merged again.
+fn inconsitent_artists_dialog(
+    a: &ReleaseArtists,
+    b: &ReleaseArtists,
+) -> Option<Value<Vec<String>>> {
     fn print(artist: &ReleaseArtists) {
-        for n in artist.names {
-            println!(" {}", n.yellow().on_black());
+        for n in artist.names.iter() {
+            println!(" {}", n.highlight().on_background());
         }
         println!();
         for (i, al) in artist.releases.iter().enumerate() {
             if i == 10 {
-                println!("   {}", "...".green());
+                println!("   {}", "...".success());
                 break;
             }
             println!("   {}:", al.name);
             for (j, s) in al.songs.iter().enumerate() {
                 if i >= 4 || j == 3 {
-                    println!("      {}", "...".green());
+                    println!("      {}", "...".success());
                     break;
                 } else {
                     println!(
@@ -516,55 +3234,70 @@ fn inconsitent_artists_dialog(a: &ReleaseArtists, b: &ReleaseArtists) -> Value<V
             }
         }
     }
-    println!("\nThese two artists are named similarly:");
+    let dialogs = &locale::get().dialogs;
+    println!("\n{}", dialogs.similar_artists);
     print(a);
     println!();
     print(b);
     println!();
+    match a.distinction(b) {
+        Some(ArtistDistinction::Genres) => {
+            println!("{}\n", dialogs.genres_differ.highlight())
+        }
+        Some(ArtistDistinction::Years) => {
+            println!("{}\n", dialogs.years_differ.highlight())
+        }
+        Some(ArtistDistinction::Ids) | None => (),
+    }
 
     let index = options_input(
         "",
         &[
-            "don't do anything",
-            "rename first to second",
-            "rename second to first",
-            "enter new name[s]",
+            dialogs.dont_do_anything,
+            dialogs.rename_first_to_second,
+            dialogs.rename_second_to_first,
+            dialogs.enter_new_names,
+            dialogs.never_ask_again,
         ],
     );
 
-    match index {
+    let value = match index {
         0 => Value::Unchanged,
         1 => {
-            println!("renaming first to second");
+            println!("{}", dialogs.renaming_first_to_second);
             Value::Update(b.names.to_vec())
         }
         2 => {
-            println!("renaming second to first");
+            println!("{}", dialogs.renaming_second_to_first);
             Value::Update(a.names.to_vec())
         }
+        4 => return None,
         3 => {
             let mut new_names = Vec::new();
             loop {
-                new_names.push(string_input("enter new name:"));
-                let mut msg = String::from("new name[s]:");
+                new_names.push(string_input(dialogs.enter_new_name));
+                let mut msg = String::from(dialogs.new_names);
                 for n in new_names.iter() {
-                    _ = write!(msg, " {}", n.green().on_black());
+                    _ = write!(msg, " {}", n.success().on_background());
                 }
 
-                let i = options_input(&msg, &["ok", "reenter name", "add another", "dismiss"]);
+                let options =
+                    [dialogs.ok, dialogs.reenter_name, dialogs.add_another, dialogs.dismiss];
+                let i = options_input(&msg, &options);
                 match i {
-                    0 => return Value::Update(new_names),
+                    0 => break Value::Update(new_names),
                     1 => {
                         new_names.pop();
                         continue;
                     }
                     2 => continue,
-                    _ => return Value::Unchanged,
+                    _ => break Value::Unchanged,
                 }
             }
         }
         _ => unreachable!(),
-    }
+    };
+    Some(value)
 }
 
 //fn inconsitent_albums_dialog(
@@ -574,7 +3307,7 @@ fn inconsitent_artists_dialog(a: &ReleaseArtists, b: &ReleaseArtists) -> Value<V
 //    b: &Release,
 //) -> Option<String> {
 //    fn print(index: &MusicIndex, album: &Release) {
-//        println!("   {}:", album.name.as_str().yellow());
+//        println!("   {}:", album.name.as_str().highlight());
 //        for s in album.songs.iter().map(|&si| &index.songs[si]) {
 //            println!(
 //                "      {:02} - {} - {}",
@@ -635,8 +3368,8 @@ fn inconsitent_artists_dialog(a: &ReleaseArtists, b: &ReleaseArtists) -> Value<V
 //) -> Option<u16> {
 //    let msg = format!(
 //        "{} - {} this album has different total tracks values:",
-//        artist.name.as_str().yellow(),
-//        album.name.as_str().yellow(),
+//        artist.name.as_str().highlight(),
+//        album.name.as_str().highlight(),
 //    );
 //    let mut options = vec!["don't do anything", "remove the value", "enter a new value"];
 //
@@ -644,8 +3377,8 @@ fn inconsitent_artists_dialog(a: &ReleaseArtists, b: &ReleaseArtists) -> Value<V
 //        .iter()
 //        .map(|(songs, tt)| {
 //            let mut tt_str = match tt {
-//                Some(n) => format!("{:02}:   ", n).yellow().to_string(),
-//                None => "none: ".yellow().to_string(),
+//                Some(n) => format!("{:02}:   ", n).highlight().to_string(),
+//                None => "none: ".highlight().to_string(),
 //            };
 //            let mut iter = songs.iter();
 //
@@ -702,8 +3435,8 @@ fn inconsitent_artists_dialog(a: &ReleaseArtists, b: &ReleaseArtists) -> Value<V
 //) -> Option<u16> {
 //    let msg = format!(
 //        "{} - {} this album has different total discs values:",
-//        artist.name.as_str().yellow(),
-//        album.name.as_str().yellow(),
+//        artist.name.as_str().highlight(),
+//        album.name.as_str().highlight(),
 //    );
 //    let mut options = vec!["don't do anything", "remove the value", "enter a new value"];
 //
@@ -711,8 +3444,8 @@ fn inconsitent_artists_dialog(a: &ReleaseArtists, b: &ReleaseArtists) -> Value<V
 //        .iter()
 //        .map(|(songs, tt)| {
 //            let mut tt_str = match tt {
-//                Some(n) => format!("{}:    ", n.to_string().yellow()),
-//                None => "none: ".yellow().to_string(),
+//                Some(n) => format!("{}:    ", n.to_string().highlight()),
+//                None => "none: ".highlight().to_string(),
 //            };
 //            let mut iter = songs.iter();
 //
@@ -772,7 +3505,7 @@ fn string_input(str: &str) -> String {
                 input.pop();
                 return input;
             }
-            Err(e) => println!("error:\n {}", e),
+            Err(e) => println!("{}:\n {e}", labels().error),
         }
     }
 }
@@ -798,17 +3531,22 @@ fn options_input(str: &str, options: &[&str]) -> usize {
         }
 
         match std::io::stdin().read_line(&mut input) {
+            Ok(0) => {
+                // stdin was closed, nobody is able to answer
+                println!("{}", locale::get().dialogs.no_input.error());
+                std::process::exit(EXIT_FATAL);
+            }
             Ok(_) => match input.trim_matches('\n').parse::<usize>() {
                 Ok(i) => {
                     if i < options.len() {
                         return i;
                     } else {
-                        println!("invalid input")
+                        println!("{}", locale::get().dialogs.invalid_input)
                     }
                 }
-                Err(_) => println!("invalid input"),
+                Err(_) => println!("{}", locale::get().dialogs.invalid_input),
             },
-            Err(e) => println!("error:\n {}", e),
+            Err(e) => println!("{}:\n {e}", labels().error),
         }
     }
 }
@@ -821,7 +3559,7 @@ fn confirm_input(str: &str) -> bool {
         let _ = std::io::stdout().flush().is_ok();
 
         if let Err(e) = std::io::stdin().read_line(&mut input) {
-            println!("error:\n {e}");
+            println!("{}:\n {e}", labels().error);
         } else {
             input.retain(|c| c != '\r' && c != '\n');
             input.make_ascii_lowercase();
@@ -831,13 +3569,13 @@ fn confirm_input(str: &str) -> bool {
             } else if input == "y" {
                 return true;
             } else {
-                println!("invalid input");
+                println!("{}", locale::get().dialogs.invalid_input);
             }
         }
     }
 }
 
 fn successfull_early_exit() {
-    println!("exiting...");
-    std::process::exit(0);
+    println!("{}", locale::get().dialogs.exiting);
+    std::process::exit(EXIT_SUCCESS);
 }
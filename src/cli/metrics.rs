@@ -0,0 +1,88 @@
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::summary::Summary;
+
+/// The metrics of all runs of this process, e.g. while watching.
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics::new());
+
+struct Metrics {
+    runs: u64,
+    organized: u64,
+    failures: u64,
+    unknown: u64,
+    last_run_duration: Duration,
+    last_run: Option<SystemTime>,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            runs: 0,
+            organized: 0,
+            failures: 0,
+            unknown: 0,
+            last_run_duration: Duration::ZERO,
+            last_run: None,
+        }
+    }
+
+    fn format(&self) -> String {
+        let mut s = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+            let _ = writeln!(s, "# HELP music_organizer_{name} {help}");
+            let _ = writeln!(s, "# TYPE music_organizer_{name} {kind}");
+            let _ = writeln!(s, "music_organizer_{name} {value}");
+        };
+        metric("runs_total", "counter", "Finished runs.", self.runs as f64);
+        metric(
+            "files_organized_total",
+            "counter",
+            "Files moved, renamed or retagged.",
+            self.organized as f64,
+        );
+        metric("errors_total", "counter", "Failed operations.", self.failures as f64);
+        metric(
+            "unknown_files",
+            "gauge",
+            "Files without the required tags in the last run.",
+            self.unknown as f64,
+        );
+        let duration = self.last_run_duration.as_secs_f64();
+        metric("last_run_duration_seconds", "gauge", "Duration of the last run.", duration);
+        let last_run = self.last_run.and_then(|t| t.duration_since(UNIX_EPOCH).ok());
+        let timestamp = last_run.map_or(0.0, |t| t.as_secs_f64());
+        metric(
+            "last_run_timestamp_seconds",
+            "gauge",
+            "End of the last run as a unix timestamp.",
+            timestamp,
+        );
+        s
+    }
+}
+
+/// Adds a finished run to the metrics and writes them to the file in the Prometheus text format,
+/// e.g. for the textfile collector of the node exporter. The file is replaced atomically, so the
+/// collector never reads a partially written file.
+pub fn record(
+    path: &Path,
+    summary: &Summary,
+    num_failures: usize,
+    duration: Duration,
+) -> std::io::Result<()> {
+    let mut metrics = METRICS.lock().unwrap();
+    metrics.runs += 1;
+    metrics.organized += summary.organized as u64;
+    metrics.failures += num_failures as u64;
+    metrics.unknown = summary.unknown as u64;
+    metrics.last_run_duration = duration;
+    metrics.last_run = Some(SystemTime::now());
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, metrics.format())?;
+    std::fs::rename(&tmp, path)
+}
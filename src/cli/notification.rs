@@ -0,0 +1,19 @@
+use notify_rust::Notification;
+
+use crate::summary::Summary;
+
+/// Sends a desktop notification like `231 files organized, 2 errors`.
+pub fn send(summary: &Summary, num_failures: usize) -> notify_rust::error::Result<()> {
+    let mut body = match summary.organized {
+        1 => "1 file organized".to_owned(),
+        n => format!("{n} files organized"),
+    };
+    match num_failures {
+        0 => (),
+        1 => body.push_str(", 1 error"),
+        n => body.push_str(&format!(", {n} errors")),
+    }
+
+    Notification::new().appname("music-organizer").summary("music-organizer").body(&body).show()?;
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::progress::stdout_is_terminal;
+
+const DEFAULT_PAGER: &str = "less -R";
+
+/// Prints the text, or pipes it through `$PAGER` (`less -R` by default) if stdout is a terminal
+/// and the text doesn't fit on the screen. An empty `$PAGER` disables paging, as does running
+/// non-interactively.
+pub fn print_paged(text: &str, interactive: bool) {
+    if !interactive || !stdout_is_terminal() || !exceeds_terminal_height(text) {
+        print!("{text}");
+        return;
+    }
+
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_owned());
+    if pager.trim().is_empty() || page(&pager, text).is_err() {
+        print!("{text}");
+    }
+}
+
+fn exceeds_terminal_height(text: &str) -> bool {
+    match console::Term::stdout().size_checked() {
+        Some((rows, _)) => text.lines().count() >= rows as usize,
+        None => false,
+    }
+}
+
+fn page(pager: &str, text: &str) -> std::io::Result<()> {
+    let mut child = Command::new("sh").arg("-c").arg(pager).stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // the pager might be quit before everything has been written
+        stdin.write_all(text.as_bytes()).ok();
+    }
+    child.wait()?;
+    Ok(())
+}
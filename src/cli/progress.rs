@@ -0,0 +1,185 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::theme::{self, palette_index};
+
+/// Formats the progress of a phase with a known total like `1234/8000 15% ~3 min left`.
+pub struct Eta {
+    total: usize,
+    start: Instant,
+}
+
+impl Eta {
+    pub fn new(total: usize) -> Self {
+        Self { total, start: Instant::now() }
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn progress(&self, done: usize) -> Progress<'_> {
+        Progress { eta: self, done }
+    }
+
+    fn remaining(&self, done: usize) -> Option<Duration> {
+        if done == 0 || done >= self.total {
+            return None;
+        }
+        let elapsed = self.start.elapsed();
+        Some(elapsed.mul_f64((self.total - done) as f64 / done as f64))
+    }
+}
+
+pub struct Progress<'a> {
+    eta: &'a Eta,
+    done: usize,
+}
+
+impl Display for Progress<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.eta.total;
+        let percent = (self.done.min(total) * 100).checked_div(total).unwrap_or(100);
+        write!(f, "{}/{} {percent}%", self.done, total)?;
+
+        if let Some(remaining) = self.eta.remaining(self.done) {
+            let secs = remaining.as_secs();
+            match secs {
+                0..=59 => write!(f, " ~{secs} s left")?,
+                60..=3599 => write!(f, " ~{} min left", secs / 60)?,
+                _ => write!(f, " ~{} h {} min left", secs / 3600, secs % 3600 / 60)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn stdout_is_terminal() -> bool {
+    static IS_TERMINAL: OnceLock<bool> = OnceLock::new();
+    *IS_TERMINAL.get_or_init(|| std::io::stdout().is_terminal())
+}
+
+/// The number of columns of the terminal, queried on each call so resizes are picked up.
+pub fn terminal_width() -> Option<usize> {
+    console::Term::stdout().size_checked().map(|(_, cols)| cols as usize)
+}
+
+/// The prefix, bar and stats of a phase bar take up about this many columns, the rest is left for
+/// the message.
+const BAR_COLUMNS: usize = 80;
+
+/// Paths in bar messages are at least this wide, even on narrow terminals.
+const MIN_PATH_COLUMNS: usize = 20;
+
+pub trait PathMessage {
+    /// Sets a path as the message, shortened to fit next to the bar, so the file name stays
+    /// visible instead of being cut off at the end.
+    fn set_path(&self, path: String);
+}
+
+impl PathMessage for ProgressBar {
+    fn set_path(&self, path: String) {
+        match terminal_width() {
+            Some(width) => {
+                let width = width.saturating_sub(BAR_COLUMNS).max(MIN_PATH_COLUMNS);
+                self.set_message(shorten_path(&path, width));
+            }
+            None => self.set_message(path),
+        }
+    }
+}
+
+/// Shortens the last line of the text to the terminal width, so it can be overwritten by erasing
+/// the line. A wrapped line can't be, since the cursor only returns to the start of its last row.
+/// Shortened lines lose their colors.
+pub fn fit_last_line(text: &str) -> Cow<'_, str> {
+    let Some(width) = terminal_width() else { return Cow::Borrowed(text) };
+    let (head, last) = text.split_at(text.rfind('\n').map_or(0, |i| i + 1));
+    // writing the last column wraps on some terminals
+    let width = width.saturating_sub(1);
+    if console::measure_text_width(last) <= width {
+        return Cow::Borrowed(text);
+    }
+    let last = truncate_middle(&console::strip_ansi_codes(last), width);
+    Cow::Owned(format!("{head}{last}"))
+}
+
+/// Replaces the middle of the text with `…`, so it is at most `width` characters wide.
+fn truncate_middle(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    if len <= width {
+        return text.to_owned();
+    }
+    let keep = width.saturating_sub(1);
+    let start: String = text.chars().take(keep - keep / 2).collect();
+    let end: String = text.chars().skip(len - keep / 2).collect();
+    format!("{start}…{end}")
+}
+
+/// Replaces the middle components of the path with `…` until it is at most `width` characters
+/// wide, e.g. `Artist/…/01 - Song.mp3`. If the first and last component alone are too wide, the
+/// middle of the text is replaced.
+fn shorten_path(path: &str, width: usize) -> String {
+    if path.chars().count() <= width {
+        return path.to_owned();
+    }
+    let parts: Vec<&str> = path.split(std::path::MAIN_SEPARATOR).collect();
+    let sep = std::path::MAIN_SEPARATOR_STR;
+    for removed in 1..parts.len().saturating_sub(1) {
+        let start = (parts.len() - removed) / 2;
+        let shortened = [&parts[..start], &["…"], &parts[start + removed..]].concat().join(sep);
+        if shortened.chars().count() <= width {
+            return shortened;
+        }
+    }
+    truncate_middle(path, width)
+}
+
+/// A progress bar for a phase with a known total, or a spinner otherwise. The bar is hidden if
+/// the output is verbose, since every item is printed on its own line, if stdout is not a
+/// terminal or reserved for the new paths, or in quiet mode.
+pub fn phase_bar(prefix: String, total: Option<usize>, verbose: bool) -> ProgressBar {
+    let reserved = crate::STDOUT_RESERVED.load(std::sync::atomic::Ordering::Relaxed);
+    let quiet = crate::QUIET.load(std::sync::atomic::Ordering::Relaxed);
+    if verbose || reserved || quiet || !stdout_is_terminal() {
+        return ProgressBar::hidden();
+    }
+
+    const STATS: &str = "{rate} ~{eta} left {wide_msg}";
+
+    let color = colored::control::SHOULD_COLORIZE.should_colorize();
+    let (bar, template) = match total {
+        Some(total) => {
+            let theme = theme::get();
+            let (fg, bg) = (palette_index(theme.number), palette_index(theme.background));
+            let bar = if color { format!("{{bar:30.{fg}/{bg}}}") } else { "{bar:30}".to_owned() };
+            (
+                ProgressBar::with_draw_target(Some(total as u64), ProgressDrawTarget::stdout()),
+                format!("{{prefix}} {bar} {{pos}}/{{len}} {{percent}}% {STATS}"),
+            )
+        }
+        None => {
+            let fg = palette_index(theme::get().number);
+            let spinner = if color { format!("{{spinner:.{fg}}}") } else { "{spinner}".to_owned() };
+            (
+                ProgressBar::with_draw_target(None, ProgressDrawTarget::stdout()),
+                format!("{{prefix}} {spinner} {{pos}} {{rate}} {{wide_msg}}"),
+            )
+        }
+    };
+    let style = ProgressStyle::with_template(&template).unwrap().with_key(
+        "rate",
+        |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+            write!(w, "{:.0}/s", state.per_sec()).ok();
+        },
+    );
+    bar.set_style(style);
+    bar.set_prefix(prefix);
+    bar.enable_steady_tick(Duration::from_millis(100));
+    bar
+}
@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+
+use music_organizer_core::{Mode, SongOperation, TagUpdate};
+use serde::Serialize;
+
+/// The findings of the checks of a dry run, written by `--check-report`. Questions that aren't
+/// answered yet are recorded with their key of the answers file instead of being asked, so an
+/// answers file can be prepared from the report.
+#[derive(Debug, Default, Serialize)]
+pub struct CheckReport {
+    pub questions: Vec<Finding>,
+    /// The songs the checks update without asking, e.g. their permissions or genres, and the
+    /// songs of answered questions.
+    pub updates: Vec<Update>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    /// The key of the question in the answers file.
+    pub key: String,
+    /// Valid answers, `names:A;B` takes any names.
+    pub answers: Vec<String>,
+    #[serde(flatten)]
+    pub details: Details,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum Details {
+    /// Both spellings of the release artists.
+    InconsistentArtists {
+        a: Vec<String>,
+        b: Vec<String>,
+    },
+    InvalidNumbers {
+        artists: Vec<String>,
+        release: String,
+        corrections: Vec<(PathBuf, TagUpdate)>,
+    },
+    InconsistentTotals {
+        artists: Vec<String>,
+        release: String,
+        /// `tracks` or `discs`.
+        total: &'static str,
+        disc: Option<u16>,
+        /// The distinct totals of the songs, ascending.
+        totals: Vec<u16>,
+        /// The highest total or number.
+        max: u16,
+        /// The number of songs or discs.
+        count: u16,
+    },
+    VariousArtists {
+        release: String,
+        dir: PathBuf,
+        num_artists: usize,
+    },
+    ReleaseArtistsVote {
+        release: String,
+        dir: PathBuf,
+        votes: Vec<(Vec<String>, usize)>,
+    },
+    SplitRelease {
+        release: String,
+        parts: Vec<(Vec<String>, usize)>,
+    },
+    ReleaseSuffix {
+        artists: Vec<String>,
+        release: String,
+        suffix: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct Update {
+    pub path: PathBuf,
+    pub reasons: Vec<String>,
+    pub tag_update: Option<TagUpdate>,
+    pub mode_update: Option<Mode>,
+}
+
+impl CheckReport {
+    pub fn add(&mut self, key: String, answers: Vec<String>, details: Details) {
+        self.questions.push(Finding { key, answers, details });
+    }
+
+    /// Records the tag and mode updates, moves aren't planned yet.
+    pub fn add_updates(&mut self, song_operations: &[SongOperation]) {
+        let updates =
+            song_operations.iter().filter(|o| o.tag_update.is_some() || o.mode_update.is_some());
+        self.updates.extend(updates.map(|o| Update {
+            path: o.song.path.clone(),
+            reasons: o.reasons.clone(),
+            tag_update: o.tag_update.clone(),
+            mode_update: o.mode_update,
+        }));
+    }
+
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
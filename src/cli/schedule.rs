@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Local, TimeDelta, Timelike};
+use serde::Deserialize;
+
+/// The longest period that is checked for missed runs, e.g. after the computer was suspended.
+const MAX_CATCH_UP_MINUTES: i64 = 24 * 60;
+
+/// A periodic run in watch mode, configured like `cron = "0 3 * * *"` and `dir = "Incoming"`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schedule {
+    pub cron: Cron,
+    /// Only organize the files inside of this dir, relative to the music dir. Defaults to the
+    /// whole music dir.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+}
+
+/// A cron expression of the minute, hour, day of month, month and day of week in local time,
+/// e.g. `30 3 * * 1-5`. Fields are `*`, numbers, ranges like `1-5` and lists like `1,3`, each
+/// with an optional step like `*/15`. Sunday is `0` or `7`. If both the day of month and the day
+/// of week are restricted, either has to match, like in crontab.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl TryFrom<String> for Cron {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("Expected 5 fields in cron expression {s}"));
+        };
+        let mut weekday_bits = parse_field(weekdays, 0, 7)?;
+        // sunday
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+/// The values of a field as bits.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse().map_err(|_| format!("Invalid step in {field}"))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let number = |n: &str| match n.parse() {
+            Ok(n) if (min..=max).contains(&n) => Ok(n),
+            _ => Err(format!("Invalid value {n} in {field}, expected {min}-{max}")),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            None if step > 1 => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if step == 0 || start > end {
+            return Err(format!("Invalid range {part}"));
+        }
+        for n in (start..=end).step_by(step) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+impl Cron {
+    pub fn matches(&self, time: &DateTime<Local>) -> bool {
+        let is_set = |bits: u64, n: u32| bits & (1 << n) != 0;
+        let day = is_set(self.days, time.day());
+        let weekday = is_set(self.weekdays, time.weekday().num_days_from_sunday());
+        let day = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        day && is_set(self.minutes, time.minute())
+            && is_set(self.hours, time.hour())
+            && is_set(self.months, time.month())
+    }
+
+    /// Whether one of the minutes after `since` up to and including `now` matches, so runs that
+    /// were missed while another run was in progress are caught up once.
+    pub fn is_due(&self, since: &DateTime<Local>, now: &DateTime<Local>) -> bool {
+        let since = (*since).max(*now - TimeDelta::minutes(MAX_CATCH_UP_MINUTES));
+        let Some(mut minute) = since.with_second(0).and_then(|t| t.with_nanosecond(0)) else {
+            return false;
+        };
+        loop {
+            minute += TimeDelta::minutes(1);
+            if minute > *now {
+                return false;
+            }
+            if self.matches(&minute) {
+                return true;
+            }
+        }
+    }
+}
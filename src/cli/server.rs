@@ -0,0 +1,463 @@
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread::Scope;
+use std::time::Duration;
+
+use music_organizer_core::{
+    Changes, Checks, Item, Phase, PhaseTotals, Progress, Question, RealFs, Resolution,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::args::Args;
+use crate::lock::RunLock;
+use crate::theme::Themed;
+use crate::{
+    change_options, drop_content_updates, exclude_from_retagging, format_title, labels,
+    load_external_updates, new_cleanup, new_index, titles,
+};
+
+/// How often the cancel token is checked while waiting for connections.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Request bodies only contain answers, which are tiny.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    #[default]
+    Idle,
+    Scanning,
+    /// A plan is ready to be applied.
+    Planned,
+    Applying,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Asked {
+    id: usize,
+    question: Question,
+    resolution: Option<Resolution>,
+}
+
+#[derive(Default)]
+struct State {
+    status: Status,
+    /// The questions of all scans, answers are used by the following scans.
+    questions: Vec<Asked>,
+    plan: Option<Changes>,
+    /// The songs of the last scan tagged to be left alone, their dirs aren't cleaned up.
+    skipped: Vec<PathBuf>,
+    /// The connected event streams, senders are dropped once their stream is closed.
+    subscribers: Vec<Sender<String>>,
+}
+
+impl State {
+    fn send(&mut self, event: Value) {
+        let event = event.to_string();
+        self.subscribers.retain(|s| s.send(event.clone()).is_ok());
+    }
+
+    fn num_unanswered(&self) -> usize {
+        self.questions.iter().filter(|q| q.resolution.is_none()).count()
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    origin: Option<String>,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Serves a small HTTP API on the address, so the music dir can be organized from a browser or
+/// scripts, e.g. on a headless NAS. There is no authentication, so the address should only be
+/// reachable by trusted clients. Requests from other sites are rejected: the `Host` has to be
+/// the address, an `Origin` has to match it and `POST` requests need a JSON content type, so
+/// browsers can't send them from a form without a preflight. Returns after being cancelled.
+///
+/// - `GET /status` the status, the number of unanswered questions and planned song operations
+/// - `POST /scan` indexes and checks the music dir in the background and plans the changes
+/// - `GET /plan` the planned changes
+/// - `GET /questions` the questions asked by the checks and their answers
+/// - `POST /questions/<id>` answers a question with a resolution, e.g.
+///   `{"kind":"proposal","value":0}`, answers are applied by the next scan
+/// - `POST /apply` executes the planned changes in the background and cleans up
+/// - `GET /events` a stream of server-sent events reporting progress
+pub fn serve(args: &Args, addr: SocketAddr) {
+    let listener = TcpListener::bind(addr).and_then(|l| l.set_nonblocking(true).map(|_| l));
+    let listener = match listener {
+        Ok(l) => l,
+        Err(e) => crate::exit_fatal(&format!("binding {addr}"), e),
+    };
+    println!(
+        "{}{} {}",
+        format_title(titles().serving),
+        "listening on".success(),
+        format!("http://{addr}").highlight()
+    );
+
+    let state = Mutex::new(State::default());
+    std::thread::scope(|scope| {
+        let state = &state;
+        while !args.exec.cancel.is_cancelled() {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    scope.spawn(move || {
+                        if let Err(e) = handle(scope, args, addr, state, stream) {
+                            println!(
+                                "{} serving: {}",
+                                labels().warning.highlight(),
+                                e.to_string().highlight()
+                            )
+                        }
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+                Err(e) => {
+                    println!(
+                        "{} accepting: {}",
+                        labels().warning.highlight(),
+                        e.to_string().highlight()
+                    )
+                }
+            }
+        }
+        // closes the event streams
+        state.lock().unwrap().subscribers.clear();
+    });
+}
+
+fn handle<'scope>(
+    scope: &'scope Scope<'scope, '_>,
+    args: &'scope Args,
+    addr: SocketAddr,
+    state: &'scope Mutex<State>,
+    stream: TcpStream,
+) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    let request = match read_request(&stream) {
+        Ok(r) => r,
+        Err(e) => return respond(&stream, 400, &json!({ "error": e.to_string() })),
+    };
+    if !is_same_origin(&request, addr) {
+        return respond(&stream, 403, &json!({ "error": "foreign host or origin" }));
+    }
+    let is_json = (request.content_type.as_deref())
+        .and_then(|c| c.split(';').next())
+        .is_some_and(|c| c.trim().eq_ignore_ascii_case("application/json"));
+    if request.method == "POST" && !is_json {
+        return respond(&stream, 415, &json!({ "error": "expected application/json" }));
+    }
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["status"]) => {
+            let state = state.lock().unwrap();
+            let body = json!({
+                "status": state.status,
+                "questions": state.num_unanswered(),
+                "song_operations": state.plan.as_ref().map_or(0, |p| p.song_operations.len()),
+            });
+            respond(&stream, 200, &body)
+        }
+        ("POST", ["scan"]) => {
+            if let Err(status) = start(state, Status::Scanning) {
+                return respond(&stream, 409, &json!({ "error": "busy", "status": status }));
+            }
+            scope.spawn(move || scan(args, state));
+            respond(&stream, 202, &json!({ "status": Status::Scanning }))
+        }
+        ("GET", ["plan"]) => match &state.lock().unwrap().plan {
+            Some(plan) => respond(&stream, 200, &json!(plan)),
+            None => respond(&stream, 404, &json!({ "error": "nothing planned, scan first" })),
+        },
+        ("GET", ["questions"]) => {
+            let questions = state.lock().unwrap().questions.clone();
+            respond(&stream, 200, &json!(questions))
+        }
+        ("POST", ["questions", id]) => {
+            let resolution = match serde_json::from_slice::<Resolution>(&request.body) {
+                Ok(r) => r,
+                Err(e) => return respond(&stream, 400, &json!({ "error": e.to_string() })),
+            };
+            let mut state = state.lock().unwrap();
+            let asked = id.parse().ok().and_then(|id: usize| state.questions.get_mut(id));
+            match asked {
+                Some(asked) => {
+                    asked.resolution = Some(resolution);
+                    respond(&stream, 200, &json!(asked))
+                }
+                None => respond(&stream, 404, &json!({ "error": "unknown question" })),
+            }
+        }
+        ("POST", ["apply"]) => {
+            if args.dry_run {
+                return respond(&stream, 409, &json!({ "error": "dry run" }));
+            }
+            let mut locked = state.lock().unwrap();
+            if locked.status != Status::Planned {
+                let body =
+                    json!({ "error": "nothing planned, scan first", "status": locked.status });
+                return respond(&stream, 409, &body);
+            }
+            let lock = match RunLock::acquire(&args.music_dir) {
+                Ok(lock) => lock,
+                Err(e) => return respond(&stream, 409, &json!({ "error": e.to_string() })),
+            };
+            let Some(plan) = locked.plan.take() else { unreachable!("planned without a plan") };
+            let skipped = std::mem::take(&mut locked.skipped);
+            locked.status = Status::Applying;
+            drop(locked);
+            scope.spawn(move || apply(args, state, plan, &skipped, lock));
+            respond(&stream, 202, &json!({ "status": Status::Applying }))
+        }
+        ("GET", ["events"]) => {
+            let (sender, receiver) = mpsc::channel();
+            state.lock().unwrap().subscribers.push(sender);
+            let mut stream = &stream;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\n\
+                Content-Type: text/event-stream\r\n\
+                Cache-Control: no-cache\r\n\
+                Connection: keep-alive\r\n\r\n"
+            )?;
+            for event in receiver {
+                write!(stream, "data: {event}\n\n")?;
+                stream.flush()?;
+            }
+            Ok(())
+        }
+        (_, ["status" | "scan" | "plan" | "questions" | "apply" | "events"])
+        | (_, ["questions", _]) => respond(&stream, 405, &json!({ "error": "method not allowed" })),
+        _ => respond(&stream, 404, &json!({ "error": "not found" })),
+    }
+}
+
+/// Whether the request is addressed to the server itself and, if sent by a browser, comes from
+/// a page served by it. Checking the host prevents DNS rebinding, names other than `localhost`
+/// are only accepted if the server is bound to that exact address.
+fn is_same_origin(request: &Request, addr: SocketAddr) -> bool {
+    let Some(host) = request.host.as_deref() else { return false };
+    let Some((name, port)) = host.rsplit_once(':') else { return false };
+    if port.parse() != Ok(addr.port()) {
+        return false;
+    }
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    let valid_host = match name.parse::<std::net::IpAddr>() {
+        Ok(ip) => ip == addr.ip() || addr.ip().is_unspecified(),
+        Err(_) => name.eq_ignore_ascii_case("localhost") && addr.ip().is_loopback(),
+    };
+    let valid_origin = (request.origin.as_deref())
+        .is_none_or(|o| o.strip_prefix("http://").is_some_and(|o| o.eq_ignore_ascii_case(host)));
+    valid_host && valid_origin
+}
+
+/// Changes the status unless a scan or apply is already running, which is returned instead.
+fn start(state: &Mutex<State>, status: Status) -> Result<(), Status> {
+    let mut state = state.lock().unwrap();
+    if matches!(state.status, Status::Scanning | Status::Applying) {
+        return Err(state.status);
+    }
+    state.status = status;
+    Ok(())
+}
+
+/// Runs the checks that ask questions, questions without an answer are skipped for now. The
+/// flags of a regular run that don't need a terminal are respected.
+fn scan(args: &Args, state: &Mutex<State>) {
+    let external_updates = load_external_updates(args);
+    let answered: Vec<(Question, Resolution)> = {
+        let mut state = state.lock().unwrap();
+        state.send(json!({ "event": "scan_started" }));
+        let answered = state
+            .questions
+            .iter()
+            .filter_map(|q| Some((q.question.clone(), q.resolution.clone()?)));
+        answered.collect()
+    };
+
+    let mut index = new_index(args);
+    index.read(&mut |_| ());
+    index.retain_matching(&args.filters);
+    let mut checks = Checks::from(&index);
+    exclude_from_retagging(&mut checks, &index, args);
+    checks.cancel = args.exec.cancel.clone();
+    checks.similarity_scope = args.similarity_scope.clone();
+    let mut asked = Vec::new();
+    if !args.no_check {
+        checks.resolve_questions(&mut |question: &Question| match answered
+            .iter()
+            .find(|(q, _)| q == question)
+        {
+            Some((_, resolution)) => resolution.clone(),
+            None => {
+                asked.push(question.clone());
+                Resolution::Skip
+            }
+        });
+    }
+    if let Some(updates) = &external_updates {
+        checks.apply_external_updates(updates);
+    }
+    let mut changes = Changes::generate(&RealFs, checks, &change_options(args));
+    drop_content_updates(&mut changes, args);
+
+    let mut state = state.lock().unwrap();
+    for question in asked {
+        if !state.questions.iter().any(|q| q.question == question) {
+            let id = state.questions.len();
+            state.questions.push(Asked { id, question, resolution: None });
+        }
+    }
+    let event = json!({
+        "event": "scan_finished",
+        "songs": index.songs.len(),
+        "questions": state.num_unanswered(),
+        "song_operations": changes.song_operations.len(),
+    });
+    state.send(event);
+    state.status = match changes.is_empty() {
+        true => Status::Idle,
+        false => Status::Planned,
+    };
+    state.plan = Some(changes);
+    state.skipped = index.skipped;
+}
+
+/// Executes the plan and cleans up the music dir afterwards, like a regular run. The lock is
+/// released once done.
+fn apply(args: &Args, state: &Mutex<State>, plan: Changes, skipped: &[PathBuf], _lock: RunLock) {
+    state.lock().unwrap().send(json!({ "event": "apply_started" }));
+    let mut progress = EventProgress { state };
+    plan.execute(&RealFs, &args.exec, &mut progress);
+    if !args.no_cleanup && !args.exec.cancel.is_cancelled() {
+        let mut cleanup = new_cleanup(args, skipped);
+        cleanup.check(&RealFs, &mut |_| ());
+        cleanup.execute(&RealFs, &args.exec, &mut progress);
+    }
+
+    let mut state = state.lock().unwrap();
+    state.send(json!({ "event": "apply_finished" }));
+    state.status = Status::Idle;
+}
+
+/// Sends the progress of applying the plan to the event streams.
+struct EventProgress<'a> {
+    state: &'a Mutex<State>,
+}
+
+impl EventProgress<'_> {
+    fn send(&mut self, event: Value) {
+        self.state.lock().unwrap().send(event);
+    }
+}
+
+impl Progress for EventProgress<'_> {
+    fn phase_started(&mut self, phase: Phase, total: usize) {
+        self.send(
+            json!({ "event": "phase_started", "phase": format!("{phase:?}"), "total": total }),
+        );
+    }
+
+    fn bytes_copied(&mut self, path: &Path, copied: u64, total: u64) {
+        self.send(
+            json!({ "event": "bytes_copied", "path": path, "copied": copied, "total": total }),
+        );
+    }
+
+    fn item_processed(&mut self, item: Item) {
+        self.send(json!({ "event": "item_processed", "path": item.path() }));
+    }
+
+    fn item_failed(&mut self, item: Item, error: &dyn std::error::Error) {
+        let event =
+            json!({ "event": "item_failed", "path": item.path(), "error": error.to_string() });
+        self.send(event);
+    }
+
+    fn phase_finished(&mut self, phase: Phase, totals: PhaseTotals) {
+        self.send(json!({
+            "event": "phase_finished",
+            "phase": format!("{phase:?}"),
+            "processed": totals.processed,
+            "failed": totals.failed,
+            "skipped": totals.skipped(),
+        }));
+    }
+}
+
+/// Reads the request line, the headers and the body of a request, only the content length,
+/// host, origin and content type headers are used.
+fn read_request(stream: &TcpStream) -> std::io::Result<Request> {
+    let invalid = |msg: &str| std::io::Error::new(ErrorKind::InvalidData, msg.to_owned());
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(invalid("malformed request line"));
+    };
+    let method = method.to_owned();
+    let path = target.split('?').next().unwrap_or_default().to_owned();
+
+    let mut content_length = 0;
+    let (mut host, mut origin, mut content_type) = (None, None, None);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse().map_err(|_| invalid("invalid content length"))?;
+            } else if name.eq_ignore_ascii_case("host") {
+                host = Some(value.to_owned());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.to_owned());
+            } else if name.eq_ignore_ascii_case("content-type") {
+                content_type = Some(value.to_owned());
+            }
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Err(invalid("request body too large"));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Request { method, path, host, origin, content_type, body })
+}
+
+fn respond(mut stream: &TcpStream, status: u16, body: &Value) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        415 => "Unsupported Media Type",
+        _ => "",
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\n\
+        Content-Type: application/json\r\n\
+        Content-Length: {}\r\n\
+        Connection: close\r\n\r\n\
+        {body}",
+        body.len()
+    )
+}
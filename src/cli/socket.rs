@@ -0,0 +1,170 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use music_organizer_core::{CancelToken, IndexCache, MusicIndex, Song};
+use serde_json::{json, Value};
+
+use crate::args::Args;
+use crate::theme::Themed;
+use crate::{labels, new_index};
+
+/// How often the cancel token is checked while waiting for connections.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// The maximum number of songs returned by a search.
+const MAX_SEARCH_RESULTS: usize = 100;
+
+/// Serves a read-only view of the index on a unix socket while watching, so other tools can
+/// query the library without reading it again. Each request is a line, each response a line of
+/// json, errors are returned as `{"error":"..."}`. Artists are the release artists joined by
+/// `, ` and compared case-insensitively.
+///
+/// - `artists` the names of all artists
+/// - `releases <artist>` the name, year and number of songs of each release of the artist
+/// - `songs <release>` the songs of all releases with the name
+/// - `search <text>` songs whose title, artists or release contain the text
+pub struct IndexServer {
+    path: PathBuf,
+    index: Arc<RwLock<MusicIndex>>,
+    thread: JoinHandle<()>,
+}
+
+impl IndexServer {
+    /// Reads the index and starts listening, a stale socket file of an earlier run is replaced.
+    pub fn start(path: &Path, args: &Args) -> std::io::Result<Self> {
+        let stale = std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket());
+        if stale && UnixStream::connect(path).is_err() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+
+        let mut index = new_index(args);
+        index.cache = Some(match &args.index_cache {
+            Some(p) => IndexCache::load(p).unwrap_or_default(),
+            None => IndexCache::default(),
+        });
+        index.read(&mut |_| ());
+        let index = Arc::new(RwLock::new(index));
+
+        let thread = {
+            let index = Arc::clone(&index);
+            let cancel = args.exec.cancel.clone();
+            std::thread::spawn(move || listen(listener, index, cancel))
+        };
+        Ok(Self { path: path.to_owned(), index, thread })
+    }
+
+    /// Reads the index again after a run changed the music dir. Unchanged files are taken from
+    /// the cache of the previous index, queries are answered from the old index in the meantime.
+    pub fn refresh(&self, args: &Args) {
+        let cache = self.index.read().unwrap().cache.clone();
+        let mut index = new_index(args);
+        index.cache = Some(cache.unwrap_or_default());
+        index.read(&mut |_| ());
+        *self.index.write().unwrap() = index;
+    }
+
+    /// Waits for the server to stop after being cancelled and removes the socket file.
+    pub fn stop(self) {
+        let _ = self.thread.join();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn listen(listener: UnixListener, index: Arc<RwLock<MusicIndex>>, cancel: CancelToken) {
+    while !cancel.is_cancelled() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let index = Arc::clone(&index);
+                std::thread::spawn(move || {
+                    if let Err(e) = handle(&index, stream) {
+                        println!(
+                            "{} serving index: {}",
+                            labels().warning.highlight(),
+                            e.to_string().highlight()
+                        )
+                    }
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                println!(
+                    "{} accepting: {}",
+                    labels().warning.highlight(),
+                    e.to_string().highlight()
+                )
+            }
+        }
+    }
+}
+
+fn handle(index: &RwLock<MusicIndex>, stream: UnixStream) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    let mut writer = &stream;
+    for line in BufReader::new(&stream).lines() {
+        let line = line?;
+        let (query, arg) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let response = query_index(&index.read().unwrap(), query, arg.trim());
+        writeln!(writer, "{response}")?;
+    }
+    Ok(())
+}
+
+fn query_index(index: &MusicIndex, query: &str, arg: &str) -> Value {
+    let is_artist = |s: &Song| s.release_artists.join(", ").eq_ignore_ascii_case(arg);
+    match query {
+        "artists" => {
+            let artists: BTreeSet<String> =
+                index.songs.iter().map(|s| s.release_artists.join(", ")).collect();
+            json!(artists)
+        }
+        "releases" => {
+            let mut releases: BTreeMap<&str, (Option<i32>, usize)> = BTreeMap::new();
+            for s in index.songs.iter().filter(|s| is_artist(s)) {
+                let (year, songs) = releases.entry(s.release.as_ref()).or_default();
+                *year = year.or(s.year);
+                *songs += 1;
+            }
+            let releases = releases
+                .into_iter()
+                .map(|(name, (year, songs))| json!({ "name": name, "year": year, "songs": songs }));
+            Value::Array(releases.collect())
+        }
+        "songs" => {
+            let mut songs: Vec<&Song> =
+                index.songs.iter().filter(|s| s.release.eq_ignore_ascii_case(arg)).collect();
+            songs.sort_by_key(|s| (s.release_artists.clone(), s.disc_number, s.track_number));
+            Value::Array(songs.into_iter().map(song_json).collect())
+        }
+        "search" if !arg.is_empty() => {
+            let text = arg.to_lowercase();
+            let matches = |s: &&Song| {
+                [s.title.as_str(), &s.artists.join(", "), &s.release]
+                    .iter()
+                    .any(|f| f.to_lowercase().contains(&text))
+            };
+            let songs = index.songs.iter().filter(matches).take(MAX_SEARCH_RESULTS);
+            Value::Array(songs.map(song_json).collect())
+        }
+        _ => json!({ "error": format!("unknown query '{query}'") }),
+    }
+}
+
+fn song_json(song: &Song) -> Value {
+    json!({
+        "path": song.path,
+        "release_artists": &*song.release_artists,
+        "artists": &*song.artists,
+        "release": &*song.release,
+        "disc": song.disc_number,
+        "track": song.track_number,
+        "title": song.title,
+        "year": song.year,
+    })
+}
@@ -0,0 +1,122 @@
+use colored::Colorize;
+use music_organizer_core::Item;
+
+use crate::theme::Themed;
+
+/// Counts of everything that was done during a run, printed at the end.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub indexed: usize,
+    pub unknown: usize,
+    pub checks_applied: usize,
+    /// Songs and other files that were moved, renamed or retagged.
+    pub organized: usize,
+    pub moved: usize,
+    pub renamed: usize,
+    pub retagged: usize,
+    pub mode_changed: usize,
+    pub playlists_updated: usize,
+    pub playlists_created: usize,
+    pub dirs_created: usize,
+    pub dirs_deleted: usize,
+    pub files_deleted: usize,
+    pub quarantined: usize,
+}
+
+impl Summary {
+    /// Counts a successfully processed item.
+    pub fn record(&mut self, item: Item) {
+        match item {
+            Item::DirCreation(_) => self.dirs_created += 1,
+            Item::SongOperation(o) => {
+                self.organized += 1;
+                if let Some(new_path) = &o.new_path {
+                    self.record_path_change(&o.song.path, new_path);
+                }
+                if o.tag_update.is_some() {
+                    self.retagged += 1;
+                }
+                if o.mode_update.is_some() {
+                    self.mode_changed += 1;
+                }
+            }
+            Item::FileOperation(f) => {
+                self.organized += 1;
+                self.record_path_change(&f.old_path, &f.new_path);
+            }
+            Item::PlaylistUpdate(_) => self.playlists_updated += 1,
+            Item::PlaylistCreation(_) => self.playlists_created += 1,
+            Item::Quarantine(_) => self.quarantined += 1,
+            Item::FileDeletion(_) => self.files_deleted += 1,
+            Item::DirDeletion(_) => self.dirs_deleted += 1,
+        }
+    }
+
+    fn record_path_change(&mut self, old: &std::path::Path, new: &std::path::Path) {
+        match old.parent() == new.parent() {
+            true => self.renamed += 1,
+            false => self.moved += 1,
+        }
+    }
+
+    /// Prints one row per count, `moved` is the past tense of the file operation type.
+    pub fn print(&self, moved: &str, num_failures: usize) {
+        let rows = [
+            ("indexed", count(self.indexed, "file") + self.unknown_str().as_str()),
+            ("checked", count(self.checks_applied, "song") + " updated"),
+            (moved, count(self.moved, "file")),
+            ("renamed", count(self.renamed, "file")),
+            ("retagged", count(self.retagged, "song")),
+            ("chmoded", count(self.mode_changed, "file")),
+            ("updated", count(self.playlists_updated, "playlist")),
+            ("set aside", count(self.quarantined, "file")),
+            (
+                "created",
+                format!(
+                    "{}, {}",
+                    count(self.dirs_created, "dir"),
+                    count(self.playlists_created, "playlist")
+                ),
+            ),
+            (
+                "deleted",
+                format!(
+                    "{}, {}",
+                    count(self.dirs_deleted, "dir"),
+                    count(self.files_deleted, "file")
+                ),
+            ),
+        ];
+        for (label, value) in rows {
+            println!("{label:<9} {value}");
+        }
+
+        let label = format!("{:<9}", "failed");
+        let label = if num_failures == 0 { label.normal() } else { label.error() };
+        println!("{label} {}", count(num_failures, "operation"));
+    }
+
+    /// All counts in a single line, for quiet mode.
+    pub fn line(&self, moved: &str, num_failures: usize) -> String {
+        format!(
+            "indexed {}, {moved} {}, renamed {}, retagged {}, deleted {}, failed {}",
+            count(self.indexed, "file"),
+            count(self.moved, "file"),
+            count(self.renamed, "file"),
+            count(self.retagged, "song"),
+            count(self.files_deleted, "file"),
+            count(num_failures, "operation"),
+        )
+    }
+
+    fn unknown_str(&self) -> String {
+        match self.unknown {
+            0 => String::new(),
+            n => format!(" ({} unknown)", n.to_string().highlight()),
+        }
+    }
+}
+
+fn count(n: usize, noun: &str) -> String {
+    format!("{} {noun}{}", n.to_string().number(), if n == 1 { "" } else { "s" })
+}
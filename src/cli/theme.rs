@@ -0,0 +1,184 @@
+use std::sync::OnceLock;
+
+use colored::{Color, ColoredString, Colorize};
+use serde::{Deserialize, Deserializer};
+
+/// The colors of the output, configured in the `[theme]` table of the config. Roles that aren't
+/// set use the color of the preset.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub preset: Preset,
+    pub title: Option<ThemeColor>,
+    pub subtitle: Option<ThemeColor>,
+    /// The background of titles and highlighted names in questions.
+    pub background: Option<ThemeColor>,
+    pub highlight: Option<ThemeColor>,
+    pub number: Option<ThemeColor>,
+    pub success: Option<ThemeColor>,
+    pub error: Option<ThemeColor>,
+    pub secondary: Option<ThemeColor>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    #[default]
+    Default,
+    /// Avoids yellow, cyan and bright colors, which are unreadable on light terminal themes.
+    HighContrast,
+}
+
+/// A color name like `yellow` or `bright blue`, or a hex code like `#ff8800`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ThemeColor(pub Color);
+
+impl<'de> Deserialize<'de> for ThemeColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let str = String::deserialize(deserializer)?;
+        parse_color(&str).map(ThemeColor).ok_or_else(|| {
+            serde::de::Error::custom(format!("invalid color '{str}', expected a name or #rrggbb"))
+        })
+    }
+}
+
+fn parse_color(str: &str) -> Option<Color> {
+    if let Some(hex) = str.strip_prefix('#') {
+        let n = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)?;
+        let [_, r, g, b] = n.to_be_bytes();
+        return Some(Color::TrueColor { r, g, b });
+    }
+    str.parse().ok()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub title: Color,
+    pub subtitle: Color,
+    pub background: Color,
+    pub highlight: Color,
+    pub number: Color,
+    pub success: Color,
+    pub error: Color,
+    pub secondary: Color,
+}
+
+impl Preset {
+    pub fn theme(self) -> Theme {
+        match self {
+            Self::Default => Theme {
+                title: Color::Magenta,
+                subtitle: Color::Cyan,
+                background: Color::Black,
+                highlight: Color::Yellow,
+                number: Color::Blue,
+                success: Color::Green,
+                error: Color::Red,
+                secondary: Color::BrightBlack,
+            },
+            Self::HighContrast => Theme {
+                title: Color::White,
+                subtitle: Color::White,
+                background: Color::Blue,
+                highlight: Color::Magenta,
+                number: Color::Blue,
+                success: Color::Green,
+                error: Color::Red,
+                secondary: Color::Blue,
+            },
+        }
+    }
+}
+
+impl From<&ThemeConfig> for Theme {
+    fn from(config: &ThemeConfig) -> Self {
+        let preset = config.preset.theme();
+        let color = |c: Option<ThemeColor>, default| c.map_or(default, |c| c.0);
+        Self {
+            title: color(config.title, preset.title),
+            subtitle: color(config.subtitle, preset.subtitle),
+            background: color(config.background, preset.background),
+            highlight: color(config.highlight, preset.highlight),
+            number: color(config.number, preset.number),
+            success: color(config.success, preset.success),
+            error: color(config.error, preset.error),
+            secondary: color(config.secondary, preset.secondary),
+        }
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Sets the theme, only the first call has an effect.
+pub fn init(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+pub fn get() -> &'static Theme {
+    THEME.get_or_init(|| Preset::Default.theme())
+}
+
+/// The 256 color palette index of a color for progress bar templates, true colors aren't
+/// supported there and use the closest basic color.
+pub fn palette_index(color: Color) -> u8 {
+    match color {
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::White => 7,
+        Color::BrightBlack => 8,
+        Color::BrightRed => 9,
+        Color::BrightGreen => 10,
+        Color::BrightYellow => 11,
+        Color::BrightBlue => 12,
+        Color::BrightMagenta => 13,
+        Color::BrightCyan => 14,
+        Color::BrightWhite => 15,
+        Color::TrueColor { r, g, b } => {
+            let bit = |c: u8| (c >= 128) as u8;
+            let bright = if r.max(g).max(b) >= 192 { 8 } else { 0 };
+            bit(r) | bit(g) << 1 | bit(b) << 2 | bright
+        }
+    }
+}
+
+/// Colors text by its role, using the configured [`Theme`].
+pub trait Themed: Colorize + Sized {
+    fn title(self) -> ColoredString {
+        self.color(get().title).on_color(get().background)
+    }
+
+    fn subtitle(self) -> ColoredString {
+        self.color(get().subtitle).on_color(get().background)
+    }
+
+    fn on_background(self) -> ColoredString {
+        self.on_color(get().background)
+    }
+
+    fn highlight(self) -> ColoredString {
+        self.color(get().highlight)
+    }
+
+    fn number(self) -> ColoredString {
+        self.color(get().number)
+    }
+
+    fn success(self) -> ColoredString {
+        self.color(get().success)
+    }
+
+    fn error(self) -> ColoredString {
+        self.color(get().error)
+    }
+
+    fn secondary(self) -> ColoredString {
+        self.color(get().secondary)
+    }
+}
+
+impl<T: Colorize> Themed for T {}
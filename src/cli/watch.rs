@@ -0,0 +1,171 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::args::Args;
+use crate::schedule::Schedule;
+#[cfg(unix)]
+use crate::socket::IndexServer;
+use crate::theme::Themed;
+use crate::{format_title, labels, run, titles, Dict};
+
+/// How often the cancel token is checked while waiting for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Watches the music dir and organizes new or modified files, once no changes happened for the
+/// settle duration. The scheduled runs of the config are started when they are due. Returns after
+/// being cancelled.
+pub fn watch(args: &Args, dict: &Dict) {
+    let (sender, receiver) = mpsc::channel();
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let _ = sender.send(event);
+    });
+    let mut watcher = match watcher {
+        Ok(w) => w,
+        Err(e) => crate::exit_fatal("starting watcher", e),
+    };
+    if let Err(e) = watcher.watch(&args.music_dir, RecursiveMode::Recursive) {
+        crate::exit_fatal("watching music dir", e);
+    }
+    #[cfg(unix)]
+    let index_server = args.index_socket.as_ref().map(|path| {
+        let server = IndexServer::start(path, args)
+            .unwrap_or_else(|e| crate::exit_fatal("starting index socket", e));
+        println!(
+            "{}{} {}",
+            format_title(titles().watching),
+            "serving the index on".success(),
+            path.display()
+        );
+        server
+    });
+    #[cfg(not(unix))]
+    if args.index_socket.is_some() {
+        crate::exit_fatal("starting index socket", "only supported on unix");
+    }
+    print_waiting(args);
+
+    let mut pending = BTreeSet::new();
+    let mut last_event = Instant::now();
+    let mut last_schedule_check = Local::now();
+    loop {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    let paths = event.paths.into_iter().filter(|p| !is_excluded(args, p));
+                    pending.extend(paths);
+                    last_event = Instant::now();
+                }
+            }
+            Ok(Err(e)) => {
+                println!("{} watching: {}", labels().warning.highlight(), e.to_string().highlight())
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if args.exec.cancel.is_cancelled() {
+            break;
+        }
+
+        let now = Local::now();
+        let schedules = args.config.schedules.iter();
+        let due: Vec<&Schedule> =
+            schedules.filter(|s| s.cron.is_due(&last_schedule_check, &now)).collect();
+        last_schedule_check = now;
+        for schedule in due {
+            let files = schedule.dir.as_ref().map(|dir| {
+                let mut files = BTreeSet::new();
+                collect_files(&args.music_dir.join(dir), &mut files);
+                files.into_iter().collect()
+            });
+            if files.as_ref().is_some_and(Vec::is_empty) {
+                continue;
+            }
+            let mut written = BTreeSet::new();
+            run(args, dict, files, &mut written);
+            #[cfg(unix)]
+            if let Some(server) = &index_server {
+                server.refresh(args);
+            }
+            if schedule.dir.is_none() {
+                pending.clear();
+            }
+            queue_unwritten(args, &receiver, &written, &mut pending, &mut last_event);
+            print_waiting(args);
+            last_schedule_check = Local::now();
+        }
+        if pending.is_empty() || last_event.elapsed() < args.settle {
+            continue;
+        }
+
+        let mut files = BTreeSet::new();
+        for p in std::mem::take(&mut pending) {
+            collect_files(&p, &mut files);
+        }
+        if !files.is_empty() {
+            let mut written = BTreeSet::new();
+            run(args, dict, Some(files.into_iter().collect()), &mut written);
+            #[cfg(unix)]
+            if let Some(server) = &index_server {
+                server.refresh(args);
+            }
+            queue_unwritten(args, &receiver, &written, &mut pending, &mut last_event);
+            print_waiting(args);
+        }
+    }
+    #[cfg(unix)]
+    if let Some(server) = index_server {
+        server.stop();
+    }
+}
+
+/// Ignores the changes made by the run, other changes that happened meanwhile, e.g. finished
+/// downloads, are organized by the next run.
+fn queue_unwritten(
+    args: &Args,
+    receiver: &Receiver<notify::Result<notify::Event>>,
+    written: &BTreeSet<PathBuf>,
+    pending: &mut BTreeSet<PathBuf>,
+    last_event: &mut Instant,
+) {
+    for event in receiver.try_iter().filter_map(Result::ok) {
+        if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            let paths =
+                (event.paths.into_iter()).filter(|p| !written.contains(p) && !is_excluded(args, p));
+            let len = pending.len();
+            pending.extend(paths);
+            if pending.len() > len {
+                *last_event = Instant::now();
+            }
+        }
+    }
+}
+
+fn print_waiting(args: &Args) {
+    println!(
+        "{}{} {}",
+        format_title(titles().watching),
+        "waiting for changes in".success(),
+        args.music_dir.display()
+    );
+}
+
+fn is_excluded(args: &Args, path: &Path) -> bool {
+    args.excluded_dirs.iter().any(|d| path.starts_with(d))
+}
+
+/// Files inside of new directories might have been created before the directory was watched.
+pub fn collect_files(path: &Path, files: &mut BTreeSet<PathBuf>) {
+    if path.is_file() {
+        files.insert(path.to_owned());
+    } else if let Ok(r) = std::fs::read_dir(path) {
+        for e in r.filter_map(|e| e.ok()) {
+            collect_files(&e.path(), files);
+        }
+    }
+}
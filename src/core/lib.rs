@@ -1,17 +0,0 @@
-mod changes;
-mod checks;
-mod cleanup;
-mod fs;
-mod index;
-mod meta;
-mod update;
-mod util;
-
-pub use changes::Changes;
-pub use checks::Checks;
-pub use cleanup::Cleanup;
-pub use fs::{DirCreation, FileOpType, FileOperation, SongOperation};
-pub use index::MusicIndex;
-pub use meta::{Metadata, Release, ReleaseArtists, Song};
-pub use update::{TagUpdate, Value};
-pub use util::*;